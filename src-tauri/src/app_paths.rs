@@ -0,0 +1,92 @@
+// Data directory resolution with a portable fallback.
+// Order: STEGSTR_DATA_DIR > portable mode (env or marker file next to the executable)
+// > platform data dir (dirs) > home dir > next to the executable > temp dir.
+
+use std::path::{Path, PathBuf};
+
+/// Explicit data directory override.
+pub const DATA_DIR_ENV: &str = "STEGSTR_DATA_DIR";
+/// Set to 1/true to keep all data next to the executable.
+pub const PORTABLE_ENV: &str = "STEGSTR_PORTABLE";
+/// Marker file next to the executable that enables portable mode (for USB installs).
+pub const PORTABLE_MARKER: &str = "stegstr.portable";
+const PORTABLE_DIR_NAME: &str = "StegstrData";
+const APP_DIR_NAME: &str = "Stegstr";
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name)
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+}
+
+/// True if portable mode is selected via env var or marker file.
+pub fn is_portable() -> bool {
+    if env_flag(PORTABLE_ENV) {
+        return true;
+    }
+    exe_dir().map(|d| d.join(PORTABLE_MARKER).exists()).unwrap_or(false)
+}
+
+/// Turn portable mode on or off by writing or removing the marker next to the executable (the
+/// settings' `portable`). STEGSTR_PORTABLE still forces it on.
+pub fn set_portable(on: bool) -> Result<(), String> {
+    let marker = exe_dir().ok_or("Cannot locate the executable")?.join(PORTABLE_MARKER);
+    let result = match (on, marker.exists()) {
+        (true, false) => std::fs::write(&marker, b""),
+        (false, true) => std::fs::remove_file(&marker),
+        _ => Ok(()),
+    };
+    result.map_err(|e| format!("{}: {}", marker.display(), e))
+}
+
+fn portable_dir() -> Option<PathBuf> {
+    exe_dir().map(|d| d.join(PORTABLE_DIR_NAME))
+}
+
+/// The STEGSTR_DATA_DIR override, if set.
+pub fn data_dir_override() -> Option<PathBuf> {
+    let dir = std::env::var(DATA_DIR_ENV).ok()?;
+    Some(PathBuf::from(dir.trim())).filter(|d| !d.as_os_str().is_empty())
+}
+
+/// Candidate data directories in preference order.
+fn candidates() -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    out.extend(data_dir_override());
+    if is_portable() {
+        out.extend(portable_dir());
+    }
+    if let Some(d) = dirs::data_local_dir() {
+        out.push(d.join(APP_DIR_NAME));
+    }
+    if let Some(h) = dirs::home_dir() {
+        out.push(h.join(format!(".{}", APP_DIR_NAME.to_lowercase())));
+    }
+    out.extend(portable_dir());
+    out.push(std::env::temp_dir().join(APP_DIR_NAME));
+    out
+}
+
+/// Resolve (and create) the directory used for logs and app data.
+/// Falls through candidates until one can be created, so hardened hosts without XDG dirs still work.
+pub fn data_dir() -> Result<PathBuf, String> {
+    let mut last_err = String::from("no data dir candidates");
+    for dir in candidates() {
+        match std::fs::create_dir_all(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) => last_err = format!("{}: {}", dir.display(), e),
+        }
+    }
+    Err(format!("Could not create data dir ({})", last_err))
+}
+
+/// Resolve a file or subdirectory inside the data dir.
+pub fn data_path(name: &str) -> Result<PathBuf, String> {
+    Ok(data_dir()?.join(name))
+}
@@ -0,0 +1,74 @@
+// Writing output images without leaving a corrupt or clobbered file. The bytes go to a temporary
+// file in the destination's directory (same filesystem, so the final step is a rename), are
+// synced to disk, then moved into place: a crash mid-write leaves the old file, or none, never a
+// truncated image. Without `overwrite` an existing destination is an error, checked again at the
+// final step (a hard link, which fails if the name was taken meanwhile; filesystems without hard
+// links fall back to a check and a rename).
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Err if `dest` exists and `overwrite` is false: lets a command refuse before doing any work.
+pub fn check_destination(dest: &Path, overwrite: bool) -> Result<(), String> {
+    match !overwrite && dest.exists() {
+        true => Err(format!("{} already exists (pass overwrite to replace it)", dest.display())),
+        false => Ok(()),
+    }
+}
+
+fn temp_path(dest: &Path) -> PathBuf {
+    let name = dest.file_name().map_or("output".into(), |n| n.to_string_lossy());
+    let nonce: u32 = rand::random();
+    dest.with_file_name(format!(".{}.{}-{:08x}.tmp", name, std::process::id(), nonce))
+}
+
+/// Write `bytes` to `dest` atomically (module header).
+pub fn write(dest: &Path, bytes: &[u8], overwrite: bool) -> Result<(), String> {
+    check_destination(dest, overwrite)?;
+    let tmp = temp_path(dest);
+    let err = |e: std::io::Error| format!("{}: {}", dest.display(), e);
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp).map_err(err)?;
+        file.write_all(bytes).map_err(err)?;
+        file.sync_all().map_err(err)?;
+        drop(file);
+        if overwrite {
+            return std::fs::rename(&tmp, dest).map_err(err);
+        }
+        match std::fs::hard_link(&tmp, dest) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => check_destination(dest, false),
+            Err(_) => check_destination(dest, false).and_then(|_| std::fs::rename(&tmp, dest).map_err(err)),
+        }
+    })();
+    let _ = std::fs::remove_file(&tmp);
+    #[cfg(unix)]
+    if result.is_ok() {
+        // Persist the rename itself.
+        if let Some(dir) = dest.parent().filter(|d| !d.as_os_str().is_empty()) {
+            let _ = std::fs::File::open(dir).and_then(|d| d.sync_all());
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_replaces_only_when_asked_and_leaves_no_temp_file() {
+        let dir = std::env::temp_dir().join(format!("stegstr_atomic_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("out.png");
+
+        write(&dest, b"first", false).unwrap();
+        assert!(write(&dest, b"second", false).unwrap_err().contains("already exists"));
+        assert_eq!(std::fs::read(&dest).unwrap(), b"first");
+        write(&dest, b"second", true).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"second");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,218 @@
+// Batch embed/decode over many images on a worker pool: one payload into every cover of a
+// directory, or every image of a directory decoded. Failures are recorded per image, never fatal,
+// so one unreadable cover does not cost the rest of the run.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use rayon::prelude::*;
+
+use crate::prefilter::Prefilter;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct BatchItem {
+    pub input: String,
+    /// Written image (embed only).
+    pub output: Option<String>,
+    pub method: Option<String>,
+    /// Decoded payload as text: JSON, decrypted bundle or base64:<data> (decode only).
+    pub payload: Option<String>,
+    pub error: Option<String>,
+    /// Prefilter probability of a payload (decode with a prefilter only).
+    pub prefilter: Option<f32>,
+    /// The prefilter ruled the image out, so it was not decoded.
+    pub skipped: bool,
+    pub millis: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Images the prefilter ruled out (neither succeeded nor failed).
+    pub skipped: usize,
+    pub elapsed_ms: u64,
+    /// One entry per input, in input order.
+    pub items: Vec<BatchItem>,
+}
+
+impl BatchSummary {
+    /// Job notification for the finished run (notify::notify_job_done), e.g. "Batch decode".
+    pub fn notify_done(&self, job: &str) {
+        let mut summary = format!("{} of {} succeeded", self.succeeded, self.total);
+        if self.failed > 0 {
+            summary += &format!(", {} failed", self.failed);
+        }
+        if self.skipped > 0 {
+            summary += &format!(", {} skipped", self.skipped);
+        }
+        crate::notify::notify_job_done(job, &summary);
+    }
+}
+
+/// Worker pool with `jobs` threads (0 = one per CPU).
+fn pool(jobs: usize) -> Result<rayon::ThreadPool, String> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+fn summarize(items: Vec<BatchItem>, started: Instant) -> BatchSummary {
+    let failed = items.iter().filter(|i| i.error.is_some()).count();
+    let skipped = items.iter().filter(|i| i.skipped).count();
+    BatchSummary {
+        total: items.len(),
+        succeeded: items.len() - failed - skipped,
+        failed,
+        skipped,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        items,
+    }
+}
+
+/// Output file for each cover: `<out_dir>/<stem>.<ext>`, where ext follows the encoded bytes (QIM
+/// writes JPEG). Covers sharing a stem (a.png, a.jpg) keep their old extension in the name.
+fn output_stems(covers: &[PathBuf]) -> Vec<String> {
+    let stem = |p: &Path| p.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "image".to_string());
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for c in covers {
+        *counts.entry(stem(c)).or_default() += 1;
+    }
+    covers
+        .iter()
+        .map(|c| {
+            let s = stem(c);
+            match c.extension() {
+                Some(ext) if counts[&s] > 1 => format!("{}_{}", s, ext.to_string_lossy()),
+                _ => s,
+            }
+        })
+        .collect()
+}
+
+fn image_extension(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        "jpg"
+    } else {
+        "png"
+    }
+}
+
+/// Run `embed` on every cover and write each result into `out_dir`. `embed` returns the encoded
+/// image and the method used. An existing output file fails its item unless `overwrite`.
+pub fn embed_all<F>(covers: &[PathBuf], out_dir: &Path, jobs: usize, overwrite: bool, embed: F) -> Result<BatchSummary, String>
+where
+    F: Fn(&Path) -> Result<(Vec<u8>, String), String> + Sync,
+{
+    std::fs::create_dir_all(out_dir).map_err(|e| format!("{}: {}", out_dir.display(), e))?;
+    let stems = output_stems(covers);
+    let started = Instant::now();
+    let items = pool(jobs)?.install(|| {
+        covers
+            .par_iter()
+            .zip(&stems)
+            .map(|(cover, stem)| {
+                let t = Instant::now();
+                let mut item = BatchItem {
+                    input: cover.to_string_lossy().to_string(),
+                    ..Default::default()
+                };
+                let written = embed(cover).and_then(|(bytes, method)| {
+                    let out = out_dir.join(format!("{}.{}", stem, image_extension(&bytes)));
+                    crate::atomic_write::write(&out, &bytes, overwrite)?;
+                    Ok((out, method))
+                });
+                match written {
+                    Ok((out, method)) => {
+                        item.output = Some(out.to_string_lossy().to_string());
+                        item.method = Some(method);
+                    }
+                    Err(e) => item.error = Some(e),
+                }
+                item.millis = t.elapsed().as_millis() as u64;
+                item
+            })
+            .collect()
+    });
+    Ok(summarize(items, started))
+}
+
+/// Run `decode` on every image. `decode` returns the payload as text and the method that found it.
+/// With a prefilter, images it rates below its threshold are skipped, as in `scan::scan`.
+pub fn decode_all<F>(paths: &[PathBuf], jobs: usize, prefilter: Option<&Prefilter>, decode: F) -> Result<BatchSummary, String>
+where
+    F: Fn(&Path) -> Result<(String, String), String> + Sync,
+{
+    let started = Instant::now();
+    let items = pool(jobs)?.install(|| {
+        paths
+            .par_iter()
+            .map(|path| {
+                let t = Instant::now();
+                let mut item = BatchItem {
+                    input: path.to_string_lossy().to_string(),
+                    ..Default::default()
+                };
+                let prediction = prefilter.and_then(|pf| pf.predict(path).ok());
+                item.prefilter = prediction.map(|p| p.probability);
+                if prediction.is_some_and(|p| !p.likely_stego) {
+                    item.skipped = true;
+                    item.millis = t.elapsed().as_millis() as u64;
+                    return item;
+                }
+                match decode(path) {
+                    Ok((payload, method)) => {
+                        item.payload = Some(payload);
+                        item.method = Some(method);
+                    }
+                    Err(e) => item.error = Some(e),
+                }
+                item.millis = t.elapsed().as_millis() as u64;
+                item
+            })
+            .collect()
+    });
+    Ok(summarize(items, started))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stego;
+
+    #[test]
+    fn test_embed_all_then_decode_all() {
+        let dir = std::env::temp_dir().join(format!("stegstr_batch_{}", std::process::id()));
+        let covers_dir = dir.join("covers");
+        std::fs::create_dir_all(&covers_dir).unwrap();
+        for (seed, name) in [(0x1234_5678, "a.png"), (0x2345_6789, "b.png"), (0x3456_789A, "a.bmp")] {
+            stegstr_core::test_util::noisy_cover(256, 256, seed).save(covers_dir.join(name)).unwrap();
+        }
+        std::fs::write(covers_dir.join("broken.png"), b"not an image").unwrap();
+
+        let covers = crate::scan::expand_paths(&[covers_dir.to_string_lossy().to_string()]).unwrap();
+        let out_dir = dir.join("out");
+        let embedded = embed_all(&covers, &out_dir, 2, false, |cover| {
+            Ok((stego::encode(cover, b"batch", &stego::EncodeOptions::default())?, "dwt".to_string()))
+        })
+        .unwrap();
+        assert_eq!((embedded.total, embedded.succeeded, embedded.failed), (4, 3, 1));
+        let outputs: Vec<String> = embedded.items.iter().filter_map(|i| i.output.clone()).collect();
+        assert!(outputs.iter().any(|o| o.ends_with("a_bmp.png")) && outputs.iter().any(|o| o.ends_with("a_png.png")));
+
+        let written = crate::scan::expand_paths(&[out_dir.to_string_lossy().to_string()]).unwrap();
+        let decoded = decode_all(&written, 2, None, |path| {
+            let payload = stego::decode(path)?;
+            Ok((String::from_utf8_lossy(&payload).to_string(), "dwt".to_string()))
+        })
+        .unwrap();
+        assert_eq!(decoded.succeeded, 3);
+        assert!(decoded.items.iter().all(|i| i.payload.as_deref() == Some("batch")));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -2,82 +2,636 @@
 //! Build with: cargo build --release --bin stegstr-cli
 
 use base64::Engine;
-use secp256k1::Secp256k1;
-use sha2::{Digest, Sha256};
 use std::env;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
+use std::ops::Deref;
 use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use stegstr_lib::error::StegoError;
+use stegstr_lib::payload_header::PayloadHeader;
+use zeroize::Zeroizing;
 
-const STEGSTR_SUFFIX: &str = " Sent by Stegstr.";
-const MAX_NOTE_LENGTH: usize = 5000;
+/// Subcommands whose result can be printed as one JSON object with the global --json flag.
+const JSON_SUBCOMMANDS: &[&str] = &["decode", "detect", "embed", "join", "combine", "post", "profile", "contacts", "dm"];
+
+/// Failure with a stable code for --json output: usage, io, no_payload, decrypt, embed or relay.
+/// Plain `?` on argument errors yields "usage"; library calls tag their errors with `.code()`.
+#[derive(Debug)]
+struct CliError {
+    code: &'static str,
+    message: String,
+    /// The --json result object (with ok: false) was already printed.
+    reported: bool,
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError {
+            code: "usage",
+            message,
+            reported: false,
+        }
+    }
+}
+
+impl From<&str> for CliError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl From<StegoError> for CliError {
+    fn from(e: StegoError) -> Self {
+        String::from(e).into()
+    }
+}
+
+trait ErrorCode<T> {
+    fn code(self, code: &'static str) -> Result<T, CliError>;
+}
+
+impl<T> ErrorCode<T> for Result<T, String> {
+    fn code(self, code: &'static str) -> Result<T, CliError> {
+        self.map_err(|message| CliError {
+            code,
+            message,
+            reported: false,
+        })
+    }
+}
+
+impl<T> ErrorCode<T> for Result<T, StegoError> {
+    fn code(self, code: &'static str) -> Result<T, CliError> {
+        self.map_err(String::from).code(code)
+    }
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> Result<(), CliError> {
+    let out = serde_json::to_string_pretty(value).map_err(|e| e.to_string()).code("io")?;
+    println!("{}", out);
+    Ok(())
+}
 
 fn usage() -> &'static str {
     r#"stegstr-cli — Stegstr command-line interface
 
 Usage:
-  stegstr-cli decode <image.png> [--decrypt]     Extract payload (optionally decrypt app-layer)
-  stegstr-cli detect <image.png>                 Decode + decrypt, print bundle JSON (same as decode --decrypt)
-  stegstr-cli embed <cover.png> -o <out.png> --payload <string|@file> [--encrypt] [--payload-base64]
-  stegstr-cli post "content" [--privkey-hex HEX] [--output bundle.json]  Create kind 1 note, output bundle JSON
+  stegstr-cli decode <image.png> [--method dwt|dot|qim|synth|<external> | --auto] [--decrypt] [--passphrase P | --privkey-hex HEX] [--region x,y,w,h] [--stego-key K]  Extract payload (dwt unless --method or --auto; optionally decrypt)
+  stegstr-cli decode-url <url> [--sha256 HEX] [--nip94 <event.json>] [--max-bytes N] [-o <image>] [--method NAME] [--decrypt] [--passphrase P | --privkey-hex HEX]
+  stegstr-cli detect <image.png> [--auto]        Decode + decrypt, print bundle JSON (same as decode --decrypt)
+  stegstr-cli verify <image> [--method NAME] [--passphrase P | --privkey-hex HEX] [--json]  Check every event id and signature in the embedded bundle
+  stegstr-cli embed <cover.png> -o <out.png> --payload <string|@file> [--encrypt | --passphrase P | --recipient NPUB] [--cipher NAME] [--bundle-v2] [--bind] [--payload-base64] [--mime TYPE] [--method dwt|dot|qim|synth|<external>] [--target NAME] [--profile NAME] [--parity N] [--tile-size N] [--redundancy N] [--interleave N] [--spread N] [--strength N] [--channels N] [--stego-key K] [--fiducials | --calibration] [--dot-render stamp|luma|blue] [--dot-contrast N] [--dot-step N] [--dot-repeat N] [--format png|webp|jpeg] [--jpeg-quality N] [--strip] [--mask x,y,w,h]... [--mask-image PNG] [--no-verify] [--max-attempts N] [--quality] [--receipt --privkey-hex HEX] [--decoy-payload <string|@file> --decoy-passphrase D]
+  stegstr-cli embed --split <cover>... -o <dir> --payload <string|@file> [--fountain N | --shamir K] [embed options]  Spread a payload over several covers (with --fountain, any N of them rebuild it; with --shamir, any K do and fewer reveal nothing)
+  stegstr-cli join <image>... [--decrypt] [--passphrase P | --privkey-hex HEX] [--stego-key K]  Reassemble a payload split with embed --split (images that do not decode are skipped; combine is an alias)
+  stegstr-cli post "content" [--reply-to ID [--root ID]] [--mention NPUB]... [--tag NAME=VALUE]... [--kind N] [--privkey-hex HEX] [--output bundle.json] [--relay URL]...  Create kind 1 note, output bundle JSON
+  stegstr-cli profile [--name N] [--about A] [--picture URL] [--privkey-hex HEX] [--output bundle.json] [--relay URL]...  Create kind 0 profile
+  stegstr-cli contacts add|remove <npub|hex>... [--from bundle.json] [--privkey-hex HEX] [--output bundle.json] [--relay URL]...  Create kind 3 contact list
+  stegstr-cli contacts list --from <bundle.json> [--json]  Print the newest contact list in a bundle
+  stegstr-cli dm <npub|hex> "message" [--kind 4|14] [--privkey-hex HEX] [--output bundle.json] [--relay URL]...  NIP-44 encrypted direct message
+  stegstr-cli dm <npub|hex> "message" --cover <cover.png> -o <out.png> [--kind 4|14] [--privkey-hex HEX]  ... embedded in an image
+  stegstr-cli dm open <bundle.json> --privkey-hex HEX [--json]  Decrypt the direct messages in a bundle
+  stegstr-cli batch embed --covers <dir> --payload <string|@file> --out <dir> [--encrypt | --passphrase P | --recipient NPUB] [--cipher NAME] [--bundle-v2] [--method NAME | --target NAME | --profile NAME] [--no-verify] [--jobs N] [--json]
+  stegstr-cli batch decode <dir|image>... [--decrypt] [--passphrase P | --privkey-hex HEX] [--method NAME] [--prefilter MODEL [--threshold P]] [--jobs N] [--json]
+  stegstr-cli watch <dir> [--interval SECS] [--existing] [--decrypt] [--passphrase P | --privkey-hex HEX] [--json] [--verbose]  Decode new images as they arrive
+  stegstr-cli scan <image|dir>... [--methods dwt,dot] [--prefilter MODEL [--threshold P]] [--json]  Batch decode with per-method timings
+  stegstr-cli scan <dir|image>... --detect [--recursive]  Fast parallel payload audit, JSON report
+  stegstr-cli capacity <image> [--method dwt|dot|qim|<external>] [--target NAME] [--json]  Largest payload the image can carry
+  stegstr-cli compare <cover> <stego> [--json] [--heatmap out.png]  PSNR and SSIM of a stego image against its cover
+  stegstr-cli analyze <image> [--json]  Detectability score from chi-square and RS steganalysis
+  stegstr-cli info <image> [--json]  Dimensions, metadata, capacity and payload presence (not the payload)
+  stegstr-cli rank-covers <dir|image>... [--json]  Sort candidate covers by suitability
+  stegstr-cli simulate <stego> [--transform SPEC]... [--method NAME] [--stego-key K] [--keep DIR] [--json]  Check which platform transforms the payload survives
+  stegstr-cli publish <bundle.json|event.json> [--relay URL]... [--timeout SECS] [--json]
+  stegstr-cli fetch [--relay URL]... [--id NOTE|NEVENT]... [--author NPUB]... [--kind N]... [--since TS] [--until TS] [--limit N] [--timeout SECS] [--output bundle.json]
+  stegstr-cli outbox add <bundle.json|event.json>... | list | flush [--relay URL]... [--timeout SECS] | remove <id>... | remove --sent  [--json]  Queue events offline, publish later
+  stegstr-cli merge <bundle.json>... [-o bundle.json]  Combine bundles: each valid event once, oldest first (invalid signatures dropped)
+  stegstr-cli codecs                            List built-in and external codecs (codecs.json)
+  stegstr-cli key import <name> [--generate] | list [--json] | export <name> | remove <name> | stego <name>  Keys in the OS keychain
+  stegstr-cli fixtures list|fetch|pin [--manifest <path>] [--dir <cache dir>]
+  stegstr-cli send "content" --to <npub|hex> --cover <cover.png> -o <out.png> [--privkey-hex HEX] [--target NAME] [--upload] [--receipt | --receipt-in-image]
+
+Keys:
+  Secret keys (--privkey-hex, or its alias --privkey) take 64-char hex or an nsec; public keys take
+  an npub or hex; event ids take a note, an nevent or hex (NIP-19).
+  key import <name> reads a secret key from stdin (or makes one with --generate) and stores it in
+  the OS keychain (macOS Keychain, Windows Credential Manager, Secret Service); post, profile,
+  contacts and dm then sign with --key <name> instead of --privkey-hex, keeping the key out of
+  shell history. key list prints names and npubs; key export prints the nsec. key stego <name>
+  reads a stego key password from stdin into the keychain, for stego_key_ref in config.toml.
+
+JSON output:
+  --json (before or after the subcommand) makes decode, detect, embed, join (combine), post,
+  profile, contacts and dm print one JSON object on stdout instead of text: the StegoDecodeResult /
+  StegoEncodeResult shapes of the app (ok, method, payload, path, capacity, ...) for
+  decode/detect/join/embed (embed --split prints the batch summary), and { ok, event_id, pubkey,
+  npub, path, bundle, relays } for post, profile, contacts add/remove and dm. Failures print { "ok": false, "error", "code" } with code
+  usage, io, no_payload, decrypt, embed or relay, and still exit non-zero. Other subcommands take
+  their own --json as documented below.
+
+Output files:
+  Images, bundles and heatmaps are written to a temporary file and renamed into place. An existing
+  file is an error unless --overwrite is given (anywhere on the command line).
+
+Pipes:
+  "-" as the image of decode, embed, verify or capacity reads it from stdin, and "-o -" writes the
+  embedded image to stdout (binary; status stays on stderr), e.g.
+    curl -s URL | stegstr-cli embed - -o - --payload @bundle.json > out.png
+
+Covers:
+  PNG, JPEG, GIF (animated GIFs and APNGs frame by frame), WebP and BMP; HEIC and AVIF photos in
+  builds with the heif feature (libheif). Output is PNG or WebP whatever the cover.
 
 Decode:
   Writes payload to stdout. With --decrypt: decrypts Stegstr app-layer and prints bundle JSON.
   Without --decrypt: raw payload (JSON text or base64:<data>). Exit 0 on success.
+  --passphrase <p>       Decrypt a passphrase-protected payload (implies --decrypt). If no payload is
+                         found, dwt is retried with positions keyed by the passphrase (embed
+                         --decoy-payload), opening the payload of that passphrase
+  --privkey-hex <hex>    Decrypt with your Nostr secret key: payloads embedded with --recipient,
+                         or sent to you with send (implies --decrypt)
+  --method <name>        Codec to read: dwt (default), dot, qim, synth or an external codec
+  --auto                 Try every codec cheapest first and report the one that found the
+                         payload on stderr ("Detected <method>")
+  --region x,y,w,h       Only search this pixel rectangle (fast decode of a known crop area; dwt)
+  A dot image embedded with --fiducials or --calibration decodes from a photo of it: when it does
+  not decode as stored, its corner markers are located and the photo is straightened back to the
+  original size.
+  --stego-key <k>        Key the image was embedded with (embed --stego-key); tries dwt then dot,
+                         or only --method
+
+Decode-url:
+  Downloads the image, then decodes it like decode. Interrupted downloads resume with HTTP Range
+  (the partial file is kept in <data dir>/downloads, so re-running also resumes).
+  A hash in the link (#sha256=<hex>, #x=<hex> or a bare 64-hex fragment) is verified.
+  --sha256 <hex>         Expected SHA-256 of the image
+  --nip94 <event.json>   NIP-94 file metadata (kind 1063): url, x (sha256) and size are checked
+  --max-bytes <n>        Refuse larger downloads (default 32 MiB)
+  -o, --output <path>    Keep the downloaded image here (default: the downloads cache)
+  --method <name>        Only try this codec (as decode)
 
 Detect:
   Decodes image and decrypts; prints Nostr bundle JSON { "version": 1, "events": [...] }.
+  Reads dwt only; --auto tries every codec (dot, qim, synth, external) as decode --auto does.
 
 Embed:
   --payload <string>     Payload as UTF-8 string (bundle JSON for full feed)
   --payload @<path>      Payload from file (e.g. --payload @bundle.json)
   --payload-base64 <b64> Payload as base64 string
+  --mime <type>          MIME type for the payload header (default: sniffed: application/json,
+                         text/plain, application/octet-stream, or the encrypted type). The
+                         header also carries the --payload @<path> file name, unless encrypted
+                         (dwt and dot zstd-compress the payload when that makes it smaller;
+                         the capacity line then shows the compressed size and the gain)
   --encrypt              Encrypt with app key before embedding (any Stegstr user can detect)
+  --passphrase <p>       Encrypt with a key derived from the passphrase (Argon2id); only holders
+                         of the passphrase can decrypt (decode --passphrase)
+  --recipient <npub|hex> Encrypt for one Nostr user (ECDH with their key + AES-GCM); only their
+                         secret key decrypts it (decode --privkey-hex)
+  --cipher <name>        AEAD for --encrypt, --passphrase and --recipient: aes-256-gcm (default) or
+                         chacha20-poly1305 (no AES hardware needed). Decoding detects the cipher;
+                         builds before chacha20-poly1305 support cannot read it
+  --bundle-v2            Store a bundle payload (encrypted or not) as bundle v2: binary,
+                         zstd-compressed, a fraction of the JSON size. Decoding turns it back into
+                         JSON, but builds before bundle v2 and the web build cannot read it, so
+                         bundles are kept as JSON by default
+  --bind                 Bind the encrypted payload to this cover's size (AEAD associated data with a
+                         per-image salt): decrypting it from an image of another size fails, so it
+                         cannot be transplanted into a different cover. Needs a codec that keeps the
+                         size (dwt, dot); not with --split, qim, --format jpeg or --profile
   -o, --output <path>    Output PNG path (required for embed)
+  --method <name>        Codec: dwt (default), dot, qim or synth. qim embeds in JPEG DCT coefficients
+                         and writes a JPEG (use a .jpg output) that survives platform recompression;
+                         masks do not apply. synth is EXPERIMENTAL coverless mode:
+                         the image is generated from the payload, so no cover is given or needed
+                         (size grows with the payload); --target and masks do not apply.
+                         Codecs registered in codecs.json (see Codecs) are accepted too
+  --target <name>        Sharing preset: email, matrix, print or usb (max size + codec; default dwt only)
+  --profile <name>       Platform survival profile: twitter, telegram (qim JPEG at the platform's
+                         width), discord or imgur (dwt PNG with parity). Read-back also runs the
+                         platform's recompression/resize pipeline (name -o .jpg or .png to match)
+  --parity <n>           dwt: Reed-Solomon parity bytes per 255-byte block (1-128), correcting n/2
+                         damaged bytes each (default 16, or STEGSTR_DWT_PARITY; 0 writes the
+                         legacy frame older builds and the web app read)
+                         dot takes its parity from STEGSTR_DOT_PARITY (default 32; 0 writes the
+                         legacy 3x repetition frame older builds read)
+  --tile-size <n>        dwt: tile edge, 64, 128, 256 (default) or 512 px. Small tiles keep small
+                         covers tiled; other sizes than 256 always use parity (default 16)
+  --redundancy <n>       dwt: embed at most n copies (default 0: every tile)
+  --interleave <n>       dwt: stripe the payload over the full tiles instead of copying it, so
+                         payloads larger than one tile fit and any n surviving tiles rebuild it
+  --spread <n>           dwt, dot: animated covers (APNG, animated GIF) are embedded frame by frame,
+                         each frame holding the whole payload; this spreads it instead, so payloads
+                         larger than one frame fit and any n frames rebuild it. Frame timing is
+                         kept; APNG takes dwt, animated GIFs take dot and are written as GIF
+  --strength <n>         dwt: 1-8 quantises coarser (level-2) coefficients in steps of 4n instead of
+                         writing LSBs, so mild blur or sharpening keeps the payload; higher is
+                         sturdier but more visible and holds a quarter as much (default 0: LSBs)
+  --channels <n>         dwt: carry bits in the first n RGB channels, 1-3 (default 3)
+  --stego-key <k>        dwt, dot: embed at positions shuffled by this key, so only decode
+                         --stego-key with the same key finds the payload (hides it; does not encrypt)
+  --fiducials            dot (implied): draw corner markers and a size strip so a camera photo of the
+                         screen or print still decodes (perspective-corrected on decode). Covers of
+                         at least 256 px per side; the markers are visible
+  --calibration          dot (implied): frame the image in a 24 px white border with corner markers and
+                         timing patterns instead, leaving the cover to the dot grid; screenshots and
+                         photos that keep the markers decode. The output is 48 px larger each way
+  --dot-render <mode>    dot (implied): stamp (default) writes white cells with a black dot; luma
+                         and blue modulate the cover's own pixels instead (blue: the blue channel
+                         where it has room), far less visible but only read from lossless copies
+  --dot-contrast <n>     --dot-render luma/blue: levels per channel between the dot and the rest
+                         of its cell, 2-255 (default 12); higher survives more, shows more
+  --dot-step <n>         dot (implied): grid pitch, 4-15 px (default 6); smaller fits more payload,
+                         larger marks fewer pixels. Recorded in the image, decode finds it
+  --dot-repeat <n>       dot (implied): cells per payload symbol, 1-7 (default 1), majority-voted on
+                         decode on top of the parity
+  --format png|webp|jpeg dwt, dot: output container; webp is lossless and usually ~30% smaller
+                         (default: webp when -o ends in .webp, else png). jpeg writes with qim and
+                         verifies the payload survives one more recompression at quality 75
+  --jpeg-quality <n>     --format jpeg: JPEG quality, 50-100 (default 75); higher survives a later
+                         recompression at a lower quality better, at the cost of file size
+  --strip               dwt, dot: privacy scrub: no EXIF (GPS, camera, dates), colour profile, text
+                         or other ancillary PNG chunk reaches the output (by default the cover's
+                         profile, EXIF and PNG text are copied, with the EXIF orientation reset).
+                         Also --strip-metadata
+  --mask x,y,w,h         Leave this rectangle untouched (faces, text, logos); repeatable
+  --mask-image <path>    Mask bitmap: light pixels (luma >= 128) are left untouched
+  --no-verify            Skip the read-back check (by default the output is decoded and byte-compared)
+  --max-attempts <n>     On read-back failure, retry with stronger settings up to n attempts in total:
+                         dwt, dwt with headroom 4, dwt with headroom 16 (default: all;
+                         or STEGSTR_MAX_ATTEMPTS). Only the chosen codec is verified; the
+                         dot codec is tried only with --method dot or a target that names it
+  --quality              Report PSNR/SSIM of the output against the cover on stderr (and in --json)
+  --receipt              Sign an embedding receipt (kind 30078) with --privkey-hex and store it locally
+  --receipt-in-image     Also add the receipt to the embedded bundle (payload must be bundle JSON)
+  --decoy-payload <s|@f> Deniable embed (dwt): also embed this innocuous payload, encrypted under
+                         --decoy-passphrase, in coefficients disjoint from the --passphrase payload.
+                         decode --passphrase yields whichever payload that passphrase opens; each
+                         gets half the capacity. Takes no --stego-key, masks, --split or receipts
+  --decoy-passphrase <p> Passphrase of the decoy payload (required with --decoy-payload)
 
 Post:
   Creates a kind 1 Nostr note with Stegstr suffix. Outputs bundle JSON to stdout or --output file.
-  --privkey-hex <hex>    Nostr secret key (64-char hex). If omitted, a new key is generated for this run.
+  --reply-to <id>        Reply to this event (note, nevent or hex): adds a NIP-10 "root" e tag, plus a
+                         p tag for its author when an nevent carries one
+  --root <id>            Thread root when replying deeper in a thread: the root gets the "root" e tag
+                         and --reply-to the "reply" e tag
+  --mention <npub>       Add a p tag for this key (npub or hex, repeatable)
+  --tag <name>=<value>   Add a tag, e.g. --tag t=art for a hashtag (repeatable)
+  --kind <n>             Event kind (default 1; only kind 1 gets the Stegstr suffix)
+  --relay <url>          Also publish the note to this relay (repeatable); per-relay results go to
+                         stderr as with publish, and the exit code is non-zero if no relay accepted it
+  --privkey-hex <hex>    Nostr secret key (hex or nsec). If omitted, a new key is generated for this run.
+                         The author's npub and hex pubkey are printed to stderr.
+
+Profile, contacts:
+  Sign a kind 0 profile or a kind 3 contact list and output its bundle as post does (--privkey-hex,
+  --output, --relay and --json work the same). A contact list replaces the previous one, so
+  add/remove start from the newest list by the same key in --from (e.g. an earlier contacts
+  --output) and sign the whole updated list; without --from they start from an empty list.
+
+Dm:
+  Encrypts a direct message to the recipient with NIP-44 and outputs its bundle as post does.
+  --kind 4|14            4 (default): the ciphertext with a p tag for the recipient; sender and time
+                         stay public. 14: a NIP-17 gift wrap, signed by a one-time key, hiding both
+                         (and you cannot reopen it later)
+  --cover <path>         Embed the bundle into this cover instead; -o/--output is then the stego
+                         image and a JSON result is printed as with send
+  dm open <bundle.json> --privkey-hex HEX prints the messages in a bundle (e.g. from decode) that
+  open with your key: "<created_at><TAB><sender npub><TAB><text>", or a JSON array with --json.
+
+Scan:
+  Tries each method in order on every image (directories expand to their images) and prints
+  which method found a payload, then per-method timing totals (tried, found, mean/max, share
+  of scan time). Timings include loading the image, which each decoder does itself.
+  --methods <list>       Order to try, comma-separated (default dwt,dot,qim,synth, then external codecs)
+  --prefilter <model>    ONNX stego-presence model (default: $STEGSTR_PREFILTER_MODEL); images it
+                         rates below --threshold are skipped without decoding. Needs a build with
+                         the ml-prefilter feature
+  --threshold <p>        Prefilter probability to decode at (0-1, default 0.2; lower finds more)
+  --json                 Print the full summary as JSON
+  --recursive            Expand directories into their subdirectories too
+  --detect               Audit instead: checks images in parallel, DWT by its frame header alone
+                         (no payload decode), then qim, synth and dot by decoding, and prints a JSON
+                         report of each file's method, payload size and confidence (1 for a decoded
+                         payload; a DWT size is as stored). Keyed embeds and external codecs are not
+                         tried; --methods does not apply
+
+Capacity:
+  Prints the largest payload (bytes) the image can carry with the method (default dwt).
+  --method <name>        Codec to measure: dwt, dot, qim or an external codec
+  --target <name>        Measure the cover as the sharing preset prepares it (downscaled if needed)
+  --json                 Print { "method", "capacity": { "bytes", "bits", "human" }, ... } instead
+
+Compare:
+  Prints PSNR (dB, over RGB; "inf" when identical), SSIM (luma, 8x8 windows) and the number of
+  changed pixels. A QIM output (downscaled) is compared against the cover resized to match.
+  --json                 Print { "psnr_db", "ssim", "changed_pixels", "resized" } instead
+  --heatmap <out.png>    Also write an overlay of where the stego image differs: changed pixels
+                         red (stronger for larger differences), each 64 px tile tinted yellow by
+                         its share of changed pixels, the rest transparent
+
+Rank covers:
+  Scores each image 0-100 from luma entropy, texture (mean neighbour difference) and the share of
+  flat 8x8 blocks, and prints "<score><TAB><capacity><TAB><flat %><TAB><path>", best first.
+  Smooth covers (screenshots, logos) score low: embeds show on them and break more easily.
+  --json                 Print [{ "path", "cover": { "entropy", "texture", "flat_fraction",
+                         "capacity_bytes", "score" } }] instead
+
+Analyze:
+  Runs the chi-square attack and RS analysis (the classic LSB-replacement estimators) on each RGB
+  channel and prints a detectability score from 0 to 1 with a low/moderate/high verdict. Check a
+  stego image before sharing it; a score of 0.1 or more warrants a different cover or codec.
+  --json                 Print { "width", "height", "chi_square", "rs_estimate", "score", "verdict" }
+
+Info:
+  Prints the image's format, dimensions, colour type and frame count, which metadata it carries
+  (ICC profile, EXIF, PNG text keywords; not their contents), the capacity of dwt, dot and qim,
+  and whether a Stegstr payload is found: codec, size, MIME type, fragment and encryption (app,
+  passphrase, recipient or none). The payload itself is never printed or decrypted.
+  --json                 Print { "format", "width", "height", "color_type", "frames", "metadata",
+                         "capacity": [...], "payload": { "method", "bytes", "mime_type",
+                         "fragment", "encryption" } | null } instead
+
+Simulate:
+  Decodes the image, then applies each transform to the original and decodes again, printing
+  "ok" or "LOST" per transform. Platform profiles follow channel_simulator/CHANNEL_SPEC.md.
+  --transform <spec>     jpeg:Q, resize:PCT, crop:PCT (trimmed from each edge), rotate:90|180|270,
+                         strip, or a profile: whatsapp, instagram, facebook, twitter. Join steps
+                         with + (jpeg:80+resize:50). Repeatable; default: strip, jpeg 95/85/75,
+                         resize 75/50, crop 10, rotate 90 and every profile
+  --method <name>        Decode with this codec (default: the one that decodes the original)
+  --stego-key <k>        Decode with this stego key (dwt, dot)
+  --keep <dir>           Keep the transformed images here as <spec>.png/.jpg
+  --json                 Print the full report as JSON
+
+Batch:
+  embed writes the same payload into every image in --covers (encrypted once), naming each output
+  <out>/<name>.png (.jpg for qim). decode reads every image and prints "<path><TAB><payload>" per hit.
+  Images run on a worker pool; each prints ok/FAIL on stderr with a summary at the end, and the
+  exit code is non-zero if any image failed.
+  --cipher <name>        embed: AEAD for the encryption option, as for embed
+  --bundle-v2            embed: store a bundle payload as bundle v2, as for embed
+  --prefilter <model>    decode: skip images the model rules out, as for scan (with --threshold)
+  --jobs <n>             Worker threads (default: one per CPU)
+  --json                 Print { "total", "succeeded", "failed", "skipped", "elapsed_ms", "items": [...] }
+
+Watch:
+  Polls <dir> for new or changed PNG/JPEG files and decodes each once it stops changing (one
+  interval with the same size and mtime), printing "<path><TAB><method><TAB><payload>" per payload.
+  Runs until interrupted.
+  --interval <secs>      Poll interval (default 2)
+  --existing             Also decode images already in the folder
+  --json                 One { "path", "method", "payload" } object per line instead
+  --verbose              Report images without a payload on stderr
+
+Verify:
+  Decodes the image, decrypts the bundle (app-layer automatically; --passphrase or --privkey-hex
+  for the other modes), recomputes each event id and checks its Schnorr signature. Prints one
+  line per event; exits non-zero if any event fails or the bundle is empty.
+  --json                 Print { "valid", "total", "invalid", "events": [...] } instead
+
+Publish:
+  Sends each event in the bundle (or the single event) to all relays concurrently and reports
+  per-relay outcomes: ok, rejected (with the relay's reason), timeout or error. Exits non-zero
+  if some event was accepted by no relay.
+  --relay <url>          Relay to publish to (repeatable; default: the app's default relays)
+  --timeout <secs>       Overall deadline per event (default 5)
+  --json                 Print [{ "event_id", "results": [...] }] instead of one line per relay
+
+Fetch:
+  Subscribes to all relays with a NIP-01 filter and writes the matching events as a bundle
+  ({ "version": 1, "events": [...] }, oldest first) ready for embed --payload @bundle.json.
+  Events are deduplicated and their ids and signatures checked. Per-relay outcomes go to stderr;
+  exits non-zero if no relay answered.
+  --relay <url>          Relay to query (repeatable; default: the app's default relays)
+  --id <note|nevent|hex> Only this event (repeatable); an nevent's relay hints are queried too
+  --author <npub|hex>    Only events by this author (repeatable)
+  --kind <n>             Only events of this kind (repeatable)
+  --since / --until <ts> Unix timestamp bounds on created_at
+  --limit <n>            Keep at most the n newest events
+  --timeout <secs>       Overall deadline (default 5)
+  --output <path>        Write the bundle here instead of stdout
+
+Codecs:
+  External codecs are executables registered in <data dir>/codecs.json (or STEGSTR_CODECS):
+    { "codecs": [ { "name": "mycodec", "command": "python3", "args": ["/path/codec.py"],
+                    "timeout_secs": 30, "auto_detect": true } ] }
+  Each call writes one JSON request to the codec's stdin and reads one JSON response:
+    {"protocol":1,"op":"encode","cover":P,"output":P,"payload":B64} -> {"ok":true}
+    {"protocol":1,"op":"decode","image":P}                          -> {"ok":true,"payload":B64}
+    {"protocol":1,"op":"capacity","cover":P}                        -> {"ok":true,"capacity":N}
+  or {"ok":false,"error":"..."}. Registered codecs work with embed --method, decode/scan
+  (when auto_detect) and capacity reporting.
+
+Config:
+  Defaults shared with the app live in ~/.config/stegstr/config.toml (the platform config
+  directory; in the data dir under portable mode or STEGSTR_DATA_DIR; STEGSTR_CONFIG names
+  another file). Flags override them.
+    method = "dwt"                   embed codec when no --method, --target or --profile is given
+    redundancy = 2                   embed --redundancy
+    stego_key_ref = "default"        keychain entry (key stego <name>) holding the embed --stego-key
+                                     password (dwt/dot); decode tries it when the plain read fails
+    portable = true                  (app settings) keep data and this file next to the executable
+    relays = ["wss://relay.example"] publish/fetch relays when no --relay is given
+    output_dir = "/home/me/Stego"    where embed writes an -o given as a bare file name
+    notify = false                   no notification when watch, batch or an outbox flush finishes
+    store_events = true              (app) keep events of decoded images in the local event store
+
+Fixtures:
+  Standard test covers for bench/robustness runs. fetch copies remote images (verifying pinned
+  SHA-256; an entry without one fails) and generates synthetic ones into the cache (default
+  <data dir>/fixtures, or STEGSTR_FIXTURES_DIR). A remote url is http(s), file:// or a path
+  relative to the manifest (the bundled one names covers in this repository). pin fetches unpinned
+  entries and writes their hashes into --manifest.
+
+Send:
+  Signs a kind 1 note, bundles it, encrypts it for the recipient (and you), picks a codec that fits
+  the cover (dwt, else dot), embeds it, and optionally uploads to nostr.build. Prints a JSON result.
+  --to <npub|hex>        Recipient public key
+  --cover <path>         Cover image
+  -o, --output <path>    Output PNG path
+  --target <name>        Sharing preset: email, matrix, print or usb (max size + codec order)
+  --upload               Upload the stego image and include its URL in the result
+  --receipt              Record a signed embedding receipt in the local receipts store
+  --receipt-in-image     Also add the receipt to the embedded bundle
 "#
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    stegstr_lib::heif::register();
+    let mut args: Vec<String> = env::args().collect();
+    // --overwrite is global wherever it appears: files the command writes may replace old ones.
+    if let Some(pos) = args.iter().skip(1).position(|a| a == "--overwrite") {
+        args.remove(pos + 1);
+        OVERWRITE.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+    // --json before the subcommand is global; after it, the JSON_SUBCOMMANDS take it as global
+    // and the rest parse their own --json.
+    let leading_json = args.get(1).is_some_and(|a| a == "--json");
+    if leading_json {
+        args.remove(1);
+    }
     if args.len() < 2 {
         eprintln!("{}", usage());
         std::process::exit(1);
     }
+    let sub = args[1].clone();
+    if JSON_SUBCOMMANDS.contains(&sub.as_str()) {
+        let json = leading_json || args[2..].iter().any(|a| a == "--json");
+        let rest: Vec<String> = args[2..].iter().filter(|a| *a != "--json").cloned().collect();
+        let result = match sub.as_str() {
+            "decode" => run_decode(&rest, json),
+            "detect" => run_detect(&rest, json),
+            "embed" => run_embed(&rest, json),
+            "join" | "combine" => run_join(&rest, json),
+            "profile" => run_profile(&rest, json),
+            "contacts" => run_contacts(&rest, json),
+            "dm" => run_dm(&rest, json),
+            _ => run_post(&rest, json),
+        };
+        if let Err(e) = result {
+            if json && !e.reported {
+                let _ = print_json(&serde_json::json!({ "ok": false, "error": e.message, "code": e.code }));
+            } else if !json {
+                eprintln!("{} error: {}", sub, e.message);
+            }
+            std::process::exit(1);
+        }
+        return;
+    }
+    if leading_json {
+        args.push("--json".to_string());
+    }
     let sub = &args[1];
-    if sub == "decode" {
-        if let Err(e) = run_decode(&args[2..]) {
-            eprintln!("decode error: {}", e);
+    if sub == "decode-url" {
+        if let Err(e) = run_decode_url(&args[2..]) {
+            eprintln!("decode-url error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "scan" {
+        if let Err(e) = run_scan(&args[2..]) {
+            eprintln!("scan error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "capacity" {
+        if let Err(e) = run_capacity(&args[2..]) {
+            eprintln!("capacity error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "simulate" {
+        if let Err(e) = run_simulate(&args[2..]) {
+            eprintln!("simulate error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "compare" {
+        if let Err(e) = run_compare(&args[2..]) {
+            eprintln!("compare error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "analyze" {
+        if let Err(e) = run_analyze(&args[2..]) {
+            eprintln!("analyze error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "info" {
+        if let Err(e) = run_info(&args[2..]) {
+            eprintln!("info error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "rank-covers" {
+        if let Err(e) = run_rank_covers(&args[2..]) {
+            eprintln!("rank-covers error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "key" {
+        if let Err(e) = run_key(&args[2..]) {
+            eprintln!("key error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "codecs" {
+        if let Err(e) = run_codecs() {
+            eprintln!("codecs error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "fetch" {
+        if let Err(e) = run_fetch(&args[2..]) {
+            eprintln!("fetch error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "publish" {
+        if let Err(e) = run_publish(&args[2..]) {
+            eprintln!("publish error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "outbox" {
+        if let Err(e) = run_outbox(&args[2..]) {
+            eprintln!("outbox error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "merge" {
+        if let Err(e) = run_merge(&args[2..]) {
+            eprintln!("merge error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if sub == "fixtures" {
+        if let Err(e) = run_fixtures(&args[2..]) {
+            eprintln!("fixtures error: {}", e);
             std::process::exit(1);
         }
         return;
     }
-    if sub == "detect" {
-        if args.len() < 3 {
-            eprintln!("{}", usage());
+    if sub == "batch" {
+        if let Err(e) = run_batch(&args[2..]) {
+            eprintln!("batch error: {}", e);
             std::process::exit(1);
         }
-        let path = &args[2];
-        if let Err(e) = run_detect(path) {
-            eprintln!("detect error: {}", e);
+        return;
+    }
+    if sub == "watch" {
+        if let Err(e) = run_watch(&args[2..]) {
+            eprintln!("watch error: {}", e);
             std::process::exit(1);
         }
         return;
     }
-    if sub == "embed" {
-        if let Err(e) = run_embed(&args[2..]) {
-            eprintln!("embed error: {}", e);
+    if sub == "verify" {
+        if let Err(e) = run_verify(&args[2..]) {
+            eprintln!("verify error: {}", e);
             std::process::exit(1);
         }
         return;
     }
-    if sub == "post" {
-        if let Err(e) = run_post(&args[2..]) {
-            eprintln!("post error: {}", e);
+    if sub == "send" {
+        if let Err(e) = run_send(&args[2..]) {
+            eprintln!("send error: {}", e);
             std::process::exit(1);
         }
         return;
@@ -86,187 +640,3013 @@ fn main() {
     std::process::exit(1);
 }
 
-fn run_decode(args: &[String]) -> Result<(), String> {
+fn run_decode(args: &[String], json: bool) -> Result<(), CliError> {
     let mut decrypt = false;
+    let mut passphrase: Option<&str> = None;
+    let mut privkey_hex: Option<&str> = None;
+    let mut method: Option<&str> = None;
+    let mut auto = false;
     let mut image_path: Option<&str> = None;
+    let mut region: Option<stegstr_lib::stego::Region> = None;
+    let mut stego_key: Option<stegstr_lib::stego_key::StegoKey> = None;
     let mut i = 0;
     while i < args.len() {
         let a = &args[i];
         if a == "--decrypt" {
             decrypt = true;
-        } else if !a.starts_with('-') && image_path.is_none() {
+        } else if a == "--passphrase" {
+            i += 1;
+            passphrase = Some(args.get(i).ok_or("missing value for --passphrase")?);
+        } else if a == "--privkey-hex" || a == "--privkey" {
+            i += 1;
+            privkey_hex = Some(args.get(i).ok_or("missing value for --privkey-hex")?);
+        } else if a == "--method" {
+            i += 1;
+            method = Some(args.get(i).ok_or("missing value for --method")?);
+        } else if a == "--auto" {
+            auto = true;
+        } else if a == "--region" {
+            i += 1;
+            region = Some(parse_region(args.get(i).ok_or("missing value for --region")?)?);
+        } else if a == "--stego-key" {
+            i += 1;
+            stego_key = Some(stegstr_lib::stego_key::StegoKey::from_password(args.get(i).ok_or("missing value for --stego-key")?)?);
+        } else if (a == "-" || !a.starts_with('-')) && image_path.is_none() {
             image_path = Some(a);
         }
         i += 1;
     }
-    let path_str = image_path.ok_or("decode requires <image.png>")?;
-    let path = Path::new(path_str);
-    let payload = stegstr_lib::stego::decode(path)?;
-    let output = if decrypt && stegstr_lib::stego_crypto::is_encrypted_payload(&payload) {
-        stegstr_lib::stego_crypto::decrypt_app(&payload)?
+    let input = InputImage::open(image_path.ok_or("decode requires <image.png>")?).code("io")?;
+    let path = input.path.as_path();
+    if auto && (region.is_some() || method.is_some()) {
+        return Err("--auto does not take --method or --region".into());
+    }
+    if region.is_some() && stego_key.is_some() {
+        return Err("--region does not take --stego-key".into());
+    }
+    // Decoy embeds key each payload's positions with its passphrase; failing that, the config
+    // file's stego key is tried.
+    let fallback_key = match (passphrase, &stego_key, region, auto, method) {
+        (Some(p), None, None, false, None | Some("dwt")) => Some((stegstr_lib::stego_key::StegoKey::from_password(p)?, &["dwt"][..])),
+        (None, None, None, false, None | Some("dwt")) => config_stego_key(stegstr_lib::config::load()).map(|k| (k, &["dwt"][..])),
+        _ => None,
+    };
+    let keyed_fallback = |e: String| match &fallback_key {
+        Some((key, order)) => stegstr_lib::scan::decode_timed_with_key(path, order, Some(key)).0.map_err(|_| e),
+        None => Err(e),
+    };
+    let (payload, found) = match (region, method) {
+        (None, m) if stego_key.is_some() => {
+            let order = m.map_or(stegstr_lib::scan::KEYED_METHODS.to_vec(), |m| vec![m]);
+            stegstr_lib::scan::decode_timed_with_key(path, &order, stego_key.as_ref()).0.code("no_payload")?
+        }
+        _ if auto => {
+            let (payload, method) = stegstr_lib::scan::detect_and_decode(path).code("no_payload")?;
+            eprintln!("Detected {}", method);
+            (payload, method)
+        }
+        (Some(r), None | Some("dwt")) => (stegstr_lib::stego::decode_region(path, r).code("no_payload")?, "dwt".to_string()),
+        (Some(_), Some(m)) => return Err(format!("--region only applies to dwt, not {}", m).into()),
+        (None, None | Some("dwt")) => match stegstr_lib::stego::decode_with_orientation(path, None) {
+            Ok((payload, orientation)) => {
+                if orientation != stegstr_lib::stego::Orientation::NoTransforms {
+                    eprintln!("Decoded after undoing {}", stegstr_lib::stego::orientation_name(orientation));
+                }
+                (payload, "dwt".to_string())
+            }
+            Err(e) => keyed_fallback(e.into()).code("no_payload")?,
+        },
+        (None, Some(m)) => (stegstr_lib::scan::decode_with(m, path).code("no_payload")?, m.to_string()),
+    };
+    let header = stegstr_lib::payload_header::read(&payload).code("no_payload")?.0;
+    if let Some(f) = header.as_ref().and_then(|h| h.fragment.as_ref()) {
+        eprintln!("Fragment {} of {} (bundle {}); reassemble with join", f.index + 1, f.total, f.bundle_id);
+    }
+    let text = payload_text(&payload, Some(path), decrypt, passphrase, privkey_hex).code("decrypt")?;
+    print_decoded(text, found, header, json)
+}
+
+/// The config file's stego key, for decode's keyed retry. A config file or keychain entry that
+/// cannot be read only loses that retry: decode warns and carries on with the defaults.
+fn config_stego_key(settings: Result<stegstr_lib::config::Settings, String>) -> Option<stegstr_lib::stego_key::StegoKey> {
+    let key = settings
+        .and_then(|s| s.stego_key_password())
+        .and_then(|p| p.as_deref().map(stegstr_lib::stego_key::StegoKey::from_password).transpose());
+    key.unwrap_or_else(|e| {
+        eprintln!("Warning: {} (decoding without the configured stego key)", e);
+        None
+    })
+}
+
+/// `join`: decode each image, reassemble the fragments (embed --split) and print the payload as
+/// decode does.
+fn run_join(args: &[String], json: bool) -> Result<(), CliError> {
+    let mut decrypt = false;
+    let mut passphrase: Option<&str> = None;
+    let mut privkey_hex: Option<&str> = None;
+    let mut stego_key: Option<stegstr_lib::stego_key::StegoKey> = None;
+    let mut images: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--decrypt" {
+            decrypt = true;
+        } else if a == "--passphrase" {
+            i += 1;
+            passphrase = Some(args.get(i).ok_or("missing value for --passphrase")?);
+        } else if a == "--privkey-hex" || a == "--privkey" {
+            i += 1;
+            privkey_hex = Some(args.get(i).ok_or("missing value for --privkey-hex")?);
+        } else if a == "--stego-key" {
+            i += 1;
+            stego_key = Some(stegstr_lib::stego_key::StegoKey::from_password(args.get(i).ok_or("missing value for --stego-key")?)?);
+        } else if !a.starts_with('-') {
+            images.push(a);
+        } else {
+            return Err(format!("unexpected argument {:?}", a).into());
+        }
+        i += 1;
+    }
+    if images.is_empty() {
+        return Err("join requires <image>...".into());
+    }
+    let mut parts = Vec::with_capacity(images.len());
+    let mut methods: Vec<String> = Vec::new();
+    for image in images {
+        let path = Path::new(image);
+        let decoded = match &stego_key {
+            Some(key) => stegstr_lib::scan::decode_timed_with_key(path, stegstr_lib::scan::KEYED_METHODS, Some(key)).0,
+            None => stegstr_lib::scan::decode_timed(path, &stegstr_lib::scan::default_order()).0,
+        };
+        // Skipped rather than fatal: fountain-coded and Shamir series do not need every image.
+        let (payload, method) = match decoded {
+            Ok(found) => found,
+            Err(e) => {
+                eprintln!("Skipping {}: {}", image, e);
+                continue;
+            }
+        };
+        parts.push(payload);
+        if !methods.contains(&method) {
+            methods.push(method);
+        }
+    }
+    let payload = stegstr_lib::fragment::join(&parts).code("no_payload")?;
+    eprintln!("Joined {} image(s) into {}", parts.len(), stegstr_lib::units::format_bytes(payload.len() as u64));
+    let header = stegstr_lib::payload_header::read(&payload).code("no_payload")?.0;
+    let text = payload_text(&payload, None, decrypt, passphrase, privkey_hex).code("decrypt")?;
+    print_decoded(text, methods.join(","), header, json)
+}
+
+/// Decoded payload: as is on stdout, or a StegoDecodeResult object with --json.
+fn print_decoded(text: String, method: String, header: Option<PayloadHeader>, json: bool) -> Result<(), CliError> {
+    if !json {
+        return io::stdout().write_all(text.as_bytes()).map_err(|e| e.to_string()).code("io");
+    }
+    print_json(&stegstr_lib::StegoDecodeResult {
+        ok: true,
+        payload: Some(text),
+        method: Some(method),
+        header,
+        ..Default::default()
+    })
+}
+
+/// Size of the image at `path`, as bound payloads are checked against.
+fn image_binding(path: &Path) -> Result<stegstr_lib::stego_crypto::ImageBinding, String> {
+    let (width, height) = image::image_dimensions(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(stegstr_lib::stego_crypto::ImageBinding { width, height })
+}
+
+/// Decode output: decrypted bundle with `decrypt`, a passphrase or a secret key, else JSON text or
+/// base64:<data>.
+fn print_payload(payload: &[u8], image: Option<&Path>, decrypt: bool, passphrase: Option<&str>, privkey_hex: Option<&str>) -> Result<(), String> {
+    let output = payload_text(payload, image, decrypt, passphrase, privkey_hex)?;
+    io::stdout().write_all(output.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Payload header split off first.
+fn payload_text(payload: &[u8], image: Option<&Path>, decrypt: bool, passphrase: Option<&str>, privkey_hex: Option<&str>) -> Result<String, String> {
+    use stegstr_lib::stego_crypto;
+    let (header, payload) = stegstr_lib::payload_header::read(payload)?;
+    let payload = payload.as_slice();
+    // Payloads embedded with --bind decrypt only with the size of the image they were read from.
+    let binding = match (stego_crypto::is_bound_payload(payload), image) {
+        (true, Some(path)) => Some(image_binding(path)?),
+        _ => None,
+    };
+    Ok(if let Some(p) = passphrase {
+        stego_crypto::decrypt_password_bound(payload, p, binding)?
+    } else if let Some(hex) = privkey_hex {
+        let sk = SecretKeyGuard(stegstr_lib::nostr_codes::parse_secret_key(hex)?);
+        stego_crypto::decrypt_payload_bound(payload, Some(&sk), binding)?
+    } else if decrypt && stego_crypto::is_encrypted_payload(payload) {
+        stego_crypto::decrypt_app_bound(payload, binding)?
     } else if decrypt {
         return Err("Payload is not Stegstr app-encrypted (use without --decrypt for raw)".to_string());
+    } else if header.is_some_and(|h| h.mime_type == stegstr_lib::payload_header::MIME_BUNDLE) {
+        stegstr_lib::bundle::unpack(payload.to_vec())?
     } else {
-        match String::from_utf8(payload.clone()) {
-            Ok(s) if s.trim_start().starts_with('{') => s,
+        match std::str::from_utf8(payload) {
+            Ok(s) if s.trim_start().starts_with('{') => s.to_string(),
             _ => format!(
                 "base64:{}",
-                base64::engine::general_purpose::STANDARD.encode(&payload)
+                base64::engine::general_purpose::STANDARD.encode(payload)
             ),
         }
+    })
+}
+
+fn run_batch(args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        Some("embed") => run_batch_embed(&args[1..]),
+        Some("decode") => run_batch_decode(&args[1..]),
+        _ => Err("batch requires embed or decode".to_string()),
+    }
+}
+
+fn parse_jobs(v: Option<&String>) -> Result<usize, String> {
+    let v = v.ok_or("missing value for --jobs")?;
+    v.parse().map_err(|_| format!("invalid --jobs {:?}", v))
+}
+
+fn parse_threshold(v: Option<&String>) -> Result<f32, String> {
+    let v = v.ok_or("missing value for --threshold")?;
+    v.parse()
+        .ok()
+        .filter(|t: &f32| (0.0..=1.0).contains(t))
+        .ok_or_else(|| format!("invalid --threshold {:?} (expected 0-1)", v))
+}
+
+/// The --prefilter model, else the one named by STEGSTR_PREFILTER_MODEL, at --threshold.
+fn load_prefilter(model: Option<&str>, threshold: Option<f32>) -> Result<Option<stegstr_lib::prefilter::Prefilter>, String> {
+    use stegstr_lib::prefilter::{Prefilter, DEFAULT_THRESHOLD};
+    let prefilter = match model {
+        Some(m) => Some(Prefilter::load(Path::new(m), threshold.unwrap_or(DEFAULT_THRESHOLD))?),
+        None => Prefilter::from_env(threshold.unwrap_or(DEFAULT_THRESHOLD)).transpose()?,
     };
-    io::stdout().write_all(output.as_bytes()).map_err(|e| e.to_string())?;
-    Ok(())
+    if prefilter.is_none() && threshold.is_some() {
+        return Err("--threshold needs --prefilter <model> (or STEGSTR_PREFILTER_MODEL)".to_string());
+    }
+    Ok(prefilter)
 }
 
-fn run_detect(image_path: &str) -> Result<(), String> {
-    let path = Path::new(image_path);
-    let payload = stegstr_lib::stego::decode(path)?;
-    let json = if stegstr_lib::stego_crypto::is_encrypted_payload(&payload) {
-        stegstr_lib::stego_crypto::decrypt_app(&payload)?
+/// One line per image on stderr (or the whole summary as JSON on stdout); errors if any image failed.
+fn report_batch(summary: &stegstr_lib::batch::BatchSummary, json: bool, print_payloads: bool) -> Result<(), String> {
+    if json {
+        let out = serde_json::to_string_pretty(summary).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
     } else {
-        String::from_utf8(payload).map_err(|e| e.to_string())?
-    };
-    io::stdout().write_all(json.as_bytes()).map_err(|e| e.to_string())?;
+        for item in &summary.items {
+            if item.skipped {
+                eprintln!("skip {} (prefilter {:.2})", item.input, item.prefilter.unwrap_or_default());
+                continue;
+            }
+            match (&item.error, &item.method) {
+                (Some(e), _) => eprintln!("FAIL {}: {}", item.input, e),
+                (None, method) => {
+                    let target = item.output.as_deref().map(|o| format!(" -> {}", o)).unwrap_or_default();
+                    eprintln!("ok {}{} ({}, {} ms)", item.input, target, method.as_deref().unwrap_or("?"), item.millis);
+                    if let (true, Some(p)) = (print_payloads, &item.payload) {
+                        // One line per image: JSON payloads are printed compact.
+                        let line = serde_json::from_str::<serde_json::Value>(p).map_or_else(|_| p.clone(), |v| v.to_string());
+                        println!("{}\t{}", item.input, line);
+                    }
+                }
+            }
+        }
+        let skipped = if summary.skipped > 0 { format!(", {} skipped", summary.skipped) } else { String::new() };
+        eprintln!("{} succeeded, {} failed{} ({} ms)", summary.succeeded, summary.failed, skipped, summary.elapsed_ms);
+    }
+    if summary.failed > 0 {
+        return Err(format!("{} of {} image(s) failed", summary.failed, summary.total));
+    }
     Ok(())
 }
 
-fn run_embed(args: &[String]) -> Result<(), String> {
-    let mut cover: Option<&str> = None;
-    let mut output: Option<&str> = None;
+fn run_batch_embed(args: &[String]) -> Result<(), String> {
+    let mut covers: Option<&str> = None;
+    let mut out_dir: Option<&str> = None;
     let mut payload_str: Option<String> = None;
-    let mut payload_base64: Option<String> = None;
-    let mut encrypt = false;
-
+    let mut encryption = Encryption::None;
+    let mut cipher: Option<stegstr_lib::stego_crypto::Cipher> = None;
+    let mut bundle_v2 = false;
+    let mut target: Option<&'static stegstr_lib::targets::Target> = None;
+    let mut profile: Option<&'static stegstr_lib::profiles::Profile> = None;
+    let mut method: Option<&'static str> = None;
+    let mut verify = true;
+    let mut jobs = 0;
+    let mut json = false;
     let mut i = 0;
     while i < args.len() {
         let a = &args[i];
-        if a == "-o" || a == "--output" {
+        if a == "--covers" {
             i += 1;
-            output = Some(args.get(i).ok_or("missing value for -o/--output")?);
+            covers = Some(args.get(i).ok_or("missing value for --covers")?);
+        } else if a == "--out" {
+            i += 1;
+            out_dir = Some(args.get(i).ok_or("missing value for --out")?);
         } else if a == "--payload" {
             i += 1;
             let v = args.get(i).ok_or("missing value for --payload")?;
-            if v.starts_with('@') {
-                let path = v.trim_start_matches('@');
-                payload_str = Some(fs::read_to_string(path).map_err(|e| e.to_string())?);
-            } else {
-                payload_str = Some(v.clone());
-            }
-        } else if a == "--payload-base64" {
-            i += 1;
-            payload_base64 = Some(args.get(i).ok_or("missing value for --payload-base64")?.clone());
+            payload_str = Some(match v.strip_prefix('@') {
+                Some(path) => fs::read_to_string(path).map_err(|e| e.to_string())?,
+                None => v.clone(),
+            });
         } else if a == "--encrypt" {
-            encrypt = true;
-        } else if !a.starts_with('-') && cover.is_none() {
-            cover = Some(a);
+            encryption = encryption.set(Encryption::App)?;
+        } else if a == "--passphrase" {
+            i += 1;
+            encryption = encryption.set(Encryption::Passphrase(args.get(i).ok_or("missing value for --passphrase")?.clone()))?;
+        } else if a == "--recipient" {
+            i += 1;
+            encryption = encryption.set(Encryption::Recipient(args.get(i).ok_or("missing value for --recipient")?.clone()))?;
+        } else if a == "--cipher" {
+            i += 1;
+            cipher = Some(stegstr_lib::stego_crypto::Cipher::parse(args.get(i).ok_or("missing value for --cipher")?)?);
+        } else if a == "--bundle-v2" {
+            bundle_v2 = true;
+        } else if a == "--target" {
+            i += 1;
+            target = Some(stegstr_lib::targets::find(args.get(i).ok_or("missing value for --target")?)?);
+        } else if a == "--profile" {
+            i += 1;
+            profile = Some(stegstr_lib::profiles::find(args.get(i).ok_or("missing value for --profile")?)?);
+        } else if a == "--method" {
+            i += 1;
+            method = Some(embed_method(args.get(i).ok_or("missing value for --method")?)?);
+        } else if a == "--no-verify" {
+            verify = false;
+        } else if a == "--jobs" {
+            i += 1;
+            jobs = parse_jobs(args.get(i))?;
+        } else if a == "--json" {
+            json = true;
+        } else {
+            return Err(format!("unexpected argument {:?}", a));
         }
         i += 1;
     }
-
-    let cover_path = cover.ok_or("embed requires <cover.png>")?;
-    let output_path = output.ok_or("embed requires -o/--output <out.png>")?;
-
-    let mut payload_bytes: Vec<u8> = if let Some(b64) = payload_base64 {
-        base64::engine::general_purpose::STANDARD
-            .decode(b64.trim())
-            .map_err(|e| e.to_string())?
-    } else if let Some(s) = payload_str {
-        s.into_bytes()
-    } else {
-        return Err("embed requires --payload <string|@file> or --payload-base64 <b64>".to_string());
+    let covers = stegstr_lib::scan::expand_paths(&[covers.ok_or("batch embed requires --covers <dir>")?.to_string()])?;
+    let out_dir = Path::new(out_dir.ok_or("batch embed requires --out <dir>")?);
+    let payload = payload_str.ok_or("batch embed requires --payload <string|@file>")?.into_bytes();
+    if method == Some("synth") {
+        return Err("batch embed does not support synth (it ignores the cover)".to_string());
+    }
+    if [method.is_some(), target.is_some(), profile.is_some()].iter().filter(|&&b| b).count() > 1 {
+        return Err("--method, --target and --profile are exclusive (targets and profiles pick their own codecs)".to_string());
+    }
+    let crypt = encryption.options(cipher, bundle_v2)?;
+    // Encrypted once: every image carries the same ciphertext.
+    let payload = encryption.apply(payload, &crypt)?;
+    let payload = encryption.header(&payload, None, None).write(&payload)?;
+    let dwt = stegstr_lib::stego::EncodeOptions::default();
+    let settings = EmbedSettings {
+        method,
+        target,
+        mask: None,
+        dwt: &dwt,
+        verify,
+        max_attempts: stegstr_lib::targets::max_attempts(),
+        jpeg_quality: None,
+        profile,
+        fiducials: false,
+        calibration: false,
+        rendering: stegstr_lib::stego_dot::Rendering::Stamp,
+        layout: stegstr_lib::stego_dot::Layout::default(),
     };
+    let summary = stegstr_lib::batch::embed_all(&covers, out_dir, jobs, OVERWRITE.load(std::sync::atomic::Ordering::Relaxed), |cover| {
+        settings.embed(cover, &payload).map(|e| (e.bytes, e.method.to_string()))
+    })?;
+    summary.notify_done("Batch embed");
+    report_batch(&summary, json, false)
+}
 
-    if encrypt {
-        let plaintext = String::from_utf8(payload_bytes).map_err(|e| e.to_string())?;
-        payload_bytes = stegstr_lib::stego_crypto::encrypt_app(&plaintext)?;
+fn run_batch_decode(args: &[String]) -> Result<(), String> {
+    let mut inputs: Vec<String> = Vec::new();
+    let mut decrypt = false;
+    let mut passphrase: Option<&str> = None;
+    let mut privkey_hex: Option<&str> = None;
+    let mut method: Option<&str> = None;
+    let mut prefilter_model: Option<&str> = None;
+    let mut threshold: Option<f32> = None;
+    let mut jobs = 0;
+    let mut json = false;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--decrypt" {
+            decrypt = true;
+        } else if a == "--passphrase" {
+            i += 1;
+            passphrase = Some(args.get(i).ok_or("missing value for --passphrase")?);
+        } else if a == "--privkey-hex" || a == "--privkey" {
+            i += 1;
+            privkey_hex = Some(args.get(i).ok_or("missing value for --privkey-hex")?);
+        } else if a == "--method" {
+            i += 1;
+            method = Some(args.get(i).ok_or("missing value for --method")?);
+        } else if a == "--prefilter" {
+            i += 1;
+            prefilter_model = Some(args.get(i).ok_or("missing value for --prefilter")?);
+        } else if a == "--threshold" {
+            i += 1;
+            threshold = Some(parse_threshold(args.get(i))?);
+        } else if a == "--jobs" {
+            i += 1;
+            jobs = parse_jobs(args.get(i))?;
+        } else if a == "--json" {
+            json = true;
+        } else if !a.starts_with('-') {
+            inputs.push(a.clone());
+        } else {
+            return Err(format!("unexpected argument {:?}", a));
+        }
+        i += 1;
+    }
+    if inputs.is_empty() {
+        return Err("batch decode requires <dir|image>...".to_string());
     }
+    let paths = stegstr_lib::scan::expand_paths(&inputs)?;
+    let order = match method {
+        Some(m) => vec![m],
+        None => stegstr_lib::scan::default_order(),
+    };
+    let prefilter = load_prefilter(prefilter_model, threshold)?;
+    let summary = stegstr_lib::batch::decode_all(&paths, jobs, prefilter.as_ref(), |path| {
+        let (payload, found) = stegstr_lib::scan::decode_timed(path, &order).0?;
+        Ok((payload_text(&payload, Some(path), decrypt, passphrase, privkey_hex)?, found))
+    })?;
+    summary.notify_done("Batch decode");
+    report_batch(&summary, json, true)
+}
 
-    let png_bytes = stegstr_lib::stego::encode(Path::new(cover_path), &payload_bytes)?;
-    fs::write(output_path, png_bytes).map_err(|e| e.to_string())?;
-    eprintln!("Wrote {}", output_path);
-    Ok(())
+fn run_watch(args: &[String]) -> Result<(), String> {
+    let mut dir: Option<&str> = None;
+    let mut interval = stegstr_lib::watch::DEFAULT_INTERVAL;
+    let mut include_existing = false;
+    let mut decrypt = false;
+    let mut passphrase: Option<&str> = None;
+    let mut privkey_hex: Option<&str> = None;
+    let mut json = false;
+    let mut verbose = false;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--interval" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --interval")?;
+            let secs: f64 = v.parse().map_err(|_| format!("invalid --interval {:?}", v))?;
+            interval = std::time::Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())?;
+        } else if a == "--existing" {
+            include_existing = true;
+        } else if a == "--decrypt" {
+            decrypt = true;
+        } else if a == "--passphrase" {
+            i += 1;
+            passphrase = Some(args.get(i).ok_or("missing value for --passphrase")?);
+        } else if a == "--privkey-hex" || a == "--privkey" {
+            i += 1;
+            privkey_hex = Some(args.get(i).ok_or("missing value for --privkey-hex")?);
+        } else if a == "--json" {
+            json = true;
+        } else if a == "--verbose" {
+            verbose = true;
+        } else if !a.starts_with('-') && dir.is_none() {
+            dir = Some(a);
+        }
+        i += 1;
+    }
+    let dir = Path::new(dir.ok_or("watch requires <dir>")?);
+    eprintln!("Watching {} (Ctrl-C to stop)", dir.display());
+    let stop = std::sync::atomic::AtomicBool::new(false);
+    stegstr_lib::watch::watch(dir, interval, include_existing, &stop, |path, decoded| {
+        let found = decoded.and_then(|(payload, method)| Ok((payload_text(&payload, Some(path), decrypt, passphrase, privkey_hex)?, method)));
+        match found {
+            Ok((text, method)) if json => {
+                println!("{}", serde_json::json!({ "path": path, "method": method, "payload": text }));
+            }
+            Ok((text, method)) => {
+                let line = serde_json::from_str::<serde_json::Value>(&text).map_or(text, |v| v.to_string());
+                println!("{}\t{}\t{}", path.display(), method, line);
+            }
+            Err(e) if verbose => eprintln!("{}: {}", path.display(), e),
+            Err(_) => {}
+        }
+        let _ = io::stdout().flush();
+    })
 }
 
-fn ensure_stegstr_suffix(content: &str) -> String {
-    let mut s = content.to_string();
-    if !s.ends_with(STEGSTR_SUFFIX) {
-        s.push_str(STEGSTR_SUFFIX);
-    }
-    if s.len() > MAX_NOTE_LENGTH {
-        s.truncate(MAX_NOTE_LENGTH);
-    }
-    s
-}
-
-/// Create a NIP-01 kind 1 event and return (id_hex, pubkey_hex, created_at, sig_hex) for bundle JSON.
-fn create_kind1_event(content: &str, sk: &secp256k1::SecretKey) -> Result<(String, String, u64, String), String> {
-    let secp = Secp256k1::new();
-    let pk = secp256k1::Keypair::from_secret_key(&secp, sk);
-    let (xonly, _parity) = pk.x_only_public_key();
-    let pubkey_hex = hex::encode(xonly.serialize());
-    let created_at = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|e| e.to_string())?
-        .as_secs();
-    let tags: Vec<Vec<String>> = vec![];
-    let serialized = serde_json::to_string(&serde_json::json!([0, pubkey_hex, created_at, 1, tags, content]))
-        .map_err(|e| e.to_string())?;
-    let id_hash = Sha256::digest(serialized.as_bytes());
-    let id_hex = hex::encode(id_hash);
-    let msg = secp256k1::Message::from_digest_slice(id_hash.as_ref()).map_err(|e| e.to_string())?;
-    let keypair = secp256k1::Keypair::from_secret_key(&secp, sk);
-    let sig = secp.sign_schnorr_no_aux_rand(&msg, &keypair);
-    let sig_hex = hex::encode(sig.serialize());
-    Ok((id_hex, pubkey_hex, created_at, sig_hex))
-}
-
-fn run_post(args: &[String]) -> Result<(), String> {
-    let mut content: Option<String> = None;
-    let mut privkey_hex: Option<String> = None;
-    let mut output_path: Option<&str> = None;
+fn run_verify(args: &[String]) -> Result<(), String> {
+    let mut passphrase: Option<&str> = None;
+    let mut privkey_hex: Option<&str> = None;
+    let mut method: Option<&str> = None;
+    let mut json = false;
+    let mut image_path: Option<&str> = None;
     let mut i = 0;
     while i < args.len() {
         let a = &args[i];
-        if a == "--privkey-hex" {
+        if a == "--passphrase" {
             i += 1;
-            privkey_hex = Some(args.get(i).ok_or("missing value for --privkey-hex")?.clone());
-        } else if a == "--output" {
+            passphrase = Some(args.get(i).ok_or("missing value for --passphrase")?);
+        } else if a == "--privkey-hex" || a == "--privkey" {
             i += 1;
-            output_path = Some(args.get(i).ok_or("missing value for --output")?);
-        } else if !a.starts_with('-') && content.is_none() {
-            content = Some(a.clone());
+            privkey_hex = Some(args.get(i).ok_or("missing value for --privkey-hex")?);
+        } else if a == "--method" {
+            i += 1;
+            method = Some(args.get(i).ok_or("missing value for --method")?);
+        } else if a == "--json" {
+            json = true;
+        } else if (a == "-" || !a.starts_with('-')) && image_path.is_none() {
+            image_path = Some(a);
         }
         i += 1;
     }
-    let content = content.ok_or("post requires content (e.g. post \"Hello world\")")?;
-    let content_with_suffix = ensure_stegstr_suffix(&content);
-    let sk = if let Some(hex) = privkey_hex {
-        let bytes = hex::decode(hex.trim()).map_err(|e| e.to_string())?;
-        secp256k1::SecretKey::from_slice(&bytes).map_err(|e| e.to_string())?
-    } else {
-        secp256k1::SecretKey::new(&mut rand::thread_rng())
+    let input = InputImage::open(image_path.ok_or("verify requires <image>")?)?;
+    let path = input.path.as_path();
+    let payload = match method {
+        Some(m) => stegstr_lib::scan::decode_with(m, path)?,
+        None => stegstr_lib::scan::decode_any(path)?,
     };
-    let (id_hex, pubkey_hex, created_at, sig_hex) = create_kind1_event(&content_with_suffix, &sk)?;
-    let event = serde_json::json!({
-        "id": id_hex,
-        "pubkey": pubkey_hex,
-        "created_at": created_at,
-        "kind": 1,
-        "tags": [],
-        "content": content_with_suffix,
-        "sig": sig_hex
-    });
-    let bundle = serde_json::json!({
-        "version": 1,
-        "events": [event]
-    });
-    let json = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
-    if let Some(path) = output_path {
-        fs::write(path, &json).map_err(|e| e.to_string())?;
-        eprintln!("Wrote {}", path);
+    // App-encrypted payloads are opened as with decode --decrypt; plain bundles are read as is.
+    let (_, payload) = stegstr_lib::payload_header::read(&payload)?;
+    let decrypt = stegstr_lib::stego_crypto::is_encrypted_payload(&payload);
+    let text = payload_text(&payload, Some(path), decrypt, passphrase, privkey_hex)?;
+    let report = stegstr_lib::nostr_verify::verify_bundle_json(&text)?;
+    if json {
+        let out = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
     } else {
-        io::stdout().write_all(json.as_bytes()).map_err(|e| e.to_string())?;
+        for e in &report.events {
+            let id = e.id.as_deref().unwrap_or("(no id)");
+            match &e.error {
+                None => println!("ok {}", id),
+                Some(err) => println!("FAIL {}: {}", id, err),
+            }
+        }
+        println!("{} of {} event(s) verified", report.total - report.invalid, report.total);
+    }
+    if !report.valid {
+        return Err(if report.total == 0 {
+            "bundle has no events".to_string()
+        } else {
+            format!("{} event(s) failed verification", report.invalid)
+        });
     }
     Ok(())
 }
+
+fn run_decode_url(args: &[String]) -> Result<(), String> {
+    use stegstr_lib::download;
+    let mut url: Option<String> = None;
+    let mut sha256: Option<String> = None;
+    let mut nip94: Option<String> = None;
+    let mut output: Option<&str> = None;
+    let mut decrypt = false;
+    let mut passphrase: Option<&str> = None;
+    let mut privkey_hex: Option<&str> = None;
+    let mut method: Option<&str> = None;
+    let mut opts = download::DownloadOptions::default();
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--sha256" {
+            i += 1;
+            sha256 = Some(args.get(i).ok_or("missing value for --sha256")?.to_ascii_lowercase());
+        } else if a == "--nip94" {
+            i += 1;
+            nip94 = Some(args.get(i).ok_or("missing value for --nip94")?.clone());
+        } else if a == "--max-bytes" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --max-bytes")?;
+            opts.max_bytes = v.parse().map_err(|_| format!("invalid --max-bytes {:?}", v))?;
+        } else if a == "-o" || a == "--output" {
+            i += 1;
+            output = Some(args.get(i).ok_or("missing value for -o/--output")?);
+        } else if a == "--decrypt" {
+            decrypt = true;
+        } else if a == "--passphrase" {
+            i += 1;
+            passphrase = Some(args.get(i).ok_or("missing value for --passphrase")?);
+        } else if a == "--privkey-hex" || a == "--privkey" {
+            i += 1;
+            privkey_hex = Some(args.get(i).ok_or("missing value for --privkey-hex")?);
+        } else if a == "--method" {
+            i += 1;
+            method = Some(args.get(i).ok_or("missing value for --method")?);
+        } else if !a.starts_with('-') && url.is_none() {
+            url = Some(a.clone());
+        }
+        i += 1;
+    }
+    let meta = match &nip94 {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            let event: stegstr_lib::nostr_event::NostrEvent = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+            download::FileMetadata::from_event(&event)?
+        }
+        None => download::FileMetadata::default(),
+    };
+    let (fetch_url, fragment_hash) = download::split_hash_fragment(&url.or(meta.url).ok_or("decode-url requires <url> (or --nip94 with a url tag)")?);
+    // Every hash given must agree; the first one becomes the expectation.
+    let hashes: Vec<String> = [sha256, fragment_hash, meta.sha256.map(|h| h.to_ascii_lowercase())].into_iter().flatten().collect();
+    if hashes.windows(2).any(|w| w[0] != w[1]) {
+        return Err(format!("conflicting expected hashes: {}", hashes.join(", ")));
+    }
+    opts.sha256 = hashes.into_iter().next();
+    opts.size = meta.size;
+
+    let report = match output {
+        Some(p) => download::download(&fetch_url, Path::new(p), &opts)?,
+        None => download::download_to_cache(&fetch_url, &opts)?,
+    };
+    eprintln!(
+        "Downloaded {} ({}{}{})",
+        report.path,
+        stegstr_lib::units::format_bytes(report.bytes),
+        if report.verified { ", sha256 verified" } else { ", no hash to verify" },
+        if report.resumed > 0 { format!(", resumed {}x", report.resumed) } else { String::new() }
+    );
+    let payload = match method {
+        Some(m) => stegstr_lib::scan::decode_with(m, Path::new(&report.path))?,
+        None => stegstr_lib::scan::decode_any(Path::new(&report.path))?,
+    };
+    print_payload(&payload, Some(Path::new(&report.path)), decrypt, passphrase, privkey_hex)
+}
+
+/// Parse "x,y,w,h" into a region (--region, --mask).
+fn parse_region(s: &str) -> Result<stegstr_lib::stego::Region, String> {
+    let parts: Vec<u32> = s
+        .split(',')
+        .map(|p| p.trim().parse::<u32>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("invalid region {:?}: {}", s, e))?;
+    if parts.len() != 4 {
+        return Err(format!("invalid region {:?}: expected x,y,w,h", s));
+    }
+    Ok(stegstr_lib::stego::Region {
+        x: parts[0],
+        y: parts[1],
+        width: parts[2],
+        height: parts[3],
+    })
+}
+
+fn run_detect(args: &[String], json: bool) -> Result<(), CliError> {
+    let auto = args.iter().any(|a| a == "--auto");
+    let [image_path] = args.iter().filter(|a| *a != "--auto").collect::<Vec<_>>()[..] else {
+        return Err("detect requires <image.png>".into());
+    };
+    let input = InputImage::open(image_path).code("io")?;
+    let (payload, method) = match auto {
+        true => stegstr_lib::scan::detect_and_decode(&input.path).code("no_payload")?,
+        false => (stegstr_lib::stego::decode(&input.path).code("no_payload")?, "dwt".to_string()),
+    };
+    let (header, payload) = stegstr_lib::payload_header::read(&payload).code("no_payload")?;
+    let text = if stegstr_lib::stego_crypto::is_encrypted_payload(&payload) {
+        stegstr_lib::stego_crypto::decrypt_app(&payload).code("decrypt")?
+    } else if header.as_ref().is_some_and(|h| h.mime_type == stegstr_lib::payload_header::MIME_BUNDLE) {
+        stegstr_lib::bundle::unpack(payload).code("no_payload")?
+    } else if serde_json::from_slice::<serde_json::Value>(&payload).is_ok() {
+        String::from_utf8(payload).map_err(|e| e.to_string()).code("no_payload")?
+    } else {
+        // Truncated/corrupt plaintext bundle: keep whatever complete events were recovered.
+        let events = stegstr_lib::event_stream::salvage_events(&payload);
+        if events.is_empty() {
+            return Err("Payload is not a bundle and no complete events could be salvaged".to_string()).code("no_payload");
+        }
+        eprintln!("warning: bundle incomplete, salvaged {} event(s)", events.len());
+        let bundle = stegstr_lib::nostr_event::Bundle::new(events);
+        serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string()).code("io")?
+    };
+    print_decoded(text, method, header, json)
+}
+
+fn run_embed(args: &[String], json: bool) -> Result<(), CliError> {
+    let config = stegstr_lib::config::load()?;
+    let mut opts = EmbedArgs::parse(args, &config)?;
+    opts.validate(&config)?;
+    let crypt = opts.encryption.options(opts.cipher, opts.bundle_v2)?;
+    let output_arg = config.output_path(opts.output.ok_or("embed requires -o/--output <out.png>")?).to_string_lossy().into_owned();
+    // Without --format, an output named *.webp gets WebP.
+    opts.dwt.format = opts.format.or_else(|| stegstr_lib::output::OutputFormat::from_path(Path::new(&output_arg))).unwrap_or_default();
+    opts.dwt = opts.dwt.validated()?;
+    let payload_bytes = opts.payload_bytes()?;
+    if json && output_arg == "-" {
+        return Err("--json prints the result on stdout; write the image to a file with -o".into());
+    }
+    if opts.is_decoy() {
+        return run_embed_decoy(opts, payload_bytes, crypt, &mut OutputImage::new(&output_arg), json);
+    }
+    if opts.split {
+        return run_embed_split(opts, payload_bytes, &crypt, Path::new(&output_arg), json);
+    }
+    if opts.method == Some("synth") {
+        return run_embed_synth(opts, payload_bytes, &crypt, &mut OutputImage::new(&output_arg), json);
+    }
+    run_embed_cover(opts, payload_bytes, crypt, &mut OutputImage::new(&output_arg), json)
+}
+
+/// `embed` flags, before the config file fills in what they leave open.
+struct EmbedArgs<'a> {
+    cover: Option<&'a str>,
+    output: Option<&'a str>,
+    payload_str: Option<String>,
+    payload_base64: Option<String>,
+    encryption: Encryption,
+    receipt: bool,
+    receipt_in_image: bool,
+    privkey_hex: Option<String>,
+    target: Option<&'static stegstr_lib::targets::Target>,
+    verify: bool,
+    max_attempts: usize,
+    mask: stegstr_lib::mask::MaskSpec,
+    method: Option<&'static str>,
+    dwt: stegstr_lib::stego::EncodeOptions,
+    quality: bool,
+    format: Option<stegstr_lib::output::OutputFormat>,
+    jpeg: bool,
+    jpeg_quality: Option<u8>,
+    profile: Option<&'static stegstr_lib::profiles::Profile>,
+    mime: Option<String>,
+    filename: Option<String>,
+    split: bool,
+    split_mode: SplitMode,
+    extra_covers: Vec<&'a str>,
+    decoy_payload: Option<String>,
+    decoy_passphrase: Option<&'a str>,
+    cipher: Option<stegstr_lib::stego_crypto::Cipher>,
+    bundle_v2: bool,
+    bind: bool,
+    fiducials: bool,
+    calibration: bool,
+    dot_render: Option<&'a str>,
+    dot_contrast: Option<u8>,
+    rendering: stegstr_lib::stego_dot::Rendering,
+    layout: stegstr_lib::stego_dot::Layout,
+}
+
+impl<'a> EmbedArgs<'a> {
+    /// Flags as given; dwt starts from the config file's redundancy.
+    fn parse(args: &'a [String], config: &stegstr_lib::config::Settings) -> Result<Self, CliError> {
+        let mut dwt = stegstr_lib::stego::EncodeOptions::default();
+        dwt.redundancy = config.redundancy.unwrap_or(dwt.redundancy);
+        let mut o = EmbedArgs {
+            cover: None,
+            output: None,
+            payload_str: None,
+            payload_base64: None,
+            encryption: Encryption::None,
+            receipt: false,
+            receipt_in_image: false,
+            privkey_hex: None,
+            target: None,
+            verify: true,
+            max_attempts: stegstr_lib::targets::max_attempts(),
+            mask: stegstr_lib::mask::MaskSpec::default(),
+            method: None,
+            dwt,
+            quality: false,
+            format: None,
+            jpeg: false,
+            jpeg_quality: None,
+            profile: None,
+            mime: None,
+            filename: None,
+            split: false,
+            split_mode: SplitMode::All,
+            extra_covers: Vec::new(),
+            decoy_payload: None,
+            decoy_passphrase: None,
+            cipher: None,
+            bundle_v2: false,
+            bind: false,
+            fiducials: false,
+            calibration: false,
+            dot_render: None,
+            dot_contrast: None,
+            rendering: stegstr_lib::stego_dot::Rendering::Stamp,
+            layout: stegstr_lib::stego_dot::Layout::default(),
+        };
+
+        let mut i = 0;
+        while i < args.len() {
+            let a = &args[i];
+            if a == "-o" || a == "--output" {
+                i += 1;
+                o.output = Some(args.get(i).ok_or("missing value for -o/--output")?);
+            } else if a == "--payload" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --payload")?;
+                if v.starts_with('@') {
+                    let path = v.trim_start_matches('@');
+                    o.payload_str = Some(fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e)).code("io")?);
+                    o.filename = Path::new(path).file_name().map(|n| n.to_string_lossy().to_string());
+                } else {
+                    o.payload_str = Some(v.clone());
+                }
+            } else if a == "--decoy-payload" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --decoy-payload")?;
+                o.decoy_payload = Some(match v.strip_prefix('@') {
+                    Some(path) => fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e)).code("io")?,
+                    None => v.clone(),
+                });
+            } else if a == "--decoy-passphrase" {
+                i += 1;
+                o.decoy_passphrase = Some(args.get(i).ok_or("missing value for --decoy-passphrase")?);
+            } else if a == "--mime" {
+                i += 1;
+                o.mime = Some(args.get(i).ok_or("missing value for --mime")?.clone());
+            } else if a == "--payload-base64" {
+                i += 1;
+                o.payload_base64 = Some(args.get(i).ok_or("missing value for --payload-base64")?.clone());
+            } else if a == "--encrypt" {
+                o.encryption = o.encryption.set(Encryption::App)?;
+            } else if a == "--passphrase" {
+                i += 1;
+                o.encryption = o.encryption.set(Encryption::Passphrase(args.get(i).ok_or("missing value for --passphrase")?.clone()))?;
+            } else if a == "--recipient" {
+                i += 1;
+                o.encryption = o.encryption.set(Encryption::Recipient(args.get(i).ok_or("missing value for --recipient")?.clone()))?;
+            } else if a == "--cipher" {
+                i += 1;
+                o.cipher = Some(stegstr_lib::stego_crypto::Cipher::parse(args.get(i).ok_or("missing value for --cipher")?)?);
+            } else if a == "--bundle-v2" {
+                o.bundle_v2 = true;
+            } else if a == "--bind" {
+                o.bind = true;
+            } else if a == "--fiducials" {
+                o.fiducials = true;
+            } else if a == "--calibration" {
+                o.calibration = true;
+            } else if a == "--dot-render" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --dot-render")?;
+                if !["stamp", "luma", "blue"].contains(&v.as_str()) {
+                    return Err(format!("unknown --dot-render {:?} (stamp, luma or blue)", v).into());
+                }
+                o.dot_render = Some(v);
+            } else if a == "--dot-contrast" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --dot-contrast")?;
+                o.dot_contrast = Some(v.parse().map_err(|_| format!("invalid --dot-contrast {:?}", v))?);
+            } else if a == "--dot-step" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --dot-step")?;
+                o.layout.step = v.parse().map_err(|_| format!("invalid --dot-step {:?}", v))?;
+            } else if a == "--dot-repeat" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --dot-repeat")?;
+                o.layout.repeat = v.parse().map_err(|_| format!("invalid --dot-repeat {:?}", v))?;
+            } else if a == "--receipt" {
+                o.receipt = true;
+            } else if a == "--mask" {
+                i += 1;
+                o.mask.rects.push(parse_region(args.get(i).ok_or("missing value for --mask")?)?);
+            } else if a == "--mask-image" {
+                i += 1;
+                o.mask.bitmap_path = Some(args.get(i).ok_or("missing value for --mask-image")?.clone());
+            } else if a == "--strip" || a == "--strip-metadata" {
+                o.dwt.strip_metadata = true;
+            } else if a == "--no-verify" {
+                o.verify = false;
+            } else if a == "--quality" {
+                o.quality = true;
+            } else if a == "--format" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --format")?;
+                if v.eq_ignore_ascii_case("jpeg") || v.eq_ignore_ascii_case("jpg") {
+                    o.jpeg = true;
+                } else {
+                    o.format = Some(stegstr_lib::output::OutputFormat::parse(v)?);
+                }
+            } else if a == "--jpeg-quality" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --jpeg-quality")?;
+                o.jpeg_quality = Some(v.parse().map_err(|_| format!("invalid --jpeg-quality {:?}", v))?);
+            } else if a == "--max-attempts" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --max-attempts")?;
+                o.max_attempts = v.parse().map_err(|_| format!("invalid --max-attempts {:?}", v))?;
+            } else if a == "--receipt-in-image" {
+                o.receipt = true;
+                o.receipt_in_image = true;
+            } else if a == "--privkey-hex" || a == "--privkey" {
+                i += 1;
+                o.privkey_hex = Some(args.get(i).ok_or("missing value for --privkey-hex")?.clone());
+            } else if a == "--target" {
+                i += 1;
+                o.target = Some(stegstr_lib::targets::find(args.get(i).ok_or("missing value for --target")?)?);
+            } else if a == "--profile" {
+                i += 1;
+                o.profile = Some(stegstr_lib::profiles::find(args.get(i).ok_or("missing value for --profile")?)?);
+            } else if a == "--parity" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --parity")?;
+                o.dwt.parity = v.parse().map_err(|_| format!("invalid --parity {:?}", v))?;
+            } else if a == "--tile-size" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --tile-size")?;
+                o.dwt.tile_size = v.parse().map_err(|_| format!("invalid --tile-size {:?}", v))?;
+            } else if a == "--redundancy" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --redundancy")?;
+                o.dwt.redundancy = v.parse().map_err(|_| format!("invalid --redundancy {:?}", v))?;
+            } else if a == "--interleave" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --interleave")?;
+                o.dwt.interleave = v.parse().map_err(|_| format!("invalid --interleave {:?}", v))?;
+            } else if a == "--spread" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --spread")?;
+                o.dwt.spread = v.parse().map_err(|_| format!("invalid --spread {:?}", v))?;
+            } else if a == "--strength" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --strength")?;
+                o.dwt.strength = v.parse().map_err(|_| format!("invalid --strength {:?}", v))?;
+            } else if a == "--channels" {
+                i += 1;
+                let v = args.get(i).ok_or("missing value for --channels")?;
+                o.dwt.channels = v.parse().map_err(|_| format!("invalid --channels {:?}", v))?;
+            } else if a == "--stego-key" {
+                i += 1;
+                o.dwt.key = Some(stegstr_lib::stego_key::StegoKey::from_password(args.get(i).ok_or("missing value for --stego-key")?)?);
+            } else if a == "--method" {
+                i += 1;
+                o.method = Some(embed_method(args.get(i).ok_or("missing value for --method")?)?);
+            } else if a == "--split" {
+                o.split = true;
+            } else if a == "--fountain" || a == "--shamir" {
+                i += 1;
+                let v = args.get(i).ok_or_else(|| format!("missing value for {}", a))?;
+                let n = v.parse().map_err(|_| format!("invalid {} {:?}", a, v))?;
+                if !matches!(o.split_mode, SplitMode::All) {
+                    return Err("--fountain and --shamir are exclusive".into());
+                }
+                o.split_mode = if a == "--fountain" { SplitMode::Fountain(n) } else { SplitMode::Shamir(n) };
+            } else if (a == "-" || !a.starts_with('-')) && o.cover.is_none() {
+                o.cover = Some(a);
+            } else if !a.starts_with('-') {
+                o.extra_covers.push(a);
+            }
+            i += 1;
+        }
+        Ok(o)
+    }
+
+    /// Checks every embed path shares: flags that exclude each other, codecs that flags imply,
+    /// then the config file's codec and stego key where no flag picks them.
+    fn validate(&mut self, config: &stegstr_lib::config::Settings) -> Result<(), CliError> {
+        if !self.split && !self.extra_covers.is_empty() {
+            return Err("embed takes one cover; --split spreads the payload over several".into());
+        }
+        if !matches!(self.split_mode, SplitMode::All) && !self.split {
+            return Err("--fountain and --shamir apply to --split".into());
+        }
+        if self.jpeg_quality.is_some() && !self.jpeg {
+            return Err("--jpeg-quality applies to --format jpeg".into());
+        }
+        if self.jpeg {
+            if self.method.is_some_and(|m| m != "qim") || self.target.is_some() || !self.mask.is_empty() || self.dwt.key.is_some() {
+                return Err("--format jpeg writes with qim; it does not take another --method, --target, masks or --stego-key".into());
+            }
+            self.method = Some("qim");
+            self.jpeg_quality = Some(self.jpeg_quality.unwrap_or(stegstr_lib::stego_qim::DEFAULT_QUALITY));
+        }
+        if self.profile.is_some() && (self.method.is_some() || self.target.is_some() || self.format.is_some()) {
+            return Err("--profile picks its own codec and format; it does not take --method, --target or --format".into());
+        }
+        if self.method.is_some() && self.target.is_some() {
+            return Err("--method and --target are exclusive (a target picks its own codecs)".into());
+        }
+        if self.fiducials && self.calibration {
+            return Err("use either --fiducials or --calibration".into());
+        }
+        let contrast = self.dot_contrast.unwrap_or(stegstr_lib::stego_dot::DEFAULT_CONTRAST);
+        self.rendering = match self.dot_render {
+            Some("luma") => stegstr_lib::stego_dot::Rendering::Luma { contrast },
+            Some("blue") => stegstr_lib::stego_dot::Rendering::Blue { contrast },
+            _ if self.dot_contrast.is_some() => return Err("--dot-contrast needs --dot-render luma or blue".into()),
+            _ => stegstr_lib::stego_dot::Rendering::Stamp,
+        };
+        if self.fiducials || self.calibration || self.dot_render.is_some() || self.layout != stegstr_lib::stego_dot::Layout::default() {
+            if self.method.is_some_and(|m| m != "dot") || self.target.is_some() || self.profile.is_some() || self.jpeg || self.split {
+                return Err("--fiducials, --calibration, --dot-render, --dot-step and --dot-repeat write with dot; they do not take another --method, --target, --profile, --format jpeg or --split".into());
+            }
+            self.method = Some("dot");
+        }
+
+        // The config file fills in the codec and stego key where no flag picks them.
+        let decoy = self.is_decoy();
+        if self.method.is_none() && self.profile.is_none() && self.target.is_none() && !decoy {
+            self.method = config.method.as_deref().map(embed_method).transpose()?;
+        }
+        if self.dwt.key.is_none() && self.profile.is_none() && self.target.is_none() && !decoy && matches!(self.method, None | Some("dwt") | Some("dot")) {
+            self.dwt.key = config.stego_key_password()?.as_deref().map(stegstr_lib::stego_key::StegoKey::from_password).transpose()?;
+        }
+
+        if self.bind && (matches!(self.encryption, Encryption::None) || self.split || self.jpeg || self.profile.is_some() || self.method.is_some_and(|m| m == "qim" || m == "synth")) {
+            return Err("--bind needs an encryption option and a codec that keeps the cover size; not --split, qim, synth, --format jpeg or --profile".into());
+        }
+        Ok(())
+    }
+
+    fn is_decoy(&self) -> bool {
+        self.decoy_payload.is_some() || self.decoy_passphrase.is_some()
+    }
+
+    /// The --payload or --payload-base64 bytes, taken out of the options.
+    fn payload_bytes(&mut self) -> Result<Vec<u8>, CliError> {
+        if let Some(b64) = self.payload_base64.take() {
+            return Ok(base64::engine::general_purpose::STANDARD.decode(b64.trim()).map_err(|e| e.to_string())?);
+        }
+        match self.payload_str.take() {
+            Some(s) => Ok(s.into_bytes()),
+            None => Err("embed requires --payload <string|@file> or --payload-base64 <b64>".into()),
+        }
+    }
+
+    /// `payload` encrypted as asked and behind its payload header.
+    fn seal(&self, payload: Vec<u8>, crypt: &stegstr_lib::stego_crypto::EncryptOptions) -> Result<Vec<u8>, CliError> {
+        let payload = self.encryption.apply(payload, crypt)?;
+        Ok(self.encryption.header(&payload, self.mime.clone(), self.filename.clone()).write(&payload)?)
+    }
+
+    fn settings(&self) -> EmbedSettings<'_> {
+        EmbedSettings {
+            method: self.method,
+            target: self.target,
+            mask: (!self.mask.is_empty()).then_some(&self.mask),
+            dwt: &self.dwt,
+            verify: self.verify,
+            max_attempts: self.max_attempts,
+            jpeg_quality: self.jpeg_quality,
+            profile: self.profile,
+            fiducials: self.fiducials,
+            calibration: self.calibration,
+            rendering: self.rendering,
+            layout: self.layout,
+        }
+    }
+}
+
+/// `embed` of one cover with dwt, dot, qim, a target or a profile (EmbedSettings::embed), with
+/// receipts, --bind and --quality.
+fn run_embed_cover(
+    opts: EmbedArgs,
+    mut payload_bytes: Vec<u8>,
+    mut crypt: stegstr_lib::stego_crypto::EncryptOptions,
+    output: &mut OutputImage,
+    json: bool,
+) -> Result<(), CliError> {
+    let cover = InputImage::open(opts.cover.ok_or("embed requires <cover.png>")?).code("io")?;
+    let cover_path = cover.path.as_path();
+    let receipt_sk = if opts.receipt {
+        let hex = opts.privkey_hex.as_deref().ok_or("--receipt requires --privkey-hex <hex>")?;
+        Some(SecretKeyGuard(stegstr_lib::nostr_codes::parse_secret_key(hex)?))
+    } else {
+        None
+    };
+
+    let mut receipt_event = None;
+    if let (true, Some(sk)) = (opts.receipt_in_image, &receipt_sk) {
+        // The receipt hashes the bundle as given, then rides along inside it.
+        let method = opts.method.unwrap_or(opts.target.map_or("dwt", |t| t.methods[0]));
+        let (bundle, ev) = stegstr_lib::receipts::embed_in_bundle(&payload_bytes, cover_path, method, sk)?;
+        stegstr_lib::receipts::append_receipt(&ev).code("io")?;
+        eprintln!("Receipt {} (embedded)", ev.id);
+        receipt_event = Some(ev);
+        payload_bytes = bundle;
+    }
+
+    if opts.bind {
+        crypt.binding = Some(image_binding(cover_path).code("io")?);
+    }
+    let payload_bytes = opts.seal(payload_bytes, &crypt)?;
+
+    let embedded = opts.settings().embed(cover_path, &payload_bytes).code("embed")?;
+    for failed in &embedded.failed_attempts {
+        eprintln!("Retrying after failed read-back: {}", failed);
+    }
+    if let Some(binding) = crypt.binding {
+        let img = image::load_from_memory(&embedded.bytes).map_err(|e| e.to_string()).code("embed")?;
+        let out = stegstr_lib::stego_crypto::ImageBinding { width: img.width(), height: img.height() };
+        if !binding.matches(out) {
+            return Err(format!("--bind: {} changed the image size, so the payload would not decrypt", embedded.method)).code("embed");
+        }
+    }
+    if let Some(s) = &embedded.settings {
+        eprintln!("Verified read-back with {}", s);
+    }
+    let method = embedded.method;
+    let quality = match opts.quality {
+        true => Some(stegstr_lib::quality::compare_bytes(cover_path, &embedded.bytes).code("io")?),
+        false => None,
+    };
+    if let Some(q) = &quality {
+        eprintln!("Quality: {}", q.summary());
+    }
+    let output_path = output.write(&embedded.bytes).code("io")?;
+    let capacity = match opts.profile {
+        Some(p) => p.capacity(cover_path, &payload_bytes).ok(),
+        None => stegstr_lib::targets::payload_capacity(cover_path, method, opts.target, &payload_bytes).ok(),
+    };
+    match &capacity {
+        Some(cap) => eprintln!("Wrote {} ({}, {})", output.name(), method, cap.summary()),
+        None => eprintln!("Wrote {} ({})", output.name(), method),
+    }
+
+    if let (false, Some(sk)) = (opts.receipt_in_image, &receipt_sk) {
+        let ev = stegstr_lib::receipts::record_embed(&payload_bytes, cover_path, &output_path, method, sk).code("io")?;
+        eprintln!("Receipt {}", ev.id);
+        receipt_event = Some(ev);
+    }
+    if !json {
+        return output.finish().code("io");
+    }
+    print_json(&stegstr_lib::StegoEncodeResult {
+        ok: true,
+        path: Some(output.name().to_string()),
+        receipt: receipt_event,
+        verified: opts.verify.then_some(true),
+        method: Some(method.to_string()),
+        settings: embedded.settings,
+        failed_attempts: embedded.failed_attempts,
+        capacity,
+        quality,
+        ..Default::default()
+    })
+}
+
+/// `embed --split`: one fragment of `payload` per cover, written to `out_dir` as batch embed does.
+/// With --fountain N the parts are fountain-coded so that any N images suffice; with --shamir K
+/// each image holds a Shamir share and any K rebuild the payload.
+fn run_embed_split(
+    opts: EmbedArgs,
+    payload: Vec<u8>,
+    crypt: &stegstr_lib::stego_crypto::EncryptOptions,
+    out_dir: &Path,
+    json: bool,
+) -> Result<(), CliError> {
+    if opts.receipt || opts.quality || !opts.mask.is_empty() || opts.method == Some("synth") || out_dir == Path::new("-") {
+        return Err("--split does not take receipts, --quality, masks, synth or -o -".into());
+    }
+    let payload = opts.seal(payload, crypt)?;
+    let covers: Vec<std::path::PathBuf> = opts.cover.into_iter().chain(opts.extra_covers.iter().copied()).map(std::path::PathBuf::from).collect();
+    if covers.iter().enumerate().any(|(i, c)| covers[..i].contains(c)) {
+        return Err("--split needs distinct covers".into());
+    }
+    let (fragments, needed) = match opts.split_mode {
+        SplitMode::All => (stegstr_lib::fragment::split(&payload, covers.len())?, "all".to_string()),
+        SplitMode::Fountain(n) => (stegstr_lib::stego_fountain::encode(&payload, covers.len(), n)?, format!("any {}", n)),
+        SplitMode::Shamir(k) => (stegstr_lib::stego_shamir::split(&payload, covers.len(), k)?, format!("any {}", k)),
+    };
+    eprintln!(
+        "Split {} into {} fragments (bundle {}); {} needed to join",
+        stegstr_lib::units::format_bytes(payload.len() as u64),
+        fragments.len(),
+        stegstr_lib::fragment::bundle_id(&payload),
+        needed
+    );
+    let settings = opts.settings();
+    let summary = stegstr_lib::batch::embed_all(&covers, out_dir, 0, OVERWRITE.load(std::sync::atomic::Ordering::Relaxed), |cover| {
+        let i = covers.iter().position(|c| c == cover).ok_or("unknown cover")?;
+        settings.embed(cover, &fragments[i]).map(|e| (e.bytes, e.method.to_string()))
+    })
+    .code("io")?;
+    report_batch(&summary, json, false).map_err(|message| CliError {
+        code: "embed",
+        message,
+        reported: json,
+    })
+}
+
+/// How embed --split spreads the payload.
+#[derive(Clone, Copy)]
+enum SplitMode {
+    /// One fragment per image; every image is needed.
+    All,
+    /// --fountain N: RaptorQ parts, any N images rebuild the payload.
+    Fountain(usize),
+    /// --shamir K: Shamir shares, any K images rebuild it and fewer reveal nothing.
+    Shamir(usize),
+}
+
+/// Embed-side encryption: --encrypt (app key), --passphrase or --recipient; at most one.
+enum Encryption {
+    None,
+    App,
+    Passphrase(String),
+    Recipient(String),
+}
+
+impl Encryption {
+    fn set(self, other: Encryption) -> Result<Encryption, String> {
+        match self {
+            Encryption::None => Ok(other),
+            _ => Err("--encrypt, --passphrase and --recipient are exclusive".to_string()),
+        }
+    }
+
+    /// Options with --cipher, which only applies when encrypting (AES-256-GCM when not given), and
+    /// --bundle-v2, which applies to every payload (bundle v1 JSON when not given).
+    fn options(&self, cipher: Option<stegstr_lib::stego_crypto::Cipher>, bundle_v2: bool) -> Result<stegstr_lib::stego_crypto::EncryptOptions, String> {
+        use stegstr_lib::bundle::BundleFormat;
+        match (self, cipher) {
+            (Encryption::None, Some(_)) => Err("--cipher applies to --encrypt, --passphrase or --recipient".to_string()),
+            (_, cipher) => Ok(stegstr_lib::stego_crypto::EncryptOptions {
+                cipher: cipher.unwrap_or_default(),
+                binding: None,
+                bundle: if bundle_v2 { BundleFormat::V2 } else { BundleFormat::V1 },
+            }),
+        }
+    }
+
+    fn apply(&self, payload_bytes: Vec<u8>, opts: &stegstr_lib::stego_crypto::EncryptOptions) -> Result<Vec<u8>, String> {
+        use stegstr_lib::stego_crypto;
+        let text = |b: Vec<u8>| String::from_utf8(b).map_err(|e| e.to_string());
+        match self {
+            // Unencrypted bundles take the same bundle::pack step the encrypt functions do.
+            Encryption::None => Ok(stegstr_lib::bundle::pack(&payload_bytes, opts.bundle)),
+            Encryption::App => stego_crypto::encrypt_app_with(&text(payload_bytes)?, opts),
+            Encryption::Passphrase(p) => stego_crypto::encrypt_password_with(&text(payload_bytes)?, p, opts),
+            Encryption::Recipient(pk) => stego_crypto::encrypt_for_pubkey_with(&text(payload_bytes)?, pk, opts),
+        }
+        .map_err(String::from)
+    }
+
+    /// Payload header for the (already encrypted) `payload`: `mime` or the sniffed type, and the
+    /// payload file name unless encrypting, which would otherwise leak it in the clear.
+    fn header(&self, payload: &[u8], mime: Option<String>, filename: Option<String>) -> PayloadHeader {
+        let sniffed = PayloadHeader::sniff(payload);
+        PayloadHeader {
+            mime_type: mime.unwrap_or(sniffed.mime_type),
+            filename: filename.filter(|_| matches!(self, Encryption::None)),
+            ..sniffed
+        }
+    }
+}
+
+fn embed_method(name: &str) -> Result<&'static str, String> {
+    Ok(match name {
+        "dwt" => "dwt",
+        "dot" => "dot",
+        "qim" => "qim",
+        "synth" => "synth",
+        other => match stegstr_lib::external::find(other) {
+            Some(codec) => codec.name.as_str(),
+            None => return Err(format!("unknown method {:?} (dwt, dot, qim, synth or a codec from `stegstr-cli codecs`)", other)),
+        },
+    })
+}
+
+/// Codec choice and read-back policy shared by embed and batch embed.
+struct EmbedSettings<'a> {
+    method: Option<&'static str>,
+    target: Option<&'static stegstr_lib::targets::Target>,
+    mask: Option<&'a stegstr_lib::mask::MaskSpec>,
+    dwt: &'a stegstr_lib::stego::EncodeOptions,
+    verify: bool,
+    max_attempts: usize,
+    /// --format jpeg: qim at this JPEG quality, verified through one more recompression.
+    jpeg_quality: Option<u8>,
+    /// --profile: codec and layout for a platform, verified through its channel pipeline.
+    profile: Option<&'static stegstr_lib::profiles::Profile>,
+    /// --fiducials or --calibration: dot with corner markers (over the cover, or in a border) for
+    /// camera photos and screenshots.
+    fiducials: bool,
+    calibration: bool,
+    /// --dot-render: how dot cells are drawn (stamped, or modulating the cover).
+    rendering: stegstr_lib::stego_dot::Rendering,
+    /// --dot-step / --dot-repeat: dot grid pitch and cells per symbol.
+    layout: stegstr_lib::stego_dot::Layout,
+}
+
+/// Encoded image and how it was produced.
+struct Embedded {
+    bytes: Vec<u8>,
+    method: &'static str,
+    /// Settings whose read-back matched; None when verification was skipped.
+    settings: Option<String>,
+    /// Settings tried first whose read-back failed, with the reason.
+    failed_attempts: Vec<String>,
+}
+
+impl EmbedSettings<'_> {
+    fn embed(&self, cover: &Path, payload: &[u8]) -> Result<Embedded, String> {
+        let dwt = Some(self.dwt);
+        if let Some(profile) = self.profile {
+            let e = profile.embed(cover, payload, self.mask, self.dwt.key, self.verify, self.max_attempts)?;
+            return Ok(Embedded {
+                bytes: e.bytes,
+                method: e.method,
+                settings: self.verify.then_some(e.settings),
+                failed_attempts: e.failed_attempts,
+            });
+        }
+        if let Some(quality) = self.jpeg_quality {
+            let bytes = stegstr_lib::stego_qim::encode_with_quality(cover, payload, quality)?;
+            let settings = format!("qim (JPEG quality {})", quality);
+            if self.verify {
+                stegstr_lib::stego_qim::verify(&bytes, payload).map_err(|e| format!("{}: {}", settings, e))?;
+            }
+            return Ok(Embedded {
+                bytes,
+                method: "qim",
+                settings: self.verify.then_some(settings),
+                failed_attempts: Vec::new(),
+            });
+        }
+        let default_layout = self.layout == stegstr_lib::stego_dot::Layout::default();
+        if self.fiducials || self.calibration || self.rendering != stegstr_lib::stego_dot::Rendering::Stamp || !default_layout {
+            let opts = stegstr_lib::stego_dot::EncodeOptions {
+                key: self.dwt.key,
+                format: self.dwt.format,
+                fiducials: self.fiducials,
+                calibration: self.calibration,
+                rendering: self.rendering,
+                layout: self.layout,
+                strip_metadata: self.dwt.strip_metadata,
+                spread: self.dwt.spread,
+                ..Default::default()
+            };
+            let mut details = Vec::new();
+            if self.fiducials {
+                details.push("fiducials".to_string());
+            }
+            if self.calibration {
+                details.push("calibration border".to_string());
+            }
+            match self.rendering {
+                stegstr_lib::stego_dot::Rendering::Luma { contrast } => details.push(format!("luma contrast {}", contrast)),
+                stegstr_lib::stego_dot::Rendering::Blue { contrast } => details.push(format!("blue contrast {}", contrast)),
+                stegstr_lib::stego_dot::Rendering::Stamp => {}
+            }
+            if !default_layout {
+                details.push(format!("step {} px, {} cell(s) per symbol", self.layout.step, self.layout.repeat));
+            }
+            let settings = format!("dot ({})", details.join(", "));
+            let bytes = stegstr_lib::stego_dot::encode_with_options(cover, payload, self.mask, &opts)?;
+            if self.verify && stegstr_lib::stego_dot::decode_bytes(&bytes, self.dwt.key.as_ref())? != payload {
+                return Err(format!("{}: read-back mismatch", settings));
+            }
+            return Ok(Embedded {
+                bytes,
+                method: "dot",
+                settings: self.verify.then_some(settings),
+                failed_attempts: Vec::new(),
+            });
+        }
+        if !self.verify {
+            let (bytes, method) = match (self.target, self.method) {
+                (Some(t), _) => stegstr_lib::targets::embed(cover, payload, Some(t), self.mask, dwt)?,
+                (None, Some(m)) => stegstr_lib::targets::embed_with_methods(cover, payload, &[m], self.mask, dwt)?,
+                (None, None) => (stegstr_lib::stego::encode_with_mask(cover, payload, self.mask, self.dwt)?, "dwt"),
+            };
+            return Ok(Embedded {
+                bytes,
+                method,
+                settings: None,
+                failed_attempts: Vec::new(),
+            });
+        }
+        // Only the requested codec (dwt by default) is verified; a target names its own order.
+        let v = match (self.method, self.target) {
+            (Some(m), _) => stegstr_lib::targets::embed_verified(cover, payload, &[m], self.mask, dwt, self.max_attempts)?,
+            (None, Some(t)) => stegstr_lib::targets::embed_verified_for_target(cover, payload, Some(t), self.mask, dwt, self.max_attempts)?,
+            (None, None) => stegstr_lib::targets::embed_verified(cover, payload, &["dwt"], self.mask, dwt, self.max_attempts)?,
+        };
+        Ok(Embedded {
+            bytes: v.png,
+            method: v.settings.method,
+            settings: Some(v.settings.to_string()),
+            failed_attempts: v.failed_attempts,
+        })
+    }
+}
+
+/// `embed --decoy-payload`: the decoy and real payloads, each encrypted under its passphrase, in
+/// disjoint lanes of one dwt embed keyed by those passphrases.
+fn run_embed_decoy(
+    opts: EmbedArgs,
+    payload_bytes: Vec<u8>,
+    mut crypt: stegstr_lib::stego_crypto::EncryptOptions,
+    output: &mut OutputImage,
+    json: bool,
+) -> Result<(), CliError> {
+    let (Some(decoy), Some(decoy_passphrase), Encryption::Passphrase(passphrase)) = (&opts.decoy_payload, opts.decoy_passphrase, &opts.encryption) else {
+        return Err("a decoy embed needs --decoy-payload, --decoy-passphrase and --passphrase".into());
+    };
+    if opts.split || opts.receipt || !opts.mask.is_empty() || opts.dwt.key.is_some() || opts.target.is_some() || opts.profile.is_some() || opts.method.is_some_and(|m| m != "dwt") || opts.jpeg {
+        return Err("a decoy embed is dwt only and does not take --split, receipts, masks, --stego-key, --target, --profile or --format jpeg".into());
+    }
+    if decoy_passphrase == passphrase.as_str() {
+        return Err("--decoy-passphrase must differ from --passphrase".into());
+    }
+    let cover = InputImage::open(opts.cover.ok_or("embed requires <cover.png>")?).code("io")?;
+    if opts.bind {
+        crypt.binding = Some(image_binding(&cover.path).code("io")?);
+    }
+    let real = opts.encryption.apply(payload_bytes, &crypt)?;
+    let real = opts.encryption.header(&real, opts.mime.clone(), None).write(&real)?;
+    let decoy_encryption = Encryption::Passphrase(decoy_passphrase.to_string());
+    let decoy = decoy_encryption.apply(decoy.clone().into_bytes(), &crypt)?;
+    let decoy = decoy_encryption.header(&decoy, None, None).write(&decoy)?;
+    let decoy_key = stegstr_lib::stego_key::StegoKey::from_password(decoy_passphrase)?;
+    let key = stegstr_lib::stego_key::StegoKey::from_password(passphrase)?;
+
+    let image = stegstr_lib::stego::encode_decoy(&cover.path, &decoy, &decoy_key, &real, &key, &opts.dwt).code("embed")?;
+    if opts.verify {
+        for (expected, key, what) in [(&decoy, &decoy_key, "decoy"), (&real, &key, "hidden")] {
+            match stegstr_lib::stego::decode_bytes_with_key(&image, Some(key)) {
+                Ok(read) if &read == expected => {}
+                _ => return Err(format!("Read-back of the {} payload failed", what)).code("embed"),
+            }
+        }
+        eprintln!("Verified read-back of both payloads");
+    }
+    output.write(&image).code("io")?;
+    eprintln!("Wrote {} (dwt, decoy and hidden payload)", output.name());
+    if !json {
+        return output.finish().code("io");
+    }
+    print_json(&stegstr_lib::StegoEncodeResult {
+        ok: true,
+        path: Some(output.name().to_string()),
+        verified: opts.verify.then_some(true),
+        method: Some("dwt".to_string()),
+        ..Default::default()
+    })
+}
+
+/// `embed --method synth`: no cover; the image is generated from the payload (experimental).
+fn run_embed_synth(
+    opts: EmbedArgs,
+    payload_bytes: Vec<u8>,
+    crypt: &stegstr_lib::stego_crypto::EncryptOptions,
+    output: &mut OutputImage,
+    json: bool,
+) -> Result<(), CliError> {
+    if opts.target.is_some() || !opts.mask.is_empty() || opts.receipt || opts.dwt.key.is_some() || opts.quality || opts.format.is_some() {
+        return Err("--method synth does not take --target, masks, receipts, --stego-key, --quality or --format".into());
+    }
+    let payload_bytes = opts.seal(payload_bytes, crypt)?;
+    eprintln!("Warning: synth (coverless) mode is experimental");
+    let image = stegstr_lib::stego_synth::encode(&payload_bytes).code("embed")?;
+    let output_path = output.write(&image).code("io")?;
+    if opts.verify {
+        stegstr_lib::targets::verify_readback(&output_path, "synth", &payload_bytes).code("embed")?;
+        eprintln!("Verified read-back with synth");
+    }
+    let cap = stegstr_lib::units::CapacityInfo::new(
+        "synth",
+        Some(payload_bytes.len() as u64),
+        stegstr_lib::stego_synth::max_payload_bytes() as u64,
+    );
+    eprintln!("Wrote {} (synth, {})", output.name(), cap.summary());
+    if !json {
+        return output.finish().code("io");
+    }
+    print_json(&stegstr_lib::StegoEncodeResult {
+        ok: true,
+        path: Some(output.name().to_string()),
+        verified: opts.verify.then_some(true),
+        method: Some("synth".to_string()),
+        capacity: Some(cap),
+        ..Default::default()
+    })
+}
+
+/// Set by --overwrite.
+static OVERWRITE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Write an output file atomically; an existing file is an error unless --overwrite was given.
+fn write_output(path: impl AsRef<Path>, bytes: &[u8]) -> Result<(), String> {
+    stegstr_lib::atomic_write::write(path.as_ref(), bytes, OVERWRITE.load(std::sync::atomic::Ordering::Relaxed))
+}
+
+/// Temp file for an image passing through stdin/stdout, named for its format: decoders pick the
+/// format from the extension.
+fn stdio_temp_path(tag: &str, bytes: &[u8]) -> std::path::PathBuf {
+    let ext = image::guess_format(bytes)
+        .ok()
+        .and_then(|f| f.extensions_str().first().copied())
+        .unwrap_or("png");
+    std::env::temp_dir().join(format!("stegstr-{}-{}-{:08x}.{}", tag, std::process::id(), rand::random::<u32>(), ext))
+}
+
+/// Image argument that may be `-` for stdin. Codecs read files, so stdin is spooled to a temp
+/// file, removed on drop.
+struct InputImage {
+    path: std::path::PathBuf,
+    temp: bool,
+}
+
+impl InputImage {
+    fn open(arg: &str) -> Result<Self, String> {
+        if arg != "-" {
+            return Ok(InputImage {
+                path: arg.into(),
+                temp: false,
+            });
+        }
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes).map_err(|e| format!("stdin: {}", e))?;
+        if bytes.is_empty() {
+            return Err("no image data on stdin".to_string());
+        }
+        let path = stdio_temp_path("stdin", &bytes);
+        stegstr_lib::atomic_write::write(&path, &bytes, false)?;
+        Ok(InputImage { path, temp: true })
+    }
+}
+
+impl Drop for InputImage {
+    fn drop(&mut self) {
+        if self.temp {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Output argument that may be `-` for stdout. The image goes to a temp file first so read-back
+/// and receipts can open it, then `finish` streams it out; nothing reaches stdout on failure.
+struct OutputImage {
+    arg: String,
+    temp: Option<std::path::PathBuf>,
+}
+
+impl OutputImage {
+    fn new(arg: &str) -> Self {
+        OutputImage {
+            arg: arg.to_string(),
+            temp: None,
+        }
+    }
+
+    fn name(&self) -> &str {
+        if self.arg == "-" {
+            "stdout"
+        } else {
+            &self.arg
+        }
+    }
+
+    /// Write the encoded image; returns the file it can be read back from.
+    fn write(&mut self, bytes: &[u8]) -> Result<std::path::PathBuf, String> {
+        let path = if self.arg == "-" {
+            let p = stdio_temp_path("stdout", bytes);
+            self.temp = Some(p.clone());
+            p
+        } else {
+            self.arg.clone().into()
+        };
+        write_output(&path, bytes)?;
+        Ok(path)
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        let Some(temp) = &self.temp else { return Ok(()) };
+        let bytes = fs::read(temp).map_err(|e| e.to_string())?;
+        let mut out = io::stdout().lock();
+        out.write_all(&bytes).and_then(|_| out.flush()).map_err(|e| format!("stdout: {}", e))
+    }
+}
+
+impl Drop for OutputImage {
+    fn drop(&mut self) {
+        if let Some(temp) = &self.temp {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+/// `post --json` (and profile, contacts add/remove) result. The bundle is included when it was
+/// not written to --output.
+#[derive(serde::Serialize)]
+struct PostResult {
+    ok: bool,
+    event_id: String,
+    pubkey: String,
+    npub: String,
+    path: Option<String>,
+    bundle: Option<stegstr_lib::nostr_event::Bundle>,
+    relays: Vec<stegstr_lib::relay::RelayOutcome>,
+    error: Option<String>,
+    code: Option<&'static str>,
+}
+
+/// --privkey-hex or --key, --output and --relay, shared by post, profile, contacts and dm.
+#[derive(Default)]
+struct EventArgs {
+    privkey_hex: Option<Zeroizing<String>>,
+    /// Name of a key in the OS keychain (key import).
+    key_name: Option<String>,
+    output_path: Option<String>,
+    relays: Vec<String>,
+}
+
+impl EventArgs {
+    /// Take `args[*i]` (and its value) if it is one of ours.
+    fn parse(&mut self, args: &[String], i: &mut usize) -> Result<bool, String> {
+        let a = &args[*i];
+        if a == "--privkey-hex" || a == "--privkey" {
+            *i += 1;
+            self.privkey_hex = Some(Zeroizing::new(args.get(*i).ok_or("missing value for --privkey-hex")?.clone()));
+        } else if a == "--key" {
+            *i += 1;
+            self.key_name = Some(args.get(*i).ok_or("missing value for --key")?.clone());
+        } else if a == "--output" {
+            *i += 1;
+            self.output_path = Some(args.get(*i).ok_or("missing value for --output")?.clone());
+        } else if a == "--relay" {
+            *i += 1;
+            self.relays.push(args.get(*i).ok_or("missing value for --relay")?.clone());
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// The signing key: from the keychain with --key, else --privkey-hex, else a new one.
+    fn secret_key(&self) -> Result<SecretKeyGuard, String> {
+        match (&self.key_name, &self.privkey_hex) {
+            (Some(_), Some(_)) => Err("use either --key or --privkey-hex".to_string()),
+            (Some(name), None) => stegstr_lib::keystore::load_key(name).map(SecretKeyGuard),
+            (None, privkey_hex) => secret_key_or_generate(privkey_hex.as_deref().map(String::as_str)),
+        }
+    }
+}
+
+fn run_post(args: &[String], json: bool) -> Result<(), CliError> {
+    let mut content: Option<String> = None;
+    let mut event_args = EventArgs::default();
+    let mut tags = stegstr_lib::nostr_event::NoteTags::default();
+    let mut kind = 1u32;
+    let mut i = 0;
+    while i < args.len() {
+        if event_args.parse(args, &mut i)? {
+            i += 1;
+            continue;
+        }
+        let a = &args[i];
+        if a == "--reply-to" || a == "--root" || a == "--mention" || a == "--tag" || a == "--kind" {
+            i += 1;
+            let value = args.get(i).ok_or_else(|| format!("missing value for {}", a))?;
+            match a.as_str() {
+                "--reply-to" => tags.reply_to = Some(stegstr_lib::nostr_codes::parse_event_pointer(value)?),
+                "--root" => tags.root = Some(stegstr_lib::nostr_codes::parse_event_pointer(value)?),
+                "--mention" => tags.mentions.push(stegstr_lib::nostr_codes::parse_pubkey(value)?),
+                "--tag" => {
+                    let (name, tag_value) = value
+                        .split_once('=')
+                        .filter(|(name, _)| !name.is_empty())
+                        .ok_or_else(|| format!("--tag expects NAME=VALUE (e.g. t=art), got {:?}", value))?;
+                    tags.extra.push(vec![name.to_string(), tag_value.to_string()]);
+                }
+                _ => kind = value.parse().map_err(|_| format!("invalid --kind {:?}", value))?,
+            }
+        } else if !a.starts_with('-') && content.is_none() {
+            content = Some(a.clone());
+        }
+        i += 1;
+    }
+    let content = content.ok_or("post requires content (e.g. post \"Hello world\")")?;
+    if tags.root.is_some() && tags.reply_to.is_none() {
+        return Err("--root requires --reply-to (the event this note answers)".into());
+    }
+    let sk = event_args.secret_key()?;
+    let event = stegstr_lib::nostr_event::create_note_event(kind, &content, &tags, &sk)?;
+    emit_event(event, &event_args, "note", json)
+}
+
+/// `profile --name --about --picture`: sign a kind 0 event and output it as post does.
+fn run_profile(args: &[String], json: bool) -> Result<(), CliError> {
+    let mut metadata = stegstr_lib::nostr_event::ProfileMetadata::default();
+    let mut event_args = EventArgs::default();
+    let mut i = 0;
+    while i < args.len() {
+        if event_args.parse(args, &mut i)? {
+            i += 1;
+            continue;
+        }
+        let a = &args[i];
+        let field = match a.as_str() {
+            "--name" => &mut metadata.name,
+            "--about" => &mut metadata.about,
+            "--picture" => &mut metadata.picture,
+            _ => return Err(format!("unexpected argument {:?}", a).into()),
+        };
+        i += 1;
+        *field = Some(args.get(i).ok_or_else(|| format!("missing value for {}", a))?.clone());
+        i += 1;
+    }
+    if metadata == Default::default() {
+        return Err("profile requires --name, --about or --picture".into());
+    }
+    let sk = event_args.secret_key()?;
+    let event = stegstr_lib::nostr_event::create_kind0_event(&metadata, &sk)?;
+    emit_event(event, &event_args, "profile", json)
+}
+
+/// Newest kind 3 event in the bundle at `path`, by `author` (hex) when given.
+fn latest_contact_list(path: &str, author: Option<&str>) -> Result<Option<stegstr_lib::nostr_event::NostrEvent>, CliError> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e)).code("io")?;
+    let bundle: stegstr_lib::nostr_event::Bundle = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(bundle
+        .events
+        .into_iter()
+        .filter(|e| e.kind == 3 && author.is_none_or(|a| e.pubkey == a))
+        .max_by_key(|e| e.created_at))
+}
+
+/// `contacts add|remove <npub|hex>... [--from bundle.json]` signs the updated kind 3 list;
+/// `contacts list --from bundle.json` prints the newest list in the bundle.
+fn run_contacts(args: &[String], json: bool) -> Result<(), CliError> {
+    let action = args.first().map(String::as_str).ok_or("contacts requires add, remove or list")?;
+    let mut keys: Vec<String> = Vec::new();
+    let mut from: Option<&str> = None;
+    let mut event_args = EventArgs::default();
+    let mut i = 1;
+    while i < args.len() {
+        if event_args.parse(args, &mut i)? {
+        } else if args[i] == "--from" {
+            i += 1;
+            from = Some(args.get(i).ok_or("missing value for --from")?);
+        } else if !args[i].starts_with('-') {
+            keys.push(stegstr_lib::nostr_codes::parse_pubkey(&args[i])?);
+        } else {
+            return Err(format!("unexpected argument {:?}", args[i]).into());
+        }
+        i += 1;
+    }
+    if action == "list" {
+        let from = from.ok_or("contacts list requires --from <bundle.json>")?;
+        let event = latest_contact_list(from, None)?.ok_or_else(|| format!("{} holds no contact list", from))?;
+        let contacts = stegstr_lib::nostr_event::contacts_from_event(&event);
+        if json {
+            return print_json(&serde_json::json!({ "ok": true, "pubkey": event.pubkey, "contacts": contacts }));
+        }
+        eprintln!("Contact list of {} ({} contacts)", stegstr_lib::nostr_codes::npub_from_hex(&event.pubkey)?, contacts.len());
+        for c in &contacts {
+            let npub = stegstr_lib::nostr_codes::npub_from_hex(&c.pubkey)?;
+            println!("{}\t{}", npub, c.petname.as_deref().unwrap_or(""));
+        }
+        return Ok(());
+    }
+    if action != "add" && action != "remove" {
+        return Err(format!("unknown contacts action {:?} (add, remove or list)", action).into());
+    }
+    if keys.is_empty() {
+        return Err(format!("contacts {} requires at least one npub or hex pubkey", action).into());
+    }
+    let sk = event_args.secret_key()?;
+    let author = stegstr_lib::nostr_event::pubkey_hex(&sk);
+    let mut contacts = match from {
+        Some(path) => match latest_contact_list(path, Some(&author))? {
+            Some(event) => stegstr_lib::nostr_event::contacts_from_event(&event),
+            None => return Err(format!("{} holds no contact list by this key", path).into()),
+        },
+        None => Vec::new(),
+    };
+    if action == "add" {
+        for key in keys {
+            if !contacts.iter().any(|c| c.pubkey == key) {
+                contacts.push(stegstr_lib::nostr_event::Contact {
+                    pubkey: key,
+                    relay: None,
+                    petname: None,
+                });
+            }
+        }
+    } else {
+        let before = contacts.len();
+        contacts.retain(|c| !keys.contains(&c.pubkey));
+        if contacts.len() + keys.len() > before {
+            eprintln!("Warning: {} key(s) were not in the list", contacts.len() + keys.len() - before);
+        }
+    }
+    eprintln!("Contact list: {} contacts", contacts.len());
+    let event = stegstr_lib::nostr_event::create_kind3_event(&contacts, &sk)?;
+    emit_event(event, &event_args, "contact list", json)
+}
+
+/// `dm <recipient> "message"` signs an encrypted direct message and outputs or embeds its bundle;
+/// `dm open <bundle.json>` decrypts the direct messages in a bundle with --privkey-hex.
+fn run_dm(args: &[String], json: bool) -> Result<(), CliError> {
+    let mut positional: Vec<&String> = Vec::new();
+    let mut kind = stegstr_lib::dm::KIND_DM;
+    let mut cover: Option<&str> = None;
+    let mut event_args = EventArgs::default();
+    let mut i = 0;
+    while i < args.len() {
+        if event_args.parse(args, &mut i)? {
+        } else if args[i] == "-o" {
+            i += 1;
+            event_args.output_path = Some(args.get(i).ok_or("missing value for -o/--output")?.clone());
+        } else if args[i] == "--kind" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --kind")?;
+            kind = v.parse().map_err(|_| format!("invalid --kind {:?}", v))?;
+        } else if args[i] == "--cover" {
+            i += 1;
+            cover = Some(args.get(i).ok_or("missing value for --cover")?);
+        } else if !args[i].starts_with('-') {
+            positional.push(&args[i]);
+        } else {
+            return Err(format!("unexpected argument {:?}", args[i]).into());
+        }
+        i += 1;
+    }
+    if positional.first().is_some_and(|a| *a == "open") {
+        let path = positional.get(1).ok_or("dm open requires a bundle file")?;
+        if event_args.privkey_hex.is_none() && event_args.key_name.is_none() {
+            return Err("dm open requires --key or --privkey-hex".into());
+        }
+        let sk = event_args.secret_key()?;
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e)).code("io")?;
+        let bundle: stegstr_lib::nostr_event::Bundle = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        let messages: Vec<stegstr_lib::dm::DirectMessage> = bundle.events.iter().filter_map(|e| stegstr_lib::dm::open_dm(e, &sk).ok()).collect();
+        if messages.is_empty() {
+            return Err(format!("{} holds no direct messages for this key", path).into());
+        }
+        if json {
+            return print_json(&messages);
+        }
+        for m in &messages {
+            println!("{}\t{}\t{}", m.created_at, stegstr_lib::nostr_codes::npub_from_hex(&m.sender)?, m.content);
+        }
+        return Ok(());
+    }
+    let [recipient, message] = positional[..] else {
+        return Err("dm requires a recipient and a message (e.g. dm npub1... \"hello\")".into());
+    };
+    let sk = event_args.secret_key()?;
+    if let Some(cover) = cover {
+        if !event_args.relays.is_empty() {
+            return Err("--relay does not apply with --cover".into());
+        }
+        let output = event_args.output_path.as_deref().ok_or("dm --cover requires -o/--output <out.png>")?;
+        let req = stegstr_lib::dm::DmRequest {
+            message,
+            recipient,
+            kind,
+            sender_sk: &sk,
+            cover_path: Some(Path::new(cover)),
+            output_path: Some(Path::new(output)),
+            overwrite: OVERWRITE.load(std::sync::atomic::Ordering::Relaxed),
+        };
+        let result = stegstr_lib::dm::send_dm(&req);
+        print_json(&result)?;
+        return match result.error {
+            Some(message) => Err(CliError {
+                code: "embed",
+                message,
+                reported: json,
+            }),
+            None => Ok(()),
+        };
+    }
+    let recipient = stegstr_lib::nostr_codes::parse_pubkey(recipient)?;
+    let event = stegstr_lib::dm::create_dm_event(kind, &recipient, message, &sk)?;
+    if event.kind != kind {
+        let sender = stegstr_lib::nostr_event::pubkey_hex(&sk);
+        eprintln!("Sender: {} (only the recipient sees it; the author below is a one-time key)", stegstr_lib::nostr_codes::npub_from_hex(&sender)?);
+    }
+    emit_event(event, &event_args, "message", json)
+}
+
+/// Bundle a signed event, write it to --output (else stdout, or the --json result) and publish it
+/// to --relay URLs. `what` names the event in messages.
+fn emit_event(event: stegstr_lib::nostr_event::NostrEvent, event_args: &EventArgs, what: &str, json: bool) -> Result<(), CliError> {
+    let output_path = event_args.output_path.as_deref();
+    let npub = stegstr_lib::nostr_codes::npub_from_hex(&event.pubkey)?;
+    eprintln!("Author: {} ({})", npub, event.pubkey);
+    let mut result = PostResult {
+        ok: true,
+        event_id: event.id.clone(),
+        pubkey: event.pubkey.clone(),
+        npub,
+        path: output_path.map(str::to_string),
+        bundle: None,
+        relays: Vec::new(),
+        error: None,
+        code: None,
+    };
+    let bundle = stegstr_lib::nostr_event::Bundle::new(vec![event]);
+    let text = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string()).code("io")?;
+    if let Some(path) = output_path {
+        write_output(path, text.as_bytes()).code("io")?;
+        eprintln!("Wrote {}", path);
+    } else if json {
+        result.bundle = Some(bundle.clone());
+    } else {
+        io::stdout().write_all(text.as_bytes()).map_err(|e| e.to_string()).code("io")?;
+    }
+    if !event_args.relays.is_empty() {
+        let (report, unpublished) = publish_events(&bundle.events, &event_args.relays, stegstr_lib::relay::DEFAULT_TIMEOUT).code("relay")?;
+        for r in report.iter().flat_map(|p| &p.results) {
+            eprintln!("{}", format_relay_result(&bundle.events[0].id, r));
+        }
+        result.relays = report.into_iter().flat_map(|p| p.results).collect();
+        if unpublished > 0 {
+            result.ok = false;
+            result.error = Some(format!("the {} was not accepted by any relay", what));
+            result.code = Some("relay");
+        }
+    }
+    if json {
+        print_json(&result)?;
+    }
+    match result.error {
+        Some(message) => Err(CliError {
+            code: "relay",
+            message,
+            reported: json,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// `key import <name> [--generate]` stores a key (read from stdin) in the OS keychain;
+/// `key list`, `key export <name>` and `key remove <name>` manage stored keys.
+fn run_key(args: &[String]) -> Result<(), String> {
+    let json = args.iter().any(|a| a == "--json");
+    let generate = args.iter().any(|a| a == "--generate");
+    let positional: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    let action = positional.first().map(|a| a.as_str()).ok_or("key requires import, list, export, remove or stego")?;
+    let name = || positional.get(1).map(|n| n.as_str()).ok_or_else(|| format!("key {} requires a key name", action));
+    match action {
+        "import" => {
+            let secret = if generate {
+                hex::encode(secp256k1::SecretKey::new(&mut rand::thread_rng()).secret_bytes())
+            } else {
+                eprintln!("Paste the secret key (nsec or hex), then Enter:");
+                let mut line = String::new();
+                io::stdin().read_line(&mut line).map_err(|e| e.to_string())?;
+                line
+            };
+            let key = stegstr_lib::keystore::store_key(name()?, secret.trim())?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&key).map_err(|e| e.to_string())?);
+            } else {
+                eprintln!("Stored {} ({}) in the keychain", key.name, key.npub);
+            }
+        }
+        "list" => {
+            let keys = stegstr_lib::keystore::list_keys()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&keys).map_err(|e| e.to_string())?);
+            }
+            for k in keys.iter().filter(|_| !json) {
+                println!("{}\t{}", k.name, k.npub);
+            }
+        }
+        "export" => {
+            let sk = SecretKeyGuard(stegstr_lib::keystore::load_key(name()?)?);
+            eprintln!("Warning: this prints the secret key; do not paste it anywhere you would not paste a password");
+            println!("{}", stegstr_lib::nostr_codes::nsec_encode(&sk.secret_bytes())?);
+        }
+        "remove" => {
+            let name = name()?;
+            if !stegstr_lib::keystore::remove_key(name)? {
+                return Err(format!("No key named {:?}", name));
+            }
+            eprintln!("Removed {}", name);
+        }
+        "stego" => {
+            let name = name()?;
+            eprintln!("Type the stego key password, then Enter:");
+            let mut line = String::new();
+            io::stdin().read_line(&mut line).map_err(|e| e.to_string())?;
+            stegstr_lib::keystore::store_stego_key(name, line.trim_end_matches(['\r', '\n']))?;
+            eprintln!("Stored stego key {} in the keychain; set stego_key_ref = {:?} in config.toml to use it", name, name);
+        }
+        other => return Err(format!("unknown key action {:?} (import, list, export, remove or stego)", other)),
+    }
+    Ok(())
+}
+
+/// A secret key that is wiped from memory when dropped.
+struct SecretKeyGuard(secp256k1::SecretKey);
+
+impl Deref for SecretKeyGuard {
+    type Target = secp256k1::SecretKey;
+
+    fn deref(&self) -> &secp256k1::SecretKey {
+        &self.0
+    }
+}
+
+impl Drop for SecretKeyGuard {
+    fn drop(&mut self) {
+        self.0.non_secure_erase();
+    }
+}
+
+fn secret_key_or_generate(privkey_hex: Option<&str>) -> Result<SecretKeyGuard, String> {
+    match privkey_hex {
+        Some(hex) => stegstr_lib::nostr_codes::parse_secret_key(hex).map(SecretKeyGuard),
+        None => Ok(SecretKeyGuard(secp256k1::SecretKey::new(&mut rand::thread_rng()))),
+    }
+}
+
+fn run_send(args: &[String]) -> Result<(), String> {
+    let mut content: Option<String> = None;
+    let mut recipient: Option<&str> = None;
+    let mut cover: Option<&str> = None;
+    let mut output: Option<&str> = None;
+    let mut privkey_hex: Option<Zeroizing<String>> = None;
+    let mut upload = false;
+    let mut receipt = false;
+    let mut receipt_in_image = false;
+    let mut target: Option<&'static stegstr_lib::targets::Target> = None;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--to" {
+            i += 1;
+            recipient = Some(args.get(i).ok_or("missing value for --to")?);
+        } else if a == "--cover" {
+            i += 1;
+            cover = Some(args.get(i).ok_or("missing value for --cover")?);
+        } else if a == "-o" || a == "--output" {
+            i += 1;
+            output = Some(args.get(i).ok_or("missing value for -o/--output")?);
+        } else if a == "--privkey-hex" || a == "--privkey" {
+            i += 1;
+            privkey_hex = Some(Zeroizing::new(args.get(i).ok_or("missing value for --privkey-hex")?.clone()));
+        } else if a == "--upload" {
+            upload = true;
+        } else if a == "--receipt" {
+            receipt = true;
+        } else if a == "--receipt-in-image" {
+            receipt = true;
+            receipt_in_image = true;
+        } else if a == "--target" {
+            i += 1;
+            target = Some(stegstr_lib::targets::find(args.get(i).ok_or("missing value for --target")?)?);
+        } else if !a.starts_with('-') && content.is_none() {
+            content = Some(a.clone());
+        }
+        i += 1;
+    }
+    let content = content.ok_or("send requires content")?;
+    let sk = secret_key_or_generate(privkey_hex.as_deref().map(String::as_str))?;
+    let req = stegstr_lib::send::SendRequest {
+        content: &content,
+        recipient: recipient.ok_or("send requires --to <npub|hex>")?,
+        cover_path: Path::new(cover.ok_or("send requires --cover <cover.png>")?),
+        output_path: Path::new(output.ok_or("send requires -o/--output <out.png>")?),
+        sender_sk: &sk,
+        upload,
+        receipt,
+        target,
+        overwrite: OVERWRITE.load(std::sync::atomic::Ordering::Relaxed),
+        receipt_in_image,
+    };
+    let result = stegstr_lib::send::send(&req);
+    let json = serde_json::to_string_pretty(&result).map_err(|e| e.to_string())?;
+    io::stdout().write_all(json.as_bytes()).map_err(|e| e.to_string())?;
+    match result.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+fn run_capacity(args: &[String]) -> Result<(), String> {
+    let mut image_path: Option<&str> = None;
+    let mut method = "dwt".to_string();
+    let mut target: Option<&'static stegstr_lib::targets::Target> = None;
+    let mut json = false;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--method" {
+            i += 1;
+            method = args.get(i).ok_or("missing value for --method")?.clone();
+        } else if a == "--target" {
+            i += 1;
+            target = Some(stegstr_lib::targets::find(args.get(i).ok_or("missing value for --target")?)?);
+        } else if a == "--json" {
+            json = true;
+        } else if (a == "-" || !a.starts_with('-')) && image_path.is_none() {
+            image_path = Some(a);
+        }
+        i += 1;
+    }
+    let input = InputImage::open(image_path.ok_or("capacity requires <image>")?)?;
+    let info = stegstr_lib::targets::capacity(&input.path, &method, target, None)?;
+    if json {
+        let out = serde_json::to_string_pretty(&info).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    println!("{}", info.capacity.bytes);
+    Ok(())
+}
+
+fn run_simulate(args: &[String]) -> Result<(), String> {
+    use stegstr_lib::{channel, scan};
+    let mut image_path: Option<&str> = None;
+    let mut specs: Vec<&str> = Vec::new();
+    let mut method: Option<&str> = None;
+    let mut stego_key: Option<stegstr_lib::stego_key::StegoKey> = None;
+    let mut keep: Option<&str> = None;
+    let mut json = false;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--transform" {
+            i += 1;
+            specs.push(args.get(i).ok_or("missing value for --transform")?);
+        } else if a == "--method" {
+            i += 1;
+            method = Some(args.get(i).ok_or("missing value for --method")?);
+        } else if a == "--stego-key" {
+            i += 1;
+            stego_key = Some(stegstr_lib::stego_key::StegoKey::from_password(args.get(i).ok_or("missing value for --stego-key")?)?);
+        } else if a == "--keep" {
+            i += 1;
+            keep = Some(args.get(i).ok_or("missing value for --keep")?);
+        } else if a == "--json" {
+            json = true;
+        } else if !a.starts_with('-') && image_path.is_none() {
+            image_path = Some(a);
+        }
+        i += 1;
+    }
+    let image_path = image_path.ok_or("simulate requires <stego image>")?;
+    if specs.is_empty() {
+        specs = channel::DEFAULT_PIPELINES.to_vec();
+    }
+    let pipelines = specs.iter().map(|s| channel::Pipeline::parse(s)).collect::<Result<Vec<_>, _>>()?;
+    // Transformed images are decoded with the codec that reads the original, so a loss reports
+    // that codec's error rather than the first one tried.
+    let found = match (method, &stego_key) {
+        (Some(m), _) => m.to_string(),
+        (None, Some(k)) => scan::decode_timed_with_key(Path::new(image_path), scan::KEYED_METHODS, Some(k)).0?.1,
+        (None, None) => stegstr_lib::scan::detect_and_decode(Path::new(image_path))?.1,
+    };
+    let order = [found.as_str()];
+    let report = channel::simulate(Path::new(image_path), &pipelines, keep.map(Path::new), |p| {
+        scan::decode_timed_with_key(p, &order, stego_key.as_ref()).0
+    })?;
+    if json {
+        let out = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    println!("{} ({}, {} bytes)", report.image, report.method, report.payload_bytes);
+    let width = report.results.iter().map(|r| r.transform.len()).max().unwrap_or(0);
+    for r in &report.results {
+        let detail = match (&r.method, &r.error) {
+            (_, Some(e)) => e.clone(),
+            (Some(m), None) => m.clone(),
+            (None, None) => String::new(),
+        };
+        println!("  {:width$}  {}  {}", r.transform, if r.survived { "ok  " } else { "LOST" }, detail, width = width);
+    }
+    println!("Survived {} of {}", report.survived, report.total);
+    Ok(())
+}
+
+fn run_compare(args: &[String]) -> Result<(), String> {
+    let mut paths: Vec<&str> = Vec::new();
+    let mut json = false;
+    let mut heatmap_out: Option<&str> = None;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--json" {
+            json = true;
+        } else if a == "--heatmap" {
+            i += 1;
+            heatmap_out = Some(args.get(i).ok_or("missing value for --heatmap")?);
+        } else if !a.starts_with('-') {
+            paths.push(a);
+        }
+        i += 1;
+    }
+    let [cover, stego] = paths[..] else {
+        return Err("compare requires <cover> <stego>".to_string());
+    };
+    let report = stegstr_lib::quality::compare(Path::new(cover), Path::new(stego))?;
+    if let Some(out) = heatmap_out {
+        let map = stegstr_lib::quality::heatmap(Path::new(cover), Path::new(stego))?;
+        write_output(out, &map.png()?)?;
+        eprintln!(
+            "Heatmap written to {} ({} of {} {}px tiles changed, largest difference {})",
+            out,
+            map.tiles_changed(),
+            map.tile_changed.len(),
+            map.tile,
+            map.max_diff
+        );
+    }
+    if json {
+        let out = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    println!("{}", report.summary());
+    Ok(())
+}
+
+fn run_analyze(args: &[String]) -> Result<(), String> {
+    let mut image: Option<&str> = None;
+    let mut json = false;
+    for a in args {
+        if a == "--json" {
+            json = true;
+        } else if !a.starts_with('-') && image.is_none() {
+            image = Some(a);
+        } else {
+            return Err(format!("unexpected argument {:?}", a));
+        }
+    }
+    let image = image.ok_or("analyze requires <image>")?;
+    let report = stegstr_lib::stego_analysis::analyze(Path::new(image))?;
+    if json {
+        let out = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    println!("{}", report.summary());
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct ImageInfo {
+    format: String,
+    width: u32,
+    height: u32,
+    color_type: String,
+    frames: usize,
+    metadata: MetadataInfo,
+    capacity: Vec<stegstr_lib::units::CapacityInfo>,
+    payload: Option<PayloadInfo>,
+}
+
+#[derive(serde::Serialize)]
+struct MetadataInfo {
+    icc_profile_bytes: Option<usize>,
+    exif_bytes: Option<usize>,
+    /// Keywords of the PNG tEXt/zTXt/iTXt chunks.
+    text_keywords: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+struct PayloadInfo {
+    method: String,
+    bytes: usize,
+    mime_type: Option<String>,
+    fragment: Option<stegstr_lib::payload_header::Fragment>,
+    /// "app", "passphrase", "recipient" or "none".
+    encryption: &'static str,
+}
+
+/// `info <image>`: what the image is and holds, without revealing the payload.
+fn run_info(args: &[String]) -> Result<(), String> {
+    use image::ImageDecoder;
+    use stegstr_lib::stego_crypto;
+    let mut image_path: Option<&str> = None;
+    let mut json = false;
+    for a in args {
+        if a == "--json" {
+            json = true;
+        } else if (a == "-" || !a.starts_with('-')) && image_path.is_none() {
+            image_path = Some(a);
+        } else {
+            return Err(format!("unexpected argument {:?}", a));
+        }
+    }
+    let input = InputImage::open(image_path.ok_or("info requires <image>")?)?;
+    let bytes = fs::read(&input.path).map_err(|e| e.to_string())?;
+    let reader = image::ImageReader::new(io::Cursor::new(&bytes)).with_guessed_format().map_err(|e| e.to_string())?;
+    let format = reader.format().map_or("unknown".to_string(), |f| format!("{:?}", f).to_lowercase());
+    let mut decoder = reader.into_decoder().map_err(|e| e.to_string())?;
+    let (width, height) = decoder.dimensions();
+    let color_type = format!("{:?}", decoder.original_color_type());
+    let metadata = stegstr_lib::output::Metadata::read(&mut decoder, &bytes);
+    let frames = stegstr_lib::stego_anim::Animation::read(&bytes).ok().flatten().map_or(1, |a| a.frames.len());
+    let metadata = MetadataInfo {
+        icc_profile_bytes: metadata.icc_profile.as_ref().map(Vec::len),
+        exif_bytes: metadata.exif.as_ref().map(Vec::len),
+        text_keywords: metadata
+            .text_chunks
+            .iter()
+            .map(|chunk| String::from_utf8_lossy(chunk[8..].split(|&b| b == 0).next().unwrap_or_default()).into_owned())
+            .collect(),
+    };
+    let capacity = ["dwt", "dot", "qim"]
+        .into_iter()
+        .filter_map(|method| stegstr_lib::targets::capacity(&input.path, method, None, None).ok())
+        .collect();
+    let payload = stegstr_lib::scan::decode_timed(&input.path, &stegstr_lib::scan::default_order()).0.ok().map(|(raw, method)| {
+        let (header, data) = stegstr_lib::payload_header::read(&raw).unwrap_or((None, raw));
+        let encryption = if !stego_crypto::is_encrypted_payload(&data) {
+            "none"
+        } else if stego_crypto::is_password_payload(&data) {
+            "passphrase"
+        } else if stego_crypto::is_pubkey_payload(&data) {
+            "recipient"
+        } else {
+            "app"
+        };
+        PayloadInfo {
+            method,
+            bytes: data.len(),
+            mime_type: header.as_ref().map(|h| h.mime_type.clone()),
+            fragment: header.and_then(|h| h.fragment),
+            encryption,
+        }
+    });
+    let info = ImageInfo { format, width, height, color_type, frames, metadata, capacity, payload };
+    if json {
+        let out = serde_json::to_string_pretty(&info).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    let frames = if info.frames > 1 { format!(", {} frames", info.frames) } else { String::new() };
+    println!("Image:     {} {}x{}, {}{}", info.format, info.width, info.height, info.color_type, frames);
+    let mut metadata = Vec::new();
+    if let Some(n) = info.metadata.icc_profile_bytes {
+        metadata.push(format!("ICC profile ({})", stegstr_lib::units::format_bytes(n as u64)));
+    }
+    if let Some(n) = info.metadata.exif_bytes {
+        metadata.push(format!("EXIF ({})", stegstr_lib::units::format_bytes(n as u64)));
+    }
+    if !info.metadata.text_keywords.is_empty() {
+        metadata.push(format!("text: {}", info.metadata.text_keywords.join(", ")));
+    }
+    println!("Metadata:  {}", if metadata.is_empty() { "none".to_string() } else { metadata.join("; ") });
+    let capacity: Vec<String> = info.capacity.iter().map(|c| format!("{} {}", c.method, c.capacity.human)).collect();
+    println!("Capacity:  {}", capacity.join(", "));
+    match &info.payload {
+        Some(p) => {
+            let mut details = vec![p.method.clone(), stegstr_lib::units::format_bytes(p.bytes as u64)];
+            details.extend(p.mime_type.clone());
+            if let Some(f) = &p.fragment {
+                details.push(format!("part {} of {}", f.index + 1, f.total));
+            }
+            details.push(match p.encryption {
+                "none" => "not encrypted".to_string(),
+                "app" => "app-encrypted".to_string(),
+                other => format!("encrypted ({})", other),
+            });
+            println!("Payload:   found ({})", details.join(", "));
+        }
+        None => println!("Payload:   none found"),
+    }
+    Ok(())
+}
+
+fn run_rank_covers(args: &[String]) -> Result<(), String> {
+    let mut paths: Vec<String> = Vec::new();
+    let mut json = false;
+    for a in args {
+        if a == "--json" {
+            json = true;
+        } else if !a.starts_with('-') {
+            paths.push(a.clone());
+        } else {
+            return Err(format!("unexpected argument {:?}", a));
+        }
+    }
+    if paths.is_empty() {
+        return Err("rank-covers requires a directory or images".to_string());
+    }
+    let mut ranked: Vec<(String, stegstr_lib::stego::CoverScore)> = Vec::new();
+    for path in stegstr_lib::scan::expand_paths(&paths)? {
+        match stegstr_lib::stego::cover_score(&path) {
+            Ok(score) => ranked.push((path.display().to_string(), score)),
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+    }
+    ranked.sort_by(|a, b| b.1.score.total_cmp(&a.1.score).then(b.1.capacity_bytes.cmp(&a.1.capacity_bytes)));
+    if json {
+        let items: Vec<serde_json::Value> = ranked
+            .iter()
+            .map(|(path, score)| serde_json::json!({ "path": path, "cover": score }))
+            .collect();
+        let out = serde_json::to_string_pretty(&items).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    for (path, score) in &ranked {
+        println!(
+            "{:.1}\t{}\t{:.0}% flat\t{}",
+            score.score,
+            stegstr_lib::units::format_bytes(score.capacity_bytes as u64),
+            score.flat_fraction * 100.0,
+            path
+        );
+    }
+    Ok(())
+}
+
+fn run_scan(args: &[String]) -> Result<(), String> {
+    use stegstr_lib::scan;
+    let mut paths: Vec<String> = Vec::new();
+    let mut methods: Option<String> = None;
+    let mut json = false;
+    let mut recursive = false;
+    let mut detect = false;
+    let mut prefilter_model: Option<&str> = None;
+    let mut threshold: Option<f32> = None;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--methods" {
+            i += 1;
+            methods = Some(args.get(i).ok_or("missing value for --methods")?.clone());
+        } else if a == "--prefilter" {
+            i += 1;
+            prefilter_model = Some(args.get(i).ok_or("missing value for --prefilter")?);
+        } else if a == "--threshold" {
+            i += 1;
+            threshold = Some(parse_threshold(args.get(i))?);
+        } else if a == "--json" {
+            json = true;
+        } else if a == "--recursive" {
+            recursive = true;
+        } else if a == "--detect" {
+            detect = true;
+        } else if !a.starts_with('-') {
+            paths.push(a.clone());
+        }
+        i += 1;
+    }
+    if paths.is_empty() {
+        return Err("scan requires at least one image or directory".to_string());
+    }
+    let order: Vec<&str> = match &methods {
+        Some(m) => m.split(',').map(str::trim).filter(|m| !m.is_empty()).collect(),
+        None => scan::default_order(),
+    };
+    let files = scan::expand_paths_with(&paths, recursive)?;
+    if detect {
+        if prefilter_model.is_some() || threshold.is_some() {
+            return Err("--prefilter does not apply to --detect".to_string());
+        }
+        let report = scan::detect_all(&files);
+        let out = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    let prefilter = load_prefilter(prefilter_model, threshold)?;
+    let summary = scan::scan(&files, &order, prefilter.as_ref());
+    if json {
+        let out = serde_json::to_string_pretty(&summary).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    for r in &summary.results {
+        if r.skipped {
+            println!("{}  skipped (prefilter {:.2})", r.path, r.prefilter.unwrap_or_default());
+            continue;
+        }
+        let micros: u64 = r.timings.iter().map(|t| t.micros).sum();
+        match (&r.method, r.payload_bytes) {
+            (Some(m), Some(n)) => println!("{}  {} ({} bytes, {} us)", r.path, m, n, micros),
+            _ => println!("{}  none ({} us)", r.path, micros),
+        }
+    }
+    println!("{} of {} image(s) had a payload; {} us total", summary.found, summary.images, summary.total_micros);
+    if summary.skipped > 0 {
+        println!("{} image(s) skipped by the prefilter", summary.skipped);
+    }
+    let width = summary.methods.iter().map(|m| m.method.len()).max().unwrap_or(0);
+    for m in &summary.methods {
+        println!(
+            "  {:<width$} tried {:>4}  found {:>4}  mean {:>8} us  max {:>8} us  {:>5.1}% of time",
+            m.method, m.tried, m.found, m.mean_micros, m.max_micros, m.share_percent
+        );
+    }
+    Ok(())
+}
+
+fn run_codecs() -> Result<(), String> {
+    use stegstr_lib::external;
+    println!("Built-in: dwt, dot, qim, synth (experimental)");
+    let path = external::config_path()?;
+    let codecs = external::registry().map_err(|e| format!("invalid codecs config: {}", e))?;
+    if codecs.is_empty() {
+        println!("No external codecs ({} not found or empty)", path.display());
+        return Ok(());
+    }
+    println!("External ({}):", path.display());
+    for c in codecs {
+        println!(
+            "  {:<12} {} {}{}{}",
+            c.name,
+            c.command,
+            c.args.join(" "),
+            if c.auto_detect { "" } else { "  [no auto-detect]" },
+            if c.description.is_empty() { String::new() } else { format!("  {}", c.description) }
+        );
+    }
+    Ok(())
+}
+
+/// Publish each event to every relay; also returns how many events no relay accepted.
+fn publish_events(
+    events: &[stegstr_lib::nostr_event::NostrEvent],
+    relays: &[String],
+    timeout: std::time::Duration,
+) -> Result<(Vec<stegstr_lib::relay::PublishReport>, usize), String> {
+    use stegstr_lib::relay;
+    let mut report = Vec::new();
+    let mut unpublished = 0;
+    for event in events {
+        let results = relay::publish(event, relays, timeout)?;
+        if !results.iter().any(|r| r.status == "ok") {
+            unpublished += 1;
+        }
+        report.push(relay::PublishReport {
+            event_id: event.id.clone(),
+            results,
+        });
+    }
+    Ok((report, unpublished))
+}
+
+fn format_relay_result(event_id: &str, r: &stegstr_lib::relay::RelayOutcome) -> String {
+    let detail = r.message.as_deref().map(|m| format!(": {}", m)).unwrap_or_default();
+    format!("{} {} {} ({} ms){}", event_id, r.status, r.relay, r.elapsed_ms, detail)
+}
+
+/// `merge a.json b.json [-o out.json]`: bundle::merge over bundle files, e.g. from `decode`
+/// of several images by one author.
+fn run_merge(args: &[String]) -> Result<(), String> {
+    use stegstr_lib::nostr_event::Bundle;
+    let mut inputs: Vec<&str> = Vec::new();
+    let mut output: Option<&str> = None;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "-o" || a == "--output" {
+            i += 1;
+            output = Some(args.get(i).ok_or("missing value for -o/--output")?);
+        } else if !a.starts_with('-') {
+            inputs.push(a);
+        } else {
+            return Err(format!("unexpected argument {:?}", a));
+        }
+        i += 1;
+    }
+    if inputs.is_empty() {
+        return Err("merge requires <bundle.json>...".to_string());
+    }
+    let mut bundles = Vec::with_capacity(inputs.len());
+    for path in &inputs {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        bundles.push(serde_json::from_str::<Bundle>(&text).map_err(|e| format!("{}: {}", path, e))?);
+    }
+    let total: usize = bundles.iter().map(|b| b.events.len()).sum();
+    let merged = stegstr_lib::bundle::merge(bundles);
+    eprintln!("{} event(s) from {} bundle(s); {} duplicate or invalid dropped", merged.events.len(), inputs.len(), total - merged.events.len());
+    let json = serde_json::to_string_pretty(&merged).map_err(|e| e.to_string())?;
+    if let Some(path) = output {
+        write_output(path, json.as_bytes())?;
+    } else {
+        io::stdout().write_all(json.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// The events of a bundle file, or the one event of an event file.
+fn read_events_file(path: &str) -> Result<Vec<stegstr_lib::nostr_event::NostrEvent>, String> {
+    use stegstr_lib::nostr_event;
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(match serde_json::from_str::<nostr_event::Bundle>(&text) {
+        Ok(bundle) => bundle.events,
+        Err(_) => vec![serde_json::from_str::<nostr_event::NostrEvent>(&text)
+            .map_err(|e| format!("{} is neither a bundle nor an event: {}", path, e))?],
+    })
+}
+
+/// `outbox add <bundle.json|event.json>... | list | flush [--relay URL]... [--timeout SECS] |
+/// remove <id>... | remove --sent`, all with --json: the queue the app's flush_outbox publishes.
+fn run_outbox(args: &[String]) -> Result<(), String> {
+    use stegstr_lib::{outbox, relay};
+    let action = args.first().map(String::as_str).ok_or("outbox requires add, list, flush or remove")?;
+    let mut json = false;
+    let mut sent = false;
+    let mut relays: Vec<String> = Vec::new();
+    let mut timeout = relay::DEFAULT_TIMEOUT;
+    let mut positional: Vec<&str> = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--json" {
+            json = true;
+        } else if a == "--sent" {
+            sent = true;
+        } else if a == "--relay" {
+            i += 1;
+            relays.push(args.get(i).ok_or("missing value for --relay")?.clone());
+        } else if a == "--timeout" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --timeout")?;
+            let secs: f64 = v.parse().map_err(|_| format!("invalid --timeout {:?}", v))?;
+            timeout = std::time::Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())?;
+        } else if !a.starts_with('-') {
+            positional.push(a);
+        } else {
+            return Err(format!("unexpected argument {:?}", a));
+        }
+        i += 1;
+    }
+    match action {
+        "add" => {
+            if positional.is_empty() {
+                return Err("outbox add requires <bundle.json|event.json>...".to_string());
+            }
+            let mut events = Vec::new();
+            for path in &positional {
+                events.extend(read_events_file(path)?);
+            }
+            let added = outbox::add(&events)?;
+            match json {
+                true => println!("{}", serde_json::json!({ "queued": added })),
+                false => eprintln!("Queued {} event(s) ({} already queued)", added, events.len() - added),
+            }
+        }
+        "list" => {
+            let entries = outbox::list()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?);
+                return Ok(());
+            }
+            for e in &entries {
+                let accepted: Vec<&str> = e.results.iter().filter(|r| r.status == "ok").map(|r| r.relay.as_str()).collect();
+                println!("{}\t{:?}\t{} attempt(s)\t{}", e.event.id, e.status, e.attempts, accepted.join(" "));
+            }
+        }
+        "flush" => {
+            if relays.is_empty() {
+                relays = stegstr_lib::config::load()?.relays_or_default();
+            }
+            let pool = stegstr_lib::relay_pool::RelayPool::new(|_| {});
+            let report = outbox::flush(&pool, &relays, timeout);
+            pool.disconnect(None);
+            let report = report?;
+            report.notify_done();
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?),
+                false => eprintln!("{} published: {} sent, {} failed, {} still pending", report.attempted, report.sent, report.failed, report.pending),
+            }
+        }
+        "remove" => {
+            let ids = (!sent).then_some(positional.iter().map(|id| stegstr_lib::nostr_codes::parse_event_id(id)).collect::<Result<Vec<_>, _>>()?);
+            if ids.as_ref().is_some_and(Vec::is_empty) {
+                return Err("outbox remove requires <id>... or --sent".to_string());
+            }
+            let removed = outbox::remove(ids.as_deref())?;
+            match json {
+                true => println!("{}", serde_json::json!({ "removed": removed })),
+                false => eprintln!("Removed {} event(s)", removed),
+            }
+        }
+        other => return Err(format!("unknown outbox action {:?} (add, list, flush or remove)", other)),
+    }
+    Ok(())
+}
+
+fn run_publish(args: &[String]) -> Result<(), String> {
+    use stegstr_lib::relay;
+    let mut input: Option<&str> = None;
+    let mut relays: Vec<String> = Vec::new();
+    let mut timeout = relay::DEFAULT_TIMEOUT;
+    let mut json = false;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--relay" {
+            i += 1;
+            relays.push(args.get(i).ok_or("missing value for --relay")?.clone());
+        } else if a == "--timeout" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --timeout")?;
+            let secs: f64 = v.parse().map_err(|_| format!("invalid --timeout {:?}", v))?;
+            timeout = std::time::Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())?;
+        } else if a == "--json" {
+            json = true;
+        } else if !a.starts_with('-') && input.is_none() {
+            input = Some(a);
+        }
+        i += 1;
+    }
+    let events = read_events_file(input.ok_or("publish requires <bundle.json|event.json>")?)?;
+    if relays.is_empty() {
+        relays = stegstr_lib::config::load()?.relays_or_default();
+    }
+
+    let (report, unpublished) = publish_events(&events, &relays, timeout)?;
+    if !json {
+        for p in &report {
+            for r in &p.results {
+                println!("{}", format_relay_result(&p.event_id, r));
+            }
+        }
+    } else {
+        let out = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        io::stdout().write_all(out.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    if unpublished > 0 {
+        return Err(format!("{} of {} event(s) were not accepted by any relay", unpublished, events.len()));
+    }
+    Ok(())
+}
+
+fn run_fetch(args: &[String]) -> Result<(), String> {
+    use stegstr_lib::{nostr_codes, nostr_event, relay};
+    let mut filter = relay::Filter::default();
+    let mut relays: Vec<String> = Vec::new();
+    let mut hints: Vec<String> = Vec::new();
+    let mut timeout = relay::DEFAULT_TIMEOUT;
+    let mut output_path: Option<&str> = None;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--relay" {
+            i += 1;
+            relays.push(args.get(i).ok_or("missing value for --relay")?.clone());
+        } else if a == "--id" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --id")?;
+            if v.to_lowercase().starts_with("nevent1") {
+                let pointer = nostr_codes::nevent_decode(v)?;
+                hints.extend(pointer.relays);
+                filter.ids.push(pointer.id);
+            } else {
+                filter.ids.push(nostr_codes::parse_event_id(v)?);
+            }
+        } else if a == "--author" {
+            i += 1;
+            filter.authors.push(nostr_codes::parse_pubkey(args.get(i).ok_or("missing value for --author")?)?);
+        } else if a == "--kind" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --kind")?;
+            filter.kinds.push(v.parse().map_err(|_| format!("invalid --kind {:?}", v))?);
+        } else if a == "--since" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --since")?;
+            filter.since = Some(v.parse().map_err(|_| format!("invalid --since {:?}", v))?);
+        } else if a == "--until" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --until")?;
+            filter.until = Some(v.parse().map_err(|_| format!("invalid --until {:?}", v))?);
+        } else if a == "--limit" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --limit")?;
+            filter.limit = Some(v.parse().map_err(|_| format!("invalid --limit {:?}", v))?);
+        } else if a == "--timeout" {
+            i += 1;
+            let v = args.get(i).ok_or("missing value for --timeout")?;
+            let secs: f64 = v.parse().map_err(|_| format!("invalid --timeout {:?}", v))?;
+            timeout = std::time::Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())?;
+        } else if a == "--output" {
+            i += 1;
+            output_path = Some(args.get(i).ok_or("missing value for --output")?);
+        } else {
+            return Err(format!("unexpected argument {:?}", a));
+        }
+        i += 1;
+    }
+    if relays.is_empty() {
+        relays = stegstr_lib::config::load()?.relays_or_default();
+    }
+    relays.extend(hints);
+
+    let report = relay::fetch(&filter, &relays, timeout)?;
+    for r in &report.results {
+        let detail = r.message.as_deref().map(|m| format!(": {}", m)).unwrap_or_default();
+        eprintln!("{} {} ({} ms){}", r.status, r.relay, r.elapsed_ms, detail);
+    }
+    if !report.results.iter().any(|r| r.status == "ok") {
+        return Err("no relay answered the subscription".to_string());
+    }
+    eprintln!("{} event(s)", report.events.len());
+    let bundle = nostr_event::Bundle::new(report.events);
+    let json = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+    if let Some(path) = output_path {
+        write_output(path, json.as_bytes())?;
+    } else {
+        io::stdout().write_all(json.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn run_fixtures(args: &[String]) -> Result<(), String> {
+    use stegstr_lib::fixtures;
+    let mut action: Option<&str> = None;
+    let mut manifest_path: Option<&str> = None;
+    let mut dir: Option<&str> = None;
+    let mut i = 0;
+    while i < args.len() {
+        let a = &args[i];
+        if a == "--manifest" {
+            i += 1;
+            manifest_path = Some(args.get(i).ok_or("missing value for --manifest")?);
+        } else if a == "--dir" {
+            i += 1;
+            dir = Some(args.get(i).ok_or("missing value for --dir")?);
+        } else if !a.starts_with('-') && action.is_none() {
+            action = Some(a);
+        }
+        i += 1;
+    }
+    let mut manifest = fixtures::load_manifest(manifest_path.map(Path::new))?;
+    let dir = match dir {
+        Some(d) => {
+            fs::create_dir_all(d).map_err(|e| e.to_string())?;
+            std::path::PathBuf::from(d)
+        }
+        None => fixtures::cache_dir()?,
+    };
+    match action.unwrap_or("list") {
+        "list" => {
+            let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+            println!("{}", json);
+            Ok(())
+        }
+        "fetch" => {
+            let results = fixtures::fetch_all(&manifest, &dir);
+            let failed = results.iter().filter(|r| r.is_err()).count();
+            let report: Vec<serde_json::Value> = results
+                .into_iter()
+                .map(|r| match r {
+                    Ok(status) => serde_json::json!(status),
+                    Err(e) => serde_json::json!({ "error": e }),
+                })
+                .collect();
+            let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+            println!("{}", json);
+            if failed > 0 {
+                return Err(format!("{} fixture(s) failed", failed));
+            }
+            Ok(())
+        }
+        "pin" => {
+            let path = manifest_path.ok_or("pin requires --manifest <path> to write hashes into")?;
+            let pinned = fixtures::pin(&mut manifest, &dir)?;
+            let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+            stegstr_lib::atomic_write::write(Path::new(path), (json + "\n").as_bytes(), true)?;
+            eprintln!("Pinned {} fixture(s) in {}", pinned, path);
+            Ok(())
+        }
+        other => Err(format!("unknown fixtures action {:?} (list|fetch|pin)", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn temp_dir(tag: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("stegstr_cli_{}_{}", tag, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_cover(path: &Path, w: u32, h: u32) -> String {
+        let img = image::RgbaImage::from_fn(w, h, |x, y| image::Rgba([(40 + (x * 3 + y) % 170) as u8, (40 + y * 5 % 170) as u8, (40 + (x ^ y) % 170) as u8, 255]));
+        img.save(path).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn embed_error(list: &[&str]) -> String {
+        let list = args(list);
+        let config = stegstr_lib::config::Settings::default();
+        let mut opts = EmbedArgs::parse(&list, &config).unwrap();
+        opts.validate(&config).expect_err("rejected").message
+    }
+
+    #[test]
+    fn test_embed_options_are_checked_for_every_codec() {
+        assert!(embed_error(&["c.png", "d.png"]).contains("--split"));
+        assert!(embed_error(&["c.png", "--fountain", "2"]).contains("apply to --split"));
+        assert!(embed_error(&["c.png", "--method", "dot", "--target", "email"]).contains("exclusive"));
+        assert!(embed_error(&["c.png", "--jpeg-quality", "80"]).contains("--format jpeg"));
+        assert!(embed_error(&["c.png", "--dot-contrast", "8"]).contains("--dot-render"));
+        assert!(embed_error(&["c.png", "--fiducials", "--method", "qim"]).contains("write with dot"));
+        assert!(embed_error(&["c.png", "--bind"]).contains("encryption"));
+
+        let list = args(&["c.png", "--fiducials", "--format", "jpeg"]);
+        let config = stegstr_lib::config::Settings::default();
+        assert!(EmbedArgs::parse(&list, &config).unwrap().validate(&config).is_err());
+        // Flags imply their codec, and the config file's codec fills in otherwise.
+        let list = args(&["c.png", "--dot-render", "luma"]);
+        let mut opts = EmbedArgs::parse(&list, &config).unwrap();
+        opts.validate(&config).unwrap();
+        assert_eq!((opts.method, opts.rendering), (Some("dot"), stegstr_lib::stego_dot::Rendering::Luma { contrast: stegstr_lib::stego_dot::DEFAULT_CONTRAST }));
+        let config = stegstr_lib::config::Settings { method: Some("qim".to_string()), redundancy: Some(2), ..Default::default() };
+        let list = args(&["c.png"]);
+        let mut opts = EmbedArgs::parse(&list, &config).unwrap();
+        opts.validate(&config).unwrap();
+        assert_eq!((opts.method, opts.dwt.redundancy), (Some("qim"), 2));
+    }
+
+    #[test]
+    fn test_decode_goes_on_without_an_unreadable_config() {
+        assert!(config_stego_key(Err("config.toml: expected `=`".to_string())).is_none());
+        assert!(config_stego_key(Ok(stegstr_lib::config::Settings::default())).is_none());
+        let settings = stegstr_lib::config::Settings { stego_key: Some("correct horse".to_string()), ..Default::default() };
+        assert!(config_stego_key(Ok(settings)) == Some(stegstr_lib::stego_key::StegoKey::from_password("correct horse").unwrap()));
+    }
+
+    #[test]
+    fn test_embed_then_inspect_the_image() {
+        let dir = temp_dir("embed");
+        let cover = write_cover(&dir.join("cover.png"), 320, 320);
+        let out = dir.join("out.png").to_string_lossy().into_owned();
+        run_embed(&args(&[&cover, "-o", &out, "--payload", "hello"]), false).unwrap();
+        run_decode(&args(&[&out]), false).unwrap();
+        run_decode(&args(&[&out, "--method", "dwt"]), true).unwrap();
+        run_capacity(&args(&[&out])).unwrap();
+        run_info(&args(&[&out, "--json"])).unwrap();
+        run_compare(&args(&[&cover, &out])).unwrap();
+        run_analyze(&args(&[&out])).unwrap();
+        run_rank_covers(&args(&[&cover])).unwrap();
+        run_simulate(&args(&[&out, "--transform", "strip"])).unwrap();
+        // The output exists, so a second embed needs --overwrite.
+        assert!(run_embed(&args(&[&cover, "-o", &out, "--payload", "again"]), false).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_split_then_join() {
+        let dir = temp_dir("split");
+        let covers: Vec<String> = (0..2).map(|i| write_cover(&dir.join(format!("cover{}.png", i)), 256, 256)).collect();
+        let out = dir.join("out");
+        let out_arg = out.to_string_lossy().into_owned();
+        run_embed(&args(&["--split", &covers[0], &covers[1], "-o", &out_arg, "--payload", "two halves"]), false).unwrap();
+        let parts: Vec<String> = fs::read_dir(&out).unwrap().map(|e| e.unwrap().path().to_string_lossy().into_owned()).collect();
+        assert_eq!(parts.len(), 2);
+        run_join(&parts, false).unwrap();
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_post_merge_and_dm_bundles() {
+        let dir = temp_dir("events");
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let (alice, bob) = ("11".repeat(32), "22".repeat(32));
+        let bob_pubkey = stegstr_lib::nostr_event::pubkey_hex(&stegstr_lib::nostr_codes::parse_secret_key(&bob).unwrap());
+        run_post(&args(&["first", "--privkey-hex", &alice, "--output", &path("a.json")]), false).unwrap();
+        run_post(&args(&["second", "--privkey-hex", &alice, "--output", &path("b.json")]), false).unwrap();
+        run_merge(&args(&[&path("a.json"), &path("b.json"), &path("a.json"), "-o", &path("merged.json")])).unwrap();
+        let merged: stegstr_lib::nostr_event::Bundle = serde_json::from_str(&fs::read_to_string(path("merged.json")).unwrap()).unwrap();
+        assert_eq!(merged.events.len(), 2);
+        assert!(run_merge(&args(&[])).is_err());
+
+        run_dm(&args(&[&bob_pubkey, "psst", "--privkey-hex", &alice, "--output", &path("dm.json")]), false).unwrap();
+        run_dm(&args(&["open", &path("dm.json"), "--privkey-hex", &bob]), true).unwrap();
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_send_embeds_for_the_recipient() {
+        let dir = temp_dir("send");
+        let cover = write_cover(&dir.join("cover.png"), 512, 512);
+        let out = dir.join("out.png").to_string_lossy().into_owned();
+        let recipient = stegstr_lib::nostr_event::pubkey_hex(&stegstr_lib::nostr_codes::parse_secret_key(&"33".repeat(32)).unwrap());
+        run_send(&args(&["hello", "--to", &recipient, "--cover", &cover, "-o", &out, "--privkey-hex", &"44".repeat(32)])).unwrap();
+        assert!(Path::new(&out).exists());
+        assert!(run_send(&args(&["hello", "--cover", &cover, "-o", &out])).unwrap_err().contains("--to"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_fixtures_pin_then_fetch() {
+        let dir = temp_dir("fixtures");
+        fs::write(dir.join("source.png"), b"cover bytes").unwrap();
+        let manifest = dir.join("manifest.json");
+        fs::write(&manifest, r#"{ "remote": [{ "name": "local", "url": "source.png" }], "synthetic": [] }"#).unwrap();
+        let (manifest_arg, cache) = (manifest.to_string_lossy().into_owned(), dir.join("cache").to_string_lossy().into_owned());
+        assert!(run_fixtures(&args(&["fetch", "--manifest", &manifest_arg, "--dir", &cache])).is_err(), "unpinned fixtures are not fetched");
+        run_fixtures(&args(&["pin", "--manifest", &manifest_arg, "--dir", &cache])).unwrap();
+        assert!(fs::read_to_string(&manifest).unwrap().contains("sha256"));
+        run_fixtures(&args(&["fetch", "--manifest", &manifest_arg, "--dir", &cache])).unwrap();
+        run_fixtures(&args(&["list", "--manifest", &manifest_arg])).unwrap();
+        assert!(run_fixtures(&args(&["bogus", "--manifest", &manifest_arg])).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_network_and_keychain_commands_check_their_arguments() {
+        assert!(run_key(&args(&["bogus"])).is_err());
+        assert!(run_publish(&args(&[])).is_err());
+        assert!(run_outbox(&args(&["bogus"])).is_err());
+    }
+}
@@ -0,0 +1,279 @@
+// Channel simulation: apply what sharing platforms do to an image (JPEG re-encode, resize, crop,
+// rotate, metadata strip) to a stego image and check whether the payload still decodes. The
+// platform profiles and their pipeline (strip, resize to max width, JPEG) follow
+// channel_simulator/CHANNEL_SPEC.md, so robustness can be checked without Python.
+// Every step writes a fresh file, so no metadata survives any of them; `strip` alone is that
+// re-encode (lossless PNG) with the EXIF orientation applied.
+
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageReader};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelProfile {
+    pub name: &'static str,
+    pub max_width: u32,
+    pub jpeg_quality: u8,
+}
+
+/// CHANNEL_SPEC.md profiles, plus telegram (photos, not files) for the survival profiles.
+pub const PROFILES: &[ChannelProfile] = &[
+    ChannelProfile { name: "whatsapp", max_width: 800, jpeg_quality: 65 },
+    ChannelProfile { name: "instagram", max_width: 1080, jpeg_quality: 82 },
+    ChannelProfile { name: "facebook", max_width: 2048, jpeg_quality: 77 },
+    ChannelProfile { name: "twitter", max_width: 600, jpeg_quality: 82 },
+    ChannelProfile { name: "telegram", max_width: 1280, jpeg_quality: 87 },
+];
+
+/// Pipelines `simulate` runs when none are given.
+pub const DEFAULT_PIPELINES: &[&str] = &[
+    "strip", "jpeg:95", "jpeg:85", "jpeg:75", "resize:75", "resize:50", "crop:10", "rotate:90", "whatsapp", "instagram",
+    "facebook", "twitter",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Re-encode as JPEG at this quality (1-100).
+    Jpeg(u8),
+    /// Scale both sides to this percentage (1-400).
+    Resize(u32),
+    /// Trim this percentage of the width and height from each edge (0-49).
+    Crop(u32),
+    /// Rotate clockwise by 90, 180 or 270 degrees.
+    Rotate(u32),
+    Strip,
+    Profile(&'static ChannelProfile),
+}
+
+impl Transform {
+    /// "jpeg:Q", "resize:PCT", "crop:PCT", "rotate:DEG", "strip" or a profile name.
+    pub fn parse(spec: &str) -> Result<Transform, String> {
+        let (name, arg) = match spec.split_once(':') {
+            Some((n, a)) => (n, Some(a)),
+            None => (spec, None),
+        };
+        let number = |lo: u32, hi: u32| -> Result<u32, String> {
+            let a = arg.ok_or_else(|| format!("{} needs a value ({}:N)", name, name))?;
+            a.parse::<u32>()
+                .ok()
+                .filter(|v| (lo..=hi).contains(v))
+                .ok_or_else(|| format!("invalid {} value {:?} (expected {}-{})", name, a, lo, hi))
+        };
+        Ok(match name {
+            "jpeg" | "jpg" => Transform::Jpeg(number(1, 100)? as u8),
+            "resize" => Transform::Resize(number(1, 400)?),
+            "crop" => Transform::Crop(number(0, 49)?),
+            "rotate" => match number(90, 270)? {
+                d @ (90 | 180 | 270) => Transform::Rotate(d),
+                d => return Err(format!("invalid rotate value {} (expected 90, 180 or 270)", d)),
+            },
+            "strip" if arg.is_none() => Transform::Strip,
+            other => match PROFILES.iter().find(|p| p.name == other) {
+                Some(p) if arg.is_none() => Transform::Profile(p),
+                _ => {
+                    let profiles: Vec<&str> = PROFILES.iter().map(|p| p.name).collect();
+                    return Err(format!(
+                        "unknown transform {:?} (jpeg:Q, resize:PCT, crop:PCT, rotate:DEG, strip, {})",
+                        spec,
+                        profiles.join(", ")
+                    ));
+                }
+            },
+        })
+    }
+
+    /// Whether the transform writes a JPEG (otherwise PNG).
+    fn is_jpeg(&self) -> bool {
+        matches!(self, Transform::Jpeg(_) | Transform::Profile(_))
+    }
+
+    fn apply(&self, img: DynamicImage) -> DynamicImage {
+        let (w, h) = (img.width(), img.height());
+        match *self {
+            Transform::Jpeg(_) | Transform::Strip => img,
+            Transform::Resize(pct) => {
+                let scale = |v: u32| ((v as u64 * pct as u64 + 50) / 100).max(1) as u32;
+                img.resize_exact(scale(w), scale(h), FilterType::Lanczos3)
+            }
+            Transform::Crop(pct) => {
+                let (dx, dy) = (w * pct / 100, h * pct / 100);
+                img.crop_imm(dx, dy, w - 2 * dx, h - 2 * dy)
+            }
+            Transform::Rotate(90) => img.rotate90(),
+            Transform::Rotate(180) => img.rotate180(),
+            Transform::Rotate(_) => img.rotate270(),
+            Transform::Profile(p) if w > p.max_width => {
+                let new_h = ((h as u64 * p.max_width as u64 + w as u64 / 2) / w as u64).max(1) as u32;
+                img.resize_exact(p.max_width, new_h, FilterType::Lanczos3)
+            }
+            Transform::Profile(_) => img,
+        }
+    }
+}
+
+/// Transforms applied in turn, written "jpeg:75+resize:50".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    pub spec: String,
+    pub steps: Vec<Transform>,
+}
+
+impl Pipeline {
+    pub fn parse(spec: &str) -> Result<Pipeline, String> {
+        let steps = spec
+            .split('+')
+            .map(|s| Transform::parse(s.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Pipeline {
+            spec: spec.to_string(),
+            steps,
+        })
+    }
+
+    /// Run the steps on image file contents; returns the new file contents and its extension.
+    pub fn run(&self, image: &[u8]) -> Result<(Vec<u8>, &'static str), String> {
+        let mut bytes = image.to_vec();
+        let mut ext = "png";
+        for step in &self.steps {
+            let img = step.apply(load_oriented(&bytes)?);
+            (bytes, ext) = if step.is_jpeg() {
+                let quality = match step {
+                    Transform::Jpeg(q) => *q,
+                    Transform::Profile(p) => p.jpeg_quality,
+                    _ => unreachable!(),
+                };
+                let mut out = Vec::new();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, quality)
+                    .encode_image(&img.to_rgb8())
+                    .map_err(|e| e.to_string())?;
+                (out, "jpg")
+            } else {
+                let mut out = Cursor::new(Vec::new());
+                img.write_to(&mut out, image::ImageFormat::Png).map_err(|e| e.to_string())?;
+                (out.into_inner(), "png")
+            };
+        }
+        Ok((bytes, ext))
+    }
+}
+
+fn load_oriented(bytes: &[u8]) -> Result<DynamicImage, String> {
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format().map_err(|e| e.to_string())?;
+    let mut decoder = reader.into_decoder().map_err(|e| e.to_string())?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TransformOutcome {
+    pub transform: String,
+    /// The payload decoded byte-exact after the transform.
+    pub survived: bool,
+    /// Codec that decoded the transformed image (possibly to a different payload).
+    pub method: Option<String>,
+    pub error: Option<String>,
+    /// Size of the transformed file.
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SimulationReport {
+    pub image: String,
+    /// Codec that decodes the untouched image.
+    pub method: String,
+    pub payload_bytes: usize,
+    pub survived: usize,
+    pub total: usize,
+    pub results: Vec<TransformOutcome>,
+}
+
+/// Decode `image`, then run each pipeline on the original and decode the result with `decode`
+/// (payload and method). Transformed files go to `keep_dir` as `<spec>.<ext>` when given,
+/// otherwise to temp files that are removed.
+pub fn simulate<F>(image: &Path, pipelines: &[Pipeline], keep_dir: Option<&Path>, decode: F) -> Result<SimulationReport, String>
+where
+    F: Fn(&Path) -> Result<(Vec<u8>, String), String>,
+{
+    let original = std::fs::read(image).map_err(|e| format!("{}: {}", image.display(), e))?;
+    let (expected, method) = decode(image).map_err(|e| format!("{} does not decode before any transform: {}", image.display(), e))?;
+    if let Some(dir) = keep_dir {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let mut results = Vec::with_capacity(pipelines.len());
+    for (i, pipeline) in pipelines.iter().enumerate() {
+        let mut outcome = TransformOutcome {
+            transform: pipeline.spec.clone(),
+            survived: false,
+            method: None,
+            error: None,
+            bytes: 0,
+        };
+        match pipeline.run(&original) {
+            Ok((bytes, ext)) => {
+                outcome.bytes = bytes.len() as u64;
+                let path = match keep_dir {
+                    Some(dir) => dir.join(format!("{}.{}", pipeline.spec.replace([':', '+'], "_"), ext)),
+                    None => std::env::temp_dir().join(format!("stegstr-simulate-{}-{}.{}", std::process::id(), i, ext)),
+                };
+                let decoded = std::fs::write(&path, &bytes).map_err(|e| e.to_string()).and_then(|_| decode(&path));
+                if keep_dir.is_none() {
+                    let _ = std::fs::remove_file(&path);
+                }
+                match decoded {
+                    Ok((payload, m)) => {
+                        outcome.survived = payload == expected;
+                        if !outcome.survived {
+                            outcome.error = Some(format!("decoded {} bytes that differ from the payload", payload.len()));
+                        }
+                        outcome.method = Some(m);
+                    }
+                    Err(e) => outcome.error = Some(e),
+                }
+            }
+            Err(e) => outcome.error = Some(e),
+        }
+        results.push(outcome);
+    }
+    Ok(SimulationReport {
+        image: image.to_string_lossy().to_string(),
+        method,
+        payload_bytes: expected.len(),
+        survived: results.iter().filter(|r| r.survived).count(),
+        total: results.len(),
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulate_reports_survival_per_transform() {
+        let dir = std::env::temp_dir().join(format!("stegstr_channel_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cover = dir.join("cover.png");
+        stegstr_core::test_util::noisy_cover(256, 256, 0x2545_F491).save(&cover).unwrap();
+        let stego = dir.join("stego.png");
+        std::fs::write(&stego, crate::stego::encode(&cover, b"survive?", &crate::stego::EncodeOptions::default()).unwrap()).unwrap();
+
+        let pipelines: Vec<Pipeline> = ["strip", "jpeg:60", "resize:50+rotate:90"].iter().map(|s| Pipeline::parse(s).unwrap()).collect();
+        assert_eq!(pipelines[2].steps, vec![Transform::Resize(50), Transform::Rotate(90)]);
+        let report = simulate(&stego, &pipelines, Some(&dir.join("out")), |p| Ok((crate::stego::decode(p)?, "dwt".to_string()))).unwrap();
+        assert_eq!((report.method.as_str(), report.total, report.survived), ("dwt", 3, 1));
+        assert!(report.results[0].survived && !report.results[1].survived);
+        assert!(dir.join("out").join("jpeg_60.jpg").exists());
+        let small = image::open(dir.join("out").join("resize_50_rotate_90.png")).unwrap();
+        assert_eq!((small.width(), small.height()), (128, 128));
+
+        assert!(Transform::parse("rotate:45").is_err());
+        assert!(Transform::parse("whatsapp").is_ok() && Transform::parse("myspace").is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
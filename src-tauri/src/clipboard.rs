@@ -0,0 +1,117 @@
+// Copying an image (not its path) to the system clipboard, so a stego image can be pasted straight
+// into a chat. Each platform's own tool does the copy, as reveal_in_finder does: osascript on
+// macOS, PowerShell with System.Windows.Forms on Windows, wl-copy under Wayland and xclip under X11
+// elsewhere. The clipboard always gets PNG data: other formats are converted to a temporary PNG
+// first (pixels unchanged, so the payload survives as well as the chat's own re-encoding allows).
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// A PNG of the image at `path`: the file itself when it is one, else a converted temporary file
+/// (removed on drop).
+struct PngFile {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl Drop for PngFile {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn png_file(path: &Path) -> Result<PngFile, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if bytes.starts_with(PNG_SIGNATURE) {
+        return Ok(PngFile { path: path.to_path_buf(), temporary: false });
+    }
+    let img = image::load_from_memory(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let nonce: u32 = rand::random();
+    let tmp = std::env::temp_dir().join(format!("stegstr_clipboard_{}_{:08x}.png", std::process::id(), nonce));
+    let file = PngFile { path: tmp, temporary: true };
+    img.save_with_format(&file.path, image::ImageFormat::Png).map_err(|e| e.to_string())?;
+    Ok(file)
+}
+
+// No pipes: xclip and wl-copy fork a process that serves the clipboard, which would hold them open.
+fn run(command: &mut Command, tool: &str, hint: &str) -> Result<(), String> {
+    let status = command.stdout(Stdio::null()).stderr(Stdio::null()).status().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => format!("{} not found{}", tool, hint),
+        _ => format!("{}: {}", tool, e),
+    })?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("{} failed ({})", tool, status)),
+    }
+}
+
+/// Put the image at `path` on the clipboard as PNG image data.
+pub fn copy_image(path: &Path) -> Result<(), String> {
+    let png = png_file(path)?;
+    copy_png(&png.path)
+}
+
+#[cfg(target_os = "macos")]
+fn copy_png(png: &Path) -> Result<(), String> {
+    // The path goes in as an argument, never into the script text.
+    let mut cmd = Command::new("osascript");
+    cmd.args(["-e", "on run argv", "-e", "set the clipboard to (read (POSIX file (item 1 of argv)) as «class PNGf»)", "-e", "end run"]).arg(png);
+    run(&mut cmd, "osascript", "")
+}
+
+#[cfg(target_os = "windows")]
+fn copy_png(png: &Path) -> Result<(), String> {
+    // Both a "PNG" stream (keeps alpha, which browsers and chat apps read) and a bitmap for older apps.
+    const SCRIPT: &str = "Add-Type -AssemblyName System.Windows.Forms, System.Drawing; \
+        $p = $env:STEGSTR_CLIPBOARD_PNG; \
+        $data = New-Object System.Windows.Forms.DataObject; \
+        $data.SetData('PNG', (New-Object System.IO.MemoryStream(,[System.IO.File]::ReadAllBytes($p)))); \
+        $data.SetImage([System.Drawing.Image]::FromFile($p)); \
+        [System.Windows.Forms.Clipboard]::SetDataObject($data, $true)";
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-STA", "-Command", SCRIPT]).env("STEGSTR_CLIPBOARD_PNG", png);
+    run(&mut cmd, "powershell", "")
+}
+
+#[cfg(all(not(target_os = "macos"), not(target_os = "windows")))]
+fn copy_png(png: &Path) -> Result<(), String> {
+    let input = || std::fs::File::open(png).map_err(|e| format!("{}: {}", png.display(), e));
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        let mut cmd = Command::new("wl-copy");
+        cmd.args(["--type", "image/png"]).stdin(input()?);
+        return run(&mut cmd, "wl-copy", " (install wl-clipboard)");
+    }
+    let mut cmd = Command::new("xclip");
+    cmd.args(["-selection", "clipboard", "-target", "image/png", "-in"]).stdin(input()?);
+    run(&mut cmd, "xclip", " (install xclip, or wl-clipboard under Wayland)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_file_converts_other_formats_and_cleans_up() {
+        let dir = std::env::temp_dir();
+        let png_path = dir.join(format!("stegstr_clip_{}.png", std::process::id()));
+        let jpeg_path = png_path.with_extension("jpg");
+        let img = image::RgbImage::from_fn(32, 16, |x, y| image::Rgb([x as u8 * 8, y as u8 * 16, 90]));
+        img.save(&png_path).unwrap();
+        img.save(&jpeg_path).unwrap();
+
+        let same = png_file(&png_path).unwrap();
+        assert_eq!((same.path.as_path(), same.temporary), (png_path.as_path(), false));
+        let converted = png_file(&jpeg_path).unwrap();
+        let tmp = converted.path.clone();
+        assert!(std::fs::read(&tmp).unwrap().starts_with(PNG_SIGNATURE));
+        assert_eq!(image::open(&tmp).unwrap().width(), 32);
+        drop((same, converted));
+        assert!(!tmp.exists() && png_path.exists());
+        let _ = std::fs::remove_file(png_path);
+        let _ = std::fs::remove_file(jpeg_path);
+    }
+}
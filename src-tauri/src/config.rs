@@ -0,0 +1,218 @@
+// User settings shared by the app and stegstr-cli: config.toml in the platform config directory
+// (~/.config/stegstr on Linux), or in the data dir when STEGSTR_DATA_DIR or portable mode puts
+// data there (app_paths); STEGSTR_CONFIG names another file. Every setting is optional and
+// unset means the built-in default; CLI flags override the file. The stego key password lives in
+// the OS keychain (keystore) and the file names its entry; `portable` is the marker file app_paths
+// looks for, so it is never written here.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Config file path override.
+pub const CONFIG_ENV: &str = "STEGSTR_CONFIG";
+const CONFIG_DIR_NAME: &str = "stegstr";
+const CONFIG_FILE: &str = "config.toml";
+/// Keychain entry a stego key given in the settings is moved to when none is named.
+pub const DEFAULT_STEGO_KEY_REF: &str = "default";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Codec embed uses when none is given: dwt, dot, qim, synth or a registered external codec.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// DWT redundancy when none is given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redundancy: Option<u32>,
+    /// Keychain entry (keystore::store_stego_key) holding the stego key password embeds use, and
+    /// decodes try when the unkeyed read finds nothing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stego_key_ref: Option<String>,
+    /// A stego key password to store: `save` moves it into the keychain under `stego_key_ref`, so
+    /// it is never written to the file or sent back. Files from before the keychain are still read.
+    #[serde(skip_serializing)]
+    pub stego_key: Option<String>,
+    /// Keep data and this file next to the executable (app_paths portable mode). `save` writes or
+    /// removes the marker file; `load` reports the current mode.
+    #[serde(skip_serializing)]
+    pub portable: Option<bool>,
+    /// Relays used when none are given (empty: relay::DEFAULT_RELAYS).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub relays: Vec<String>,
+    /// Directory outputs given as a bare file name are written to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<String>,
+    /// Native notification when a watch, batch or outbox job finishes (unset: on).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify: Option<bool>,
+    /// Keep the events of decoded images in the local event store (unset: off, so decoding
+    /// leaves nothing behind).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store_events: Option<bool>,
+}
+
+impl Settings {
+    /// Err naming the first setting that would be rejected where it is used.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(method) = &self.method {
+            if !matches!(method.as_str(), "dwt" | "dot" | "qim" | "synth") && crate::external::find(method).is_none() {
+                return Err(format!("method: unknown method {:?}", method));
+            }
+        }
+        if let Some(redundancy) = self.redundancy {
+            crate::stego::EncodeOptions { redundancy, ..Default::default() }
+                .validated()
+                .map_err(|e| format!("redundancy: {}", e))?;
+        }
+        if self.stego_key.as_deref().is_some_and(str::is_empty) {
+            return Err("stego_key: must not be empty".to_string());
+        }
+        if self.stego_key_ref.as_deref().is_some_and(|r| r.trim().is_empty()) {
+            return Err("stego_key_ref: must not be empty".to_string());
+        }
+        if let Some(url) = self.relays.iter().find(|u| !(u.starts_with("ws://") || u.starts_with("wss://"))) {
+            return Err(format!("relays: {} must start with ws:// or wss://", url));
+        }
+        if let Some(dir) = self.output_dir.as_deref().filter(|d| !Path::new(d).is_dir()) {
+            return Err(format!("output_dir: {} is not a directory", dir));
+        }
+        Ok(())
+    }
+
+    /// The stego key password: from the keychain entry `stego_key_ref`, else a plain `stego_key`
+    /// read from an older file.
+    pub fn stego_key_password(&self) -> Result<Option<String>, String> {
+        match (&self.stego_key, &self.stego_key_ref) {
+            (Some(password), _) => Ok(Some(password.clone())),
+            (None, Some(name)) => crate::keystore::load_stego_key(name).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// `relays`, or the default relays when none are set.
+    pub fn relays_or_default(&self) -> Vec<String> {
+        match self.relays.is_empty() {
+            true => crate::relay::default_relays(),
+            false => self.relays.clone(),
+        }
+    }
+
+    /// `output` inside `output_dir` when it is a bare file name; otherwise as given.
+    pub fn output_path(&self, output: &str) -> PathBuf {
+        let path = Path::new(output);
+        match (&self.output_dir, path.parent()) {
+            (Some(dir), Some(parent)) if parent.as_os_str().is_empty() && output != "-" => Path::new(dir).join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+pub fn config_path() -> Result<PathBuf, String> {
+    if let Ok(p) = std::env::var(CONFIG_ENV) {
+        if !p.trim().is_empty() {
+            return Ok(PathBuf::from(p.trim()));
+        }
+    }
+    // A portable (USB) install or an explicit data dir keeps its settings with its data.
+    if crate::app_paths::is_portable() || crate::app_paths::data_dir_override().is_some() {
+        return crate::app_paths::data_path(CONFIG_FILE);
+    }
+    match dirs::config_dir() {
+        Some(dir) => Ok(dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE)),
+        None => crate::app_paths::data_path(CONFIG_FILE),
+    }
+}
+
+/// Settings from the config file; a missing file means all defaults.
+pub fn load() -> Result<Settings, String> {
+    let mut settings = load_from(&config_path()?)?;
+    settings.portable = Some(crate::app_paths::is_portable());
+    Ok(settings)
+}
+
+pub fn load_from(path: &Path) -> Result<Settings, String> {
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Check and write `settings`, replacing the file: `portable` switches the mode first (so the
+/// file lands in the new location) and a `stego_key` goes to the keychain.
+pub fn save(settings: &Settings) -> Result<(), String> {
+    settings.validate()?;
+    let mut settings = settings.clone();
+    if let Some(on) = settings.portable.take() {
+        crate::app_paths::set_portable(on)?;
+    }
+    if let Some(password) = settings.stego_key.take() {
+        let name = settings.stego_key_ref.get_or_insert_with(|| DEFAULT_STEGO_KEY_REF.to_string());
+        crate::keystore::store_stego_key(name, &password)?;
+    }
+    save_to(&config_path()?, &settings)
+}
+
+pub fn save_to(path: &Path, settings: &Settings) -> Result<(), String> {
+    settings.validate()?;
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let text = toml::to_string(settings).map_err(|e| e.to_string())?;
+    crate::atomic_write::write(path, text.as_bytes(), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_round_trip_and_validate() {
+        let dir = std::env::temp_dir().join(format!("stegstr_config_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stegstr").join(CONFIG_FILE);
+        assert_eq!(load_from(&path).unwrap(), Settings::default());
+
+        let settings = Settings {
+            method: Some("dot".to_string()),
+            redundancy: Some(3),
+            stego_key_ref: Some("work".to_string()),
+            relays: vec!["wss://relay.example".to_string()],
+            output_dir: Some(dir.to_string_lossy().into_owned()),
+            notify: Some(false),
+            ..Default::default()
+        };
+        save_to(&path, &settings).unwrap();
+        assert_eq!(load_from(&path).unwrap(), settings);
+        // Neither a password nor the portable flag reaches the file.
+        let secret = Settings { stego_key: Some("correct horse".to_string()), portable: Some(true), ..settings.clone() };
+        save_to(&path, &secret).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("correct horse") && !text.contains("portable"), "{}", text);
+        // Files from before the keychain still give their key.
+        std::fs::write(&path, "stego_key = \"old horse\"\n").unwrap();
+        assert_eq!(load_from(&path).unwrap().stego_key_password().unwrap().as_deref(), Some("old horse"));
+        assert_eq!(settings.output_path("out.png"), dir.join("out.png"));
+        assert_eq!(settings.output_path("sub/out.png"), Path::new("sub/out.png"));
+
+        assert!(save_to(&path, &Settings { method: Some("nope".to_string()), ..Default::default() }).unwrap_err().starts_with("method"));
+        assert!(Settings { relays: vec!["https://x".to_string()], ..Default::default() }.validate().is_err());
+        std::fs::write(&path, "colour = 1\n").unwrap();
+        assert!(load_from(&path).is_err());
+        assert_eq!(Settings::default().relays_or_default(), crate::relay::default_relays());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_config_follows_an_explicit_data_dir() {
+        let dir = std::env::temp_dir().join(format!("stegstr_config_data_{}", std::process::id()));
+        std::env::remove_var(CONFIG_ENV);
+        std::env::set_var(crate::app_paths::DATA_DIR_ENV, &dir);
+        let path = config_path();
+        std::env::remove_var(crate::app_paths::DATA_DIR_ENV);
+        assert_eq!(path.unwrap(), dir.join(CONFIG_FILE));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
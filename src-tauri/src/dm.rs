@@ -0,0 +1,245 @@
+// Encrypted direct messages: sign -> NIP-44 encrypt -> bundle -> optional embed.
+// Kind 4: the NIP-44 ciphertext as content with a "p" tag for the recipient (sender and time are
+// public). Kind 14 (NIP-17): the message is an unsigned kind 14 "rumor", sealed in a kind 13 signed
+// by the sender and gift-wrapped in a kind 1059 signed by a one-time key, both backdated up to two
+// days, so only the recipient learns who wrote it and when. Only the recipient's wrap is made, so
+// the sender cannot reopen a kind 14 message later.
+
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::nostr_event::{self, Bundle, NostrEvent};
+use crate::{nostr_codes, nostr_verify, payload_header, stego_crypto, targets};
+
+pub const KIND_DM: u32 = 4;
+pub const KIND_PRIVATE: u32 = 14;
+const KIND_SEAL: u32 = 13;
+const KIND_GIFT_WRAP: u32 = 1059;
+/// NIP-17 backdates seals and wraps by a random amount up to two days.
+const MAX_BACKDATE_SECS: u64 = 2 * 24 * 3600;
+
+pub struct DmRequest<'a> {
+    pub message: &'a str,
+    /// Recipient npub or hex pubkey.
+    pub recipient: &'a str,
+    /// KIND_DM or KIND_PRIVATE.
+    pub kind: u32,
+    pub sender_sk: &'a secp256k1::SecretKey,
+    /// Embed the bundle into this cover, written to `output_path`.
+    pub cover_path: Option<&'a Path>,
+    pub output_path: Option<&'a Path>,
+    /// Replace an existing output file instead of failing.
+    pub overwrite: bool,
+}
+
+/// IPC options for send_dm.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DmOptions {
+    pub message: String,
+    /// Recipient npub or 64-char hex pubkey.
+    pub recipient: String,
+    /// Sender secret key, 64-char hex or nsec.
+    #[schemars(regex(pattern = r"^([0-9a-fA-F]{64}|nsec1[02-9ac-hj-np-z]{58})$"))]
+    pub privkey_hex: String,
+    /// 4 (default) or 14 (NIP-17 gift wrap).
+    #[serde(default)]
+    pub kind: Option<u32>,
+    /// Embed the bundle into this cover; requires output_path.
+    #[serde(default)]
+    pub cover_path: Option<String>,
+    #[serde(default)]
+    pub output_path: Option<String>,
+    /// Replace an existing output file (default false: it is an error).
+    #[serde(default)]
+    pub overwrite: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct DmResult {
+    pub ok: bool,
+    /// Id of the event in the bundle (the gift wrap for kind 14).
+    pub event_id: Option<String>,
+    pub kind: u32,
+    pub sender_pubkey: Option<String>,
+    pub sender_npub: Option<String>,
+    pub recipient_pubkey: Option<String>,
+    pub recipient_npub: Option<String>,
+    pub bundle: Option<Bundle>,
+    /// Codec the bundle was embedded with, when a cover was given.
+    pub method: Option<String>,
+    pub payload_bytes: Option<usize>,
+    pub path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// An opened direct message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DirectMessage {
+    pub kind: u32,
+    /// Hex pubkeys.
+    pub sender: String,
+    pub recipient: String,
+    pub content: String,
+    /// When the message was written (the rumor's time for kind 14).
+    pub created_at: u64,
+}
+
+fn now() -> Result<u64, String> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?.as_secs())
+}
+
+fn backdated(now: u64) -> u64 {
+    now.saturating_sub(rand::thread_rng().gen_range(0..MAX_BACKDATE_SECS))
+}
+
+fn p_tag(event_tags: &[Vec<String>]) -> Option<String> {
+    event_tags.iter().find(|t| t.len() >= 2 && t[0] == "p").map(|t| t[1].clone())
+}
+
+/// Build the signed event carrying `message` to `recipient` (hex): a kind 4 event, or for
+/// KIND_PRIVATE the kind 1059 gift wrap.
+pub fn create_dm_event(kind: u32, recipient: &str, message: &str, sk: &secp256k1::SecretKey) -> Result<NostrEvent, String> {
+    let p = vec![vec!["p".to_string(), recipient.to_string()]];
+    match kind {
+        KIND_DM => nostr_event::sign_event(KIND_DM, p, &stego_crypto::nip44_encrypt(message, sk, recipient)?, sk),
+        KIND_PRIVATE => {
+            let now = now()?;
+            let sender = nostr_event::pubkey_hex(sk);
+            let rumor = serde_json::json!({
+                "id": nostr_event::event_id(&sender, now, KIND_PRIVATE, &p, message)?,
+                "pubkey": sender,
+                "created_at": now,
+                "kind": KIND_PRIVATE,
+                "tags": p,
+                "content": message,
+            });
+            let sealed = stego_crypto::nip44_encrypt(&rumor.to_string(), sk, recipient)?;
+            let seal = nostr_event::sign_event_at(KIND_SEAL, vec![], &sealed, backdated(now), sk)?;
+            let wrap_sk = secp256k1::SecretKey::new(&mut rand::thread_rng());
+            let seal_json = serde_json::to_string(&seal).map_err(|e| e.to_string())?;
+            let wrapped = stego_crypto::nip44_encrypt(&seal_json, &wrap_sk, recipient)?;
+            nostr_event::sign_event_at(KIND_GIFT_WRAP, p, &wrapped, backdated(now), &wrap_sk)
+        }
+        _ => Err(format!("Direct messages are kind {} or {}, not {}", KIND_DM, KIND_PRIVATE, kind)),
+    }
+}
+
+/// Decrypt a kind 4 event (NIP-44 or legacy NIP-04 content) sent by or to us, or a kind 1059 gift
+/// wrap addressed to us. Every signed layer is verified; a seal must be signed by the rumor's author.
+pub fn open_dm(event: &NostrEvent, sk: &secp256k1::SecretKey) -> Result<DirectMessage, String> {
+    nostr_verify::verify_event(event)?;
+    let ours = nostr_event::pubkey_hex(sk);
+    match event.kind {
+        KIND_DM => {
+            let recipient = p_tag(&event.tags).ok_or("Direct message has no p tag")?;
+            let other = if event.pubkey == ours { &recipient } else { &event.pubkey };
+            let content = if event.content.contains("?iv=") {
+                stego_crypto::nip04_decrypt(&event.content, sk, other)?
+            } else {
+                stego_crypto::nip44_decrypt(&event.content, sk, other)?
+            };
+            Ok(DirectMessage {
+                kind: KIND_DM,
+                sender: event.pubkey.clone(),
+                recipient,
+                content,
+                created_at: event.created_at,
+            })
+        }
+        KIND_GIFT_WRAP => {
+            let seal_json = stego_crypto::nip44_decrypt(&event.content, sk, &event.pubkey)?;
+            let seal: NostrEvent = serde_json::from_str(&seal_json).map_err(|e| format!("Invalid seal: {}", e))?;
+            if seal.kind != KIND_SEAL {
+                return Err(format!("Gift wrap holds kind {}, not a seal", seal.kind));
+            }
+            nostr_verify::verify_event(&seal).map_err(|e| format!("Invalid seal: {}", e))?;
+            let rumor_json = stego_crypto::nip44_decrypt(&seal.content, sk, &seal.pubkey)?;
+            let rumor: serde_json::Value = serde_json::from_str(&rumor_json).map_err(|e| format!("Invalid message: {}", e))?;
+            let field = |name: &str| rumor.get(name).cloned().ok_or_else(|| format!("Message has no {}", name));
+            let sender: String = serde_json::from_value(field("pubkey")?).map_err(|e| e.to_string())?;
+            if sender != seal.pubkey {
+                return Err("Message author does not match the seal's signer".to_string());
+            }
+            let tags: Vec<Vec<String>> = serde_json::from_value(field("tags")?).map_err(|e| e.to_string())?;
+            Ok(DirectMessage {
+                kind: serde_json::from_value(field("kind")?).map_err(|e| e.to_string())?,
+                sender,
+                recipient: p_tag(&tags).unwrap_or(ours),
+                content: serde_json::from_value(field("content")?).map_err(|e| e.to_string())?,
+                created_at: serde_json::from_value(field("created_at")?).map_err(|e| e.to_string())?,
+            })
+        }
+        other => Err(format!("Kind {} is not a direct message", other)),
+    }
+}
+
+/// Run the whole pipeline. Failures after signing still report the event id in the result.
+pub fn send_dm(req: &DmRequest) -> DmResult {
+    let mut result = DmResult {
+        kind: req.kind,
+        ..Default::default()
+    };
+    if let Err(e) = send_dm_inner(req, &mut result) {
+        result.error = Some(e);
+    }
+    result
+}
+
+fn send_dm_inner(req: &DmRequest, result: &mut DmResult) -> Result<(), String> {
+    let recipient = nostr_codes::parse_pubkey(req.recipient)?;
+    result.recipient_npub = nostr_codes::npub_from_hex(&recipient).ok();
+    result.recipient_pubkey = Some(recipient.clone());
+    let sender = nostr_event::pubkey_hex(req.sender_sk);
+    result.sender_npub = nostr_codes::npub_from_hex(&sender).ok();
+    result.sender_pubkey = Some(sender);
+
+    let event = create_dm_event(req.kind, &recipient, req.message, req.sender_sk)?;
+    result.event_id = Some(event.id.clone());
+    let bundle = Bundle::new(vec![event]);
+
+    if let Some(cover) = req.cover_path {
+        let output = req.output_path.ok_or("Embedding a direct message needs an output path")?;
+        // The content is already end-to-end encrypted; the bundle goes in as plain JSON.
+        let json = serde_json::to_vec(&bundle).map_err(|e| e.to_string())?;
+        let payload = payload_header::PayloadHeader::new(payload_header::MIME_JSON).write(&json)?;
+        result.payload_bytes = Some(payload.len());
+        let (png, method) = targets::embed(cover, &payload, None, None, None)?;
+        result.method = Some(method.to_string());
+        crate::atomic_write::write(output, &png, req.overwrite)?;
+        result.path = Some(output.to_string_lossy().to_string());
+    }
+    result.bundle = Some(bundle);
+    result.ok = true;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dm_kinds_open_for_recipient_only() {
+        let sender = secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let recipient = secp256k1::SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let outsider = secp256k1::SecretKey::from_slice(&[0x33; 32]).unwrap();
+        let to = nostr_event::pubkey_hex(&recipient);
+        let from = nostr_event::pubkey_hex(&sender);
+        for kind in [KIND_DM, KIND_PRIVATE] {
+            let event = create_dm_event(kind, &to, "meet at noon", &sender).unwrap();
+            let dm = open_dm(&event, &recipient).unwrap();
+            assert_eq!((dm.kind, dm.sender.as_str(), dm.recipient.as_str(), dm.content.as_str()), (kind, from.as_str(), to.as_str(), "meet at noon"));
+            assert!(open_dm(&event, &outsider).is_err());
+        }
+        // Kind 4 reopens for its sender; the gift wrap hides the sender.
+        let dm = create_dm_event(KIND_DM, &to, "hi", &sender).unwrap();
+        assert_eq!(open_dm(&dm, &sender).unwrap().content, "hi");
+        let wrap = create_dm_event(KIND_PRIVATE, &to, "hi", &sender).unwrap();
+        assert_eq!(wrap.kind, KIND_GIFT_WRAP);
+        assert_ne!(wrap.pubkey, from);
+        assert!(create_dm_event(1, &to, "hi", &sender).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+// Image downloads for decode-url: resumable over HTTP Range, size-capped, and checked against a
+// SHA-256 when the link carries one (`#sha256=<hex>`, `#x=<hex>` or a bare 64-hex fragment) or
+// NIP-94 file metadata (kind 1063: "url", "x", "size" tags) is given.
+// Partial bytes are kept in `<dest>.part` between attempts, so a flaky link resumes where it
+// stopped instead of starting over.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::nostr_event::NostrEvent;
+
+pub const DEFAULT_MAX_BYTES: u64 = 32 * 1024 * 1024;
+pub const DEFAULT_RETRIES: usize = 4;
+const NIP94_KIND: u32 = 1063;
+
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Hex SHA-256 the finished file must match.
+    pub sha256: Option<String>,
+    /// Exact size the finished file must have.
+    pub size: Option<u64>,
+    pub max_bytes: u64,
+    /// Extra attempts after a dropped connection, each resuming from the bytes already saved.
+    pub retries: usize,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            sha256: None,
+            size: None,
+            max_bytes: DEFAULT_MAX_BYTES,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DownloadReport {
+    pub path: String,
+    pub bytes: u64,
+    pub sha256: String,
+    /// True when the file matched an expected hash.
+    pub verified: bool,
+    /// Times the download resumed from a partial file.
+    pub resumed: usize,
+}
+
+/// NIP-94 file metadata relevant to a download.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileMetadata {
+    pub url: Option<String>,
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+}
+
+impl FileMetadata {
+    pub fn from_event(event: &NostrEvent) -> Result<Self, String> {
+        if event.kind != NIP94_KIND {
+            return Err(format!("expected a kind {} file metadata event, got kind {}", NIP94_KIND, event.kind));
+        }
+        let tag = |name: &str| event.tags.iter().find(|t| t.first().map(String::as_str) == Some(name)).and_then(|t| t.get(1)).cloned();
+        Ok(FileMetadata {
+            url: tag("url"),
+            sha256: tag("x"),
+            size: tag("size").and_then(|s| s.parse().ok()),
+        })
+    }
+}
+
+fn is_sha256_hex(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Split a hash fragment off `url`: returns the URL to fetch and the expected SHA-256, if any.
+pub fn split_hash_fragment(url: &str) -> (String, Option<String>) {
+    let Some((base, fragment)) = url.split_once('#') else {
+        return (url.to_string(), None);
+    };
+    let hash = fragment
+        .split('&')
+        .map(|part| part.split_once('=').map_or(part, |(k, v)| if k == "sha256" || k == "x" { v } else { "" }))
+        .find(|v| is_sha256_hex(v))
+        .map(str::to_ascii_lowercase);
+    match hash {
+        Some(h) => (base.to_string(), Some(h)),
+        None => (url.to_string(), None),
+    }
+}
+
+fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+enum Attempt {
+    Complete,
+    /// Connection dropped mid-body; the partial file is kept.
+    Interrupted(String),
+}
+
+/// One GET, resuming from `part`'s current length. Appends what arrives to `part`.
+fn attempt(url: &str, part: &Path, max_bytes: u64) -> Result<Attempt, String> {
+    let have = std::fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+    let mut req = ureq::get(url).timeout(Duration::from_secs(60));
+    if have > 0 {
+        req = req.set("Range", &format!("bytes={}-", have));
+    }
+    let resp = match req.call() {
+        Ok(r) => r,
+        // The part file already holds everything.
+        Err(ureq::Error::Status(416, _)) if have > 0 => return Ok(Attempt::Complete),
+        Err(ureq::Error::Status(code, _)) => return Err(format!("GET {}: HTTP {}", url, code)),
+        Err(e) => return Ok(Attempt::Interrupted(e.to_string())),
+    };
+    // 206 continues the part file; 200 means the server ignored Range, so start over.
+    let append = resp.status() == 206;
+    let start = if append { have } else { 0 };
+    if let Some(len) = resp.header("Content-Length").and_then(|v| v.parse::<u64>().ok()) {
+        if start + len > max_bytes {
+            return Err(format!("{} is {} bytes, over the {} byte limit", url, start + len, max_bytes));
+        }
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(part)
+        .map_err(|e| e.to_string())?;
+    let mut reader = resp.into_reader().take(max_bytes - start + 1);
+    let mut written = start;
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => return Ok(Attempt::Interrupted(e.to_string())),
+        };
+        written += n as u64;
+        if written > max_bytes {
+            drop(file);
+            let _ = std::fs::remove_file(part);
+            return Err(format!("{} exceeds the {} byte limit", url, max_bytes));
+        }
+        file.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+    }
+    Ok(Attempt::Complete)
+}
+
+/// Download `url` to `dest`, resuming and verifying per `opts`. A hash or size mismatch deletes
+/// the partial file so the next run starts clean.
+pub fn download(url: &str, dest: &Path, opts: &DownloadOptions) -> Result<DownloadReport, String> {
+    let part = PathBuf::from(format!("{}.part", dest.display()));
+    let mut resumed = 0;
+    let mut tries = 0;
+    loop {
+        if std::fs::metadata(&part).is_ok_and(|m| m.len() > 0) {
+            resumed += 1;
+        }
+        match attempt(url, &part, opts.max_bytes)? {
+            Attempt::Complete => break,
+            Attempt::Interrupted(e) if tries >= opts.retries => {
+                return Err(format!("GET {}: {} (gave up after {} attempts; partial file kept for resume)", url, e, tries + 1));
+            }
+            Attempt::Interrupted(_) => {
+                tries += 1;
+                std::thread::sleep(Duration::from_millis(250 * tries as u64));
+            }
+        }
+    }
+    let bytes = std::fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+    let sha256 = sha256_file(&part)?;
+    if let Some(size) = opts.size.filter(|&s| s != bytes) {
+        let _ = std::fs::remove_file(&part);
+        return Err(format!("{}: size mismatch (expected {} bytes, got {})", url, size, bytes));
+    }
+    if let Some(expected) = opts.sha256.as_deref().filter(|e| !e.eq_ignore_ascii_case(&sha256)) {
+        let _ = std::fs::remove_file(&part);
+        return Err(format!("{}: hash mismatch (expected {}, got {})", url, expected, sha256));
+    }
+    std::fs::rename(&part, dest).map_err(|e| e.to_string())?;
+    Ok(DownloadReport {
+        path: dest.to_string_lossy().to_string(),
+        bytes,
+        sha256,
+        verified: opts.sha256.is_some(),
+        resumed,
+    })
+}
+
+/// Cache path for `url` under <data dir>/downloads (stable per URL, so retries find the part file).
+pub fn cache_path(url: &str) -> Result<PathBuf, String> {
+    let dir = crate::app_paths::data_path("downloads")?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let name = hex::encode(&Sha256::digest(url.as_bytes())[..12]);
+    Ok(dir.join(name))
+}
+
+/// Download into the cache and give the file its image extension (decoders pick the format from
+/// it), sniffed from the content.
+pub fn download_to_cache(url: &str, opts: &DownloadOptions) -> Result<DownloadReport, String> {
+    let mut report = download(url, &cache_path(url)?, opts)?;
+    let mut head = [0u8; 32];
+    let n = std::fs::File::open(&report.path).and_then(|mut f| f.read(&mut head)).map_err(|e| e.to_string())?;
+    if let Some(ext) = image::guess_format(&head[..n]).ok().and_then(|f| f.extensions_str().first()) {
+        let named = format!("{}.{}", report.path, ext);
+        std::fs::rename(&report.path, &named).map_err(|e| e.to_string())?;
+        report.path = named;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// Serves `body`; the first response is cut off halfway, later ones honour Range.
+    fn flaky_server(body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/img.png", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for (n, stream) in listener.incoming().take(2).enumerate() {
+                let mut stream = stream.unwrap();
+                let mut req = [0u8; 2048];
+                let len = stream.read(&mut req).unwrap();
+                let req = String::from_utf8_lossy(&req[..len]).to_ascii_lowercase();
+                let from: usize = req
+                    .split("range: bytes=")
+                    .nth(1)
+                    .and_then(|r| r.split('-').next())
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(0);
+                let status = if from > 0 { "206 Partial Content" } else { "200 OK" };
+                let head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, body.len() - from);
+                stream.write_all(head.as_bytes()).unwrap();
+                let end = if n == 0 { body.len() / 2 } else { body.len() };
+                stream.write_all(&body[from..end]).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_download_resumes_and_verifies() {
+        let body: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let hash = hex::encode(Sha256::digest(&body));
+        let url = flaky_server(body.clone());
+        let (fetch_url, expected) = split_hash_fragment(&format!("{}#sha256={}", url, hash));
+        assert_eq!((fetch_url.as_str(), expected.as_deref()), (url.as_str(), Some(hash.as_str())));
+
+        let dest = std::env::temp_dir().join(format!("stegstr_download_{}.png", std::process::id()));
+        let opts = DownloadOptions {
+            sha256: expected,
+            ..Default::default()
+        };
+        let report = download(&fetch_url, &dest, &opts).unwrap();
+        assert!(report.verified);
+        assert!(report.resumed >= 1);
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        let _ = std::fs::remove_file(dest);
+    }
+}
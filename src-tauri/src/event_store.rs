@@ -0,0 +1,214 @@
+// Local store of events decoded from images, so the app can build a timeline across every image
+// it has opened instead of forgetting each decode. Events are signature-checked before they are
+// kept and deduplicated by id; each record notes the image it came from. Records live in a SQLite
+// database at <data dir>/events.db, indexed on author, kind and created_at so filters run in SQL.
+// Queries take a NIP-01 filter and return newest first. Decode commands feed events in as the
+// bundle stream parser reaches them (event_stream), so partial payloads still populate it.
+
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::nostr_event::NostrEvent;
+use crate::relay::Filter;
+
+const EVENTS_DB: &str = "events.db";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    id TEXT PRIMARY KEY,
+    pubkey TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    kind INTEGER NOT NULL,
+    tags TEXT NOT NULL,
+    content TEXT NOT NULL,
+    sig TEXT NOT NULL,
+    source TEXT,
+    stored_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_pubkey ON events (pubkey);
+CREATE INDEX IF NOT EXISTS events_kind ON events (kind);
+CREATE INDEX IF NOT EXISTS events_created_at ON events (created_at);
+";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StoredEvent {
+    pub event: NostrEvent,
+    /// Image (or other origin) the event was decoded from.
+    pub source: Option<String>,
+    /// Unix time the event was first stored.
+    pub stored_at: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StoreReport {
+    pub added: usize,
+    /// Already stored (or repeated in the input).
+    pub duplicates: usize,
+    /// Failed the id or signature check.
+    pub invalid: usize,
+}
+
+impl std::ops::AddAssign for StoreReport {
+    fn add_assign(&mut self, other: StoreReport) {
+        self.added += other.added;
+        self.duplicates += other.duplicates;
+        self.invalid += other.invalid;
+    }
+}
+
+/// Open database of stored events.
+pub struct EventStore {
+    conn: Connection,
+}
+
+impl EventStore {
+    /// The app's store, <data dir>/events.db.
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&crate::app_paths::data_path(EVENTS_DB)?)
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        // Decodes running in parallel (batch, watcher) wait for each other's writes instead of failing.
+        conn.busy_timeout(Duration::from_secs(5)).map_err(|e| e.to_string())?;
+        conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+        Ok(EventStore { conn })
+    }
+
+    /// Store the valid events not stored yet, recording `source`.
+    pub fn add(&mut self, events: &[NostrEvent], source: Option<&str>) -> Result<StoreReport, String> {
+        let stored_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mut report = StoreReport::default();
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        {
+            let mut insert = tx
+                .prepare(
+                    "INSERT OR IGNORE INTO events (id, pubkey, created_at, kind, tags, content, sig, source, stored_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                )
+                .map_err(|e| e.to_string())?;
+            for event in events {
+                if crate::nostr_verify::verify_event(event).is_err() {
+                    report.invalid += 1;
+                    continue;
+                }
+                let tags = serde_json::to_string(&event.tags).map_err(|e| e.to_string())?;
+                let inserted = insert
+                    .execute(rusqlite::params![
+                        event.id,
+                        event.pubkey,
+                        event.created_at as i64,
+                        event.kind,
+                        tags,
+                        event.content,
+                        event.sig,
+                        source,
+                        stored_at as i64,
+                    ])
+                    .map_err(|e| e.to_string())?;
+                if inserted == 0 {
+                    report.duplicates += 1;
+                } else {
+                    report.added += 1;
+                }
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(report)
+    }
+
+    /// Stored events matching `filter` (ids, authors, kinds, since/until on created_at), newest
+    /// first, at most `filter.limit`.
+    pub fn query(&self, filter: &Filter) -> Result<Vec<StoredEvent>, String> {
+        let mut clauses = vec!["1".to_string()];
+        let mut params: Vec<Value> = Vec::new();
+        any_of("id", filter.ids.iter().cloned().map(Value::Text).collect(), &mut clauses, &mut params);
+        any_of("pubkey", filter.authors.iter().cloned().map(Value::Text).collect(), &mut clauses, &mut params);
+        any_of("kind", filter.kinds.iter().map(|&k| Value::Integer(k.into())).collect(), &mut clauses, &mut params);
+        if let Some(since) = filter.since {
+            clauses.push("created_at >= ?".to_string());
+            params.push(Value::Integer(since as i64));
+        }
+        if let Some(until) = filter.until {
+            clauses.push("created_at <= ?".to_string());
+            params.push(Value::Integer(until as i64));
+        }
+        // LIMIT -1 is SQLite for no limit.
+        params.push(Value::Integer(filter.limit.map_or(-1, i64::from)));
+        let sql = format!(
+            "SELECT id, pubkey, created_at, kind, tags, content, sig, source, stored_at FROM events
+             WHERE {} ORDER BY created_at DESC, id ASC LIMIT ?",
+            clauses.join(" AND ")
+        );
+        let mut stmt = self.conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params_from_iter(params), |row| {
+                let tags: String = row.get(4)?;
+                Ok(StoredEvent {
+                    event: NostrEvent {
+                        id: row.get(0)?,
+                        pubkey: row.get(1)?,
+                        created_at: row.get::<_, i64>(2)? as u64,
+                        kind: row.get(3)?,
+                        tags: serde_json::from_str(&tags).unwrap_or_default(),
+                        content: row.get(5)?,
+                        sig: row.get(6)?,
+                    },
+                    source: row.get(7)?,
+                    stored_at: row.get::<_, i64>(8)? as u64,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+}
+
+/// `column IN (?, ...)` for a non-empty filter list.
+fn any_of(column: &str, values: Vec<Value>, clauses: &mut Vec<String>, params: &mut Vec<Value>) {
+    if !values.is_empty() {
+        clauses.push(format!("{} IN ({})", column, vec!["?"; values.len()].join(", ")));
+        params.extend(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr_event::sign_event_at;
+
+    #[test]
+    fn test_store_dedupes_and_queries_newest_first() {
+        let path = std::env::temp_dir().join(format!("stegstr_events_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let alice = secp256k1::SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let bob = secp256k1::SecretKey::from_slice(&[4u8; 32]).unwrap();
+        let a1 = sign_event_at(1, vec![vec!["t".to_string(), "stegstr".to_string()]], "a1", 100, &alice).unwrap();
+        let a2 = sign_event_at(0, vec![], "{}", 300, &alice).unwrap();
+        let b1 = sign_event_at(1, vec![], "b1", 200, &bob).unwrap();
+        let mut forged = b1.clone();
+        forged.content = "forged".to_string();
+
+        let mut store = EventStore::open(&path).unwrap();
+        let report = store.add(&[a1.clone(), b1.clone(), a1.clone(), forged], Some("one.png")).unwrap();
+        assert_eq!(report, StoreReport { added: 2, duplicates: 1, invalid: 1 });
+        let report = store.add(&[b1.clone(), a2.clone()], Some("two.png")).unwrap();
+        assert_eq!((report.added, report.duplicates), (1, 1));
+
+        let all = store.query(&Filter::default()).unwrap();
+        assert_eq!(all.iter().map(|s| s.event.content.as_str()).collect::<Vec<_>>(), ["{}", "b1", "a1"]);
+        assert_eq!(all[1].source.as_deref(), Some("one.png"));
+
+        let by_alice = Filter { authors: vec![a1.pubkey.clone()], kinds: vec![1], ..Default::default() };
+        assert_eq!(store.query(&by_alice).unwrap()[0].event, a1);
+        let window = Filter { since: Some(150), until: Some(300), limit: Some(1), ..Default::default() };
+        assert_eq!(store.query(&window).unwrap().iter().map(|s| s.event.created_at).collect::<Vec<_>>(), [300]);
+        let by_id = Filter { ids: vec![b1.id.clone(), "00".repeat(32)], ..Default::default() };
+        assert_eq!(store.query(&by_id).unwrap().len(), 1);
+        drop(store);
+        let _ = std::fs::remove_file(path);
+    }
+}
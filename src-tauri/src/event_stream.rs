@@ -0,0 +1,134 @@
+// Incremental bundle parser: emits each complete event as soon as its JSON object closes,
+// so truncated or partially corrupt payloads still yield the events recovered so far.
+// Decode commands run each extracted bundle through it into the local event store (event_store).
+// Accepts `{ "version": 1, "events": [ {...}, ... ] }` or a bare `[ {...}, ... ]`.
+
+use crate::nostr_event::NostrEvent;
+
+#[derive(Debug, Default)]
+pub struct EventStreamParser {
+    /// Open containers ('{' or '['), outermost first.
+    stack: Vec<u8>,
+    in_string: bool,
+    escaped: bool,
+    /// Bytes of the event object currently being captured.
+    current: Option<Vec<u8>>,
+    /// Objects that closed but did not parse as events.
+    pub skipped: usize,
+}
+
+impl EventStreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Event objects sit at depth 2 in a bundle (object > array) or depth 1 in a bare array.
+    fn at_event_depth(&self) -> bool {
+        matches!(self.stack.as_slice(), [b'{', b'['] | [b'['])
+    }
+
+    /// Feed the next chunk; returns events completed within it.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<NostrEvent> {
+        let mut out = Vec::new();
+        self.feed_each(chunk, |ev| out.push(ev));
+        out
+    }
+
+    /// Feed the next chunk, handing each event to `on_event` the moment its object closes.
+    pub fn feed_each(&mut self, chunk: &[u8], mut on_event: impl FnMut(NostrEvent)) {
+        for &b in chunk {
+            if let Some(buf) = self.current.as_mut() {
+                buf.push(b);
+            }
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+            match b {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => {
+                    if b == b'{' && self.current.is_none() && self.at_event_depth() {
+                        self.current = Some(vec![b'{']);
+                    }
+                    self.stack.push(b);
+                }
+                b'}' | b']' => {
+                    self.stack.pop();
+                    if b == b'}' && self.at_event_depth() {
+                        if let Some(buf) = self.current.take() {
+                            match serde_json::from_slice::<NostrEvent>(&buf) {
+                                Ok(ev) => on_event(ev),
+                                Err(_) => self.skipped += 1,
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// True while an event object is open (its bytes have not all arrived).
+    pub fn has_partial_event(&self) -> bool {
+        self.current.is_some()
+    }
+}
+
+/// Parse whatever complete events exist in `bytes`, ignoring a truncated tail.
+pub fn salvage_events(bytes: &[u8]) -> Vec<NostrEvent> {
+    EventStreamParser::new().feed(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_salvage_truncated_bundle() {
+        let ev = r#"{"id":"a","pubkey":"b","created_at":1,"kind":1,"tags":[["t","x}"]],"content":"hi \"}\" there","sig":"c"}"#;
+        let bundle = format!(r#"{{"version":1,"events":[{},{}]}}"#, ev, ev);
+        let cut = &bundle.as_bytes()[..bundle.len() - 20];
+        let mut parser = EventStreamParser::new();
+        let mut events = Vec::new();
+        for chunk in cut.chunks(7) {
+            events.extend(parser.feed(chunk));
+        }
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].content, "hi \"}\" there");
+        assert!(parser.has_partial_event());
+        assert_eq!(salvage_events(bundle.as_bytes()).len(), 2);
+    }
+
+    #[test]
+    fn test_feed_each_stores_events_as_they_close() {
+        let path = std::env::temp_dir().join(format!("stegstr_stream_store_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let sk = secp256k1::SecretKey::from_slice(&[5u8; 32]).unwrap();
+        let events: Vec<NostrEvent> = (0..3)
+            .map(|i| crate::nostr_event::sign_event_at(1, vec![], &format!("note {}", i), 100 + i, &sk).unwrap())
+            .collect();
+        let bundle = serde_json::to_string(&crate::nostr_event::Bundle { version: 1, events }).unwrap();
+        // The payload breaks off inside the third event.
+        let cut = &bundle.as_bytes()[..bundle.len() - 40];
+
+        let mut store = crate::event_store::EventStore::open(&path).unwrap();
+        let mut parser = EventStreamParser::new();
+        let mut stored_per_chunk = Vec::new();
+        for chunk in cut.chunks(64) {
+            parser.feed_each(chunk, |ev| assert_eq!(store.add(&[ev], Some("cut.png")).unwrap().added, 1));
+            stored_per_chunk.push(store.query(&Default::default()).unwrap().len());
+        }
+        // Events reach the store while later bytes are still arriving.
+        assert_eq!(stored_per_chunk.iter().position(|&n| n == 1).map(|i| i < stored_per_chunk.len() - 1), Some(true));
+        assert_eq!(*stored_per_chunk.last().unwrap(), 2);
+        assert!(parser.has_partial_event());
+        drop(store);
+        let _ = std::fs::remove_file(path);
+    }
+}
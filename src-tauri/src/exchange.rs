@@ -0,0 +1,140 @@
+// Exchange folder version negotiation. Two Stegstr builds sharing an exchange folder write a
+// small manifest next to the artifacts saying which formats they used; before reading or writing,
+// the other side checks it and fails with a clear message instead of a confusing decode error.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{nostr_event, stego_crypto};
+
+/// Bump when exchange artifacts change in a way older builds cannot read.
+pub const EXCHANGE_PROTOCOL: u32 = 1;
+/// Oldest protocol whose artifacts this build can still read.
+pub const MIN_READ_PROTOCOL: u32 = 1;
+pub const MANIFEST_FILE: &str = "stegstr-exchange.json";
+const EXCHANGE_DIR_NAME: &str = "stegstr-test-exchange";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ExchangeManifest {
+    pub protocol: u32,
+    /// Oldest protocol a reader must speak to read these artifacts.
+    pub min_read_protocol: u32,
+    pub bundle_version: u32,
+    pub crypto_version: u8,
+    /// Stegstr version that wrote the folder, for the error message.
+    pub app_version: String,
+    pub written_at: u64,
+}
+
+impl ExchangeManifest {
+    pub fn current() -> Self {
+        ExchangeManifest {
+            protocol: EXCHANGE_PROTOCOL,
+            min_read_protocol: MIN_READ_PROTOCOL,
+            bundle_version: nostr_event::BUNDLE_VERSION,
+            crypto_version: stego_crypto::VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            written_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// Shared folder used by "Embed to exchange" / "Detect from exchange" (created if missing).
+pub fn exchange_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(EXCHANGE_DIR_NAME);
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+pub fn read_manifest(dir: &Path) -> Result<Option<ExchangeManifest>, String> {
+    let path = dir.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map(Some).map_err(|e| {
+        format!(
+            "Exchange manifest {} is unreadable ({}). It may come from a newer Stegstr; update this copy, or delete the file to start over.",
+            path.display(),
+            e
+        )
+    })
+}
+
+pub fn write_manifest(dir: &Path) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(&ExchangeManifest::current()).map_err(|e| e.to_string())?;
+    crate::atomic_write::write(&dir.join(MANIFEST_FILE), text.as_bytes(), true)
+}
+
+/// Check the folder's manifest against this build. No manifest means a build from before
+/// negotiation existed, which wrote protocol 1 artifacts.
+pub fn negotiate(dir: &Path) -> Result<(), String> {
+    let Some(theirs) = read_manifest(dir)? else {
+        return Ok(());
+    };
+    let ours = ExchangeManifest::current();
+    if theirs.min_read_protocol > EXCHANGE_PROTOCOL
+        || theirs.bundle_version > ours.bundle_version
+        || theirs.crypto_version != ours.crypto_version
+    {
+        return Err(format!(
+            "The exchange folder was written by Stegstr {} (exchange protocol {}), which this Stegstr {} (protocol {}) cannot read. Update Stegstr on this machine, or have the other side re-embed with a matching version.",
+            theirs.app_version, theirs.protocol, ours.app_version, EXCHANGE_PROTOCOL
+        ));
+    }
+    if theirs.protocol < MIN_READ_PROTOCOL {
+        return Err(format!(
+            "The exchange folder was written by Stegstr {} (exchange protocol {}), which is too old for this Stegstr {} (protocol {}). Ask the other side to update Stegstr and re-embed.",
+            theirs.app_version, theirs.protocol, ours.app_version, EXCHANGE_PROTOCOL
+        ));
+    }
+    Ok(())
+}
+
+/// After writing `output`, stamp the manifest if it landed in the exchange folder.
+pub fn stamp_if_exchange(output: &Path) -> Result<(), String> {
+    let (Some(parent), Ok(dir)) = (output.parent(), exchange_dir().canonicalize()) else {
+        return Ok(());
+    };
+    match parent.canonicalize() {
+        Ok(p) if p == dir => write_manifest(&dir),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_rejects_newer_protocol() {
+        let dir = std::env::temp_dir().join(format!("stegstr_exchange_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(negotiate(&dir).is_ok());
+        write_manifest(&dir).unwrap();
+        assert!(negotiate(&dir).is_ok());
+
+        let newer = ExchangeManifest {
+            protocol: EXCHANGE_PROTOCOL + 1,
+            min_read_protocol: EXCHANGE_PROTOCOL + 1,
+            app_version: "9.9.9".to_string(),
+            ..ExchangeManifest::current()
+        };
+        std::fs::write(dir.join(MANIFEST_FILE), serde_json::to_string(&newer).unwrap()).unwrap();
+        let err = negotiate(&dir).unwrap_err();
+        assert!(err.contains("9.9.9") && err.contains("Update Stegstr"), "{}", err);
+
+        // Newer writer that stays readable by older builds.
+        let compatible = ExchangeManifest {
+            protocol: EXCHANGE_PROTOCOL + 1,
+            ..ExchangeManifest::current()
+        };
+        std::fs::write(dir.join(MANIFEST_FILE), serde_json::to_string(&compatible).unwrap()).unwrap();
+        assert!(negotiate(&dir).is_ok());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,215 @@
+// External codec adapters: embedding methods implemented outside the crate, registered in
+// codecs.json and driven over one generic protocol. A codec is an executable that reads one
+// JSON request on stdin and writes one JSON response on stdout:
+//   {"protocol":1,"op":"encode","cover":P,"output":P,"payload":B64} -> {"ok":true}
+//   {"protocol":1,"op":"decode","image":P}                          -> {"ok":true,"payload":B64}
+//   {"protocol":1,"op":"capacity","cover":P}                        -> {"ok":true,"capacity":N}
+// Failures answer {"ok":false,"error":"..."}. Registered codecs take part in auto-detection
+// (scan order), capacity and `embed --method` like the built-in ones.
+
+use base64::Engine;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, OnceLock};
+use std::time::Duration;
+
+/// Path of the codecs config (default: <data dir>/codecs.json).
+pub const CODECS_CONFIG_ENV: &str = "STEGSTR_CODECS";
+const CONFIG_FILE: &str = "codecs.json";
+pub const PROTOCOL: u32 = 1;
+/// Names the built-in codecs already use.
+const RESERVED: &[&str] = &["dwt", "dot", "synth", "qim"];
+
+fn default_timeout() -> u64 {
+    30
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExternalCodec {
+    pub name: String,
+    /// Executable to run (looked up on PATH when not absolute).
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+    /// Try this codec when decoding images of unknown method.
+    #[serde(default = "default_true")]
+    pub auto_detect: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    codecs: Vec<ExternalCodec>,
+}
+
+pub fn config_path() -> Result<PathBuf, String> {
+    match std::env::var(CODECS_CONFIG_ENV) {
+        Ok(p) if !p.trim().is_empty() => Ok(PathBuf::from(p.trim())),
+        _ => crate::app_paths::data_path(CONFIG_FILE),
+    }
+}
+
+/// Parse and check a codecs config. A missing file means no external codecs.
+pub fn load_config(path: &Path) -> Result<Vec<ExternalCodec>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let config: Config = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut seen: Vec<&str> = Vec::new();
+    for c in &config.codecs {
+        if c.name.is_empty() || !c.name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_') {
+            return Err(format!("{}: codec name {:?} must be letters, digits, - or _", path.display(), c.name));
+        }
+        if RESERVED.contains(&c.name.as_str()) || seen.contains(&c.name.as_str()) {
+            return Err(format!("{}: codec name {} is already taken", path.display(), c.name));
+        }
+        seen.push(&c.name);
+    }
+    Ok(config.codecs)
+}
+
+static REGISTRY: OnceLock<Result<Vec<ExternalCodec>, String>> = OnceLock::new();
+
+/// Registered codecs, loaded once per process.
+pub fn registry() -> Result<&'static [ExternalCodec], String> {
+    REGISTRY
+        .get_or_init(|| config_path().and_then(|p| load_config(&p)))
+        .as_ref()
+        .map(Vec::as_slice)
+        .map_err(Clone::clone)
+}
+
+/// Registered codecs; a broken config counts as none (see `registry` for the error).
+pub fn codecs() -> &'static [ExternalCodec] {
+    registry().unwrap_or(&[])
+}
+
+pub fn find(name: &str) -> Option<&'static ExternalCodec> {
+    codecs().iter().find(|c| c.name == name)
+}
+
+/// Run one request through the codec and return its response (`ok` checked).
+fn call(codec: &ExternalCodec, request: Value) -> Result<Value, String> {
+    let mut child = Command::new(&codec.command)
+        .args(&codec.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{}: could not start {}: {}", codec.name, codec.command, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let line = format!("{}\n", request);
+        // A codec may exit without reading its input; the response decides.
+        let _ = stdin.write_all(line.as_bytes());
+    }
+    let (tx, rx) = mpsc::channel();
+    let pid = child.id();
+    std::thread::spawn(move || {
+        let _ = tx.send(child.wait_with_output());
+    });
+    let output = match rx.recv_timeout(Duration::from_secs(codec.timeout_secs)) {
+        Ok(out) => out.map_err(|e| format!("{}: {}", codec.name, e))?,
+        Err(_) => {
+            kill(pid);
+            return Err(format!("{}: timed out after {} seconds", codec.name, codec.timeout_secs));
+        }
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let response: Value = serde_json::from_str(stdout.trim()).map_err(|e| {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        format!("{}: invalid response ({}){}", codec.name, e, if stderr.trim().is_empty() { String::new() } else { format!(": {}", stderr.trim()) })
+    })?;
+    if response.get("ok").and_then(Value::as_bool) != Some(true) {
+        let error = response.get("error").and_then(Value::as_str).unwrap_or("codec reported failure");
+        return Err(format!("{}: {}", codec.name, error));
+    }
+    Ok(response)
+}
+
+fn kill(pid: u32) {
+    #[cfg(unix)]
+    let _ = Command::new("kill").arg("-9").arg(pid.to_string()).status();
+    #[cfg(windows)]
+    let _ = Command::new("taskkill").args(["/F", "/PID", &pid.to_string()]).status();
+}
+
+/// Encode `payload` into `cover`; returns the codec's output file bytes.
+pub fn encode(codec: &ExternalCodec, cover: &Path, payload: &[u8]) -> Result<Vec<u8>, String> {
+    let output = std::env::temp_dir().join(format!("stegstr-ext-{}-{}-{}", codec.name, std::process::id(), rand::random::<u32>()));
+    let result = call(
+        codec,
+        json!({
+            "protocol": PROTOCOL,
+            "op": "encode",
+            "cover": cover.to_string_lossy(),
+            "output": output.to_string_lossy(),
+            "payload": base64::engine::general_purpose::STANDARD.encode(payload),
+        }),
+    )
+    .and_then(|_| std::fs::read(&output).map_err(|e| format!("{}: no output file ({})", codec.name, e)));
+    let _ = std::fs::remove_file(&output);
+    result
+}
+
+pub fn decode(codec: &ExternalCodec, image: &Path) -> Result<Vec<u8>, String> {
+    let response = call(codec, json!({ "protocol": PROTOCOL, "op": "decode", "image": image.to_string_lossy() }))?;
+    let b64 = response.get("payload").and_then(Value::as_str).ok_or_else(|| format!("{}: response has no payload", codec.name))?;
+    base64::engine::general_purpose::STANDARD
+        .decode(b64.trim())
+        .map_err(|e| format!("{}: payload is not base64 ({})", codec.name, e))
+}
+
+pub fn capacity(codec: &ExternalCodec, cover: &Path) -> Result<usize, String> {
+    let response = call(codec, json!({ "protocol": PROTOCOL, "op": "capacity", "cover": cover.to_string_lossy() }))?;
+    response
+        .get("capacity")
+        .and_then(Value::as_u64)
+        .map(|c| c as usize)
+        .ok_or_else(|| format!("{}: response has no capacity", codec.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell_codec(script: &str) -> ExternalCodec {
+        ExternalCodec {
+            name: "test".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            description: String::new(),
+            timeout_secs: 5,
+            auto_detect: true,
+        }
+    }
+
+    #[test]
+    fn test_external_codec_protocol() {
+        let image = Path::new("/nonexistent.png");
+        let ok = shell_codec(r#"cat >/dev/null; echo '{"ok":true,"payload":"aGk=","capacity":42}'"#);
+        assert_eq!(decode(&ok, image).unwrap(), b"hi");
+        assert_eq!(capacity(&ok, image).unwrap(), 42);
+        let failing = shell_codec(r#"cat >/dev/null; echo '{"ok":false,"error":"no payload here"}'"#);
+        assert_eq!(decode(&failing, image).unwrap_err(), "test: no payload here");
+
+        let config = std::env::temp_dir().join(format!("stegstr_codecs_{}.json", std::process::id()));
+        std::fs::write(&config, r#"{"codecs":[{"name":"dot","command":"x"}]}"#).unwrap();
+        assert!(load_config(&config).unwrap_err().contains("already taken"));
+        std::fs::write(&config, r#"{"codecs":[{"name":"lsb-ext","command":"x"}]}"#).unwrap();
+        let codecs = load_config(&config).unwrap();
+        assert_eq!((codecs[0].timeout_secs, codecs[0].auto_detect), (30, true));
+        let _ = std::fs::remove_file(config);
+    }
+}
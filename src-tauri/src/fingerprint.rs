@@ -0,0 +1,121 @@
+// Visual and word fingerprints of a Nostr public key, so a user can check "this came from the
+// sender I expect" at a glance instead of comparing 64 hex characters. Both are derived from
+// sha256(domain || pubkey), so they are stable across devices and unrelated to the npub's own text.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::nostr_codes;
+
+const DOMAIN: &[u8] = b"stegstr-fingerprint-v1";
+pub const IDENTICON_SIZE: usize = 5;
+pub const WORD_COUNT: usize = 6;
+
+/// 256 short, distinct nouns: one word per digest byte.
+const WORDS: [&str; 256] = [
+    "acorn", "adobe", "agate", "alarm", "album", "amber", "anchor", "angle", "apple", "apron", "arrow", "aspen",
+    "atlas", "attic", "autumn", "axle", "badge", "bagel", "bamboo", "banjo", "barrel", "basil", "beacon", "beard",
+    "beetle", "bell", "berry", "bison", "blanket", "blossom", "bonnet", "boulder", "bramble", "brass", "bread", "breeze",
+    "brick", "bridge", "bronze", "broom", "bubble", "bucket", "buffalo", "button", "cabin", "cactus", "camel", "candle",
+    "canoe", "canyon", "carrot", "castle", "cedar", "cello", "chalk", "cherry", "chess", "chimney", "cider", "cinder",
+    "citrus", "clover", "cobalt", "comet", "compass", "copper", "coral", "cotton", "cougar", "crane", "crater", "cricket",
+    "crystal", "cupboard", "cypress", "daisy", "delta", "desert", "diamond", "dolphin", "dragon", "drum", "dune", "eagle",
+    "easel", "echo", "ember", "emerald", "engine", "falcon", "feather", "fern", "ferry", "fiddle", "flame", "flint",
+    "forest", "fossil", "fountain", "fox", "frost", "galaxy", "garden", "garnet", "geyser", "ginger", "glacier", "globe",
+    "goblet", "granite", "grape", "gravel", "harbor", "harp", "hazel", "heron", "hickory", "hill", "honey", "horizon",
+    "hornet", "husky", "iceberg", "igloo", "indigo", "iris", "island", "ivory", "jacket", "jade", "jaguar", "jasmine",
+    "jelly", "jigsaw", "jungle", "juniper", "kayak", "kettle", "kiwi", "koala", "ladder", "lagoon", "lantern", "lark",
+    "laurel", "lava", "lemon", "lichen", "lily", "linen", "lizard", "lobster", "locket", "lotus", "lynx", "magnet",
+    "mango", "maple", "marble", "meadow", "melon", "meteor", "mint", "mirror", "mitten", "monsoon", "moose", "mosaic",
+    "moss", "mountain", "mulberry", "nectar", "needle", "nest", "nickel", "nutmeg", "oak", "oasis", "ocean", "olive",
+    "onyx", "orbit", "orchid", "otter", "owl", "oyster", "paddle", "palm", "panda", "paper", "parrot", "pebble",
+    "pelican", "pepper", "pine", "planet", "plum", "pocket", "pony", "poppy", "prairie", "prism", "pumpkin", "quail",
+    "quartz", "quill", "rabbit", "radish", "raven", "reef", "ribbon", "river", "robin", "rocket", "ruby", "saddle",
+    "saffron", "salmon", "sapphire", "scarf", "shell", "sierra", "silver", "sparrow", "spruce", "squid", "star", "stone",
+    "summit", "sunset", "swan", "tablet", "tango", "teapot", "thistle", "thunder", "tiger", "timber", "topaz", "torch",
+    "tulip", "tundra", "turtle", "valley", "velvet", "violet", "walnut", "walrus", "willow", "wizard", "yarrow", "zebra",
+    "zenith", "zephyr", "fjord", "lemur",
+];
+
+/// Symmetric geometric identicon: a square grid mirrored left-right, plus colours.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Identicon {
+    /// Grid width and height in cells.
+    pub size: usize,
+    /// Row-major filled cells, `size * size` entries.
+    pub cells: Vec<bool>,
+    /// Foreground HSL.
+    pub hue: u16,
+    pub saturation: u8,
+    pub lightness: u8,
+    /// Background hue; background uses low saturation and high lightness.
+    pub background_hue: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Fingerprint {
+    pub pubkey: String,
+    pub npub: String,
+    pub words: Vec<String>,
+    pub identicon: Identicon,
+}
+
+fn identicon(digest: &[u8]) -> Identicon {
+    let half = IDENTICON_SIZE.div_ceil(2);
+    // Bits for the left half (including the middle column) come from bytes 8..; then mirror.
+    let bits: Vec<bool> = digest[8..].iter().flat_map(|b| (0..8).map(move |i| b >> i & 1 == 1)).collect();
+    let mut cells = vec![false; IDENTICON_SIZE * IDENTICON_SIZE];
+    for row in 0..IDENTICON_SIZE {
+        for col in 0..half {
+            let on = bits[row * half + col];
+            cells[row * IDENTICON_SIZE + col] = on;
+            cells[row * IDENTICON_SIZE + (IDENTICON_SIZE - 1 - col)] = on;
+        }
+    }
+    Identicon {
+        size: IDENTICON_SIZE,
+        cells,
+        hue: u16::from_be_bytes([digest[0], digest[1]]) % 360,
+        saturation: 45 + digest[2] % 40,
+        lightness: 35 + digest[3] % 25,
+        background_hue: u16::from_be_bytes([digest[4], digest[5]]) % 360,
+    }
+}
+
+/// Fingerprint for an npub or 64-char hex public key.
+pub fn fingerprint(pubkey: &str) -> Result<Fingerprint, String> {
+    let hex_key = nostr_codes::parse_pubkey(pubkey)?;
+    let key: [u8; 32] = hex::decode(&hex_key)
+        .map_err(|e| e.to_string())?
+        .try_into()
+        .map_err(|_| "Public key must be 32 bytes".to_string())?;
+    let digest = Sha256::new().chain_update(DOMAIN).chain_update(key).finalize();
+    Ok(Fingerprint {
+        npub: nostr_codes::npub_encode(&key)?,
+        pubkey: hex_key,
+        words: digest[..WORD_COUNT].iter().map(|&b| WORDS[b as usize].to_string()).collect(),
+        identicon: identicon(&digest),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_stable_and_symmetric() {
+        let hex_key = "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e";
+        let from_hex = fingerprint(hex_key).unwrap();
+        let from_npub = fingerprint(&from_hex.npub).unwrap();
+        assert_eq!(from_hex, from_npub);
+        assert_eq!(from_hex.words.len(), WORD_COUNT);
+        let n = from_hex.identicon.size;
+        for row in 0..n {
+            for col in 0..n {
+                assert_eq!(from_hex.identicon.cells[row * n + col], from_hex.identicon.cells[row * n + n - 1 - col]);
+            }
+        }
+        assert_ne!(fingerprint(&"1".repeat(64)).unwrap().words, from_hex.words);
+    }
+}
@@ -0,0 +1,295 @@
+// Standard cover corpus for bench/robustness runs, cached locally so results compare across machines.
+// Remote images are copied once and verified against pinned SHA-256 hashes (fixtures/manifest.json):
+// the bundled manifest names images in this repository by relative path, and a manifest given with
+// --manifest may add http(s) or file:// sources. Synthetic covers are generated deterministically,
+// so they need no download or hash.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_MANIFEST: &str = include_str!("../fixtures/manifest.json");
+/// Relative sources in the bundled manifest resolve against its directory in the source tree.
+const DEFAULT_MANIFEST_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+/// Override for the cache directory (default: <data dir>/fixtures).
+pub const FIXTURES_DIR_ENV: &str = "STEGSTR_FIXTURES_DIR";
+const MAX_DOWNLOAD_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteFixture {
+    pub name: String,
+    /// http(s) or file:// URL, or a path relative to the manifest's directory.
+    pub url: String,
+    /// Pinned hex SHA-256; None until `pin` has been run for this entry, which `fetch_remote` refuses.
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    /// Directory a relative `url` resolves against; set when the manifest is loaded.
+    #[serde(skip)]
+    pub base: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntheticFixture {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// noise | gradient | flat | checker
+    pub pattern: String,
+    /// png | jpeg | bmp
+    pub format: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub remote: Vec<RemoteFixture>,
+    pub synthetic: Vec<SyntheticFixture>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureStatus {
+    pub name: String,
+    pub path: String,
+    pub sha256: String,
+}
+
+impl Manifest {
+    fn with_base(mut self, base: &Path) -> Self {
+        for f in &mut self.remote {
+            f.base = Some(base.to_path_buf());
+        }
+        self
+    }
+}
+
+pub fn default_manifest() -> Manifest {
+    let manifest: Manifest = serde_json::from_str(DEFAULT_MANIFEST).expect("bundled fixtures manifest is valid JSON");
+    manifest.with_base(Path::new(DEFAULT_MANIFEST_DIR))
+}
+
+pub fn load_manifest(path: Option<&Path>) -> Result<Manifest, String> {
+    match path {
+        Some(p) => {
+            let text = std::fs::read_to_string(p).map_err(|e| e.to_string())?;
+            let manifest: Manifest = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+            Ok(manifest.with_base(p.parent().unwrap_or(Path::new("."))))
+        }
+        None => Ok(default_manifest()),
+    }
+}
+
+pub fn cache_dir() -> Result<PathBuf, String> {
+    let dir = match std::env::var(FIXTURES_DIR_ENV) {
+        Ok(d) if !d.trim().is_empty() => PathBuf::from(d.trim()),
+        _ => crate::app_paths::data_path("fixtures")?,
+    };
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn extension_for_url(url: &str) -> &str {
+    url.rsplit('.')
+        .next()
+        .filter(|e| e.len() <= 4 && e.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or("bin")
+}
+
+/// The fixture's bytes: downloaded for http(s), read for file:// and relative paths.
+fn download(fixture: &RemoteFixture) -> Result<Vec<u8>, String> {
+    let url = fixture.url.as_str();
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        let path = match url.strip_prefix("file://") {
+            Some(p) => PathBuf::from(p),
+            None => fixture.base.as_deref().unwrap_or(Path::new(".")).join(url),
+        };
+        let meta = std::fs::metadata(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if meta.len() > MAX_DOWNLOAD_BYTES {
+            return Err(format!("{} exceeds {} bytes", path.display(), MAX_DOWNLOAD_BYTES));
+        }
+        return std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+    let resp = ureq::get(url).call().map_err(|e| format!("GET {}: {}", url, e))?;
+    let mut buf = Vec::new();
+    resp.into_reader()
+        .take(MAX_DOWNLOAD_BYTES + 1)
+        .read_to_end(&mut buf)
+        .map_err(|e| e.to_string())?;
+    if buf.len() as u64 > MAX_DOWNLOAD_BYTES {
+        return Err(format!("{} exceeds {} bytes", url, MAX_DOWNLOAD_BYTES));
+    }
+    Ok(buf)
+}
+
+fn remote_path(fixture: &RemoteFixture, dir: &Path) -> PathBuf {
+    dir.join(format!("{}.{}", fixture.name, extension_for_url(&fixture.url)))
+}
+
+/// Fetch (or reuse the cached copy of) a remote fixture, verifying the pinned hash. Entries
+/// without one are an error: an unverified download would make results incomparable.
+pub fn fetch_remote(fixture: &RemoteFixture, dir: &Path) -> Result<FixtureStatus, String> {
+    let pinned = fixture
+        .sha256
+        .as_deref()
+        .ok_or_else(|| format!("{}: no pinned sha256 (run fixtures pin on the manifest first)", fixture.name))?;
+    let path = remote_path(fixture, dir);
+    let cached = std::fs::read(&path).ok().filter(|b| sha256_hex(b).eq_ignore_ascii_case(pinned));
+    if cached.is_none() {
+        let b = download(fixture)?;
+        let got = sha256_hex(&b);
+        if !got.eq_ignore_ascii_case(pinned) {
+            return Err(format!("{}: hash mismatch (pinned {}, got {})", fixture.name, pinned, got));
+        }
+        crate::atomic_write::write(&path, &b, true)?;
+    }
+    Ok(FixtureStatus {
+        name: fixture.name.clone(),
+        path: path.to_string_lossy().to_string(),
+        sha256: pinned.to_ascii_lowercase(),
+    })
+}
+
+/// Deterministic pixel pattern (xorshift noise seeded from the name).
+fn synthesize(fixture: &SyntheticFixture) -> Result<image::RgbImage, String> {
+    if fixture.width == 0 || fixture.height == 0 {
+        return Err(format!("{}: empty dimensions", fixture.name));
+    }
+    let mut state: u64 = fixture
+        .name
+        .bytes()
+        .fold(0x9E37_79B9_7F4A_7C15, |acc, b| (acc ^ b as u64).wrapping_mul(0x100_0000_01B3));
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let (w, h) = (fixture.width, fixture.height);
+    let img = match fixture.pattern.as_str() {
+        "noise" => image::RgbImage::from_fn(w, h, |x, y| {
+            let r = next();
+            let base = ((x * 255 / w) as u64 + (y * 255 / h) as u64) / 2;
+            let c = |shift: u32| (30 + (base + ((r >> shift) & 63)) % 196) as u8;
+            image::Rgb([c(0), c(8), c(16)])
+        }),
+        "gradient" => image::RgbImage::from_fn(w, h, |x, y| {
+            image::Rgb([(x * 255 / w) as u8, (y * 255 / h) as u8, ((x + y) * 127 / (w + h)) as u8])
+        }),
+        "flat" => image::RgbImage::from_pixel(w, h, image::Rgb([128, 128, 128])),
+        "checker" => image::RgbImage::from_fn(w, h, |x, y| {
+            if ((x / 16) + (y / 16)) % 2 == 0 {
+                image::Rgb([40, 40, 40])
+            } else {
+                image::Rgb([215, 215, 215])
+            }
+        }),
+        other => return Err(format!("{}: unknown pattern {}", fixture.name, other)),
+    };
+    Ok(img)
+}
+
+/// Generate a synthetic fixture into the cache (skipped if already present).
+pub fn generate_synthetic(fixture: &SyntheticFixture, dir: &Path) -> Result<FixtureStatus, String> {
+    let (ext, format) = match fixture.format.as_str() {
+        "png" => ("png", image::ImageFormat::Png),
+        "jpeg" | "jpg" => ("jpg", image::ImageFormat::Jpeg),
+        "bmp" => ("bmp", image::ImageFormat::Bmp),
+        other => return Err(format!("{}: unsupported format {}", fixture.name, other)),
+    };
+    let path = dir.join(format!("{}.{}", fixture.name, ext));
+    if !path.exists() {
+        synthesize(fixture)?
+            .save_with_format(&path, format)
+            .map_err(|e| e.to_string())?;
+    }
+    let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+    Ok(FixtureStatus {
+        name: fixture.name.clone(),
+        path: path.to_string_lossy().to_string(),
+        sha256: sha256_hex(&bytes),
+    })
+}
+
+/// Fetch and generate every fixture. Per-fixture failures are returned alongside successes.
+pub fn fetch_all(manifest: &Manifest, dir: &Path) -> Vec<Result<FixtureStatus, String>> {
+    let mut out: Vec<Result<FixtureStatus, String>> =
+        manifest.remote.iter().map(|f| fetch_remote(f, dir)).collect();
+    out.extend(manifest.synthetic.iter().map(|f| generate_synthetic(f, dir)));
+    out
+}
+
+/// Download unpinned remote fixtures and record their hashes in the manifest (trust on first use).
+pub fn pin(manifest: &mut Manifest, dir: &Path) -> Result<usize, String> {
+    let mut pinned = 0;
+    for f in manifest.remote.iter_mut().filter(|f| f.sha256.is_none()) {
+        let bytes = download(f)?;
+        crate::atomic_write::write(&remote_path(f, dir), &bytes, true)?;
+        f.sha256 = Some(sha256_hex(&bytes));
+        pinned += 1;
+    }
+    Ok(pinned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fetch_remote_requires_a_pinned_hash() {
+        let dir = std::env::temp_dir().join(format!("stegstr_fixtures_{}", std::process::id()));
+        let cache = dir.join("cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(dir.join("source.png"), b"cover bytes").unwrap();
+        let mut fixture = RemoteFixture {
+            name: "local".to_string(),
+            url: format!("file://{}", dir.join("source.png").display()),
+            sha256: None,
+            description: String::new(),
+            base: None,
+        };
+        assert!(fetch_remote(&fixture, &cache).unwrap_err().contains("no pinned sha256"));
+        let mut manifest = Manifest { remote: vec![fixture.clone()], synthetic: vec![] };
+        assert_eq!(pin(&mut manifest, &cache).unwrap(), 1);
+        assert_eq!(manifest.remote[0].sha256, Some(sha256_hex(b"cover bytes")));
+
+        // Copied on first use, then served from the cache while it matches the pin.
+        fixture.sha256 = Some(sha256_hex(b"cover bytes").to_uppercase());
+        std::fs::remove_file(remote_path(&fixture, &cache)).unwrap();
+        let status = fetch_remote(&fixture, &cache).unwrap();
+        assert_eq!(status.sha256, sha256_hex(b"cover bytes"));
+        assert_eq!(std::fs::read(&status.path).unwrap(), b"cover bytes");
+        // A source that no longer matches is refused.
+        std::fs::write(dir.join("source.png"), b"other bytes").unwrap();
+        std::fs::remove_file(&status.path).unwrap();
+        assert!(fetch_remote(&fixture, &cache).unwrap_err().contains("hash mismatch"));
+        // Relative sources resolve against the manifest's directory.
+        fixture.url = "source.png".to_string();
+        fixture.base = Some(dir.clone());
+        fixture.sha256 = Some(sha256_hex(b"other bytes"));
+        assert!(fetch_remote(&fixture, &cache).is_ok());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_bundled_corpus_carries_a_payload_through_lossless_sharing() {
+        let dir = std::env::temp_dir().join(format!("stegstr_corpus_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = default_manifest();
+        assert!(manifest.remote.iter().all(|f| f.sha256.is_some()), "every bundled remote fixture is pinned");
+        let covers: Vec<FixtureStatus> = fetch_all(&manifest, &dir).into_iter().map(Result::unwrap).collect();
+        let strip = [crate::channel::Pipeline::parse("strip").unwrap()];
+        // The smaller covers keep the test quick; bench runs take the whole corpus.
+        for cover in covers.iter().filter(|c| image::image_dimensions(&c.path).is_ok_and(|(w, h)| w * h <= 800 * 600)) {
+            let stego = dir.join(format!("{}-stego.png", cover.name));
+            let png = crate::stego::encode(Path::new(&cover.path), b"corpus", &crate::stego::EncodeOptions::default()).unwrap();
+            std::fs::write(&stego, png).unwrap();
+            let report = crate::channel::simulate(&stego, &strip, None, |p| Ok((crate::stego::decode(p)?, "dwt".to_string()))).unwrap();
+            assert_eq!(report.survived, 1, "{}: {:?}", cover.name, report.results);
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,106 @@
+// JSON Schemas for the Tauri IPC contract, generated from the Rust types (schemars).
+// Every structured argument from the webview (option objects, masks, events, filters, bundles,
+// settings) is validated against its schema before deserializing, so the frontend gets
+// "at /field: problem" errors instead of a generic serde failure.
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+
+fn schema_value<T: JsonSchema>() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(T)).unwrap_or(serde_json::Value::Null)
+}
+
+/// All IPC schemas keyed by type name.
+pub fn schemas() -> BTreeMap<&'static str, serde_json::Value> {
+    let mut out = BTreeMap::new();
+    out.insert("StegoDecodeResult", schema_value::<crate::StegoDecodeResult>());
+    out.insert("WatchFound", schema_value::<crate::WatchFound>());
+    out.insert("StegoEncodeResult", schema_value::<crate::StegoEncodeResult>());
+    out.insert("StegoProgress", schema_value::<crate::StegoProgress>());
+    out.insert("LogEntry", schema_value::<crate::logs::LogEntry>());
+    out.insert("Settings", schema_value::<crate::config::Settings>());
+    out.insert("RelayMessage", schema_value::<crate::relay_pool::RelayMessage>());
+    out.insert("RelayStatus", schema_value::<crate::relay_pool::RelayStatus>());
+    out.insert("StoredEvent", schema_value::<crate::event_store::StoredEvent>());
+    out.insert("StoreReport", schema_value::<crate::event_store::StoreReport>());
+    out.insert("OutboxEntry", schema_value::<crate::outbox::OutboxEntry>());
+    out.insert("FlushReport", schema_value::<crate::outbox::FlushReport>());
+    out.insert("StegoImageResult", schema_value::<crate::StegoImageResult>());
+    out.insert("ErrorCode", schema_value::<crate::error::ErrorCode>());
+    out.insert("DataDirInfo", schema_value::<crate::DataDirInfo>());
+    out.insert("SalvageResult", schema_value::<crate::SalvageResult>());
+    out.insert("Thumbnail", schema_value::<crate::thumbnail::Thumbnail>());
+    out.insert("QualityReport", schema_value::<crate::quality::QualityReport>());
+    out.insert("Heatmap", schema_value::<crate::quality::Heatmap>());
+    out.insert("CapacityInfo", schema_value::<crate::units::CapacityInfo>());
+    out.insert("ExchangeManifest", schema_value::<crate::exchange::ExchangeManifest>());
+    out.insert("ExternalCodec", schema_value::<crate::external::ExternalCodec>());
+    out.insert("SanitizedBundle", schema_value::<crate::sanitize::SanitizedBundle>());
+    out.insert("BundleVerification", schema_value::<crate::nostr_verify::BundleVerification>());
+    out.insert("ScanSummary", schema_value::<crate::scan::ScanSummary>());
+    out.insert("DetectReport", schema_value::<crate::scan::DetectReport>());
+    out.insert("Region", schema_value::<crate::stego::Region>());
+    out.insert("Detection", schema_value::<crate::stego::Detection>());
+    out.insert("DwtCapacity", schema_value::<crate::stego::DwtCapacity>());
+    out.insert("MaskSpec", schema_value::<crate::mask::MaskSpec>());
+    out.insert("SendOptions", schema_value::<crate::send::SendOptions>());
+    out.insert("SendResult", schema_value::<crate::send::SendResult>());
+    out.insert("DmOptions", schema_value::<crate::dm::DmOptions>());
+    out.insert("DmResult", schema_value::<crate::dm::DmResult>());
+    out.insert("DirectMessage", schema_value::<crate::dm::DirectMessage>());
+    out.insert("Fingerprint", schema_value::<crate::fingerprint::Fingerprint>());
+    out.insert("RelayOutcome", schema_value::<crate::relay::RelayOutcome>());
+    out.insert("FetchReport", schema_value::<crate::relay::FetchReport>());
+    out.insert("Target", schema_value::<crate::targets::Target>());
+    out.insert("Profile", schema_value::<crate::profiles::Profile>());
+    out.insert("AnalysisReport", schema_value::<crate::stego_analysis::AnalysisReport>());
+    out.insert("NostrEvent", schema_value::<crate::nostr_event::NostrEvent>());
+    out.insert("Bundle", schema_value::<crate::nostr_event::Bundle>());
+    out.insert("UnsignedEvent", schema_value::<crate::nostr_event::UnsignedEvent>());
+    out.insert("StoredKey", schema_value::<crate::keystore::StoredKey>());
+    out
+}
+
+/// Validate `value` against T's schema, then deserialize it.
+pub fn parse_options<T: DeserializeOwned + JsonSchema>(value: serde_json::Value) -> Result<T, String> {
+    let schema = schema_value::<T>();
+    let validator = jsonschema::validator_for(&schema).map_err(|e| format!("Invalid schema for {}: {}", T::schema_name(), e))?;
+    let problems: Vec<String> = validator
+        .iter_errors(&value)
+        .map(|e| {
+            let path = e.instance_path().to_string();
+            format!("at {}: {}", if path.is_empty() { "/" } else { &path }, e)
+        })
+        .collect();
+    if !problems.is_empty() {
+        return Err(format!("Invalid {}: {}", T::schema_name(), problems.join("; ")));
+    }
+    serde_json::from_value(value).map_err(|e| format!("Invalid {}: {}", T::schema_name(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options_reports_field_errors() {
+        let bad = serde_json::json!({
+            "content": "hi",
+            "recipient": "npub1x",
+            "cover_path": "/tmp/c.png",
+            "privkey_hex": "zz",
+            "colour": "red"
+        });
+        let err = parse_options::<crate::send::SendOptions>(bad).unwrap_err();
+        assert!(err.contains("output_path"), "{}", err);
+        assert!(err.contains("/privkey_hex"), "{}", err);
+        assert!(err.contains("colour"), "{}", err);
+        assert!(schemas().contains_key("Bundle"));
+
+        let mask = parse_options::<crate::mask::MaskSpec>(serde_json::json!({ "rects": [{ "x": 1, "y": 2, "width": 3 }] }));
+        assert!(mask.unwrap_err().contains("/rects/0"));
+        let events = parse_options::<Vec<crate::nostr_event::NostrEvent>>(serde_json::json!([{ "id": "x" }]));
+        assert!(events.unwrap_err().contains("/0"));
+    }
+}
@@ -0,0 +1,698 @@
+// Baseline JPEG at the coefficient level: read the quantized DCT blocks of a baseline (Huffman,
+// sequential) JPEG, build them from RGB pixels, and write them back without touching the pixel
+// domain. This is what the QIM codec embeds into; libjpeg's read_dct/write_dct in Python terms.
+// Progressive and arithmetic-coded files are rejected.
+
+use image::RgbImage;
+
+/// Zigzag position -> natural (row-major) index.
+pub const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50,
+    43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// IJG base quantization tables (natural order), scaled by `quality_table`.
+pub const LUMA_QUANT: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56, 14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56,
+    68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113, 92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+const CHROMA_QUANT: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99, 47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+// Standard Huffman tables (JPEG Annex K.3): code counts per length 1..=16, then symbols.
+const DC_LUMA_BITS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const DC_CHROMA_BITS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const AC_LUMA_BITS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+const AC_LUMA_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07, 0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08,
+    0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0, 0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59,
+    0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6,
+    0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+];
+const AC_CHROMA_BITS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const AC_CHROMA_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71, 0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91,
+    0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0, 0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58,
+    0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4,
+    0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+];
+
+pub type Block = [i16; 64];
+
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub id: u8,
+    pub h: u8,
+    pub v: u8,
+    /// Quantization table slot.
+    pub tq: u8,
+    /// Blocks that cover the component's own samples (libjpeg's width/height_in_blocks).
+    pub blocks_w: usize,
+    pub blocks_h: usize,
+    /// Row stride of `blocks`, which also holds MCU padding.
+    stride: usize,
+    /// Quantized coefficients per block, natural order.
+    blocks: Vec<Block>,
+}
+
+impl Component {
+    pub fn block(&self, bx: usize, by: usize) -> &Block {
+        &self.blocks[by * self.stride + bx]
+    }
+
+    pub fn block_mut(&mut self, bx: usize, by: usize) -> &mut Block {
+        &mut self.blocks[by * self.stride + bx]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DctImage {
+    pub width: u32,
+    pub height: u32,
+    pub components: Vec<Component>,
+    /// Quantization tables by slot, natural order.
+    pub qtables: [Option<[u16; 64]>; 4],
+}
+
+/// IJG quality scaling of a base table (quality 1..=100).
+pub fn quality_table(base: &[u16; 64], quality: u8) -> [u16; 64] {
+    let q = quality.clamp(1, 100) as u32;
+    let scale = if q < 50 { 5000 / q } else { 200 - 2 * q };
+    base.map(|b| ((b as u32 * scale + 50) / 100).clamp(1, 255) as u16)
+}
+
+// ---- reading ----
+
+struct Huffman {
+    maxcode: [i32; 17],
+    mincode: [i32; 17],
+    valptr: [usize; 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(bits: &[u8], values: &[u8]) -> Self {
+        let mut h = Huffman {
+            maxcode: [-1; 17],
+            mincode: [0; 17],
+            valptr: [0; 17],
+            values: values.to_vec(),
+        };
+        let (mut code, mut k) = (0i32, 0usize);
+        for len in 1..=16 {
+            let n = bits[len - 1] as usize;
+            if n > 0 {
+                h.valptr[len] = k;
+                h.mincode[len] = code;
+                code += n as i32;
+                k += n;
+                h.maxcode[len] = code - 1;
+            }
+            code <<= 1;
+        }
+        h
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u32,
+    bits: u32,
+    /// A marker was reached; further reads are zero padding.
+    at_marker: bool,
+}
+
+impl<'a> BitReader<'a> {
+    fn bit(&mut self) -> u32 {
+        if self.bits == 0 {
+            let mut byte = 0;
+            if !self.at_marker && self.pos < self.data.len() {
+                byte = self.data[self.pos];
+                if byte == 0xFF {
+                    match self.data.get(self.pos + 1) {
+                        Some(0) => self.pos += 2,
+                        _ => {
+                            self.at_marker = true;
+                            byte = 0;
+                        }
+                    }
+                } else {
+                    self.pos += 1;
+                }
+            }
+            self.acc = byte as u32;
+            self.bits = 8;
+        }
+        self.bits -= 1;
+        (self.acc >> self.bits) & 1
+    }
+
+    fn receive(&mut self, n: u8) -> i32 {
+        (0..n).fold(0, |acc, _| (acc << 1) | self.bit() as i32)
+    }
+
+    /// Read `n` bits and sign-extend them (JPEG F.2.2.1).
+    fn receive_extend(&mut self, n: u8) -> i32 {
+        if n == 0 {
+            return 0;
+        }
+        let v = self.receive(n);
+        if v < 1 << (n - 1) {
+            v - (1 << n) + 1
+        } else {
+            v
+        }
+    }
+
+    fn decode(&mut self, table: &Huffman) -> Result<u8, String> {
+        let mut code = 0i32;
+        for len in 1..=16 {
+            code = (code << 1) | self.bit() as i32;
+            if code <= table.maxcode[len] {
+                return Ok(table.values[table.valptr[len] + (code - table.mincode[len]) as usize]);
+            }
+        }
+        Err("Corrupt JPEG: bad Huffman code".to_string())
+    }
+
+    /// Skip to after the next RSTn marker.
+    fn restart(&mut self) -> Result<(), String> {
+        self.bits = 0;
+        self.at_marker = false;
+        while self.pos + 1 < self.data.len() {
+            if self.data[self.pos] == 0xFF && (0xD0..=0xD7).contains(&self.data[self.pos + 1]) {
+                self.pos += 2;
+                return Ok(());
+            }
+            self.pos += 1;
+        }
+        Err("Corrupt JPEG: missing restart marker".to_string())
+    }
+}
+
+fn decode_block(reader: &mut BitReader, dc: &Huffman, ac: &Huffman, pred: &mut i32, block: &mut Block) -> Result<(), String> {
+    let t = reader.decode(dc)?;
+    *pred += reader.receive_extend(t);
+    block[0] = *pred as i16;
+    let mut k = 1;
+    while k < 64 {
+        let rs = reader.decode(ac)?;
+        let (run, size) = ((rs >> 4) as usize, rs & 15);
+        if size == 0 {
+            if run != 15 {
+                break;
+            }
+            k += 16;
+            continue;
+        }
+        k += run;
+        if k > 63 {
+            return Err("Corrupt JPEG: coefficient index out of range".to_string());
+        }
+        block[ZIGZAG[k]] = reader.receive_extend(size) as i16;
+        k += 1;
+    }
+    Ok(())
+}
+
+fn be16(data: &[u8], at: usize) -> Result<usize, String> {
+    match data.get(at..at + 2) {
+        Some(b) => Ok(u16::from_be_bytes([b[0], b[1]]) as usize),
+        None => Err("Truncated JPEG".to_string()),
+    }
+}
+
+/// Read the quantized DCT coefficients of a baseline JPEG.
+pub fn read(data: &[u8]) -> Result<DctImage, String> {
+    if data.len() < 4 || data[0] != 0xFF || data[1] != 0xD8 {
+        return Err("Not a JPEG file".to_string());
+    }
+    let mut qtables: [Option<[u16; 64]>; 4] = [None; 4];
+    let mut dc_tables: [Option<Huffman>; 4] = [None, None, None, None];
+    let mut ac_tables: [Option<Huffman>; 4] = [None, None, None, None];
+    let mut image: Option<DctImage> = None;
+    let mut restart_interval = 0usize;
+    let (mut mcux, mut mcuy) = (0usize, 0usize);
+    let mut pos = 2;
+    loop {
+        while data.get(pos) == Some(&0xFF) && data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        if data.get(pos) != Some(&0xFF) {
+            return Err("Corrupt JPEG: expected a marker".to_string());
+        }
+        let marker = *data.get(pos + 1).ok_or("Truncated JPEG")?;
+        pos += 2;
+        if marker == 0xD9 {
+            break;
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            continue;
+        }
+        let len = be16(data, pos)?;
+        let seg = data.get(pos + 2..pos + len).ok_or("Truncated JPEG")?;
+        match marker {
+            0xDB => {
+                let mut i = 0;
+                while i < seg.len() {
+                    let (precision, slot) = (seg[i] >> 4, (seg[i] & 3) as usize);
+                    let size = if precision == 0 { 64 } else { 128 };
+                    let raw = seg.get(i + 1..i + 1 + size).ok_or("Truncated JPEG quantization table")?;
+                    let mut table = [0u16; 64];
+                    for k in 0..64 {
+                        table[ZIGZAG[k]] = if precision == 0 { raw[k] as u16 } else { u16::from_be_bytes([raw[2 * k], raw[2 * k + 1]]) };
+                    }
+                    qtables[slot] = Some(table);
+                    i += 1 + size;
+                }
+            }
+            0xC4 => {
+                let mut i = 0;
+                while i + 17 <= seg.len() {
+                    let (class, slot) = (seg[i] >> 4, (seg[i] & 3) as usize);
+                    let bits = &seg[i + 1..i + 17];
+                    let count: usize = bits.iter().map(|&b| b as usize).sum();
+                    let values = seg.get(i + 17..i + 17 + count).ok_or("Truncated JPEG Huffman table")?;
+                    let table = Some(Huffman::new(bits, values));
+                    if class == 0 {
+                        dc_tables[slot] = table;
+                    } else {
+                        ac_tables[slot] = table;
+                    }
+                    i += 17 + count;
+                }
+            }
+            0xC0 | 0xC1 => {
+                if seg.len() < 6 || seg[0] != 8 {
+                    return Err("Unsupported JPEG: only 8-bit samples".to_string());
+                }
+                let height = be16(seg, 1)? as u32;
+                let width = be16(seg, 3)? as u32;
+                let n = seg[5] as usize;
+                if width == 0 || height == 0 || n == 0 || seg.len() < 6 + 3 * n {
+                    return Err("Corrupt JPEG frame header".to_string());
+                }
+                let specs: Vec<(u8, u8, u8, u8)> = (0..n).map(|c| (seg[6 + 3 * c], seg[7 + 3 * c] >> 4, seg[7 + 3 * c] & 15, seg[8 + 3 * c] & 3)).collect();
+                if specs.iter().any(|&(_, h, v, _)| !(1..=4).contains(&h) || !(1..=4).contains(&v)) {
+                    return Err("Corrupt JPEG sampling factors".to_string());
+                }
+                let hmax = specs.iter().map(|s| s.1 as usize).max().unwrap_or(1);
+                let vmax = specs.iter().map(|s| s.2 as usize).max().unwrap_or(1);
+                mcux = (width as usize).div_ceil(8 * hmax);
+                mcuy = (height as usize).div_ceil(8 * vmax);
+                let components = specs
+                    .into_iter()
+                    .map(|(id, h, v, tq)| {
+                        let stride = mcux * h as usize;
+                        Component {
+                            id,
+                            h,
+                            v,
+                            tq,
+                            blocks_w: (width as usize * h as usize).div_ceil(hmax).div_ceil(8),
+                            blocks_h: (height as usize * v as usize).div_ceil(vmax).div_ceil(8),
+                            stride,
+                            blocks: vec![[0; 64]; stride * mcuy * v as usize],
+                        }
+                    })
+                    .collect();
+                image = Some(DctImage {
+                    width,
+                    height,
+                    components,
+                    qtables,
+                });
+            }
+            0xC2 | 0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => {
+                return Err("Unsupported JPEG: progressive, lossless or arithmetic-coded".to_string());
+            }
+            0xDD => restart_interval = be16(seg, 0)?,
+            0xDA => {
+                let img = image.as_mut().ok_or("Corrupt JPEG: scan before frame header")?;
+                let ns = *seg.first().ok_or("Corrupt JPEG scan header")? as usize;
+                if seg.len() < 1 + 2 * ns + 3 {
+                    return Err("Corrupt JPEG scan header".to_string());
+                }
+                let mut scan: Vec<(usize, usize, usize)> = Vec::with_capacity(ns);
+                for s in 0..ns {
+                    let (id, tables) = (seg[1 + 2 * s], seg[2 + 2 * s]);
+                    let ci = img.components.iter().position(|c| c.id == id).ok_or("Corrupt JPEG: unknown scan component")?;
+                    scan.push((ci, (tables >> 4) as usize & 3, tables as usize & 3));
+                }
+                for &(_, td, ta) in &scan {
+                    if dc_tables[td].is_none() || ac_tables[ta].is_none() {
+                        return Err("Corrupt JPEG: missing Huffman table".to_string());
+                    }
+                }
+                let mut reader = BitReader {
+                    data,
+                    pos: pos + len,
+                    acc: 0,
+                    bits: 0,
+                    at_marker: false,
+                };
+                let mut preds = vec![0i32; ns];
+                // Interleaved scans walk MCUs; a single-component scan walks that component's blocks.
+                let units: Vec<Vec<(usize, usize, usize)>> = if ns > 1 {
+                    (0..mcuy * mcux)
+                        .map(|m| {
+                            let (mx, my) = (m % mcux, m / mcux);
+                            let mut unit = Vec::new();
+                            for (si, &(ci, _, _)) in scan.iter().enumerate() {
+                                let c = &img.components[ci];
+                                for v in 0..c.v as usize {
+                                    for h in 0..c.h as usize {
+                                        unit.push((si, mx * c.h as usize + h, my * c.v as usize + v));
+                                    }
+                                }
+                            }
+                            unit
+                        })
+                        .collect()
+                } else {
+                    let c = &img.components[scan[0].0];
+                    (0..c.blocks_h * c.blocks_w).map(|b| vec![(0, b % c.blocks_w, b / c.blocks_w)]).collect()
+                };
+                for (n, unit) in units.iter().enumerate() {
+                    if restart_interval > 0 && n > 0 && n % restart_interval == 0 {
+                        reader.restart()?;
+                        preds.iter_mut().for_each(|p| *p = 0);
+                    }
+                    for &(si, bx, by) in unit {
+                        let (ci, td, ta) = scan[si];
+                        let (dc, ac) = (dc_tables[td].as_ref().expect("checked"), ac_tables[ta].as_ref().expect("checked"));
+                        decode_block(&mut reader, dc, ac, &mut preds[si], img.components[ci].block_mut(bx, by))?;
+                    }
+                }
+                // Resume marker parsing after the entropy-coded data.
+                pos = reader.pos;
+                while pos + 1 < data.len() && !(data[pos] == 0xFF && data[pos + 1] != 0 && !(0xD0..=0xD7).contains(&data[pos + 1])) {
+                    pos += 1;
+                }
+                if pos + 1 >= data.len() {
+                    break;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        pos += len;
+    }
+    let mut img = image.ok_or("Corrupt JPEG: no frame header")?;
+    img.qtables = qtables;
+    Ok(img)
+}
+
+// ---- building and writing ----
+
+/// Forward DCT of a level-shifted 8x8 block, quantized by `q` (natural order).
+fn fdct_quantize(samples: &[f32; 64], q: &[u16; 64]) -> Block {
+    let mut cos = [[0f32; 8]; 8];
+    for (u, row) in cos.iter_mut().enumerate() {
+        let cu = if u == 0 { std::f32::consts::FRAC_1_SQRT_2 } else { 1.0 };
+        for (x, c) in row.iter_mut().enumerate() {
+            *c = cu * 0.5 * (((2 * x + 1) * u) as f32 * std::f32::consts::PI / 16.0).cos();
+        }
+    }
+    let mut tmp = [0f32; 64];
+    for y in 0..8 {
+        for u in 0..8 {
+            tmp[y * 8 + u] = (0..8).map(|x| cos[u][x] * samples[y * 8 + x]).sum();
+        }
+    }
+    let mut out = [0i16; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            let f: f32 = (0..8).map(|y| cos[v][y] * tmp[y * 8 + u]).sum();
+            out[v * 8 + u] = (f / q[v * 8 + u] as f32).round() as i16;
+        }
+    }
+    out
+}
+
+impl DctImage {
+    /// Compress pixels to YCbCr 4:4:4 coefficients at an IJG `quality`, like PIL's
+    /// `save(quality=q, subsampling=0)`.
+    pub fn from_rgb(img: &RgbImage, quality: u8) -> DctImage {
+        let (width, height) = img.dimensions();
+        let (bw, bh) = ((width as usize).div_ceil(8), (height as usize).div_ceil(8));
+        let luma = quality_table(&LUMA_QUANT, quality);
+        let chroma = quality_table(&CHROMA_QUANT, quality);
+        let mut components: Vec<Component> = (0..3u8)
+            .map(|i| Component {
+                id: i + 1,
+                h: 1,
+                v: 1,
+                tq: i.min(1),
+                blocks_w: bw,
+                blocks_h: bh,
+                stride: bw,
+                blocks: vec![[0; 64]; bw * bh],
+            })
+            .collect();
+        let mut planes = [[0f32; 64]; 3];
+        for by in 0..bh {
+            for bx in 0..bw {
+                for y in 0..8 {
+                    for x in 0..8 {
+                        // Edge blocks repeat the last row/column.
+                        let px = ((bx * 8 + x) as u32).min(width - 1);
+                        let py = ((by * 8 + y) as u32).min(height - 1);
+                        let [r, g, b] = img.get_pixel(px, py).0.map(|c| c as f32);
+                        planes[0][y * 8 + x] = 0.299 * r + 0.587 * g + 0.114 * b - 128.0;
+                        planes[1][y * 8 + x] = -0.168_736 * r - 0.331_264 * g + 0.5 * b;
+                        planes[2][y * 8 + x] = 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+                    }
+                }
+                for (c, comp) in components.iter_mut().enumerate() {
+                    *comp.block_mut(bx, by) = fdct_quantize(&planes[c], if c == 0 { &luma } else { &chroma });
+                }
+            }
+        }
+        DctImage {
+            width,
+            height,
+            components,
+            qtables: [Some(luma), Some(chroma), None, None],
+        }
+    }
+
+    /// Luma (first) component.
+    pub fn luma(&self) -> &Component {
+        &self.components[0]
+    }
+
+    pub fn luma_mut(&mut self) -> &mut Component {
+        &mut self.components[0]
+    }
+
+    /// Quantization table of the luma component.
+    pub fn luma_qtable(&self) -> Option<&[u16; 64]> {
+        self.qtables[self.luma().tq as usize].as_ref()
+    }
+
+    /// Write a baseline JPEG with the standard Huffman tables. Only unsubsampled images (every
+    /// component 1x1, as `from_rgb` builds) are supported.
+    pub fn write(&self) -> Result<Vec<u8>, String> {
+        if self.components.iter().any(|c| c.h != 1 || c.v != 1) {
+            return Err("Writing subsampled JPEGs is not supported".to_string());
+        }
+        let mut out = vec![0xFF, 0xD8];
+        segment(&mut out, 0xE0, b"JFIF\0\x01\x01\x00\x00\x01\x00\x01\x00\x00");
+        for (slot, table) in self.qtables.iter().enumerate() {
+            let Some(table) = table else { continue };
+            if !self.components.iter().any(|c| c.tq as usize == slot) {
+                continue;
+            }
+            let wide = table.iter().any(|&q| q > 255);
+            let mut body = vec![((wide as u8) << 4) | slot as u8];
+            for &n in &ZIGZAG {
+                if wide {
+                    body.extend_from_slice(&table[n].to_be_bytes());
+                } else {
+                    body.push(table[n] as u8);
+                }
+            }
+            segment(&mut out, 0xDB, &body);
+        }
+        let mut frame = vec![8];
+        frame.extend_from_slice(&(self.height as u16).to_be_bytes());
+        frame.extend_from_slice(&(self.width as u16).to_be_bytes());
+        frame.push(self.components.len() as u8);
+        for c in &self.components {
+            frame.extend_from_slice(&[c.id, 0x11, c.tq]);
+        }
+        segment(&mut out, if self.qtables.iter().flatten().any(|t| t.iter().any(|&q| q > 255)) { 0xC1 } else { 0xC0 }, &frame);
+        let tables = [
+            (0x00, &DC_LUMA_BITS, &DC_VALUES[..]),
+            (0x10, &AC_LUMA_BITS, &AC_LUMA_VALUES[..]),
+            (0x01, &DC_CHROMA_BITS, &DC_VALUES[..]),
+            (0x11, &AC_CHROMA_BITS, &AC_CHROMA_VALUES[..]),
+        ];
+        for (class, bits, values) in tables {
+            let mut body = vec![class];
+            body.extend_from_slice(bits);
+            body.extend_from_slice(values);
+            segment(&mut out, 0xC4, &body);
+        }
+        let mut scan = vec![self.components.len() as u8];
+        for (i, c) in self.components.iter().enumerate() {
+            scan.extend_from_slice(&[c.id, if i == 0 { 0x00 } else { 0x11 }]);
+        }
+        scan.extend_from_slice(&[0, 63, 0]);
+        segment(&mut out, 0xDA, &scan);
+
+        let codes = [
+            (HuffmanCodes::new(&DC_LUMA_BITS, &DC_VALUES), HuffmanCodes::new(&AC_LUMA_BITS, &AC_LUMA_VALUES)),
+            (HuffmanCodes::new(&DC_CHROMA_BITS, &DC_VALUES), HuffmanCodes::new(&AC_CHROMA_BITS, &AC_CHROMA_VALUES)),
+        ];
+        let mut writer = BitWriter { out, acc: 0, bits: 0 };
+        let mut preds = vec![0i32; self.components.len()];
+        let first = &self.components[0];
+        for by in 0..first.blocks_h {
+            for bx in 0..first.blocks_w {
+                for (i, c) in self.components.iter().enumerate() {
+                    let (dc, ac) = &codes[i.min(1)];
+                    encode_block(&mut writer, dc, ac, &mut preds[i], c.block(bx, by));
+                }
+            }
+        }
+        let mut out = writer.finish();
+        out.extend_from_slice(&[0xFF, 0xD9]);
+        Ok(out)
+    }
+}
+
+fn segment(out: &mut Vec<u8>, marker: u8, body: &[u8]) {
+    out.extend_from_slice(&[0xFF, marker]);
+    out.extend_from_slice(&((body.len() + 2) as u16).to_be_bytes());
+    out.extend_from_slice(body);
+}
+
+struct HuffmanCodes {
+    /// (code, length) per symbol.
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanCodes {
+    fn new(bits: &[u8; 16], values: &[u8]) -> Self {
+        let mut codes = [(0u16, 0u8); 256];
+        let (mut code, mut k) = (0u16, 0usize);
+        for len in 1..=16u8 {
+            for _ in 0..bits[len as usize - 1] {
+                codes[values[k] as usize] = (code, len);
+                code += 1;
+                k += 1;
+            }
+            code <<= 1;
+        }
+        HuffmanCodes { codes }
+    }
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, len: u8) {
+        self.acc = (self.acc << len) | (value as u64 & ((1u64 << len) - 1));
+        self.bits += len as u32;
+        while self.bits >= 8 {
+            let byte = (self.acc >> (self.bits - 8)) as u8;
+            self.out.push(byte);
+            if byte == 0xFF {
+                self.out.push(0);
+            }
+            self.bits -= 8;
+        }
+        self.acc &= (1u64 << self.bits) - 1;
+    }
+
+    fn symbol(&mut self, table: &HuffmanCodes, symbol: u8) {
+        let (code, len) = table.codes[symbol as usize];
+        self.put(code as u32, len);
+    }
+
+    /// Pad the last byte with 1 bits.
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            let pad = 8 - self.bits as u8;
+            self.put((1 << pad) - 1, pad);
+        }
+        self.out
+    }
+}
+
+/// Magnitude category and the bits that encode `v` in it.
+fn category(v: i32) -> (u8, u32) {
+    let size = (32 - v.unsigned_abs().leading_zeros()) as u8;
+    let bits = if v < 0 { (v - 1) as u32 } else { v as u32 };
+    (size, bits)
+}
+
+fn encode_block(writer: &mut BitWriter, dc: &HuffmanCodes, ac: &HuffmanCodes, pred: &mut i32, block: &Block) {
+    let (size, bits) = category(block[0] as i32 - *pred);
+    *pred = block[0] as i32;
+    writer.symbol(dc, size);
+    writer.put(bits, size);
+    let mut run = 0;
+    for &n in &ZIGZAG[1..] {
+        let v = block[n] as i32;
+        if v == 0 {
+            run += 1;
+            continue;
+        }
+        while run > 15 {
+            writer.symbol(ac, 0xF0);
+            run -= 16;
+        }
+        let (size, bits) = category(v);
+        writer.symbol(ac, (run << 4) | size);
+        writer.put(bits, size);
+        run = 0;
+    }
+    if run > 0 {
+        writer.symbol(ac, 0x00);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jpeg_coefficients_roundtrip() {
+        let img = RgbImage::from_fn(37, 21, |x, y| image::Rgb([(x * 7) as u8, (y * 11) as u8, ((x + y) * 5) as u8]));
+        let dct = DctImage::from_rgb(&img, 75);
+        let jpeg = dct.write().unwrap();
+        // Other decoders accept the file and see roughly the same picture.
+        let decoded = image::load_from_memory(&jpeg).unwrap().to_rgb8();
+        assert_eq!(decoded.dimensions(), (37, 21));
+        let diff = decoded.get_pixel(20, 10).0.iter().zip(img.get_pixel(20, 10).0).map(|(&a, b)| (a as i32 - b as i32).abs()).max().unwrap();
+        assert!(diff < 24, "pixel differs by {}", diff);
+        // Reading gives back exactly the coefficients written.
+        let back = read(&jpeg).unwrap();
+        assert_eq!((back.luma().blocks_w, back.luma().blocks_h), (5, 3));
+        for (a, b) in back.components.iter().zip(&dct.components) {
+            for by in 0..a.blocks_h {
+                for bx in 0..a.blocks_w {
+                    assert_eq!(a.block(bx, by), b.block(bx, by));
+                }
+            }
+        }
+        assert_eq!(back.qtables[0], dct.qtables[0]);
+    }
+}
@@ -0,0 +1,169 @@
+// Named Nostr keys in the OS keychain (macOS Keychain, Windows Credential Manager, Secret Service
+// on Linux), so secret keys need not pass through flags, env vars or the webview. The keychain
+// holds the secret as hex under service "stegstr" and the key's name; keychains cannot be listed
+// portably, so names and pubkeys (nothing secret) are also kept in <data dir>/keys.json.
+// Stego key passwords (the settings' stego_key_ref) sit under their own service, "stegstr-stego-key".
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::nostr_event::{self, NostrEvent, UnsignedEvent};
+use crate::nostr_codes;
+
+const SERVICE: &str = "stegstr";
+const INDEX_FILE: &str = "keys.json";
+const STEGO_KEY_SERVICE: &str = "stegstr-stego-key";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StoredKey {
+    pub name: String,
+    /// Hex public key.
+    pub pubkey: String,
+    pub npub: String,
+    /// Unix seconds when the key was stored.
+    pub created_at: u64,
+}
+
+fn entry(name: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(SERVICE, name).map_err(|e| format!("Keychain: {}", e))
+}
+
+fn validate_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > 64 || name.chars().any(|c| c.is_control()) {
+        return Err("Key names are 1 to 64 printable characters".to_string());
+    }
+    Ok(name)
+}
+
+fn read_index(path: &Path) -> Result<Vec<StoredKey>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn write_index(path: &Path, keys: &[StoredKey]) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(keys).map_err(|e| e.to_string())?;
+    crate::atomic_write::write(path, text.as_bytes(), true)
+}
+
+/// Replace the entry with the same name, or append; keeps the list sorted by name.
+fn upsert(keys: &mut Vec<StoredKey>, key: StoredKey) {
+    keys.retain(|k| k.name != key.name);
+    keys.push(key);
+    keys.sort_by(|a, b| a.name.cmp(&b.name));
+}
+
+/// Store `secret` (hex or nsec) under `name`, replacing any key of that name.
+pub fn store_key(name: &str, secret: &str) -> Result<StoredKey, String> {
+    let name = validate_name(name)?;
+    let sk = nostr_codes::parse_secret_key(secret)?;
+    entry(name)?
+        .set_password(&hex::encode(sk.secret_bytes()))
+        .map_err(|e| format!("Keychain: {}", e))?;
+    let pubkey = nostr_event::pubkey_hex(&sk);
+    let key = StoredKey {
+        name: name.to_string(),
+        npub: nostr_codes::npub_from_hex(&pubkey)?,
+        pubkey,
+        created_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs(),
+    };
+    let path = crate::app_paths::data_path(INDEX_FILE)?;
+    let mut keys = read_index(&path)?;
+    upsert(&mut keys, key.clone());
+    write_index(&path, &keys)?;
+    Ok(key)
+}
+
+/// Stored keys by name (public parts only).
+pub fn list_keys() -> Result<Vec<StoredKey>, String> {
+    read_index(&crate::app_paths::data_path(INDEX_FILE)?)
+}
+
+/// The secret key stored under `name`.
+pub fn load_key(name: &str) -> Result<secp256k1::SecretKey, String> {
+    let name = validate_name(name)?;
+    match entry(name)?.get_password() {
+        Ok(hex) => nostr_event::secret_key_from_hex(&hex),
+        Err(keyring::Error::NoEntry) => Err(format!("No key named {:?} in the keychain", name)),
+        Err(e) => Err(format!("Keychain: {}", e)),
+    }
+}
+
+/// Delete the key stored under `name`; false if there was none.
+pub fn remove_key(name: &str) -> Result<bool, String> {
+    let name = validate_name(name)?;
+    let removed = match entry(name)?.delete_credential() {
+        Ok(()) => true,
+        Err(keyring::Error::NoEntry) => false,
+        Err(e) => return Err(format!("Keychain: {}", e)),
+    };
+    let path = crate::app_paths::data_path(INDEX_FILE)?;
+    let mut keys = read_index(&path)?;
+    let before = keys.len();
+    keys.retain(|k| k.name != name);
+    if keys.len() != before {
+        write_index(&path, &keys)?;
+    }
+    Ok(removed || keys.len() != before)
+}
+
+/// Store a stego key password under `name`, replacing any of that name.
+pub fn store_stego_key(name: &str, password: &str) -> Result<(), String> {
+    let name = validate_name(name)?;
+    if password.is_empty() {
+        return Err("Stego key must not be empty".to_string());
+    }
+    keyring::Entry::new(STEGO_KEY_SERVICE, name)
+        .and_then(|e| e.set_password(password))
+        .map_err(|e| format!("Keychain: {}", e))
+}
+
+/// The stego key password stored under `name`.
+pub fn load_stego_key(name: &str) -> Result<String, String> {
+    let name = validate_name(name)?;
+    match keyring::Entry::new(STEGO_KEY_SERVICE, name).and_then(|e| e.get_password()) {
+        Ok(password) => Ok(password),
+        Err(keyring::Error::NoEntry) => Err(format!("No stego key named {:?} in the keychain", name)),
+        Err(e) => Err(format!("Keychain: {}", e)),
+    }
+}
+
+/// Sign an event template with the key stored under `name`.
+pub fn sign_with_key(name: &str, template: &UnsignedEvent) -> Result<NostrEvent, String> {
+    nostr_event::sign_unsigned(template, &load_key(name)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_upsert_and_names() {
+        let path = std::env::temp_dir().join(format!("stegstr_keys_{}.json", std::process::id()));
+        assert_eq!(read_index(&path).unwrap(), vec![]);
+        let key = |name: &str, pubkey: &str| StoredKey {
+            name: name.to_string(),
+            pubkey: pubkey.to_string(),
+            npub: String::new(),
+            created_at: 0,
+        };
+        let mut keys = vec![];
+        upsert(&mut keys, key("work", "aa"));
+        upsert(&mut keys, key("alt", "bb"));
+        upsert(&mut keys, key("work", "cc"));
+        write_index(&path, &keys).unwrap();
+        let read = read_index(&path).unwrap();
+        assert_eq!(read.iter().map(|k| (k.name.as_str(), k.pubkey.as_str())).collect::<Vec<_>>(), [("alt", "bb"), ("work", "cc")]);
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(validate_name("  main ").unwrap(), "main");
+        assert!(validate_name("").is_err() && validate_name("a\nb").is_err());
+    }
+}
@@ -1,158 +1,1357 @@
-pub mod stego;
-pub mod stego_crypto;
-pub mod stego_dot;
+pub mod app_paths;
+pub mod atomic_write;
+pub mod batch;
+pub mod channel;
+pub mod clipboard;
+pub mod config;
+pub mod dm;
+pub mod download;
+pub mod event_store;
+pub mod event_stream;
+pub mod exchange;
+pub mod external;
+pub mod fingerprint;
+pub mod fixtures;
+pub mod ipc_schema;
+pub mod jpeg_dct;
+pub mod keystore;
+pub mod logs;
+pub mod nostr_verify;
+pub mod notify;
+pub mod outbox;
+pub mod path_guard;
+pub mod prefilter;
+pub mod profiles;
+pub mod quality;
+pub mod receipts;
+pub mod relay;
+pub mod relay_pool;
+pub mod sanitize;
+pub mod scan;
+pub mod send;
+pub mod stego_analysis;
+pub mod stego_qim;
+pub mod stego_synth;
+pub mod targets;
+pub mod thumbnail;
+pub mod units;
+pub mod upload;
+pub mod watch;
+
+// Codecs, crypto and Nostr events live in the Tauri-free stegstr-core crate.
+pub use stegstr_core::{bundle, error, fragment, heif, mask, nostr_codes, nostr_event, output, payload_header, progress, reed_solomon, stego, stego_anim, stego_crypto, stego_dot, stego_fountain, stego_key, stego_shamir};
 
 use base64::Engine;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::sync::mpsc;
-use std::thread;
 use std::time::Duration;
 
-/// Normalize path: strip file:// prefix if present (e.g. from some dialogs)
-fn normalize_path(s: &str) -> &str {
-    s.trim_start_matches("file://")
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct StegoDecodeResult {
     pub ok: bool,
     pub payload: Option<String>,
     pub error: Option<String>,
+    /// Kind of `error`, stable across releases.
+    #[serde(default)]
+    pub error_code: Option<error::ErrorCode>,
+    /// Fields cleaned by safe mode in a JSON bundle payload, per event.
+    #[serde(default)]
+    pub sanitized: Vec<sanitize::SanitizeNote>,
+    /// Bundle events dropped by safe mode (over the limit or not objects).
+    #[serde(default)]
+    pub dropped_events: usize,
+    /// Codec that found the payload, when it was detected (decode_stego_auto).
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Rotation/mirroring the image had undergone and decode undid (stego::orientation_name);
+    /// None when it decoded as stored.
+    #[serde(default)]
+    pub orientation: Option<String>,
+    /// Payload header (MIME type, file name, compression); None for payloads from older builds.
+    #[serde(default)]
+    pub header: Option<payload_header::PayloadHeader>,
+    /// Events of a plaintext bundle kept in the local event store (decodes of a file or URL, with
+    /// the store_events setting on).
+    #[serde(default)]
+    pub stored: Option<event_store::StoreReport>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl StegoDecodeResult {
+    fn success(payload: String) -> Self {
+        StegoDecodeResult {
+            ok: true,
+            payload: Some(payload),
+            ..Default::default()
+        }
+    }
+
+    /// Success for decoded bytes (payload header split off into `header`); JSON bundles go
+    /// through safe mode before crossing IPC.
+    fn decoded(payload: &[u8]) -> Self {
+        Self::decoded_each(payload, |_| {})
+    }
+
+    /// `decoded`, and with the store_events setting on, keeping each event of a plaintext bundle
+    /// in the local event store, noted as coming from `source`. The codecs hand over the payload only once it is fully recovered (the
+    /// error correction spans every tile), so events are stored after extraction; a bundle cut
+    /// short still stores the events before the break.
+    fn decoded_and_stored(payload: &[u8], source: &str) -> Self {
+        if !config::load().is_ok_and(|s| s.store_events == Some(true)) {
+            return Self::decoded(payload);
+        }
+        let mut store = match event_store::EventStore::open_default() {
+            Ok(store) => store,
+            Err(e) => {
+                tracing::warn!(error = %e, "event store unavailable");
+                return Self::decoded(payload);
+            }
+        };
+        let mut report = event_store::StoreReport::default();
+        let mut result = Self::decoded_each(payload, |event| {
+            match store.add(std::slice::from_ref(&event), Some(source)) {
+                Ok(r) => report += r,
+                Err(e) => tracing::warn!(error = %e, id = %event.id, "event not stored"),
+            }
+        });
+        if report != event_store::StoreReport::default() {
+            result.stored = Some(report);
+        }
+        result
+    }
+
+    /// `decoded`, handing each complete event of a plaintext bundle to `on_event` (before safe
+    /// mode, which may change what the signature covers).
+    fn decoded_each(payload: &[u8], on_event: impl FnMut(nostr_event::NostrEvent)) -> Self {
+        let (header, data) = match payload_header::read(payload) {
+            Ok(split) => split,
+            Err(e) => return Self::failure(e),
+        };
+        let text = match &header {
+            Some(h) if h.mime_type == payload_header::MIME_BUNDLE => match bundle::unpack(data) {
+                Ok(json) => json,
+                Err(e) => return Self::failure(e),
+            },
+            Some(h) if h.mime_type != payload_header::MIME_JSON => {
+                format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(&data))
+            }
+            _ => payload_to_string(&data),
+        };
+        if !text.starts_with("base64:") {
+            event_stream::EventStreamParser::new().feed_each(text.as_bytes(), on_event);
+        }
+        let result = if text.starts_with("base64:") {
+            Self::success(text)
+        } else {
+            match sanitize::sanitize_json(&text) {
+                Ok((clean, report)) => StegoDecodeResult {
+                    sanitized: report.notes,
+                    dropped_events: report.dropped_events,
+                    ..Self::success(clean)
+                },
+                // Not valid JSON: the frontend reports it as an invalid payload.
+                Err(_) => Self::success(text),
+            }
+        };
+        StegoDecodeResult { header, ..result }
+    }
+
+    fn failure(error: impl Into<error::StegoError>) -> Self {
+        let error = error.into();
+        StegoDecodeResult {
+            error_code: Some(error.code()),
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Payload argument from the webview: `base64:<data>` or UTF-8 text.
+fn payload_arg_bytes(payload: String) -> Result<Vec<u8>, String> {
+    if payload.starts_with("base64:") {
+        base64::engine::general_purpose::STANDARD
+            .decode(payload.trim_start_matches("base64:").as_bytes())
+            .map_err(|e| e.to_string())
+    } else {
+        Ok(payload.into_bytes())
+    }
+}
+
+/// `payload_arg_bytes` behind a payload header with the sniffed MIME type, as embedded.
+fn embed_payload_bytes(payload: String) -> Result<Vec<u8>, String> {
+    let bytes = payload_arg_bytes(payload)?;
+    Ok(payload_header::PayloadHeader::sniff(&bytes).write(&bytes)?)
+}
+
+/// `embed_payload_bytes`; with `in_image` a receipt signed by `receipt_privkey_hex` for an embed
+/// into `cover` with `method` is first added to the bundle (receipts::embed_in_bundle) and returned.
+fn receipt_payload_bytes(
+    payload: String,
+    cover: &str,
+    method: &str,
+    receipt_privkey_hex: Option<&str>,
+    in_image: bool,
+) -> Result<(Vec<u8>, Option<nostr_event::NostrEvent>), String> {
+    if !in_image {
+        return Ok((embed_payload_bytes(payload)?, None));
+    }
+    let sk = nostr_codes::parse_secret_key(receipt_privkey_hex.ok_or("receipt_in_image requires receipt_privkey_hex")?)?;
+    let (bundle, receipt) = receipts::embed_in_bundle(&payload_arg_bytes(payload)?, std::path::Path::new(cover), method, &sk)?;
+    Ok((payload_header::PayloadHeader::sniff(&bundle).write(&bundle)?, Some(receipt)))
+}
+
+/// Image data from the webview: base64, optionally as a `data:image/...;base64,` URL or with the
+/// `base64:` prefix payloads use.
+fn image_arg_bytes(image: &str) -> Result<Vec<u8>, String> {
+    let data = image.trim();
+    let data = match data.strip_prefix("data:") {
+        Some(url) => url.split_once(',').map(|(_, d)| d).ok_or("Invalid data URL")?,
+        None => data.trim_start_matches("base64:"),
+    };
+    base64::engine::general_purpose::STANDARD
+        .decode(data.as_bytes())
+        .map_err(|e| format!("Invalid base64 image: {}", e))
+}
+
+/// JSON payloads are returned as text; anything else as `base64:<data>`.
+fn payload_to_string(payload: &[u8]) -> String {
+    match std::str::from_utf8(payload) {
+        Ok(s) if s.trim_start().starts_with('{') => s.to_string(),
+        _ => format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(payload)),
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
 pub struct StegoEncodeResult {
     pub ok: bool,
     pub path: Option<String>,
     pub error: Option<String>,
+    /// Kind of `error`, stable across releases.
+    #[serde(default)]
+    pub error_code: Option<error::ErrorCode>,
+    /// Signed embedding receipt, when one was requested.
+    pub receipt: Option<nostr_event::NostrEvent>,
+    /// Read-back decode matched the payload; None when verification was skipped.
+    pub verified: Option<bool>,
+    /// Codec that produced the image ("dwt" or "dot").
+    pub method: Option<String>,
+    /// Settings that produced the image, e.g. "dwt (headroom 4)" after escalation.
+    pub settings: Option<String>,
+    /// Settings tried first whose read-back failed, with the reason.
+    #[serde(default)]
+    pub failed_attempts: Vec<String>,
+    /// Payload size against the cover's capacity for `method`.
+    pub capacity: Option<units::CapacityInfo>,
+    /// PSNR/SSIM of the stego image against the cover, when requested.
+    pub quality: Option<quality::QualityReport>,
+}
+
+/// Result of an in-memory encode: the stego PNG itself instead of a path.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct StegoImageResult {
+    pub ok: bool,
+    /// PNG file contents, base64.
+    pub image: Option<String>,
+    pub error: Option<String>,
+    /// Kind of `error`, stable across releases.
+    #[serde(default)]
+    pub error_code: Option<error::ErrorCode>,
 }
 
+impl StegoEncodeResult {
+    fn success(path: String) -> Self {
+        StegoEncodeResult {
+            ok: true,
+            path: Some(path),
+            ..Default::default()
+        }
+    }
+
+    /// Record a receipt for a successful encode. A receipt failure keeps ok=true (the image exists)
+    /// and is reported in `error`.
+    fn with_receipt(mut self, receipt_privkey_hex: Option<&str>, payload: &[u8], cover: &str, method: &str) -> Self {
+        let (true, Some(hex), Some(path)) = (self.ok, receipt_privkey_hex, self.path.clone()) else {
+            return self;
+        };
+        let recorded = nostr_codes::parse_secret_key(hex).and_then(|sk| {
+            receipts::record_embed(payload, std::path::Path::new(cover), std::path::Path::new(&path), method, &sk)
+        });
+        match recorded {
+            Ok(receipt) => self.receipt = Some(receipt),
+            Err(e) => self.error = Some(format!("Receipt not recorded: {}", e)),
+        }
+        self
+    }
+
+    /// Store a receipt that went into the image (receipt_payload_bytes) once the encode succeeded.
+    fn with_embedded_receipt(mut self, receipt: Option<nostr_event::NostrEvent>) -> Self {
+        let (true, Some(receipt)) = (self.ok, receipt) else {
+            return self;
+        };
+        match receipts::append_receipt(&receipt) {
+            Ok(()) => self.receipt = Some(receipt),
+            Err(e) => self.error = Some(format!("Receipt not recorded: {}", e)),
+        }
+        self
+    }
+
+    fn failure(error: impl Into<error::StegoError>) -> Self {
+        let error = error.into();
+        StegoEncodeResult {
+            error_code: Some(error.code()),
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Progress handles of the running stego commands, by the `task_id` the frontend gave them.
+#[derive(Default)]
+struct StegoTasks(std::sync::Mutex<std::collections::HashMap<String, progress::Progress>>);
+
+/// Payload of the `stego://progress` events.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StegoProgress {
+    pub task_id: String,
+    /// Whole percent done of the current pass (an encode retried with stronger settings starts over).
+    pub percent: u8,
+}
+
+/// A command's progress handle; leaves StegoTasks when the command returns.
+struct StegoTask<'a> {
+    tasks: &'a StegoTasks,
+    task_id: Option<String>,
+    progress: progress::Progress,
+}
+
+impl StegoTasks {
+    /// With a `task_id`, a handle emitting `stego://progress` on each whole percent that
+    /// cancel_stego_task can cancel; without one, a handle that reports nowhere.
+    fn start(&self, app: &tauri::AppHandle, task_id: Option<String>) -> Result<StegoTask<'_>, String> {
+        use tauri::Emitter;
+        let Some(id) = task_id else {
+            return Ok(StegoTask { tasks: self, task_id: None, progress: Default::default() });
+        };
+        let (app, event_id) = (app.clone(), id.clone());
+        let last = std::sync::atomic::AtomicU8::new(u8::MAX);
+        let progress = progress::Progress::new(move |fraction| {
+            let percent = (fraction.clamp(0.0, 1.0) * 100.0) as u8;
+            if last.swap(percent, std::sync::atomic::Ordering::Relaxed) != percent {
+                let _ = app.emit("stego://progress", StegoProgress { task_id: event_id.clone(), percent });
+            }
+        });
+        let mut running = self.0.lock().map_err(|e| e.to_string())?;
+        if running.contains_key(&id) {
+            return Err(format!("Task {} is already running", id));
+        }
+        running.insert(id.clone(), progress.clone());
+        Ok(StegoTask { tasks: self, task_id: Some(id), progress })
+    }
+}
+
+impl Drop for StegoTask<'_> {
+    fn drop(&mut self) {
+        if let (Some(id), Ok(mut running)) = (&self.task_id, self.tasks.0.lock()) {
+            running.remove(id);
+        }
+    }
+}
+
+/// Cancel the stego command started with `task_id`; it returns a result with error_code
+/// "cancelled". Returns whether such a command was running.
 #[tauri::command]
-fn decode_stego_image(path: String) -> Result<StegoDecodeResult, String> {
-    let p = normalize_path(&path);
-    match stego::decode(std::path::Path::new(p)) {
-        Ok(payload) => {
-            let payload_str = match String::from_utf8(payload.clone()) {
-                Ok(s) if s.trim_start().starts_with('{') => s,
-                _ => format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(&payload)),
-            };
-            Ok(StegoDecodeResult {
-                ok: true,
-                payload: Some(payload_str),
-                error: None,
-            })
+fn cancel_stego_task(tasks: tauri::State<'_, StegoTasks>, task_id: String) -> Result<bool, String> {
+    let running = tasks.0.lock().map_err(|e| e.to_string())?;
+    Ok(running.get(&task_id).map(|p| p.cancel()).is_some())
+}
+
+/// DWT decode; `stego_key` is the key the image was embedded with, if any. With a `task_id` it
+/// emits `stego://progress` (share of windows searched) and can be cancelled (cancel_stego_task).
+#[tauri::command(async)]
+fn decode_stego_image(
+    app: tauri::AppHandle,
+    tasks: tauri::State<'_, StegoTasks>,
+    path: String,
+    stego_key: Option<String>,
+    task_id: Option<String>,
+) -> Result<StegoDecodeResult, String> {
+    let p = path_guard::input(&path)?;
+    let key = stego_key::parse(stego_key.as_deref())?;
+    let task = tasks.start(&app, task_id)?;
+    match stego::decode_with_progress(&p, key.as_ref(), &task.progress) {
+        Ok((payload, orientation)) => Ok(StegoDecodeResult {
+            orientation: (orientation != stego::Orientation::NoTransforms)
+                .then(|| stego::orientation_name(orientation).to_string()),
+            ..StegoDecodeResult::decoded_and_stored(&payload, &path)
+        }),
+        Err(e) => Ok(StegoDecodeResult::failure(e)),
+    }
+}
+
+/// Decode with whichever codec finds a payload, cheapest first; `method` names it. With a
+/// `stego_key` only the keyed codecs (scan::KEYED_METHODS) are tried.
+#[tauri::command(async)]
+fn decode_stego_auto(path: String, stego_key: Option<String>) -> Result<StegoDecodeResult, String> {
+    let p = &path_guard::input(&path)?;
+    let decoded = match stego_key::parse(stego_key.as_deref())? {
+        Some(key) => scan::decode_timed_with_key(p, scan::KEYED_METHODS, Some(&key)).0.map_err(error::StegoError::NotStego),
+        None => scan::detect_and_decode(p),
+    };
+    match decoded {
+        Ok((payload, method)) => Ok(StegoDecodeResult {
+            method: Some(method),
+            ..StegoDecodeResult::decoded_and_stored(&payload, &path)
+        }),
+        Err(e) => Ok(StegoDecodeResult::failure(e)),
+    }
+}
+
+/// Reassemble a payload split across images (stegstr-cli embed --split): decode each image as in
+/// decode_stego_auto, then join the fragments in whatever order `paths` lists them. Images that do
+/// not decode are left out (fountain-coded and Shamir series need only some); the join error names
+/// them.
+#[tauri::command]
+fn join_stego_images(paths: Vec<String>, stego_key: Option<String>) -> Result<StegoDecodeResult, String> {
+    let key = stego_key::parse(stego_key.as_deref())?;
+    let mut parts = Vec::with_capacity(paths.len());
+    let mut skipped = Vec::new();
+    for path in &paths {
+        let p = &path_guard::input(path)?;
+        let decoded = match &key {
+            Some(key) => scan::decode_timed_with_key(p, scan::KEYED_METHODS, Some(key)).0.map_err(error::StegoError::NotStego),
+            None => scan::detect_and_decode(p),
+        };
+        match decoded {
+            Ok((payload, _)) => parts.push(payload),
+            Err(e) => skipped.push(format!("{}: {}", path, e)),
         }
+    }
+    match fragment::join(&parts) {
+        Ok(payload) => Ok(StegoDecodeResult::decoded_and_stored(&payload, &paths.join(", "))),
+        Err(e) if skipped.is_empty() => Ok(StegoDecodeResult::failure(e)),
         Err(e) => Ok(StegoDecodeResult {
-            ok: false,
-            payload: None,
-            error: Some(e),
+            error: Some(format!("{} (not decoded: {})", e, skipped.join("; "))),
+            ..StegoDecodeResult::failure(e)
         }),
     }
 }
 
+/// Payload of the `stegstr://watch-found` event: an image in the watched folder that decoded.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WatchFound {
+    pub path: String,
+    pub result: StegoDecodeResult,
+}
+
+/// Stop flag of the running folder watch, if any.
+#[derive(Default)]
+struct WatchState(std::sync::Mutex<Option<std::sync::Arc<std::sync::atomic::AtomicBool>>>);
+
+/// Watch `dir` for new PNG/JPEG files (replacing any running watch) and emit `stegstr://watch-found`
+/// for each one carrying a payload. Files already in the folder are skipped unless `include_existing`.
 #[tauri::command]
-fn decode_stego_dot(path: String) -> Result<StegoDecodeResult, String> {
-    let p = normalize_path(&path);
-    match stego_dot::decode(std::path::Path::new(p)) {
-        Ok(payload) => {
-            let payload_str = match String::from_utf8(payload.clone()) {
-                Ok(s) if s.trim_start().starts_with('{') => s,
-                _ => format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(&payload)),
+fn start_watch(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, WatchState>,
+    dir: String,
+    interval_ms: Option<u64>,
+    include_existing: Option<bool>,
+) -> Result<(), String> {
+    use tauri::Emitter;
+    let dir = path_guard::input(&dir)?;
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+    let interval = interval_ms.map(Duration::from_millis).unwrap_or(watch::DEFAULT_INTERVAL);
+    let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let mut running = state.0.lock().map_err(|e| e.to_string())?;
+    if let Some(previous) = running.replace(stop.clone()) {
+        previous.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+    std::thread::spawn(move || {
+        let result = watch::watch(&dir, interval, include_existing.unwrap_or(false), &stop, |path, decoded| {
+            let Ok((payload, method)) = decoded else { return };
+            let found = WatchFound {
+                path: path.to_string_lossy().to_string(),
+                result: StegoDecodeResult {
+                    method: Some(method),
+                    ..StegoDecodeResult::decoded_and_stored(&payload, &path.to_string_lossy())
+                },
             };
-            Ok(StegoDecodeResult {
-                ok: true,
-                payload: Some(payload_str),
-                error: None,
-            })
+            let _ = app.emit("stegstr://watch-found", found);
+        });
+        if let Err(e) = result {
+            let _ = app.emit("stegstr://watch-error", e);
         }
-        Err(e) => Ok(StegoDecodeResult {
-            ok: false,
-            payload: None,
-            error: Some(e),
-        }),
+    });
+    Ok(())
+}
+
+/// Stop the running folder watch; returns whether one was running.
+#[tauri::command]
+fn stop_watch(state: tauri::State<'_, WatchState>) -> Result<bool, String> {
+    let stop = state.0.lock().map_err(|e| e.to_string())?.take();
+    if let Some(stop) = &stop {
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+    Ok(stop.is_some())
+}
+
+/// Download an image (resumable, size-capped, checked against a `#sha256=` fragment or `sha256`)
+/// and decode it with each codec in turn.
+#[tauri::command(async)]
+fn decode_url(url: String, sha256: Option<String>, max_bytes: Option<u64>) -> Result<StegoDecodeResult, String> {
+    let (fetch_url, fragment_hash) = download::split_hash_fragment(&url);
+    let opts = download::DownloadOptions {
+        sha256: sha256.or(fragment_hash),
+        max_bytes: max_bytes.unwrap_or(download::DEFAULT_MAX_BYTES),
+        ..Default::default()
+    };
+    let report = match download::download_to_cache(&fetch_url, &opts) {
+        Ok(r) => r,
+        Err(e) => return Ok(StegoDecodeResult::failure(e)),
+    };
+    match scan::decode_any(std::path::Path::new(&report.path)) {
+        Ok(payload) => Ok(StegoDecodeResult::decoded_and_stored(&payload, &url)),
+        Err(e) => Ok(StegoDecodeResult::failure(e)),
     }
 }
 
 #[tauri::command]
-fn encode_stego_image(cover_path: String, output_path: String, payload: String) -> Result<StegoEncodeResult, String> {
-    let cover = normalize_path(&cover_path);
-    let output = normalize_path(&output_path);
-    let payload_bytes: Vec<u8> = if payload.starts_with("base64:") {
-        base64::engine::general_purpose::STANDARD
-            .decode(payload.trim_start_matches("base64:").as_bytes())
-            .map_err(|e| e.to_string())?
+fn decode_region(path: String, x: u32, y: u32, width: u32, height: u32) -> Result<StegoDecodeResult, String> {
+    let p = path_guard::input(&path)?;
+    let region = stego::Region { x, y, width, height };
+    match stego::decode_region(&p, region) {
+        Ok(payload) => Ok(StegoDecodeResult::decoded_and_stored(&payload, &path)),
+        Err(e) => Ok(StegoDecodeResult::failure(e)),
+    }
+}
+
+/// Header-only check for a DWT payload (stego::detect), for scanning many images quickly.
+#[tauri::command]
+fn detect_stego_image(path: String) -> Result<Option<stego::Detection>, String> {
+    stego::detect(&path_guard::input(&path)?).map_err(|e| e.to_string())
+}
+
+#[tauri::command(async)]
+fn decode_stego_dot(path: String, stego_key: Option<String>) -> Result<StegoDecodeResult, String> {
+    let p = path_guard::input(&path)?;
+    let key = stego_key::parse(stego_key.as_deref())?;
+    match stego_dot::decode_with_key(&p, key.as_ref()) {
+        Ok(payload) => Ok(StegoDecodeResult::decoded_and_stored(&payload, &path)),
+        Err(e) => Ok(StegoDecodeResult::failure(e)),
+    }
+}
+
+/// Shared body of the encode commands. With `verify`, the output is decoded back and, on a
+/// mismatch, re-encoded with stronger settings (targets::ESCALATION limited to `methods`, up to
+/// targets::max_attempts()); nothing is written unless some attempt verifies.
+/// Without it, only `methods[0]` is used. With `quality` the result carries PSNR/SSIM against the cover.
+#[allow(clippy::too_many_arguments)]
+fn encode_to_file(
+    cover: &str,
+    output: &str,
+    payload_bytes: &[u8],
+    methods: &[&'static str],
+    verify: bool,
+    mask: Option<&mask::MaskSpec>,
+    dwt: Option<&stego::EncodeOptions>,
+    receipt_privkey_hex: Option<&str>,
+    quality: bool,
+    overwrite: bool,
+    progress: &progress::Progress,
+) -> Result<StegoEncodeResult, String> {
+    let cover_path = std::path::Path::new(cover);
+    // Animated GIF covers come back as GIF (stego_anim): name the file for what it will hold, and
+    // refuse an existing file of that name before encoding.
+    let animated_gif = std::fs::read(cover_path).is_ok_and(|b| b.starts_with(b"GIF8") && stego_anim::is_animated(&b));
+    let output = match animated_gif {
+        true => std::path::Path::new(output).with_extension("gif").to_string_lossy().to_string(),
+        false => output.to_string(),
+    };
+    let output = output.as_str();
+    atomic_write::check_destination(std::path::Path::new(output), overwrite)?;
+    let failure = |e: String| match progress.is_cancelled() {
+        true => StegoEncodeResult::failure(error::StegoError::Cancelled(e)),
+        false => StegoEncodeResult::failure(e),
+    };
+    let (png_bytes, mut result) = if verify {
+        match targets::embed_verified_with_progress(cover_path, payload_bytes, methods, mask, dwt, targets::max_attempts(), progress) {
+            Ok(v) => (
+                v.png,
+                StegoEncodeResult {
+                    verified: Some(true),
+                    method: Some(v.settings.method.to_string()),
+                    settings: Some(v.settings.to_string()),
+                    failed_attempts: v.failed_attempts,
+                    ..StegoEncodeResult::success(output.to_string())
+                },
+            ),
+            Err(e) => {
+                return Ok(StegoEncodeResult {
+                    verified: Some(false),
+                    ..failure(e)
+                })
+            }
+        }
     } else {
-        payload.into_bytes()
+        match targets::embed_with_progress(cover_path, payload_bytes, &methods[..1], mask, dwt, progress) {
+            Ok((png, method)) => (
+                png,
+                StegoEncodeResult {
+                    method: Some(method.to_string()),
+                    settings: Some(method.to_string()),
+                    ..StegoEncodeResult::success(output.to_string())
+                },
+            ),
+            Err(e) => return Ok(failure(e)),
+        }
+    };
+    if quality {
+        result.quality = Some(quality::compare_bytes(cover_path, &png_bytes)?);
+    }
+    result.path = Some(output.to_string());
+    atomic_write::write(std::path::Path::new(output), &png_bytes, overwrite)?;
+    exchange::stamp_if_exchange(std::path::Path::new(output))?;
+    let method = result.method.clone().unwrap_or_default();
+    result.capacity = targets::payload_capacity(cover_path, &method, None, payload_bytes).ok();
+    result = result.with_receipt(receipt_privkey_hex, payload_bytes, cover, &method);
+    Ok(result)
+}
+
+/// QIM encode straight to a JPEG at `jpeg_quality`; `verify` checks the payload survives one more
+/// recompression (stego_qim::verify).
+#[allow(clippy::too_many_arguments)]
+fn encode_jpeg_to_file(
+    cover: &str,
+    output: &str,
+    payload_bytes: &[u8],
+    jpeg_quality: u8,
+    verify: bool,
+    receipt_privkey_hex: Option<&str>,
+    quality: bool,
+    overwrite: bool,
+) -> Result<StegoEncodeResult, String> {
+    atomic_write::check_destination(std::path::Path::new(output), overwrite)?;
+    let cover_path = std::path::Path::new(cover);
+    let jpeg = match stego_qim::encode_with_quality(cover_path, payload_bytes, jpeg_quality) {
+        Ok(j) => j,
+        Err(e) => return Ok(StegoEncodeResult::failure(format!("JPEG encode failed: {}", e))),
+    };
+    let settings = format!("qim (JPEG quality {})", jpeg_quality);
+    if verify {
+        if let Err(e) = stego_qim::verify(&jpeg, payload_bytes) {
+            return Ok(StegoEncodeResult {
+                verified: Some(false),
+                failed_attempts: vec![format!("{}: {}", settings, e)],
+                ..StegoEncodeResult::failure(format!("JPEG read-back failed: {}", e))
+            });
+        }
+    }
+    let mut result = StegoEncodeResult {
+        verified: verify.then_some(true),
+        method: Some("qim".to_string()),
+        settings: Some(settings),
+        ..StegoEncodeResult::success(output.to_string())
     };
-    let encode_result = stego::encode(std::path::Path::new(cover), &payload_bytes);
-    match encode_result {
-        Ok(png_bytes) => {
-            std::fs::write(output, png_bytes).map_err(|e| e.to_string())?;
-            Ok(StegoEncodeResult {
-                ok: true,
-                path: Some(output.to_string()),
-                error: None,
+    if quality {
+        result.quality = Some(quality::compare_bytes(cover_path, &jpeg)?);
+    }
+    atomic_write::write(std::path::Path::new(output), &jpeg, overwrite)?;
+    exchange::stamp_if_exchange(std::path::Path::new(output))?;
+    result.capacity = targets::payload_capacity(cover_path, "qim", None, payload_bytes).ok();
+    Ok(result.with_receipt(receipt_privkey_hex, payload_bytes, cover, "qim"))
+}
+
+/// Encode for a survival profile; the output path gets the profile's extension.
+#[allow(clippy::too_many_arguments)]
+fn encode_profile_to_file(
+    cover: &str,
+    output_path: &str,
+    payload_bytes: &[u8],
+    profile: &profiles::Profile,
+    mask: Option<&mask::MaskSpec>,
+    key: Option<stego_key::StegoKey>,
+    verify: bool,
+    receipt_privkey_hex: Option<&str>,
+    quality: bool,
+    overwrite: bool,
+) -> Result<StegoEncodeResult, String> {
+    let cover_path = std::path::Path::new(cover);
+    let output = path_guard::output(output_path)?.with_extension(profile.extension());
+    atomic_write::check_destination(&output, overwrite)?;
+    let output = output.to_string_lossy().to_string();
+    let embedded = match profile.embed(cover_path, payload_bytes, mask, key, verify, targets::max_attempts()) {
+        Ok(e) => e,
+        Err(e) => {
+            return Ok(StegoEncodeResult {
+                verified: verify.then_some(false),
+                ..StegoEncodeResult::failure(e)
             })
         }
-        Err(e) => Ok(StegoEncodeResult {
-            ok: false,
-            path: None,
-            error: Some(e),
-        }),
+    };
+    let mut result = StegoEncodeResult {
+        verified: verify.then_some(true),
+        method: Some(embedded.method.to_string()),
+        settings: Some(embedded.settings),
+        failed_attempts: embedded.failed_attempts,
+        ..StegoEncodeResult::success(output.clone())
+    };
+    if quality {
+        result.quality = Some(quality::compare_bytes(cover_path, &embedded.bytes)?);
     }
+    atomic_write::write(std::path::Path::new(&output), &embedded.bytes, overwrite)?;
+    exchange::stamp_if_exchange(std::path::Path::new(&output))?;
+    result.capacity = profile.capacity(cover_path, payload_bytes).ok();
+    Ok(result.with_receipt(receipt_privkey_hex, payload_bytes, cover, embedded.method))
+}
+
+/// DWT encode of image bytes (e.g. a dropped or pasted image, base64) without temp files.
+#[tauri::command]
+fn encode_stego_image_bytes(image: String, payload: String) -> Result<StegoImageResult, String> {
+    let image = image_arg_bytes(&image)?;
+    let payload_bytes = embed_payload_bytes(payload)?;
+    Ok(match stego::encode_bytes(&image, &payload_bytes) {
+        Ok(png) => StegoImageResult {
+            ok: true,
+            image: Some(base64::engine::general_purpose::STANDARD.encode(png)),
+            ..Default::default()
+        },
+        Err(e) => StegoImageResult {
+            error_code: Some(e.code()),
+            error: Some(e.into()),
+            ..Default::default()
+        },
+    })
 }
 
 #[tauri::command]
-fn encode_stego_dot(cover_path: String, output_path: String, payload: String) -> Result<StegoEncodeResult, String> {
-    let cover = normalize_path(&cover_path);
-    let output_raw = normalize_path(&output_path);
-    let output_path_buf = std::path::Path::new(output_raw).with_extension("png");
+fn decode_stego_image_bytes(image: String) -> Result<StegoDecodeResult, String> {
+    let image = image_arg_bytes(&image)?;
+    match stego::decode_bytes(&image) {
+        Ok(payload) => Ok(StegoDecodeResult::decoded(&payload)),
+        Err(e) => Ok(StegoDecodeResult::failure(e)),
+    }
+}
+
+/// DWT encode; if read-back fails, escalates to DWT with headroom. Only DWT is verified: when no
+/// rung reads back, the result is a failure (ok=false, verified=false) and nothing is written.
+/// `tile_size`, `redundancy`, `channels`, `parity`, `interleave` and `strength` override the DWT
+/// layout defaults (stego::EncodeOptions); `strip_metadata` leaves the cover's ICC profile, EXIF
+/// and PNG text chunks out of the output; `spread` fountain-codes the payload over an animated
+/// cover's frames (APNG keeps DWT; animated GIFs take dot and are written as .gif);
+/// `stego_key` seeds the embedding positions of both codecs. `quality`
+/// adds a PSNR/SSIM report. `format` is "png" (default), "webp" (lossless; the output path gets
+/// a .webp extension) or "jpeg": a .jpg written by the QIM codec at `jpeg_quality` (default 75),
+/// verified to survive one more recompression; the DWT layout options, masks and the stego key do
+/// not apply to it. `profile` ("twitter", "discord", "telegram", "imgur") picks codec, layout and
+/// output format for that platform instead (see list_profiles).
+/// An existing output file is an error unless `overwrite`; the output is written to a temporary
+/// file and renamed into place (atomic_write). `receipt_in_image` (with `receipt_privkey_hex`, and
+/// a bundle JSON payload) adds the signed receipt to the embedded bundle as well as the local store.
+/// With a `task_id` the DWT and dot paths emit `stego://progress` (DWT tiles embedded, then windows
+/// read back) and can be cancelled (cancel_stego_task); profile and JPEG encodes cannot.
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn encode_stego_image(
+    app: tauri::AppHandle,
+    tasks: tauri::State<'_, StegoTasks>,
+    cover_path: String,
+    output_path: String,
+    payload: String,
+    receipt_privkey_hex: Option<String>,
+    verify: Option<bool>,
+    mask: Option<serde_json::Value>,
+    tile_size: Option<u32>,
+    redundancy: Option<u32>,
+    channels: Option<u8>,
+    parity: Option<u8>,
+    stego_key: Option<String>,
+    quality: Option<bool>,
+    format: Option<String>,
+    jpeg_quality: Option<u8>,
+    profile: Option<String>,
+    interleave: Option<u32>,
+    strength: Option<u8>,
+    strip_metadata: Option<bool>,
+    spread: Option<u32>,
+    overwrite: Option<bool>,
+    receipt_in_image: Option<bool>,
+    task_id: Option<String>,
+) -> Result<StegoEncodeResult, String> {
+    let cover = &path_guard::input(&cover_path)?.to_string_lossy().into_owned();
+    let mask = mask.map(ipc_schema::parse_options::<mask::MaskSpec>).transpose()?;
+    let overwrite = overwrite.unwrap_or(false);
+    let task = tasks.start(&app, task_id)?;
+    let jpeg = format.as_deref().is_some_and(|f| f.eq_ignore_ascii_case("jpeg") || f.eq_ignore_ascii_case("jpg"));
+    let method = if jpeg && profile.is_none() { "qim" } else { "dwt" };
+    let (payload_bytes, embedded) = receipt_payload_bytes(payload, cover, method, receipt_privkey_hex.as_deref(), receipt_in_image.unwrap_or(false))?;
+    let receipt_privkey_hex = receipt_privkey_hex.filter(|_| embedded.is_none());
+    if let Some(name) = profile {
+        if tile_size.is_some() || redundancy.is_some() || channels.is_some() || parity.is_some() || interleave.is_some() || strength.is_some() || strip_metadata.is_some() || spread.is_some() || format.is_some() || jpeg_quality.is_some() {
+            return Err("A profile picks its own layout, format and metadata handling".to_string());
+        }
+        let key = stego_key::parse(stego_key.as_deref())?;
+        return encode_profile_to_file(
+            cover,
+            &output_path,
+            &payload_bytes,
+            profiles::find(&name)?,
+            mask.as_ref(),
+            key,
+            verify.unwrap_or(true),
+            receipt_privkey_hex.as_deref(),
+            quality.unwrap_or(false),
+            overwrite,
+        )
+        .map(|r| r.with_embedded_receipt(embedded));
+    }
+    if jpeg {
+        if mask.is_some_and(|m| !m.is_empty()) || stego_key.is_some() {
+            return Err("JPEG output does not take masks or a stego key".to_string());
+        }
+        let output = path_guard::output(&output_path)?.with_extension("jpg");
+        return encode_jpeg_to_file(
+            cover,
+            &output.to_string_lossy(),
+            &payload_bytes,
+            jpeg_quality.unwrap_or(stego_qim::DEFAULT_QUALITY),
+            verify.unwrap_or(true),
+            receipt_privkey_hex.as_deref(),
+            quality.unwrap_or(false),
+            overwrite,
+        )
+        .map(|r| r.with_embedded_receipt(embedded));
+    }
+    if jpeg_quality.is_some() {
+        return Err("jpeg_quality applies to format \"jpeg\"".to_string());
+    }
+    let format = format.as_deref().map(output::OutputFormat::parse).transpose()?.unwrap_or_default();
+    let output_path_buf = match format {
+        output::OutputFormat::Png => path_guard::output(&output_path)?,
+        other => path_guard::output(&output_path)?.with_extension(other.extension()),
+    };
+    let output = &output_path_buf.to_string_lossy().to_string();
+    let defaults = stego::EncodeOptions::default();
+    let dwt = stego::EncodeOptions {
+        tile_size: tile_size.unwrap_or(defaults.tile_size),
+        redundancy: redundancy.unwrap_or(defaults.redundancy),
+        channels: channels.unwrap_or(defaults.channels),
+        parity: parity.unwrap_or(defaults.parity),
+        key: stego_key::parse(stego_key.as_deref())?,
+        format,
+        interleave: interleave.unwrap_or(defaults.interleave),
+        strength: strength.unwrap_or(defaults.strength),
+        strip_metadata: strip_metadata.unwrap_or(defaults.strip_metadata),
+        spread: spread.unwrap_or(defaults.spread),
+    }
+    .validated()?;
+    encode_to_file(
+        cover,
+        output,
+        &payload_bytes,
+        &["dwt"],
+        verify.unwrap_or(true),
+        mask.as_ref(),
+        Some(&dwt),
+        receipt_privkey_hex.as_deref(),
+        quality.unwrap_or(false),
+        overwrite,
+        &task.progress,
+    )
+    .map(|r| r.with_embedded_receipt(embedded))
+}
+
+/// Dot encode; `stego_key`, `quality`, `format`, `strip_metadata`, `spread`, `overwrite`,
+/// `receipt_in_image` and `task_id` as for encode_stego_image.
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn encode_stego_dot(
+    app: tauri::AppHandle,
+    tasks: tauri::State<'_, StegoTasks>,
+    cover_path: String,
+    output_path: String,
+    payload: String,
+    receipt_privkey_hex: Option<String>,
+    verify: Option<bool>,
+    mask: Option<serde_json::Value>,
+    stego_key: Option<String>,
+    quality: Option<bool>,
+    format: Option<String>,
+    strip_metadata: Option<bool>,
+    spread: Option<u32>,
+    overwrite: Option<bool>,
+    receipt_in_image: Option<bool>,
+    task_id: Option<String>,
+) -> Result<StegoEncodeResult, String> {
+    let cover = &path_guard::input(&cover_path)?.to_string_lossy().into_owned();
+    let mask = mask.map(ipc_schema::parse_options::<mask::MaskSpec>).transpose()?;
+    let overwrite = overwrite.unwrap_or(false);
+    let task = tasks.start(&app, task_id)?;
+    let key = stego_key::parse(stego_key.as_deref())?;
+    let format = format.as_deref().map(output::OutputFormat::parse).transpose()?.unwrap_or_default();
+    // Only the key, format, metadata choice and spread of the DWT options reach the dot codec.
+    let options = stego::EncodeOptions {
+        key,
+        format,
+        strip_metadata: strip_metadata.unwrap_or(false),
+        spread: spread.unwrap_or(0),
+        ..Default::default()
+    };
+    let output_path_buf = path_guard::output(&output_path)?.with_extension(format.extension());
     let output = output_path_buf.to_string_lossy().to_string();
-    let payload_bytes: Vec<u8> = if payload.starts_with("base64:") {
-        base64::engine::general_purpose::STANDARD
-            .decode(payload.trim_start_matches("base64:").as_bytes())
-            .map_err(|e| e.to_string())?
-    } else {
-        payload.into_bytes()
+    let (payload_bytes, embedded) = receipt_payload_bytes(payload, cover, "dot", receipt_privkey_hex.as_deref(), receipt_in_image.unwrap_or(false))?;
+    let receipt_privkey_hex = receipt_privkey_hex.filter(|_| embedded.is_none());
+    encode_to_file(
+        cover,
+        &output,
+        &payload_bytes,
+        &["dot"],
+        verify.unwrap_or(true),
+        mask.as_ref(),
+        Some(&options),
+        receipt_privkey_hex.as_deref(),
+        quality.unwrap_or(false),
+        overwrite,
+        &task.progress,
+    )
+    .map(|r| r.with_embedded_receipt(embedded))
+}
+
+/// `options` is a SendOptions object (see get_schemas).
+#[tauri::command]
+fn send_to_contact(options: serde_json::Value) -> Result<send::SendResult, String> {
+    let opts: send::SendOptions = ipc_schema::parse_options(options)?;
+    let sk = nostr_codes::parse_secret_key(&opts.privkey_hex)?;
+    let req = send::SendRequest {
+        content: &opts.content,
+        recipient: &opts.recipient,
+        cover_path: &path_guard::input(&opts.cover_path)?,
+        output_path: &path_guard::output(&opts.output_path)?,
+        sender_sk: &sk,
+        upload: opts.upload.unwrap_or(false),
+        receipt: opts.receipt.unwrap_or(false),
+        target: opts.target.as_deref().map(targets::find).transpose()?,
+        overwrite: opts.overwrite.unwrap_or(false),
+        receipt_in_image: opts.receipt_in_image.unwrap_or(false),
     };
-    let encode_result = stego_dot::encode(std::path::Path::new(cover), &payload_bytes);
-    match encode_result {
-        Ok(png_bytes) => {
-            std::fs::write(output.clone(), png_bytes).map_err(|e| e.to_string())?;
-            let sig = std::fs::read(&output).map_err(|e| e.to_string())?;
-            if sig.len() < 8 || sig[..8] != [0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a] {
-                return Ok(StegoEncodeResult {
-                    ok: false,
-                    path: None,
-                    error: Some("Dot encoder output is not PNG".to_string()),
-                });
-            }
-            Ok(StegoEncodeResult {
-                ok: true,
-                path: Some(output.to_string()),
-                error: None,
-            })
-        }
-        Err(e) => Ok(StegoEncodeResult {
-            ok: false,
-            path: None,
-            error: Some(e),
-        }),
+    Ok(send::send(&req))
+}
+
+/// `options` is a DmOptions object (see get_schemas): a NIP-44 encrypted kind 4 or NIP-17 kind 14
+/// direct message, bundled and embedded into `cover_path` when given.
+#[tauri::command]
+fn send_dm(options: serde_json::Value) -> Result<dm::DmResult, String> {
+    let opts: dm::DmOptions = ipc_schema::parse_options(options)?;
+    let sk = nostr_codes::parse_secret_key(&opts.privkey_hex)?;
+    let cover_path = opts.cover_path.as_deref().map(path_guard::input).transpose()?;
+    let output_path = opts.output_path.as_deref().map(path_guard::output).transpose()?;
+    let req = dm::DmRequest {
+        message: &opts.message,
+        recipient: &opts.recipient,
+        kind: opts.kind.unwrap_or(dm::KIND_DM),
+        sender_sk: &sk,
+        cover_path: cover_path.as_deref(),
+        output_path: output_path.as_deref(),
+        overwrite: opts.overwrite.unwrap_or(false),
+    };
+    Ok(dm::send_dm(&req))
+}
+
+/// Sign an event template (kind, tags, content, optional created_at) with the keychain key
+/// `key_name` (store_key), so the webview holds neither signing code nor the secret. The content
+/// is signed as given.
+#[tauri::command]
+fn sign_nostr_event(event: serde_json::Value, key_name: String) -> Result<nostr_event::NostrEvent, String> {
+    keystore::sign_with_key(&key_name, &ipc_schema::parse_options(event)?)
+}
+
+/// Store a secret key (hex or nsec) in the OS keychain under `name`, replacing any key of that name.
+#[tauri::command]
+fn store_key(name: String, secret: String) -> Result<keystore::StoredKey, String> {
+    keystore::store_key(&name, &secret)
+}
+
+/// Keys stored with store_key (names and public keys only).
+#[tauri::command]
+fn list_keys() -> Result<Vec<keystore::StoredKey>, String> {
+    keystore::list_keys()
+}
+
+/// Sign an event template with the keychain key `name`; the secret never reaches the webview.
+#[tauri::command]
+fn sign_with_key(name: String, event: serde_json::Value) -> Result<nostr_event::NostrEvent, String> {
+    keystore::sign_with_key(&name, &ipc_schema::parse_options(event)?)
+}
+
+/// Delete a stored key; false if there was none.
+#[tauri::command]
+fn remove_key(name: String) -> Result<bool, String> {
+    keystore::remove_key(&name)
+}
+
+/// Decrypt a direct message (kind 4 or gift wrap) from a decoded bundle with our key.
+#[tauri::command]
+fn open_dm(event: serde_json::Value, privkey_hex: String) -> Result<dm::DirectMessage, String> {
+    dm::open_dm(&ipc_schema::parse_options(event)?, &nostr_codes::parse_secret_key(&privkey_hex)?)
+}
+
+/// Native OS notification; returns false when disabled via STEGSTR_NO_NOTIFY.
+#[tauri::command]
+fn notify(title: String, body: String) -> Result<bool, String> {
+    notify::notify(&title, &body)
+}
+
+/// Publish a signed event to all relays concurrently (default: the configured relays, else
+/// relay::DEFAULT_RELAYS; 5 s).
+/// Returns one outcome per relay: ok, rejected (with the relay's reason), timeout or error.
+#[tauri::command]
+fn publish_event(
+    event: serde_json::Value,
+    relays: Option<Vec<String>>,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<relay::RelayOutcome>, String> {
+    let event: nostr_event::NostrEvent = ipc_schema::parse_options(event)?;
+    let relays = match relays.filter(|r| !r.is_empty()) {
+        Some(r) => r,
+        None => config::load()?.relays_or_default(),
+    };
+    let timeout = timeout_ms.map(Duration::from_millis).unwrap_or(relay::DEFAULT_TIMEOUT);
+    relay::publish(&event, &relays, timeout)
+}
+
+/// Keep decoded events (verified, deduplicated by id) in the local event store, noting the image
+/// they came from. With the store_events setting on, the decode commands store plaintext bundles
+/// themselves; this is for bundles decrypted in the webview.
+#[tauri::command]
+fn store_decoded_events(events: serde_json::Value, source: Option<String>) -> Result<event_store::StoreReport, String> {
+    let events: Vec<nostr_event::NostrEvent> = ipc_schema::parse_options(events)?;
+    event_store::EventStore::open_default()?.add(&events, source.as_deref())
+}
+
+/// Stored events matching a NIP-01 filter (authors, kinds, since/until), newest first.
+#[tauri::command]
+fn query_stored_events(filter: serde_json::Value) -> Result<Vec<event_store::StoredEvent>, String> {
+    event_store::EventStore::open_default()?.query(&ipc_schema::parse_options(filter)?)
+}
+
+/// Queue signed events (e.g. notes composed offline and embedded into images) for publishing
+/// later; returns how many were not queued already.
+#[tauri::command]
+fn queue_outbox_events(events: serde_json::Value) -> Result<usize, String> {
+    outbox::add(&ipc_schema::parse_options::<Vec<nostr_event::NostrEvent>>(events)?)
+}
+
+/// Queued events with their publish status and each relay's latest outcome, oldest first.
+#[tauri::command]
+fn list_outbox() -> Result<Vec<outbox::OutboxEntry>, String> {
+    outbox::list()
+}
+
+/// Publish every pending outbox event over the app's relay connections (relays and timeout as
+/// for publish_event); events no relay answered stay pending for the next flush.
+#[tauri::command(async)]
+fn flush_outbox(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, RelayState>,
+    relays: Option<Vec<String>>,
+    timeout_ms: Option<u64>,
+) -> Result<outbox::FlushReport, String> {
+    let relays = match relays.filter(|r| !r.is_empty()) {
+        Some(r) => r,
+        None => config::load()?.relays_or_default(),
+    };
+    let timeout = timeout_ms.map(Duration::from_millis).unwrap_or(relay::DEFAULT_TIMEOUT);
+    let report = outbox::flush(&state.pool(&app)?, &relays, timeout)?;
+    report.notify_done();
+    Ok(report)
+}
+
+/// Remove outbox events by id, or every sent one without `ids`; returns how many.
+#[tauri::command]
+fn remove_outbox_events(ids: Option<Vec<String>>) -> Result<usize, String> {
+    outbox::remove(ids.as_deref())
+}
+
+/// The app's relay connections, created on first use so they can emit to the webview.
+#[derive(Default)]
+struct RelayState(std::sync::Mutex<Option<relay_pool::RelayPool>>);
+
+impl RelayState {
+    fn pool(&self, app: &tauri::AppHandle) -> Result<relay_pool::RelayPool, String> {
+        use tauri::Emitter;
+        let mut pool = self.0.lock().map_err(|e| e.to_string())?;
+        let app = app.clone();
+        Ok(pool
+            .get_or_insert_with(|| relay_pool::RelayPool::new(move |message| {
+                let _ = app.emit("relay://message", message);
+            }))
+            .clone())
     }
 }
 
+/// Keep connections open to `relays` (default: the configured relays, else relay::DEFAULT_RELAYS),
+/// reconnecting as needed. Traffic arrives as `relay://message` events (relay_pool::RelayMessage).
+#[tauri::command]
+fn relay_connect(app: tauri::AppHandle, state: tauri::State<'_, RelayState>, relays: Option<Vec<String>>) -> Result<Vec<relay_pool::RelayStatus>, String> {
+    let relays = match relays.filter(|r| !r.is_empty()) {
+        Some(r) => r,
+        None => config::load()?.relays_or_default(),
+    };
+    let pool = state.pool(&app)?;
+    pool.connect(&relays)?;
+    Ok(pool.status())
+}
+
+/// Close connections to `relays` (all when omitted); returns how many were open.
+#[tauri::command]
+fn relay_disconnect(app: tauri::AppHandle, state: tauri::State<'_, RelayState>, relays: Option<Vec<String>>) -> Result<usize, String> {
+    Ok(state.pool(&app)?.disconnect(relays.as_deref()))
+}
+
+#[tauri::command]
+fn relay_status(app: tauri::AppHandle, state: tauri::State<'_, RelayState>) -> Result<Vec<relay_pool::RelayStatus>, String> {
+    Ok(state.pool(&app)?.status())
+}
+
+/// Subscribe on every connected relay; matching events stream as `relay://message` until
+/// relay_unsubscribe. Returns the subscription id (`subscription_id`, or a generated one).
+#[tauri::command]
+fn relay_subscribe(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, RelayState>,
+    filters: serde_json::Value,
+    subscription_id: Option<String>,
+) -> Result<String, String> {
+    state.pool(&app)?.subscribe(subscription_id, ipc_schema::parse_options(filters)?)
+}
+
+#[tauri::command]
+fn relay_unsubscribe(app: tauri::AppHandle, state: tauri::State<'_, RelayState>, subscription_id: String) -> Result<bool, String> {
+    Ok(state.pool(&app)?.unsubscribe(&subscription_id))
+}
+
+/// Publish over the open connections (or `relays`, connecting as needed) and wait for each OK.
+#[tauri::command(async)]
+fn relay_publish(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, RelayState>,
+    event: serde_json::Value,
+    relays: Option<Vec<String>>,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<relay::RelayOutcome>, String> {
+    let event: nostr_event::NostrEvent = ipc_schema::parse_options(event)?;
+    let timeout = timeout_ms.map(Duration::from_millis).unwrap_or(relay::DEFAULT_TIMEOUT);
+    state.pool(&app)?.publish(&event, relays.as_deref(), timeout)
+}
+
+/// Fetch events matching a NIP-01 filter from all relays concurrently (same defaults as publish_event).
+/// Events are deduplicated, signature-checked and sorted oldest first.
+#[tauri::command]
+fn fetch_events(
+    filter: serde_json::Value,
+    relays: Option<Vec<String>>,
+    timeout_ms: Option<u64>,
+) -> Result<relay::FetchReport, String> {
+    let filter: relay::Filter = ipc_schema::parse_options(filter)?;
+    let relays = match relays.filter(|r| !r.is_empty()) {
+        Some(r) => r,
+        None => config::load()?.relays_or_default(),
+    };
+    let timeout = timeout_ms.map(Duration::from_millis).unwrap_or(relay::DEFAULT_TIMEOUT);
+    relay::fetch(&filter, &relays, timeout)
+}
+
+/// Identicon parameters and word fingerprint for an npub or hex pubkey (sender verification).
+#[tauri::command]
+fn key_fingerprint(pubkey: String) -> Result<fingerprint::Fingerprint, String> {
+    fingerprint::fingerprint(&pubkey)
+}
+
+/// External codecs registered in codecs.json; errors if the config is invalid.
+#[tauri::command]
+fn list_external_codecs() -> Result<Vec<external::ExternalCodec>, String> {
+    external::registry().map(<[_]>::to_vec)
+}
+
+/// Named sharing targets (email, matrix, print, usb) for the output preset dropdown.
+#[tauri::command]
+fn list_targets() -> &'static [targets::Target] {
+    targets::TARGETS
+}
+
+/// Chi-square and RS steganalysis of an image: how detectable it is before sharing.
+#[tauri::command]
+fn analyze_stego_image(path: String) -> Result<stego_analysis::AnalysisReport, String> {
+    stego_analysis::analyze(&path_guard::input(&path)?)
+}
+
+/// Social-platform survival profiles (twitter, discord, telegram, imgur).
+#[tauri::command]
+fn list_profiles() -> &'static [profiles::Profile] {
+    profiles::PROFILES
+}
+
+/// Stored embedding receipts, oldest first.
+#[tauri::command]
+fn list_receipts() -> Result<Vec<nostr_event::NostrEvent>, String> {
+    receipts::list_receipts()
+}
+
+/// JSON Schemas of the IPC types, keyed by type name.
+#[tauri::command]
+fn get_schemas() -> std::collections::BTreeMap<&'static str, serde_json::Value> {
+    ipc_schema::schemas()
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SalvageResult {
+    pub events: Vec<nostr_event::NostrEvent>,
+    /// Object entries that closed but were not valid events.
+    pub skipped: usize,
+    /// False when the payload ended inside an event.
+    pub complete: bool,
+    /// Fields cleaned by safe mode, per event.
+    #[serde(default)]
+    pub sanitized: Vec<sanitize::SanitizeNote>,
+}
+
+/// Recover complete events from a truncated or partly corrupt bundle payload (JSON or base64:).
+#[tauri::command]
+fn salvage_bundle_events(payload: String) -> Result<SalvageResult, String> {
+    let bytes = payload_arg_bytes(payload)?;
+    let mut parser = event_stream::EventStreamParser::new();
+    let events = parser.feed(&bytes);
+    let report = sanitize::sanitize_bundle(serde_json::to_value(&events).map_err(|e| e.to_string())?);
+    Ok(SalvageResult {
+        events: serde_json::from_value(report.bundle).map_err(|e| e.to_string())?,
+        skipped: parser.skipped,
+        complete: !parser.has_partial_event(),
+        sanitized: report.notes,
+    })
+}
+
+/// Safe mode for a bundle decrypted in the webview: same limits as the decode commands.
+#[tauri::command]
+fn sanitize_bundle(bundle_json: String) -> Result<sanitize::SanitizedBundle, String> {
+    sanitize::sanitize_json(&bundle_json).map(|(_, report)| report)
+}
+
+/// Bundle JSON as stored (bundle::pack, `base64:`): the text's bytes, or with `format` v2 the
+/// bundle v2 form of a v1 bundle. stego-crypto.ts encrypts this, so app-encrypted bundles take
+/// the same format choice as the Rust encrypt paths.
+#[tauri::command]
+fn pack_bundle(json: String, format: Option<serde_json::Value>) -> Result<String, String> {
+    let format: Option<bundle::BundleFormat> = format.map(ipc_schema::parse_options).transpose()?;
+    Ok(format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(bundle::pack(json.as_bytes(), format.unwrap_or_default()))))
+}
+
+/// Undo pack_bundle on a decrypted plaintext (`base64:`): v1 JSON.
+#[tauri::command]
+fn unpack_bundle(data: String) -> Result<String, String> {
+    Ok(bundle::unpack(payload_arg_bytes(data)?)?)
+}
+
+/// Combine bundles decoded from several images: valid events only, each once, oldest first.
+#[tauri::command]
+fn merge_bundles(bundles: serde_json::Value) -> Result<nostr_event::Bundle, String> {
+    Ok(bundle::merge(ipc_schema::parse_options(bundles)?))
+}
+
+/// Recompute every event id in a decrypted bundle and check its signature.
+#[tauri::command]
+fn verify_bundle(bundle_json: String) -> Result<nostr_verify::BundleVerification, String> {
+    nostr_verify::verify_bundle_json(&bundle_json)
+}
+
+/// Encrypt `plaintext` for one Nostr user (npub or hex); returns a `base64:` payload for the encode
+/// commands. `cipher` is "aes-256-gcm" (default) or "chacha20-poly1305"; decrypt_with_privkey
+/// detects it.
+#[tauri::command]
+fn encrypt_for_pubkey(plaintext: String, recipient: String, cipher: Option<String>) -> Result<String, String> {
+    let cipher = cipher.as_deref().map(stego_crypto::Cipher::parse).transpose()?.unwrap_or_default();
+    let encrypted = stego_crypto::encrypt_for_pubkey_with(&plaintext, &recipient, &stego_crypto::EncryptOptions { cipher, ..Default::default() })?;
+    Ok(format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(encrypted)))
+}
+
+/// Decrypt a decoded public-key payload (`base64:` as returned by the decode commands).
+/// `privkey_hex` may also be an nsec.
+#[tauri::command]
+fn decrypt_with_privkey(payload: String, privkey_hex: String) -> Result<String, String> {
+    let sk = nostr_codes::parse_secret_key(&privkey_hex)?;
+    stego_crypto::decrypt_with_privkey(&payload_arg_bytes(payload)?, &sk).map_err(String::from)
+}
+
 #[tauri::command]
 fn check_png_signature(path: String) -> Result<bool, String> {
-    let p = normalize_path(&path);
+    let p = path_guard::input(&path)?;
     let sig = std::fs::read(p).map_err(|e| e.to_string())?;
     Ok(sig.len() >= 8 && sig[..8] == [0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a])
 }
 
+/// File extensions the cover pickers offer: HEIC/AVIF only in builds with the heif feature.
+#[tauri::command]
+fn supported_cover_extensions() -> Vec<&'static str> {
+    let mut extensions = vec!["png", "jpg", "jpeg", "gif", "webp", "bmp"];
+    if heif::supported() {
+        extensions.extend(["heic", "heif", "avif"]);
+    }
+    extensions
+}
+
+/// DWT capacity of a cover: the overall maximum and the size that still gets a copy per tile.
+/// Downscaled base64 preview of an image for display (thumbnail::generate): at most
+/// `max_dimension` px (default 256) as "jpeg" (default, at `quality`, default 80) or "webp".
+#[tauri::command(async)]
+fn generate_thumbnail(path: String, max_dimension: Option<u32>, quality: Option<u8>, format: Option<String>) -> Result<thumbnail::Thumbnail, String> {
+    thumbnail::generate(
+        &path_guard::input(&path)?,
+        max_dimension.unwrap_or(thumbnail::DEFAULT_MAX_DIMENSION),
+        quality.unwrap_or(thumbnail::DEFAULT_QUALITY),
+        format.as_deref().unwrap_or("jpeg"),
+    )
+}
+
+/// Where `stego_path` differs from `cover_path`: a base64 PNG overlay plus per-tile change shares
+/// (quality::heatmap).
+#[tauri::command(async)]
+fn stego_heatmap(cover_path: String, stego_path: String) -> Result<quality::Heatmap, String> {
+    quality::heatmap(&path_guard::input(&cover_path)?, &path_guard::input(&stego_path)?)
+}
+
+#[tauri::command]
+fn get_dwt_capacity(path: String) -> Result<stego::DwtCapacity, String> {
+    let p = path_guard::input(&path)?;
+    stego::capacity(&p).map_err(String::from)
+}
+
 #[tauri::command]
 fn get_dot_capacity(path: String) -> Result<usize, String> {
-    let p = normalize_path(&path);
-    stego_dot::max_payload_bytes(std::path::Path::new(p))
+    let p = path_guard::input(&path)?;
+    stego_dot::max_payload_bytes(&p).map_err(String::from)
+}
+/// Decode a batch of images (directories expand to their images) with per-method timings.
+/// `methods` sets the order to try (default: dwt, dot, synth, then auto-detect external codecs).
+/// A prefilter model named by STEGSTR_PREFILTER_MODEL skips images it rules out.
+#[tauri::command]
+fn scan_images(paths: Vec<String>, methods: Option<Vec<String>>) -> Result<scan::ScanSummary, String> {
+    let paths = paths.iter().map(|p| path_guard::input(p).map(|p| p.to_string_lossy().into_owned())).collect::<Result<Vec<_>, _>>()?;
+    // Directory listings may hold links out of the allowed roots.
+    let files: Vec<_> = scan::expand_paths(&paths)?.into_iter().filter(|f| path_guard::input(&f.to_string_lossy()).is_ok()).collect();
+    let order: Vec<&str> = match &methods {
+        Some(m) if !m.is_empty() => m.iter().map(String::as_str).collect(),
+        _ => scan::default_order(),
+    };
+    let prefilter = prefilter::Prefilter::from_env(prefilter::DEFAULT_THRESHOLD).transpose()?;
+    Ok(scan::scan(&files, &order, prefilter.as_ref()))
 }
+
+/// Capacity of a cover for "dwt" (default) or "dot", optionally against a planned payload size.
+#[tauri::command]
+fn get_capacity(path: String, method: Option<String>, payload_bytes: Option<u64>) -> Result<units::CapacityInfo, String> {
+    let p = path_guard::input(&path)?;
+    targets::capacity(&p, method.as_deref().unwrap_or("dwt"), None, payload_bytes)
+}
+
 #[tauri::command]
 fn stegstr_log(
     level: String,
@@ -162,49 +1361,83 @@ fn stegstr_log(
     error: Option<String>,
     stack: Option<String>,
 ) -> Result<(), String> {
-    use std::io::Write;
-    let log_dir = dirs::data_local_dir()
-        .or_else(dirs::home_dir)
-        .ok_or("no log dir")?
-        .join("Stegstr");
-    let _ = std::fs::create_dir_all(&log_dir);
-    let log_path = log_dir.join("stegstr.log");
-    let mut file = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_path)
-        .map_err(|e| e.to_string())?;
-    let ts = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map(|d| d.as_secs_f64())
-        .unwrap_or(0.0);
-    let line = serde_json::json!({
-        "ts": ts,
-        "level": level,
-        "action": action,
-        "message": message,
-        "details": details,
-        "error": error,
-        "stack": stack,
-    });
-    writeln!(file, "{}", line).map_err(|e| e.to_string())?;
+    logs::append(&logs::LogEntry {
+        action: Some(action),
+        details,
+        error,
+        stack,
+        ..logs::LogEntry::new(logs::Level::parse(&level)?, message)
+    })
+}
+
+/// Newest log entries (oldest first) for a log viewer: at `level` or more severe, written at or
+/// after `since` (Unix seconds), at most `limit` (default logs::DEFAULT_LIMIT).
+#[tauri::command]
+fn get_logs(level: Option<String>, since: Option<f64>, limit: Option<usize>) -> Result<Vec<logs::LogEntry>, String> {
+    logs::query(level.as_deref().map(logs::Level::parse).transpose()?, since, limit)
+}
+
+#[tauri::command]
+fn clear_logs() -> Result<(), String> {
+    logs::clear()
+}
+
+/// Level below which entries are dropped, until the app exits (STEGSTR_LOG_LEVEL sets the start).
+#[tauri::command]
+fn set_log_level(level: String) -> Result<(), String> {
+    logs::set_level(logs::Level::parse(&level)?);
     Ok(())
 }
 
+/// Saved settings (config::Settings) shared with stegstr-cli.
+#[tauri::command]
+fn get_settings() -> Result<config::Settings, String> {
+    config::load()
+}
+
+/// Replace the saved settings; nothing is written if any setting is invalid.
+#[tauri::command]
+fn set_settings(settings: serde_json::Value) -> Result<config::Settings, String> {
+    config::save(&ipc_schema::parse_options(settings)?)?;
+    config::load()
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DataDirInfo {
+    pub path: String,
+    pub portable: bool,
+}
+
+#[tauri::command]
+fn get_data_dir() -> Result<DataDirInfo, String> {
+    let dir = app_paths::data_dir()?;
+    Ok(DataDirInfo {
+        path: dir.to_string_lossy().to_string(),
+        portable: app_paths::is_portable(),
+    })
+}
+
+/// Exchange image path; fails with a suggested action if the folder's manifest was written by an
+/// incompatible Stegstr build.
 #[tauri::command]
 fn get_exchange_path() -> Result<String, String> {
-    let dir = std::env::temp_dir().join("stegstr-test-exchange");
-    let _ = std::fs::create_dir_all(&dir);
+    let dir = exchange::exchange_dir();
+    exchange::negotiate(&dir)?;
     Ok(dir.join("exchange.png").to_string_lossy().to_string())
 }
 
 #[tauri::command]
 fn get_exchange_path_qim() -> Result<String, String> {
-    let dir = std::env::temp_dir().join("stegstr-test-exchange");
-    let _ = std::fs::create_dir_all(&dir);
+    let dir = exchange::exchange_dir();
+    exchange::negotiate(&dir)?;
     Ok(dir.join("exchange.jpg").to_string_lossy().to_string())
 }
 
+#[tauri::command]
+fn get_exchange_manifest() -> Result<Option<exchange::ExchangeManifest>, String> {
+    exchange::read_manifest(&exchange::exchange_dir())
+}
+
 #[tauri::command]
 fn get_test_profile() -> Option<String> {
     std::env::var("STEGSTR_TEST_PROFILE").ok().filter(|s| !s.is_empty())
@@ -217,136 +1450,69 @@ fn get_desktop_path() -> Result<String, String> {
         .ok_or_else(|| "Could not get Desktop path".to_string())
 }
 
-fn qim_cli_path() -> std::path::PathBuf {
-    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-        .parent()
-        .unwrap()
-        .join("channel_simulator")
-        .join("qim_cli.py")
-}
-
+/// QIM encode (JPEG DCT domain, survives platform recompression). Always writes a JPEG.
+/// `profile` tunes it for a platform that recompresses photos ("twitter" or "telegram").
+/// `overwrite` as for encode_stego_image.
 #[tauri::command]
-fn encode_stego_qim(cover_path: String, output_path: String, payload: String) -> Result<StegoEncodeResult, String> {
-    let cover = normalize_path(&cover_path);
-    let output = normalize_path(&output_path);
-    let qim_cli = qim_cli_path();
-    if !qim_cli.exists() {
-        return Ok(StegoEncodeResult {
-            ok: false,
-            path: None,
-            error: Some(format!(
-                "QIM script not found at {}. Install channel_simulator deps: pip install jpeglib reedsolo numpy",
-                qim_cli.display()
-            )),
-        });
+fn encode_stego_qim(
+    cover_path: String,
+    output_path: String,
+    payload: String,
+    profile: Option<String>,
+    overwrite: Option<bool>,
+) -> Result<StegoEncodeResult, String> {
+    let cover = &path_guard::input(&cover_path)?.to_string_lossy().into_owned();
+    let overwrite = overwrite.unwrap_or(false);
+    if let Some(name) = profile {
+        let profile = profiles::find(&name)?;
+        if profile.method() != "qim" {
+            return Err(format!("The {} profile does not write with qim", profile.name));
+        }
+        let payload_bytes = embed_payload_bytes(payload)?;
+        return encode_profile_to_file(cover, &output_path, &payload_bytes, profile, None, None, true, None, false, overwrite);
     }
-    let payload_b64 = if payload.starts_with("base64:") {
-        payload.trim_start_matches("base64:").to_string()
-    } else {
-        base64::engine::general_purpose::STANDARD.encode(payload.as_bytes())
+    let output = &path_guard::output(&output_path)?.to_string_lossy().into_owned();
+    atomic_write::check_destination(std::path::Path::new(output), overwrite)?;
+    let payload_bytes = embed_payload_bytes(payload)?;
+    let jpeg = match stego_qim::encode(std::path::Path::new(cover), &payload_bytes) {
+        Ok(j) => j,
+        Err(e) => return Ok(StegoEncodeResult::failure(format!("QIM encode failed: {}", e))),
     };
-    let output_buf = std::process::Command::new("python3")
-        .arg(&qim_cli)
-        .arg("encode")
-        .arg(cover)
-        .arg(output)
-        .arg(&payload_b64)
-        .output()
-        .map_err(|e| format!("QIM encode failed: {}", e))?;
-    if !output_buf.status.success() {
-        let err = String::from_utf8_lossy(&output_buf.stderr);
-        return Ok(StegoEncodeResult {
-            ok: false,
-            path: None,
-            error: Some(format!("QIM encode failed: {}", err.trim())),
-        });
-    }
+    atomic_write::write(std::path::Path::new(output), &jpeg, overwrite)?;
+    exchange::stamp_if_exchange(std::path::Path::new(output))?;
     Ok(StegoEncodeResult {
-        ok: true,
-        path: Some(output.to_string()),
-        error: None,
+        method: Some("qim".to_string()),
+        ..StegoEncodeResult::success(output.to_string())
     })
 }
 
 #[tauri::command]
 fn decode_stego_qim(path: String) -> Result<StegoDecodeResult, String> {
-    let p = normalize_path(&path);
-    let qim_cli = qim_cli_path();
-    if !qim_cli.exists() {
-        return Ok(StegoDecodeResult {
-            ok: false,
-            payload: None,
-            error: Some(format!(
-                "QIM script not found at {}. Install channel_simulator deps: pip install jpeglib reedsolo numpy",
-                qim_cli.display()
-            )),
-        });
-    }
-    let (tx, rx) = mpsc::channel();
-    let qim_cli = qim_cli.clone();
-    let p_owned = p.to_string();
-    thread::spawn(move || {
-        let out = std::process::Command::new("python3")
-            .arg(&qim_cli)
-            .arg("decode")
-            .arg(&p_owned)
-            .output();
-        let _ = tx.send(out);
-    });
-    let output_buf = match rx.recv_timeout(Duration::from_secs(30)) {
-        Ok(Ok(buf)) => buf,
-        Ok(Err(e)) => {
-            return Ok(StegoDecodeResult {
-                ok: false,
-                payload: None,
-                error: Some(format!("QIM decode failed: {}", e)),
-            });
-        }
-        Err(mpsc::RecvTimeoutError::Timeout) => {
-            return Ok(StegoDecodeResult {
-                ok: false,
-                payload: None,
-                error: Some("QIM decode timed out after 30 seconds".to_string()),
-            });
-        }
-        Err(mpsc::RecvTimeoutError::Disconnected) => {
-            return Ok(StegoDecodeResult {
-                ok: false,
-                payload: None,
-                error: Some("QIM decode thread disconnected".to_string()),
-            });
-        }
-    };
-    let stderr_str = String::from_utf8_lossy(&output_buf.stderr);
-    if !output_buf.status.success() {
-        return Ok(StegoDecodeResult {
-            ok: false,
-            payload: None,
-            error: Some(format!("QIM decode failed: {}", stderr_str.trim())),
-        });
+    let p = path_guard::input(&path)?;
+    match stego_qim::decode(&p) {
+        Ok(payload) => Ok(StegoDecodeResult::decoded_and_stored(&payload, &path)),
+        Err(e) => Ok(StegoDecodeResult::failure(e)),
     }
-    let payload_b64 = String::from_utf8_lossy(&output_buf.stdout).trim().to_string();
-    let payload_bytes = base64::engine::general_purpose::STANDARD
-        .decode(&payload_b64)
-        .map_err(|e| format!("QIM payload decode error: {} (stderr: {})", e, stderr_str.trim()))?;
-    let payload_str = format!("base64:{}", base64::engine::general_purpose::STANDARD.encode(&payload_bytes));
-    Ok(StegoDecodeResult {
-        ok: true,
-        payload: Some(payload_str),
-        error: None,
-    })
+}
+
+/// Put the image at `path` on the system clipboard as image data (PNG), ready to paste into a chat.
+#[tauri::command(async)]
+fn copy_image_to_clipboard(path: String) -> Result<(), String> {
+    clipboard::copy_image(&path_guard::input(&path)?)
 }
 
 #[tauri::command]
 fn reveal_in_finder(path: String) -> Result<(), String> {
+    let path = path_guard::input(&path)?;
     #[cfg(target_os = "macos")]
     {
-        std::process::Command::new("open").args(["-R", &path]).spawn().map_err(|e| e.to_string())?;
+        std::process::Command::new("open").arg("-R").arg(&path).spawn().map_err(|e| e.to_string())?;
     }
     #[cfg(target_os = "windows")]
     {
         std::process::Command::new("explorer")
-            .args(["/select,", &path])
+            .arg("/select,")
+            .arg(&path)
             .spawn()
             .map_err(|e| e.to_string())?;
     }
@@ -359,24 +1525,93 @@ fn reveal_in_finder(path: String) -> Result<(), String> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    heif::register();
+    path_guard::init_from_env();
+    logs::init();
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "Stegstr started");
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .manage(WatchState::default())
+        .manage(StegoTasks::default())
+        .manage(RelayState::default())
         .invoke_handler(tauri::generate_handler![
             decode_stego_image,
+            decode_stego_image_bytes,
+            decode_stego_auto,
+            join_stego_images,
+            start_watch,
+            stop_watch,
+            cancel_stego_task,
+            decode_region,
+            detect_stego_image,
+            decode_url,
             encode_stego_image,
+            encode_stego_image_bytes,
             decode_stego_dot,
             encode_stego_dot,
+            send_to_contact,
+            send_dm,
+            sign_nostr_event,
+            store_key,
+            list_keys,
+            sign_with_key,
+            remove_key,
+            open_dm,
+            get_schemas,
+            list_receipts,
+            list_targets,
+            list_profiles,
+            analyze_stego_image,
+            list_external_codecs,
+            key_fingerprint,
+            publish_event,
+            fetch_events,
+            relay_connect,
+            relay_disconnect,
+            relay_status,
+            relay_subscribe,
+            relay_unsubscribe,
+            relay_publish,
+            store_decoded_events,
+            query_stored_events,
+            queue_outbox_events,
+            list_outbox,
+            flush_outbox,
+            remove_outbox_events,
+            notify,
+            salvage_bundle_events,
+            sanitize_bundle,
+            pack_bundle,
+            unpack_bundle,
+            merge_bundles,
+            verify_bundle,
+            encrypt_for_pubkey,
+            decrypt_with_privkey,
+            get_dwt_capacity,
+            generate_thumbnail,
+            stego_heatmap,
             get_dot_capacity,
+            get_capacity,
+            scan_images,
             check_png_signature,
+            supported_cover_extensions,
             decode_stego_qim,
             encode_stego_qim,
             get_desktop_path,
             get_test_profile,
             get_exchange_path,
             get_exchange_path_qim,
+            get_exchange_manifest,
             reveal_in_finder,
-            stegstr_log
+            copy_image_to_clipboard,
+            stegstr_log,
+            get_logs,
+            clear_logs,
+            set_log_level,
+            get_settings,
+            set_settings,
+            get_data_dir
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,294 @@
+// Application log: JSON lines in stegstr.log in the data directory, written by the frontend
+// (stegstr_log) and by `tracing` events from the Rust side (a subscriber installed by `init`).
+// Entries below the current level are dropped; the level starts at STEGSTR_LOG_LEVEL (default
+// info) and can be changed at runtime. When the file passes MAX_FILE_BYTES it is rotated to
+// stegstr.log.1 (older files shift up, at most MAX_ROTATED kept) and rotated files older than
+// MAX_AGE are deleted, so the log no longer grows without bound.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Initial level (error, warn, info, debug or trace).
+pub const LEVEL_ENV: &str = "STEGSTR_LOG_LEVEL";
+pub const MAX_FILE_BYTES: u64 = 1024 * 1024;
+pub const MAX_ROTATED: usize = 4;
+pub const MAX_AGE: Duration = Duration::from_secs(14 * 24 * 3600);
+/// Entries `query` returns when no limit is given.
+pub const DEFAULT_LIMIT: usize = 500;
+const FILE_NAME: &str = "stegstr.log";
+
+/// Severity, most severe first: an entry is kept when its level is at most the current one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /// Also accepts the frontend's "action" (logged at info).
+    pub fn parse(s: &str) -> Result<Level, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" | "warning" => Ok(Level::Warn),
+            "info" | "action" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            other => Err(format!("Unknown log level {} (error, warn, info, debug or trace)", other)),
+        }
+    }
+}
+
+impl From<tracing::Level> for Level {
+    fn from(level: tracing::Level) -> Self {
+        match level {
+            tracing::Level::ERROR => Level::Error,
+            tracing::Level::WARN => Level::Warn,
+            tracing::Level::INFO => Level::Info,
+            tracing::Level::DEBUG => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LogEntry {
+    /// Unix time in seconds.
+    pub ts: f64,
+    pub level: Level,
+    /// Frontend action name, or the Rust module for tracing events.
+    pub action: Option<String>,
+    pub message: String,
+    pub details: Option<String>,
+    pub error: Option<String>,
+    pub stack: Option<String>,
+}
+
+impl LogEntry {
+    pub fn new(level: Level, message: impl Into<String>) -> Self {
+        let ts = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or(0.0);
+        LogEntry { ts, level, action: None, message: message.into(), details: None, error: None, stack: None }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+/// Serializes appends and rotation within the process.
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => Level::Error,
+        1 => Level::Warn,
+        2 => Level::Info,
+        3 => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+fn log_path() -> Result<PathBuf, String> {
+    crate::app_paths::data_path(FILE_NAME)
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Take the level from LEVEL_ENV, drop expired rotated files and route `tracing` events here.
+pub fn init() {
+    if let Some(level) = std::env::var(LEVEL_ENV).ok().and_then(|l| Level::parse(&l).ok()) {
+        set_level(level);
+    }
+    if let Ok(path) = log_path() {
+        prune(&path, SystemTime::now());
+    }
+    let _ = tracing::subscriber::set_global_default(FileSubscriber);
+}
+
+/// Append `entry` unless it is below the current level.
+pub fn append(entry: &LogEntry) -> Result<(), String> {
+    match entry.level <= level() {
+        true => append_to(&log_path()?, entry, MAX_FILE_BYTES),
+        false => Ok(()),
+    }
+}
+
+fn append_to(path: &Path, entry: &LogEntry, max_bytes: u64) -> Result<(), String> {
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if std::fs::metadata(path).is_ok_and(|m| m.len() >= max_bytes) {
+        rotate(path)?;
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path).map_err(|e| e.to_string())?;
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+fn rotate(path: &Path) -> Result<(), String> {
+    let _ = std::fs::remove_file(rotated(path, MAX_ROTATED));
+    for n in (1..MAX_ROTATED).rev() {
+        let _ = std::fs::rename(rotated(path, n), rotated(path, n + 1));
+    }
+    std::fs::rename(path, rotated(path, 1)).map_err(|e| e.to_string())?;
+    prune(path, SystemTime::now());
+    Ok(())
+}
+
+/// Delete rotated files last written more than MAX_AGE before `now`.
+fn prune(path: &Path, now: SystemTime) {
+    for n in 1..=MAX_ROTATED {
+        let file = rotated(path, n);
+        let expired = std::fs::metadata(&file)
+            .and_then(|m| m.modified())
+            .is_ok_and(|t| now.duration_since(t).is_ok_and(|age| age > MAX_AGE));
+        if expired {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+}
+
+/// The newest `limit` entries (oldest first) at `min_level` or more severe, written at or after
+/// `since` (Unix seconds), across the current and rotated files.
+pub fn query(min_level: Option<Level>, since: Option<f64>, limit: Option<usize>) -> Result<Vec<LogEntry>, String> {
+    query_in(&log_path()?, min_level, since, limit.unwrap_or(DEFAULT_LIMIT))
+}
+
+fn query_in(path: &Path, min_level: Option<Level>, since: Option<f64>, limit: usize) -> Result<Vec<LogEntry>, String> {
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let files = (1..=MAX_ROTATED).rev().map(|n| rotated(path, n)).chain(std::iter::once(path.to_path_buf()));
+    let mut out = std::collections::VecDeque::new();
+    for file in files {
+        let Ok(text) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        // Lines the old logger wrote, or a torn last line, are skipped.
+        for entry in text.lines().filter_map(|l| serde_json::from_str::<LogEntry>(l).ok()) {
+            if min_level.is_some_and(|m| entry.level > m) || since.is_some_and(|s| entry.ts < s) {
+                continue;
+            }
+            out.push_back(entry);
+            if out.len() > limit {
+                out.pop_front();
+            }
+        }
+    }
+    Ok(out.into())
+}
+
+/// Delete the log and its rotated files.
+pub fn clear() -> Result<(), String> {
+    clear_in(&log_path()?)
+}
+
+fn clear_in(path: &Path) -> Result<(), String> {
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    for file in std::iter::once(path.to_path_buf()).chain((1..=MAX_ROTATED).map(|n| rotated(path, n))) {
+        match std::fs::remove_file(&file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(format!("{}: {}", file.display(), e)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Writes `tracing` events as log entries; spans are not tracked.
+struct FileSubscriber;
+
+#[derive(Default)]
+struct EventFields {
+    message: String,
+    details: serde_json::Map<String, serde_json::Value>,
+}
+
+impl tracing::field::Visit for EventFields {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            name => {
+                self.details.insert(name.to_string(), value.into());
+            }
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+impl tracing::Subscriber for FileSubscriber {
+    // Never cached, so `set_level` applies to callsites already seen.
+    fn register_callsite(&self, _: &'static tracing::Metadata<'static>) -> tracing::subscriber::Interest {
+        tracing::subscriber::Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        Level::from(*metadata.level()) <= level()
+    }
+
+    fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+        let mut entry = LogEntry::new(Level::from(*event.metadata().level()), fields.message);
+        entry.action = Some(event.metadata().target().to_string());
+        entry.details = (!fields.details.is_empty()).then(|| serde_json::Value::Object(fields.details).to_string());
+        let _ = append(&entry);
+    }
+
+    fn enter(&self, _: &tracing::span::Id) {}
+
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_rotates_filters_and_clears() {
+        let dir = std::env::temp_dir().join(format!("stegstr_logs_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(FILE_NAME);
+
+        for i in 0..40 {
+            let mut entry = LogEntry::new(if i % 4 == 0 { Level::Error } else { Level::Info }, format!("entry {}", i));
+            entry.ts = i as f64;
+            append_to(&path, &entry, 512).unwrap();
+        }
+        assert!(rotated(&path, 1).exists());
+        assert!(!rotated(&path, MAX_ROTATED + 1).exists());
+        assert!(std::fs::metadata(&path).unwrap().len() < 1024);
+
+        let newest = query_in(&path, None, None, 3).unwrap();
+        assert_eq!(newest.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(), ["entry 37", "entry 38", "entry 39"]);
+        let errors = query_in(&path, Some(Level::Error), Some(30.0), 100).unwrap();
+        assert_eq!(errors.iter().map(|e| e.ts).collect::<Vec<_>>(), [32.0, 36.0]);
+
+        prune(&path, SystemTime::now() + MAX_AGE * 2);
+        assert!(!rotated(&path, 1).exists() && path.exists());
+        clear_in(&path).unwrap();
+        assert!(query_in(&path, None, None, 10).unwrap().is_empty());
+        assert_eq!(Level::parse("action").unwrap(), Level::Info);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,102 @@
+// Signature verification for decoded bundles and fetched events: recompute each NIP-01 event id
+// and check its BIP-340 Schnorr signature against the event's pubkey. Without this an image can
+// carry notes that claim any author.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::nostr_event::NostrEvent;
+pub use crate::nostr_event::verify_event;
+
+/// Verification result for one event.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EventVerification {
+    /// Position in the bundle's events array.
+    pub index: usize,
+    pub id: Option<String>,
+    pub pubkey: Option<String>,
+    pub valid: bool,
+    /// Why the event failed: malformed, id mismatch or bad signature.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BundleVerification {
+    /// True when the bundle has events and every one verified.
+    pub valid: bool,
+    pub total: usize,
+    pub invalid: usize,
+    pub events: Vec<EventVerification>,
+}
+
+/// Verify every event of a `{ version, events }` bundle. Works on raw JSON so one malformed event
+/// is reported instead of failing the whole bundle.
+pub fn verify_bundle(bundle: &Value) -> Result<BundleVerification, String> {
+    let events = bundle
+        .get("events")
+        .and_then(Value::as_array)
+        .ok_or("Bundle has no events array")?;
+    let results: Vec<EventVerification> = events
+        .iter()
+        .enumerate()
+        .map(|(index, raw)| {
+            let field = |k: &str| raw.get(k).and_then(Value::as_str).map(str::to_string);
+            let error = match serde_json::from_value::<NostrEvent>(raw.clone()) {
+                Ok(event) => verify_event(&event).err(),
+                Err(e) => Some(format!("malformed event: {}", e)),
+            };
+            EventVerification {
+                index,
+                id: field("id"),
+                pubkey: field("pubkey"),
+                valid: error.is_none(),
+                error,
+            }
+        })
+        .collect();
+    let invalid = results.iter().filter(|r| !r.valid).count();
+    Ok(BundleVerification {
+        valid: !results.is_empty() && invalid == 0,
+        total: results.len(),
+        invalid,
+        events: results,
+    })
+}
+
+/// Parse bundle JSON text and verify it.
+pub fn verify_bundle_json(json: &str) -> Result<BundleVerification, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("Invalid bundle JSON: {}", e))?;
+    verify_bundle(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr_event;
+
+    #[test]
+    fn test_verify_bundle_flags_tampered_events() {
+        let sk = secp256k1::SecretKey::from_slice(&[5u8; 32]).unwrap();
+        let good = nostr_event::create_kind1_event("signed", &sk).unwrap();
+        assert!(verify_event(&good).is_ok());
+
+        let mut edited = good.clone();
+        edited.content = "edited".to_string();
+        let mut resigned_id = edited.clone();
+        resigned_id.id = nostr_event::event_id(&edited.pubkey, edited.created_at, 1, &edited.tags, &edited.content).unwrap();
+
+        let bundle = serde_json::json!({
+            "version": 1,
+            "events": [good, edited, resigned_id, { "id": "x" }]
+        });
+        let report = verify_bundle(&bundle).unwrap();
+        assert!(!report.valid);
+        assert_eq!((report.total, report.invalid), (4, 3));
+        let errors: Vec<String> = report.events.iter().map(|e| e.error.clone().unwrap_or_default()).collect();
+        assert_eq!(errors[0], "");
+        assert!(errors[1].contains("id mismatch"), "{}", errors[1]);
+        assert_eq!(errors[2], "invalid signature");
+        assert!(errors[3].starts_with("malformed"), "{}", errors[3]);
+    }
+}
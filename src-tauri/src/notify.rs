@@ -0,0 +1,55 @@
+// Native OS notifications for background work (watch folders, batch jobs, outbox flushes) so
+// headless runs can alert the user without the UI polling. Job notifications follow the `notify`
+// setting (config.toml); STEGSTR_NO_NOTIFY=1 disables every notification (CI, servers).
+
+pub const NO_NOTIFY_ENV: &str = "STEGSTR_NO_NOTIFY";
+const APP_NAME: &str = "Stegstr";
+
+pub fn notifications_disabled() -> bool {
+    std::env::var(NO_NOTIFY_ENV)
+        .map(|v| !v.is_empty() && v != "0")
+        .unwrap_or(false)
+}
+
+/// Show a native notification. Returns Ok(false) when disabled via env.
+pub fn notify(title: &str, body: &str) -> Result<bool, String> {
+    if notifications_disabled() {
+        return Ok(false);
+    }
+    notify_rust::Notification::new()
+        .appname(APP_NAME)
+        .summary(title)
+        .body(body)
+        .show()
+        .map_err(|e| format!("Notification failed: {}", e))?;
+    Ok(true)
+}
+
+/// Whether a finished job raises a notification: the `notify` setting (unset means on), unless
+/// notifications are disabled via env.
+pub fn should_notify_job(setting: Option<bool>, env_disabled: bool) -> bool {
+    !env_disabled && setting.unwrap_or(true)
+}
+
+/// Best-effort notification for job completion, if the settings allow it; failures (no
+/// notification daemon) are ignored. An unreadable config file leaves the default (on).
+pub fn notify_job_done(job: &str, summary: &str) {
+    let setting = crate::config::load().ok().and_then(|s| s.notify);
+    if should_notify_job(setting, notifications_disabled()) {
+        let _ = notify(&format!("{} finished", job), summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_notify_job() {
+        assert!(should_notify_job(None, false));
+        assert!(should_notify_job(Some(true), false));
+        assert!(!should_notify_job(Some(false), false));
+        assert!(!should_notify_job(None, true));
+        assert!(!should_notify_job(Some(true), true));
+    }
+}
@@ -0,0 +1,291 @@
+// Outbox of signed events waiting for relays: notes composed offline (and embedded into images)
+// are queued here, and `flush` publishes them once relays are reachable. Each entry keeps every
+// relay's outcome from its latest attempt. An event is sent once any relay accepted it, failed
+// once every relay rejected it (retrying would not help), and stays pending through timeouts and
+// connection errors; an event whose publish errored keeps the error and stays pending. `flush`
+// sends the whole batch through a RelayPool, one connection per relay. The queue is
+// <data dir>/outbox.json, rewritten atomically on every change; publishing runs outside the file
+// lock, so a slow relay does not block queueing.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::nostr_event::NostrEvent;
+use crate::relay::RelayOutcome;
+use crate::relay_pool::RelayPool;
+
+const OUTBOX_FILE: &str = "outbox.json";
+
+/// Serializes read-modify-write cycles within the process.
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxStatus {
+    /// Not published yet, or no relay answered; `flush` retries it.
+    Pending,
+    /// At least one relay accepted it.
+    Sent,
+    /// Every relay that answered rejected it.
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct OutboxEntry {
+    pub event: NostrEvent,
+    pub status: OutboxStatus,
+    /// Unix time the event was queued.
+    pub queued_at: u64,
+    pub attempts: u32,
+    /// Unix time of the latest publish attempt.
+    pub last_attempt: Option<u64>,
+    /// Each relay's outcome from the latest attempt.
+    pub results: Vec<RelayOutcome>,
+    /// Why the latest attempt could not publish at all (the event stays pending).
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FlushReport {
+    /// Pending events published in this flush.
+    pub attempted: usize,
+    pub sent: usize,
+    pub failed: usize,
+    /// Still pending afterwards (no relay answered).
+    pub pending: usize,
+}
+
+impl FlushReport {
+    /// Job notification for a flush that tried to publish anything (notify::notify_job_done).
+    pub fn notify_done(&self) {
+        if self.attempted > 0 {
+            let summary = format!("{} of {} sent, {} failed, {} still pending", self.sent, self.attempted, self.failed, self.pending);
+            crate::notify::notify_job_done("Outbox", &summary);
+        }
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn outbox_path() -> Result<std::path::PathBuf, String> {
+    crate::app_paths::data_path(OUTBOX_FILE)
+}
+
+fn read_all(path: &Path) -> Result<Vec<OutboxEntry>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+fn write_all(path: &Path, entries: &[OutboxEntry]) -> Result<(), String> {
+    let json = serde_json::to_vec_pretty(entries).map_err(|e| e.to_string())?;
+    crate::atomic_write::write(path, &json, true)
+}
+
+/// Status of an attempt from its relay outcomes.
+fn status_of(results: &[RelayOutcome]) -> OutboxStatus {
+    if results.iter().any(|r| r.status == "ok") {
+        OutboxStatus::Sent
+    } else if !results.is_empty() && results.iter().all(|r| r.status == "rejected") {
+        OutboxStatus::Failed
+    } else {
+        OutboxStatus::Pending
+    }
+}
+
+/// Queue signed events for publishing; returns how many were new. Every event must verify (else
+/// nothing is queued); events already queued are skipped.
+pub fn add(events: &[NostrEvent]) -> Result<usize, String> {
+    add_to(&outbox_path()?, events)
+}
+
+fn add_to(path: &Path, events: &[NostrEvent]) -> Result<usize, String> {
+    for event in events {
+        crate::nostr_verify::verify_event(event).map_err(|e| format!("Event {}: {}", event.id, e))?;
+    }
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = read_all(path)?;
+    let mut ids: HashSet<String> = entries.iter().map(|e| e.event.id.clone()).collect();
+    let queued_at = now();
+    let before = entries.len();
+    for event in events.iter().filter(|e| ids.insert(e.id.clone())) {
+        entries.push(OutboxEntry {
+            event: event.clone(),
+            status: OutboxStatus::Pending,
+            queued_at,
+            attempts: 0,
+            last_attempt: None,
+            results: vec![],
+            last_error: None,
+        });
+    }
+    let added = entries.len() - before;
+    if added > 0 {
+        write_all(path, &entries)?;
+    }
+    Ok(added)
+}
+
+/// Every queued event, oldest first.
+pub fn list() -> Result<Vec<OutboxEntry>, String> {
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    read_all(&outbox_path()?)
+}
+
+/// Publish every pending event to `relays` through `pool` (connecting as needed) and record the
+/// outcomes.
+pub fn flush(pool: &RelayPool, relays: &[String], timeout: Duration) -> Result<FlushReport, String> {
+    flush_in(&outbox_path()?, |events| match pool.publish_many(events, Some(relays), timeout) {
+        Ok(outcomes) => outcomes.into_iter().map(Ok).collect(),
+        Err(e) => vec![Err(e); events.len()],
+    })
+}
+
+/// `publish` gets every pending event at once and returns one result per event, in order.
+fn flush_in(path: &Path, publish: impl FnOnce(&[NostrEvent]) -> Vec<Result<Vec<RelayOutcome>, String>>) -> Result<FlushReport, String> {
+    let pending: Vec<NostrEvent> = {
+        let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        read_all(path)?.into_iter().filter(|e| e.status == OutboxStatus::Pending).map(|e| e.event).collect()
+    };
+    if pending.is_empty() {
+        return Ok(FlushReport::default());
+    }
+    let attempted_at = now();
+    let outcomes: Vec<(String, Result<Vec<RelayOutcome>, String>)> = pending.iter().map(|e| e.id.clone()).zip(publish(&pending)).collect();
+
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    // Re-read: entries may have been queued or removed while publishing.
+    let mut entries = read_all(path)?;
+    let mut report = FlushReport { attempted: outcomes.len(), ..Default::default() };
+    for (id, results) in outcomes {
+        let Some(entry) = entries.iter_mut().find(|e| e.event.id == id) else {
+            continue;
+        };
+        entry.attempts += 1;
+        entry.last_attempt = Some(attempted_at);
+        (entry.results, entry.last_error) = match results {
+            Ok(results) => (results, None),
+            Err(e) => (vec![], Some(e)),
+        };
+        entry.status = status_of(&entry.results);
+        match entry.status {
+            OutboxStatus::Sent => report.sent += 1,
+            OutboxStatus::Failed => report.failed += 1,
+            OutboxStatus::Pending => report.pending += 1,
+        }
+    }
+    write_all(path, &entries)?;
+    Ok(report)
+}
+
+/// Drop the events with these ids, or every sent event when `ids` is None; returns how many.
+pub fn remove(ids: Option<&[String]>) -> Result<usize, String> {
+    remove_in(&outbox_path()?, ids)
+}
+
+fn remove_in(path: &Path, ids: Option<&[String]>) -> Result<usize, String> {
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = read_all(path)?;
+    let before = entries.len();
+    entries.retain(|e| match ids {
+        Some(ids) => !ids.contains(&e.event.id),
+        None => e.status != OutboxStatus::Sent,
+    });
+    let removed = before - entries.len();
+    if removed > 0 {
+        write_all(path, &entries)?;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr_event::sign_event_at;
+
+    #[test]
+    fn test_outbox_flush_tracks_status_per_event() {
+        let dir = std::env::temp_dir().join(format!("stegstr_outbox_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(OUTBOX_FILE);
+        let sk = secp256k1::SecretKey::from_slice(&[5u8; 32]).unwrap();
+        let [accepted, rejected, offline] = ["accepted", "rejected", "offline"].map(|c| sign_event_at(1, vec![], c, 100, &sk).unwrap());
+        let mut forged = accepted.clone();
+        forged.content = "forged".to_string();
+
+        assert!(add_to(&path, &[offline.clone(), forged]).is_err());
+        assert_eq!(add_to(&path, &[accepted.clone(), rejected.clone(), offline.clone()]).unwrap(), 3);
+        assert_eq!(add_to(&path, std::slice::from_ref(&accepted)).unwrap(), 0);
+
+        let outcome = |relay: &str, status: &str| RelayOutcome { relay: relay.to_string(), status: status.to_string(), message: None, elapsed_ms: 1 };
+        let online = std::cell::Cell::new(false);
+        let publish = |events: &[NostrEvent]| {
+            let per_event = |event: &NostrEvent| match (event.content.as_str(), online.get()) {
+                ("accepted", _) => vec![outcome("wss://a", "rejected"), outcome("wss://b", "ok")],
+                ("rejected", _) => vec![outcome("wss://a", "rejected"), outcome("wss://b", "rejected")],
+                (_, false) => vec![outcome("wss://a", "error"), outcome("wss://b", "timeout")],
+                (_, true) => vec![outcome("wss://a", "ok")],
+            };
+            events.iter().map(|e| Ok(per_event(e))).collect()
+        };
+        let report = flush_in(&path, publish).unwrap();
+        assert_eq!(report, FlushReport { attempted: 3, sent: 1, failed: 1, pending: 1 });
+
+        online.set(true);
+        assert_eq!(flush_in(&path, publish).unwrap(), FlushReport { attempted: 1, sent: 1, failed: 0, pending: 0 });
+        let entries = read_all(&path).unwrap();
+        let statuses: Vec<(OutboxStatus, u32)> = entries.iter().map(|e| (e.status, e.attempts)).collect();
+        assert_eq!(statuses, [(OutboxStatus::Sent, 1), (OutboxStatus::Failed, 1), (OutboxStatus::Sent, 2)]);
+        assert_eq!(entries[0].results[1].relay, "wss://b");
+
+        assert_eq!(remove_in(&path, None).unwrap(), 2);
+        assert_eq!(remove_in(&path, Some(std::slice::from_ref(&rejected.id))).unwrap(), 1);
+        assert!(read_all(&path).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_outbox_flush_records_publish_errors_per_event() {
+        let dir = std::env::temp_dir().join(format!("stegstr_outbox_errors_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(OUTBOX_FILE);
+        let sk = secp256k1::SecretKey::from_slice(&[6u8; 32]).unwrap();
+        let [fine, broken] = ["fine", "broken"].map(|c| sign_event_at(1, vec![], c, 100, &sk).unwrap());
+        add_to(&path, &[broken.clone(), fine.clone()]).unwrap();
+
+        let ok = RelayOutcome { relay: "wss://a".to_string(), status: "ok".to_string(), message: None, elapsed_ms: 1 };
+        // One event errors; the other's outcome is still recorded.
+        let report = flush_in(&path, |events| {
+            events.iter().map(|e| if e.content == "broken" { Err("relay refused the frame".to_string()) } else { Ok(vec![ok.clone()]) }).collect()
+        })
+        .unwrap();
+        assert_eq!(report, FlushReport { attempted: 2, sent: 1, failed: 0, pending: 1 });
+        let entries = read_all(&path).unwrap();
+        assert_eq!((entries[0].status, entries[0].attempts), (OutboxStatus::Pending, 1));
+        assert_eq!(entries[0].last_error.as_deref(), Some("relay refused the frame"));
+        assert_eq!((entries[1].status, entries[1].last_error.as_deref()), (OutboxStatus::Sent, None));
+
+        // The retry publishes only the pending event and clears its error.
+        let report = flush_in(&path, |events| {
+            assert_eq!(events, std::slice::from_ref(&broken));
+            vec![Ok(vec![ok.clone()])]
+        })
+        .unwrap();
+        assert_eq!(report.sent, 1);
+        let entries = read_all(&path).unwrap();
+        assert_eq!((entries[0].status, entries[0].attempts, entries[0].last_error.as_deref()), (OutboxStatus::Sent, 2, None));
+        assert_eq!(flush_in(&path, |_| unreachable!()).unwrap(), FlushReport::default());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
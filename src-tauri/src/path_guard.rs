@@ -0,0 +1,185 @@
+// Validation of the paths commands receive from the webview. A file: URL (as some dialogs return)
+// is turned into a path first: percent-decoded, with a Windows drive (file:///C:/...) or a host
+// (file://server/share, a UNC path) kept. The path is then resolved to its canonical form (symlinks followed; for an output that does not exist
+// yet, its parent directory is resolved), then refused if it is a network or device path (UNC
+// shares, \\.\ and \\?\UNC namespaces, Windows device names, /dev, /proc and /sys), a device file,
+// FIFO or socket, or, when an allow-list is configured, outside every allowed root. The allow-list
+// is read once at startup from STEGSTR_ALLOWED_ROOTS (separated like PATH); the data directory is
+// always allowed alongside it. Without it any local file or directory passes.
+
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::sync::OnceLock;
+
+/// Roots commands may read and write under, separated like PATH (unset: no restriction).
+pub const ALLOWED_ROOTS_ENV: &str = "STEGSTR_ALLOWED_ROOTS";
+
+static ROOTS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+/// Read the allow-list from the environment; call once at startup. Roots that do not exist are
+/// dropped (a root must be canonical to compare against).
+pub fn init_from_env() {
+    let Some(value) = std::env::var_os(ALLOWED_ROOTS_ENV).filter(|v| !v.is_empty()) else {
+        return;
+    };
+    let mut roots: Vec<PathBuf> = std::env::split_paths(&value).collect();
+    roots.extend(crate::app_paths::data_dir().ok());
+    let _ = ROOTS.set(roots.iter().filter_map(|r| r.canonicalize().ok()).collect());
+}
+
+/// Configured roots; empty when unrestricted.
+pub fn allowed_roots() -> &'static [PathBuf] {
+    ROOTS.get().map(Vec::as_slice).unwrap_or(&[])
+}
+
+/// An existing file or directory to read.
+pub fn input(path: &str) -> Result<PathBuf, String> {
+    validate(path, false, allowed_roots())
+}
+
+/// A file to write: it may not exist yet, but its directory must.
+pub fn output(path: &str) -> Result<PathBuf, String> {
+    validate(path, true, allowed_roots())
+}
+
+fn validate(raw: &str, output: bool, roots: &[PathBuf]) -> Result<PathBuf, String> {
+    let local = from_file_url(raw)?;
+    if local.is_empty() {
+        return Err("Empty path".to_string());
+    }
+    if local.starts_with("\\\\") || local.starts_with("//") {
+        return Err(format!("{}: network and device paths are not allowed", raw));
+    }
+    let path = Path::new(&local);
+    let canonical = match path.canonicalize() {
+        Ok(p) => p,
+        Err(e) if output && e.kind() == std::io::ErrorKind::NotFound => {
+            let name = path.file_name().ok_or_else(|| format!("{}: not a file path", raw))?;
+            if cfg!(windows) && is_windows_device_name(&name.to_string_lossy()) {
+                return Err(format!("{}: device names are not allowed", raw));
+            }
+            let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            parent.canonicalize().map_err(|e| format!("{}: {}", parent.display(), e))?.join(name)
+        }
+        Err(e) => return Err(format!("{}: {}", raw, e)),
+    };
+    if is_device_path(&canonical) {
+        return Err(format!("{}: network and device paths are not allowed", raw));
+    }
+    if let Ok(meta) = std::fs::metadata(&canonical) {
+        let kind_ok = if output { meta.is_file() } else { meta.is_file() || meta.is_dir() };
+        if !kind_ok {
+            return Err(format!("{}: not a regular file", raw));
+        }
+    }
+    if !roots.is_empty() && !roots.iter().any(|r| canonical.starts_with(r)) {
+        return Err(format!("{}: outside the allowed directories", raw));
+    }
+    Ok(canonical)
+}
+
+/// The local path a file: URL names; anything else is taken as a path already. The query and
+/// fragment are dropped, a host other than localhost becomes a UNC path.
+fn from_file_url(raw: &str) -> Result<String, String> {
+    let rest = match raw.get(..5) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("file:") => &raw[5..],
+        _ => return Ok(raw.to_string()),
+    };
+    let rest = rest.split(['?', '#']).next().unwrap_or("");
+    let (host, path) = match rest.strip_prefix("//") {
+        Some(authority) => authority.split_at(authority.find('/').unwrap_or(authority.len())),
+        None => ("", rest),
+    };
+    let mut path = percent_decode(path).ok_or_else(|| format!("{}: not a valid file URL", raw))?;
+    if cfg!(windows) {
+        // "/C:/x" (or the older "/C|/x") is the drive path C:\x.
+        let bytes = path.as_bytes();
+        if bytes.len() >= 3 && bytes[0] == b'/' && bytes[1].is_ascii_alphabetic() && matches!(bytes[2], b':' | b'|') {
+            path = format!("{}:{}", &path[1..2], &path[3..]);
+        }
+        path = path.replace('/', "\\");
+    }
+    match host {
+        h if h.is_empty() || h.eq_ignore_ascii_case("localhost") => Ok(path),
+        h => Ok(format!("\\\\{}{}", h, path.replace('/', "\\"))),
+    }
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+fn is_device_path(canonical: &Path) -> bool {
+    if let Some(Component::Prefix(prefix)) = canonical.components().next() {
+        return matches!(prefix.kind(), Prefix::UNC(..) | Prefix::VerbatimUNC(..) | Prefix::DeviceNS(_));
+    }
+    cfg!(unix) && ["/dev", "/proc", "/sys"].iter().any(|d| canonical.starts_with(d))
+}
+
+fn is_windows_device_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or("").trim_end().to_ascii_uppercase();
+    matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || (stem.len() == 4 && (stem.starts_with("COM") || stem.starts_with("LPT")) && stem.as_bytes()[3].is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_canonicalizes_and_refuses_devices_and_outside_roots() {
+        let dir = std::env::temp_dir().join(format!("stegstr_guard_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let file = dir.join("in.png");
+        std::fs::write(&file, b"x").unwrap();
+        let raw = format!("file://{}", dir.join(".").join("in.png").display());
+
+        assert_eq!(validate(&raw, false, &[]).unwrap(), file);
+        assert_eq!(validate(&dir.join("new.png").to_string_lossy(), true, &[]).unwrap(), dir.join("new.png"));
+        assert!(validate(&dir.join("missing.png").to_string_lossy(), false, &[]).is_err());
+        assert!(validate(&dir.to_string_lossy(), true, &[]).unwrap_err().contains("not a regular file"));
+        assert!(validate("\\\\server\\share\\a.png", false, &[]).unwrap_err().contains("network"));
+        #[cfg(unix)]
+        assert!(validate("/dev/null", false, &[]).is_err());
+
+        assert_eq!(validate(&file.to_string_lossy(), false, std::slice::from_ref(&dir)).unwrap(), file);
+        let elsewhere = vec![dir.join("sub")];
+        assert!(validate(&file.to_string_lossy(), false, &elsewhere).unwrap_err().contains("outside"));
+        #[cfg(unix)]
+        {
+            let link = dir.join("link.png");
+            std::os::unix::fs::symlink("/etc", &link).unwrap();
+            assert!(validate(&link.to_string_lossy(), false, std::slice::from_ref(&dir)).unwrap_err().contains("outside"));
+        }
+        assert!(is_windows_device_name("com1.png") && is_windows_device_name("NUL") && !is_windows_device_name("console.png"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_file_urls_are_percent_decoded() {
+        assert_eq!(from_file_url("/plain/100%.png").unwrap(), "/plain/100%.png");
+        #[cfg(unix)]
+        {
+            assert_eq!(from_file_url("file:///Users/me/My%20Pics/%C3%BCmlaut.png").unwrap(), "/Users/me/My Pics/ümlaut.png");
+            assert_eq!(from_file_url("FILE://localhost/tmp/a.png?x=1#top").unwrap(), "/tmp/a.png");
+            assert_eq!(from_file_url("file:/tmp/ü.png").unwrap(), "/tmp/ü.png");
+        }
+        #[cfg(windows)]
+        assert_eq!(from_file_url("file:///C:/My%20Pics/a.png").unwrap(), "C:\\My Pics\\a.png");
+        assert!(from_file_url("file://server/share/a.png").unwrap().starts_with("\\\\server"));
+        assert!(validate("file://server/share/a.png", false, &[]).unwrap_err().contains("network"));
+        assert!(from_file_url("file:///bad%zz.png").is_err());
+        assert!(from_file_url("file:///bad%FF.png").is_err());
+    }
+}
@@ -0,0 +1,208 @@
+// Optional ML pre-filter for bulk scans (feature "ml-prefilter", ONNX via tract).
+// Predicts whether an image likely carries a Stegstr payload so exact decoders can be skipped.
+//
+// Model contract: input f32 [1, 3, 256, 256] = RGB of the top-left 256x256 tile (zero-padded),
+// scaled to [0, 1], no resizing (LSB detail matters). Output: one value, a probability in [0, 1]
+// or a logit (values outside [0, 1] are passed through a sigmoid).
+
+use std::path::Path;
+
+pub const INPUT_SIZE: u32 = 256;
+/// Path of the model used when no explicit path is given.
+pub const MODEL_ENV: &str = "STEGSTR_PREFILTER_MODEL";
+/// Default decision threshold; lower it for higher recall.
+pub const DEFAULT_THRESHOLD: f32 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Prediction {
+    pub probability: f32,
+    /// probability >= threshold: run the exact decoders.
+    pub likely_stego: bool,
+}
+
+/// Tile tensor (CHW, f32 in [0, 1]) per the model contract.
+pub fn input_tensor(image_path: &Path) -> Result<Vec<f32>, String> {
+    let img = image::open(image_path).map_err(|e| e.to_string())?.to_rgb8();
+    let n = INPUT_SIZE as usize;
+    let mut out = vec![0f32; 3 * n * n];
+    for y in 0..INPUT_SIZE.min(img.height()) {
+        for x in 0..INPUT_SIZE.min(img.width()) {
+            let p = img.get_pixel(x, y);
+            for c in 0..3 {
+                out[c * n * n + y as usize * n + x as usize] = p[c] as f32 / 255.0;
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn to_probability(v: f32) -> f32 {
+    if (0.0..=1.0).contains(&v) {
+        v
+    } else {
+        1.0 / (1.0 + (-v).exp())
+    }
+}
+
+#[cfg(feature = "ml-prefilter")]
+mod backend {
+    use tract_onnx::prelude::*;
+
+    pub type Model = SimplePlan<TypedFact, Box<dyn TypedOp>, Graph<TypedFact, Box<dyn TypedOp>>>;
+
+    pub fn load(path: &std::path::Path) -> Result<Model, String> {
+        let n = super::INPUT_SIZE as usize;
+        tract_onnx::onnx()
+            .model_for_path(path)
+            .and_then(|m| m.with_input_fact(0, f32::fact([1, 3, n, n]).into()))
+            .and_then(|m| m.into_optimized())
+            .and_then(|m| m.into_runnable())
+            .map_err(|e| format!("Failed to load prefilter model {}: {}", path.display(), e))
+    }
+
+    pub fn run(model: &Model, input: Vec<f32>) -> Result<f32, String> {
+        let n = super::INPUT_SIZE as usize;
+        let tensor: Tensor = tract_ndarray::Array4::from_shape_vec((1, 3, n, n), input)
+            .map_err(|e| e.to_string())?
+            .into();
+        let out = model.run(tvec!(tensor.into())).map_err(|e| e.to_string())?;
+        let view = out[0].to_array_view::<f32>().map_err(|e| e.to_string())?;
+        view.iter().next().copied().ok_or_else(|| "Prefilter model produced no output".to_string())
+    }
+}
+
+pub struct Prefilter {
+    #[cfg(feature = "ml-prefilter")]
+    model: backend::Model,
+    threshold: f32,
+}
+
+impl Prefilter {
+    /// Load an ONNX model. Errors if the crate was built without the "ml-prefilter" feature.
+    pub fn load(model_path: &Path, threshold: f32) -> Result<Self, String> {
+        #[cfg(feature = "ml-prefilter")]
+        {
+            Ok(Prefilter {
+                model: backend::load(model_path)?,
+                threshold,
+            })
+        }
+        #[cfg(not(feature = "ml-prefilter"))]
+        {
+            let _ = (model_path, threshold);
+            Err("Built without the ml-prefilter feature".to_string())
+        }
+    }
+
+    /// Load the model named by STEGSTR_PREFILTER_MODEL, if set.
+    pub fn from_env(threshold: f32) -> Option<Result<Self, String>> {
+        let path = std::env::var(MODEL_ENV).ok().filter(|s| !s.is_empty())?;
+        Some(Self::load(Path::new(&path), threshold))
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn predict(&self, image_path: &Path) -> Result<Prediction, String> {
+        let probability = to_probability(self.run(input_tensor(image_path)?)?);
+        Ok(Prediction {
+            probability,
+            likely_stego: probability >= self.threshold,
+        })
+    }
+
+    #[cfg(feature = "ml-prefilter")]
+    fn run(&self, input: Vec<f32>) -> Result<f32, String> {
+        backend::run(&self.model, input)
+    }
+
+    #[cfg(not(feature = "ml-prefilter"))]
+    fn run(&self, _input: Vec<f32>) -> Result<f32, String> {
+        Err("Built without the ml-prefilter feature".to_string())
+    }
+}
+
+#[cfg(all(test, feature = "ml-prefilter"))]
+mod tests {
+    use super::*;
+
+    fn varint(mut v: u64, out: &mut Vec<u8>) {
+        while v >= 0x80 {
+            out.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        out.push(v as u8);
+    }
+
+    /// Protobuf length-delimited field.
+    fn field(tag: u64, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        varint(tag << 3 | 2, &mut out);
+        varint(data.len() as u64, &mut out);
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn int_field(tag: u64, v: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        varint(tag << 3, &mut out);
+        varint(v, &mut out);
+        out
+    }
+
+    /// One-node ONNX model: ReduceMean over the whole input, so the "probability" is the tile's
+    /// mean brightness.
+    fn stub_model() -> Vec<u8> {
+        let float_tensor = |dims: &[u64]| {
+            let shape: Vec<u8> = dims.iter().flat_map(|&d| field(1, &int_field(1, d))).collect();
+            field(1, &[int_field(1, 1), field(2, &shape)].concat())
+        };
+        let value_info = |name: &str, dims: &[u64]| [field(1, name.as_bytes()), field(2, &float_tensor(dims))].concat();
+        let keepdims = [field(1, b"keepdims"), int_field(3, 0), int_field(20, 2)].concat();
+        let node = [field(1, b"input"), field(2, b"p"), field(4, b"ReduceMean"), field(5, &keepdims)].concat();
+        let graph = [
+            field(1, &node),
+            field(2, b"stub"),
+            field(11, &value_info("input", &[1, 3, 256, 256])),
+            field(12, &value_info("p", &[])),
+        ]
+        .concat();
+        let opset = [field(1, b""), int_field(2, 13)].concat();
+        [int_field(1, 7), field(7, &graph), field(8, &opset)].concat()
+    }
+
+    #[test]
+    fn test_stub_model_skips_dark_images_in_scan_and_batch() {
+        let dir = std::env::temp_dir().join(format!("stegstr_prefilter_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let model_path = dir.join("stub.onnx");
+        std::fs::write(&model_path, stub_model()).unwrap();
+        let prefilter = Prefilter::load(&model_path, DEFAULT_THRESHOLD).unwrap();
+
+        // Mean brightness ~0.47 passes the 0.2 threshold; ~0.04 does not.
+        let cover = dir.join("cover.png");
+        stegstr_core::test_util::noisy_cover(256, 256, 0x0773_0001).save(&cover).unwrap();
+        let stego_path = dir.join("stego.png");
+        std::fs::write(&stego_path, crate::stego::encode(&cover, b"prefiltered", &Default::default()).unwrap()).unwrap();
+        let dark = dir.join("dark.png");
+        stegstr_core::test_util::noisy_cover_in(256, 256, 0x0773_0002, 0, 20).save(&dark).unwrap();
+        let bright = prefilter.predict(&stego_path).unwrap();
+        assert!(bright.likely_stego && (0.4..0.55).contains(&bright.probability), "{:?}", bright);
+        assert!(!prefilter.predict(&dark).unwrap().likely_stego);
+
+        let paths = vec![stego_path.clone(), dark.clone()];
+        let summary = crate::scan::scan(&paths, &["dwt"], Some(&prefilter));
+        assert_eq!((summary.found, summary.skipped), (1, 1));
+        assert!(summary.results[1].skipped && summary.results[1].timings.is_empty());
+        assert_eq!(summary.methods[0].tried, 1);
+
+        let batch = crate::batch::decode_all(&paths, 2, Some(&prefilter), |p| {
+            Ok((String::from_utf8_lossy(&crate::stego::decode(p)?).to_string(), "dwt".to_string()))
+        })
+        .unwrap();
+        assert_eq!((batch.succeeded, batch.failed, batch.skipped), (1, 0, 1));
+        assert!(batch.items[1].skipped && batch.items[1].payload.is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,244 @@
+// Social-platform survival profiles: for a platform whose upload pipeline is known (channel.rs
+// simulates it), pick the codec and its strength so the payload comes out the other side, and
+// bring the cover down to the size the platform keeps so it has nothing to resize. Twitter and
+// Telegram recompress every photo, so they get QIM at the platform's width; Discord and Imgur
+// keep PNG attachments as sent (below their size limits), so they get DWT with Reed-Solomon
+// parity. Verification runs the platform's channel pipeline on the result and decodes that.
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::channel::Pipeline;
+use crate::mask::MaskSpec;
+use crate::stego;
+use crate::stego_key::StegoKey;
+use crate::stego_qim::{self, Strength};
+use crate::targets;
+
+#[derive(Debug, Clone, Copy, Serialize, JsonSchema)]
+#[serde(tag = "codec", rename_all = "lowercase")]
+pub enum ProfileCodec {
+    /// JPEG written by QIM, at most `max_width` wide.
+    Qim { max_width: u32, jpeg_quality: u8, strength: Strength },
+    /// PNG written by DWT (dot when DWT does not verify), longest side at most `max_dimension`.
+    Dwt { max_dimension: Option<u32>, parity: u8, redundancy: u32 },
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Profile {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub description: &'static str,
+    /// What the platform does to uploads, as a channel pipeline ("twitter", "strip", ...).
+    pub channel: &'static str,
+    pub codec: ProfileCodec,
+}
+
+pub const PROFILES: &[Profile] = &[
+    Profile {
+        name: "twitter",
+        label: "Twitter / X",
+        description: "Resized to 600 px wide and re-encoded as JPEG (quality 82): QIM at 600 px with the strong lattice.",
+        channel: "twitter",
+        codec: ProfileCodec::Qim {
+            max_width: 600,
+            jpeg_quality: 90,
+            strength: Strength::STRONG,
+        },
+    },
+    Profile {
+        name: "discord",
+        label: "Discord",
+        description: "Attachments are kept as sent with metadata stripped: full-size DWT PNG with parity 16.",
+        channel: "strip",
+        codec: ProfileCodec::Dwt {
+            max_dimension: None,
+            parity: 16,
+            redundancy: 0,
+        },
+    },
+    Profile {
+        name: "telegram",
+        label: "Telegram",
+        description: "Photos are resized to 1280 px and re-encoded as JPEG (quality 87): QIM at 1280 px.",
+        channel: "telegram",
+        codec: ProfileCodec::Qim {
+            max_width: 1280,
+            jpeg_quality: 90,
+            strength: Strength::STANDARD,
+        },
+    },
+    Profile {
+        name: "imgur",
+        label: "Imgur",
+        description: "PNGs under 5 MB are kept (larger ones become JPEG): DWT PNG capped at 1600 px with parity 32.",
+        channel: "strip",
+        codec: ProfileCodec::Dwt {
+            max_dimension: Some(1600),
+            parity: 32,
+            redundancy: 0,
+        },
+    },
+];
+
+pub fn find(name: &str) -> Result<&'static Profile, String> {
+    let name = name.trim();
+    PROFILES.iter().find(|p| p.name.eq_ignore_ascii_case(name)).ok_or_else(|| {
+        let names: Vec<&str> = PROFILES.iter().map(|p| p.name).collect();
+        format!("Unknown profile {} (expected one of: {})", name, names.join(", "))
+    })
+}
+
+/// A profile embed: the image, the codec and settings that produced it, and failed attempts.
+#[derive(Debug, Clone)]
+pub struct ProfileEmbed {
+    pub bytes: Vec<u8>,
+    pub method: &'static str,
+    pub settings: String,
+    pub failed_attempts: Vec<String>,
+}
+
+impl Profile {
+    /// Codec the profile writes with (dwt profiles may fall back to dot).
+    pub fn method(&self) -> &'static str {
+        match self.codec {
+            ProfileCodec::Qim { .. } => "qim",
+            ProfileCodec::Dwt { .. } => "dwt",
+        }
+    }
+
+    /// Output file extension.
+    pub fn extension(&self) -> &'static str {
+        match self.codec {
+            ProfileCodec::Qim { .. } => "jpg",
+            ProfileCodec::Dwt { .. } => "png",
+        }
+    }
+
+    /// Capacity of `cover` as the profile prepares it, with `payload` as usage.
+    pub fn capacity(&self, cover: &Path, payload: &[u8]) -> Result<crate::units::CapacityInfo, String> {
+        let usage = Some(payload.len() as u64);
+        Ok(match self.codec {
+            ProfileCodec::Qim { max_width, strength, .. } => {
+                let (w, h) = image::image_dimensions(cover).map_err(|e| e.to_string())?;
+                let bytes = stego_qim::capacity_at(w, h, max_width, &strength);
+                crate::units::CapacityInfo::new("qim", usage, bytes as u64)
+            }
+            ProfileCodec::Dwt { max_dimension, .. } => {
+                let bytes = targets::with_scaled_cover(cover, max_dimension, None, |c, _| stego::max_payload_bytes(c).map_err(String::from))?;
+                crate::units::CapacityInfo::new("dwt", usage, bytes as u64).with_stored(crate::payload_header::stored_len(payload) as u64)
+            }
+        })
+    }
+
+    /// Embed `payload` for this platform. With `verify`, the result must decode after the
+    /// platform's channel pipeline; dwt profiles first escalate as `targets::embed_verified` does.
+    pub fn embed(
+        &self,
+        cover: &Path,
+        payload: &[u8],
+        mask: Option<&MaskSpec>,
+        key: Option<StegoKey>,
+        verify: bool,
+        max_attempts: usize,
+    ) -> Result<ProfileEmbed, String> {
+        let mut embedded = match self.codec {
+            ProfileCodec::Qim {
+                max_width,
+                jpeg_quality,
+                strength,
+            } => {
+                if mask.is_some_and(|m| !m.is_empty()) || key.is_some() {
+                    return Err(format!("The {} profile writes with qim, which takes no masks or stego key", self.name));
+                }
+                let options = stego_qim::EncodeOptions {
+                    quality: jpeg_quality,
+                    strength,
+                    max_width,
+                };
+                ProfileEmbed {
+                    bytes: stego_qim::encode_with_options(cover, payload, &options)?,
+                    method: "qim",
+                    settings: format!("qim (JPEG quality {})", jpeg_quality),
+                    failed_attempts: Vec::new(),
+                }
+            }
+            ProfileCodec::Dwt {
+                max_dimension,
+                parity,
+                redundancy,
+            } => {
+                let dwt = stego::EncodeOptions {
+                    parity,
+                    redundancy,
+                    key,
+                    ..Default::default()
+                }
+                .validated()?;
+                targets::with_scaled_cover(cover, max_dimension, mask, |c, m| {
+                    if verify {
+                        let v = targets::embed_verified(c, payload, &["dwt"], m, Some(&dwt), max_attempts)?;
+                        Ok(ProfileEmbed {
+                            bytes: v.png,
+                            method: v.settings.method,
+                            settings: v.settings.to_string(),
+                            failed_attempts: v.failed_attempts,
+                        })
+                    } else {
+                        let (bytes, method) = targets::embed_with_methods(c, payload, &["dwt"], m, Some(&dwt))?;
+                        Ok(ProfileEmbed {
+                            bytes,
+                            method,
+                            settings: method.to_string(),
+                            failed_attempts: Vec::new(),
+                        })
+                    }
+                })?
+            }
+        };
+        if verify {
+            self.check_channel(&embedded.bytes, embedded.method, payload, key.as_ref())
+                .map_err(|e| format!("{}: {}", embedded.settings, e))?;
+        }
+        embedded.settings = format!("{}, {} profile", embedded.settings, self.name);
+        Ok(embedded)
+    }
+
+    /// Run the platform's pipeline on `image` and decode the result.
+    fn check_channel(&self, image: &[u8], method: &str, payload: &[u8], key: Option<&StegoKey>) -> Result<(), String> {
+        let (bytes, ext) = Pipeline::parse(self.channel)?.run(image)?;
+        let path = std::env::temp_dir().join(format!("stegstr-profile-{}-{}.{}", std::process::id(), self.name, ext));
+        crate::atomic_write::write(&path, &bytes, false)?;
+        let decoded = crate::scan::decode_with_key(method, &path, key);
+        let _ = std::fs::remove_file(&path);
+        match decoded {
+            Ok(d) if d == payload => Ok(()),
+            Ok(_) => Err(format!("read-back mismatch after the {} pipeline", self.channel)),
+            Err(e) => Err(format!("lost in the {} pipeline: {}", self.channel, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_survive_their_channel() {
+        assert!(PROFILES.iter().all(|p| Pipeline::parse(p.channel).is_ok()));
+        assert!(find("Telegram").is_ok() && find("myspace").is_err());
+        let cover = std::env::temp_dir().join(format!("stegstr_profile_cover_{}.png", std::process::id()));
+        image::RgbImage::from_fn(900, 600, |x, y| image::Rgb([64 + ((x ^ y) % 128) as u8, 64 + (x / 8) as u8, 64 + (y / 6) as u8]))
+            .save(&cover)
+            .unwrap();
+        let payload: Vec<u8> = (0..300u32).map(|i| (i * 7 % 256) as u8).collect();
+        for name in ["twitter", "discord"] {
+            let profile = find(name).unwrap();
+            let embedded = profile.embed(&cover, &payload, None, None, true, targets::max_attempts()).unwrap();
+            assert!(embedded.settings.ends_with(&format!("{} profile", name)));
+            assert!(profile.capacity(&cover, &payload).unwrap().used_percent.unwrap() < 100.0);
+        }
+        let _ = std::fs::remove_file(cover);
+    }
+}
@@ -0,0 +1,284 @@
+// How visible an embed is: PSNR over RGB and mean SSIM over luma between cover and stego image.
+// SSIM uses 8x8 windows at a stride of 4 with the usual constants (K1 = 0.01, K2 = 0.03).
+// Codecs that change the geometry are handled: DWT crops to even dimensions (compared on the
+// overlap), QIM downscales (the cover is resized to the stego image first).
+// `heatmap` shows where an embed touched the image: an RGBA overlay at the stego image's size,
+// red per changed pixel (alpha grows with the largest channel difference, amplified by
+// HEATMAP_GAIN) over a yellow tint per HEATMAP_TILE tile (alpha grows with the share of the
+// tile's pixels changed), transparent where nothing changed.
+
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use base64::Engine;
+use image::{ImageDecoder, ImageReader, RgbImage, RgbaImage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const SSIM_WINDOW: u32 = 8;
+const SSIM_STRIDE: u32 = 4;
+/// Heatmap tile edge in pixels (the smallest DWT tile).
+pub const HEATMAP_TILE: u32 = 64;
+/// Overlay alpha per unit of pixel difference: LSB changes of 1-2 show clearly.
+pub const HEATMAP_GAIN: u32 = 64;
+/// Least overlay alpha of a changed pixel, and most of a tile tint.
+const HEATMAP_MIN_ALPHA: u32 = 96;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct QualityReport {
+    /// Peak signal-to-noise ratio in dB; None when the images are identical (infinite).
+    pub psnr_db: Option<f64>,
+    /// Mean structural similarity, 1.0 for identical images.
+    pub ssim: f64,
+    /// Pixels with any channel changed.
+    pub changed_pixels: u64,
+    /// The cover was resized to the stego image's dimensions before comparing.
+    pub resized: bool,
+}
+
+impl QualityReport {
+    /// One-line summary, e.g. "PSNR 51.3 dB, SSIM 0.9987, 1204 pixels changed".
+    pub fn summary(&self) -> String {
+        let psnr = self.psnr_db.map_or("inf".to_string(), |p| format!("{:.1}", p));
+        format!(
+            "PSNR {} dB, SSIM {:.4}, {} pixels changed{}",
+            psnr,
+            self.ssim,
+            self.changed_pixels,
+            if self.resized { " (cover resized to match)" } else { "" }
+        )
+    }
+}
+
+/// Where a stego image differs from its cover (module header).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Heatmap {
+    /// The overlay as a base64 PNG, the stego image's size.
+    pub png_base64: String,
+    pub width: u32,
+    pub height: u32,
+    pub tile: u32,
+    pub tiles_x: u32,
+    pub tiles_y: u32,
+    /// Share of changed pixels per tile, row by row.
+    pub tile_changed: Vec<f64>,
+    /// Largest difference of any channel of any pixel.
+    pub max_diff: u8,
+    /// The cover was resized to the stego image's dimensions first.
+    pub resized: bool,
+}
+
+impl Heatmap {
+    /// The overlay PNG file contents.
+    pub fn png(&self) -> Result<Vec<u8>, String> {
+        base64::engine::general_purpose::STANDARD.decode(&self.png_base64).map_err(|e| e.to_string())
+    }
+
+    /// Tiles with any changed pixel.
+    pub fn tiles_changed(&self) -> usize {
+        self.tile_changed.iter().filter(|&&c| c > 0.0).count()
+    }
+}
+
+fn load(path: &Path) -> Result<RgbImage, String> {
+    let reader = ImageReader::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut decoder = reader.into_decoder().map_err(|e| format!("{}: {}", path.display(), e))?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = image::DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    img.apply_orientation(orientation);
+    Ok(img.to_rgb8())
+}
+
+fn luma(img: &RgbImage) -> Vec<f64> {
+    img.pixels()
+        .map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64)
+        .collect()
+}
+
+fn ssim(a: &[f64], b: &[f64], w: u32, h: u32) -> f64 {
+    let c1 = (0.01f64 * 255.0).powi(2);
+    let c2 = (0.03f64 * 255.0).powi(2);
+    let (win_w, win_h) = (SSIM_WINDOW.min(w), SSIM_WINDOW.min(h));
+    let n = (win_w * win_h) as f64;
+    let mut total = 0.0;
+    let mut windows = 0usize;
+    for y0 in (0..=h - win_h).step_by(SSIM_STRIDE as usize) {
+        for x0 in (0..=w - win_w).step_by(SSIM_STRIDE as usize) {
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for y in y0..y0 + win_h {
+                for x in x0..x0 + win_w {
+                    let i = (y * w + x) as usize;
+                    let (va, vb) = (a[i], b[i]);
+                    sa += va;
+                    sb += vb;
+                    saa += va * va;
+                    sbb += vb * vb;
+                    sab += va * vb;
+                }
+            }
+            let (ma, mb) = (sa / n, sb / n);
+            let (va, vb, cov) = (saa / n - ma * ma, sbb / n - mb * mb, sab / n - ma * mb);
+            total += ((2.0 * ma * mb + c1) * (2.0 * cov + c2)) / ((ma * ma + mb * mb + c1) * (va + vb + c2));
+            windows += 1;
+        }
+    }
+    total / windows as f64
+}
+
+/// Compare two decoded images of the same size.
+fn compare_same_size(cover: &RgbImage, stego: &RgbImage, resized: bool) -> QualityReport {
+    let (w, h) = stego.dimensions();
+    let mut sq_err = 0u64;
+    let mut changed_pixels = 0u64;
+    for (p, q) in cover.pixels().zip(stego.pixels()) {
+        let mut changed = false;
+        for c in 0..3 {
+            let d = p[c] as i64 - q[c] as i64;
+            sq_err += (d * d) as u64;
+            changed |= d != 0;
+        }
+        changed_pixels += changed as u64;
+    }
+    let mse = sq_err as f64 / (w as f64 * h as f64 * 3.0);
+    QualityReport {
+        psnr_db: (mse > 0.0).then(|| 10.0 * (255.0 * 255.0 / mse).log10()),
+        ssim: ssim(&luma(cover), &luma(stego), w, h),
+        changed_pixels,
+        resized,
+    }
+}
+
+/// `cover` brought to the stego image's size: cropped when the stego image is at most one pixel
+/// smaller per side, resized (and flagged) for any other size difference.
+fn aligned_cover(cover: &RgbImage, stego: &RgbImage) -> Result<(RgbImage, bool), String> {
+    let (cw, ch) = cover.dimensions();
+    let (sw, sh) = stego.dimensions();
+    if sw == 0 || sh == 0 {
+        return Err("Stego image is empty".to_string());
+    }
+    if (cw, ch) == (sw, sh) {
+        return Ok((cover.clone(), false));
+    }
+    if sw <= cw && sh <= ch && cw - sw <= 1 && ch - sh <= 1 {
+        return Ok((image::imageops::crop_imm(cover, 0, 0, sw, sh).to_image(), false));
+    }
+    Ok((image::imageops::resize(cover, sw, sh, FilterType::Triangle), true))
+}
+
+/// Compare a cover with its stego image. A stego image at most one pixel smaller per side is
+/// compared on the overlap; any other size difference resizes the cover first.
+pub fn compare_images(cover: &RgbImage, stego: &RgbImage) -> Result<QualityReport, String> {
+    let (cover, resized) = aligned_cover(cover, stego)?;
+    Ok(compare_same_size(&cover, stego, resized))
+}
+
+/// Difference heatmap of a stego image against its cover (module header), sizes matched as in
+/// `compare_images`.
+pub fn heatmap_images(cover: &RgbImage, stego: &RgbImage) -> Result<Heatmap, String> {
+    let (cover, resized) = aligned_cover(cover, stego)?;
+    let (w, h) = stego.dimensions();
+    let diff = |x: u32, y: u32| {
+        let (p, q) = (cover.get_pixel(x, y), stego.get_pixel(x, y));
+        (0..3).map(|c| p[c].abs_diff(q[c])).max().unwrap_or(0)
+    };
+    let (tiles_x, tiles_y) = (w.div_ceil(HEATMAP_TILE), h.div_ceil(HEATMAP_TILE));
+    let mut changed = vec![0u64; (tiles_x * tiles_y) as usize];
+    let mut max_diff = 0;
+    let diffs: Vec<u8> = (0..h)
+        .flat_map(|y| (0..w).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let d = diff(x, y);
+            max_diff = max_diff.max(d);
+            changed[((y / HEATMAP_TILE) * tiles_x + x / HEATMAP_TILE) as usize] += (d > 0) as u64;
+            d
+        })
+        .collect();
+    let tile_changed: Vec<f64> = changed
+        .iter()
+        .enumerate()
+        .map(|(i, &n)| {
+            let (tx, ty) = (i as u32 % tiles_x, i as u32 / tiles_x);
+            let area = (HEATMAP_TILE.min(w - tx * HEATMAP_TILE) * HEATMAP_TILE.min(h - ty * HEATMAP_TILE)) as f64;
+            n as f64 / area
+        })
+        .collect();
+    let overlay = RgbaImage::from_fn(w, h, |x, y| match diffs[(y * w + x) as usize] as u32 {
+        0 => {
+            let share = tile_changed[((y / HEATMAP_TILE) * tiles_x + x / HEATMAP_TILE) as usize];
+            image::Rgba([255, 220, 0, (share * HEATMAP_MIN_ALPHA as f64).ceil() as u8])
+        }
+        d => image::Rgba([255, 0, 0, (d * HEATMAP_GAIN).clamp(HEATMAP_MIN_ALPHA, 255) as u8]),
+    });
+    let png = crate::output::OutputFormat::Png.encode(overlay.as_raw(), w, h, image::ExtendedColorType::Rgba8)?;
+    Ok(Heatmap {
+        png_base64: base64::engine::general_purpose::STANDARD.encode(png),
+        width: w,
+        height: h,
+        tile: HEATMAP_TILE,
+        tiles_x,
+        tiles_y,
+        tile_changed,
+        max_diff,
+        resized,
+    })
+}
+
+/// `heatmap_images` for two image files.
+pub fn heatmap(cover: &Path, stego: &Path) -> Result<Heatmap, String> {
+    heatmap_images(&load(cover)?, &load(stego)?)
+}
+
+/// `compare_images` for two image files.
+pub fn compare(cover: &Path, stego: &Path) -> Result<QualityReport, String> {
+    compare_images(&load(cover)?, &load(stego)?)
+}
+
+/// `compare` for a stego image still held in memory (encoder output).
+pub fn compare_bytes(cover: &Path, stego: &[u8]) -> Result<QualityReport, String> {
+    let stego = image::load_from_memory(stego).map_err(|e| e.to_string())?.to_rgb8();
+    compare_images(&load(cover)?, &stego)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_reports_psnr_and_ssim() {
+        let cover = RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128]));
+        let same = compare_images(&cover, &cover).unwrap();
+        assert_eq!((same.psnr_db, same.ssim, same.changed_pixels), (None, 1.0, 0));
+
+        let mut stego = cover.clone();
+        for x in 0..64 {
+            stego.get_pixel_mut(x, 10)[0] ^= 1;
+        }
+        let r = compare_images(&cover, &stego).unwrap();
+        assert_eq!(r.changed_pixels, 64);
+        // MSE = 64 / (64 * 64 * 3): PSNR = 10 log10(255^2 * 192) ~ 70.96 dB.
+        assert!((r.psnr_db.unwrap() - 70.96).abs() < 0.01, "{:?}", r.psnr_db);
+        assert!(r.ssim > 0.99 && r.ssim < 1.0);
+
+        let cropped = image::imageops::crop_imm(&stego, 0, 0, 63, 63).to_image();
+        assert!(!compare_images(&cover, &cropped).unwrap().resized);
+        let half = image::imageops::resize(&cover, 32, 32, FilterType::Triangle);
+        assert!(compare_images(&cover, &half).unwrap().resized);
+    }
+
+    #[test]
+    fn test_heatmap_marks_changed_pixels_and_tiles() {
+        let cover = RgbImage::from_fn(100, 70, |x, y| image::Rgb([x as u8, y as u8, 128]));
+        let mut stego = cover.clone();
+        stego.get_pixel_mut(70, 5)[1] ^= 1;
+        stego.get_pixel_mut(3, 66)[2] ^= 3;
+        let map = heatmap_images(&cover, &stego).unwrap();
+        assert_eq!((map.tiles_x, map.tiles_y, map.max_diff, map.tiles_changed()), (2, 2, 3, 2));
+        assert_eq!(map.tile_changed[1], 1.0 / (36.0 * 64.0));
+        let overlay = image::load_from_memory(&map.png().unwrap()).unwrap().to_rgba8();
+        assert_eq!(overlay.dimensions(), (100, 70));
+        assert_eq!(overlay.get_pixel(3, 66).0, [255, 0, 0, 192]);
+        assert_eq!(overlay.get_pixel(70, 5).0, [255, 0, 0, 96]);
+        assert_eq!(overlay.get_pixel(71, 5).0[3], 1);
+        assert_eq!(overlay.get_pixel(10, 10).0[3], 0);
+    }
+}
@@ -0,0 +1,131 @@
+// Embedding receipts: signed NIP-78 (kind 30078) app-data events recording what was embedded,
+// into which cover, with which method and when. Appended to <data dir>/receipts.jsonl.
+
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+
+use crate::nostr_event::{self, NostrEvent};
+
+pub const RECEIPT_KIND: u32 = 30078;
+const RECEIPTS_FILE: &str = "receipts.jsonl";
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// What a receipt records. `output` is None when the receipt is embedded in that same image.
+pub struct ReceiptInfo<'a> {
+    pub payload: &'a [u8],
+    pub cover: &'a [u8],
+    pub output: Option<&'a [u8]>,
+    pub method: &'a str,
+}
+
+pub fn create_receipt(info: &ReceiptInfo, sk: &secp256k1::SecretKey) -> Result<NostrEvent, String> {
+    let payload_hash = sha256_hex(info.payload);
+    let mut tags = vec![
+        vec!["d".to_string(), format!("stegstr/receipt/{}", payload_hash)],
+        vec!["x".to_string(), payload_hash],
+        vec!["cover".to_string(), sha256_hex(info.cover)],
+        vec!["method".to_string(), info.method.to_string()],
+        vec!["size".to_string(), info.payload.len().to_string()],
+        vec!["alt".to_string(), "Stegstr embedding receipt".to_string()],
+    ];
+    if let Some(output) = info.output {
+        tags.push(vec!["output".to_string(), sha256_hex(output)]);
+    }
+    nostr_event::sign_event(RECEIPT_KIND, tags, "", sk)
+}
+
+/// Create a receipt for a finished embed (reading cover and output from disk) and store it.
+pub fn record_embed(
+    payload: &[u8],
+    cover_path: &Path,
+    output_path: &Path,
+    method: &str,
+    sk: &secp256k1::SecretKey,
+) -> Result<NostrEvent, String> {
+    let cover = std::fs::read(cover_path).map_err(|e| e.to_string())?;
+    let output = std::fs::read(output_path).map_err(|e| e.to_string())?;
+    let info = ReceiptInfo {
+        payload,
+        cover: &cover,
+        output: Some(&output),
+        method,
+    };
+    let receipt = create_receipt(&info, sk)?;
+    append_receipt(&receipt)?;
+    Ok(receipt)
+}
+
+/// Sign a receipt for `bundle_json` (a bundle as given) going into `cover_path` with `method`, and
+/// add it to the bundle so it rides along inside the image. Returns the new bundle JSON and the
+/// receipt, which the caller stores (append_receipt) once the embed succeeds.
+pub fn embed_in_bundle(
+    bundle_json: &[u8],
+    cover_path: &Path,
+    method: &str,
+    sk: &secp256k1::SecretKey,
+) -> Result<(Vec<u8>, NostrEvent), String> {
+    let mut bundle: nostr_event::Bundle =
+        serde_json::from_slice(bundle_json).map_err(|e| format!("A receipt in the image requires a bundle JSON payload: {}", e))?;
+    let cover = std::fs::read(cover_path).map_err(|e| format!("{}: {}", cover_path.display(), e))?;
+    let info = ReceiptInfo {
+        payload: bundle_json,
+        cover: &cover,
+        output: None,
+        method,
+    };
+    let receipt = create_receipt(&info, sk)?;
+    bundle.events.push(receipt.clone());
+    Ok((serde_json::to_vec(&bundle).map_err(|e| e.to_string())?, receipt))
+}
+
+pub fn append_receipt(receipt: &NostrEvent) -> Result<(), String> {
+    let path = crate::app_paths::data_path(RECEIPTS_FILE)?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    let line = serde_json::to_string(receipt).map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+/// All stored receipts, oldest first. Unparseable lines are skipped.
+pub fn list_receipts() -> Result<Vec<NostrEvent>, String> {
+    let path = crate::app_paths::data_path(RECEIPTS_FILE)?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.to_string()),
+    };
+    Ok(text
+        .lines()
+        .filter_map(|l| serde_json::from_str::<NostrEvent>(l).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embed_in_bundle_hashes_the_bundle_as_given() {
+        let path = std::env::temp_dir().join(format!("stegstr_receipt_cover_{}.png", std::process::id()));
+        std::fs::write(&path, b"cover bytes").unwrap();
+        let sk = secp256k1::SecretKey::from_slice(&[0x44; 32]).unwrap();
+        let note = nostr_event::sign_event_at(1, vec![], "hello", 100, &sk).unwrap();
+        let json = serde_json::to_vec(&nostr_event::Bundle::new(vec![note.clone()])).unwrap();
+
+        let (with_receipt, receipt) = embed_in_bundle(&json, &path, "dwt", &sk).unwrap();
+        let bundle: nostr_event::Bundle = serde_json::from_slice(&with_receipt).unwrap();
+        assert_eq!(bundle.events, vec![note, receipt.clone()]);
+        assert!(receipt.tags.contains(&vec!["x".to_string(), sha256_hex(&json)]));
+        assert!(receipt.tags.contains(&vec!["cover".to_string(), sha256_hex(b"cover bytes")]));
+        assert!(!receipt.tags.iter().any(|t| t[0] == "output"));
+        assert!(embed_in_bundle(b"not a bundle", &path, "dwt", &sk).is_err());
+        let _ = std::fs::remove_file(path);
+    }
+}
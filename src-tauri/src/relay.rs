@@ -0,0 +1,388 @@
+// Relay publishing and fetching: send a signed event to many relays at once (or subscribe with a
+// NIP-01 filter) and collect a per-relay outcome. Each relay gets its own thread and the whole call
+// shares one deadline, so a dead relay costs at most the timeout instead of stalling the others
+// (relay.ts publishEvent is fire-and-forget).
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::http::Uri;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+use crate::nostr_event::NostrEvent;
+
+/// Same defaults as relay.ts DEFAULT_RELAYS.
+pub const DEFAULT_RELAYS: &[&str] = &[
+    "wss://relay.primal.net",
+    "wss://relay.damus.io",
+    "wss://nos.lol",
+    "wss://relay.nostr.band",
+];
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RelayOutcome {
+    pub relay: String,
+    /// "ok", "rejected" (relay answered OK false or CLOSED), "timeout" or "error".
+    pub status: String,
+    /// The relay's OK message (the reason when rejected) or the connection error.
+    pub message: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+/// Outcomes for one event, as printed by `stegstr-cli publish --json`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PublishReport {
+    pub event_id: String,
+    pub results: Vec<RelayOutcome>,
+}
+
+/// NIP-01 subscription filter; unset fields are left out of the REQ.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Filter {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// Events collected by `fetch` (deduplicated, verified, oldest first) and how each relay fared.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FetchReport {
+    pub events: Vec<NostrEvent>,
+    pub results: Vec<RelayOutcome>,
+}
+
+impl RelayOutcome {
+    pub(crate) fn new(relay: &str, status: &str, message: Option<String>, started: Instant) -> Self {
+        RelayOutcome {
+            relay: relay.to_string(),
+            status: status.to_string(),
+            message: message.filter(|m| !m.is_empty()),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut)
+}
+
+fn remaining(deadline: Instant) -> Option<Duration> {
+    deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero())
+}
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// Open a WebSocket to `url` within the deadline. The second handle shares the socket so callers
+/// can move the read deadline as time passes; errors are (status, message) for RelayOutcome.
+pub(crate) fn connect(url: &str, deadline: Instant) -> Result<(Socket, TcpStream), (&'static str, String)> {
+    let timeout = |msg: &str| ("timeout", msg.to_string());
+    let error = |msg: String| ("error", msg);
+
+    let uri: Uri = match url.parse() {
+        Ok(u) => u,
+        Err(e) => return Err(error(e.to_string())),
+    };
+    let default_port = match uri.scheme_str() {
+        Some("wss") => 443,
+        Some("ws") => 80,
+        _ => return Err(error("relay URL must start with ws:// or wss://".to_string())),
+    };
+    let Some(host) = uri.host() else {
+        return Err(error("relay URL has no host".to_string()));
+    };
+    let addrs: Vec<_> = match (host, uri.port_u16().unwrap_or(default_port)).to_socket_addrs() {
+        Ok(a) => a.collect(),
+        Err(e) => return Err(error(e.to_string())),
+    };
+    let Some(addr) = addrs.first() else {
+        return Err(error(format!("{} did not resolve", host)));
+    };
+    let Some(left) = remaining(deadline) else {
+        return Err(timeout("deadline passed before connecting"));
+    };
+    let stream = match TcpStream::connect_timeout(addr, left) {
+        Ok(s) => s,
+        Err(e) if is_timeout(&e) => return Err(timeout("connect timed out")),
+        Err(e) => return Err(error(e.to_string())),
+    };
+    let control = stream.try_clone().map_err(|e| error(e.to_string()))?;
+    let _ = control.set_read_timeout(remaining(deadline));
+    let _ = control.set_write_timeout(remaining(deadline));
+
+    match tungstenite::client_tls(url, stream) {
+        Ok((ws, _response)) => Ok((ws, control)),
+        Err(e) => Err(error(format!("handshake: {}", e))),
+    }
+}
+
+/// Connect, send ["EVENT", event] and wait for the matching ["OK", id, accepted, message].
+fn publish_one(url: &str, payload: &str, event_id: &str, deadline: Instant) -> RelayOutcome {
+    let started = Instant::now();
+    let timeout = |msg: &str| RelayOutcome::new(url, "timeout", Some(msg.to_string()), started);
+    let error = |msg: String| RelayOutcome::new(url, "error", Some(msg), started);
+
+    let (mut ws, control) = match connect(url, deadline) {
+        Ok(c) => c,
+        Err((status, msg)) => return RelayOutcome::new(url, status, Some(msg), started),
+    };
+    if let Err(e) = ws.send(Message::text(payload)) {
+        return error(e.to_string());
+    }
+    loop {
+        let Some(left) = remaining(deadline) else {
+            return timeout("no OK before deadline");
+        };
+        let _ = control.set_read_timeout(Some(left));
+        let msg = match ws.read() {
+            Ok(m) => m,
+            Err(tungstenite::Error::Io(e)) if is_timeout(&e) => return timeout("no OK before deadline"),
+            Err(e) => return error(e.to_string()),
+        };
+        let Message::Text(text) = msg else { continue };
+        let Ok(frame) = serde_json::from_str::<Vec<serde_json::Value>>(text.as_str()) else {
+            continue;
+        };
+        if frame.first().and_then(|v| v.as_str()) != Some("OK") || frame.get(1).and_then(|v| v.as_str()) != Some(event_id) {
+            continue;
+        }
+        let accepted = frame.get(2).and_then(|v| v.as_bool()).unwrap_or(false);
+        let message = frame.get(3).and_then(|v| v.as_str()).map(str::to_string);
+        let _ = ws.close(None);
+        return RelayOutcome::new(url, if accepted { "ok" } else { "rejected" }, message, started);
+    }
+}
+
+/// Send ["REQ", sub, filter] and collect EVENT frames until EOSE, CLOSED or the deadline.
+fn fetch_one(url: &str, req: &str, sub_id: &str, deadline: Instant) -> (RelayOutcome, Vec<NostrEvent>) {
+    let started = Instant::now();
+    let mut events = Vec::new();
+    let (mut ws, control) = match connect(url, deadline) {
+        Ok(c) => c,
+        Err((status, msg)) => return (RelayOutcome::new(url, status, Some(msg), started), events),
+    };
+    if let Err(e) = ws.send(Message::text(req)) {
+        return (RelayOutcome::new(url, "error", Some(e.to_string()), started), events);
+    }
+    let outcome = loop {
+        let Some(left) = remaining(deadline) else {
+            break RelayOutcome::new(url, "timeout", Some("no EOSE before deadline".to_string()), started);
+        };
+        let _ = control.set_read_timeout(Some(left));
+        let msg = match ws.read() {
+            Ok(m) => m,
+            Err(tungstenite::Error::Io(e)) if is_timeout(&e) => {
+                break RelayOutcome::new(url, "timeout", Some("no EOSE before deadline".to_string()), started)
+            }
+            Err(e) => break RelayOutcome::new(url, "error", Some(e.to_string()), started),
+        };
+        let Message::Text(text) = msg else { continue };
+        let Ok(frame) = serde_json::from_str::<Vec<serde_json::Value>>(text.as_str()) else {
+            continue;
+        };
+        if frame.get(1).and_then(|v| v.as_str()) != Some(sub_id) {
+            continue;
+        }
+        match frame.first().and_then(|v| v.as_str()) {
+            Some("EVENT") => {
+                // Relays are untrusted: keep only events whose id and signature check out.
+                if let Some(event) = frame.get(2).and_then(|v| serde_json::from_value::<NostrEvent>(v.clone()).ok()) {
+                    if crate::nostr_verify::verify_event(&event).is_ok() {
+                        events.push(event);
+                    }
+                }
+            }
+            Some("EOSE") => break RelayOutcome::new(url, "ok", None, started),
+            Some("CLOSED") => {
+                let message = frame.get(2).and_then(|v| v.as_str()).map(str::to_string);
+                break RelayOutcome::new(url, "rejected", message, started);
+            }
+            _ => {}
+        }
+    };
+    let close = serde_json::json!(["CLOSE", sub_id]).to_string();
+    let _ = ws.send(Message::text(close));
+    let _ = ws.close(None);
+    // A relay that timed out may still have sent stored events; they are kept.
+    (outcome, events)
+}
+
+/// Trimmed, de-duplicated relay URLs in input order.
+pub(crate) fn unique_urls(relays: &[String]) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for r in relays.iter().map(|r| r.trim().trim_end_matches('/').to_string()) {
+        if !r.is_empty() && !urls.contains(&r) {
+            urls.push(r);
+        }
+    }
+    urls
+}
+
+/// Run `work` for every URL on its own thread and gather results in input order. Workers
+/// enforce the deadline themselves; the grace covers DNS, which has no timeout.
+fn run_per_relay<T, F>(urls: &[String], deadline: Instant, work: F) -> Vec<Option<T>>
+where
+    T: Send + 'static,
+    F: Fn(&str) -> T + Send + Clone + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    for (i, url) in urls.iter().enumerate() {
+        let (tx, url, work) = (tx.clone(), url.clone(), work.clone());
+        thread::spawn(move || {
+            let _ = tx.send((i, work(&url)));
+        });
+    }
+    drop(tx);
+
+    let mut out: Vec<Option<T>> = (0..urls.len()).map(|_| None).collect();
+    let hard_deadline = deadline + Duration::from_millis(500);
+    while let Some(left) = remaining(hard_deadline) {
+        match rx.recv_timeout(left) {
+            Ok((i, result)) => out[i] = Some(result),
+            Err(_) => break,
+        }
+    }
+    out
+}
+
+fn no_response(url: &str, started: Instant) -> RelayOutcome {
+    RelayOutcome::new(url, "timeout", Some("no response".to_string()), started)
+}
+
+/// Publish `event` to every relay concurrently; one outcome per relay, in input order (duplicates dropped).
+pub fn publish(event: &NostrEvent, relays: &[String], timeout: Duration) -> Result<Vec<RelayOutcome>, String> {
+    let payload = serde_json::to_string(&serde_json::json!(["EVENT", event])).map_err(|e| e.to_string())?;
+    let urls = unique_urls(relays);
+    let started = Instant::now();
+    let deadline = started + timeout;
+    let id = event.id.clone();
+    let outcomes = run_per_relay(&urls, deadline, move |url| publish_one(url, &payload, &id, deadline));
+    Ok(outcomes
+        .into_iter()
+        .zip(&urls)
+        .map(|(o, url)| o.unwrap_or_else(|| no_response(url, started)))
+        .collect())
+}
+
+/// Subscribe to every relay with `filter` and merge what they return until EOSE or the deadline.
+pub fn fetch(filter: &Filter, relays: &[String], timeout: Duration) -> Result<FetchReport, String> {
+    let started = Instant::now();
+    let sub_id = format!("stegstr-{:x}", rand::random::<u32>());
+    let req = serde_json::to_string(&serde_json::json!(["REQ", sub_id, filter])).map_err(|e| e.to_string())?;
+    let urls = unique_urls(relays);
+    let deadline = started + timeout;
+    let per_relay = run_per_relay(&urls, deadline, move |url| fetch_one(url, &req, &sub_id, deadline));
+
+    let mut seen = HashSet::new();
+    let mut events = Vec::new();
+    let mut results = Vec::new();
+    for (r, url) in per_relay.into_iter().zip(&urls) {
+        let Some((outcome, evs)) = r else {
+            results.push(no_response(url, started));
+            continue;
+        };
+        results.push(outcome);
+        events.extend(evs.into_iter().filter(|e| seen.insert(e.id.clone())));
+    }
+    events.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+    if let Some(limit) = filter.limit {
+        // Each relay applies the limit to its newest events; keep the newest overall.
+        let excess = events.len().saturating_sub(limit as usize);
+        events.drain(..excess);
+    }
+    Ok(FetchReport { events, results })
+}
+
+pub fn default_relays() -> Vec<String> {
+    DEFAULT_RELAYS.iter().map(|r| r.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_publish_reports_each_relay() {
+        let sk = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let event = crate::nostr_event::create_kind1_event("relay test", &sk).unwrap();
+
+        // Accepting relay: answers OK true.
+        let good = TcpListener::bind("127.0.0.1:0").unwrap();
+        let good_url = format!("ws://{}", good.local_addr().unwrap());
+        let id = event.id.clone();
+        thread::spawn(move || {
+            let (stream, _) = good.accept().unwrap();
+            let mut ws = tungstenite::accept(stream).unwrap();
+            let _ = ws.read().unwrap();
+            ws.send(Message::text(serde_json::json!(["OK", id, true, ""]).to_string())).unwrap();
+            let _ = ws.read();
+        });
+        // Silent relay: completes the handshake, never answers.
+        let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+        let silent_url = format!("ws://{}", silent.local_addr().unwrap());
+        thread::spawn(move || {
+            let (stream, _) = silent.accept().unwrap();
+            let mut ws = tungstenite::accept(stream).unwrap();
+            let _ = ws.read();
+            thread::sleep(Duration::from_secs(3));
+        });
+
+        let relays = vec![good_url.clone(), silent_url, "https://not-a-relay".to_string(), good_url];
+        let started = Instant::now();
+        let out = publish(&event, &relays, Duration::from_millis(800)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let statuses: Vec<&str> = out.iter().map(|o| o.status.as_str()).collect();
+        assert_eq!(statuses, vec!["ok", "timeout", "error"]);
+    }
+
+    #[test]
+    fn test_fetch_merges_verified_events() {
+        let sk = secp256k1::SecretKey::from_slice(&[9u8; 32]).unwrap();
+        let old = crate::nostr_event::sign_event_at(1, vec![], "old", 100, &sk).unwrap();
+        let new = crate::nostr_event::sign_event_at(1, vec![], "new", 200, &sk).unwrap();
+        let mut forged = new.clone();
+        forged.content = "forged".to_string();
+
+        // Both relays return the same stored events plus one forgery, then EOSE.
+        let mut relays = Vec::new();
+        for events in [vec![new.clone(), old.clone()], vec![old.clone(), forged]] {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            relays.push(format!("ws://{}", listener.local_addr().unwrap()));
+            thread::spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                let mut ws = tungstenite::accept(stream).unwrap();
+                let req: Vec<serde_json::Value> = serde_json::from_str(ws.read().unwrap().to_text().unwrap()).unwrap();
+                assert_eq!(req[2]["since"], 50);
+                let sub = req[1].clone();
+                for e in events {
+                    ws.send(Message::text(serde_json::json!(["EVENT", sub, e]).to_string())).unwrap();
+                }
+                ws.send(Message::text(serde_json::json!(["EOSE", sub]).to_string())).unwrap();
+                let _ = ws.read();
+            });
+        }
+
+        let filter = Filter {
+            since: Some(50),
+            ..Default::default()
+        };
+        let report = fetch(&filter, &relays, Duration::from_secs(2)).unwrap();
+        assert_eq!(report.events, vec![old, new]);
+        assert!(report.results.iter().all(|r| r.status == "ok"));
+    }
+}
@@ -0,0 +1,413 @@
+// Long-lived relay connections for the desktop app, next to relay.rs's one-shot publish and fetch.
+// Each connected relay gets a thread that owns its WebSocket, reconnects with backoff (1 s doubling
+// to a minute) and re-sends every open subscription after a reconnect. Incoming frames become
+// RelayMessages handed to the pool's sink (the app emits them as `relay://message`): events are
+// signature-checked and deduplicated per subscription across relays. `publish` sends an event
+// through the open connections and waits for each relay's OK, like relay::publish;
+// `publish_many` does the same for a batch (the outbox flush).
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::Message;
+
+use crate::nostr_event::NostrEvent;
+use crate::relay::{self, Filter, RelayOutcome};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a relay thread blocks on a read before checking for outgoing frames.
+const POLL: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// What a relay sent (or what happened to its connection), as streamed to the frontend.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RelayMessage {
+    /// A verified event for a subscription, the first time any relay sends it.
+    Event { relay: String, subscription: String, event: NostrEvent },
+    /// The relay has sent all stored events for the subscription; new ones follow as they arrive.
+    Eose { relay: String, subscription: String },
+    /// The relay ended the subscription.
+    Closed { relay: String, subscription: String, message: Option<String> },
+    Notice { relay: String, message: String },
+    /// Connected, or lost the connection (with the error) and will retry.
+    Status { relay: String, connected: bool, error: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RelayStatus {
+    pub relay: String,
+    pub connected: bool,
+    /// Why the last attempt failed, while disconnected.
+    pub error: Option<String>,
+}
+
+struct Connection {
+    outgoing: mpsc::Sender<String>,
+    stop: Arc<AtomicBool>,
+}
+
+struct Subscription {
+    req: Vec<serde_json::Value>,
+    seen: HashSet<String>,
+}
+
+type OkSender = mpsc::Sender<(bool, Option<String>)>;
+
+struct Inner {
+    sink: Box<dyn Fn(RelayMessage) + Send + Sync>,
+    connections: Mutex<HashMap<String, Connection>>,
+    status: Mutex<HashMap<String, RelayStatus>>,
+    subscriptions: Mutex<HashMap<String, Subscription>>,
+    /// Publishes waiting for OK, by (relay, event id).
+    pending: Mutex<HashMap<(String, String), OkSender>>,
+}
+
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Connections, subscriptions and pending publishes; clones share them.
+#[derive(Clone)]
+pub struct RelayPool {
+    inner: Arc<Inner>,
+}
+
+impl RelayPool {
+    /// `sink` receives every RelayMessage, on the relay threads.
+    pub fn new(sink: impl Fn(RelayMessage) + Send + Sync + 'static) -> Self {
+        RelayPool {
+            inner: Arc::new(Inner {
+                sink: Box::new(sink),
+                connections: Mutex::default(),
+                status: Mutex::default(),
+                subscriptions: Mutex::default(),
+                pending: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Start connections to `relays` not already open; returns the normalized URLs.
+    pub fn connect(&self, relays: &[String]) -> Result<Vec<String>, String> {
+        let urls = relay::unique_urls(relays);
+        if let Some(bad) = urls.iter().find(|u| !(u.starts_with("ws://") || u.starts_with("wss://"))) {
+            return Err(format!("{}: relay URL must start with ws:// or wss://", bad));
+        }
+        let mut connections = lock(&self.inner.connections);
+        for url in &urls {
+            if connections.contains_key(url) {
+                continue;
+            }
+            let (outgoing, rx) = mpsc::channel();
+            let stop = Arc::new(AtomicBool::new(false));
+            connections.insert(url.clone(), Connection { outgoing, stop: stop.clone() });
+            lock(&self.inner.status).insert(url.clone(), RelayStatus { relay: url.clone(), connected: false, error: None });
+            let (inner, url) = (self.inner.clone(), url.clone());
+            thread::spawn(move || run_relay(&inner, &url, &rx, &stop));
+        }
+        Ok(urls)
+    }
+
+    /// Close the connections to `relays` (all when None); returns how many were open.
+    pub fn disconnect(&self, relays: Option<&[String]>) -> usize {
+        let mut connections = lock(&self.inner.connections);
+        let urls: Vec<String> = match relays {
+            Some(r) => relay::unique_urls(r),
+            None => connections.keys().cloned().collect(),
+        };
+        let mut closed = 0;
+        for url in urls {
+            if let Some(conn) = connections.remove(&url) {
+                conn.stop.store(true, Ordering::Relaxed);
+                lock(&self.inner.status).remove(&url);
+                closed += 1;
+            }
+        }
+        closed
+    }
+
+    /// Every relay the pool holds, sorted by URL.
+    pub fn status(&self) -> Vec<RelayStatus> {
+        let mut out: Vec<RelayStatus> = lock(&self.inner.status).values().cloned().collect();
+        out.sort_by(|a, b| a.relay.cmp(&b.relay));
+        out
+    }
+
+    /// Open (or replace) subscription `id` with `filters` on every connected relay, and on relays
+    /// connected later. Returns the id (generated when None).
+    pub fn subscribe(&self, id: Option<String>, filters: Vec<Filter>) -> Result<String, String> {
+        if filters.is_empty() {
+            return Err("A subscription needs at least one filter".to_string());
+        }
+        let id = id.filter(|i| !i.is_empty()).unwrap_or_else(|| format!("stegstr-{:x}", rand::random::<u32>()));
+        let mut req = vec![serde_json::json!("REQ"), serde_json::json!(id)];
+        for filter in &filters {
+            req.push(serde_json::to_value(filter).map_err(|e| e.to_string())?);
+        }
+        let frame = serde_json::Value::Array(req.clone()).to_string();
+        lock(&self.inner.subscriptions).insert(id.clone(), Subscription { req, seen: HashSet::new() });
+        self.broadcast(&frame, None);
+        Ok(id)
+    }
+
+    /// Close subscription `id` everywhere; false if it was not open.
+    pub fn unsubscribe(&self, id: &str) -> bool {
+        if lock(&self.inner.subscriptions).remove(id).is_none() {
+            return false;
+        }
+        self.broadcast(&serde_json::json!(["CLOSE", id]).to_string(), None);
+        true
+    }
+
+    /// Send `event` to `relays` (connecting to any not open; default: every open connection) and
+    /// wait up to `timeout` for each relay's OK. One outcome per relay, in order.
+    pub fn publish(&self, event: &NostrEvent, relays: Option<&[String]>, timeout: Duration) -> Result<Vec<RelayOutcome>, String> {
+        Ok(self.publish_many(std::slice::from_ref(event), relays, timeout)?.remove(0))
+    }
+
+    /// `publish` for a batch: every event goes out over the same connections before any OK is
+    /// awaited, and all share one `timeout`. Outcomes per event, in order.
+    pub fn publish_many(&self, events: &[NostrEvent], relays: Option<&[String]>, timeout: Duration) -> Result<Vec<Vec<RelayOutcome>>, String> {
+        let urls = match relays {
+            Some(r) if !r.is_empty() => self.connect(r)?,
+            _ => lock(&self.inner.connections).keys().cloned().collect(),
+        };
+        if urls.is_empty() {
+            return Err("No relays connected (relay_connect first, or name relays)".to_string());
+        }
+        let started = Instant::now();
+        let waiting: Vec<Vec<_>> = events
+            .iter()
+            .map(|event| {
+                urls.iter()
+                    .map(|url| {
+                        let (tx, rx) = mpsc::channel();
+                        lock(&self.inner.pending).insert((url.clone(), event.id.clone()), tx);
+                        (url, rx)
+                    })
+                    .collect()
+            })
+            .collect();
+        for event in events {
+            self.broadcast(&serde_json::json!(["EVENT", event]).to_string(), Some(&urls));
+        }
+        let deadline = started + timeout;
+        let outcomes = waiting
+            .into_iter()
+            .zip(events)
+            .map(|(per_relay, event)| {
+                per_relay
+                    .into_iter()
+                    .map(|(url, rx)| {
+                        let left = deadline.saturating_duration_since(Instant::now());
+                        let outcome = match rx.recv_timeout(left) {
+                            Ok((true, message)) => RelayOutcome::new(url, "ok", message, started),
+                            Ok((false, message)) => RelayOutcome::new(url, "rejected", message, started),
+                            Err(_) => RelayOutcome::new(url, "timeout", Some("no OK before deadline".to_string()), started),
+                        };
+                        lock(&self.inner.pending).remove(&(url.clone(), event.id.clone()));
+                        outcome
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(outcomes)
+    }
+
+    /// Queue `frame` for `urls` (every connection when None); relays still connecting send it
+    /// once connected.
+    fn broadcast(&self, frame: &str, urls: Option<&[String]>) {
+        let connections = lock(&self.inner.connections);
+        for (url, conn) in connections.iter() {
+            if urls.is_none_or(|u| u.contains(url)) {
+                let _ = conn.outgoing.send(frame.to_string());
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn set_status(&self, url: &str, connected: bool, error: Option<String>) {
+        if let Some(status) = lock(&self.status).get_mut(url) {
+            status.connected = connected;
+            status.error = error.clone();
+        }
+        (self.sink)(RelayMessage::Status { relay: url.to_string(), connected, error });
+    }
+
+    fn handle(&self, url: &str, text: &str) {
+        let Ok(frame) = serde_json::from_str::<Vec<serde_json::Value>>(text) else {
+            return;
+        };
+        let str_at = |i: usize| frame.get(i).and_then(|v| v.as_str()).map(str::to_string);
+        let relay = url.to_string();
+        let message = match (str_at(0).as_deref(), str_at(1)) {
+            (Some("EVENT"), Some(subscription)) => {
+                let Some(event) = frame.get(2).and_then(|v| serde_json::from_value::<NostrEvent>(v.clone()).ok()) else {
+                    return;
+                };
+                // Relays are untrusted: only events whose id and signature check out, once each.
+                let mut subscriptions = lock(&self.subscriptions);
+                let Some(sub) = subscriptions.get_mut(&subscription) else {
+                    return;
+                };
+                if crate::nostr_verify::verify_event(&event).is_err() || !sub.seen.insert(event.id.clone()) {
+                    return;
+                }
+                RelayMessage::Event { relay, subscription, event }
+            }
+            (Some("EOSE"), Some(subscription)) => RelayMessage::Eose { relay, subscription },
+            (Some("CLOSED"), Some(subscription)) => RelayMessage::Closed { relay, subscription, message: str_at(2).filter(|m| !m.is_empty()) },
+            (Some("NOTICE"), Some(message)) => RelayMessage::Notice { relay, message },
+            (Some("OK"), Some(event_id)) => {
+                let accepted = frame.get(2).and_then(|v| v.as_bool()).unwrap_or(false);
+                if let Some(tx) = lock(&self.pending).remove(&(relay, event_id)) {
+                    let _ = tx.send((accepted, str_at(3).filter(|m| !m.is_empty())));
+                }
+                return;
+            }
+            _ => return,
+        };
+        (self.sink)(message);
+    }
+}
+
+/// Sleep `d` in POLL steps; false if stopped meanwhile.
+fn wait(d: Duration, stop: &AtomicBool) -> bool {
+    let until = Instant::now() + d;
+    while Instant::now() < until {
+        if stop.load(Ordering::Relaxed) {
+            return false;
+        }
+        thread::sleep(POLL);
+    }
+    !stop.load(Ordering::Relaxed)
+}
+
+/// A relay's thread: connect, replay subscriptions, then pump frames both ways until stopped,
+/// reconnecting after errors.
+fn run_relay(inner: &Inner, url: &str, outgoing: &mpsc::Receiver<String>, stop: &AtomicBool) {
+    let mut backoff = Duration::from_secs(1);
+    while !stop.load(Ordering::Relaxed) {
+        let (mut ws, control) = match relay::connect(url, Instant::now() + CONNECT_TIMEOUT) {
+            Ok(c) => c,
+            Err((_, message)) => {
+                inner.set_status(url, false, Some(message));
+                if !wait(backoff, stop) {
+                    return;
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+        backoff = Duration::from_secs(1);
+        let _ = control.set_read_timeout(Some(POLL));
+        let _ = control.set_write_timeout(Some(CONNECT_TIMEOUT));
+        inner.set_status(url, true, None);
+        // Subscriptions are replayed as they stand now, so REQ and CLOSE frames queued while
+        // disconnected are dropped; queued events still go out.
+        let mut replay: Vec<String> = lock(&inner.subscriptions).values().map(|s| serde_json::Value::Array(s.req.clone()).to_string()).collect();
+        replay.extend(outgoing.try_iter().filter(|f| f.starts_with("[\"EVENT\"")));
+        let error = 'session: {
+            for frame in replay {
+                if let Err(e) = ws.send(Message::text(frame)) {
+                    break 'session e.to_string();
+                }
+            }
+            loop {
+                if stop.load(Ordering::Relaxed) {
+                    let _ = ws.close(None);
+                    let _ = ws.flush();
+                    return;
+                }
+                while let Ok(frame) = outgoing.try_recv() {
+                    if let Err(e) = ws.send(Message::text(frame)) {
+                        break 'session e.to_string();
+                    }
+                }
+                match ws.read() {
+                    Ok(Message::Text(text)) => inner.handle(url, text.as_str()),
+                    Ok(_) => {}
+                    Err(tungstenite::Error::Io(e)) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {}
+                    Err(e) => break 'session e.to_string(),
+                }
+            }
+        };
+        inner.set_status(url, false, Some(error));
+        if !wait(backoff, stop) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_pool_streams_deduplicated_events_and_tracks_ok() {
+        let sk = secp256k1::SecretKey::from_slice(&[11u8; 32]).unwrap();
+        let stored = crate::nostr_event::sign_event_at(1, vec![], "stored", 100, &sk).unwrap();
+        let mut forged = stored.clone();
+        forged.content = "forged".to_string();
+        let mine = crate::nostr_event::create_kind1_event("published", &sk).unwrap();
+
+        // Two relays each send the stored event, one a forgery too, and answer EVENT with OK.
+        let mut relays = Vec::new();
+        for (events, accept) in [(vec![stored.clone(), forged], true), (vec![stored.clone()], false)] {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            relays.push(format!("ws://{}", listener.local_addr().unwrap()));
+            thread::spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                let mut ws = tungstenite::accept(stream).unwrap();
+                while let Ok(msg) = ws.read() {
+                    let Ok(frame) = serde_json::from_str::<Vec<serde_json::Value>>(msg.to_text().unwrap_or("")) else { continue };
+                    match frame[0].as_str() {
+                        Some("REQ") => {
+                            for e in &events {
+                                ws.send(Message::text(serde_json::json!(["EVENT", frame[1], e]).to_string())).unwrap();
+                            }
+                            ws.send(Message::text(serde_json::json!(["EOSE", frame[1]]).to_string())).unwrap();
+                        }
+                        Some("EVENT") => {
+                            ws.send(Message::text(serde_json::json!(["OK", frame[1]["id"], accept, if accept { "" } else { "blocked: test" }]).to_string())).unwrap();
+                        }
+                        _ => {}
+                    }
+                }
+            });
+        }
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let pool = RelayPool::new(move |m| sink.lock().unwrap().push(m));
+        pool.connect(&relays).unwrap();
+        let sub = pool.subscribe(Some("feed".to_string()), vec![Filter::default()]).unwrap();
+        assert_eq!(sub, "feed");
+
+        let out = pool.publish(&mine, None, Duration::from_secs(3)).unwrap();
+        let mut statuses: Vec<_> = out.iter().map(|o| (o.status.as_str(), o.message.as_deref())).collect();
+        statuses.sort();
+        assert_eq!(statuses, vec![("ok", None), ("rejected", Some("blocked: test"))]);
+        let later = crate::nostr_event::create_kind1_event("published later", &sk).unwrap();
+        let batch = pool.publish_many(&[mine.clone(), later], None, Duration::from_secs(3)).unwrap();
+        assert!(batch.iter().all(|out| out.len() == 2 && out.iter().any(|o| o.status == "ok")));
+
+        let until = Instant::now() + Duration::from_secs(3);
+        let count = |pred: &dyn Fn(&RelayMessage) -> bool| seen.lock().unwrap().iter().filter(|m| pred(m)).count();
+        while count(&|m| matches!(m, RelayMessage::Eose { .. })) < 2 && Instant::now() < until {
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(count(&|m| matches!(m, RelayMessage::Event { event, .. } if *event == stored)), 1);
+        assert_eq!(count(&|m| matches!(m, RelayMessage::Event { .. })), 1);
+        assert!(pool.status().iter().all(|s| s.connected));
+        assert!(pool.unsubscribe("feed") && !pool.unsubscribe("feed"));
+        assert_eq!(pool.disconnect(None), 2);
+        assert!(pool.status().is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+// Safe mode for decoded bundles: a hostile image can carry megabyte-long notes, thousands of tags
+// or control characters that hang the renderer. Bundles are cleaned here before they cross IPC.
+// Works on raw JSON (not NostrEvent) so bundles the frontend tolerates, e.g. kind as a string,
+// still pass. Edited events no longer match their signature; they are for display only.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const MAX_EVENTS: usize = 5000;
+pub const MAX_CONTENT_CHARS: usize = 64 * 1024;
+pub const MAX_TAGS: usize = 256;
+pub const MAX_TAG_VALUES: usize = 16;
+pub const MAX_TAG_VALUE_CHARS: usize = 1024;
+
+/// What was changed in one event.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SanitizeNote {
+    /// Position in the bundle's events array.
+    pub index: usize,
+    pub id: Option<String>,
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SanitizedBundle {
+    /// The bundle with oversized or unsafe fields cleaned.
+    pub bundle: Value,
+    pub notes: Vec<SanitizeNote>,
+    /// Events dropped: past MAX_EVENTS or not JSON objects.
+    pub dropped_events: usize,
+}
+
+/// C0/C1 controls other than tab and newlines, plus bidi overrides/isolates that reorder text.
+fn is_unsafe_char(c: char) -> bool {
+    (c.is_control() && !matches!(c, '\n' | '\t' | '\r')) || matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Remove `<script ...>...</script>` blocks (case-insensitive; an unclosed one runs to the end).
+fn strip_scripts(s: &str) -> (String, usize) {
+    let lower = s.to_ascii_lowercase();
+    let mut out = String::with_capacity(s.len());
+    let mut removed = 0;
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find("<script").map(|i| pos + i) {
+        out.push_str(&s[pos..start]);
+        removed += 1;
+        pos = match lower[start..].find("</script") {
+            Some(end) => {
+                let close = start + end;
+                lower[close..].find('>').map_or(s.len(), |i| close + i + 1)
+            }
+            None => s.len(),
+        };
+    }
+    out.push_str(&s[pos..]);
+    (out, removed)
+}
+
+/// Clean one string field: strip unsafe characters, then cut to `max_chars`.
+fn clean_text(s: &str, max_chars: usize, field: &str, notes: &mut Vec<String>) -> String {
+    let mut out: String = s.chars().filter(|&c| !is_unsafe_char(c)).collect();
+    let kept = out.chars().count();
+    let removed = s.chars().count() - kept;
+    if removed > 0 {
+        notes.push(format!("{}: removed {} control character(s)", field, removed));
+    }
+    if let Some((cut, _)) = out.char_indices().nth(max_chars) {
+        notes.push(format!("{}: truncated from {} to {} characters", field, kept, max_chars));
+        out.truncate(cut);
+    }
+    out
+}
+
+fn sanitize_event(event: &mut serde_json::Map<String, Value>) -> Vec<String> {
+    let mut notes = Vec::new();
+    if let Some(Value::String(content)) = event.get("content") {
+        let (content, scripts) = strip_scripts(content);
+        if scripts > 0 {
+            notes.push(format!("content: removed {} script element(s)", scripts));
+        }
+        let content = clean_text(&content, MAX_CONTENT_CHARS, "content", &mut notes);
+        event.insert("content".to_string(), Value::String(content));
+    }
+    if let Some(Value::Array(tags)) = event.get_mut("tags") {
+        if tags.len() > MAX_TAGS {
+            notes.push(format!("tags: kept {} of {}", MAX_TAGS, tags.len()));
+            tags.truncate(MAX_TAGS);
+        }
+        for tag in tags.iter_mut() {
+            let Value::Array(values) = tag else { continue };
+            if values.len() > MAX_TAG_VALUES {
+                notes.push(format!("tags: cut a tag from {} to {} values", values.len(), MAX_TAG_VALUES));
+                values.truncate(MAX_TAG_VALUES);
+            }
+            for v in values.iter_mut() {
+                if let Value::String(s) = v {
+                    *s = clean_text(s, MAX_TAG_VALUE_CHARS, "tag value", &mut notes);
+                }
+            }
+        }
+    }
+    notes
+}
+
+/// Sanitize a bundle (`{ "version": 1, "events": [...] }` or a bare events array).
+pub fn sanitize_bundle(mut bundle: Value) -> SanitizedBundle {
+    let mut notes = Vec::new();
+    let mut dropped_events = 0;
+    let events = match &mut bundle {
+        Value::Array(events) => Some(events),
+        Value::Object(obj) => match obj.get_mut("events") {
+            Some(Value::Array(events)) => Some(events),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(events) = events {
+        if events.len() > MAX_EVENTS {
+            dropped_events += events.len() - MAX_EVENTS;
+            events.truncate(MAX_EVENTS);
+        }
+        let before = events.len();
+        events.retain(Value::is_object);
+        dropped_events += before - events.len();
+        for (index, event) in events.iter_mut().enumerate() {
+            let Value::Object(event) = event else { continue };
+            let event_notes = sanitize_event(event);
+            if !event_notes.is_empty() {
+                notes.push(SanitizeNote {
+                    index,
+                    id: event.get("id").and_then(Value::as_str).map(str::to_string),
+                    notes: event_notes,
+                });
+            }
+        }
+    }
+    SanitizedBundle {
+        bundle,
+        notes,
+        dropped_events,
+    }
+}
+
+/// Sanitize bundle JSON text; returns the cleaned JSON and what changed.
+pub fn sanitize_json(json: &str) -> Result<(String, SanitizedBundle), String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("Invalid bundle JSON: {}", e))?;
+    let report = sanitize_bundle(value);
+    let text = serde_json::to_string(&report.bundle).map_err(|e| e.to_string())?;
+    Ok((text, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_hostile_bundle() {
+        let long = "a".repeat(MAX_CONTENT_CHARS + 10);
+        let tags: Vec<Value> = (0..MAX_TAGS + 5).map(|i| serde_json::json!(["t", format!("tag{}", i)])).collect();
+        let bundle = serde_json::json!({
+            "version": 1,
+            "events": [
+                { "id": "ok", "content": "hello\nworld", "tags": [["p", "abc"]] },
+                { "id": "bad", "content": format!("hi\u{0}\u{202E}<SCRIPT>alert(1)</script>there{}", long), "tags": tags },
+                "not an event"
+            ]
+        });
+        let out = sanitize_bundle(bundle);
+        assert_eq!(out.dropped_events, 1);
+        assert_eq!(out.notes.len(), 1);
+        assert_eq!(out.notes[0].id.as_deref(), Some("bad"));
+        let events = out.bundle["events"].as_array().unwrap();
+        assert_eq!(events[0]["content"], "hello\nworld");
+        let content = events[1]["content"].as_str().unwrap();
+        assert!(content.starts_with("hithereaaa"));
+        assert_eq!(content.chars().count(), MAX_CONTENT_CHARS);
+        assert_eq!(events[1]["tags"].as_array().unwrap().len(), MAX_TAGS);
+    }
+}
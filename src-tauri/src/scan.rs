@@ -0,0 +1,375 @@
+// Method-ordered decode with timing: try each codec in turn, record how long each took per image,
+// and aggregate the timings over a batch so the method order can be tuned from real scans.
+// Each decoder loads the image itself, so a method's time includes decoding the file.
+// An optional ML prefilter (prefilter.rs) rules images out before any decoder runs.
+// `detect_all` is the fast audit variant: DWT by its frame header alone (stego::detect), the
+// cheap-to-reject codecs by decoding, images in parallel.
+
+use schemars::JsonSchema;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::error::StegoError;
+use crate::prefilter::Prefilter;
+use crate::stego_key::StegoKey;
+use crate::{external, stego, stego_dot, stego_qim, stego_synth};
+
+/// QIM rejects anything but a baseline JPEG from the header; synth last: it rejects ordinary
+/// images cheaply (grid size and magic check).
+pub const DEFAULT_ORDER: &[&str] = &["dwt", "dot", "qim", "synth"];
+/// Cheapest rejection first, from scans of covers without a payload: QIM stops at the JPEG
+/// header, synth at its grid check, dot reads one tile grid, DWT slides windows of every tile size.
+pub const COST_ORDER: &[&str] = &["qim", "synth", "dot", "dwt"];
+/// Codecs that take a stego key, in the order a keyed decode tries them.
+pub const KEYED_METHODS: &[&str] = &["dwt", "dot"];
+/// Codecs `detect` decodes after the DWT header check: the ones COST_ORDER puts before DWT.
+const DETECT_DECODE_ORDER: &[&str] = &["qim", "synth", "dot"];
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "gif", "webp"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MethodTiming {
+    pub method: String,
+    pub micros: u64,
+    pub found: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImageScan {
+    pub path: String,
+    /// Method that found a payload, if any.
+    pub method: Option<String>,
+    pub payload_bytes: Option<usize>,
+    pub error: Option<String>,
+    /// Methods tried, in order, up to and including the one that found the payload.
+    pub timings: Vec<MethodTiming>,
+    /// Prefilter probability of a payload, when a prefilter ran on this image.
+    pub prefilter: Option<f32>,
+    /// The prefilter ruled the image out, so no method was tried.
+    pub skipped: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct MethodStats {
+    pub method: String,
+    /// Images this method was tried on.
+    pub tried: usize,
+    /// Images where it found the payload.
+    pub found: usize,
+    pub total_micros: u64,
+    pub mean_micros: u64,
+    pub max_micros: u64,
+    /// Share of the whole scan's decode time, in percent.
+    pub share_percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScanSummary {
+    pub images: usize,
+    pub found: usize,
+    /// Images the prefilter ruled out.
+    pub skipped: usize,
+    pub total_micros: u64,
+    /// Per-method totals in the order the methods were tried.
+    pub methods: Vec<MethodStats>,
+    pub results: Vec<ImageScan>,
+}
+
+/// One image of a `detect_all` audit.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Detected {
+    pub path: String,
+    /// Method whose payload was found, if any.
+    pub method: Option<String>,
+    /// Payload size: as stored (the DWT header's length) for dwt, decoded for the other methods.
+    pub payload_bytes: Option<usize>,
+    /// stego::Detection confidence for dwt; 1 for a payload that decoded.
+    pub confidence: Option<f64>,
+    /// Why the image could not be read (no payload is not an error).
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DetectReport {
+    pub images: usize,
+    pub found: usize,
+    /// Wall-clock time of the whole audit.
+    pub total_micros: u64,
+    pub results: Vec<Detected>,
+}
+
+/// Decode with one method: a built-in codec name or an external codec.
+pub fn decode_with(method: &str, path: &Path) -> Result<Vec<u8>, String> {
+    decode_with_key(method, path, None)
+}
+
+/// `decode_with` for images embedded with a stego key (KEYED_METHODS only).
+pub fn decode_with_key(method: &str, path: &Path, key: Option<&StegoKey>) -> Result<Vec<u8>, String> {
+    match method {
+        "dwt" => stego::decode_with_key(path, key).map_err(String::from),
+        "dot" => stego_dot::decode_with_key(path, key).map_err(String::from),
+        other if key.is_some() => Err(format!("{} does not take a stego key", other)),
+        "qim" => stego_qim::decode(path),
+        "synth" => stego_synth::decode(path),
+        other => match external::find(other) {
+            Some(codec) => external::decode(codec, path),
+            None => Err(format!("unknown method {}", other)),
+        },
+    }
+}
+
+/// DEFAULT_ORDER followed by the external codecs registered for auto-detection.
+pub fn default_order() -> Vec<&'static str> {
+    let mut order = DEFAULT_ORDER.to_vec();
+    order.extend(external::codecs().iter().filter(|c| c.auto_detect).map(|c| c.name.as_str()));
+    order
+}
+
+/// Try `order` until one method decodes; returns the payload with the winning method, plus the
+/// timing of every method tried. The error is the first method's (the most likely codec).
+#[allow(clippy::type_complexity)]
+pub fn decode_timed(path: &Path, order: &[&str]) -> (Result<(Vec<u8>, String), String>, Vec<MethodTiming>) {
+    decode_timed_with_key(path, order, None)
+}
+
+/// `decode_timed` with a stego key for the keyed codecs.
+#[allow(clippy::type_complexity)]
+pub fn decode_timed_with_key(
+    path: &Path,
+    order: &[&str],
+    key: Option<&StegoKey>,
+) -> (Result<(Vec<u8>, String), String>, Vec<MethodTiming>) {
+    let mut timings = Vec::new();
+    let mut first_err = None;
+    for &method in order {
+        let started = Instant::now();
+        let result = decode_with_key(method, path, key);
+        timings.push(MethodTiming {
+            method: method.to_string(),
+            micros: started.elapsed().as_micros() as u64,
+            found: result.is_ok(),
+        });
+        match result {
+            Ok(payload) => return (Ok((payload, method.to_string())), timings),
+            Err(e) => {
+                first_err.get_or_insert(e);
+            }
+        }
+    }
+    (Err(first_err.unwrap_or_else(|| "No decode methods given".to_string())), timings)
+}
+
+/// COST_ORDER followed by the auto-detect external codecs (each call spawns a process).
+pub fn cost_order() -> Vec<&'static str> {
+    let mut order = COST_ORDER.to_vec();
+    order.extend(external::codecs().iter().filter(|c| c.auto_detect).map(|c| c.name.as_str()));
+    order
+}
+
+/// Try every registered codec, cheapest first (cost_order), and return the payload with the
+/// method that found it.
+pub fn detect_and_decode(image_path: &Path) -> Result<(Vec<u8>, String), StegoError> {
+    let order = cost_order();
+    decode_timed(image_path, &order)
+        .0
+        .map_err(|_| StegoError::NotStego(format!("No Stegstr payload found (tried {})", order.join(", "))))
+}
+
+/// DWT decode, falling back to the dot codec, QIM, synth, then external codecs.
+pub fn decode_any(path: &Path) -> Result<Vec<u8>, String> {
+    decode_timed(path, &default_order()).0.map(|(payload, _)| payload)
+}
+
+/// Files to scan: each path as given, directories expanded (non-recursive) to their images.
+pub fn expand_paths(paths: &[String]) -> Result<Vec<PathBuf>, String> {
+    expand_paths_with(paths, false)
+}
+
+/// `expand_paths`; with `recursive`, subdirectories too (depth first, sorted, symlinked
+/// directories not followed).
+pub fn expand_paths_with(paths: &[String], recursive: bool) -> Result<Vec<PathBuf>, String> {
+    let mut out = Vec::new();
+    for p in paths.iter().map(PathBuf::from) {
+        if p.is_dir() {
+            expand_dir(&p, recursive, &mut out)?;
+        } else {
+            out.push(p);
+        }
+    }
+    Ok(out)
+}
+
+fn expand_dir(dir: &Path, recursive: bool, out: &mut Vec<PathBuf>) -> Result<(), String> {
+    let mut entries: Vec<std::fs::DirEntry> = std::fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir.display(), e))?
+        .filter_map(|e| e.ok())
+        .collect();
+    entries.sort_by_key(|e| e.path());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            if recursive {
+                expand_dir(&path, true, out)?;
+            }
+        } else if path
+            .extension()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| IMAGE_EXTENSIONS.contains(&x.to_ascii_lowercase().as_str()))
+        {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Fast payload check of one image: the DWT frame header (no payload decode), else a decode with
+/// each of DETECT_DECODE_ORDER. Keyed and QIM-strength DWT embeds and external codecs are not
+/// tried.
+pub fn detect(path: &Path) -> Detected {
+    let mut out = Detected {
+        path: path.to_string_lossy().to_string(),
+        method: None,
+        payload_bytes: None,
+        confidence: None,
+        error: None,
+    };
+    match stego::detect(path) {
+        Ok(Some(d)) => {
+            out.method = Some("dwt".to_string());
+            out.payload_bytes = Some(d.payload_bytes);
+            out.confidence = Some(d.confidence);
+            return out;
+        }
+        Ok(None) => {}
+        Err(e) => {
+            out.error = Some(e.to_string());
+            return out;
+        }
+    }
+    if let Ok((payload, method)) = decode_timed(path, DETECT_DECODE_ORDER).0 {
+        out.method = Some(method);
+        out.payload_bytes = Some(payload.len());
+        out.confidence = Some(1.0);
+    }
+    out
+}
+
+/// `detect` on every image in parallel; results keep the order of `paths`.
+pub fn detect_all(paths: &[PathBuf]) -> DetectReport {
+    let started = Instant::now();
+    let results: Vec<Detected> = paths.par_iter().map(|p| detect(p)).collect();
+    DetectReport {
+        images: results.len(),
+        found: results.iter().filter(|r| r.method.is_some()).count(),
+        total_micros: started.elapsed().as_micros() as u64,
+        results,
+    }
+}
+
+/// Decode every image with `order`, keeping per-image results and per-method timing totals.
+/// With a prefilter, images it rates below its threshold are skipped; images it cannot read are
+/// decoded as usual.
+pub fn scan(paths: &[PathBuf], order: &[&str], prefilter: Option<&Prefilter>) -> ScanSummary {
+    let mut methods: Vec<MethodStats> = order
+        .iter()
+        .map(|m| MethodStats {
+            method: m.to_string(),
+            ..Default::default()
+        })
+        .collect();
+    let mut results = Vec::with_capacity(paths.len());
+    for path in paths {
+        let prediction = prefilter.and_then(|pf| pf.predict(path).ok());
+        if prediction.is_some_and(|p| !p.likely_stego) {
+            results.push(ImageScan {
+                path: path.to_string_lossy().to_string(),
+                method: None,
+                payload_bytes: None,
+                error: None,
+                timings: Vec::new(),
+                prefilter: prediction.map(|p| p.probability),
+                skipped: true,
+            });
+            continue;
+        }
+        let (result, timings) = decode_timed(path, order);
+        for t in &timings {
+            if let Some(stats) = methods.iter_mut().find(|s| s.method == t.method) {
+                stats.tried += 1;
+                stats.found += t.found as usize;
+                stats.total_micros += t.micros;
+                stats.max_micros = stats.max_micros.max(t.micros);
+            }
+        }
+        let (method, payload_bytes, error) = match result {
+            Ok((payload, method)) => (Some(method), Some(payload.len()), None),
+            Err(e) => (None, None, Some(e)),
+        };
+        results.push(ImageScan {
+            path: path.to_string_lossy().to_string(),
+            method,
+            payload_bytes,
+            error,
+            timings,
+            prefilter: prediction.map(|p| p.probability),
+            skipped: false,
+        });
+    }
+    let total_micros: u64 = methods.iter().map(|s| s.total_micros).sum();
+    for s in &mut methods {
+        s.mean_micros = if s.tried > 0 { s.total_micros / s.tried as u64 } else { 0 };
+        s.share_percent = crate::units::percent(s.total_micros, total_micros);
+    }
+    ScanSummary {
+        images: results.len(),
+        found: results.iter().filter(|r| r.method.is_some()).count(),
+        skipped: results.iter().filter(|r| r.skipped).count(),
+        total_micros,
+        methods,
+        results,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_aggregates_method_timings() {
+        let dir = std::env::temp_dir().join(format!("stegstr_scan_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cover = dir.join("cover.png");
+        stegstr_core::test_util::noisy_cover(256, 256, 0x9E37_79B9).save(&cover).unwrap();
+        std::fs::write(dir.join("dwt.png"), stego::encode(&cover, b"scan me", &stego::EncodeOptions::default()).unwrap()).unwrap();
+        std::fs::write(dir.join("dot.png"), stego_dot::encode(&cover, b"scan me").unwrap()).unwrap();
+
+        let paths = expand_paths(&[dir.to_string_lossy().to_string()]).unwrap();
+        let summary = scan(&paths, DEFAULT_ORDER, None);
+        assert_eq!(summary.images, 3);
+        assert_eq!(summary.found, 2);
+        let dwt = &summary.methods[0];
+        let dot = &summary.methods[1];
+        assert_eq!((dwt.tried, dwt.found), (3, 1));
+        assert_eq!((dot.tried, dot.found), (2, 1));
+        assert_eq!(summary.total_micros, summary.methods.iter().map(|m| m.total_micros).sum::<u64>());
+        for name in ["dwt", "dot"] {
+            let (payload, method) = detect_and_decode(&dir.join(format!("{}.png", name))).unwrap();
+            assert_eq!((payload.as_slice(), method.as_str()), (&b"scan me"[..], name));
+        }
+        assert!(detect_and_decode(&cover).is_err());
+
+        // Fast audit, with the dwt image moved into a subdirectory only a recursive walk reaches.
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::rename(dir.join("dwt.png"), dir.join("sub").join("dwt.png")).unwrap();
+        let arg = [dir.to_string_lossy().to_string()];
+        assert_eq!(expand_paths(&arg).unwrap().len(), 2);
+        let report = detect_all(&expand_paths_with(&arg, true).unwrap());
+        assert_eq!((report.images, report.found), (3, 2));
+        let methods: Vec<Option<&str>> = report.results.iter().map(|r| r.method.as_deref()).collect();
+        assert_eq!(methods, [None, Some("dot"), Some("dwt")]);
+        assert!(report.results.iter().all(|r| r.error.is_none()));
+        assert_eq!(report.results[1].payload_bytes, Some(b"scan me".len()));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,186 @@
+// "Send to contact": sign -> bundle -> encrypt for recipient -> capacity plan -> embed -> optional upload.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{nostr_codes, nostr_event, payload_header, stego_crypto, targets};
+
+pub struct SendRequest<'a> {
+    pub content: &'a str,
+    /// Recipient npub or hex pubkey.
+    pub recipient: &'a str,
+    pub cover_path: &'a Path,
+    pub output_path: &'a Path,
+    pub sender_sk: &'a secp256k1::SecretKey,
+    pub upload: bool,
+    /// Record a signed embedding receipt in the local receipts store.
+    pub receipt: bool,
+    /// Sharing target (size/codec preset); None tries dwt, then dot, at full size.
+    pub target: Option<&'static targets::Target>,
+    /// Replace an existing output file instead of failing.
+    pub overwrite: bool,
+    /// Also add the receipt to the embedded bundle (with `receipt`).
+    pub receipt_in_image: bool,
+}
+
+/// IPC options for send_to_contact.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SendOptions {
+    pub content: String,
+    /// Recipient npub or 64-char hex pubkey.
+    pub recipient: String,
+    pub cover_path: String,
+    pub output_path: String,
+    /// Sender secret key, 64-char hex or nsec.
+    #[schemars(regex(pattern = r"^([0-9a-fA-F]{64}|nsec1[02-9ac-hj-np-z]{58})$"))]
+    pub privkey_hex: String,
+    #[serde(default)]
+    pub upload: Option<bool>,
+    #[serde(default)]
+    pub receipt: Option<bool>,
+    /// Sharing target name from list_targets (e.g. "email").
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Replace an existing output file (default false: it is an error).
+    #[serde(default)]
+    pub overwrite: Option<bool>,
+    /// Also add the receipt to the embedded bundle (implies receipt).
+    #[serde(default)]
+    pub receipt_in_image: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SendResult {
+    pub ok: bool,
+    pub event_id: Option<String>,
+    pub sender_pubkey: Option<String>,
+    pub sender_npub: Option<String>,
+    pub recipient_pubkey: Option<String>,
+    pub recipient_npub: Option<String>,
+    /// Codec chosen by the capacity plan: "dwt" or "dot".
+    pub method: Option<String>,
+    pub payload_bytes: Option<usize>,
+    /// Payload size against the chosen codec's capacity (of the cover as the target prepared it).
+    pub capacity: Option<crate::units::CapacityInfo>,
+    pub path: Option<String>,
+    pub url: Option<String>,
+    pub receipt_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Run the whole pipeline. Failures after signing still report the event id in the result.
+pub fn send(req: &SendRequest) -> SendResult {
+    let mut result = SendResult::default();
+    if let Err(e) = send_inner(req, &mut result) {
+        result.error = Some(e);
+    }
+    result
+}
+
+fn send_inner(req: &SendRequest, result: &mut SendResult) -> Result<(), String> {
+    let recipient = nostr_codes::parse_pubkey(req.recipient)?;
+    result.recipient_npub = nostr_codes::npub_from_hex(&recipient).ok();
+    result.recipient_pubkey = Some(recipient.clone());
+
+    let event = nostr_event::create_kind1_event(req.content, req.sender_sk)?;
+    result.event_id = Some(event.id.clone());
+    result.sender_npub = nostr_codes::npub_from_hex(&event.pubkey).ok();
+    result.sender_pubkey = Some(event.pubkey.clone());
+    let bundle = nostr_event::Bundle::new(vec![event.clone()]);
+    let mut json = serde_json::to_string(&bundle).map_err(|e| e.to_string())?;
+    // The receipt hashes the bundle as signed, then rides along inside it (encrypted with it).
+    let mut embedded = None;
+    if req.receipt_in_image {
+        let method = req.target.map_or("dwt", |t| t.methods[0]);
+        let (with_receipt, receipt) = crate::receipts::embed_in_bundle(json.as_bytes(), req.cover_path, method, req.sender_sk)?;
+        json = String::from_utf8(with_receipt).map_err(|e| e.to_string())?;
+        embedded = Some(receipt);
+    }
+
+    // Sender is included so the image stays readable by its author.
+    let mut recipients = vec![recipient];
+    if !recipients.contains(&event.pubkey) {
+        recipients.push(event.pubkey.clone());
+    }
+    let payload = stego_crypto::encrypt_for_recipients(&json, req.sender_sk, &recipients, crate::bundle::BundleFormat::default())?;
+    let payload = payload_header::PayloadHeader::new(payload_header::MIME_ENCRYPTED).write(&payload)?;
+    result.payload_bytes = Some(payload.len());
+
+    let (png, method) = targets::embed(req.cover_path, &payload, req.target, None, None)?;
+    result.method = Some(method.to_string());
+    result.capacity = targets::payload_capacity(req.cover_path, method, req.target, &payload).ok();
+    crate::atomic_write::write(req.output_path, &png, req.overwrite)?;
+    result.path = Some(req.output_path.to_string_lossy().to_string());
+
+    if let Some(receipt) = embedded {
+        crate::receipts::append_receipt(&receipt)?;
+        result.receipt_id = Some(receipt.id);
+    } else if req.receipt {
+        let receipt = crate::receipts::record_embed(&payload, req.cover_path, req.output_path, method, req.sender_sk)?;
+        result.receipt_id = Some(receipt.id);
+    }
+
+    if req.upload {
+        result.url = Some(crate::upload::upload_media(req.output_path)?);
+    }
+    result.ok = true;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_roundtrip_opens_for_recipient_and_sender() {
+        let dir = std::env::temp_dir().join(format!("stegstr_send_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cover = dir.join("cover.png");
+        stegstr_core::test_util::noisy_cover(256, 256, 0x5EED_0772).save(&cover).unwrap();
+        let output = dir.join("out.png");
+        let sender = secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let recipient = secp256k1::SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let outsider = secp256k1::SecretKey::from_slice(&[0x33; 32]).unwrap();
+        let npub = nostr_codes::npub_from_hex(&nostr_event::pubkey_hex(&recipient)).unwrap();
+
+        let req = SendRequest {
+            content: "meet at noon",
+            recipient: &npub,
+            cover_path: &cover,
+            output_path: &output,
+            sender_sk: &sender,
+            upload: false,
+            receipt: false,
+            target: None,
+            overwrite: false,
+            receipt_in_image: false,
+        };
+        let result = send(&req);
+        assert!(result.ok, "{:?}", result.error);
+        assert_eq!(result.method.as_deref(), Some("dwt"));
+        assert_eq!(result.recipient_pubkey, Some(nostr_event::pubkey_hex(&recipient)));
+        assert_eq!(result.path, Some(output.to_string_lossy().to_string()));
+
+        let (stored, method) = crate::scan::detect_and_decode(&output).unwrap();
+        assert_eq!(method, "dwt");
+        let (_, payload) = payload_header::read(&stored).unwrap();
+        // The sender is added as a recipient so the author can reopen the image.
+        for sk in [&recipient, &sender] {
+            let json = stego_crypto::decrypt_payload(&payload, Some(sk)).unwrap();
+            let bundle: nostr_event::Bundle = serde_json::from_str(&json).unwrap();
+            let event = &bundle.events[0];
+            assert_eq!(Some(&event.id), result.event_id.as_ref());
+            assert_eq!(event.content, nostr_event::ensure_stegstr_suffix("meet at noon"));
+            nostr_event::verify_event(event).unwrap();
+        }
+        assert!(stego_crypto::decrypt_payload(&payload, Some(&outsider)).is_err());
+
+        let again = send(&req);
+        assert!(again.error.as_deref().is_some_and(|e| e.contains("already exists")), "{:?}", again.error);
+        let bad = send(&SendRequest { recipient: "npub1notakey", ..req });
+        assert!(!bad.ok && bad.error.is_some() && bad.event_id.is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,295 @@
+// Steganalysis self-check: how detectable an image is to the classic LSB estimators, so a user
+// can check a stego image before sharing it.
+// - Chi-square attack (Westfeld & Pfitzmann): LSB replacement equalizes the histogram pairs
+//   (2k, 2k+1); the p-value of the pairs being equal is near 1 for a fully embedded image.
+// - RS analysis (Fridrich, Goljan & Du): flipping LSBs in groups of 4 pixels changes the counts
+//   of regular and singular groups differently for clean and embedded images; the quadratic in
+//   the four counts gives the embedded fraction of pixels.
+// Both run per RGB channel. Stegstr's codecs do not replace LSBs, so photos score low; a high
+// score means the image looks like LSB embedding to these tests (synthetic covers with smooth
+// gradients can trip RS on their own).
+
+use image::metadata::Orientation;
+use image::{ImageDecoder, ImageReader, RgbImage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Pixels per RS group (horizontal runs).
+const GROUP: usize = 4;
+/// RS flipping mask: 0 leaves the pixel, 1 flips it.
+const MASK: [i32; GROUP] = [0, 1, 1, 0];
+/// Scores at or above these read as moderately / highly detectable.
+const MODERATE: f64 = 0.1;
+const HIGH: f64 = 0.3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AnalysisReport {
+    pub width: u32,
+    pub height: u32,
+    /// Chi-square attack p-value per channel (R, G, B); near 1 for LSB-replaced images.
+    pub chi_square: [f64; 3],
+    /// RS estimate of the fraction of pixels carrying LSB-replaced bits per channel (0 to 1).
+    pub rs_estimate: [f64; 3],
+    /// 0 (nothing found) to 1 (certainly detectable): the largest of the estimates.
+    pub score: f64,
+    /// "low", "moderate" or "high".
+    pub verdict: String,
+}
+
+impl AnalysisReport {
+    /// One-line summary, e.g. "Detectability 0.02 (low): chi-square p 0.00/0.00/0.00, RS 1.2%/...".
+    pub fn summary(&self) -> String {
+        let chi: Vec<String> = self.chi_square.iter().map(|p| format!("{:.2}", p)).collect();
+        let rs: Vec<String> = self.rs_estimate.iter().map(|r| format!("{:.1}%", r * 100.0)).collect();
+        format!(
+            "Detectability {:.2} ({}): chi-square p {}, RS estimate {}",
+            self.score,
+            self.verdict,
+            chi.join("/"),
+            rs.join("/")
+        )
+    }
+}
+
+fn load(path: &Path) -> Result<RgbImage, String> {
+    let reader = ImageReader::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut decoder = reader.into_decoder().map_err(|e| format!("{}: {}", path.display(), e))?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = image::DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    img.apply_orientation(orientation);
+    Ok(img.to_rgb8())
+}
+
+/// Analyze the image at `path`.
+pub fn analyze(path: &Path) -> Result<AnalysisReport, String> {
+    Ok(analyze_image(&load(path)?))
+}
+
+pub fn analyze_image(img: &RgbImage) -> AnalysisReport {
+    let (w, h) = img.dimensions();
+    let channel = |c: usize| -> Vec<u8> { img.pixels().map(|p| p[c]).collect() };
+    let mut chi_square = [0.0; 3];
+    let mut rs_estimate = [0.0; 3];
+    for c in 0..3 {
+        let values = channel(c);
+        chi_square[c] = chi_square_p(&values);
+        rs_estimate[c] = rs_rate(&values, w as usize);
+    }
+    let score = chi_square.iter().chain(&rs_estimate).cloned().fold(0.0, f64::max);
+    let verdict = match score {
+        s if s >= HIGH => "high",
+        s if s >= MODERATE => "moderate",
+        _ => "low",
+    };
+    AnalysisReport {
+        width: w,
+        height: h,
+        chi_square,
+        rs_estimate,
+        score,
+        verdict: verdict.to_string(),
+    }
+}
+
+/// Chi-square attack over the whole channel: p-value that pair counts are equal.
+fn chi_square_p(values: &[u8]) -> f64 {
+    let mut hist = [0u64; 256];
+    for &v in values {
+        hist[v as usize] += 1;
+    }
+    let (mut stat, mut dof) = (0.0, 0usize);
+    for k in 0..128 {
+        let (a, b) = (hist[2 * k] as f64, hist[2 * k + 1] as f64);
+        let expected = (a + b) / 2.0;
+        // Sparse pairs make the statistic meaningless.
+        if expected < 5.0 {
+            continue;
+        }
+        stat += (a - expected).powi(2) / expected;
+        dof += 1;
+    }
+    if dof < 2 {
+        return 0.0;
+    }
+    chi_square_sf(stat, (dof - 1) as f64)
+}
+
+/// Sum of absolute neighbour differences: how "noisy" a group is.
+fn smoothness(group: &[i32]) -> i32 {
+    group.windows(2).map(|p| (p[1] - p[0]).abs()).sum()
+}
+
+/// F1 (2k <-> 2k+1) for m = 1, F-1 (2k-1 <-> 2k) for m = -1.
+fn flip(x: i32, m: i32) -> i32 {
+    match m {
+        1 => x ^ 1,
+        -1 => ((x + 1) ^ 1) - 1,
+        _ => x,
+    }
+}
+
+/// (R_M - S_M, R_-M - S_-M) as fractions of the groups, with every value's LSB first flipped
+/// when `flipped`.
+fn rs_counts(values: &[u8], width: usize, flipped: bool) -> (f64, f64) {
+    let (mut rm, mut sm, mut rn, mut sn, mut groups) = (0i64, 0i64, 0i64, 0i64, 0i64);
+    let mut group = [0i32; GROUP];
+    let mut changed = [0i32; GROUP];
+    for row in values.chunks_exact(width) {
+        for chunk in row.chunks_exact(GROUP) {
+            for (g, &v) in group.iter_mut().zip(chunk) {
+                *g = if flipped { (v ^ 1) as i32 } else { v as i32 };
+            }
+            let base = smoothness(&group);
+            for (sign, (r, s)) in [(1, (&mut rm, &mut sm)), (-1, (&mut rn, &mut sn))] {
+                for i in 0..GROUP {
+                    changed[i] = flip(group[i], MASK[i] * sign);
+                }
+                match smoothness(&changed).cmp(&base) {
+                    std::cmp::Ordering::Greater => *r += 1,
+                    std::cmp::Ordering::Less => *s += 1,
+                    std::cmp::Ordering::Equal => {}
+                }
+            }
+            groups += 1;
+        }
+    }
+    if groups == 0 {
+        return (0.0, 0.0);
+    }
+    let g = groups as f64;
+    ((rm - sm) as f64 / g, (rn - sn) as f64 / g)
+}
+
+/// RS estimate of the embedded fraction, clamped to 0..=1.
+fn rs_rate(values: &[u8], width: usize) -> f64 {
+    if width < GROUP {
+        return 0.0;
+    }
+    let (d0, dn0) = rs_counts(values, width, false);
+    let (d1, dn1) = rs_counts(values, width, true);
+    // 2(d1 + d0) z^2 + (dn0 - dn1 - d1 - 3 d0) z + (d0 - dn0) = 0; the root of smaller magnitude.
+    let (a, b, c) = (2.0 * (d1 + d0), dn0 - dn1 - d1 - 3.0 * d0, d0 - dn0);
+    let z = if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return 0.0;
+        }
+        -c / b
+    } else {
+        let disc = b * b - 4.0 * a * c;
+        if disc < 0.0 {
+            return 0.0;
+        }
+        let (r1, r2) = ((-b + disc.sqrt()) / (2.0 * a), (-b - disc.sqrt()) / (2.0 * a));
+        if r1.abs() < r2.abs() {
+            r1
+        } else {
+            r2
+        }
+    };
+    let p = z / (z - 0.5);
+    if p.is_finite() && p > 0.0 {
+        p.min(1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Survival function of the chi-square distribution with `k` degrees of freedom.
+fn chi_square_sf(x: f64, k: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    upper_gamma_regularized(k / 2.0, x / 2.0)
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    // Lanczos approximation (g = 7, n = 9).
+    const C: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series: f64 = C[0] + (1..9).map(|i| C[i] / (x + i as f64)).sum::<f64>();
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// Q(a, x): series below a + 1, continued fraction above (Numerical Recipes gammq).
+fn upper_gamma_regularized(a: f64, x: f64) -> f64 {
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..500 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-14 {
+                break;
+            }
+        }
+        (1.0 - sum * prefix).clamp(0.0, 1.0)
+    } else {
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..500 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-14 {
+                break;
+            }
+        }
+        (prefix * h).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_lsb_replacement_is_detected() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let cover = RgbImage::from_fn(256, 256, |x, y| {
+            let base = 40.0 + 120.0 * ((x as f64 / 40.0).sin() * (y as f64 / 55.0).cos() + 1.0) / 2.0;
+            image::Rgb([0, 1, 2].map(|c| (base + c as f64 * 20.0 + rng.gen_range(-3.0..3.0)) as u8))
+        });
+        let clean = analyze_image(&cover);
+        assert_eq!(clean.verdict, "low", "{}", clean.summary());
+        let mut stego = cover.clone();
+        for p in stego.pixels_mut() {
+            for c in &mut p.0 {
+                if rng.gen_bool(0.5) {
+                    *c = (*c & !1) | rng.gen_range(0..2u8);
+                }
+            }
+        }
+        let report = analyze_image(&stego);
+        assert_eq!(report.verdict, "high", "{}", report.summary());
+        // Half the pixels carry bits; RS estimates that fraction.
+        assert!(report.rs_estimate.iter().all(|&r| (0.3..0.7).contains(&r)));
+        assert!(report.chi_square.iter().all(|&p| p > 0.5));
+    }
+}
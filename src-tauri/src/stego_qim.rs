@@ -0,0 +1,313 @@
+// QIM (quantization index modulation) on JPEG DCT coefficients, for channels that recompress
+// uploads (WhatsApp, Instagram, ...). Native port of channel_simulator's encode_dct_qim /
+// decode_dct_qim; images from either side decode on the other.
+// The cover is resized to at most 1080 px wide and compressed at quality 75 (4:4:4). Each bit is
+// repeated 5 times and written into the first 24 AC coefficients of every luma block by moving
+// the coefficient to the nearest cell*DELTA -/+ 4. Frame: u16 codeword length + RS(nsym 128) of
+// MAGIC + u32 payload length + payload. Low-confidence bytes are passed to RS as erasures.
+// The lattice is measured in quality-75 quantization steps: a file at another quality (written by
+// `encode_with_quality`, or recompressed by a platform) has its coefficients rescaled by the ratio
+// of its luma table to the quality-75 one, which is the identity for quality 75 itself.
+// `Strength::STRONG` (DELTA 20, 7 repeats) trades capacity for margin on harsher channels; the
+// decoder tries each strength in turn.
+
+use image::imageops::FilterType;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::jpeg_dct::{DctImage, ZIGZAG};
+use crate::reed_solomon;
+
+const MAGIC: &[u8] = b"STEGSTR";
+const LENGTH_BYTES: usize = 4;
+const RS_NSYM: usize = 128;
+const EMBED_QUALITY: u8 = 75;
+/// Lowest `encode_with_quality` quality: coarser steps than this round the embedded values off
+/// the lattice before any recompression.
+pub const MIN_QUALITY: u8 = 50;
+pub const DEFAULT_QUALITY: u8 = EMBED_QUALITY;
+pub const MAX_WIDTH: u32 = 1080;
+/// AC coefficients used per block (zigzag positions 1..=24).
+const AC_PER_BLOCK: usize = 24;
+/// Largest magnitude baseline Huffman tables can code for an AC coefficient.
+const MAX_COEFF: i32 = 1023;
+
+/// Lattice spacing and bit repetition. STANDARD is the channel_simulator format.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub struct Strength {
+    /// Lattice cell size in quality-75 quantization steps.
+    pub delta: f64,
+    /// Distance of the embedded levels from the cell centre.
+    pub offset: f64,
+    /// Coefficients per bit (majority vote).
+    pub repeat: usize,
+}
+
+impl Strength {
+    pub const STANDARD: Strength = Strength { delta: 14.0, offset: 4.0, repeat: 5 };
+    pub const STRONG: Strength = Strength { delta: 20.0, offset: 6.0, repeat: 7 };
+    /// Tried in this order when decoding.
+    pub const ALL: [Strength; 2] = [Strength::STANDARD, Strength::STRONG];
+
+    /// Bytes whose weakest bit's average margin is below this are treated as erasures.
+    fn erasure_margin(&self) -> f64 {
+        self.delta / 6.0
+    }
+}
+
+/// How `encode_with_options` writes the JPEG.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodeOptions {
+    /// JPEG quality, MIN_QUALITY..=100.
+    pub quality: u8,
+    pub strength: Strength,
+    /// Wider covers are downscaled to this width first.
+    pub max_width: u32,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            quality: DEFAULT_QUALITY,
+            strength: Strength::STANDARD,
+            max_width: MAX_WIDTH,
+        }
+    }
+}
+
+/// Natural-order coefficient positions in the embedding stream: block rows, block columns, then
+/// AC position.
+fn stream(dct: &DctImage) -> impl Iterator<Item = (usize, usize, usize)> {
+    let (bw, bh) = (dct.luma().blocks_w, dct.luma().blocks_h);
+    (0..bh).flat_map(move |by| (0..bw).flat_map(move |bx| (1..=AC_PER_BLOCK).map(move |zi| (bx, by, ZIGZAG[zi]))))
+}
+
+/// Per-coefficient factor from `dct`'s luma steps to quality-75 steps (natural order).
+fn lattice_scale(dct: &DctImage) -> Result<[f64; 64], String> {
+    let table = dct.luma_qtable().ok_or("JPEG has no luma quantization table")?;
+    let reference = crate::jpeg_dct::quality_table(&crate::jpeg_dct::LUMA_QUANT, EMBED_QUALITY);
+    Ok(std::array::from_fn(|n| table[n] as f64 / reference[n] as f64))
+}
+
+/// `c` (in steps scaled by `scale` against quality 75) moved onto the level for `bit`.
+fn embed_value(c: i32, bit: bool, scale: f64, strength: &Strength) -> i32 {
+    let z = c as f64 * scale;
+    let cell = (z / strength.delta).round_ties_even() * strength.delta;
+    let y = if bit { cell + strength.offset } else { cell - strength.offset };
+    ((y / scale).round() as i32).clamp(-MAX_COEFF, MAX_COEFF)
+}
+
+/// Nearest reconstruction level of `z` (quality-75 steps): (bit, distance margin between the two
+/// levels).
+fn detect(z: f64, delta: f64) -> (bool, f64) {
+    let cell = (z / delta).round_ties_even() * delta;
+    let d0 = (z - (cell - delta / 4.0)).abs();
+    let d1 = (z - (cell + delta / 4.0)).abs();
+    (d0 > d1, (d0 - d1).abs())
+}
+
+fn to_bits(data: &[u8]) -> Vec<bool> {
+    data.iter().flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 == 1)).collect()
+}
+
+fn from_bits(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8).map(|c| c.iter().fold(0u8, |acc, &b| (acc << 1) | b as u8)).collect()
+}
+
+/// Cover size after the pre-resize to `max_width`.
+fn target_size(width: u32, height: u32, max_width: u32) -> (u32, u32) {
+    if width <= max_width {
+        return (width, height);
+    }
+    let h = ((height as f64 * max_width as f64 / width as f64).round() as u32).max(1);
+    (max_width, h)
+}
+
+/// Payload bytes that fit in an image of `width` x `height` after the pre-resize to `max_width`.
+pub fn capacity_at(width: u32, height: u32, max_width: u32, strength: &Strength) -> usize {
+    let (w, h) = target_size(width, height, max_width);
+    let coeffs = (w as usize).div_ceil(8) * (h as usize).div_ceil(8) * AC_PER_BLOCK;
+    let codeword = (coeffs / strength.repeat / 8).saturating_sub(2).min(u16::MAX as usize);
+    let data = codeword / reed_solomon::BLOCK * (reed_solomon::BLOCK - RS_NSYM) + (codeword % reed_solomon::BLOCK).saturating_sub(RS_NSYM);
+    data.saturating_sub(MAGIC.len() + LENGTH_BYTES)
+}
+
+/// Largest payload `cover` can carry.
+pub fn max_payload_bytes(cover: &Path) -> Result<usize, String> {
+    max_payload_bytes_with(cover, &Strength::STANDARD)
+}
+
+/// `max_payload_bytes` at `strength`.
+pub fn max_payload_bytes_with(cover: &Path, strength: &Strength) -> Result<usize, String> {
+    let (w, h) = image::image_dimensions(cover).map_err(|e| e.to_string())?;
+    Ok(capacity_at(w, h, MAX_WIDTH, strength))
+}
+
+/// Embed `payload` into `cover` (any image format); returns the stego JPEG.
+pub fn encode(cover: &Path, payload: &[u8]) -> Result<Vec<u8>, String> {
+    encode_with_quality(cover, payload, DEFAULT_QUALITY)
+}
+
+/// `encode` writing the JPEG at `quality` (MIN_QUALITY..=100). Higher qualities cost file size
+/// but leave the embedded levels closer to the lattice, so they survive a later recompression at
+/// a lower quality better.
+pub fn encode_with_quality(cover: &Path, payload: &[u8], quality: u8) -> Result<Vec<u8>, String> {
+    encode_with_options(cover, payload, &EncodeOptions { quality, ..Default::default() })
+}
+
+/// `encode` with the JPEG quality and lattice strength of `options`.
+pub fn encode_with_options(cover: &Path, payload: &[u8], options: &EncodeOptions) -> Result<Vec<u8>, String> {
+    let EncodeOptions { quality, strength, max_width } = *options;
+    if !(MIN_QUALITY..=100).contains(&quality) {
+        return Err(format!("JPEG quality must be {} to 100, not {}", MIN_QUALITY, quality));
+    }
+    let mut img = image::open(cover).map_err(|e| e.to_string())?.to_rgb8();
+    let (w, h) = target_size(img.width(), img.height(), max_width);
+    if (w, h) != img.dimensions() {
+        img = image::imageops::resize(&img, w, h, FilterType::Lanczos3);
+    }
+    let mut raw = Vec::with_capacity(MAGIC.len() + LENGTH_BYTES + payload.len());
+    raw.extend_from_slice(MAGIC);
+    raw.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    raw.extend_from_slice(payload);
+    let codeword = reed_solomon::encode(&raw, RS_NSYM);
+    if codeword.len() > u16::MAX as usize {
+        return Err(format!("Payload too large for QIM: {} bytes", payload.len()));
+    }
+    let mut framed = (codeword.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(&codeword);
+    let bits: Vec<bool> = to_bits(&framed).into_iter().flat_map(|b| std::iter::repeat_n(b, strength.repeat)).collect();
+
+    let mut dct = DctImage::from_rgb(&img, quality);
+    let scale = lattice_scale(&dct)?;
+    let positions: Vec<_> = stream(&dct).collect();
+    if bits.len() > positions.len() {
+        return Err(format!(
+            "Payload too large for QIM: {} bytes (max {} for this cover)",
+            payload.len(),
+            capacity_at(img.width(), img.height(), max_width, &strength)
+        ));
+    }
+    let luma = dct.luma_mut();
+    for (&bit, &(bx, by, n)) in bits.iter().zip(&positions) {
+        let block = luma.block_mut(bx, by);
+        block[n] = embed_value(block[n] as i32, bit, scale[n], &strength) as i16;
+    }
+    dct.write()
+}
+
+/// Extract a payload from QIM JPEG bytes, trying each strength.
+pub fn decode_bytes(jpeg: &[u8]) -> Result<Vec<u8>, String> {
+    let dct = crate::jpeg_dct::read(jpeg)?;
+    let mut first_err = None;
+    for strength in &Strength::ALL {
+        match decode_dct(&dct, strength) {
+            Ok(payload) => return Ok(payload),
+            Err(e) => {
+                first_err.get_or_insert(e);
+            }
+        }
+    }
+    Err(first_err.expect("at least one strength"))
+}
+
+fn decode_dct(dct: &DctImage, strength: &Strength) -> Result<Vec<u8>, String> {
+    let luma = dct.luma();
+    let scale = lattice_scale(dct)?;
+    let repeat = strength.repeat;
+    // Majority vote per repeated bit; its margin is the group's average.
+    let (mut bits, mut margins) = (Vec::new(), Vec::new());
+    let mut group = Vec::with_capacity(repeat);
+    for (bx, by, n) in stream(dct) {
+        group.push(detect(luma.block(bx, by)[n] as f64 * scale[n], strength.delta));
+        if group.len() == repeat {
+            bits.push(group.iter().filter(|(b, _)| *b).count() > repeat / 2);
+            margins.push(group.iter().map(|(_, m)| m).sum::<f64>() / repeat as f64);
+            group.clear();
+        }
+    }
+    if bits.len() < 16 {
+        return Err("No QIM payload found (image too small)".to_string());
+    }
+    let codeword_len = u16::from_be_bytes([from_bits(&bits[..8])[0], from_bits(&bits[8..16])[0]]) as usize;
+    let total_bits = (2 + codeword_len) * 8;
+    if codeword_len == 0 || total_bits > bits.len() {
+        return Err("No QIM payload found".to_string());
+    }
+    let codeword = from_bits(&bits[16..total_bits]);
+    let erasures: Vec<usize> = (0..codeword_len)
+        .filter(|i| {
+            let start = 16 + i * 8;
+            margins[start..start + 8].iter().cloned().fold(f64::INFINITY, f64::min) < strength.erasure_margin()
+        })
+        .collect();
+    let decoded = reed_solomon::decode(&codeword, RS_NSYM, &erasures)
+        .or_else(|_| reed_solomon::decode(&codeword, RS_NSYM, &[]))
+        .map_err(|e| format!("No QIM payload found ({})", e))?;
+    let header = MAGIC.len() + LENGTH_BYTES;
+    if decoded.len() < header || &decoded[..MAGIC.len()] != MAGIC {
+        return Err("No QIM payload found".to_string());
+    }
+    let len = u32::from_be_bytes(decoded[MAGIC.len()..header].try_into().expect("4 bytes")) as usize;
+    decoded
+        .get(header..header + len)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| "QIM payload length exceeds decoded data".to_string())
+}
+
+pub fn decode(path: &Path) -> Result<Vec<u8>, String> {
+    decode_bytes(&std::fs::read(path).map_err(|e| e.to_string())?)
+}
+
+/// Read-back check for a stego JPEG: `payload` must decode from it as written and after one more
+/// pixel-domain recompression at quality 75, as a platform would apply.
+pub fn verify(jpeg: &[u8], payload: &[u8]) -> Result<(), String> {
+    verify_at(jpeg, payload, EMBED_QUALITY)
+}
+
+/// `verify` with the recompression at `recompress_quality`.
+pub fn verify_at(jpeg: &[u8], payload: &[u8], recompress_quality: u8) -> Result<(), String> {
+    if decode_bytes(jpeg)? != payload {
+        return Err("read-back mismatch".to_string());
+    }
+    let pixels = image::load_from_memory(jpeg).map_err(|e| e.to_string())?.to_rgb8();
+    let recompressed = DctImage::from_rgb(&pixels, recompress_quality).write()?;
+    match decode_bytes(&recompressed) {
+        Ok(p) if p == payload => Ok(()),
+        Ok(_) => Err("read-back mismatch after recompression".to_string()),
+        Err(e) => Err(format!("lost after recompression: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qim_roundtrip() {
+        let cover = std::env::temp_dir().join(format!("stegstr_qim_cover_{}.png", std::process::id()));
+        image::RgbImage::from_fn(256, 200, |x, y| image::Rgb([64 + ((x ^ y) % 128) as u8, 64 + (x / 2) as u8, 64 + (y / 2) as u8])).save(&cover).unwrap();
+        let payload: Vec<u8> = (0..200u32).map(|i| (i * 13 % 256) as u8).collect();
+        assert!(max_payload_bytes(&cover).unwrap() >= payload.len());
+        let jpeg = encode(&cover, &payload).unwrap();
+        assert_eq!(decode_bytes(&jpeg).unwrap(), payload);
+        // Survives a pixel-domain recompression at the embed quality (no clipping in this cover).
+        let pixels = image::load_from_memory(&jpeg).unwrap().to_rgb8();
+        let recompressed = DctImage::from_rgb(&pixels, 75).write().unwrap();
+        assert_eq!(decode_bytes(&recompressed).unwrap(), payload);
+        // Written at quality 90, it survives recompression at a lower quality too.
+        let jpeg = encode_with_quality(&cover, &payload, 90).unwrap();
+        assert_eq!(crate::jpeg_dct::read(&jpeg).unwrap().luma_qtable(), Some(&crate::jpeg_dct::quality_table(&crate::jpeg_dct::LUMA_QUANT, 90)));
+        verify(&jpeg, &payload).unwrap();
+        let pixels = image::load_from_memory(&jpeg).unwrap().to_rgb8();
+        assert_eq!(decode_bytes(&DctImage::from_rgb(&pixels, 60).write().unwrap()).unwrap(), payload);
+        assert!(encode_with_quality(&cover, &payload, 20).is_err());
+        // The strong lattice decodes without being told, at a smaller capacity.
+        let strong = EncodeOptions { quality: 90, strength: Strength::STRONG, ..Default::default() };
+        assert!(max_payload_bytes_with(&cover, &Strength::STRONG).unwrap() < max_payload_bytes(&cover).unwrap());
+        let jpeg = encode_with_options(&cover, &payload[..100], &strong).unwrap();
+        verify_at(&jpeg, &payload[..100], 60).unwrap();
+        let _ = std::fs::remove_file(cover);
+    }
+}
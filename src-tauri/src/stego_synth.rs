@@ -0,0 +1,212 @@
+// Coverless steganography (experimental): the carrier image is synthesized from the payload, so
+// there is no original to compare against. The image is a grid of 8x8 cells over a smooth random
+// colour field; each cell carries 2 bits as the orientation of a fine stripe texture (horizontal,
+// vertical or either diagonal). The four textures are orthogonal over a cell, so the decoder
+// picks the one with the highest correlation.
+// Frame: MAGIC + 4-byte big-endian length + payload + first 4 bytes of SHA-256(payload), XORed
+// with a fixed keystream so the texture looks random whatever the payload.
+
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, RgbImage};
+use sha2::{Digest, Sha256};
+
+const MAGIC: &[u8] = b"STEGSYN";
+const LENGTH_BYTES: usize = 4;
+const CHECK_BYTES: usize = 4;
+const HEADER_BYTES: usize = MAGIC.len() + LENGTH_BYTES;
+
+const CELL: u32 = 8;
+/// Stripe amplitude per channel; the colour field stays within [60, 195] so nothing clips.
+const AMPLITUDE: i32 = 36;
+/// Spacing of the colour field's control points.
+const FIELD_GRID: u32 = 32;
+const MIN_CELLS_PER_SIDE: u32 = 8;
+const MAX_SIDE: u32 = 4096;
+const KEYSTREAM_SEED: u64 = 0x5354_4547_5359_4E31;
+
+/// splitmix64: small deterministic generator, stable across platforms and crate versions.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+fn whiten(data: &mut [u8], offset: usize) {
+    let mut rng = Rng(KEYSTREAM_SEED);
+    for _ in 0..offset {
+        rng.next();
+    }
+    for b in data {
+        *b ^= rng.next() as u8;
+    }
+}
+
+fn checksum(payload: &[u8]) -> [u8; CHECK_BYTES] {
+    let digest = Sha256::digest(payload);
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+/// Stripe value (1, 0, -1, 0) of `symbol`'s texture at cell-local (x, y).
+fn pattern(symbol: u8, x: u32, y: u32) -> i32 {
+    let phase = match symbol {
+        0 => y,
+        1 => x,
+        2 => x + y,
+        _ => x + CELL - y,
+    };
+    [1, 0, -1, 0][(phase % 4) as usize]
+}
+
+/// Smooth random colour field: random control points every FIELD_GRID pixels, bilinearly blended.
+fn colour_field(width: u32, height: u32, seed: u64) -> impl Fn(u32, u32) -> [i32; 3] {
+    let gw = width / FIELD_GRID + 2;
+    let gh = height / FIELD_GRID + 2;
+    let mut rng = Rng(seed);
+    let points: Vec<[i32; 3]> = (0..gw * gh)
+        .map(|_| {
+            let v = rng.next();
+            [60 + (v % 136) as i32, 60 + ((v >> 16) % 136) as i32, 60 + ((v >> 32) % 136) as i32]
+        })
+        .collect();
+    move |x, y| {
+        let (gx, gy) = (x / FIELD_GRID, y / FIELD_GRID);
+        let (fx, fy) = ((x % FIELD_GRID) as i32, (y % FIELD_GRID) as i32);
+        let g = FIELD_GRID as i32;
+        let at = |cx: u32, cy: u32| points[(cy * gw + cx) as usize];
+        let (p00, p10, p01, p11) = (at(gx, gy), at(gx + 1, gy), at(gx, gy + 1), at(gx + 1, gy + 1));
+        let mut out = [0; 3];
+        for c in 0..3 {
+            let top = p00[c] * (g - fx) + p10[c] * fx;
+            let bottom = p01[c] * (g - fx) + p11[c] * fx;
+            out[c] = (top * (g - fy) + bottom * fy) / (g * g);
+        }
+        out
+    }
+}
+
+fn bytes_to_symbols(data: &[u8]) -> Vec<u8> {
+    data.iter().flat_map(|b| [b >> 6, (b >> 4) & 3, (b >> 2) & 3, b & 3]).collect()
+}
+
+/// Grid size (cells) for `cells` symbols: roughly square, at least MIN_CELLS_PER_SIDE each way.
+fn grid_for(cells: usize) -> (u32, u32) {
+    let cols = ((cells as f64).sqrt().ceil() as u32).max(MIN_CELLS_PER_SIDE);
+    let rows = (cells as u32).div_ceil(cols).max(MIN_CELLS_PER_SIDE);
+    (cols, rows)
+}
+
+/// Largest payload a synthesized image can carry (limited by MAX_SIDE).
+pub fn max_payload_bytes() -> usize {
+    let cells = (MAX_SIDE / CELL) as usize * (MAX_SIDE / CELL) as usize;
+    cells / 4 - HEADER_BYTES - CHECK_BYTES
+}
+
+/// Synthesize a PNG carrying `payload`. The image size follows from the payload size.
+pub fn encode(payload: &[u8]) -> Result<Vec<u8>, String> {
+    if payload.len() > max_payload_bytes() {
+        return Err(format!("Payload too large for synth: {} bytes (max {})", payload.len(), max_payload_bytes()));
+    }
+    let mut framed = Vec::with_capacity(HEADER_BYTES + payload.len() + CHECK_BYTES);
+    framed.extend_from_slice(MAGIC);
+    framed.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    framed.extend_from_slice(payload);
+    framed.extend_from_slice(&checksum(payload));
+    whiten(&mut framed, 0);
+    let mut symbols = bytes_to_symbols(&framed);
+
+    let (cols, rows) = grid_for(symbols.len());
+    let seed = u64::from_be_bytes(Sha256::digest(payload)[..8].try_into().expect("8 bytes"));
+    let mut filler = Rng(seed ^ KEYSTREAM_SEED);
+    symbols.resize_with((cols * rows) as usize, || (filler.next() & 3) as u8);
+
+    let (width, height) = (cols * CELL, rows * CELL);
+    let field = colour_field(width, height, seed);
+    let img = RgbImage::from_fn(width, height, |x, y| {
+        let symbol = symbols[((y / CELL) * cols + x / CELL) as usize];
+        let offset = AMPLITUDE * pattern(symbol, x % CELL, y % CELL);
+        let base = field(x, y);
+        image::Rgb(base.map(|c| (c + offset).clamp(0, 255) as u8))
+    });
+    let mut out = Vec::new();
+    PngEncoder::new(&mut out)
+        .write_image(img.as_raw(), width, height, ExtendedColorType::Rgb8)
+        .map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+/// Most likely symbol of the cell at (cx, cy).
+fn read_symbol(img: &RgbImage, cx: u32, cy: u32) -> u8 {
+    let mut scores = [0i64; 4];
+    for y in 0..CELL {
+        for x in 0..CELL {
+            let p = img.get_pixel(cx * CELL + x, cy * CELL + y);
+            let luma = p[0] as i64 + p[1] as i64 + p[2] as i64;
+            for (s, score) in scores.iter_mut().enumerate() {
+                *score += luma * pattern(s as u8, x, y) as i64;
+            }
+        }
+    }
+    (0..4).max_by_key(|&s| scores[s as usize]).unwrap_or(0)
+}
+
+/// Read `count` bytes starting at byte `start` of the frame.
+fn read_bytes(img: &RgbImage, cols: u32, start: usize, count: usize) -> Vec<u8> {
+    let mut out: Vec<u8> = (start..start + count)
+        .map(|b| {
+            (0..4).fold(0u8, |acc, k| {
+                let cell = (b * 4 + k) as u32;
+                (acc << 2) | read_symbol(img, cell % cols, cell / cols)
+            })
+        })
+        .collect();
+    whiten(&mut out, start);
+    out
+}
+
+pub fn decode(path: &std::path::Path) -> Result<Vec<u8>, String> {
+    let img = image::open(path).map_err(|e| e.to_string())?.to_rgb8();
+    let (width, height) = img.dimensions();
+    if width % CELL != 0 || height % CELL != 0 || width < CELL * MIN_CELLS_PER_SIDE || height < CELL * MIN_CELLS_PER_SIDE {
+        return Err("Not a synth image (size is not a whole cell grid)".to_string());
+    }
+    let cols = width / CELL;
+    let capacity = (cols * (height / CELL)) as usize / 4;
+    let header = read_bytes(&img, cols, 0, HEADER_BYTES);
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err("No synth payload found".to_string());
+    }
+    let len = u32::from_be_bytes(header[MAGIC.len()..].try_into().expect("4 bytes")) as usize;
+    if HEADER_BYTES + len + CHECK_BYTES > capacity {
+        return Err(format!("Synth payload length {} exceeds image capacity", len));
+    }
+    let body = read_bytes(&img, cols, HEADER_BYTES, len + CHECK_BYTES);
+    let (payload, check) = body.split_at(len);
+    if check != checksum(payload) {
+        return Err("Synth payload checksum mismatch".to_string());
+    }
+    Ok(payload.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synth_roundtrip() {
+        let payload: Vec<u8> = (0..3000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let png = encode(&payload).unwrap();
+        let path = std::env::temp_dir().join(format!("stegstr_synth_{}.png", std::process::id()));
+        std::fs::write(&path, &png).unwrap();
+        assert_eq!(decode(&path).unwrap(), payload);
+        // An ordinary image of a whole cell grid is rejected, not misread.
+        image::RgbImage::from_pixel(128, 128, image::Rgb([120, 130, 140])).save(&path).unwrap();
+        assert!(decode(&path).is_err());
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,521 @@
+// Named sharing targets: one choice picks output container, max dimensions, codec order and
+// robustness for where the image is going. Both codecs need a lossless container, so every target
+// writes PNG; targets differ in how far the cover is downscaled and which codec is preferred.
+
+use image::metadata::Orientation;
+use image::{ImageDecoder, ImageReader};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::mask::MaskSpec;
+use crate::output::OutputFormat;
+use crate::progress::Progress;
+use crate::stego_key::StegoKey;
+use crate::{stego, stego_dot};
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Target {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub description: &'static str,
+    /// Output container (always "png": both codecs are destroyed by lossy recompression).
+    pub container: &'static str,
+    /// Longest side in pixels; larger covers are downscaled before embedding. None = keep size.
+    pub max_dimension: Option<u32>,
+    /// Codecs to try in order: "dwt" (capacity) and/or "dot" (redundant, more robust).
+    pub methods: &'static [&'static str],
+    /// "standard" or "high".
+    pub robustness: &'static str,
+}
+
+pub const TARGETS: &[Target] = &[
+    Target {
+        name: "email",
+        label: "Email",
+        description: "Attachment-sized PNG (max 1600 px) that stays under common mail size limits.",
+        container: "png",
+        max_dimension: Some(1600),
+        methods: &["dwt", "dot"],
+        robustness: "standard",
+    },
+    Target {
+        name: "matrix",
+        label: "Matrix",
+        description: "Max 2048 px PNG; send it as a file so the client does not recompress it.",
+        container: "png",
+        max_dimension: Some(2048),
+        methods: &["dwt", "dot"],
+        robustness: "standard",
+    },
+    Target {
+        name: "print",
+        label: "Print",
+        description: "Full resolution with the redundant dot codec only.",
+        container: "png",
+        max_dimension: None,
+        methods: &["dot"],
+        robustness: "high",
+    },
+    Target {
+        name: "usb",
+        label: "USB / file copy",
+        description: "Full resolution, byte-exact copy; DWT first for the most capacity.",
+        container: "png",
+        max_dimension: None,
+        methods: &["dwt", "dot"],
+        robustness: "standard",
+    },
+];
+
+/// Codec order used when no target is chosen.
+pub const DEFAULT_METHODS: &[&str] = &["dwt", "dot"];
+
+/// One rung of the retry ladder used when read-back verification fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeSettings {
+    pub method: &'static str,
+    /// Cover pixels are first remapped into [headroom, 255 - headroom] (outside the mask) so DWT
+    /// coefficient changes are not clipped at black/white. Decoders need no change.
+    pub headroom: u8,
+}
+
+impl std::fmt::Display for EncodeSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.headroom {
+            0 => write!(f, "{}", self.method),
+            h => write!(f, "{} (headroom {})", self.method, h),
+        }
+    }
+}
+
+/// Weakest (least visible) first. Dot has no tunable strength without changing its format; QIM
+/// (JPEG output) is only on the ladder when asked for, since no target lists it.
+pub const ESCALATION: &[EncodeSettings] = &[
+    EncodeSettings { method: "dwt", headroom: 0 },
+    EncodeSettings { method: "dwt", headroom: 4 },
+    EncodeSettings { method: "dwt", headroom: 16 },
+    EncodeSettings { method: "dot", headroom: 0 },
+    EncodeSettings { method: "qim", headroom: 0 },
+];
+
+/// Limit on escalation attempts (default: the whole ladder).
+pub const MAX_ATTEMPTS_ENV: &str = "STEGSTR_MAX_ATTEMPTS";
+
+pub fn max_attempts() -> usize {
+    std::env::var(MAX_ATTEMPTS_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(ESCALATION.len() + crate::external::codecs().len())
+}
+
+/// A verified embed: the PNG, the settings that produced it, and the attempts that failed first.
+#[derive(Debug, Clone)]
+pub struct VerifiedEmbed {
+    pub png: Vec<u8>,
+    pub settings: EncodeSettings,
+    pub failed_attempts: Vec<String>,
+}
+
+pub fn find(name: &str) -> Result<&'static Target, String> {
+    let name = name.trim();
+    TARGETS.iter().find(|t| t.name.eq_ignore_ascii_case(name)).ok_or_else(|| {
+        let names: Vec<&str> = TARGETS.iter().map(|t| t.name).collect();
+        format!("Unknown target {} (expected one of: {})", name, names.join(", "))
+    })
+}
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn temp_png(tag: &str) -> PathBuf {
+    let n = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("stegstr-{}-{}-{}.png", tag, std::process::id(), n))
+}
+
+/// `cover` decoded with its EXIF orientation applied.
+pub(crate) fn load_oriented(cover: &Path) -> Result<image::DynamicImage, String> {
+    let reader = ImageReader::open(cover).map_err(|e| e.to_string())?;
+    let mut decoder = reader.into_decoder().map_err(|e| e.to_string())?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = image::DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    // Apply orientation here: temporary PNGs carry no EXIF.
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// Temporary downscaled copy of a cover.
+struct ScaledCover {
+    path: PathBuf,
+    from: (u32, u32),
+    to: (u32, u32),
+}
+
+/// Downscale `cover` into a temporary PNG when it exceeds `max_dimension`. Returns None if it fits.
+fn downscaled_cover(cover: &Path, max_dimension: u32) -> Result<Option<ScaledCover>, String> {
+    let img = load_oriented(cover)?;
+    if img.width().max(img.height()) <= max_dimension {
+        return Ok(None);
+    }
+    if is_animated(cover) {
+        return Err(format!("Animated covers are not downscaled; this target needs one at most {} px", max_dimension));
+    }
+    let resized = img.resize(max_dimension, max_dimension, image::imageops::FilterType::Lanczos3);
+    let path = temp_png("target");
+    resized.to_rgba8().save(&path).map_err(|e| e.to_string())?;
+    Ok(Some(ScaledCover {
+        path,
+        from: (img.width(), img.height()),
+        to: (resized.width(), resized.height()),
+    }))
+}
+
+/// Copy of `cover` with pixels outside the mask remapped into [headroom, 255 - headroom].
+fn headroom_cover(cover: &Path, headroom: u8, mask: Option<&MaskSpec>) -> Result<PathBuf, String> {
+    let mut img = load_oriented(cover)?.to_rgba8();
+    let mask = match mask {
+        Some(spec) if !spec.is_empty() => Some(spec.build(img.width(), img.height())?),
+        _ => None,
+    };
+    let span = 255 - 2 * headroom as u32;
+    for (x, y, p) in img.enumerate_pixels_mut() {
+        if mask.as_ref().is_some_and(|m| m.is_excluded(x, y)) {
+            continue;
+        }
+        for c in &mut p.0[..3] {
+            *c = (headroom as u32 + (*c as u32 * span + 127) / 255) as u8;
+        }
+    }
+    let path = temp_png("headroom");
+    img.save(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// True for an APNG or animated GIF cover, which only the DWT and dot codecs embed frame by frame.
+fn is_animated(cover: &Path) -> bool {
+    std::fs::read(cover).is_ok_and(|bytes| crate::stego_anim::is_animated(&bytes))
+}
+
+/// Encode once with `method`; dot is only attempted when its capacity allows (per frame, unless
+/// the payload is spread over an animation's frames). `dwt` sets the DWT layout (default when
+/// None); its stego key, spread and a WebP output format also apply to dot, and rule out the other
+/// codecs, and dot strips cover metadata when it does.
+/// DWT reports its tiles to `progress`; every codec is refused once it is cancelled.
+fn encode_once(
+    cover: &Path,
+    payload: &[u8],
+    method: &str,
+    mask: Option<&MaskSpec>,
+    dwt: Option<&stego::EncodeOptions>,
+    progress: &Progress,
+) -> Result<Vec<u8>, String> {
+    progress.check()?;
+    let key = dwt.and_then(|o| o.key);
+    let format = dwt.map(|o| o.format).unwrap_or_default();
+    let strip_metadata = dwt.is_some_and(|o| o.strip_metadata);
+    let spread = dwt.map_or(0, |o| o.spread);
+    if !matches!(method, "dwt" | "dot") && is_animated(cover) {
+        return Err(format!("{} cannot write animations", method));
+    }
+    match method {
+        "dwt" => stego::encode_with_progress(cover, payload, mask, &dwt.copied().unwrap_or_default(), progress).map_err(String::from),
+        "dot" => match stego_dot::max_payload_bytes(cover)? {
+            cap if spread == 0 && crate::payload_header::stored_len(payload) > cap => Err(format!("capacity {} bytes", cap)),
+            _ => {
+                let opts = stego_dot::EncodeOptions { key, format, strip_metadata, spread, ..Default::default() };
+                stego_dot::encode_with_options(cover, payload, mask, &opts).map_err(String::from)
+            }
+        },
+        other if key.is_some() => Err(format!("{} does not take a stego key", other)),
+        other if format != OutputFormat::Png => Err(format!("{} cannot write {}", other, format.extension())),
+        // Writes a JPEG: the only codec whose output survives recompression. The whole cover is
+        // re-encoded, so masks cannot be honoured.
+        "qim" if mask.is_some_and(|m| !m.is_empty()) => Err("qim does not support masks".to_string()),
+        "qim" => crate::stego_qim::encode(cover, payload),
+        other => match crate::external::find(other) {
+            Some(codec) => crate::external::encode(codec, cover, payload),
+            None => Err(format!("unknown method {}", other)),
+        },
+    }
+}
+
+/// Try each codec in `methods` order. Masked pixels are left untouched by either codec.
+/// Returns (png, method).
+pub fn embed_with_methods(
+    cover: &Path,
+    payload: &[u8],
+    methods: &[&'static str],
+    mask: Option<&MaskSpec>,
+    dwt: Option<&stego::EncodeOptions>,
+) -> Result<(Vec<u8>, &'static str), String> {
+    embed_with_progress(cover, payload, methods, mask, dwt, &Progress::default())
+}
+
+/// `embed_with_methods`, reporting each attempt to `progress` and stopping once it is cancelled.
+pub fn embed_with_progress(
+    cover: &Path,
+    payload: &[u8],
+    methods: &[&'static str],
+    mask: Option<&MaskSpec>,
+    dwt: Option<&stego::EncodeOptions>,
+    progress: &Progress,
+) -> Result<(Vec<u8>, &'static str), String> {
+    let mut errors = Vec::new();
+    for &method in methods {
+        match encode_once(cover, payload, method, mask, dwt, progress) {
+            Ok(png) => return Ok((png, method)),
+            Err(e) if progress.is_cancelled() => return Err(e),
+            Err(e) => errors.push(format!("{}: {}", method, e)),
+        }
+    }
+    Err(format!(
+        "Cover too small for {} byte payload ({})",
+        payload.len(),
+        errors.join("; ")
+    ))
+}
+
+/// Encode and read back, escalating through ESCALATION (restricted to `methods`) until the
+/// payload decodes byte-exact or `max_attempts` rungs have been tried.
+pub fn embed_verified(
+    cover: &Path,
+    payload: &[u8],
+    methods: &[&str],
+    mask: Option<&MaskSpec>,
+    dwt: Option<&stego::EncodeOptions>,
+    max_attempts: usize,
+) -> Result<VerifiedEmbed, String> {
+    embed_verified_with_progress(cover, payload, methods, mask, dwt, max_attempts, &Progress::default())
+}
+
+/// `embed_verified` reporting to `progress`: each rung's encode is its first half, the read-back
+/// its second (a retry starts over). Stops at the next check once `progress` is cancelled.
+pub fn embed_verified_with_progress(
+    cover: &Path,
+    payload: &[u8],
+    methods: &[&str],
+    mask: Option<&MaskSpec>,
+    dwt: Option<&stego::EncodeOptions>,
+    max_attempts: usize,
+    progress: &Progress,
+) -> Result<VerifiedEmbed, String> {
+    let mut failed_attempts = Vec::new();
+    // External codecs have no strength settings: one rung each, after the built-in ones.
+    let external = methods.iter().filter_map(|m| crate::external::find(m)).map(|c| EncodeSettings {
+        method: c.name.as_str(),
+        headroom: 0,
+    });
+    // Headroom covers are re-saved stills: an animation keeps its frames only on the plain rungs.
+    let animated = is_animated(cover);
+    let ladder: Vec<EncodeSettings> = ESCALATION
+        .iter()
+        .copied()
+        .filter(|s| methods.contains(&s.method) && !(animated && s.headroom > 0))
+        .chain(external)
+        .take(max_attempts.max(1))
+        .collect();
+    for settings in ladder {
+        let prepared = match settings.headroom {
+            0 => None,
+            h => Some(headroom_cover(cover, h, mask)?),
+        };
+        let out = temp_png("verify");
+        let attempt = encode_once(prepared.as_deref().unwrap_or(cover), payload, settings.method, mask, dwt, &progress.stage(0, 2)).and_then(|png| {
+            crate::atomic_write::write(&out, &png, false)?;
+            readback(&out, settings.method, payload, dwt.and_then(|o| o.key.as_ref()), &progress.stage(1, 2))?;
+            Ok(png)
+        });
+        for p in prepared.iter().chain(std::iter::once(&out)) {
+            let _ = std::fs::remove_file(p);
+        }
+        match attempt {
+            Ok(png) => {
+                return Ok(VerifiedEmbed {
+                    png,
+                    settings,
+                    failed_attempts,
+                })
+            }
+            Err(e) if progress.is_cancelled() => return Err(e),
+            Err(e) => failed_attempts.push(format!("{}: {}", settings, e)),
+        }
+    }
+    Err(format!("No settings verified ({})", failed_attempts.join("; ")))
+}
+
+/// Run `f` on the cover as the target prepares it (downscaled if needed, mask scaled with it).
+fn with_target_cover<R>(
+    cover: &Path,
+    target: &Target,
+    mask: Option<&MaskSpec>,
+    f: impl FnOnce(&Path, Option<&MaskSpec>) -> Result<R, String>,
+) -> Result<R, String> {
+    with_scaled_cover(cover, target.max_dimension, mask, f)
+}
+
+/// Run `f` on the cover downscaled to `max_dimension` if it is larger (mask scaled with it).
+pub(crate) fn with_scaled_cover<R>(
+    cover: &Path,
+    max_dimension: Option<u32>,
+    mask: Option<&MaskSpec>,
+    f: impl FnOnce(&Path, Option<&MaskSpec>) -> Result<R, String>,
+) -> Result<R, String> {
+    let scaled = match max_dimension {
+        Some(max) => downscaled_cover(cover, max)?,
+        None => None,
+    };
+    let result = match &scaled {
+        Some(sc) => {
+            let mask = mask.map(|m| m.scaled(sc.from, sc.to));
+            f(&sc.path, mask.as_ref())
+        }
+        None => f(cover, mask),
+    };
+    if let Some(sc) = scaled {
+        let _ = std::fs::remove_file(sc.path);
+    }
+    result
+}
+
+/// Embed for a sharing target (or the default codec order when None). Returns (png, method).
+/// A mask is given in the cover's coordinates and is scaled along with a downscaled cover.
+pub fn embed(
+    cover: &Path,
+    payload: &[u8],
+    target: Option<&Target>,
+    mask: Option<&MaskSpec>,
+    dwt: Option<&stego::EncodeOptions>,
+) -> Result<(Vec<u8>, &'static str), String> {
+    match target {
+        Some(t) => with_target_cover(cover, t, mask, |c, m| embed_with_methods(c, payload, t.methods, m, dwt)),
+        None => embed_with_methods(cover, payload, DEFAULT_METHODS, mask, dwt),
+    }
+}
+
+/// `embed_verified` for a sharing target (or the default codec order when None).
+pub fn embed_verified_for_target(
+    cover: &Path,
+    payload: &[u8],
+    target: Option<&Target>,
+    mask: Option<&MaskSpec>,
+    dwt: Option<&stego::EncodeOptions>,
+    max_attempts: usize,
+) -> Result<VerifiedEmbed, String> {
+    match target {
+        Some(t) => with_target_cover(cover, t, mask, |c, m| embed_verified(c, payload, t.methods, m, dwt, max_attempts)),
+        None => embed_verified(cover, payload, DEFAULT_METHODS, mask, dwt, max_attempts),
+    }
+}
+
+/// Capacity of `cover` for `method` as a sharing target prepares it, with `payload_bytes` as usage.
+pub fn capacity(
+    cover: &Path,
+    method: &str,
+    target: Option<&Target>,
+    payload_bytes: Option<u64>,
+) -> Result<crate::units::CapacityInfo, String> {
+    let measure = |c: &Path, _: Option<&MaskSpec>| match method {
+        "dwt" => stego::max_payload_bytes(c).map_err(String::from),
+        "dot" => stego_dot::max_payload_bytes(c).map_err(String::from),
+        "qim" => crate::stego_qim::max_payload_bytes(c),
+        other => match crate::external::find(other) {
+            Some(codec) => crate::external::capacity(codec, c),
+            None => Err(format!("unknown method {}", other)),
+        },
+    };
+    let bytes = match target {
+        Some(t) => with_target_cover(cover, t, None, measure)?,
+        None => measure(cover, None)?,
+    };
+    Ok(crate::units::CapacityInfo::new(method, payload_bytes, bytes as u64))
+}
+
+/// `capacity` for `payload`; for dwt and dot, which zstd-compress what they embed, it also
+/// reports the compressed size.
+pub fn payload_capacity(cover: &Path, method: &str, target: Option<&Target>, payload: &[u8]) -> Result<crate::units::CapacityInfo, String> {
+    let info = capacity(cover, method, target, Some(payload.len() as u64))?;
+    Ok(match method {
+        "dwt" | "dot" => info.with_stored(crate::payload_header::stored_len(payload) as u64),
+        _ => info,
+    })
+}
+
+/// Decode `output` with `method` and byte-compare against the payload that was embedded.
+pub fn verify_readback(output: &Path, method: &str, expected: &[u8]) -> Result<(), String> {
+    verify_readback_with_key(output, method, expected, None)
+}
+
+/// `verify_readback` of an image embedded with a stego key.
+pub fn verify_readback_with_key(output: &Path, method: &str, expected: &[u8], key: Option<&StegoKey>) -> Result<(), String> {
+    readback(output, method, expected, key, &Progress::default())
+}
+
+/// `verify_readback_with_key`; a DWT read-back reports to `progress`.
+fn readback(output: &Path, method: &str, expected: &[u8], key: Option<&StegoKey>, progress: &Progress) -> Result<(), String> {
+    progress.check()?;
+    let decoded = match method {
+        "dwt" => stego::decode_with_progress(output, key, progress).map(|(payload, _)| payload).map_err(|e| match e {
+            e @ crate::error::StegoError::Cancelled(_) => e.to_string(),
+            e => format!("Read-back verification failed: {}", e),
+        })?,
+        _ => crate::scan::decode_with_key(method, output, key).map_err(|e| format!("Read-back verification failed: {}", e))?,
+    };
+    if decoded != expected {
+        return Err(format!(
+            "Read-back verification failed: decoded {} bytes that differ from the {} byte payload",
+            decoded.len(),
+            expected.len()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embed_verified_escalates_on_clipped_cover() {
+        // Near-white covers clip the DWT inverse, so the first rung fails read-back.
+        let cover = stegstr_core::test_util::noisy_cover_in(256, 256, 0x1234_5678, 252, 4);
+        let cover_path = temp_png("test-cover");
+        cover.save(&cover_path).unwrap();
+        let payload: Vec<u8> = (0..200u32).map(|i| (i * 37 % 251) as u8).collect();
+
+        let v = embed_verified(&cover_path, &payload, DEFAULT_METHODS, None, None, ESCALATION.len()).unwrap();
+        assert_ne!(v.settings, ESCALATION[0]);
+        assert!(!v.failed_attempts.is_empty());
+        assert!(embed_verified(&cover_path, &payload, DEFAULT_METHODS, None, None, 1).is_err());
+        // Restricted to dwt, the ladder never falls back to another codec.
+        let v = embed_verified(&cover_path, &payload, &["dwt"], None, None, ESCALATION.len()).unwrap();
+        assert_eq!(v.settings.method, "dwt");
+        let mut x = 0x9e37_79b9u32;
+        let too_big: Vec<u8> = (0..64 * 1024)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect();
+        let err = embed_verified(&cover_path, &too_big, &["dwt"], None, None, ESCALATION.len()).unwrap_err();
+        assert!(!err.contains("dot"), "{}", err);
+        let _ = std::fs::remove_file(cover_path);
+    }
+
+    #[test]
+    fn test_downscaled_cover_keeps_alpha() {
+        let cover = image::RgbaImage::from_fn(600, 400, |x, _| image::Rgba([90, 120, 150, if x < 300 { 0 } else { 255 }]));
+        let cover_path = temp_png("test-alpha");
+        cover.save(&cover_path).unwrap();
+        let scaled = downscaled_cover(&cover_path, 300).unwrap().unwrap();
+        assert_eq!((scaled.from, scaled.to), ((600, 400), (300, 200)));
+        let img = image::open(&scaled.path).unwrap();
+        assert!(img.color().has_alpha());
+        let img = img.to_rgba8();
+        assert_eq!((img.get_pixel(10, 100)[3], img.get_pixel(290, 100)[3]), (0, 255));
+        let _ = std::fs::remove_file(cover_path);
+        let _ = std::fs::remove_file(scaled.path);
+    }
+}
@@ -0,0 +1,80 @@
+// Downscaled previews, so the frontend need not load full-size images to show them. The image is
+// read with its EXIF orientation applied (the loader the targets use), fitted within a maximum
+// dimension (never enlarged) and returned base64-encoded as JPEG at a quality, or as WebP. The
+// image crate only writes lossless WebP, so quality does not apply to it.
+
+use base64::Engine;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::output::OutputFormat;
+
+pub const DEFAULT_MAX_DIMENSION: u32 = 256;
+pub const DEFAULT_QUALITY: u8 = 80;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Thumbnail {
+    /// "image/jpeg" or "image/webp".
+    pub mime: String,
+    /// Base64 of the encoded preview (without a data: prefix).
+    pub data: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Preview of the image at `path` within `max_dimension` px, as `format` "jpeg" or "webp".
+pub fn generate(path: &Path, max_dimension: u32, quality: u8, format: &str) -> Result<Thumbnail, String> {
+    if max_dimension == 0 {
+        return Err("max_dimension must be at least 1".to_string());
+    }
+    if !(1..=100).contains(&quality) {
+        return Err("quality must be 1-100".to_string());
+    }
+    let mut img = crate::targets::load_oriented(path)?;
+    if img.width().max(img.height()) > max_dimension {
+        img = img.thumbnail(max_dimension, max_dimension);
+    }
+    let (width, height) = (img.width(), img.height());
+    let (mime, bytes) = match format.to_ascii_lowercase().as_str() {
+        "jpeg" | "jpg" => {
+            let mut out = Vec::new();
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, quality)
+                .encode_image(&img.to_rgb8())
+                .map_err(|e| e.to_string())?;
+            ("image/jpeg", out)
+        }
+        "webp" => ("image/webp", OutputFormat::Webp.encode(img.to_rgba8().as_raw(), width, height, image::ExtendedColorType::Rgba8)?),
+        other => return Err(format!("Unknown thumbnail format {} (jpeg or webp)", other)),
+    };
+    Ok(Thumbnail {
+        mime: mime.to_string(),
+        data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        width,
+        height,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnail_fits_max_dimension_in_either_format() {
+        let path = std::env::temp_dir().join(format!("stegstr_thumb_{}.png", std::process::id()));
+        image::RgbImage::from_fn(400, 200, |x, y| image::Rgb([x as u8, y as u8, 128])).save(&path).unwrap();
+
+        let jpeg = generate(&path, 100, 70, "jpeg").unwrap();
+        assert_eq!((jpeg.mime.as_str(), jpeg.width, jpeg.height), ("image/jpeg", 100, 50));
+        let bytes = base64::engine::general_purpose::STANDARD.decode(&jpeg.data).unwrap();
+        assert_eq!(image::load_from_memory(&bytes).unwrap().width(), 100);
+
+        // Never enlarged.
+        let webp = generate(&path, 1000, DEFAULT_QUALITY, "webp").unwrap();
+        assert_eq!((webp.mime.as_str(), webp.width, webp.height), ("image/webp", 400, 200));
+
+        assert!(generate(&path, 100, 0, "jpeg").is_err());
+        assert!(generate(&path, 100, 70, "gif").is_err());
+        let _ = std::fs::remove_file(path);
+    }
+}
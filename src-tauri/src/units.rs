@@ -0,0 +1,158 @@
+// Size and capacity formatting shared by command results and CLI output: every size carries
+// the raw byte count (for frontends that localize themselves) next to a ready-made string.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SizeInfo {
+    pub bytes: u64,
+    pub bits: u64,
+    /// e.g. "1.5 KB" (1024-based, decimal separator from the locale).
+    pub human: String,
+}
+
+impl SizeInfo {
+    pub fn new(bytes: u64) -> Self {
+        SizeInfo {
+            bytes,
+            bits: bytes * 8,
+            human: format_bytes(bytes),
+        }
+    }
+}
+
+/// Payload size against what the cover can hold.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CapacityInfo {
+    pub method: String,
+    pub payload: Option<SizeInfo>,
+    pub capacity: SizeInfo,
+    /// Payload as a percentage of capacity, one decimal; None without a payload.
+    pub used_percent: Option<f64>,
+    /// What the payload takes up once compressed, when the codec compressed it; `used_percent`
+    /// is then of this size.
+    #[serde(default)]
+    pub compressed: Option<SizeInfo>,
+}
+
+impl CapacityInfo {
+    pub fn new(method: &str, payload_bytes: Option<u64>, capacity_bytes: u64) -> Self {
+        CapacityInfo {
+            method: method.to_string(),
+            payload: payload_bytes.map(SizeInfo::new),
+            capacity: SizeInfo::new(capacity_bytes),
+            used_percent: payload_bytes.map(|p| percent(p, capacity_bytes)),
+            compressed: None,
+        }
+    }
+
+    /// Record that the codec stores the payload in `stored_bytes` (ignored unless smaller).
+    pub fn with_stored(self, stored_bytes: u64) -> Self {
+        match &self.payload {
+            Some(p) if stored_bytes < p.bytes => CapacityInfo {
+                used_percent: Some(percent(stored_bytes, self.capacity.bytes)),
+                compressed: Some(SizeInfo::new(stored_bytes)),
+                ..self
+            },
+            _ => self,
+        }
+    }
+
+    /// Capacity gain from compression: payload size over stored size (1 when uncompressed).
+    pub fn compression_ratio(&self) -> f64 {
+        match (&self.payload, &self.compressed) {
+            (Some(p), Some(c)) if c.bytes > 0 => p.bytes as f64 / c.bytes as f64,
+            _ => 1.0,
+        }
+    }
+
+    /// One-line summary for CLI output, e.g. "1.2 KB of 6 KB (20%)".
+    pub fn summary(&self) -> String {
+        match (&self.payload, self.used_percent) {
+            (Some(p), Some(pct)) => match &self.compressed {
+                Some(c) => format!(
+                    "{} compressed to {} ({}x) of {} ({}%)",
+                    p.human,
+                    c.human,
+                    format_decimal(self.compression_ratio(), decimal_separator()),
+                    self.capacity.human,
+                    format_decimal(pct, decimal_separator())
+                ),
+                None => format!("{} of {} ({}%)", p.human, self.capacity.human, format_decimal(pct, decimal_separator())),
+            },
+            _ => format!("capacity {}", self.capacity.human),
+        }
+    }
+}
+
+/// `part` as a percentage of `whole`, rounded to one decimal (0 when `whole` is 0).
+pub fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    (part as f64 * 1000.0 / whole as f64).round() / 10.0
+}
+
+/// Decimal separator for the user's locale (LC_ALL, LC_NUMERIC, then LANG): "," for locales
+/// that write 1,5 and "." otherwise.
+pub fn decimal_separator() -> char {
+    const COMMA_LANGS: &[&str] = &[
+        "de", "fr", "es", "it", "pt", "nl", "ru", "pl", "sv", "da", "nb", "nn", "fi", "cs", "sk", "tr", "uk", "el", "hu", "ro", "id",
+    ];
+    let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|k| std::env::var(k).ok())
+        .find(|v| !v.is_empty())
+        .unwrap_or_default();
+    let lang = locale.split(['_', '-', '.']).next().unwrap_or("").to_ascii_lowercase();
+    if COMMA_LANGS.contains(&lang.as_str()) {
+        ','
+    } else {
+        '.'
+    }
+}
+
+/// One decimal place, trailing ".0" dropped.
+fn format_decimal(value: f64, separator: char) -> String {
+    let s = format!("{:.1}", value);
+    let s = s.strip_suffix(".0").unwrap_or(&s).to_string();
+    s.replace('.', &separator.to_string())
+}
+
+pub fn format_bytes_with(bytes: u64, separator: char) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{} {}", format_decimal(value, separator), UNITS[unit])
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    format_bytes_with(bytes, decimal_separator())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes_and_percent() {
+        assert_eq!(format_bytes_with(512, '.'), "512 B");
+        assert_eq!(format_bytes_with(1536, '.'), "1.5 KB");
+        assert_eq!(format_bytes_with(1536, ','), "1,5 KB");
+        assert_eq!(format_bytes_with(6 * 1024, '.'), "6 KB");
+        assert_eq!(format_bytes_with(5 * 1024 * 1024 + 300 * 1024, '.'), "5.3 MB");
+        assert_eq!(percent(1, 3), 33.3);
+        assert_eq!(percent(5, 0), 0.0);
+        let packed = CapacityInfo::new("dwt", Some(4096), 2048).with_stored(1024);
+        assert_eq!((packed.used_percent, packed.compression_ratio()), (Some(50.0), 4.0));
+        assert_eq!(CapacityInfo::new("dwt", Some(100), 2048).with_stored(120).compressed, None);
+    }
+}
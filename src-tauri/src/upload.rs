@@ -0,0 +1,65 @@
+// Media upload to nostr.build, matching upload.ts (multipart "file" field, URL in response JSON).
+
+use std::path::Path;
+
+pub const NOSTR_BUILD_UPLOAD_URL: &str = "https://nostr.build/api/v2/upload/files";
+
+fn mime_for(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+/// nostr.build may return { data: [{ url }] }, an array of objects with url, or { url }.
+fn url_from_response(data: &serde_json::Value) -> Option<String> {
+    let candidates = [
+        data.get(0).and_then(|v| v.get("url")),
+        data.get(0),
+        data.get("data").and_then(|d| d.get(0)).and_then(|v| v.get("url")),
+        data.get("url"),
+        data.get("data").and_then(|d| d.get("url")),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .find_map(|v| v.as_str().map(str::to_string))
+}
+
+/// Upload a file and return its public URL.
+pub fn upload_media(path: &Path) -> Result<String, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let filename = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("image.png")
+        .replace('"', "");
+    let boundary = format!("----stegstr{}", hex::encode(rand::random::<[u8; 8]>()));
+    let mut body = Vec::with_capacity(bytes.len() + 256);
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+            boundary,
+            filename,
+            mime_for(path)
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(&bytes);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    let resp = ureq::post(NOSTR_BUILD_UPLOAD_URL)
+        .set("Content-Type", &format!("multipart/form-data; boundary={}", boundary))
+        .send_bytes(&body)
+        .map_err(|e| format!("Upload failed: {}", e))?;
+    let data: serde_json::Value = resp.into_json().map_err(|e| format!("Upload response not JSON: {}", e))?;
+    url_from_response(&data).ok_or_else(|| "Upload response missing URL".to_string())
+}
@@ -0,0 +1,129 @@
+// Watch-folder decoding: poll a directory (e.g. Downloads) for new or changed PNG/JPEG files and
+// try to decode each once its size and mtime have held still for one interval, so half-written
+// downloads are not read. Polling needs no platform watcher and behaves the same on network drives.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+const WATCH_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+pub struct Watcher {
+    dir: PathBuf,
+    /// Files already handed out (or present at start), with the stamp they had then.
+    known: HashMap<PathBuf, FileStamp>,
+    /// New or changed files waiting for a second poll with the same stamp.
+    pending: HashMap<PathBuf, FileStamp>,
+}
+
+impl Watcher {
+    /// Start watching `dir`. Files already there are skipped unless `include_existing`.
+    pub fn new(dir: &Path, include_existing: bool) -> Result<Self, String> {
+        let mut w = Watcher {
+            dir: dir.to_path_buf(),
+            known: HashMap::new(),
+            pending: HashMap::new(),
+        };
+        let current = w.list()?;
+        if !include_existing {
+            w.known = current;
+        }
+        Ok(w)
+    }
+
+    fn list(&self) -> Result<HashMap<PathBuf, FileStamp>, String> {
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| format!("{}: {}", self.dir.display(), e))?;
+        Ok(entries
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                e.path()
+                    .extension()
+                    .and_then(|x| x.to_str())
+                    .is_some_and(|x| WATCH_EXTENSIONS.contains(&x.to_ascii_lowercase().as_str()))
+            })
+            .filter_map(|e| {
+                let meta = e.metadata().ok().filter(|m| m.is_file())?;
+                Some((e.path(), FileStamp { len: meta.len(), modified: meta.modified().ok() }))
+            })
+            .collect())
+    }
+
+    /// Files that appeared or changed and have been stable since the previous poll, sorted.
+    pub fn poll(&mut self) -> Result<Vec<PathBuf>, String> {
+        let current = self.list()?;
+        self.known.retain(|p, _| current.contains_key(p));
+        self.pending.retain(|p, _| current.contains_key(p));
+        let mut ready = Vec::new();
+        for (path, stamp) in current {
+            if self.known.get(&path) == Some(&stamp) {
+                continue;
+            }
+            if self.pending.get(&path) == Some(&stamp) {
+                self.pending.remove(&path);
+                self.known.insert(path.clone(), stamp);
+                ready.push(path);
+            } else {
+                self.pending.insert(path, stamp);
+            }
+        }
+        ready.sort();
+        Ok(ready)
+    }
+}
+
+/// Poll `dir` every `interval` until `stop` is set, decoding each settled file (cheapest codec
+/// first). `on_file` gets every decode attempt: the payload and method, or why nothing was found.
+/// Each file that yields a payload also raises a job notification (notify::notify_job_done).
+pub fn watch<F>(dir: &Path, interval: Duration, include_existing: bool, stop: &AtomicBool, mut on_file: F) -> Result<(), String>
+where
+    F: FnMut(&Path, Result<(Vec<u8>, String), String>),
+{
+    let mut watcher = Watcher::new(dir, include_existing)?;
+    while !stop.load(Ordering::Relaxed) {
+        for path in watcher.poll()? {
+            let decoded = crate::scan::detect_and_decode(&path).map_err(String::from);
+            if let Ok((_, method)) = &decoded {
+                let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+                crate::notify::notify_job_done("Watch folder", &format!("Payload found in {} ({})", name, method));
+            }
+            on_file(&path, decoded);
+        }
+        std::thread::sleep(interval);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watcher_reports_settled_new_files() {
+        let dir = std::env::temp_dir().join(format!("stegstr_watch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("old.png"), b"old").unwrap();
+        let mut w = Watcher::new(&dir, false).unwrap();
+        assert!(w.poll().unwrap().is_empty());
+
+        std::fs::write(dir.join("new.png"), b"partial").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"ignored").unwrap();
+        assert!(w.poll().unwrap().is_empty(), "first sighting waits for a stable stamp");
+        std::fs::write(dir.join("new.png"), b"partial, now complete").unwrap();
+        assert!(w.poll().unwrap().is_empty(), "still growing");
+        assert_eq!(w.poll().unwrap(), vec![dir.join("new.png")]);
+        assert!(w.poll().unwrap().is_empty(), "reported once");
+
+        let mut all = Watcher::new(&dir, true).unwrap();
+        all.poll().unwrap();
+        assert_eq!(all.poll().unwrap(), vec![dir.join("new.png"), dir.join("old.png")]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1266,23 +1266,33 @@ function App({ profile }: { profile: string | null }) {
     logger.logAction("detect_started", "Decoding stego image", { path });
     try {
       const isJpeg = /\.jpe?g$/i.test(path);
-      let result: { ok: boolean; payload?: string; error?: string };
+      type SanitizeNote = { index: number; id?: string | null; notes: string[] };
+      type DecodeResult = { ok: boolean; payload?: string; error?: string; sanitized?: SanitizeNote[]; dropped_events?: number; orientation?: string | null };
+      let result: DecodeResult;
       setStegoProgress("Extracting hidden data (Dot decode)...");
       addStegoLog("Running Dot steganography decode...");
       console.log("[Detect] Trying Dot decode first:", path);
-      result = await tauri.invoke<{ ok: boolean; payload?: string; error?: string }>("decode_stego_dot", { path });
+      result = await tauri.invoke<DecodeResult>("decode_stego_dot", { path });
       console.log("[Detect] Dot result: ok=", result.ok, "error=", result.error ?? "(none)");
       if (!result.ok) {
         addStegoLog(`Dot decode failed: ${result.error ?? "unknown error"}`);
         if (isJpeg) {
           addStegoLog("Falling back to QIM decode (JPEG)...");
           console.log("[Detect] JPEG: falling back to QIM decode:", path);
-          result = await tauri.invoke<{ ok: boolean; payload?: string; error?: string }>("decode_stego_qim", { path });
+          result = await tauri.invoke<DecodeResult>("decode_stego_qim", { path });
           console.log("[Detect] QIM result: ok=", result.ok, "error=", result.error ?? "(none)", "payloadLen=", result.payload?.length ?? 0);
         } else {
           addStegoLog("Falling back to DWT decode (PNG/other)...");
           console.log("[Detect] PNG/other: falling back to DWT decode:", path);
-          result = await tauri.invoke<{ ok: boolean; payload?: string; error?: string }>("decode_stego_image", { path });
+          const taskId = `decode-${Date.now()}`;
+          const unlistenProgress = await tauri.listen<{ task_id: string; percent: number }>("stego://progress", (e) => {
+            if (e.payload.task_id === taskId) setStegoProgress(`Extracting hidden data (DWT decode)... ${e.payload.percent}%`);
+          });
+          try {
+            result = await tauri.invoke<DecodeResult>("decode_stego_image", { path, taskId });
+          } finally {
+            unlistenProgress();
+          }
           console.log("[Detect] DWT result: ok=", result.ok, "error=", result.error ?? "(none)");
         }
       }
@@ -1294,6 +1304,7 @@ function App({ profile }: { profile: string | null }) {
         return;
       }
       addStegoLog(`Dot decode OK! Payload: ${result.payload.length} chars`);
+      if (result.orientation) addStegoLog(`Image was turned (${result.orientation}); decoded after undoing it`);
       let jsonString: string;
       const raw = result.payload;
       if (raw.startsWith("base64:")) {
@@ -1334,6 +1345,19 @@ function App({ profile }: { profile: string | null }) {
         logger.logAction("detect_error", "Invalid payload", { path });
         return;
       }
+      // Safe mode: plaintext bundles were cleaned by the decode command; decrypted ones are cleaned here.
+      let notes = result.sanitized ?? [];
+      if (raw.startsWith("base64:")) {
+        const safe = await tauri.invoke<{ bundle: unknown; notes: SanitizeNote[]; dropped_events: number }>("sanitize_bundle", {
+          bundleJson: jsonString,
+        });
+        jsonString = JSON.stringify(safe.bundle);
+        notes = safe.notes;
+        if (safe.dropped_events > 0) addStegoLog(`Safe mode: dropped ${safe.dropped_events} event(s)`);
+      } else if (result.dropped_events) {
+        addStegoLog(`Safe mode: dropped ${result.dropped_events} event(s)`);
+      }
+      notes.forEach((n) => addStegoLog(`Safe mode: event ${n.id ?? `#${n.index}`}: ${n.notes.join("; ")}`));
       const bundle = JSON.parse(jsonString) as NostrStateBundle;
       if (!Array.isArray(bundle.events)) {
         setDecodeError("Invalid payload");
@@ -1350,6 +1374,8 @@ function App({ profile }: { profile: string | null }) {
         normalized.forEach((e) => byId.set(e.id, e));
         return Array.from(byId.values()).sort((a, b) => b.created_at - a.created_at);
       });
+      // Plaintext bundles were stored by the decode command as it parsed them; decrypted ones are stored here.
+      if (raw.startsWith("base64:")) tauri.invoke("store_decoded_events", { events: bundle.events, source: path }).catch(() => {});
       const profileUpdates: Record<string, ProfileData> = {};
       bundle.events.filter((e) => e.kind === 0).forEach((e) => {
         try {
@@ -1438,7 +1464,7 @@ function App({ profile }: { profile: string | null }) {
       const tauri = await getTauri();
       const coverPath = await tauri.openDialog({
         multiple: false,
-        filters: [{ name: "Images", extensions: ["png", "jpg", "jpeg", "gif", "webp", "bmp"] }],
+        filters: [{ name: "Images", extensions: await tauri.invoke<string[]>("supported_cover_extensions") }],
       });
       if (!coverPath || typeof coverPath !== "string") {
         setDetecting(false);
@@ -1450,10 +1476,11 @@ function App({ profile }: { profile: string | null }) {
       const encrypted = await stegoCrypto.encryptOpen(jsonString);
       const payloadToEmbed = "base64:" + uint8ArrayToBase64(encrypted);
       const cmd = "encode_stego_dot";
-      const result = await tauri.invoke<{ ok: boolean; path?: string; error?: string }>(cmd, {
+      const result = await tauri.invoke<{ ok: boolean; path?: string; error?: string; verified?: boolean | null; settings?: string | null; failed_attempts?: string[] }>(cmd, {
         coverPath,
         outputPath,
         payload: payloadToEmbed,
+        overwrite: true,
       });
       if (result.ok && result.path) {
         try {
@@ -1462,6 +1489,8 @@ function App({ profile }: { profile: string | null }) {
         } catch (e) {
           addStegoLog(`PNG signature check error: ${e instanceof Error ? e.message : String(e)}`);
         }
+        for (const failed of result.failed_attempts ?? []) addStegoLog(`Retried after: ${failed}`);
+        if (result.verified) addStegoLog(`Read-back verification: OK (${result.settings})`);
         addStegoLog(`Saved to: ${result.path}`);
         setStatus(`Saved to exchange. B can click Detect from exchange.`);
         logger.logAction("embed_completed", "Embed to exchange done", { path: result.path, eventCount: events.length });
@@ -1663,7 +1692,7 @@ function App({ profile }: { profile: string | null }) {
       const tauri = await getTauri();
       const coverPath = await tauri.openDialog({
         multiple: false,
-        filters: [{ name: "Images", extensions: ["png", "jpg", "jpeg", "gif", "webp", "bmp"] }],
+        filters: [{ name: "Images", extensions: await tauri.invoke<string[]>("supported_cover_extensions") }],
       });
       if (!coverPath || typeof coverPath !== "string") {
         setEmbedModalOpen(false);
@@ -1739,10 +1768,11 @@ function App({ profile }: { profile: string | null }) {
       const payloadToEmbed = "base64:" + uint8ArrayToBase64(payloadBytes);
       setStegoProgress("Embedding with Dot (offset, robust)...");
       const cmd = "encode_stego_dot";
-      const result = await tauri.invoke<{ ok: boolean; path?: string; error?: string }>(cmd, {
+      const result = await tauri.invoke<{ ok: boolean; path?: string; error?: string; verified?: boolean | null; settings?: string | null; failed_attempts?: string[] }>(cmd, {
         coverPath,
         outputPath: finalOutputPath,
         payload: payloadToEmbed,
+        overwrite: true,
       });
       setEmbedModalOpen(false);
       if (result.ok && result.path) {
@@ -1752,6 +1782,8 @@ function App({ profile }: { profile: string | null }) {
         } catch (e) {
           addStegoLog(`PNG signature check error: ${e instanceof Error ? e.message : String(e)}`);
         }
+        for (const failed of result.failed_attempts ?? []) addStegoLog(`Retried after: ${failed}`);
+        if (result.verified) addStegoLog(`Read-back verification: OK (${result.settings})`);
         addStegoLog(`Saved to: ${result.path}`);
         setStatus(`Saved to ${result.path}. Finder opened.`);
         logger.logAction("embed_completed", "Embed saved successfully", { path: result.path, eventCount: events.length });
@@ -2,7 +2,7 @@ import type React from "react";
 import { NoteThread } from "./NoteCard";
 import type { NoteCardActions, NoteCardState } from "./NoteCard";
 import type { NostrEvent, ProfileData, View } from "./types";
-import { MAX_NOTE_USER_CONTENT } from "./constants";
+import { MAX_NOTE_USER_CONTENT, noteLength } from "./constants";
 
 export type FeedItem = { type: "note"; note: NostrEvent; sortAt: number } | { type: "repost"; repost: NostrEvent; note: NostrEvent; sortAt: number };
 
@@ -102,7 +102,7 @@ export function FeedView({
             </button>
             <button type="button" onClick={handlePost} className="btn-primary" disabled={(!newPost.trim() && postMediaUrls.length === 0) || uploadingMedia}>Post</button>
           </div>
-          <p className="muted char-counter">{newPost.length}/{MAX_NOTE_USER_CONTENT} (appends &quot; Sent by Stegstr.&quot;)</p>
+          <p className="muted char-counter">{noteLength(newPost)}/{MAX_NOTE_USER_CONTENT} (appends &quot; Sent by Stegstr.&quot;)</p>
         </div>
       </section>
 
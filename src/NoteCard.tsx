@@ -1,5 +1,5 @@
 import { extractImageUrls, mediaUrlsFromTags, isVideoUrl, contentWithoutImages } from "./utils";
-import { MAX_NOTE_USER_CONTENT } from "./constants";
+import { MAX_NOTE_USER_CONTENT, noteLength } from "./constants";
 import type { NostrEvent, ProfileData } from "./types";
 
 /** Callbacks the NoteCard may invoke. All are optional — omit to hide that action. */
@@ -253,7 +253,7 @@ export function NoteReplyList({
             <div className="reply-box reply-box-inline">
               <p className="muted">Replying to {(profiles[replyingTo.pubkey]?.name ?? replyingTo.pubkey.slice(0, 8))}…</p>
               <textarea value={replyContent} onChange={(e) => onReplyContentChange(e.target.value)} placeholder="Write a reply…" rows={2} className="wide" maxLength={MAX_NOTE_USER_CONTENT} />
-              <p className="muted char-counter">{replyContent.length}/{MAX_NOTE_USER_CONTENT}</p>
+              <p className="muted char-counter">{noteLength(replyContent)}/{MAX_NOTE_USER_CONTENT}</p>
               <div className="row">
                 <button type="button" onClick={onReplyCancel}>Cancel</button>
                 <button type="button" onClick={onReplySend} className="btn-primary">Reply</button>
@@ -325,7 +325,7 @@ export function NoteThread({
         <div className="reply-box">
           <p className="muted">Replying to {(profiles[replyingTo.pubkey]?.name ?? replyingTo.pubkey.slice(0, 8))}…</p>
           <textarea value={replyContent} onChange={(e) => onReplyContentChange(e.target.value)} placeholder="Write a reply…" rows={2} className="wide" maxLength={MAX_NOTE_USER_CONTENT} />
-          <p className="muted char-counter">{replyContent.length}/{MAX_NOTE_USER_CONTENT}</p>
+          <p className="muted char-counter">{noteLength(replyContent)}/{MAX_NOTE_USER_CONTENT}</p>
           <div className="row">
             <button type="button" onClick={onReplyCancel}>Cancel</button>
             <button type="button" onClick={onReplySend} className="btn-primary">Reply</button>
@@ -65,4 +65,19 @@ describe("stego-crypto", () => {
     const decrypted = await decryptApp(encrypted);
     expect(decrypted).toBe(json);
   });
+
+  it("bundle codec packs on encrypt and unpacks v2 on decrypt", async () => {
+    const { encryptApp, decryptApp, setBundleCodec } = await import("../stego-crypto");
+    const json = JSON.stringify({ version: 1, events: [] });
+    setBundleCodec({ pack: async () => new Uint8Array([2, 0, 0x90]), unpack: async () => json });
+    let v2: Uint8Array;
+    try {
+      v2 = await encryptApp(json);
+      expect(await decryptApp(v2)).toBe(json);
+    } finally {
+      setBundleCodec(null);
+    }
+    expect(v2.length).toBeLessThan((await encryptApp(json)).length);
+    await expect(decryptApp(v2)).rejects.toThrow("v2 bundle");
+  });
 });
@@ -1,23 +1,24 @@
 /** Suffix appended to all Nostr kind 1 posts published through Stegstr */
 export const STEGSTR_SUFFIX = " Sent by Stegstr.";
 
-/** Max length for published note (common Nostr client limit) */
+/** Max length for published note (common Nostr client limit), in code points after NFC normalization */
 export const MAX_NOTE_LENGTH = 5000;
 
 /** Max characters the user can type; remainder reserved for STEGSTR_SUFFIX */
 export const MAX_NOTE_USER_CONTENT = MAX_NOTE_LENGTH - STEGSTR_SUFFIX.length;
 
+/** Length of a note as counted against MAX_NOTE_LENGTH (code points, not UTF-16 units). */
+export function noteLength(content: string): number {
+  return Array.from(content.normalize("NFC")).length;
+}
+
 /**
  * Ensures Nostr kind 1 content ends with " Sent by Stegstr.".
- * Appends suffix if missing; truncates to MAX_NOTE_LENGTH if over.
+ * Normalizes to NFC; if over MAX_NOTE_LENGTH, cuts the text on a code point boundary
+ * before the suffix so the suffix is always kept. Matches nostr_event::ensure_stegstr_suffix.
  */
 export function ensureStegstrSuffix(content: string): string {
-  let result = content;
-  if (!result.endsWith(STEGSTR_SUFFIX)) {
-    result = result + STEGSTR_SUFFIX;
-  }
-  if (result.length > MAX_NOTE_LENGTH) {
-    result = result.slice(0, MAX_NOTE_LENGTH);
-  }
-  return result;
+  const normalized = content.normalize("NFC");
+  const body = normalized.endsWith(STEGSTR_SUFFIX) ? normalized.slice(0, -STEGSTR_SUFFIX.length) : normalized;
+  return Array.from(body).slice(0, MAX_NOTE_USER_CONTENT).join("") + STEGSTR_SUFFIX;
 }
@@ -2,11 +2,15 @@
  * Desktop (Tauri) platform: lazy-load Tauri APIs so web build never loads them.
  */
 
+import { setBundleCodec } from "./stego-crypto";
+import { uint8ArrayToBase64 } from "./utils";
+
 let cached: Promise<{
   invoke: typeof import("@tauri-apps/api/core").invoke;
   openDialog: typeof import("@tauri-apps/plugin-dialog").open;
   saveDialog: typeof import("@tauri-apps/plugin-dialog").save;
   getCurrentWindow: typeof import("@tauri-apps/api/window").getCurrentWindow;
+  listen: typeof import("@tauri-apps/api/event").listen;
 }> | null = null;
 
 export async function getTauri(): Promise<{
@@ -14,17 +18,28 @@ export async function getTauri(): Promise<{
   openDialog: typeof import("@tauri-apps/plugin-dialog").open;
   saveDialog: typeof import("@tauri-apps/plugin-dialog").save;
   getCurrentWindow: typeof import("@tauri-apps/api/window").getCurrentWindow;
+  listen: typeof import("@tauri-apps/api/event").listen;
 }> {
   if (!cached) {
     cached = (async () => {
       const core = await import("@tauri-apps/api/core");
       const dialog = await import("@tauri-apps/plugin-dialog");
       const win = await import("@tauri-apps/api/window");
+      const event = await import("@tauri-apps/api/event");
+      // Bundles are packed (v1 JSON, the default format) and unpacked (v2 too) by the Rust side.
+      setBundleCodec({
+        pack: async (plaintext) => {
+          const packed = await core.invoke<string>("pack_bundle", { json: plaintext });
+          return Uint8Array.from(atob(packed.slice("base64:".length)), (c) => c.charCodeAt(0));
+        },
+        unpack: (packed) => core.invoke<string>("unpack_bundle", { data: "base64:" + uint8ArrayToBase64(packed) }),
+      });
       return {
         invoke: core.invoke,
         openDialog: dialog.open,
         saveDialog: dialog.save,
         getCurrentWindow: win.getCurrentWindow,
+        listen: event.listen,
       };
     })();
   }
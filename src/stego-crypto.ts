@@ -12,6 +12,37 @@ const APP_KEY_SALT = "stegstr-decrypt-v1";
 
 let cachedAppKey: CryptoKey | null = null;
 
+/** First byte of a bundle v2 plaintext (binary, compressed; see stegstr-core bundle.rs). JSON never starts with it. */
+const BUNDLE_V2 = 2;
+
+/** Converts bundle JSON to and from its stored form; the desktop app registers one backed by Rust. */
+export interface BundleCodec {
+  /** Bytes to encrypt for `plaintext` in the stored bundle format (bundle::pack; v1 JSON by default). */
+  pack(plaintext: string): Promise<Uint8Array>;
+  /** v1 JSON for a decrypted bundle v2 plaintext. */
+  unpack(packed: Uint8Array): Promise<string>;
+}
+
+let bundleCodec: BundleCodec | null = null;
+
+/** Without a codec (web build) bundles are encrypted as JSON and v2 plaintexts cannot be read. */
+export function setBundleCodec(codec: BundleCodec | null): void {
+  bundleCodec = codec;
+}
+
+async function plaintextBytes(plaintext: string): Promise<Uint8Array> {
+  return bundleCodec ? bundleCodec.pack(plaintext) : new TextEncoder().encode(plaintext);
+}
+
+async function plaintextString(dec: ArrayBuffer): Promise<string> {
+  const bytes = new Uint8Array(dec);
+  if (bytes[0] === BUNDLE_V2) {
+    if (!bundleCodec) throw new Error("Payload is a v2 bundle, which this build cannot read; open it in the desktop app");
+    return bundleCodec.unpack(bytes);
+  }
+  return new TextDecoder().decode(bytes);
+}
+
 async function getAppKey(): Promise<CryptoKey> {
   if (cachedAppKey) return cachedAppKey;
   const msg = new TextEncoder().encode(APP_KEY_SALT);
@@ -30,7 +61,7 @@ async function getAppKey(): Promise<CryptoKey> {
 export async function encryptApp(plaintext: string): Promise<Uint8Array> {
   const key = await getAppKey();
   const iv = crypto.getRandomValues(new Uint8Array(12));
-  const encoded = new TextEncoder().encode(plaintext);
+  const encoded = await plaintextBytes(plaintext);
   const ciphertext = await crypto.subtle.encrypt(
     { name: "AES-GCM", iv, tagLength: 128 },
     key,
@@ -70,7 +101,7 @@ export async function decryptApp(encrypted: Uint8Array): Promise<string> {
       ciphertext
     );
     console.log("[stego-crypto] decryptApp: SUCCESS, decrypted len=", dec.byteLength);
-    return new TextDecoder().decode(dec);
+    return await plaintextString(dec);
   } catch (e) {
     console.error("[stego-crypto] decryptApp: AES-GCM decrypt FAILED:", e);
     throw e;
@@ -109,7 +140,7 @@ export async function encryptForRecipients(
   const ciphertext = await crypto.subtle.encrypt(
     { name: "AES-GCM", iv, tagLength: 128 },
     key,
-    new TextEncoder().encode(jsonString)
+    await plaintextBytes(jsonString)
   );
   const ctWithIv = new Uint8Array(iv.length + ciphertext.byteLength);
   ctWithIv.set(iv, 0);
@@ -164,7 +195,7 @@ export async function decryptPayload(
       key,
       ciphertext
     );
-    return new TextDecoder().decode(dec);
+    return plaintextString(dec);
   }
   return inner;
 }
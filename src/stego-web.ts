@@ -2,6 +2,7 @@
  * DWT (Haar 2D) steganography in TypeScript for browser.
  * Same format as Rust: magic "STEGSTR" + 4-byte big-endian length + payload.
  * Embeds in LSB of LH coefficients. Tile-based (256x256) for crop survival.
+ * Reads and writes the legacy frame only; Reed-Solomon frames (Rust, parity > 0) need the app.
  */
 
 import { decodePngToRGBA } from "./png-decode";
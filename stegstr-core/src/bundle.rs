@@ -0,0 +1,250 @@
+// Bundle encodings and merging. A v1 bundle is the JSON `{ "version": 1, "events": [...] }`; v2
+// stores the same events in a fraction of the capacity: a version byte (V2), a compression byte
+// (payload_header ids; zstd when the `zstd` feature is on and it helps) and the events as
+// MessagePack arrays [id, pubkey, created_at, kind, tags, content, sig] (rmp-serde), with the hex
+// fields as raw bytes. JSON never starts with V2, so readers tell the two apart by the first byte.
+// Writers pick the format with `pack`, for plaintext and encrypted payloads alike; v1 stays the
+// default until every reader (the web build included) decodes v2. `unpack` turns either back
+// into v1 JSON, so callers never see v2.
+//
+// Merging combines bundles decoded from several images: images from the same author usually carry
+// overlapping slices of their history, so the merge keeps each event once, drops any whose id or
+// signature does not check out, and orders the result oldest first.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::error::StegoError;
+use crate::nostr_event::{verify_event, Bundle, NostrEvent, BUNDLE_VERSION};
+use crate::payload_header::Compression;
+
+/// First byte of a v2 bundle.
+pub const V2: u8 = 2;
+
+/// Format `pack` writes bundles in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleFormat {
+    /// JSON, readable by every build.
+    #[default]
+    V1,
+    /// Binary and compressed; builds before bundle v2 (and the web build) cannot read it.
+    V2,
+}
+
+/// A hex id, pubkey or signature: raw bytes (MessagePack bin) when it is canonical lowercase hex
+/// of the expected length, else the string as is, so it reads back unchanged.
+enum Hex {
+    Raw(Vec<u8>),
+    Text(String),
+}
+
+impl Serialize for Hex {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Hex::Raw(raw) => serializer.serialize_bytes(raw),
+            Hex::Text(s) => serializer.serialize_str(s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Hex {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HexVisitor;
+        impl serde::de::Visitor<'_> for HexVisitor {
+            type Value = Hex;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("bytes or a string")
+            }
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Hex, E> {
+                Ok(Hex::Raw(v.to_vec()))
+            }
+            fn visit_str<E>(self, v: &str) -> Result<Hex, E> {
+                Ok(Hex::Text(v.to_string()))
+            }
+        }
+        deserializer.deserialize_any(HexVisitor)
+    }
+}
+
+impl Hex {
+    fn new(s: &str, bytes: usize) -> Self {
+        let canonical = s.len() == bytes * 2 && s.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+        match canonical.then(|| hex::decode(s).ok()).flatten() {
+            Some(raw) => Hex::Raw(raw),
+            None => Hex::Text(s.to_string()),
+        }
+    }
+
+    fn into_string(self) -> String {
+        match self {
+            Hex::Raw(raw) => hex::encode(raw),
+            Hex::Text(s) => s,
+        }
+    }
+}
+
+/// An event as a v2 MessagePack array.
+#[derive(Serialize, Deserialize)]
+struct WireEvent(Hex, Hex, u64, u32, Vec<Vec<String>>, String, Hex);
+
+impl From<&NostrEvent> for WireEvent {
+    fn from(e: &NostrEvent) -> Self {
+        WireEvent(Hex::new(&e.id, 32), Hex::new(&e.pubkey, 32), e.created_at, e.kind, e.tags.clone(), e.content.clone(), Hex::new(&e.sig, 64))
+    }
+}
+
+impl From<WireEvent> for NostrEvent {
+    fn from(WireEvent(id, pubkey, created_at, kind, tags, content, sig): WireEvent) -> Self {
+        NostrEvent { id: id.into_string(), pubkey: pubkey.into_string(), created_at, kind, tags, content, sig: sig.into_string() }
+    }
+}
+
+/// True if `bytes` start like a v2 bundle (see `decode_v2` for a full check).
+pub fn is_v2(bytes: &[u8]) -> bool {
+    bytes.first() == Some(&V2)
+}
+
+/// The v2 encoding of `bundle` (its `version` field is implied).
+pub fn encode_v2(bundle: &Bundle) -> Vec<u8> {
+    let events: Vec<WireEvent> = bundle.events.iter().map(WireEvent::from).collect();
+    let body = rmp_serde::to_vec(&events).expect("MessagePack encoding of owned events cannot fail");
+    let (compression, body) = compress(body);
+    let mut out = Vec::with_capacity(2 + body.len());
+    out.push(V2);
+    out.push(compression.id());
+    out.extend_from_slice(&body);
+    out
+}
+
+#[cfg(feature = "zstd")]
+fn compress(body: Vec<u8>) -> (Compression, Vec<u8>) {
+    match zstd::encode_all(body.as_slice(), crate::payload_header::ZSTD_LEVEL) {
+        Ok(packed) if packed.len() < body.len() => (Compression::Zstd, packed),
+        _ => (Compression::None, body),
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress(body: Vec<u8>) -> (Compression, Vec<u8>) {
+    (Compression::None, body)
+}
+
+/// Parse a v2 bundle.
+pub fn decode_v2(bytes: &[u8]) -> Result<Bundle, StegoError> {
+    let corrupt = |what: &dyn std::fmt::Display| StegoError::NotStego(format!("Bundle v2 corrupt: {}", what));
+    let [V2, compression, body @ ..] = bytes else {
+        return Err(StegoError::NotStego("Not a v2 bundle".to_string()));
+    };
+    let body = match Compression::from_id(*compression) {
+        Some(Compression::None) => body.to_vec(),
+        Some(Compression::Zstd) => crate::payload_header::zstd_unpack(body)?,
+        None => return Err(corrupt(&format!("unknown compression {}", compression))),
+    };
+    let mut reader = std::io::Cursor::new(body.as_slice());
+    let events: Vec<WireEvent> = rmp_serde::from_read(&mut reader).map_err(|e| corrupt(&e))?;
+    if reader.position() != body.len() as u64 {
+        return Err(corrupt(&"trailing bytes"));
+    }
+    Ok(Bundle::new(events.into_iter().map(NostrEvent::from).collect()))
+}
+
+/// Parse a bundle in either format.
+pub fn decode(bytes: &[u8]) -> Result<Bundle, StegoError> {
+    match is_v2(bytes) {
+        true => decode_v2(bytes),
+        false => serde_json::from_slice(bytes).map_err(|e| StegoError::NotStego(format!("Invalid bundle: {}", e))),
+    }
+}
+
+/// What to store for `plaintext` (plaintext and encrypted payloads alike): with `V2`, its v2
+/// encoding when it is a v1 bundle that v2 holds exactly (no extra fields) in fewer bytes; else
+/// the bytes as they are.
+pub fn pack(plaintext: &[u8], format: BundleFormat) -> Vec<u8> {
+    let packed = || {
+        let value: serde_json::Value = serde_json::from_slice(plaintext).ok()?;
+        let bundle: Bundle = serde_json::from_value(value.clone()).ok()?;
+        if bundle.version != BUNDLE_VERSION || serde_json::to_value(&bundle).ok()? != value {
+            return None;
+        }
+        Some(encode_v2(&bundle)).filter(|v2| v2.len() < plaintext.len())
+    };
+    match format {
+        BundleFormat::V1 => plaintext.to_vec(),
+        BundleFormat::V2 => packed().unwrap_or_else(|| plaintext.to_vec()),
+    }
+}
+
+/// Undo `pack`: a v2 bundle comes back as compact v1 JSON; anything else must be UTF-8 text.
+pub fn unpack(stored: Vec<u8>) -> Result<String, StegoError> {
+    if is_v2(&stored) {
+        let bundle = decode_v2(&stored)?;
+        return serde_json::to_string(&bundle).map_err(|e| StegoError::Other(e.to_string()));
+    }
+    String::from_utf8(stored).map_err(|e| StegoError::Other(e.to_string()))
+}
+
+/// One bundle holding every valid event of `bundles`, deduplicated by id and sorted by
+/// created_at (ties by id, so the result does not depend on input order).
+pub fn merge(bundles: Vec<Bundle>) -> Bundle {
+    let mut ids = HashSet::new();
+    let mut events: Vec<NostrEvent> = bundles
+        .into_iter()
+        .flat_map(|b| b.events)
+        .filter(|e| verify_event(e).is_ok() && ids.insert(e.id.clone()))
+        .collect();
+    events.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+    Bundle::new(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr_event::sign_event_at;
+
+    #[test]
+    fn test_merge_dedupes_sorts_and_drops_invalid() {
+        let sk = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let e1 = sign_event_at(1, vec![], "one", 100, &sk).unwrap();
+        let e2 = sign_event_at(1, vec![], "two", 200, &sk).unwrap();
+        let e3 = sign_event_at(1, vec![], "three", 300, &sk).unwrap();
+        let mut forged = e2.clone();
+        forged.content = "forged".to_string();
+
+        let a = Bundle::new(vec![e3.clone(), forged, e1.clone()]);
+        let b = Bundle::new(vec![e2.clone(), e3.clone()]);
+        let merged = merge(vec![a, b]);
+        assert_eq!(merged, Bundle::new(vec![e1, e2, e3]));
+        assert!(merge(vec![]).events.is_empty());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_v2_round_trips_and_is_smaller() {
+        let sk = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let long = "a longer note that repeats itself. ".repeat(20);
+        let tags = vec![vec!["p".to_string(), "ab".repeat(32)], vec!["t".to_string(), "stegstr".to_string()]];
+        let mut odd = sign_event_at(1, vec![], "odd", 70_000, &sk).unwrap();
+        odd.sig = "NOT-HEX".to_string();
+        let bundle = Bundle::new(vec![
+            sign_event_at(0, vec![], "{}", 1_700_000_000, &sk).unwrap(),
+            sign_event_at(1, tags, &long, 1_700_000_001, &sk).unwrap(),
+            odd,
+        ]);
+        let json = serde_json::to_string(&bundle).unwrap();
+        let v2 = encode_v2(&bundle);
+        assert!(is_v2(&v2) && v2.len() < json.len() / 2, "{} vs {}", v2.len(), json.len());
+        assert_eq!(decode_v2(&v2).unwrap(), bundle);
+        assert_eq!(decode(json.as_bytes()).unwrap(), decode(&v2).unwrap());
+
+        assert_eq!(pack(json.as_bytes(), BundleFormat::V2), v2);
+        assert_eq!(unpack(pack(json.as_bytes(), BundleFormat::V2)).unwrap(), json);
+        assert_eq!(pack(json.as_bytes(), BundleFormat::default()), json.as_bytes());
+        let extra = json.replacen("{", "{\"note\":1,", 1);
+        assert_eq!(pack(extra.as_bytes(), BundleFormat::V2), extra.as_bytes());
+        assert_eq!(unpack(pack(b"plain text", BundleFormat::V2)).unwrap(), "plain text");
+        assert!(decode_v2(&v2[..v2.len() - 1]).is_err());
+        assert!(decode_v2(&[V2, 0, 0xdd, 0xff, 0xff, 0xff, 0xff]).is_err());
+    }
+}
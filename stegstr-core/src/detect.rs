@@ -0,0 +1,56 @@
+// In-memory detection over the core codecs, for callers without file access (the WASM viewer).
+// Only dot and DWT live here; QIM, synth and external codecs are app-side (src-tauri scan).
+
+use crate::error::StegoError;
+use crate::stego_key::StegoKey;
+use crate::{stego, stego_dot};
+
+/// Core codecs in the order `detect_bytes` tries them (cheapest first, as scan::COST_ORDER).
+pub const METHODS: &[&str] = &["dot", "dwt"];
+
+/// Payload and the codec that found it, for image file contents held in memory. Unreadable
+/// images fail with their image error; images neither codec decodes with NotStego.
+pub fn detect_bytes(image: &[u8], key: Option<&StegoKey>) -> Result<(Vec<u8>, &'static str), StegoError> {
+    for &method in METHODS {
+        let decoded = match method {
+            "dot" => stego_dot::decode_bytes(image, key),
+            _ => stego::decode_bytes_with_key(image, key),
+        };
+        match decoded {
+            Ok(payload) => return Ok((payload, method)),
+            Err(e @ (StegoError::Io(_) | StegoError::Image(_))) => return Err(e),
+            Err(_) => {}
+        }
+    }
+    Err(StegoError::NotStego(format!("No Stegstr payload found (tried {})", METHODS.join(", "))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_bytes_finds_each_core_codec() {
+        let cover = image::RgbImage::from_fn(300, 300, |x, y| image::Rgb([(x % 200) as u8 + 20, (y % 200) as u8 + 20, 128]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        cover.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let png = png.into_inner();
+
+        let dwt = stego::encode_bytes(&png, b"in memory").unwrap();
+        assert_eq!(detect_bytes(&dwt, None).unwrap(), (b"in memory".to_vec(), "dwt"));
+        let key = StegoKey::from_password("pw").unwrap();
+        let dot = stego_dot::encode_bytes(
+            &png,
+            b"keyed dot",
+            &stego_dot::EncodeOptions {
+                key: Some(key),
+                format: crate::output::OutputFormat::Webp,
+                ..Default::default()
+            },
+        ).unwrap();
+        assert_eq!(detect_bytes(&dot, Some(&key)).unwrap(), (b"keyed dot".to_vec(), "dot"));
+        assert!(matches!(detect_bytes(&dot, None), Err(StegoError::NotStego(_))));
+        assert!(matches!(detect_bytes(&png, None), Err(StegoError::NotStego(_))));
+        assert!(matches!(detect_bytes(b"not an image", None), Err(StegoError::Image(_))));
+    }
+}
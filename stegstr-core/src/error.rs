@@ -0,0 +1,119 @@
+// Typed errors of the codecs (stego, stego_dot) and payload crypto (stego_crypto). Each kind has
+// a stable code that the Tauri result structs carry as `error_code`, so the frontend can branch on
+// capacity vs. not-a-stego-image vs. IO without parsing messages. Messages are unchanged from the
+// String errors these replaced; modules still on String convert both ways with `?`.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StegoError {
+    /// Reading or writing a file failed.
+    #[error("{0}")]
+    Io(String),
+    /// The image could not be decoded or encoded (unsupported or corrupt file).
+    #[error("{0}")]
+    Image(String),
+    /// The payload does not fit the cover (or the cover is too small for the codec).
+    #[error("{0}")]
+    Capacity(String),
+    /// No payload found: not a Stegstr image, wrong codec or stego key, or too damaged.
+    #[error("{0}")]
+    NotStego(String),
+    /// The payload is encrypted and needs a passphrase or private key that was not given.
+    #[error("{0}")]
+    KeyRequired(String),
+    /// Decryption failed: wrong passphrase or key, or a corrupted ciphertext.
+    #[error("{0}")]
+    Decrypt(String),
+    /// An option, key or argument was malformed.
+    #[error("{0}")]
+    InvalidInput(String),
+    /// The caller cancelled the run (progress::Progress::cancel).
+    #[error("{0}")]
+    Cancelled(String),
+    /// Anything else (errors passed through from String-typed modules).
+    #[error("{0}")]
+    Other(String),
+}
+
+/// `StegoError` kinds as serialized in `error_code`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Io,
+    Image,
+    Capacity,
+    NotStego,
+    KeyRequired,
+    Decrypt,
+    InvalidInput,
+    Cancelled,
+    Other,
+}
+
+impl StegoError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            StegoError::Io(_) => ErrorCode::Io,
+            StegoError::Image(_) => ErrorCode::Image,
+            StegoError::Capacity(_) => ErrorCode::Capacity,
+            StegoError::NotStego(_) => ErrorCode::NotStego,
+            StegoError::KeyRequired(_) => ErrorCode::KeyRequired,
+            StegoError::Decrypt(_) => ErrorCode::Decrypt,
+            StegoError::InvalidInput(_) => ErrorCode::InvalidInput,
+            StegoError::Cancelled(_) => ErrorCode::Cancelled,
+            StegoError::Other(_) => ErrorCode::Other,
+        }
+    }
+}
+
+impl From<StegoError> for String {
+    fn from(e: StegoError) -> String {
+        e.to_string()
+    }
+}
+
+impl From<String> for StegoError {
+    fn from(message: String) -> Self {
+        StegoError::Other(message)
+    }
+}
+
+impl From<&str> for StegoError {
+    fn from(message: &str) -> Self {
+        StegoError::Other(message.to_string())
+    }
+}
+
+impl From<std::io::Error> for StegoError {
+    fn from(e: std::io::Error) -> Self {
+        StegoError::Io(e.to_string())
+    }
+}
+
+impl From<image::ImageError> for StegoError {
+    fn from(e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::IoError(io) => StegoError::Io(io.to_string()),
+            other => StegoError::Image(other.to_string()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "crypto"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_serialize_stably() {
+        let e = StegoError::NotStego("Not a Stegstr image (magic not found)".to_string());
+        assert_eq!(e.to_string(), "Not a Stegstr image (magic not found)");
+        assert_eq!(serde_json::to_value(e.code()).unwrap(), "not_stego");
+        assert_eq!(serde_json::to_value(StegoError::KeyRequired(String::new()).code()).unwrap(), "key_required");
+        let missing = image::open("/nonexistent/stegstr.png").unwrap_err();
+        assert_eq!(StegoError::from(missing).code(), ErrorCode::Io);
+        let s: String = StegoError::Capacity("too big".to_string()).into();
+        assert_eq!(s, "too big");
+    }
+}
@@ -0,0 +1,363 @@
+// Fiducials for photographed dot images (screen or print shot with a camera).
+// Encode draws a marker in each corner: a 12 px black square in a 20 px white box, centred 10 px
+// in from both edges. Along the top edge, between 20% and 80% of the span between the top marker
+// centres, a strip of 64 black/white modules carries the image's width and height (u16 BE each)
+// plus 4 Reed-Solomon parity bytes; the photo alone does not tell the decoder the original size.
+// Decode thresholds the photo (Otsu), finds the dark square nearest each corner that has a light
+// ring around it, fits the homography from the marker-centre unit square to those four points,
+// reads the size strip and resamples the image at its original size (nearest neighbour) so the
+// dot grid lines up again. The markers and strip sit in masked areas, so no dot cell is written
+// under them.
+// Calibration border (`calibrate`): instead of drawing over the cover, it is framed by a BORDER px
+// white quiet zone holding the markers and size strip (of the framed image), plus timing patterns
+// in the bottom and right borders: modules one grid pitch long, alternating dark and light from
+// the cover's edge. A screenshot or photo is straightened by the markers as above; the timing
+// patterns then fix the cover's origin to the pixel (`calibrated_inner`) before the border is cut
+// off, and also pick out a framed image stored as is.
+
+use image::RgbImage;
+
+use crate::error::StegoError;
+use crate::stego::Region;
+
+/// White box around each marker, in pixels.
+const MARKER_BOX: u32 = 20;
+/// Black square inside the box (inset (MARKER_BOX - MARKER_INK) / 2 on every side).
+const MARKER_INK: u32 = 12;
+/// Marker centre's distance from the image edges.
+const MARKER_CENTER: f64 = MARKER_BOX as f64 / 2.0;
+/// Size strip: width and height, then parity.
+const SIZE_BYTES: usize = 4;
+const SIZE_NSYM: usize = 4;
+const STRIP_MODULES: usize = (SIZE_BYTES + SIZE_NSYM) * 8;
+/// Strip span as fractions of the distance between the top marker centres.
+const STRIP_FROM: f64 = 0.2;
+const STRIP_TO: f64 = 0.8;
+/// Smallest side fiducials are drawn on: keeps strip modules at least 2 px wide.
+pub const MIN_SIDE: u32 = 256;
+/// Calibration border width on each side.
+pub const BORDER: u32 = 24;
+/// Timing band: this many px in from the outer edge, TIMING_WIDTH px wide.
+const TIMING_INSET: u32 = 8;
+const TIMING_WIDTH: u32 = 6;
+/// Timing modules (out of those checked) that must match for a border to count as found.
+const TIMING_MIN_MATCH: f64 = 0.9;
+
+/// Marker centres in image coordinates, in unit-square order: top-left, top-right, bottom-left,
+/// bottom-right.
+fn marker_centers(w: u32, h: u32) -> [(f64, f64); 4] {
+    let (r, b) = (w as f64 - MARKER_CENTER, h as f64 - MARKER_CENTER);
+    [(MARKER_CENTER, MARKER_CENTER), (r, MARKER_CENTER), (MARKER_CENTER, b), (r, b)]
+}
+
+/// Strip module k's span along u (fraction of the marker-centre span).
+fn module_span(k: usize) -> (f64, f64) {
+    let du = (STRIP_TO - STRIP_FROM) / STRIP_MODULES as f64;
+    (STRIP_FROM + k as f64 * du, STRIP_FROM + (k + 1) as f64 * du)
+}
+
+/// Draw the corner markers and size strip into `img`. Returns the areas they cover, for the
+/// encoder's mask.
+pub fn draw(img: &mut RgbImage) -> Result<Vec<Region>, StegoError> {
+    let (w, h) = img.dimensions();
+    if w < MIN_SIDE || h < MIN_SIDE || w > u16::MAX as u32 || h > u16::MAX as u32 {
+        return Err(StegoError::InvalidInput(format!("Fiducials need an image between {} and {} px per side", MIN_SIDE, u16::MAX)));
+    }
+    let mut areas = Vec::with_capacity(5);
+    let inset = (MARKER_BOX - MARKER_INK) / 2;
+    for (x0, y0) in [(0, 0), (w - MARKER_BOX, 0), (0, h - MARKER_BOX), (w - MARKER_BOX, h - MARKER_BOX)] {
+        for y in 0..MARKER_BOX {
+            for x in 0..MARKER_BOX {
+                let ink = (inset..inset + MARKER_INK).contains(&x) && (inset..inset + MARKER_INK).contains(&y);
+                img.put_pixel(x0 + x, y0 + y, image::Rgb(if ink { [0; 3] } else { [255; 3] }));
+            }
+        }
+        areas.push(Region { x: x0, y: y0, width: MARKER_BOX, height: MARKER_BOX });
+    }
+
+    let mut size = Vec::with_capacity(SIZE_BYTES);
+    size.extend_from_slice(&(w as u16).to_be_bytes());
+    size.extend_from_slice(&(h as u16).to_be_bytes());
+    let bits: Vec<bool> = crate::reed_solomon::encode(&size, SIZE_NSYM).iter().flat_map(|b| (0..8).rev().map(move |i| b >> i & 1 != 0)).collect();
+    let span = w as f64 - 2.0 * MARKER_CENTER;
+    let to_x = |u: f64| MARKER_CENTER + u * span;
+    let (x0, x1) = (to_x(STRIP_FROM).floor() as u32, to_x(STRIP_TO).ceil() as u32);
+    for y in inset..inset + MARKER_INK {
+        for x in x0..x1 {
+            let u = (x as f64 + 0.5 - MARKER_CENTER) / span;
+            let dark = (0..STRIP_MODULES).any(|k| {
+                let (a, b) = module_span(k);
+                bits[k] && (a..b).contains(&u)
+            });
+            // One-pixel light margin above and below the modules.
+            let ink = dark && y > inset && y + 1 < inset + MARKER_INK;
+            img.put_pixel(x, y, image::Rgb(if ink { [0; 3] } else { [255; 3] }));
+        }
+    }
+    areas.push(Region { x: x0, y: inset, width: x1 - x0, height: MARKER_INK });
+    Ok(areas)
+}
+
+/// `cover` framed by the calibration border, with timing modules `pitch` px long. Returns the
+/// framed image; the cover sits at (BORDER, BORDER).
+pub fn calibrate(cover: &RgbImage, pitch: u32) -> Result<RgbImage, StegoError> {
+    let (w, h) = cover.dimensions();
+    let mut framed = RgbImage::from_pixel(w + 2 * BORDER, h + 2 * BORDER, image::Rgb([255; 3]));
+    image::imageops::replace(&mut framed, cover, BORDER as i64, BORDER as i64);
+    draw(&mut framed)?;
+    let (fw, fh) = framed.dimensions();
+    let band = |edge: u32| edge - TIMING_INSET - TIMING_WIDTH..edge - TIMING_INSET;
+    for k in (0..w / pitch).step_by(2) {
+        for x in BORDER + k * pitch..BORDER + (k + 1) * pitch {
+            for y in band(fh) {
+                framed.put_pixel(x, y, image::Rgb([0; 3]));
+            }
+        }
+    }
+    for k in (0..h / pitch).step_by(2) {
+        for y in BORDER + k * pitch..BORDER + (k + 1) * pitch {
+            for x in band(fw) {
+                framed.put_pixel(x, y, image::Rgb([0; 3]));
+            }
+        }
+    }
+    Ok(framed)
+}
+
+/// Shift (-2..=2 px) of the cover's edge along one timing band that best matches the pattern,
+/// if one matches TIMING_MIN_MATCH of the modules. `dark(i)` reads position i along the band.
+fn timing_shift(len: u32, pitch: u32, dark: impl Fn(i64) -> bool) -> Option<i64> {
+    let modules = len.saturating_sub(2 * BORDER) / pitch;
+    if modules < 8 {
+        return None;
+    }
+    (-2i64..=2)
+        .map(|shift| {
+            let matches = (0..modules)
+                .filter(|&k| dark(BORDER as i64 + shift + (k * pitch + pitch / 2) as i64) == k.is_multiple_of(2))
+                .count();
+            (matches, -shift.abs(), shift)
+        })
+        .max()
+        .filter(|&(matches, _, _)| matches as f64 >= modules as f64 * TIMING_MIN_MATCH)
+        .map(|(_, _, shift)| shift)
+}
+
+/// The cover inside a calibration-framed image (as stored or from `rectify`), located by its
+/// timing patterns; None when `img` has none.
+pub fn calibrated_inner(img: &RgbImage, pitch: u32) -> Option<RgbImage> {
+    let (w, h) = img.dimensions();
+    if w <= 2 * BORDER || h <= 2 * BORDER {
+        return None;
+    }
+    let threshold = otsu(img);
+    let dark = |x: i64, y: i64| {
+        let (x, y) = (x.clamp(0, w as i64 - 1) as u32, y.clamp(0, h as i64 - 1) as u32);
+        luma(img.get_pixel(x, y)) < threshold
+    };
+    let mid = |edge: u32| (edge - TIMING_INSET - TIMING_WIDTH / 2) as i64;
+    let dx = timing_shift(w, pitch, |x| dark(x, mid(h)))?;
+    let dy = timing_shift(h, pitch, |y| dark(mid(w), y))?;
+    let (x0, y0) = ((BORDER as i64 + dx) as u32, (BORDER as i64 + dy) as u32);
+    let (iw, ih) = (w - 2 * BORDER, h - 2 * BORDER);
+    (x0 + iw <= w && y0 + ih <= h).then(|| image::imageops::crop_imm(img, x0, y0, iw, ih).to_image())
+}
+
+fn luma(p: &image::Rgb<u8>) -> u8 {
+    ((p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000) as u8
+}
+
+/// Otsu threshold of the photo's luma: pixels below it count as dark.
+fn otsu(img: &RgbImage) -> u8 {
+    let mut hist = [0u64; 256];
+    for p in img.pixels() {
+        hist[luma(p) as usize] += 1;
+    }
+    let total: u64 = hist.iter().sum();
+    let sum_all: u64 = hist.iter().enumerate().map(|(v, &n)| v as u64 * n).sum();
+    let (mut below, mut sum_below, mut best, mut threshold) = (0u64, 0u64, 0f64, 128u8);
+    for (v, &n) in hist.iter().enumerate() {
+        below += n;
+        sum_below += v as u64 * n;
+        let above = total - below;
+        if below == 0 || above == 0 {
+            continue;
+        }
+        let mean_below = sum_below as f64 / below as f64;
+        let mean_above = (sum_all - sum_below) as f64 / above as f64;
+        let between = below as f64 * above as f64 * (mean_below - mean_above).powi(2);
+        if between > best {
+            best = between;
+            threshold = v as u8 + 1;
+        }
+    }
+    threshold
+}
+
+/// Centres of dark, filled, roughly square blobs with a light ring around them.
+fn marker_candidates(img: &RgbImage, threshold: u8) -> Vec<(f64, f64)> {
+    let (w, h) = img.dimensions();
+    let dark: Vec<bool> = img.pixels().map(|p| luma(p) < threshold).collect();
+    let mut seen = vec![false; dark.len()];
+    let mut found = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..dark.len() {
+        if !dark[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        stack.push(start);
+        let (mut area, mut sx, mut sy) = (0u64, 0f64, 0f64);
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (w, h, 0, 0);
+        while let Some(i) = stack.pop() {
+            let (x, y) = ((i as u32) % w, (i as u32) / w);
+            area += 1;
+            sx += x as f64 + 0.5;
+            sy += y as f64 + 0.5;
+            (min_x, min_y, max_x, max_y) = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+            let neighbours = [(x > 0).then(|| i - 1), (x + 1 < w).then(|| i + 1), (y > 0).then(|| i - w as usize), (y + 1 < h).then(|| i + w as usize)];
+            for n in neighbours.into_iter().flatten() {
+                if dark[n] && !seen[n] {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+        let (bw, bh) = (max_x - min_x + 1, max_y - min_y + 1);
+        let squareish = bw * 2 >= bh && bh * 2 >= bw;
+        if area < 16 || area > (w as u64 * h as u64) / 16 || !squareish || area * 10 < bw as u64 * bh as u64 * 6 {
+            continue;
+        }
+        // The white box shows as a light ring (a third of the square wide; a fifth is checked).
+        let m = (bw.max(bh) / 5).max(1);
+        let (rx0, ry0) = (min_x.saturating_sub(m), min_y.saturating_sub(m));
+        let (rx1, ry1) = ((max_x + m).min(w - 1), (max_y + m).min(h - 1));
+        let ring = (ry0..=ry1).flat_map(|y| (rx0..=rx1).map(move |x| (x, y))).filter(|&(x, y)| x < min_x || x > max_x || y < min_y || y > max_y);
+        let (mut light, mut total) = (0usize, 0usize);
+        for (x, y) in ring {
+            total += 1;
+            light += usize::from(!dark[(y * w + x) as usize]);
+        }
+        if total > 0 && light * 10 >= total * 9 {
+            found.push((sx / area as f64, sy / area as f64));
+        }
+    }
+    found
+}
+
+/// Homography taking the four `from` points to the four `to` points (row-major 3x3, h[8] = 1).
+/// None when the points are degenerate.
+pub fn homography(from: &[(f64, f64); 4], to: &[(f64, f64); 4]) -> Option<[f64; 9]> {
+    let mut a = [[0f64; 9]; 8];
+    for (k, (&(x, y), &(u, v))) in from.iter().zip(to).enumerate() {
+        a[2 * k] = [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, u];
+        a[2 * k + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, v];
+    }
+    // Gaussian elimination with partial pivoting on the augmented 8x9 system.
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        let pivot_row = a[col];
+        for (_, row) in a.iter_mut().enumerate().filter(|&(r, _)| r != col) {
+            let f = row[col] / pivot_row[col];
+            for (v, p) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *v -= f * p;
+            }
+        }
+    }
+    let mut h = [1f64; 9];
+    for (i, row) in a.iter().enumerate() {
+        h[i] = row[8] / row[i];
+    }
+    Some(h)
+}
+
+/// `h` applied to (x, y).
+pub fn project(h: &[f64; 9], x: f64, y: f64) -> (f64, f64) {
+    let d = h[6] * x + h[7] * y + h[8];
+    ((h[0] * x + h[1] * y + h[2]) / d, (h[3] * x + h[4] * y + h[5]) / d)
+}
+
+/// The detected marker centres in unit-square order, if one is found in each quadrant.
+fn find_markers(img: &RgbImage, threshold: u8) -> Option<[(f64, f64); 4]> {
+    let (w, h) = (img.width() as f64, img.height() as f64);
+    let candidates = marker_candidates(img, threshold);
+    let mut corners = [(0f64, 0f64); 4];
+    for (corner, (cx, cy)) in corners.iter_mut().zip([(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)]) {
+        *corner = candidates
+            .iter()
+            .copied()
+            .filter(|&(x, y)| (x < w / 2.0) == (cx == 0.0) && (y < h / 2.0) == (cy == 0.0))
+            .min_by(|a, b| (a.0 - cx).hypot(a.1 - cy).total_cmp(&(b.0 - cx).hypot(b.1 - cy)))?;
+    }
+    Some(corners)
+}
+
+/// Photo of a fiducial image resampled to the image's original size, or None when the markers or
+/// the size strip cannot be read.
+pub fn rectify(photo: &RgbImage) -> Option<RgbImage> {
+    let threshold = otsu(photo);
+    let corners = find_markers(photo, threshold)?;
+    let unit = homography(&[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)], &corners)?;
+    let sample = |x: f64, y: f64| {
+        let px = (x.max(0.0) as u32).min(photo.width() - 1);
+        let py = (y.max(0.0) as u32).min(photo.height() - 1);
+        *photo.get_pixel(px, py)
+    };
+
+    let bits: Vec<u8> = (0..STRIP_MODULES)
+        .map(|k| {
+            let (a, b) = module_span(k);
+            let (x, y) = project(&unit, (a + b) / 2.0, 0.0);
+            u8::from(luma(&sample(x, y)) < threshold)
+        })
+        .collect();
+    let codeword: Vec<u8> = bits.chunks(8).map(|c| c.iter().fold(0u8, |acc, &b| acc << 1 | b)).collect();
+    let size = crate::reed_solomon::decode(&codeword, SIZE_NSYM, &[]).ok()?;
+    let (w, h) = (u16::from_be_bytes([size[0], size[1]]) as u32, u16::from_be_bytes([size[2], size[3]]) as u32);
+    if w < MIN_SIDE || h < MIN_SIDE {
+        return None;
+    }
+
+    let to_photo = homography(&marker_centers(w, h), &corners)?;
+    Some(RgbImage::from_fn(w, h, |x, y| {
+        let (px, py) = project(&to_photo, x as f64 + 0.5, y as f64 + 0.5);
+        sample(px, py)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_photographed_dot_image_decodes() {
+        let cover = RgbImage::from_fn(300, 260, |x, y| image::Rgb([(x % 150) as u8 + 60, (y % 130) as u8 + 60, 140]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        cover.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let opts = crate::stego_dot::EncodeOptions { fiducials: true, ..Default::default() };
+        let stego = crate::stego_dot::encode_bytes(png.get_ref(), b"seen through a lens", &opts).unwrap();
+        let img = image::load_from_memory(&stego).unwrap().to_rgb8();
+        // A tilted shot, 1.5x larger, on a dark background.
+        let photo_corners = [(40.0, 30.0), (480.0, 60.0), (20.0, 420.0), (500.0, 400.0)];
+        let back = homography(&photo_corners, &[(0.0, 0.0), (300.0, 0.0), (0.0, 260.0), (300.0, 260.0)]).unwrap();
+        let photo = RgbImage::from_fn(520, 460, |x, y| {
+            let (ix, iy) = project(&back, x as f64 + 0.5, y as f64 + 0.5);
+            if (0.0..300.0).contains(&ix) && (0.0..260.0).contains(&iy) {
+                *img.get_pixel(ix as u32, iy as u32)
+            } else {
+                image::Rgb([30, 30, 30])
+            }
+        });
+        let rectified = rectify(&photo).unwrap();
+        assert_eq!(rectified.dimensions(), (300, 260));
+        let mut photo_png = std::io::Cursor::new(Vec::new());
+        photo.write_to(&mut photo_png, image::ImageFormat::Png).unwrap();
+        assert_eq!(crate::stego_dot::decode_bytes(photo_png.get_ref(), None).unwrap(), b"seen through a lens");
+        // Without fiducials there is nothing to line the grid up with.
+        assert!(rectify(&cover).is_none());
+    }
+}
@@ -0,0 +1,90 @@
+// Multi-image payloads: a payload too large for one cover is cut into fragments, one per image,
+// each behind a payload header carrying the fragment index, the total and a bundle id (the first
+// 8 bytes of the SHA-256 of the whole payload). `join` takes the decoded fragments in any order,
+// checks that all are there and that the reassembled payload hashes to the bundle id.
+// Fountain-coded parts (stego_fountain) and Shamir shares (stego_shamir), where a subset of the
+// images suffices, join the same way.
+
+use sha2::{Digest, Sha256};
+
+use crate::error::StegoError;
+use crate::payload_header::{self, Fragment, PayloadHeader};
+
+pub const MIME_FRAGMENT: &str = "application/vnd.stegstr.fragment";
+
+/// Bundle id of `payload`: 16 hex digits.
+pub fn bundle_id(payload: &[u8]) -> String {
+    Sha256::digest(payload)[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `payload` in `count` fragments of (nearly) equal size, each ready to embed.
+pub fn split(payload: &[u8], count: usize) -> Result<Vec<Vec<u8>>, StegoError> {
+    if count < 2 || count > u16::MAX as usize {
+        return Err(StegoError::InvalidInput(format!("Split needs 2 to {} images, not {}", u16::MAX, count)));
+    }
+    if payload.len() < count {
+        return Err(StegoError::InvalidInput(format!("{} byte payload cannot be split across {} images", payload.len(), count)));
+    }
+    let id = bundle_id(payload);
+    payload
+        .chunks(payload.len().div_ceil(count))
+        .enumerate()
+        .map(|(i, chunk)| {
+            let header = PayloadHeader {
+                fragment: Some(Fragment { bundle_id: id.clone(), index: i as u16, total: count as u16 }),
+                ..PayloadHeader::new(MIME_FRAGMENT)
+            };
+            header.write(chunk)
+        })
+        .collect()
+}
+
+/// Reassemble the payload from decoded fragments (as extracted, any order, duplicates allowed).
+pub fn join(parts: &[Vec<u8>]) -> Result<Vec<u8>, StegoError> {
+    if parts.first().is_some_and(|p| crate::stego_fountain::is_fountain_part(p)) {
+        return crate::stego_fountain::decode(parts);
+    }
+    if parts.first().is_some_and(|p| crate::stego_shamir::is_shamir_part(p)) {
+        return crate::stego_shamir::combine(parts);
+    }
+    let mut fragments: Vec<(Fragment, Vec<u8>)> = Vec::with_capacity(parts.len());
+    for part in parts {
+        match payload_header::read(part)? {
+            (Some(PayloadHeader { fragment: Some(f), .. }), data) => fragments.push((f, data)),
+            _ => return Err(StegoError::InvalidInput("Image does not hold a payload fragment".to_string())),
+        }
+    }
+    let (first, _) = fragments.first().ok_or_else(|| StegoError::InvalidInput("No fragments to join".to_string()))?;
+    let (id, total) = (first.bundle_id.clone(), first.total);
+    if let Some((f, _)) = fragments.iter().find(|(f, _)| f.bundle_id != id || f.total != total) {
+        return Err(StegoError::InvalidInput(format!("Fragments of different payloads ({} and {})", id, f.bundle_id)));
+    }
+    fragments.sort_by_key(|(f, _)| f.index);
+    fragments.dedup_by_key(|(f, _)| f.index);
+    if let Some(missing) = (0..total).find(|&i| fragments.get(i as usize).is_none_or(|(f, _)| f.index != i)) {
+        return Err(StegoError::InvalidInput(format!("Fragment {} of {} is missing (bundle {})", missing + 1, total, id)));
+    }
+    let payload: Vec<u8> = fragments.into_iter().flat_map(|(_, data)| data).collect();
+    if bundle_id(&payload) != id {
+        return Err(StegoError::NotStego(format!("Reassembled payload does not match bundle {}", id)));
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_join_any_order() {
+        let payload: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let mut parts = split(&payload, 3).unwrap();
+        assert_eq!(parts.len(), 3);
+        parts.reverse();
+        parts.push(parts[0].clone());
+        assert_eq!(join(&parts).unwrap(), payload);
+        assert!(join(&parts[..2]).is_err());
+        let other = split(b"another payload", 3).unwrap();
+        assert!(join(&[parts[0].clone(), parts[1].clone(), other[0].clone()]).is_err());
+    }
+}
@@ -0,0 +1,20 @@
+// HEIC/AVIF covers, as iPhones and recent Android phones save photos. With the `heif` feature,
+// `register` hooks libheif into the image crate, so every loader that sniffs the format
+// (ImageReader::with_guessed_format) decodes them like any other cover: the primary image with
+// its rotation and mirroring applied, 8 or 16 bits per sample. Stego output stays PNG or WebP.
+// Without the feature `register` does nothing and such covers fail as an unsupported format.
+
+/// Make HEIC/AVIF readable for the rest of the process; call once at startup (repeat calls are
+/// no-ops).
+pub fn register() {
+    #[cfg(feature = "heif")]
+    {
+        static ONCE: std::sync::Once = std::sync::Once::new();
+        ONCE.call_once(libheif_rs::integration::image::register_all_decoding_hooks);
+    }
+}
+
+/// True when this build reads HEIC/AVIF covers.
+pub fn supported() -> bool {
+    cfg!(feature = "heif")
+}
@@ -0,0 +1,34 @@
+// Stegstr core: the DWT and dot codecs, the payload header, payload encryption and Nostr event signing, with no Tauri
+// or network dependencies. The desktop app and stegstr-cli (src-tauri) re-export these modules;
+// other Rust projects can depend on this crate alone to read and write Stegstr images.
+// Path-based APIs sit behind the default `fs` feature; the in-memory ones (`*_bytes`, `detect`)
+// also build for wasm32-unknown-unknown, where the `wasm` feature exports them to JavaScript.
+
+#[cfg(feature = "crypto")]
+pub mod bundle;
+pub mod detect;
+pub mod error;
+pub mod fiducial;
+pub mod fragment;
+pub mod heif;
+pub mod mask;
+#[cfg(feature = "crypto")]
+pub mod nostr_codes;
+#[cfg(feature = "crypto")]
+pub mod nostr_event;
+pub mod output;
+pub mod payload_header;
+pub mod progress;
+pub mod reed_solomon;
+pub mod stego;
+pub mod stego_anim;
+#[cfg(feature = "crypto")]
+pub mod stego_crypto;
+pub mod stego_dot;
+pub mod stego_fountain;
+pub mod stego_shamir;
+pub mod stego_key;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,144 @@
+// Embed exclusion masks: user-drawn areas (faces, text, logos) the encoders must leave untouched.
+// A mask is given as rectangles and/or a bitmap (light pixels = excluded) and is resolved against
+// the cover's orientation-corrected size when encoding.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::stego::Region;
+
+/// Mask as passed over IPC / from the CLI.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct MaskSpec {
+    /// Excluded rectangles in cover pixel coordinates.
+    #[serde(default)]
+    pub rects: Vec<Region>,
+    /// Mask image; pixels with luma >= 128 are excluded. Stretched to the cover size if it differs.
+    #[serde(default)]
+    pub bitmap_path: Option<String>,
+}
+
+impl MaskSpec {
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty() && self.bitmap_path.is_none()
+    }
+
+    /// Same mask for a cover resized from `from` to `to` (w, h). Rects grow outward so scaling
+    /// never uncovers a protected pixel; the bitmap is stretched at build time anyway.
+    pub fn scaled(&self, from: (u32, u32), to: (u32, u32)) -> MaskSpec {
+        let sx = |v: u32| (v as u64 * to.0 as u64 / from.0.max(1) as u64) as u32;
+        let sy = |v: u32| (v as u64 * to.1 as u64 / from.1.max(1) as u64) as u32;
+        let cx = |v: u32| (v as u64 * to.0 as u64).div_ceil(from.0.max(1) as u64) as u32;
+        let cy = |v: u32| (v as u64 * to.1 as u64).div_ceil(from.1.max(1) as u64) as u32;
+        MaskSpec {
+            rects: self
+                .rects
+                .iter()
+                .map(|r| Region {
+                    x: sx(r.x),
+                    y: sy(r.y),
+                    width: cx(r.x.saturating_add(r.width)) - sx(r.x),
+                    height: cy(r.y.saturating_add(r.height)) - sy(r.y),
+                })
+                .collect(),
+            bitmap_path: self.bitmap_path.clone(),
+        }
+    }
+
+    pub fn build(&self, width: u32, height: u32) -> Result<Mask, String> {
+        let mut mask = Mask {
+            width,
+            height,
+            excluded: vec![false; width as usize * height as usize],
+        };
+        for r in &self.rects {
+            let x1 = r.x.saturating_add(r.width).min(width);
+            let y1 = r.y.saturating_add(r.height).min(height);
+            for y in r.y.min(height)..y1 {
+                for x in r.x.min(width)..x1 {
+                    mask.excluded[(y * width + x) as usize] = true;
+                }
+            }
+        }
+        if let Some(path) = &self.bitmap_path {
+            let mut bitmap = load_bitmap(path)?;
+            if bitmap.dimensions() != (width, height) {
+                bitmap = image::imageops::resize(&bitmap, width, height, image::imageops::FilterType::Nearest);
+            }
+            for (i, p) in bitmap.pixels().enumerate() {
+                if p[0] >= 128 {
+                    mask.excluded[i] = true;
+                }
+            }
+        }
+        Ok(mask)
+    }
+}
+
+#[cfg(feature = "fs")]
+fn load_bitmap(path: &str) -> Result<image::GrayImage, String> {
+    Ok(image::open(path).map_err(|e| format!("mask {}: {}", path, e))?.to_luma8())
+}
+
+#[cfg(not(feature = "fs"))]
+fn load_bitmap(path: &str) -> Result<image::GrayImage, String> {
+    Err(format!("mask {}: bitmap masks need file access (the fs feature)", path))
+}
+
+/// Per-pixel exclusion map at the cover's size.
+#[derive(Debug, Clone)]
+pub struct Mask {
+    width: u32,
+    height: u32,
+    excluded: Vec<bool>,
+}
+
+impl Mask {
+    pub fn is_excluded(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height && self.excluded[(y * self.width + x) as usize]
+    }
+
+    /// True if any pixel of the w x h block at (x, y) is excluded.
+    pub fn intersects(&self, x: u32, y: u32, w: u32, h: u32) -> bool {
+        (y..(y + h).min(self.height)).any(|yy| (x..(x + w).min(self.width)).any(|xx| self.is_excluded(xx, yy)))
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_masked_encodes_leave_region_untouched() {
+        let cover = crate::test_util::noisy_cover(768, 512, 0x2545_F491);
+        let dir = std::env::temp_dir();
+        let cover_path = dir.join("stegstr_mask_cover.png");
+        cover.save(&cover_path).unwrap();
+        let spec = MaskSpec {
+            rects: vec![Region { x: 100, y: 100, width: 64, height: 64 }],
+            bitmap_path: None,
+        };
+        let payload = b"masked payload";
+
+        for (name, png) in [
+            ("dwt", crate::stego::encode_with_mask(&cover_path, payload, Some(&spec), &Default::default()).unwrap()),
+            ("dot", crate::stego_dot::encode_with_mask(&cover_path, payload, Some(&spec)).unwrap()),
+        ] {
+            let out_path = dir.join(format!("stegstr_mask_{}.png", name));
+            std::fs::write(&out_path, &png).unwrap();
+            let out = image::open(&out_path).unwrap().to_rgb8();
+            for y in 100..164 {
+                for x in 100..164 {
+                    assert_eq!(out.get_pixel(x, y), cover.get_pixel(x, y), "{} changed ({}, {})", name, x, y);
+                }
+            }
+            let decoded = match name {
+                "dwt" => crate::stego::decode(&out_path),
+                _ => crate::stego_dot::decode(&out_path),
+            };
+            assert_eq!(decoded.unwrap(), payload, "{} read-back", name);
+            let _ = std::fs::remove_file(out_path);
+        }
+        let _ = std::fs::remove_file(cover_path);
+    }
+}
@@ -0,0 +1,213 @@
+// NIP-19 bech32 entities (npub, nsec, note, nevent), matching nostr-stub.ts nip19.
+// The parse_* helpers accept either the bech32 form or raw hex wherever a key or event id is taken.
+
+use bech32::{Bech32, Hrp};
+
+// nevent TLV types.
+const TLV_SPECIAL: u8 = 0;
+const TLV_RELAY: u8 = 1;
+const TLV_AUTHOR: u8 = 2;
+const TLV_KIND: u8 = 3;
+
+fn decode_bytes(s: &str, expected_hrp: &str) -> Result<Vec<u8>, String> {
+    let (hrp, data) = bech32::decode(s.trim()).map_err(|e| format!("invalid {}: {}", expected_hrp, e))?;
+    if hrp.as_str().to_lowercase() != expected_hrp {
+        return Err(format!("expected {} prefix, got {}", expected_hrp, hrp));
+    }
+    Ok(data)
+}
+
+fn decode_32(s: &str, expected_hrp: &str) -> Result<[u8; 32], String> {
+    decode_bytes(s, expected_hrp)?
+        .try_into()
+        .map_err(|_| format!("{} must decode to 32 bytes", expected_hrp))
+}
+
+fn encode_32(hrp: &str, bytes: &[u8; 32]) -> Result<String, String> {
+    let hrp = Hrp::parse(hrp).map_err(|e| e.to_string())?;
+    bech32::encode::<Bech32>(hrp, bytes).map_err(|e| e.to_string())
+}
+
+fn hex_32(s: &str, what: &str) -> Result<[u8; 32], String> {
+    hex::decode(s.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| format!("{} must be 64-char hex", what))
+}
+
+/// Encode a 32-byte x-only public key as npub.
+pub fn npub_encode(pubkey: &[u8; 32]) -> Result<String, String> {
+    encode_32("npub", pubkey)
+}
+
+/// Decode an npub into the 32-byte x-only public key.
+pub fn npub_decode(npub: &str) -> Result<[u8; 32], String> {
+    decode_32(npub, "npub")
+}
+
+/// Accept an npub or 64-char hex public key; returns lowercase hex.
+pub fn parse_pubkey(s: &str) -> Result<String, String> {
+    let s = s.trim();
+    if s.to_lowercase().starts_with("npub1") {
+        return Ok(hex::encode(npub_decode(s)?));
+    }
+    if s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(s.to_lowercase());
+    }
+    Err("Invalid public key (expected npub or 64-char hex)".to_string())
+}
+
+/// Encode a 32-byte secret key as nsec.
+pub fn nsec_encode(sk: &[u8; 32]) -> Result<String, String> {
+    encode_32("nsec", sk)
+}
+
+/// Decode an nsec into the 32-byte secret key.
+pub fn nsec_decode(nsec: &str) -> Result<[u8; 32], String> {
+    decode_32(nsec, "nsec")
+}
+
+/// Accept an nsec or 64-char hex secret key.
+pub fn parse_secret_key(s: &str) -> Result<secp256k1::SecretKey, String> {
+    let s = s.trim();
+    let bytes = if s.to_lowercase().starts_with("nsec1") {
+        nsec_decode(s)?
+    } else {
+        hex_32(s, "secret key").map_err(|_| "Invalid secret key (expected nsec or 64-char hex)".to_string())?
+    };
+    secp256k1::SecretKey::from_slice(&bytes).map_err(|e| e.to_string())
+}
+
+/// Encode a 32-byte event id as note.
+pub fn note_encode(id: &[u8; 32]) -> Result<String, String> {
+    encode_32("note", id)
+}
+
+/// Decode a note into the 32-byte event id.
+pub fn note_decode(note: &str) -> Result<[u8; 32], String> {
+    decode_32(note, "note")
+}
+
+/// Event reference carried by an nevent: id plus optional relay hints, author and kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventPointer {
+    /// Event id, lowercase hex.
+    pub id: String,
+    pub relays: Vec<String>,
+    /// Author pubkey, lowercase hex.
+    pub author: Option<String>,
+    pub kind: Option<u32>,
+}
+
+/// Encode an event pointer as nevent (TLV: 0 id, 1 relay, 2 author, 3 kind as u32 big-endian).
+pub fn nevent_encode(pointer: &EventPointer) -> Result<String, String> {
+    let mut tlv = Vec::new();
+    let mut push = |t: u8, v: &[u8]| -> Result<(), String> {
+        let len = u8::try_from(v.len()).map_err(|_| "nevent field longer than 255 bytes".to_string())?;
+        tlv.push(t);
+        tlv.push(len);
+        tlv.extend_from_slice(v);
+        Ok(())
+    };
+    push(TLV_SPECIAL, &hex_32(&pointer.id, "event id")?)?;
+    for relay in &pointer.relays {
+        push(TLV_RELAY, relay.as_bytes())?;
+    }
+    if let Some(author) = &pointer.author {
+        push(TLV_AUTHOR, &hex_32(author, "author")?)?;
+    }
+    if let Some(kind) = pointer.kind {
+        push(TLV_KIND, &kind.to_be_bytes())?;
+    }
+    let hrp = Hrp::parse("nevent").map_err(|e| e.to_string())?;
+    bech32::encode::<Bech32>(hrp, &tlv).map_err(|e| e.to_string())
+}
+
+/// Decode an nevent. Unknown TLV types are skipped, as NIP-19 requires.
+pub fn nevent_decode(nevent: &str) -> Result<EventPointer, String> {
+    let data = decode_bytes(nevent, "nevent")?;
+    let mut pointer = EventPointer::default();
+    let mut id = None;
+    let mut rest = data.as_slice();
+    while let [t, len, tail @ ..] = rest {
+        let len = *len as usize;
+        if tail.len() < len {
+            return Err("invalid nevent: truncated TLV".to_string());
+        }
+        let (v, next) = tail.split_at(len);
+        match *t {
+            TLV_SPECIAL if len == 32 => id = Some(hex::encode(v)),
+            TLV_RELAY => pointer.relays.push(String::from_utf8_lossy(v).to_string()),
+            TLV_AUTHOR if len == 32 => pointer.author = Some(hex::encode(v)),
+            TLV_KIND if len == 4 => pointer.kind = Some(u32::from_be_bytes([v[0], v[1], v[2], v[3]])),
+            _ => {}
+        }
+        rest = next;
+    }
+    pointer.id = id.ok_or("invalid nevent: no event id")?;
+    Ok(pointer)
+}
+
+/// Accept a note, nevent or 64-char hex event id; returns lowercase hex.
+pub fn parse_event_id(s: &str) -> Result<String, String> {
+    parse_event_pointer(s).map(|p| p.id)
+}
+
+/// Like parse_event_id, keeping the relay hints, author and kind an nevent carries.
+pub fn parse_event_pointer(s: &str) -> Result<EventPointer, String> {
+    let s = s.trim();
+    let lower = s.to_lowercase();
+    if lower.starts_with("nevent1") {
+        return nevent_decode(s);
+    }
+    let id = if lower.starts_with("note1") {
+        hex::encode(note_decode(s)?)
+    } else {
+        hex_32(s, "event id")
+            .map(hex::encode)
+            .map_err(|_| "Invalid event id (expected note, nevent or 64-char hex)".to_string())?
+    };
+    Ok(EventPointer {
+        id,
+        ..Default::default()
+    })
+}
+
+/// npub for a hex pubkey, for printing next to the hex form.
+pub fn npub_from_hex(pubkey_hex: &str) -> Result<String, String> {
+    npub_encode(&hex_32(pubkey_hex, "public key")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nip19_roundtrips() {
+        // NIP-19 test vector.
+        let npub = "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg";
+        let hex_pk = "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e";
+        assert_eq!(parse_pubkey(npub).unwrap(), hex_pk);
+        assert_eq!(npub_from_hex(hex_pk).unwrap(), npub);
+
+        let nsec = "nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5";
+        let sk = parse_secret_key(nsec).unwrap();
+        assert_eq!(hex::encode(sk.secret_bytes()), "67dea2ed018072d675f5415ecfaed7d2597555e202d85b3d65ea4e58d2d92ffa");
+        assert_eq!(nsec_encode(&sk.secret_bytes()).unwrap(), nsec);
+
+        let id = "ab".repeat(32);
+        let note = note_encode(&hex_32(&id, "id").unwrap()).unwrap();
+        assert_eq!(parse_event_id(&note).unwrap(), id);
+        let pointer = EventPointer {
+            id: id.clone(),
+            relays: vec!["wss://relay.damus.io".to_string(), "wss://nos.lol".to_string()],
+            author: Some(hex_pk.to_string()),
+            kind: Some(1),
+        };
+        let nevent = nevent_encode(&pointer).unwrap();
+        assert!(nevent.len() > 90);
+        assert_eq!(nevent_decode(&nevent).unwrap(), pointer);
+        assert_eq!(parse_event_id(&nevent).unwrap(), id);
+        assert!(parse_event_id("note1xyz").is_err());
+    }
+}
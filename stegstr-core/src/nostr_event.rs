@@ -0,0 +1,368 @@
+// NIP-01 event construction and Schnorr signing, shared by the CLI and Tauri commands.
+// Matches nostr-stub.ts finishEventAsync: id = sha256([0, pubkey, created_at, kind, tags, content]).
+
+use schemars::JsonSchema;
+use secp256k1::Secp256k1;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::nostr_codes::EventPointer;
+
+pub const STEGSTR_SUFFIX: &str = " Sent by Stegstr.";
+/// Note cap in Unicode scalar values (chars), after NFC normalization.
+pub const MAX_NOTE_LENGTH: usize = 5000;
+pub const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct NostrEvent {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: u64,
+    pub kind: u32,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: String,
+}
+
+/// Bundle embedded in images: `{ "version": 1, "events": [...] }`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct Bundle {
+    pub version: u32,
+    pub events: Vec<NostrEvent>,
+}
+
+impl Bundle {
+    pub fn new(events: Vec<NostrEvent>) -> Self {
+        Bundle {
+            version: BUNDLE_VERSION,
+            events,
+        }
+    }
+}
+
+/// Length of a note as counted against MAX_NOTE_LENGTH: chars of the NFC-normalized text.
+pub fn note_length(content: &str) -> usize {
+    content.nfc().count()
+}
+
+/// Ensure kind 1 content is NFC-normalized, ends with the Stegstr suffix and fits the note cap.
+/// Over-long content is cut on a char boundary before the suffix, so the suffix is always kept.
+pub fn ensure_stegstr_suffix(content: &str) -> String {
+    let normalized: String = content.nfc().collect();
+    let body = normalized.strip_suffix(STEGSTR_SUFFIX).unwrap_or(&normalized);
+    let max_body = MAX_NOTE_LENGTH - STEGSTR_SUFFIX.chars().count();
+    let mut s: String = body.chars().take(max_body).collect();
+    s.push_str(STEGSTR_SUFFIX);
+    s
+}
+
+/// X-only public key hex for a secret key.
+pub fn pubkey_hex(sk: &secp256k1::SecretKey) -> String {
+    let secp = Secp256k1::new();
+    let keypair = secp256k1::Keypair::from_secret_key(&secp, sk);
+    let (xonly, _parity) = keypair.x_only_public_key();
+    hex::encode(xonly.serialize())
+}
+
+/// Parse a 64-char hex secret key.
+pub fn secret_key_from_hex(hex_str: &str) -> Result<secp256k1::SecretKey, String> {
+    let bytes = hex::decode(hex_str.trim()).map_err(|e| e.to_string())?;
+    secp256k1::SecretKey::from_slice(&bytes).map_err(|e| e.to_string())
+}
+
+/// NIP-01 event id (hex) for the given fields.
+pub fn event_id(pubkey: &str, created_at: u64, kind: u32, tags: &[Vec<String>], content: &str) -> Result<String, String> {
+    let serialized = serde_json::to_string(&serde_json::json!([0, pubkey, created_at, kind, tags, content]))
+        .map_err(|e| e.to_string())?;
+    Ok(hex::encode(Sha256::digest(serialized.as_bytes())))
+}
+
+/// Build and sign an event with the current time.
+pub fn sign_event(kind: u32, tags: Vec<Vec<String>>, content: &str, sk: &secp256k1::SecretKey) -> Result<NostrEvent, String> {
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+    sign_event_at(kind, tags, content, created_at, sk)
+}
+
+/// Build and sign an event with an explicit timestamp.
+pub fn sign_event_at(
+    kind: u32,
+    tags: Vec<Vec<String>>,
+    content: &str,
+    created_at: u64,
+    sk: &secp256k1::SecretKey,
+) -> Result<NostrEvent, String> {
+    let secp = Secp256k1::new();
+    let keypair = secp256k1::Keypair::from_secret_key(&secp, sk);
+    let pubkey = pubkey_hex(sk);
+    let id = event_id(&pubkey, created_at, kind, &tags, content)?;
+    let id_bytes = hex::decode(&id).map_err(|e| e.to_string())?;
+    let msg = secp256k1::Message::from_digest_slice(&id_bytes).map_err(|e| e.to_string())?;
+    let sig = secp.sign_schnorr_no_aux_rand(&msg, &keypair);
+    Ok(NostrEvent {
+        id,
+        pubkey,
+        created_at,
+        kind,
+        tags,
+        content: content.to_string(),
+        sig: hex::encode(sig.serialize()),
+    })
+}
+
+/// Check that the event id matches its fields and the Schnorr signature is valid for its pubkey.
+pub fn verify_event(event: &NostrEvent) -> Result<(), String> {
+    let id = event_id(&event.pubkey, event.created_at, event.kind, &event.tags, &event.content)?;
+    if id != event.id {
+        return Err(format!("event id mismatch: expected {}", id));
+    }
+    let secp = Secp256k1::verification_only();
+    let pubkey_bytes = hex::decode(&event.pubkey).map_err(|e| format!("invalid pubkey: {}", e))?;
+    let pubkey = secp256k1::XOnlyPublicKey::from_slice(&pubkey_bytes).map_err(|e| format!("invalid pubkey: {}", e))?;
+    let sig_bytes = hex::decode(&event.sig).map_err(|e| format!("invalid sig: {}", e))?;
+    let sig = secp256k1::schnorr::Signature::from_slice(&sig_bytes).map_err(|e| format!("invalid sig: {}", e))?;
+    let id_bytes = hex::decode(&id).map_err(|e| e.to_string())?;
+    let msg = secp256k1::Message::from_digest_slice(&id_bytes).map_err(|e| e.to_string())?;
+    secp.verify_schnorr(&sig, &msg, &pubkey)
+        .map_err(|_| "invalid signature".to_string())
+}
+
+/// Event template to sign, as nostr-stub.ts finishEventAsync takes it. `created_at` defaults to
+/// now; a `pubkey`, if present, must be the signing key's.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct UnsignedEvent {
+    pub kind: u32,
+    #[serde(default)]
+    pub tags: Vec<Vec<String>>,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub created_at: Option<u64>,
+    #[serde(default)]
+    pub pubkey: Option<String>,
+}
+
+/// Sign a template as is: no Stegstr suffix or note cap, the caller built the content.
+pub fn sign_unsigned(template: &UnsignedEvent, sk: &secp256k1::SecretKey) -> Result<NostrEvent, String> {
+    if let Some(pubkey) = &template.pubkey {
+        if !pubkey.eq_ignore_ascii_case(&pubkey_hex(sk)) {
+            return Err("Event pubkey does not match the signing key".to_string());
+        }
+    }
+    let tags = template.tags.clone();
+    match template.created_at {
+        Some(created_at) => sign_event_at(template.kind, tags, &template.content, created_at, sk),
+        None => sign_event(template.kind, tags, &template.content, sk),
+    }
+}
+
+/// Sign a kind 1 note, appending the Stegstr suffix.
+pub fn create_kind1_event(content: &str, sk: &secp256k1::SecretKey) -> Result<NostrEvent, String> {
+    create_note_event(1, content, &NoteTags::default(), sk)
+}
+
+/// Threading and tags of a note: NIP-10 marked "e" tags for the thread root and the event
+/// replied to, "p" tags for their authors and mentions, then any extra tags (e.g. ["t", "art"]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NoteTags {
+    /// Root of the thread; when unset, the event replied to is the root.
+    pub root: Option<EventPointer>,
+    pub reply_to: Option<EventPointer>,
+    /// Hex public keys.
+    pub mentions: Vec<String>,
+    pub extra: Vec<Vec<String>>,
+}
+
+impl NoteTags {
+    pub fn to_tags(&self) -> Vec<Vec<String>> {
+        let e_tag = |p: &EventPointer, marker: &str| {
+            let relay = p.relays.first().cloned().unwrap_or_default();
+            let mut tag = vec!["e".to_string(), p.id.clone(), relay, marker.to_string()];
+            tag.extend(p.author.clone());
+            tag
+        };
+        let mut tags = Vec::new();
+        match (&self.root, &self.reply_to) {
+            (Some(root), Some(reply)) if root.id != reply.id => {
+                tags.push(e_tag(root, "root"));
+                tags.push(e_tag(reply, "reply"));
+            }
+            (Some(root), _) | (None, Some(root)) => tags.push(e_tag(root, "root")),
+            (None, None) => {}
+        }
+        let mut pubkeys: Vec<&String> = Vec::new();
+        let authors = [&self.root, &self.reply_to].into_iter().flatten().filter_map(|p| p.author.as_ref());
+        for pk in authors.chain(&self.mentions) {
+            if !pubkeys.contains(&pk) {
+                pubkeys.push(pk);
+            }
+        }
+        tags.extend(pubkeys.into_iter().map(|pk| vec!["p".to_string(), pk.clone()]));
+        tags.extend(self.extra.iter().cloned());
+        tags
+    }
+}
+
+/// Sign a note of `kind` with `tags`; kind 1 gets the Stegstr suffix as create_kind1_event does.
+pub fn create_note_event(kind: u32, content: &str, tags: &NoteTags, sk: &secp256k1::SecretKey) -> Result<NostrEvent, String> {
+    let content = if kind == 1 {
+        ensure_stegstr_suffix(content)
+    } else {
+        content.to_string()
+    };
+    sign_event(kind, tags.to_tags(), &content, sk)
+}
+
+/// Kind 0 profile metadata (NIP-01); unset fields are left out of the content.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct ProfileMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub about: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picture: Option<String>,
+}
+
+/// Sign a kind 0 profile event.
+pub fn create_kind0_event(metadata: &ProfileMetadata, sk: &secp256k1::SecretKey) -> Result<NostrEvent, String> {
+    let content = serde_json::to_string(metadata).map_err(|e| e.to_string())?;
+    sign_event(0, vec![], &content, sk)
+}
+
+/// One entry of a kind 3 contact list (NIP-02): ["p", pubkey, relay, petname].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct Contact {
+    /// Hex public key.
+    pub pubkey: String,
+    pub relay: Option<String>,
+    pub petname: Option<String>,
+}
+
+/// Sign a kind 3 contact list. The list replaces any earlier one, so pass every contact.
+pub fn create_kind3_event(contacts: &[Contact], sk: &secp256k1::SecretKey) -> Result<NostrEvent, String> {
+    let tags = contacts
+        .iter()
+        .map(|c| {
+            let mut tag = vec!["p".to_string(), c.pubkey.clone()];
+            if c.relay.is_some() || c.petname.is_some() {
+                tag.push(c.relay.clone().unwrap_or_default());
+            }
+            if let Some(petname) = &c.petname {
+                tag.push(petname.clone());
+            }
+            tag
+        })
+        .collect();
+    sign_event(3, tags, "", sk)
+}
+
+/// Contacts of a kind 3 event, in tag order (non-"p" tags are skipped).
+pub fn contacts_from_event(event: &NostrEvent) -> Vec<Contact> {
+    event
+        .tags
+        .iter()
+        .filter(|t| t.first().is_some_and(|k| k == "p") && t.len() >= 2)
+        .map(|t| Contact {
+            pubkey: t[1].clone(),
+            relay: t.get(2).filter(|r| !r.is_empty()).cloned(),
+            petname: t.get(3).filter(|p| !p.is_empty()).cloned(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ensure_stegstr_suffix_counts_chars() {
+        let emoji = "\u{1F600}".repeat(6000);
+        let s = ensure_stegstr_suffix(&emoji);
+        assert_eq!(note_length(&s), MAX_NOTE_LENGTH);
+        assert!(s.ends_with(STEGSTR_SUFFIX));
+        assert!(s.len() > MAX_NOTE_LENGTH);
+
+        // Decomposed e + combining acute is normalized to a single char.
+        assert_eq!(ensure_stegstr_suffix("caf\u{65}\u{301}"), format!("caf\u{e9}{}", STEGSTR_SUFFIX));
+        let already = format!("hi{}", STEGSTR_SUFFIX);
+        assert_eq!(ensure_stegstr_suffix(&already), already);
+    }
+
+    #[test]
+    fn test_sign_unsigned_verifies() {
+        let sk = secp256k1::SecretKey::from_slice(&[9u8; 32]).unwrap();
+        let template: UnsignedEvent = serde_json::from_str(r#"{"kind":7,"tags":[["e","ab"]],"content":"+","created_at":1700000000}"#).unwrap();
+        let event = sign_unsigned(&template, &sk).unwrap();
+        assert_eq!((event.kind, event.created_at, event.content.as_str()), (7, 1_700_000_000, "+"));
+        assert!(verify_event(&event).is_ok());
+        let mut tampered = event.clone();
+        tampered.content = "-".to_string();
+        assert!(verify_event(&tampered).unwrap_err().contains("id mismatch"));
+
+        let other = UnsignedEvent {
+            pubkey: Some("00".repeat(32)),
+            ..template
+        };
+        assert!(sign_unsigned(&other, &sk).is_err());
+    }
+
+    #[test]
+    fn test_reply_tags_follow_nip10() {
+        let (root, reply, author) = ("aa".repeat(32), "bb".repeat(32), "cc".repeat(32));
+        let mut tags = NoteTags {
+            reply_to: Some(EventPointer {
+                id: root.clone(),
+                relays: vec!["wss://nos.lol".to_string()],
+                author: Some(author.clone()),
+                kind: None,
+            }),
+            mentions: vec![author.clone(), "dd".repeat(32)],
+            extra: vec![vec!["t".to_string(), "art".to_string()]],
+            ..Default::default()
+        };
+        // A direct reply to the root carries a single "root" e tag; authors are not repeated.
+        let expected = vec![
+            vec!["e".to_string(), root.clone(), "wss://nos.lol".to_string(), "root".to_string(), author.clone()],
+            vec!["p".to_string(), author.clone()],
+            vec!["p".to_string(), "dd".repeat(32)],
+            vec!["t".to_string(), "art".to_string()],
+        ];
+        assert_eq!(tags.to_tags(), expected);
+
+        tags.root = tags.reply_to.take();
+        tags.reply_to = Some(EventPointer {
+            id: reply.clone(),
+            ..Default::default()
+        });
+        let e: Vec<_> = tags.to_tags().into_iter().filter(|t| t[0] == "e").collect();
+        assert_eq!((e[0][3].as_str(), e[1][3].as_str(), e[1][1].as_str()), ("root", "reply", reply.as_str()));
+
+        let sk = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let event = create_note_event(1111, "hi", &tags, &sk).unwrap();
+        assert_eq!((event.kind, event.content.as_str(), event.tags.len()), (1111, "hi", 5));
+    }
+
+    #[test]
+    fn test_kind0_and_kind3_events() {
+        let sk = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let metadata = ProfileMetadata {
+            name: Some("alice".to_string()),
+            ..Default::default()
+        };
+        let profile = create_kind0_event(&metadata, &sk).unwrap();
+        assert_eq!((profile.kind, profile.content.as_str()), (0, r#"{"name":"alice"}"#));
+        let contacts = vec![
+            Contact { pubkey: "a".repeat(64), relay: None, petname: None },
+            Contact { pubkey: "b".repeat(64), relay: None, petname: Some("bob".to_string()) },
+        ];
+        let list = create_kind3_event(&contacts, &sk).unwrap();
+        assert_eq!(list.kind, 3);
+        assert_eq!(list.tags[1], vec!["p".to_string(), "b".repeat(64), String::new(), "bob".to_string()]);
+        assert_eq!(contacts_from_event(&list), contacts);
+    }
+}
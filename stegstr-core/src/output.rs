@@ -0,0 +1,199 @@
+// Container of encoded stego images. Both options are lossless, so every embedded bit survives:
+// PNG (default, opens everywhere) or WebP lossless, typically about 30% smaller. Lossy WebP or
+// JPEG would destroy the DWT and dot payloads.
+// Cover metadata (Metadata) can ride along so the stego image looks and describes itself like the
+// cover: the ICC profile (without it colours shift on wide-gamut screens), EXIF with the
+// orientation reset (loaders already turned the pixels) and, in PNG, the cover's text chunks
+// (tEXt, zTXt, iTXt) copied verbatim. None of it carries payload bits.
+// Without metadata (the encoders' strip_metadata) a PNG keeps only its critical chunks (IHDR,
+// PLTE, IDAT, IEND), so no EXIF, GPS, text or timestamp can reach a publicly shared image
+// whatever the PNG encoder adds; WebP only gets metadata chunks when some is given.
+
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::metadata::Orientation;
+use image::{ExtendedColorType, ImageDecoder, ImageEncoder};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use crate::error::StegoError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    /// WebP lossless.
+    Webp,
+}
+
+impl OutputFormat {
+    pub fn parse(name: &str) -> Result<Self, StegoError> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Ok(OutputFormat::Png),
+            "webp" => Ok(OutputFormat::Webp),
+            other => Err(StegoError::InvalidInput(format!("Unknown output format {} (png or webp)", other))),
+        }
+    }
+
+    /// Format implied by a file name's extension, if it is one of ours.
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        OutputFormat::parse(ext).ok()
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+        }
+    }
+
+    /// Encode raw pixels (`color` layout) into this container.
+    pub fn encode(&self, raw: &[u8], width: u32, height: u32, color: ExtendedColorType) -> Result<Vec<u8>, StegoError> {
+        self.encode_with_metadata(raw, width, height, color, &Metadata::default())
+    }
+
+    /// `encode` carrying `metadata`; WebP has no text chunks and drops them. Empty metadata
+    /// leaves a PNG with critical chunks only.
+    pub fn encode_with_metadata(&self, raw: &[u8], width: u32, height: u32, color: ExtendedColorType, metadata: &Metadata) -> Result<Vec<u8>, StegoError> {
+        let mut out = Cursor::new(Vec::new());
+        match self {
+            OutputFormat::Png => {
+                let mut encoder = PngEncoder::new(&mut out);
+                metadata.set_on(&mut encoder)?;
+                encoder.write_image(raw, width, height, color)?;
+            }
+            OutputFormat::Webp => {
+                let mut encoder = WebPEncoder::new_lossless(&mut out);
+                metadata.set_on(&mut encoder)?;
+                encoder.write_image(raw, width, height, color)?;
+            }
+        }
+        let mut bytes = out.into_inner();
+        if *self == OutputFormat::Png && *metadata == Metadata::default() {
+            return Ok(critical_chunks_only(&bytes));
+        }
+        if *self == OutputFormat::Png && !metadata.text_chunks.is_empty() {
+            // Before the trailing IEND chunk (12 bytes).
+            let end = bytes.len() - 12;
+            bytes.splice(end..end, metadata.text_chunks.concat());
+        }
+        Ok(bytes)
+    }
+}
+
+/// Cover metadata to carry into a stego image (see the module header).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub icc_profile: Option<Vec<u8>>,
+    /// EXIF block with its orientation set to normal.
+    pub exif: Option<Vec<u8>>,
+    /// Whole PNG text chunks: length, type, data and CRC.
+    pub text_chunks: Vec<Vec<u8>>,
+}
+
+impl Metadata {
+    /// Metadata of a cover whose file contents are `bytes`, read from its `decoder` before the
+    /// pixels are. Unreadable parts are left out.
+    pub fn read(decoder: &mut impl ImageDecoder, bytes: &[u8]) -> Metadata {
+        let exif = decoder.exif_metadata().ok().flatten().map(|mut exif| {
+            let _ = Orientation::remove_from_exif_chunk(&mut exif);
+            exif
+        });
+        Metadata {
+            icc_profile: decoder.icc_profile().ok().flatten(),
+            exif,
+            text_chunks: png_chunks(bytes)
+                .filter(|chunk| matches!(&chunk[4..8], b"tEXt" | b"zTXt" | b"iTXt"))
+                .map(<[u8]>::to_vec)
+                .collect(),
+        }
+    }
+
+    fn set_on(&self, encoder: &mut impl ImageEncoder) -> Result<(), StegoError> {
+        if let Some(icc) = &self.icc_profile {
+            encoder.set_icc_profile(icc.clone()).map_err(image::ImageError::Unsupported)?;
+        }
+        if let Some(exif) = &self.exif {
+            encoder.set_exif_metadata(exif.clone()).map_err(image::ImageError::Unsupported)?;
+        }
+        Ok(())
+    }
+}
+
+/// `png` without its ancillary chunks (those whose type starts with a lower-case letter).
+fn critical_chunks_only(png: &[u8]) -> Vec<u8> {
+    let mut out = png[..8].to_vec();
+    for chunk in png_chunks(png).filter(|chunk| chunk[4].is_ascii_uppercase()) {
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+/// Whole chunks of a PNG file (empty for anything else), up to the first truncated one.
+pub(crate) fn png_chunks(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = bytes.strip_prefix(b"\x89PNG\r\n\x1a\n".as_slice()).unwrap_or_default();
+    std::iter::from_fn(move || {
+        let len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let chunk = rest.get(..len.checked_add(12)?)?;
+        rest = &rest[chunk.len()..];
+        Some(chunk)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webp_output_is_lossless() {
+        let img = image::RgbaImage::from_fn(96, 64, |x, y| image::Rgba([(x * 3) as u8, (y * 5) as u8, (x ^ y) as u8, 255]));
+        let webp = OutputFormat::Webp.encode(img.as_raw(), 96, 64, ExtendedColorType::Rgba8).unwrap();
+        assert_eq!(image::guess_format(&webp).unwrap(), image::ImageFormat::WebP);
+        assert_eq!(image::load_from_memory(&webp).unwrap().to_rgba8(), img);
+        assert_eq!(OutputFormat::from_path(std::path::Path::new("out.WEBP")), Some(OutputFormat::Webp));
+        assert!(OutputFormat::parse("jpeg").is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_cover_metadata_survives_both_codecs() {
+        // Big-endian TIFF with one IFD entry: orientation 6 (rotate 90).
+        let exif = vec![0x4d, 0x4d, 0, 42, 0, 0, 0, 8, 0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut text = vec![0, 0, 0, 27];
+        text.extend_from_slice(b"tEXtComment\0kept through encode");
+        text.extend_from_slice(&[95, 149, 74, 249]);
+        let cover_metadata = Metadata { icc_profile: Some(vec![7; 132]), exif: Some(exif), text_chunks: vec![text.clone()] };
+        let img = image::RgbImage::from_fn(300, 280, |x, y| image::Rgb([(x % 200) as u8 + 20, (y % 200) as u8 + 20, 128]));
+        let cover = OutputFormat::Png.encode_with_metadata(img.as_raw(), 300, 280, ExtendedColorType::Rgb8, &cover_metadata).unwrap();
+
+        let dot = crate::stego_dot::EncodeOptions::default();
+        let outputs = [
+            crate::stego::encode_bytes(&cover, b"with metadata").unwrap(),
+            crate::stego_dot::encode_bytes(&cover, b"with metadata", &dot).unwrap(),
+        ];
+        for out in &outputs {
+            let mut decoder = image::ImageReader::new(Cursor::new(out)).with_guessed_format().unwrap().into_decoder().unwrap();
+            let metadata = Metadata::read(&mut decoder, out);
+            assert_eq!(decoder.dimensions(), (280, 300));
+            assert_eq!(Orientation::from_exif_chunk(metadata.exif.as_deref().unwrap()), Some(Orientation::NoTransforms));
+            assert_eq!(metadata.icc_profile, cover_metadata.icc_profile);
+            assert_eq!(metadata.text_chunks, vec![text.clone()]);
+        }
+        assert_eq!(crate::stego_dot::decode_bytes(&outputs[1], None).unwrap(), b"with metadata");
+        let stripped = crate::stego_dot::encode_bytes(&cover, b"x", &crate::stego_dot::EncodeOptions { strip_metadata: true, ..dot }).unwrap();
+        let cover_path = std::env::temp_dir().join("stego_metadata_cover.png");
+        std::fs::write(&cover_path, &cover).unwrap();
+        let dwt = crate::stego::EncodeOptions { strip_metadata: true, ..Default::default() };
+        let stripped_dwt = crate::stego::encode(&cover_path, b"x", &dwt).unwrap();
+        let _ = std::fs::remove_file(cover_path);
+        for out in [&stripped, &stripped_dwt] {
+            let mut decoder = image::ImageReader::new(Cursor::new(out)).with_guessed_format().unwrap().into_decoder().unwrap();
+            assert_eq!(Metadata::read(&mut decoder, out), Metadata::default());
+            let types: Vec<&[u8]> = png_chunks(out).map(|chunk| &chunk[4..8]).collect();
+            assert!(types.iter().all(|t| [&b"IHDR"[..], b"IDAT", b"IEND"].contains(t)), "{types:?}");
+        }
+    }
+}
@@ -0,0 +1,291 @@
+// Inner payload header, written in front of the payload before it goes into any codec:
+// magic, version, flags, compression, MIME type and optionally the original file name, so a
+// decoder knows what it extracted instead of guessing JSON vs binary.
+// Layout: MAGIC, version, flags, compression, mime len (u8), mime, [name len (u8), name],
+// [bundle id (8), fragment index (u16), fragment total (u16)], data.
+// Payloads without the magic (images from older builds) read as headerless.
+// The DWT and dot codecs zstd-compress what they embed when that is smaller (`compress`) and undo
+// it on decode (`decompress`), so callers get back exactly the bytes they embedded. A headerless
+// payload gets a bare header for this, flagged so decompress drops it again.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::StegoError;
+
+/// Starts with NUL, so no JSON or text payload is mistaken for a header.
+pub const MAGIC: &[u8; 4] = b"\0SGP";
+pub const VERSION: u8 = 1;
+const FLAG_FILENAME: u8 = 1;
+/// Header only there to carry the compression; the embedded payload had none.
+const FLAG_BARE: u8 = 2;
+const FLAG_FRAGMENT: u8 = 4;
+#[cfg(feature = "zstd")]
+pub(crate) const ZSTD_LEVEL: i32 = 19;
+/// Largest decompressed payload, so a crafted image cannot expand into gigabytes.
+pub const MAX_DECOMPRESSED: usize = 64 << 20;
+
+pub const MIME_JSON: &str = "application/json";
+pub const MIME_TEXT: &str = "text/plain";
+/// Payloads encrypted by stego_crypto.
+pub const MIME_ENCRYPTED: &str = "application/vnd.stegstr.encrypted";
+pub const MIME_BINARY: &str = "application/octet-stream";
+/// Bundles in the binary v2 format (see `bundle`).
+pub const MIME_BUNDLE: &str = "application/vnd.stegstr.bundle";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+}
+
+impl Compression {
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Zstd => 1,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PayloadHeader {
+    pub mime_type: String,
+    /// Name of the file the payload came from, without directories.
+    #[serde(default)]
+    pub filename: Option<String>,
+    #[serde(default)]
+    pub compression: Compression,
+    /// Set when the data is one piece of a payload split across images (see `fragment`).
+    #[serde(default)]
+    pub fragment: Option<Fragment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Fragment {
+    /// 16 hex digits shared by all fragments of one payload.
+    pub bundle_id: String,
+    /// 0-based.
+    pub index: u16,
+    pub total: u16,
+}
+
+impl PayloadHeader {
+    pub fn new(mime_type: impl Into<String>) -> Self {
+        PayloadHeader { mime_type: mime_type.into(), filename: None, compression: Compression::None, fragment: None }
+    }
+
+    /// Header for `payload` with its MIME type guessed from the bytes: encrypted, v2 bundle, JSON
+    /// (starts with '{'), other UTF-8 text, else binary.
+    pub fn sniff(payload: &[u8]) -> Self {
+        #[cfg(feature = "crypto")]
+        if crate::stego_crypto::is_encrypted_payload(payload) {
+            return PayloadHeader::new(MIME_ENCRYPTED);
+        }
+        #[cfg(feature = "crypto")]
+        if crate::bundle::decode_v2(payload).is_ok() {
+            return PayloadHeader::new(MIME_BUNDLE);
+        }
+        PayloadHeader::new(match std::str::from_utf8(payload) {
+            Ok(s) if s.trim_start().starts_with('{') => MIME_JSON,
+            Ok(_) => MIME_TEXT,
+            Err(_) => MIME_BINARY,
+        })
+    }
+
+    /// `payload` with this header in front.
+    pub fn write(&self, payload: &[u8]) -> Result<Vec<u8>, StegoError> {
+        self.write_flags(0, payload)
+    }
+
+    fn write_flags(&self, flags: u8, payload: &[u8]) -> Result<Vec<u8>, StegoError> {
+        let mime = field(&self.mime_type, "MIME type")?;
+        let mut out = Vec::with_capacity(MAGIC.len() + 4 + mime.len() + payload.len());
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        let fragment = self.fragment.as_ref().map(|f| f.bundle_id_bytes().map(|id| (id, f))).transpose()?;
+        out.push(flags | if self.filename.is_some() { FLAG_FILENAME } else { 0 } | if fragment.is_some() { FLAG_FRAGMENT } else { 0 });
+        out.push(self.compression.id());
+        out.push(mime.len() as u8);
+        out.extend_from_slice(mime);
+        if let Some(name) = &self.filename {
+            let name = field(name, "file name")?;
+            out.push(name.len() as u8);
+            out.extend_from_slice(name);
+        }
+        if let Some((id, f)) = fragment {
+            out.extend_from_slice(&id);
+            out.extend_from_slice(&f.index.to_be_bytes());
+            out.extend_from_slice(&f.total.to_be_bytes());
+        }
+        out.extend_from_slice(payload);
+        Ok(out)
+    }
+}
+
+impl Fragment {
+    fn bundle_id_bytes(&self) -> Result<[u8; 8], StegoError> {
+        let invalid = || StegoError::InvalidInput(format!("Fragment bundle id must be 16 hex digits, not {:?}", self.bundle_id));
+        if self.bundle_id.len() != 16 {
+            return Err(invalid());
+        }
+        let mut id = [0u8; 8];
+        for (i, b) in id.iter_mut().enumerate() {
+            *b = u8::from_str_radix(self.bundle_id.get(i * 2..i * 2 + 2).ok_or_else(invalid)?, 16).map_err(|_| invalid())?;
+        }
+        Ok(id)
+    }
+}
+
+fn field<'a>(value: &'a str, what: &str) -> Result<&'a [u8], StegoError> {
+    if value.len() > u8::MAX as usize {
+        return Err(StegoError::InvalidInput(format!("Payload {} longer than 255 bytes", what)));
+    }
+    Ok(value.as_bytes())
+}
+
+/// Split a decoded payload into its header (None for headerless payloads) and data, decompressing
+/// the data if it is still compressed.
+pub fn read(payload: &[u8]) -> Result<(Option<PayloadHeader>, Vec<u8>), StegoError> {
+    let payload = decompress(payload)?;
+    Ok(match parse(&payload)? {
+        Some((header, _, data)) => (Some(header), data.to_vec()),
+        None => (None, payload),
+    })
+}
+
+/// What the codecs embed for `payload`: zstd-compressed behind its header (or a bare one) when
+/// that is smaller, else `payload` as is. Without the `zstd` feature always as is.
+pub fn compress(payload: &[u8]) -> Vec<u8> {
+    #[cfg(feature = "zstd")]
+    if let Some(packed) = zstd_pack(payload) {
+        if packed.len() < payload.len() {
+            return packed;
+        }
+    }
+    payload.to_vec()
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_pack(payload: &[u8]) -> Option<Vec<u8>> {
+    let (header, flags, data) = match parse(payload).ok()? {
+        Some((header, _, _)) if header.compression != Compression::None => return None,
+        Some((header, flags, data)) => (header, flags, data),
+        None => (PayloadHeader::new(""), FLAG_BARE, payload),
+    };
+    let packed = zstd::encode_all(data, ZSTD_LEVEL).ok()?;
+    PayloadHeader { compression: Compression::Zstd, ..header }.write_flags(flags & FLAG_BARE, &packed).ok()
+}
+
+/// Bytes `compress` embeds for `payload` (for capacity checks and reports).
+pub fn stored_len(payload: &[u8]) -> usize {
+    compress(payload).len()
+}
+
+/// Undo `compress`: the payload exactly as it was given to the encoder.
+pub fn decompress(stored: &[u8]) -> Result<Vec<u8>, StegoError> {
+    let Some((header, flags, data)) = parse(stored)? else {
+        return Ok(stored.to_vec());
+    };
+    if header.compression == Compression::None {
+        return Ok(stored.to_vec());
+    }
+    let data = zstd_unpack(data)?;
+    if flags & FLAG_BARE != 0 {
+        return Ok(data);
+    }
+    PayloadHeader { compression: Compression::None, ..header }.write(&data)
+}
+
+pub(crate) fn zstd_unpack(data: &[u8]) -> Result<Vec<u8>, StegoError> {
+    use std::io::Read;
+    let corrupt = |e: &dyn std::fmt::Display| StegoError::NotStego(format!("Compressed payload corrupt: {}", e));
+    let decoder = ruzstd::decoding::StreamingDecoder::new(data).map_err(|e| corrupt(&e))?;
+    let mut out = Vec::new();
+    decoder.take(MAX_DECOMPRESSED as u64 + 1).read_to_end(&mut out).map_err(|e| corrupt(&e))?;
+    if out.len() > MAX_DECOMPRESSED {
+        return Err(StegoError::Capacity(format!("Compressed payload expands beyond {} MiB", MAX_DECOMPRESSED >> 20)));
+    }
+    Ok(out)
+}
+
+/// Header, flags and data of a payload.
+type Parsed<'a> = (PayloadHeader, u8, &'a [u8]);
+
+/// `Parsed` of a payload starting with MAGIC; None for headerless payloads.
+fn parse(payload: &[u8]) -> Result<Option<Parsed<'_>>, StegoError> {
+    let Some(rest) = payload.strip_prefix(&MAGIC[..]) else {
+        return Ok(None);
+    };
+    let truncated = || StegoError::NotStego("Payload header truncated".to_string());
+    let [version, flags, compression, mime_len, rest @ ..] = rest else {
+        return Err(truncated());
+    };
+    if *version != VERSION {
+        return Err(StegoError::Other(format!("Unsupported payload header version {} (newer Stegstr?)", version)));
+    }
+    let compression = Compression::from_id(*compression)
+        .ok_or_else(|| StegoError::Other(format!("Unknown payload compression {}", compression)))?;
+    let (mime, mut rest) = rest.split_at_checked(*mime_len as usize).ok_or_else(truncated)?;
+    let filename = if flags & FLAG_FILENAME != 0 {
+        let (&len, after) = rest.split_first().ok_or_else(truncated)?;
+        let (name, after) = after.split_at_checked(len as usize).ok_or_else(truncated)?;
+        rest = after;
+        Some(String::from_utf8_lossy(name).into_owned())
+    } else {
+        None
+    };
+    let fragment = if flags & FLAG_FRAGMENT != 0 {
+        let (info, after) = rest.split_at_checked(12).ok_or_else(truncated)?;
+        rest = after;
+        Some(Fragment {
+            bundle_id: info[..8].iter().map(|b| format!("{:02x}", b)).collect(),
+            index: u16::from_be_bytes([info[8], info[9]]),
+            total: u16::from_be_bytes([info[10], info[11]]),
+        })
+    } else {
+        None
+    };
+    let header = PayloadHeader { mime_type: String::from_utf8_lossy(mime).into_owned(), filename, compression, fragment };
+    Ok(Some((header, *flags, rest)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip_and_headerless() {
+        let header = PayloadHeader { filename: Some("notes.txt".to_string()), ..PayloadHeader::sniff(b"hello") };
+        assert_eq!(header.mime_type, MIME_TEXT);
+        let framed = header.write(b"hello").unwrap();
+        assert_eq!(read(&framed).unwrap(), (Some(header), b"hello".to_vec()));
+        assert_eq!(PayloadHeader::sniff(b"{\"events\":[]}").mime_type, MIME_JSON);
+        assert_eq!(read(b"{\"events\":[]}").unwrap(), (None, b"{\"events\":[]}".to_vec()));
+        assert!(read(&framed[..MAGIC.len() + 5]).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compress_round_trip() {
+        let bundle = format!("{{\"version\":1,\"events\":[{}]}}", "{\"kind\":1,\"content\":\"gm\"},".repeat(40));
+        let headed = PayloadHeader::sniff(bundle.as_bytes()).write(bundle.as_bytes()).unwrap();
+        for payload in [bundle.as_bytes(), &headed[..], b"tiny"] {
+            let stored = compress(payload);
+            assert_eq!(decompress(&stored).unwrap(), payload);
+        }
+        assert!(stored_len(bundle.as_bytes()) < bundle.len() / 4);
+        assert_eq!(compress(b"tiny"), b"tiny");
+        assert_eq!(read(&compress(&headed)).unwrap().1, bundle.as_bytes());
+    }
+}
@@ -0,0 +1,109 @@
+// Progress reporting and cancellation for long codec runs (a DWT embed into a 50 MP cover, a
+// sliding-window decode). The caller makes a `Progress` with a report callback and hands it to a
+// `*_with_progress` function; the codec reports the fraction done as it goes and, once the caller
+// calls `cancel` (from any thread), stops at its next check with StegoError::Cancelled.
+// Clones share the callback and the cancel flag. `stage` narrows a clone to a slice of the range,
+// for callers running several codec passes as one task. `Progress::default()` reports nowhere and
+// is never cancelled.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::StegoError;
+
+struct Shared {
+    cancelled: AtomicBool,
+    report: Box<dyn Fn(f64) + Send + Sync>,
+}
+
+/// Progress callback and cancel flag of one task (module header).
+#[derive(Clone)]
+pub struct Progress {
+    shared: Option<Arc<Shared>>,
+    /// Slice of the task's 0-1 range this handle reports into.
+    start: f64,
+    span: f64,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress { shared: None, start: 0.0, span: 1.0 }
+    }
+}
+
+impl std::fmt::Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Progress").field("start", &self.start).field("span", &self.span).field("cancelled", &self.is_cancelled()).finish()
+    }
+}
+
+impl Progress {
+    /// `report` receives the fraction done (0-1) of the whole task.
+    pub fn new(report: impl Fn(f64) + Send + Sync + 'static) -> Self {
+        Progress { shared: Some(Arc::new(Shared { cancelled: AtomicBool::new(false), report: Box::new(report) })), ..Default::default() }
+    }
+
+    /// Handle for stage `index` of `count` equal stages of this one's range.
+    pub fn stage(&self, index: usize, count: usize) -> Progress {
+        let span = self.span / count.max(1) as f64;
+        Progress { shared: self.shared.clone(), start: self.start + span * index as f64, span }
+    }
+
+    /// Report `done` of `total` steps of this handle's range.
+    pub fn step(&self, done: usize, total: usize) {
+        if let Some(shared) = &self.shared {
+            let fraction = if total == 0 { 1.0 } else { (done as f64 / total as f64).min(1.0) };
+            (shared.report)(self.start + self.span * fraction);
+        }
+    }
+
+    /// Ask the codec to stop; it fails with StegoError::Cancelled at its next check.
+    pub fn cancel(&self) {
+        if let Some(shared) = &self.shared {
+            shared.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.as_ref().is_some_and(|s| s.cancelled.load(Ordering::Relaxed))
+    }
+
+    /// Err(Cancelled) once `cancel` has been called.
+    pub fn check(&self) -> Result<(), StegoError> {
+        match self.is_cancelled() {
+            true => Err(StegoError::Cancelled("Cancelled".to_string())),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+    use crate::stego;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_progress_reports_tiles_and_cancels() {
+        let cover = image::RgbaImage::from_fn(512, 512, |x, y| image::Rgba([(40 + (x * 3 + y) % 170) as u8, (40 + y * 5 % 170) as u8, (40 + (x ^ y) % 170) as u8, 255]));
+        let cover_path = std::env::temp_dir().join(format!("stegstr_progress_{}.png", std::process::id()));
+        cover.save(&cover_path).unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let progress = Progress::new(move |f| sink.lock().unwrap().push(f));
+
+        let png = stego::encode_with_progress(&cover_path, b"tracked", None, &Default::default(), &progress).unwrap();
+        let reports = std::mem::take(&mut *seen.lock().unwrap());
+        assert_eq!(reports.len(), 4);
+        assert_eq!(reports.iter().copied().fold(0.0, f64::max), 1.0);
+        progress.stage(1, 2).step(1, 2);
+        assert_eq!(seen.lock().unwrap().pop(), Some(0.75));
+
+        progress.cancel();
+        assert!(matches!(stego::encode_with_progress(&cover_path, b"tracked", None, &Default::default(), &progress), Err(StegoError::Cancelled(_))));
+        std::fs::write(&cover_path, png).unwrap();
+        assert!(matches!(stego::decode_with_progress(&cover_path, None, &progress), Err(StegoError::Cancelled(_))));
+        assert_eq!(stego::decode_with_progress(&cover_path, None, &Progress::default()).unwrap().0, b"tracked");
+        let _ = std::fs::remove_file(cover_path);
+    }
+}
@@ -0,0 +1,251 @@
+// Reed-Solomon over GF(2^8), compatible with Python's `reedsolo` (RSCodec defaults: primitive
+// polynomial 0x11d, generator 2, first consecutive root 0, 255-byte blocks). Messages longer
+// than 255 - nsym bytes are split into blocks, each carrying its own nsym parity bytes.
+// Decoding corrects errors and known erasures (Forney syndromes + Berlekamp-Massey).
+
+use std::sync::OnceLock;
+
+const PRIM: u16 = 0x11d;
+pub const BLOCK: usize = 255;
+
+struct Tables {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut t = Tables { exp: [0; 512], log: [0; 256] };
+        let mut x: u16 = 1;
+        for i in 0..255 {
+            t.exp[i] = x as u8;
+            t.log[x as usize] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= PRIM;
+            }
+        }
+        for i in 255..512 {
+            t.exp[i] = t.exp[i - 255];
+        }
+        t
+    })
+}
+
+pub(crate) fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    let t = tables();
+    t.exp[t.log[a as usize] as usize + t.log[b as usize] as usize]
+}
+
+pub(crate) fn gf_div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        return 0;
+    }
+    let t = tables();
+    t.exp[(t.log[a as usize] as usize + 255 - t.log[b as usize] as usize) % 255]
+}
+
+/// alpha^i.
+fn pow_alpha(i: usize) -> u8 {
+    tables().exp[i % 255]
+}
+
+// Polynomials below are in ascending order (index = power of x), except codewords, whose first
+// byte is the highest power, as in reedsolo.
+
+fn poly_mul(p: &[u8], q: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; p.len() + q.len() - 1];
+    for (i, &a) in p.iter().enumerate() {
+        for (j, &b) in q.iter().enumerate() {
+            out[i + j] ^= gf_mul(a, b);
+        }
+    }
+    out
+}
+
+fn poly_eval(p: &[u8], x: u8) -> u8 {
+    p.iter().rev().fold(0, |acc, &c| gf_mul(acc, x) ^ c)
+}
+
+/// Generator polynomial prod (x - alpha^i), i = 0..nsym, highest power first.
+fn generator(nsym: usize) -> Vec<u8> {
+    let mut g = vec![1u8];
+    for i in 0..nsym {
+        let mut next = vec![0u8; g.len() + 1];
+        for (j, &c) in g.iter().enumerate() {
+            next[j] ^= c;
+            next[j + 1] ^= gf_mul(c, pow_alpha(i));
+        }
+        g = next;
+    }
+    g
+}
+
+/// One block: `data` followed by `nsym` parity bytes.
+fn encode_block(data: &[u8], gen: &[u8]) -> Vec<u8> {
+    let nsym = gen.len() - 1;
+    let mut out = data.to_vec();
+    out.resize(data.len() + nsym, 0);
+    for i in 0..data.len() {
+        let coef = out[i];
+        if coef != 0 {
+            for (j, &g) in gen.iter().enumerate().skip(1) {
+                out[i + j] ^= gf_mul(g, coef);
+            }
+        }
+    }
+    out[..data.len()].copy_from_slice(data);
+    out
+}
+
+/// S_j = c(alpha^j), j = 0..nsym.
+fn syndromes(block: &[u8], nsym: usize) -> Vec<u8> {
+    (0..nsym)
+        .map(|j| {
+            let x = pow_alpha(j);
+            block.iter().fold(0, |acc, &c| gf_mul(acc, x) ^ c)
+        })
+        .collect()
+}
+
+/// Berlekamp-Massey: shortest LFSR (ascending locator) generating `s`.
+fn berlekamp_massey(s: &[u8]) -> Vec<u8> {
+    let mut c = vec![1u8];
+    let mut b = vec![1u8];
+    let (mut l, mut m, mut bd) = (0usize, 1usize, 1u8);
+    for n in 0..s.len() {
+        let mut d = s[n];
+        for i in 1..=l.min(c.len() - 1) {
+            d ^= gf_mul(c[i], s[n - i]);
+        }
+        if d == 0 {
+            m += 1;
+            continue;
+        }
+        let coef = gf_div(d, bd);
+        let mut next = c.clone();
+        if next.len() < b.len() + m {
+            next.resize(b.len() + m, 0);
+        }
+        for (i, &bi) in b.iter().enumerate() {
+            next[i + m] ^= gf_mul(coef, bi);
+        }
+        if 2 * l <= n {
+            l = n + 1 - l;
+            b = std::mem::replace(&mut c, next);
+            bd = d;
+            m = 1;
+        } else {
+            c = next;
+            m += 1;
+        }
+    }
+    c.truncate(l + 1);
+    c
+}
+
+/// Correct one block in place. `erasures` are byte positions within the block.
+fn correct_block(block: &mut [u8], nsym: usize, erasures: &[usize]) -> Result<(), String> {
+    let n = block.len();
+    if erasures.len() > nsym {
+        return Err("Too many erasures to correct".to_string());
+    }
+    let synd = syndromes(block, nsym);
+    if synd.iter().all(|&s| s == 0) {
+        return Ok(());
+    }
+    // Erasure locator prod (1 - X_k x), X_k = alpha^(n-1-pos).
+    let mut gamma = vec![1u8];
+    for &p in erasures {
+        gamma = poly_mul(&gamma, &[1, pow_alpha(n - 1 - p)]);
+    }
+    // Forney syndromes: S(x)Gamma(x) mod x^nsym, past the first `erasures` terms.
+    let mut modified = poly_mul(&synd, &gamma);
+    modified.truncate(nsym);
+    let sigma = berlekamp_massey(&modified[erasures.len()..]);
+    if 2 * (sigma.len() - 1) + erasures.len() > nsym {
+        return Err("Too many errors to correct".to_string());
+    }
+    let lambda = poly_mul(&sigma, &gamma);
+    let degree = lambda.iter().rposition(|&c| c != 0).unwrap_or(0);
+    let positions: Vec<usize> = (0..n).filter(|&p| poly_eval(&lambda, pow_alpha(255 - (n - 1 - p) % 255)) == 0).collect();
+    if positions.len() != degree {
+        return Err("Could not locate errors".to_string());
+    }
+    let mut omega = poly_mul(&synd, &lambda);
+    omega.truncate(nsym);
+    let derivative: Vec<u8> = lambda.iter().enumerate().skip(1).map(|(i, &c)| if i % 2 == 1 { c } else { 0 }).collect();
+    for p in positions {
+        let x = pow_alpha(n - 1 - p);
+        let x_inv = gf_div(1, x);
+        let denom = poly_eval(&derivative, x_inv);
+        if denom == 0 {
+            return Err("Could not compute error magnitude".to_string());
+        }
+        block[p] ^= gf_div(gf_mul(x, poly_eval(&omega, x_inv)), denom);
+    }
+    if syndromes(block, nsym).iter().any(|&s| s != 0) {
+        return Err("Residual errors after correction".to_string());
+    }
+    Ok(())
+}
+
+/// Encode `data` in blocks of 255 - nsym bytes, each followed by nsym parity bytes.
+pub fn encode(data: &[u8], nsym: usize) -> Vec<u8> {
+    let gen = generator(nsym);
+    data.chunks(BLOCK - nsym).flat_map(|chunk| encode_block(chunk, &gen)).collect()
+}
+
+/// Length of `encode`'s output for `data_len` bytes.
+pub fn encoded_len(data_len: usize, nsym: usize) -> usize {
+    data_len + data_len.div_ceil(BLOCK - nsym) * nsym
+}
+
+/// Decode a codeword from `encode`, correcting errors and `erasures` (byte positions in the
+/// codeword). Returns the data without parity.
+pub fn decode(codeword: &[u8], nsym: usize, erasures: &[usize]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(codeword.len());
+    for (i, chunk) in codeword.chunks(BLOCK).enumerate() {
+        if chunk.len() <= nsym {
+            return Err("Reed-Solomon block too short".to_string());
+        }
+        let start = i * BLOCK;
+        let local: Vec<usize> = erasures.iter().filter(|&&p| p >= start && p < start + chunk.len()).map(|&p| p - start).collect();
+        let mut block = chunk.to_vec();
+        correct_block(&mut block, nsym, &local)?;
+        out.extend_from_slice(&block[..block.len() - nsym]);
+    }
+    Ok(out)
+}
+
+#[cfg(all(test, feature = "crypto"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reed_solomon_errors_and_erasures() {
+        let data: Vec<u8> = (0..300u32).map(|i| (i * 31 % 256) as u8).collect();
+        let code = encode(&data, 32);
+        assert_eq!(code.len(), 300 + 2 * 32);
+        // reedsolo RSCodec(64).encode(b"STEGSTR\0\0\0\rchannel_test!"), from channel_simulator's
+        // demo_qim_stego.jpg.
+        let known = hex::decode("535445475354520000000d6368616e6e656c5f7465737421aa804cb80a3cad1cd7e0b4bab0250fc8144f915a963145e185591af2eb4af87f5487500e629619e1a78ab5cb113dbb9ae566136b33028b09bb4e348ba421c37e").unwrap();
+        assert_eq!(encode(&known[..24], 64), known);
+
+        let mut damaged = code.clone();
+        for p in [0, 5, 40, 100, 222] {
+            damaged[p] ^= 0x5a;
+        }
+        let erased = [300, 301, 302];
+        for &p in &erased {
+            damaged[p] = 0;
+        }
+        assert_eq!(decode(&damaged, 32, &erased).unwrap(), data);
+        damaged[..20].iter_mut().for_each(|b| *b ^= 0xff);
+        assert!(decode(&damaged, 32, &[]).is_err());
+    }
+}
@@ -0,0 +1,1810 @@
+// DWT (Haar 2D) steganography: payload = magic + 4-byte length (big-endian) + data.
+// Embeds in LSB of LH (detail) coefficients.
+// Phase 1.1: Tile-based redundant embedding for crop survival.
+// Tiles are embedded, and decode windows scanned, in parallel (rayon).
+
+use image::DynamicImage;
+use image::ExtendedColorType;
+use image::ImageDecoder;
+use image::ImageReader;
+pub use image::metadata::Orientation;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Write};
+
+use crate::error::StegoError;
+use crate::progress::Progress;
+use crate::mask::{Mask, MaskSpec};
+use crate::output::{Metadata, OutputFormat};
+use crate::payload_header;
+use crate::stego_key::StegoKey;
+
+const MAGIC: &[u8] = b"STEGSTR";
+const MAGIC_LEN: usize = 7;
+const LENGTH_BYTES: usize = 4;
+const TILE_SIZE: u32 = 256;
+/// Tile sizes `encode` accepts, in the order the decoder tries them. Non-default tiles always use
+/// the FEC frame: its checked header stops a window of the wrong size from returning a scrambled
+/// payload.
+pub const TILE_SIZES: &[u32] = &[256, 128, 512, 64];
+/// Parity used for non-default tile sizes when none was asked for.
+const CUSTOM_TILE_PARITY: u8 = 16;
+const FEC_VERSION: u8 = 1;
+/// Parity bytes protecting the FEC header (version, parity, 4-byte length).
+const HEADER_NSYM: usize = 8;
+const FEC_HEADER_LEN: usize = 6 + HEADER_NSYM;
+/// Largest parity per 255-byte block (leaves at least half of each block for data).
+pub const MAX_PARITY: u8 = 128;
+/// Bits in the pilot sequence of each tile.
+const PILOT_BITS: usize = 256;
+/// Pilot bits a rescaled corner must match to count as found (about 90%).
+const PILOT_MIN_MATCH: usize = 232;
+/// Highest `EncodeOptions::strength`.
+pub const MAX_STRENGTH: u8 = 8;
+/// QIM step of strength 1, in level-2 LH units (one unit moves 16 pixels by one level each).
+const QIM_STEP: f64 = 4.0;
+/// Magic bits a QIM read may get wrong before its header is checked.
+const QIM_MAGIC_ERRORS: usize = 10;
+/// Parity when STEGSTR_DWT_PARITY is unset: 8 correctable bytes per block.
+pub const DEFAULT_PARITY: u8 = 16;
+/// Reed-Solomon parity bytes per block for `encode`; 0 writes the legacy frame.
+pub const PARITY_ENV: &str = "STEGSTR_DWT_PARITY";
+
+pub fn parity() -> u8 {
+    std::env::var(PARITY_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse::<u8>().ok())
+        .map_or(DEFAULT_PARITY, |p| p.min(MAX_PARITY))
+}
+
+/// Layout of a DWT embed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// Tile edge in pixels, one of TILE_SIZES. Smaller tiles keep small covers tiled (and survive
+    /// tighter crops); larger ones hold bigger payloads per copy.
+    pub tile_size: u32,
+    /// Most copies of the payload to embed, in reading order; 0 fills every tile.
+    pub redundancy: u32,
+    /// RGB channels carrying bits (1-3, starting with red). Fewer channels change fewer pixels
+    /// but hold proportionally less.
+    pub channels: u8,
+    /// Reed-Solomon parity bytes per 255-byte block; 0 writes the legacy frame.
+    pub parity: u8,
+    /// Seeds the coefficient order; `decode_with_key` needs the same key. Targets also hand it to
+    /// the dot codec.
+    pub key: Option<StegoKey>,
+    /// Container of the stego image (PNG or WebP lossless); targets hand it to the dot codec too.
+    pub format: OutputFormat,
+    /// Stripe the payload over the full tiles (one fountain-coded part each) so that any this
+    /// many of them rebuild it; 0 repeats the whole payload in every tile.
+    pub interleave: u32,
+    /// 0 writes LSBs of the level-1 LH band; 1 to MAX_STRENGTH quantise level-2 LH coefficients
+    /// to steps of 4 * strength instead (`embed_qim_in_tile`), surviving mild blur and sharpening.
+    pub strength: u8,
+    /// Leave the cover's ICC profile, EXIF and PNG text chunks out of the output.
+    pub strip_metadata: bool,
+    /// Animated covers: fountain-code the payload into one part per frame, any this many of which
+    /// rebuild it; 0 embeds the whole payload in every frame. Still covers take 0. Targets hand it
+    /// to the dot codec too.
+    pub spread: u32,
+}
+
+impl Default for EncodeOptions {
+    /// 256 px tiles, every tile, all channels, parity from STEGSTR_DWT_PARITY, no key, PNG,
+    /// not interleaved, level-1 LSBs, cover metadata kept, payload in every frame.
+    fn default() -> Self {
+        EncodeOptions {
+            tile_size: TILE_SIZE,
+            redundancy: 0,
+            channels: 3,
+            parity: parity(),
+            key: None,
+            format: OutputFormat::Png,
+            interleave: 0,
+            strength: 0,
+            strip_metadata: false,
+            spread: 0,
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// Checked copy; non-default tile sizes and QIM embeds get CUSTOM_TILE_PARITY when parity is 0.
+    pub fn validated(&self) -> Result<EncodeOptions, StegoError> {
+        if !TILE_SIZES.contains(&self.tile_size) {
+            return Err(StegoError::InvalidInput(format!("Tile size must be one of {:?}", TILE_SIZES)));
+        }
+        if !(1..=3).contains(&self.channels) {
+            return Err(StegoError::InvalidInput("Channels must be 1, 2 or 3".to_string()));
+        }
+        if self.parity > MAX_PARITY {
+            return Err(StegoError::InvalidInput(format!("Parity must be at most {} bytes per block", MAX_PARITY)));
+        }
+        if self.strength > MAX_STRENGTH {
+            return Err(StegoError::InvalidInput(format!("Strength must be at most {}", MAX_STRENGTH)));
+        }
+        let parity = match (self.tile_size, self.strength, self.parity) {
+            (TILE_SIZE, 0, p) | (_, _, p @ 1..) => p,
+            _ => CUSTOM_TILE_PARITY,
+        };
+        Ok(EncodeOptions { parity, ..*self })
+    }
+}
+
+#[cfg(feature = "fs")]
+fn load_image_with_orientation(image_path: &std::path::Path) -> Result<image::RgbaImage, StegoError> {
+    let reader = ImageReader::open(image_path)?.with_guessed_format()?;
+    oriented_rgba(reader)
+}
+
+/// Like `load_image_with_orientation`, for image file contents (format sniffed from the data).
+fn load_image_bytes_with_orientation(bytes: &[u8]) -> Result<image::RgbaImage, StegoError> {
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    oriented_rgba(reader)
+}
+
+fn oriented_rgba<R: std::io::BufRead + std::io::Seek>(reader: ImageReader<R>) -> Result<image::RgbaImage, StegoError> {
+    Ok(oriented_image(reader.into_decoder()?)?.to_rgba8())
+}
+
+/// The decoded image with its EXIF orientation applied, in its own colour type.
+fn oriented_image(mut decoder: impl ImageDecoder) -> Result<DynamicImage, StegoError> {
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// Cover for `encode`: like `load_image_with_orientation`, keeping the colour type, and the
+/// metadata to carry over.
+#[cfg(feature = "fs")]
+fn load_cover(image_path: &std::path::Path) -> Result<(DynamicImage, Metadata), StegoError> {
+    load_cover_bytes(&std::fs::read(image_path)?)
+}
+
+/// `load_cover` for image file contents.
+fn load_cover_bytes(bytes: &[u8]) -> Result<(DynamicImage, Metadata), StegoError> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?.into_decoder()?;
+    let metadata = Metadata::read(&mut decoder, bytes);
+    Ok((oriented_image(decoder)?, metadata))
+}
+
+/// Channels carrying bits in `cover`: one for grayscale covers (so they stay grayscale).
+fn cover_channels(cover: &DynamicImage, channels: u8) -> u8 {
+    if cover.color().has_color() { channels } else { 1 }
+}
+
+/// `embedded` (the Haar path's RGBA8 result, possibly cropped) written in `cover`'s colour type:
+/// grey or RGB, alpha only when the cover has it, 16 bits per sample for 16-bit covers in PNG
+/// (WebP holds 8 bits). The Haar path works on the 8-bit rounding of each 16-bit sample, so
+/// samples move by 257 per 8-bit step, keeping what lies below the 8-bit level, and the 8-bit
+/// conversion on decode reads exactly the embedded values.
+fn encode_like_cover(cover: &DynamicImage, embedded: &image::RgbaImage, format: OutputFormat, metadata: &Metadata) -> Result<Vec<u8>, StegoError> {
+    let (w, h) = embedded.dimensions();
+    let color = cover.color();
+    let samples: &[usize] = match (color.has_color(), color.has_alpha()) {
+        (false, false) => &[0],
+        (false, true) => &[0, 3],
+        (true, false) => &[0, 1, 2],
+        (true, true) => &[0, 1, 2, 3],
+    };
+    if color.bytes_per_pixel() / color.channel_count() == 2 && format == OutputFormat::Png {
+        let wide = cover.to_rgba16();
+        let mut raw = Vec::with_capacity((w * h) as usize * samples.len() * 2);
+        for (x, y, p) in embedded.enumerate_pixels() {
+            let original = wide.get_pixel(x, y);
+            for &c in samples {
+                let before = ((original[c] as i32 + 128) / 257) as u8;
+                let moved = original[c] as i32 + 257 * (p[c] as i32 - before as i32);
+                raw.extend_from_slice(&(moved.clamp(0, 65535) as u16).to_ne_bytes());
+            }
+        }
+        let layout = [ExtendedColorType::L16, ExtendedColorType::La16, ExtendedColorType::Rgb16, ExtendedColorType::Rgba16][samples.len() - 1];
+        return format.encode_with_metadata(&raw, w, h, layout, metadata);
+    }
+    let raw: Vec<u8> = embedded.pixels().flat_map(|p| samples.iter().map(move |&c| p[c])).collect();
+    let layout = [ExtendedColorType::L8, ExtendedColorType::La8, ExtendedColorType::Rgb8, ExtendedColorType::Rgba8][samples.len() - 1];
+    format.encode_with_metadata(&raw, w, h, layout, metadata)
+}
+
+/// Crop image to even dimensions for DWT (required for Haar 2x2).
+fn ensure_even_dimensions(img: &image::RgbaImage) -> image::RgbaImage {
+    let w = img.width();
+    let h = img.height();
+    let w_even = if w.is_multiple_of(2) { w } else { w.saturating_sub(1) };
+    let h_even = if h.is_multiple_of(2) { h } else { h.saturating_sub(1) };
+    if w_even == w && h_even == h {
+        img.clone()
+    } else {
+        image::imageops::crop_imm(img, 0, 0, w_even.max(2), h_even.max(2)).to_image()
+    }
+}
+
+// Haar 2x2 DWT: block [a,b; c,d] -> LL=(a+b+c+d)/4, LH=(b+d-a-c)/4, HL=(c+d-a-b)/4, HH=(b+c-a-d)/4
+#[allow(clippy::identity_op)]
+fn haar2d_forward(
+    img: &[u8],
+    w: u32,
+    h: u32,
+    ch: usize,
+) -> (Vec<i32>, Vec<i32>, Vec<i32>, Vec<i32>) {
+    let half_w = (w / 2) as usize;
+    let half_h = (h / 2) as usize;
+    let stride = (w * 4) as usize;
+    let mut ll = vec![0i32; half_w * half_h];
+    let mut lh = vec![0i32; half_w * half_h];
+    let mut hl = vec![0i32; half_w * half_h];
+    let mut hh = vec![0i32; half_w * half_h];
+    for i in 0..half_h {
+        for j in 0..half_w {
+            let a = img[(i * 2 + 0) * stride + (j * 2 + 0) * 4 + ch] as i32;
+            let b = img[(i * 2 + 0) * stride + (j * 2 + 1) * 4 + ch] as i32;
+            let c = img[(i * 2 + 1) * stride + (j * 2 + 0) * 4 + ch] as i32;
+            let d = img[(i * 2 + 1) * stride + (j * 2 + 1) * 4 + ch] as i32;
+            let idx = i * half_w + j;
+            ll[idx] = (a + b + c + d) / 4;
+            lh[idx] = (b + d - a - c) / 4;
+            hl[idx] = (c + d - a - b) / 4;
+            hh[idx] = (b + c - a - d) / 4;
+        }
+    }
+    (ll, lh, hl, hh)
+}
+
+/// LSBs of one channel's LH band in `haar2d_forward` order, without the other bands.
+fn push_lh_lsbs(img: &[u8], w: u32, h: u32, ch: usize, out: &mut Vec<bool>) {
+    let half_w = (w / 2) as usize;
+    let stride = (w * 4) as usize;
+    for i in 0..(h / 2) as usize {
+        let top = &img[i * 2 * stride..];
+        let bottom = &img[(i * 2 + 1) * stride..];
+        out.extend((0..half_w).map(|j| {
+            let (a, b) = (top[j * 8 + ch] as i32, top[j * 8 + 4 + ch] as i32);
+            let (c, d) = (bottom[j * 8 + ch] as i32, bottom[j * 8 + 4 + ch] as i32);
+            ((b + d - a - c) / 4) & 1 != 0
+        }));
+    }
+}
+
+#[allow(clippy::identity_op, clippy::too_many_arguments)]
+fn haar2d_inverse(
+    out: &mut [u8],
+    w: u32,
+    h: u32,
+    ch: usize,
+    ll: &[i32],
+    lh: &[i32],
+    hl: &[i32],
+    hh: &[i32],
+) {
+    let half_w = (w / 2) as usize;
+    let half_h = (h / 2) as usize;
+    let stride = (w * 4) as usize;
+    for i in 0..half_h {
+        for j in 0..half_w {
+            let idx = i * half_w + j;
+            let ll_ij = ll[idx];
+            let lh_ij = lh[idx];
+            let hl_ij = hl[idx];
+            let hh_ij = hh[idx];
+            let a = (ll_ij - lh_ij - hl_ij - hh_ij).clamp(0, 255);
+            let b = (ll_ij + lh_ij - hl_ij + hh_ij).clamp(0, 255);
+            let c = (ll_ij - lh_ij + hl_ij + hh_ij).clamp(0, 255);
+            let d = (ll_ij + lh_ij + hl_ij - hh_ij).clamp(0, 255);
+            out[(i * 2 + 0) * stride + (j * 2 + 0) * 4 + ch] = a as u8;
+            out[(i * 2 + 0) * stride + (j * 2 + 1) * 4 + ch] = b as u8;
+            out[(i * 2 + 1) * stride + (j * 2 + 0) * 4 + ch] = c as u8;
+            out[(i * 2 + 1) * stride + (j * 2 + 1) * 4 + ch] = d as u8;
+        }
+    }
+}
+
+fn bits_to_bytes(b: &[bool]) -> Vec<u8> {
+    b.chunks(8)
+        .map(|c| {
+            let mut byte = 0u8;
+            for (i, &bit) in c.iter().enumerate() {
+                if bit {
+                    byte |= 1 << (7 - i);
+                }
+            }
+            byte
+        })
+        .collect()
+}
+
+/// Coefficient order within one channel of a tw x th tile: keyed, or raster order when None.
+/// The decoder needs the same key to line the bits back up.
+fn coefficient_order(key: Option<&StegoKey>, tw: u32, th: u32) -> Option<Vec<usize>> {
+    key.map(|k| k.permutation("dwt", (tw / 2) as usize * (th / 2) as usize))
+}
+
+/// Keyed order of one decoy-embed lane (0 decoy, 1 hidden) of a tw x th tile: the lane's
+/// coefficients are the even or odd indices of the channel's LH band.
+fn lane_order(key: &StegoKey, tw: u32, th: u32, lane: usize) -> Vec<usize> {
+    let half = (tw / 2) as usize * (th / 2) as usize / 2;
+    key.permutation("dwt-lane", half).into_iter().map(|i| 2 * i + lane).collect()
+}
+
+/// Orders a decode with `key` tries in a tw x th window: the whole band, then each lane.
+fn decode_orders(key: Option<&StegoKey>, tw: u32, th: u32) -> Vec<Option<Vec<usize>>> {
+    match key {
+        None => vec![None],
+        Some(k) => vec![coefficient_order(Some(k), tw, th), Some(lane_order(k, tw, th, 0)), Some(lane_order(k, tw, th, 1))],
+    }
+}
+
+/// Block order within one channel for a QIM embed in a tw x th tile: keyed, or raster order.
+fn qim_order(key: Option<&StegoKey>, tw: u32, th: u32) -> Option<Vec<usize>> {
+    key.map(|k| k.permutation("dwt-qim", (tw / 4) as usize * (th / 4) as usize))
+}
+
+/// One way of reading a frame out of a window: level-1 LH LSBs or level-2 QIM, in an order.
+enum Reader {
+    Lsb(Option<Vec<usize>>),
+    Qim(Option<Vec<usize>>),
+}
+
+impl Reader {
+    fn read(&self, raw: &[u8], tw: u32, th: u32, fec_only: bool) -> Option<Vec<u8>> {
+        match self {
+            Reader::Lsb(order) => decode_from_tile(raw, tw, th, order.as_deref(), fec_only).ok(),
+            Reader::Qim(order) => decode_qim_from_tile(raw, tw, th, order.as_deref()),
+        }
+    }
+}
+
+/// Readers a decode with `key` tries in a tw x th window: each of `decode_orders`, then QIM.
+fn decode_readers(key: Option<&StegoKey>, tw: u32, th: u32) -> Vec<Reader> {
+    let mut readers: Vec<Reader> = decode_orders(key, tw, th).into_iter().map(Reader::Lsb).collect();
+    readers.push(Reader::Qim(qim_order(key, tw, th)));
+    readers
+}
+
+/// The pilot sequence: SHA-256 of a fixed label, MSB first. Every full tile carries it in the LSBs
+/// of red's HL band (which the frame never uses), spread evenly over the tile, so `estimate_scale`
+/// can find the scale of a resized image.
+fn pilot() -> Vec<bool> {
+    let digest = Sha256::digest(b"stegstr-dwt-pilot");
+    (0..PILOT_BITS).map(|k| digest[k / 8] >> (7 - k % 8) & 1 != 0).collect()
+}
+
+/// HL coefficient (in `haar2d_forward` order) carrying pilot bit k in a band of `blocks`.
+fn pilot_index(k: usize, blocks: usize) -> usize {
+    k * blocks / PILOT_BITS
+}
+
+/// Source coordinate of pixel `x` when nearest-neighbour scaling `from` pixels to `to`.
+fn nearest(x: u32, from: u32, to: u32) -> u32 {
+    ((2 * x as u64 + 1) * from as u64 / (2 * to as u64)) as u32
+}
+
+/// `img` nearest-neighbour scaled to w x h.
+fn scale_nearest(img: &image::RgbaImage, w: u32, h: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(w, h, |x, y| *img.get_pixel(nearest(x, img.width(), w), nearest(y, img.height(), h)))
+}
+
+/// Pilot bits matched by the red HL band of the top-left crop x crop corner of `img` scaled to
+/// tile x tile (only the pixels the pilot sits on are sampled).
+fn pilot_matches(img: &image::RgbaImage, tile: u32, crop: u32, pilot: &[bool]) -> usize {
+    let half_w = (tile / 2) as usize;
+    let blocks = half_w * half_w;
+    let px = |y: usize, x: usize| img.get_pixel(nearest(x as u32, crop, tile), nearest(y as u32, crop, tile)).0[0] as i32;
+    pilot
+        .iter()
+        .enumerate()
+        .filter(|&(k, &bit)| {
+            let idx = pilot_index(k, blocks);
+            let (i, j) = (idx / half_w, idx % half_w);
+            let (a, b, c, d) = (px(2 * i, 2 * j), px(2 * i, 2 * j + 1), px(2 * i + 1, 2 * j), px(2 * i + 1, 2 * j + 1));
+            (((c + d - a - b) / 4) & 1 != 0) == bit
+        })
+        .count()
+}
+
+/// Pre-pass for resized images: the tile size and top-left crop edge (75% to 125% of each tile
+/// size) whose rescale to the tile best matches the pilot, if one matches at least
+/// PILOT_MIN_MATCH bits. Crops the size of a tile are skipped (the image as stored has already
+/// been tried). Scaling back restores the LSBs only after nearest-neighbour resizes (e.g.
+/// pixel-art upscales and some chat apps); smoothing filters rewrite them.
+fn estimate_scale(img: &image::RgbaImage) -> Option<(u32, u32)> {
+    let pilot = pilot();
+    let edge = img.width().min(img.height());
+    let candidates: Vec<(u32, u32)> = TILE_SIZES
+        .iter()
+        .flat_map(|&tile| ((tile * 3).div_ceil(4)..=(tile * 5 / 4).min(edge)).filter(move |&c| c != tile).map(move |c| (tile, c)))
+        .collect();
+    candidates
+        .par_iter()
+        .map(|&(tile, crop)| (pilot_matches(img, tile, crop, &pilot), tile, crop))
+        .filter(|&(matches, _, _)| matches >= PILOT_MIN_MATCH)
+        .max_by_key(|&(matches, _, _)| matches)
+        .map(|(_, tile, crop)| (tile, crop))
+}
+
+/// `img` scaled back by the scale `estimate_scale` found, if it found one.
+fn rescaled_by_pilot(img: &image::RgbaImage) -> Option<image::RgbaImage> {
+    let (tile, crop) = estimate_scale(img)?;
+    let scale = |side: u32| ((side as u64 * tile as u64 + crop as u64 / 2) / crop as u64) as u32;
+    Some(scale_nearest(img, scale(img.width()), scale(img.height())))
+}
+
+/// One frame to embed: over the whole band (in `key` order when set) or over one lane, as LSBs
+/// or (strength > 0) by QIM in the level-2 band.
+struct Layer<'a> {
+    frame: Vec<u8>,
+    key: Option<&'a StegoKey>,
+    lane: Option<usize>,
+    strength: u8,
+}
+
+impl Layer<'_> {
+    fn order(&self, tw: u32, th: u32) -> Option<Vec<usize>> {
+        match (self.key, self.lane) {
+            _ if self.strength > 0 => qim_order(self.key, tw, th),
+            (Some(k), Some(lane)) => Some(lane_order(k, tw, th, lane)),
+            (key, _) => coefficient_order(key, tw, th),
+        }
+    }
+
+    /// Bits this layer can hold in a tw x th tile over `channels` channels.
+    fn capacity(&self, tw: u32, th: u32, channels: usize) -> usize {
+        if self.strength > 0 {
+            return (tw / 4) as usize * (th / 4) as usize * channels;
+        }
+        let blocks = (tw / 2) as usize * (th / 2) as usize;
+        match self.lane {
+            Some(_) => blocks / 2 * channels,
+            None => blocks * channels,
+        }
+    }
+
+    fn fits(&self, tw: u32, th: u32, channels: usize) -> bool {
+        self.frame.len() * 8 <= self.capacity(tw, th, channels)
+    }
+}
+
+/// Embed each layer into a single tile (raw RGBA) using the first `channels` channels. Tile must
+/// be even dimensions. Bit k of a channel goes to LH coefficient `order[k]` of the layer's order
+/// (k itself without a key). With `pilot` the red HL band also gets the pilot sequence.
+fn embed_in_tile(raw: &[u8], tw: u32, th: u32, layers: &[Layer], channels: usize, pilot: bool) -> Result<Vec<u8>, StegoError> {
+    let blocks_per_channel = (tw / 2) as usize * (th / 2) as usize;
+    if let Some(layer) = layers.iter().find(|l| !l.fits(tw, th, channels)) {
+        return Err(StegoError::Capacity(format!(
+            "Tile too small: need {} bits, have {}",
+            layer.frame.len() * 8,
+            layer.capacity(tw, th, channels)
+        )));
+    }
+    if let Some(layer) = layers.iter().find(|l| l.strength > 0) {
+        return Ok(embed_qim_in_tile(raw, tw, th, layer, channels));
+    }
+    let orders: Vec<Option<Vec<usize>>> = layers.iter().map(|l| l.order(tw, th)).collect();
+    let mut out_raw = raw.to_vec();
+    for ch in 0..channels {
+        let (ll, lh, mut hl, hh) = haar2d_forward(&out_raw, tw, th, ch);
+        if pilot && ch == 0 {
+            for (k, bit) in self::pilot().into_iter().enumerate() {
+                let coef = &mut hl[pilot_index(k, blocks_per_channel)];
+                *coef = (*coef & !1) | bit as i32;
+            }
+        }
+        let mut lh_mod = lh;
+        for (layer, order) in layers.iter().zip(&orders) {
+            let bits_needed = layer.frame.len() * 8;
+            let bits_per_channel = order.as_ref().map_or(blocks_per_channel, |o| o.len());
+            for slot in 0..bits_per_channel {
+                let global_idx = ch * bits_per_channel + slot;
+                if global_idx >= bits_needed {
+                    break;
+                }
+                let byte_idx = global_idx / 8;
+                let bit_in_byte = 7 - (global_idx % 8);
+                let bit = (layer.frame[byte_idx] >> bit_in_byte) & 1;
+                let coef = &mut lh_mod[order.as_ref().map_or(slot, |o| o[slot])];
+                *coef = (*coef & !1) | (bit as i32);
+            }
+        }
+        haar2d_inverse(&mut out_raw, tw, th, ch, &ll, &lh_mod, &hl, &hh);
+    }
+    Ok(out_raw)
+}
+
+/// Level-2 Haar LH coefficient of channel `ch` in the 4x4 block at (x, y) of a `w` px wide RGBA
+/// image: the right half's mean minus the left half's, halved (the LH of the four level-1 LLs).
+fn lh2(raw: &[u8], w: u32, x: usize, y: usize, ch: usize) -> f64 {
+    let stride = w as usize * 4;
+    let sum: i32 = (0..4)
+        .flat_map(|dy| (0..4).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| {
+            let v = raw[(y + dy) * stride + (x + dx) * 4 + ch] as i32;
+            if dx < 2 { -v } else { v }
+        })
+        .sum();
+    sum as f64 / 16.0
+}
+
+/// QIM embed of `layer` into a single tile (raw RGBA): bit k of a channel moves the level-2 LH
+/// coefficient of block `order[k]` (k without a key) to the nearest multiple of the step (0) or
+/// odd half-step (1). Pixels are moved in opposite directions on the block's two halves; passes
+/// repeat while clamping at 0 or 255 leaves the coefficient short.
+/// Blur and sharpening nudge these coarse coefficients by less than a quarter step where they
+/// rewrite level-1 LSBs outright; the price is a quarter of the capacity and a larger change per
+/// pixel. QIM frames always carry FEC, and are not written in decoy lanes or given a pilot.
+fn embed_qim_in_tile(raw: &[u8], tw: u32, th: u32, layer: &Layer, channels: usize) -> Vec<u8> {
+    let step = layer.strength as f64 * QIM_STEP;
+    let blocks_w = (tw / 4) as usize;
+    let bits_per_channel = blocks_w * (th / 4) as usize;
+    let order = layer.order(tw, th);
+    let stride = tw as usize * 4;
+    let mut out_raw = raw.to_vec();
+    for ch in 0..channels {
+        for slot in 0..bits_per_channel {
+            let k = ch * bits_per_channel + slot;
+            if k >= layer.frame.len() * 8 {
+                break;
+            }
+            let offset = (layer.frame[k / 8] >> (7 - k % 8) & 1) as f64 * step / 2.0;
+            let block = order.as_ref().map_or(slot, |o| o[slot]);
+            let (x, y) = (block % blocks_w * 4, block / blocks_w * 4);
+            let coef = lh2(&out_raw, tw, x, y, ch);
+            let target = ((coef - offset) / step).round() * step + offset;
+            for _ in 0..4 {
+                let delta = (target - lh2(&out_raw, tw, x, y, ch)).round() as i32;
+                if delta == 0 {
+                    break;
+                }
+                for dy in 0..4 {
+                    for dx in 0..4 {
+                        let px = &mut out_raw[(y + dy) * stride + (x + dx) * 4 + ch];
+                        let moved = if dx < 2 { *px as i32 - delta } else { *px as i32 + delta };
+                        *px = moved.clamp(0, 255) as u8;
+                    }
+                }
+            }
+        }
+    }
+    out_raw
+}
+
+/// QIM decode of a single tile (raw RGBA) at each strength: the frame starts at the first block
+/// of `order` (a `qim_order`) and must carry FEC; up to QIM_MAGIC_ERRORS magic bits may be wrong.
+fn decode_qim_from_tile(raw: &[u8], tw: u32, th: u32, order: Option<&[usize]>) -> Option<Vec<u8>> {
+    let blocks_w = (tw / 4) as usize;
+    let blocks = blocks_w * (th / 4) as usize;
+    if blocks * 3 < (MAGIC_LEN + FEC_HEADER_LEN) * 8 {
+        return None;
+    }
+    let coefs: Vec<f64> = (0..3)
+        .flat_map(|ch| {
+            (0..blocks).map(move |slot| {
+                let block = order.map_or(slot, |o| o[slot]);
+                lh2(raw, tw, block % blocks_w * 4, block / blocks_w * 4, ch)
+            })
+        })
+        .collect();
+    let magic: Vec<bool> = MAGIC.iter().flat_map(|&b| (0..8).map(move |i| b >> (7 - i) & 1 != 0)).collect();
+    (1..=MAX_STRENGTH).find_map(|strength| {
+        let step = strength as f64 * QIM_STEP;
+        let bit = |c: f64| (c.rem_euclid(step) - step / 2.0).abs() < step / 4.0;
+        let wrong = magic.iter().zip(&coefs).filter(|&(&m, &c)| bit(c) != m).count();
+        if wrong > QIM_MAGIC_ERRORS {
+            return None;
+        }
+        let bits: Vec<bool> = coefs[MAGIC_LEN * 8..].iter().map(|&c| bit(c)).collect();
+        decode_fec_frame(&bits)
+    })
+}
+
+/// Decode payload from a single tile (raw RGBA). `order` is the tile's `coefficient_order`.
+/// `fec_only` skips legacy frames, which carry no check that the payload read is the one written.
+fn decode_from_tile(raw: &[u8], tw: u32, th: u32, order: Option<&[usize]>, fec_only: bool) -> Result<Vec<u8>, StegoError> {
+    if tw < 2 || th < 2 {
+        return Err(StegoError::NotStego("Tile too small".to_string()));
+    }
+    let half_w = (tw / 2) as usize;
+    let half_h = (th / 2) as usize;
+    let blocks_per_channel = half_w * half_h;
+    let total_bits = blocks_per_channel * 3;
+    if total_bits < 88 {
+        return Err(StegoError::NotStego("Tile too small".to_string()));
+    }
+    let mut bits = Vec::with_capacity(total_bits);
+    for ch in 0..3 {
+        push_lh_lsbs(raw, tw, th, ch, &mut bits);
+    }
+    if let Some(order) = order {
+        bits = bits
+            .chunks(blocks_per_channel)
+            .flat_map(|channel| order.iter().map(move |&i| channel[i]))
+            .collect();
+    }
+    // Rolling 56-bit window compared against the magic.
+    let magic = MAGIC.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+    let window_mask = (1u64 << (MAGIC_LEN * 8)) - 1;
+    let mut window = 0u64;
+    for (i, &bit) in bits.iter().enumerate() {
+        window = ((window << 1) | bit as u64) & window_mask;
+        let Some(start) = (i + 1).checked_sub(MAGIC_LEN * 8) else {
+            continue;
+        };
+        if start + 88 >= bits.len() {
+            break;
+        }
+        if window == magic {
+            if let Some(payload) = decode_fec_frame(&bits[start + MAGIC_LEN * 8..]) {
+                return Ok(payload);
+            }
+            if fec_only {
+                continue;
+            }
+            let len_slice = &bits[start + MAGIC_LEN * 8..start + (MAGIC_LEN + LENGTH_BYTES) * 8];
+            let len_bytes = bits_to_bytes(len_slice);
+            let payload_len = u32::from_be_bytes([
+                len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3],
+            ]) as usize;
+            let payload_end = start + (MAGIC_LEN + LENGTH_BYTES) * 8 + payload_len * 8;
+            if payload_end > bits.len() {
+                continue;
+            }
+            let payload_bits =
+                &bits[start + (MAGIC_LEN + LENGTH_BYTES) * 8..payload_end];
+            return Ok(bits_to_bytes(payload_bits));
+        }
+    }
+    Err(StegoError::NotStego("Magic not found in tile".to_string()))
+}
+
+/// FEC frame after the magic: header codeword, then the data codeword. None if `bits` do not
+/// hold one (legacy frame, or too damaged to correct).
+fn decode_fec_frame(bits: &[bool]) -> Option<Vec<u8>> {
+    let header_bits = bits.get(..FEC_HEADER_LEN * 8)?;
+    let header = crate::reed_solomon::decode(&bits_to_bytes(header_bits), HEADER_NSYM, &[]).ok()?;
+    if header[0] != FEC_VERSION || header[1] == 0 || header[1] > MAX_PARITY {
+        return None;
+    }
+    let parity = header[1] as usize;
+    let len = u32::from_be_bytes([header[2], header[3], header[4], header[5]]) as usize;
+    let codeword_bits = bits.get(FEC_HEADER_LEN * 8..(FEC_HEADER_LEN + crate::reed_solomon::encoded_len(len, parity)) * 8)?;
+    let data = crate::reed_solomon::decode(&bits_to_bytes(codeword_bits), parity, &[]).ok()?;
+    (data.len() == len).then_some(data)
+}
+
+/// Bytes embedded for `payload`: magic + length + data, or the FEC frame when `parity` > 0:
+/// magic + RS(8) of [version, parity, length] + RS(parity) of the data, so scattered bit errors
+/// are corrected. The version byte sits where the legacy length's high byte (always 0) was, so
+/// both frames decode.
+fn frame(payload: &[u8], parity: u8) -> Result<Vec<u8>, StegoError> {
+    if parity > MAX_PARITY {
+        return Err(StegoError::InvalidInput(format!("Parity must be at most {} bytes per block", MAX_PARITY)));
+    }
+    let len = u32::try_from(payload.len()).map_err(|_| StegoError::Capacity("Payload too large".to_string()))?;
+    let mut out = Vec::with_capacity(MAGIC_LEN + FEC_HEADER_LEN + payload.len());
+    out.write_all(MAGIC)?;
+    if parity == 0 {
+        out.write_all(&len.to_be_bytes())?;
+        out.write_all(payload)?;
+    } else {
+        let mut header = vec![FEC_VERSION, parity];
+        header.extend_from_slice(&len.to_be_bytes());
+        out.extend(crate::reed_solomon::encode(&header, HEADER_NSYM));
+        out.extend(crate::reed_solomon::encode(payload, parity as usize));
+    }
+    Ok(out)
+}
+
+/// Copy of the tw x th tile at (tx, ty) of `raw` (RGBA, `w` pixels wide).
+fn tile_at(raw: &[u8], w: u32, tx: u32, ty: u32, tw: u32, th: u32) -> Vec<u8> {
+    let mut tile = Vec::with_capacity((tw * th * 4) as usize);
+    for y in 0..th {
+        let row_start = ((ty + y) * w * 4 + tx * 4) as usize;
+        tile.extend_from_slice(&raw[row_start..row_start + (tw * 4) as usize]);
+    }
+    tile
+}
+
+/// Write a tile from `tile_at` back at (tx, ty).
+fn put_tile(out_img: &mut [u8], w: u32, tx: u32, ty: u32, tw: u32, tile: &[u8]) {
+    for (y, row) in tile.chunks((tw * 4) as usize).enumerate() {
+        let out_row_start = ((ty + y as u32) * w * 4 + tx * 4) as usize;
+        out_img[out_row_start..out_row_start + row.len()].copy_from_slice(row);
+    }
+}
+
+/// Tile origins for a masked encode: full `tile` x `tile` tiles on the decoder's sliding-window
+/// grid (half-tile steps) that avoid the mask, picked greedily so they don't overlap each other.
+fn plan_masked_tiles(w: u32, h: u32, mask: &Mask, tile: u32) -> Vec<(u32, u32)> {
+    let mut chosen: Vec<(u32, u32)> = Vec::new();
+    if w < tile || h < tile {
+        return chosen;
+    }
+    for oy in (0..=h - tile).step_by((tile / 2) as usize) {
+        for ox in (0..=w - tile).step_by((tile / 2) as usize) {
+            let overlaps = chosen
+                .iter()
+                .any(|&(cx, cy)| ox < cx + tile && cx < ox + tile && oy < cy + tile && cy < oy + tile);
+            if !overlaps && !mask.intersects(ox, oy, tile, tile) {
+                chosen.push((ox, oy));
+            }
+        }
+    }
+    chosen
+}
+
+/// Encode payload into image using DWT (Haar 2D). Embeds in LH coefficient LSB.
+/// Uses tile-based redundancy: embeds full payload in each 256x256 tile for crop survival.
+/// Returns PNG bytes. Image is auto-cropped to even dimensions if needed.
+/// `opts` sets the tile size, number of copies, channels and Reed-Solomon parity (parity / 2
+/// correctable byte errors per 255-byte block; 0 writes the legacy frame older builds read).
+#[cfg(feature = "fs")]
+pub fn encode(image_path: &std::path::Path, payload: &[u8], opts: &EncodeOptions) -> Result<Vec<u8>, StegoError> {
+    encode_with_mask(image_path, payload, None, opts)
+}
+
+/// `encode` that leaves masked pixels untouched: tiles are re-planned onto the half-tile decode
+/// grid around the mask, and the whole-image fallback is not available.
+#[cfg(feature = "fs")]
+pub fn encode_with_mask(image_path: &std::path::Path, payload: &[u8], mask: Option<&MaskSpec>, opts: &EncodeOptions) -> Result<Vec<u8>, StegoError> {
+    encode_file_bytes(&std::fs::read(image_path)?, payload, mask, opts, &Progress::default())
+}
+
+/// `encode_with_mask` reporting the share of tiles embedded to `progress`, and failing with
+/// StegoError::Cancelled once it is cancelled.
+#[cfg(feature = "fs")]
+pub fn encode_with_progress(
+    image_path: &std::path::Path,
+    payload: &[u8],
+    mask: Option<&MaskSpec>,
+    opts: &EncodeOptions,
+    progress: &Progress,
+) -> Result<Vec<u8>, StegoError> {
+    encode_file_bytes(&std::fs::read(image_path)?, payload, mask, opts, progress)
+}
+
+/// `encode` for an image held in memory (PNG, JPEG, ... file contents). Returns PNG bytes.
+pub fn encode_bytes(image: &[u8], payload: &[u8]) -> Result<Vec<u8>, StegoError> {
+    encode_bytes_with_options(image, payload, &EncodeOptions::default())
+}
+
+/// `encode_bytes` with explicit options.
+pub fn encode_bytes_with_options(image: &[u8], payload: &[u8], opts: &EncodeOptions) -> Result<Vec<u8>, StegoError> {
+    encode_file_bytes(image, payload, None, opts, &Progress::default())
+}
+
+/// `encode_image` of image file contents, or `embed_rgba` into each frame of an animated PNG
+/// (`opts.spread` spreads the payload over the frames). Animated GIFs are left to the dot codec:
+/// their palette quantisation rewrites LSBs.
+fn encode_file_bytes(bytes: &[u8], payload: &[u8], mask: Option<&MaskSpec>, opts: &EncodeOptions, progress: &Progress) -> Result<Vec<u8>, StegoError> {
+    let Some(animation) = crate::stego_anim::Animation::read(bytes)? else {
+        if opts.spread > 0 {
+            return Err(StegoError::InvalidInput("Spread applies to animated covers only".to_string()));
+        }
+        let (cover, metadata) = load_cover_bytes(bytes)?;
+        return encode_image(&cover, &metadata, payload, mask, opts, progress);
+    };
+    if animation.container == crate::stego_anim::Container::Gif {
+        return Err(StegoError::InvalidInput("GIF frames are palette-quantised, which DWT bits do not survive; use the dot codec".to_string()));
+    }
+    let frames = animation.frames.len();
+    let done = std::sync::atomic::AtomicUsize::new(0);
+    animation.embed(payload, opts.spread, |frame, part| {
+        let index = done.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        embed_rgba(frame, part, mask, opts, &progress.stage(index, frames))
+    })
+}
+
+/// Decoy embed: `decoy` readable with `decoy_key` and `payload` with `key`. Each channel's LH
+/// coefficients are split into two lanes (even and odd indices), the decoy written in keyed order
+/// over the first and the payload over the second; a keyed decode tries the whole band, then each
+/// lane, so either key reads its own payload. The lane split is public: a decoy-key holder can
+/// tell a lane embed from a plain keyed one, not whether the second lane holds anything.
+/// `opts.key` must be None. Each payload gets half the capacity of a plain embed. Encrypt both
+/// first: the keys hide positions, not contents.
+#[cfg(feature = "fs")]
+pub fn encode_decoy(
+    image_path: &std::path::Path,
+    decoy: &[u8],
+    decoy_key: &StegoKey,
+    payload: &[u8],
+    key: &StegoKey,
+    opts: &EncodeOptions,
+) -> Result<Vec<u8>, StegoError> {
+    encode_decoy_rgba(&load_image_with_orientation(image_path)?, decoy, decoy_key, payload, key, opts)
+}
+
+#[cfg(feature = "fs")]
+fn encode_decoy_rgba(img_rgba: &image::RgbaImage, decoy: &[u8], decoy_key: &StegoKey, payload: &[u8], key: &StegoKey, opts: &EncodeOptions) -> Result<Vec<u8>, StegoError> {
+    if opts.key.is_some() {
+        return Err(StegoError::InvalidInput("A decoy embed takes one key per payload, not a stego key".to_string()));
+    }
+    if decoy_key == key {
+        return Err(StegoError::InvalidInput("The decoy and hidden payloads need different keys".to_string()));
+    }
+    if opts.interleave > 0 {
+        return Err(StegoError::InvalidInput("A decoy embed repeats both payloads in every tile; it cannot be interleaved".to_string()));
+    }
+    if opts.strength > 0 {
+        return Err(StegoError::InvalidInput("A decoy embed writes LSB lanes; it takes no strength".to_string()));
+    }
+    let opts = opts.validated()?;
+    let layers = [
+        Layer { frame: frame(&payload_header::compress(decoy), opts.parity)?, key: Some(decoy_key), lane: Some(0), strength: 0 },
+        Layer { frame: frame(&payload_header::compress(payload), opts.parity)?, key: Some(key), lane: Some(1), strength: 0 },
+    ];
+    let embedded = encode_layers(img_rgba, &layers, None, &opts, &Progress::default())?;
+    opts.format.encode(embedded.as_raw(), embedded.width(), embedded.height(), ExtendedColorType::Rgba8)
+}
+
+/// `encode_rgba` of `cover` in its own colour type (see `encode_like_cover`), carrying `metadata`
+/// unless `opts.strip_metadata`.
+fn encode_image(
+    cover: &DynamicImage,
+    metadata: &Metadata,
+    payload: &[u8],
+    mask: Option<&MaskSpec>,
+    opts: &EncodeOptions,
+    progress: &Progress,
+) -> Result<Vec<u8>, StegoError> {
+    let opts = EncodeOptions { channels: cover_channels(cover, opts.channels), ..*opts };
+    let embedded = embed_rgba(&cover.to_rgba8(), payload, mask, &opts, progress)?;
+    let stripped = Metadata::default();
+    encode_like_cover(cover, &embedded, opts.format, if opts.strip_metadata { &stripped } else { metadata })
+}
+
+#[cfg(test)]
+fn encode_rgba(img_rgba: &image::RgbaImage, payload: &[u8], mask: Option<&MaskSpec>, opts: &EncodeOptions) -> Result<Vec<u8>, StegoError> {
+    let embedded = embed_rgba(img_rgba, payload, mask, opts, &Progress::default())?;
+    opts.format.encode(embedded.as_raw(), embedded.width(), embedded.height(), ExtendedColorType::Rgba8)
+}
+
+/// The embedded (even-cropped) RGBA image. Data is zstd-compressed when that makes it smaller
+/// (payload_header::compress).
+fn embed_rgba(img_rgba: &image::RgbaImage, payload: &[u8], mask: Option<&MaskSpec>, opts: &EncodeOptions, progress: &Progress) -> Result<image::RgbaImage, StegoError> {
+    let opts = opts.validated()?;
+    if opts.interleave > 0 {
+        return encode_interleaved(img_rgba, payload, mask, &opts, progress);
+    }
+    let layer = Layer { frame: frame(&payload_header::compress(payload), opts.parity)?, key: opts.key.as_ref(), lane: None, strength: opts.strength };
+    encode_layers(img_rgba, &[layer], mask, &opts, progress)
+}
+
+/// Embed `layers` into every planned tile (or once over the whole image); `opts` is validated.
+/// Returns the embedded (even-cropped) image. Tiles embedded are reported to `progress`.
+fn encode_layers(
+    img_rgba: &image::RgbaImage,
+    layers: &[Layer],
+    mask: Option<&MaskSpec>,
+    opts: &EncodeOptions,
+    progress: &Progress,
+) -> Result<image::RgbaImage, StegoError> {
+    let (tile, channels) = (opts.tile_size, opts.channels as usize);
+    let copies = match opts.redundancy {
+        0 => usize::MAX,
+        n => n as usize,
+    };
+    let img_rgba = ensure_even_dimensions(img_rgba);
+    let (w, h) = (img_rgba.width(), img_rgba.height());
+    if w < 2 || h < 2 {
+        return Err(StegoError::Capacity("Image must be at least 2x2 after cropping to even dimensions".to_string()));
+    }
+    let raw = img_rgba.as_raw();
+    let mask = match mask {
+        Some(spec) if !spec.is_empty() => Some(spec.build(w, h)?),
+        _ => None,
+    };
+
+    // Tiles to fill as (x, y, width, height), all with room for the frame.
+    let mut plan: Vec<(u32, u32, u32, u32)> = Vec::new();
+    if let Some(mask) = &mask {
+        if let Some(layer) = layers.iter().find(|l| !l.fits(tile, tile, channels)) {
+            return Err(StegoError::Capacity(format!(
+                "Payload too large for a masked encode: need {} bits, a tile holds {}",
+                layer.frame.len() * 8,
+                layer.capacity(tile, tile, channels)
+            )));
+        }
+        plan.extend(plan_masked_tiles(w, h, mask, tile).into_iter().take(copies).map(|(tx, ty)| (tx, ty, tile, tile)));
+        if plan.is_empty() {
+            return Err(StegoError::Capacity(format!("Mask leaves no unmasked {}x{} tile for the payload", tile, tile)));
+        }
+    } else {
+        for ty in (0..h).step_by(tile as usize) {
+            for tx in (0..w).step_by(tile as usize) {
+                let tw = tile.min(w - tx);
+                let th = tile.min(h - ty);
+                let tw_even = if tw % 2 == 0 { tw } else { tw - 1 };
+                let th_even = if th % 2 == 0 { th } else { th - 1 };
+                if tw_even < 2 || th_even < 2 {
+                    continue;
+                }
+                if layers.iter().all(|l| l.fits(tw_even, th_even, channels)) && plan.len() < copies {
+                    plan.push((tx, ty, tw_even, th_even));
+                }
+            }
+        }
+    }
+
+    let done = std::sync::atomic::AtomicUsize::new(0);
+    let embedded: Vec<(u32, u32, u32, Vec<u8>)> = plan
+        .par_iter()
+        .filter_map(|&(tx, ty, tw, th)| {
+            if progress.is_cancelled() {
+                return None;
+            }
+            let full = tw == tile && th == tile;
+            let modified = embed_in_tile(&tile_at(raw, w, tx, ty, tw, th), tw, th, layers, channels, full).ok();
+            progress.step(done.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1, plan.len());
+            Some((tx, ty, tw, modified?))
+        })
+        .collect();
+    progress.check()?;
+    let mut out_img = raw.to_vec();
+    for (tx, ty, tw, modified) in &embedded {
+        put_tile(&mut out_img, w, *tx, *ty, *tw, modified);
+    }
+
+    if embedded.is_empty() {
+        if let Some(layer) = layers.iter().find(|l| !l.fits(w, h, channels)) {
+            return Err(StegoError::Capacity(format!(
+                "Payload too large: need {} bits, image has {} (no tile had capacity)",
+                layer.frame.len() * 8,
+                layer.capacity(w, h, channels)
+            )));
+        }
+        out_img = embed_in_tile(raw, w, h, layers, channels, false)?;
+        progress.step(1, 1);
+    }
+
+    Ok(image::RgbaImage::from_raw(w, h, out_img).expect("embedding keeps the buffer size"))
+}
+
+/// Interleaved embed: one fountain-coded part of `payload` in each full tile (on the half-tile
+/// decode grid around a mask), any `opts.interleave` of which rebuild it; `opts` is validated.
+/// A payload several tiles large so still survives losing the other tiles to a crop. The decoder
+/// collects every window's part when the first to decode holds one, and returns that part alone
+/// when they do not suffice, as for a part of a multi-image split.
+fn encode_interleaved(
+    img_rgba: &image::RgbaImage,
+    payload: &[u8],
+    mask: Option<&MaskSpec>,
+    opts: &EncodeOptions,
+    progress: &Progress,
+) -> Result<image::RgbaImage, StegoError> {
+    let (tile, channels) = (opts.tile_size, opts.channels as usize);
+    let img_rgba = ensure_even_dimensions(img_rgba);
+    let (w, h) = (img_rgba.width(), img_rgba.height());
+    let mut plan: Vec<(u32, u32)> = match mask {
+        Some(spec) if !spec.is_empty() => plan_masked_tiles(w, h, &spec.build(w, h)?, tile),
+        _ if w < tile || h < tile => Vec::new(),
+        _ => (0..=h - tile)
+            .step_by(tile as usize)
+            .flat_map(|ty| (0..=w - tile).step_by(tile as usize).map(move |tx| (tx, ty)))
+            .collect(),
+    };
+    if opts.redundancy > 0 {
+        plan.truncate(opts.redundancy as usize);
+    }
+    let needed = opts.interleave as usize;
+    if plan.len() < needed.max(2) {
+        return Err(StegoError::Capacity(format!(
+            "Interleaving so that any {} tiles rebuild the payload needs at least {} full {}x{} tiles, the image has {}",
+            needed,
+            needed.max(2),
+            tile,
+            tile,
+            plan.len()
+        )));
+    }
+    let parts = crate::stego_fountain::encode(&payload_header::compress(payload), plan.len(), needed)?;
+    let layers = parts
+        .iter()
+        .map(|part| Ok(Layer { frame: frame(part, opts.parity)?, key: opts.key.as_ref(), lane: None, strength: opts.strength }))
+        .collect::<Result<Vec<_>, StegoError>>()?;
+    if let Some(layer) = layers.iter().find(|l| !l.fits(tile, tile, channels)) {
+        return Err(StegoError::Capacity(format!(
+            "Payload too large to interleave over any {} tiles: each tile needs {} bits, holds {}",
+            needed,
+            layer.frame.len() * 8,
+            layer.capacity(tile, tile, channels)
+        )));
+    }
+    let raw = img_rgba.as_raw();
+    let done = std::sync::atomic::AtomicUsize::new(0);
+    let embedded = plan
+        .par_iter()
+        .zip(&layers)
+        .map(|(&(tx, ty), layer)| {
+            progress.check()?;
+            let modified = embed_in_tile(&tile_at(raw, w, tx, ty, tile, tile), tile, tile, std::slice::from_ref(layer), channels, true)?;
+            progress.step(done.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1, plan.len());
+            Ok((tx, ty, modified))
+        })
+        .collect::<Result<Vec<_>, StegoError>>()?;
+    let mut out_img = raw.to_vec();
+    for (tx, ty, modified) in &embedded {
+        put_tile(&mut out_img, w, *tx, *ty, tile, modified);
+    }
+    Ok(image::RgbaImage::from_raw(w, h, out_img).expect("embedding keeps the buffer size"))
+}
+
+/// DWT capacity of a cover, split by how `encode` would lay the payload out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DwtCapacity {
+    /// Largest payload `encode` accepts; past `tile_bytes` it is embedded once over the whole image.
+    pub max_bytes: usize,
+    /// Largest payload repeated in every tile, so it survives cropping (0 below 256x256).
+    pub tile_bytes: usize,
+    /// Full 256x256 tiles: copies a payload of up to `tile_bytes` gets.
+    pub tiles: usize,
+}
+
+/// Payload bytes that fit a `w` x `h` region (one bit per LH coefficient of each channel).
+#[cfg(feature = "fs")]
+fn region_payload_bytes(w: u32, h: u32, channels: u8) -> usize {
+    let bits = (w / 2) as usize * (h / 2) as usize * channels as usize;
+    (bits / 8).saturating_sub(MAGIC_LEN + LENGTH_BYTES)
+}
+
+#[cfg(feature = "fs")]
+pub fn capacity(image_path: &std::path::Path) -> Result<DwtCapacity, StegoError> {
+    let (cover, _) = load_cover(image_path)?;
+    let channels = cover_channels(&cover, 3);
+    let (w, h) = (cover.width() & !1, cover.height() & !1);
+    let tiles = (w / TILE_SIZE) as usize * (h / TILE_SIZE) as usize;
+    Ok(DwtCapacity {
+        max_bytes: region_payload_bytes(w, h, channels),
+        tile_bytes: if tiles > 0 { region_payload_bytes(TILE_SIZE, TILE_SIZE, channels) } else { 0 },
+        tiles,
+    })
+}
+
+/// Largest payload `encode` can embed: the whole-image capacity (payloads that fit one
+/// 256x256 tile are additionally repeated per tile).
+/// Counts stored bytes; compare `payload_header::stored_len` of a payload against it.
+#[cfg(feature = "fs")]
+pub fn max_payload_bytes(image_path: &std::path::Path) -> Result<usize, StegoError> {
+    capacity(image_path).map(|c| c.max_bytes)
+}
+
+/// Luma blocks whose standard deviation is below this count as flat.
+#[cfg(feature = "fs")]
+const FLAT_BLOCK_STDDEV: f64 = 2.0;
+/// Mean neighbour difference at which texture stops adding to the score.
+#[cfg(feature = "fs")]
+const FULL_TEXTURE: f64 = 12.0;
+
+/// How well a cover hides an embed. Textured, high-entropy images mask the coefficient changes;
+/// flat areas (screenshots, logos) show them as artifacts and lose them to recompression.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct CoverScore {
+    /// Shannon entropy of the luma histogram, 0 to 8 bits.
+    pub entropy: f64,
+    /// Mean absolute luma difference between horizontal and vertical neighbours.
+    pub texture: f64,
+    /// Fraction of 8x8 luma blocks that are (nearly) flat.
+    pub flat_fraction: f64,
+    /// Largest DWT payload (`max_payload_bytes`).
+    pub capacity_bytes: usize,
+    /// 0 (unsuitable) to 100: entropy 30%, texture 30%, non-flat blocks 40%.
+    pub score: f64,
+}
+
+/// Suitability of the cover at `image_path` (see CoverScore).
+#[cfg(feature = "fs")]
+pub fn cover_score(image_path: &std::path::Path) -> Result<CoverScore, StegoError> {
+    let (cover, _) = load_cover(image_path)?;
+    let img = ensure_even_dimensions(&cover.to_rgba8());
+    Ok(cover_score_rgba(&img, region_payload_bytes(img.width(), img.height(), cover_channels(&cover, 3))))
+}
+
+#[cfg(feature = "fs")]
+fn cover_score_rgba(img: &image::RgbaImage, capacity_bytes: usize) -> CoverScore {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let luma: Vec<f64> = img
+        .pixels()
+        .map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64)
+        .collect();
+    let mut hist = [0usize; 256];
+    for &v in &luma {
+        hist[(v.round() as usize).min(255)] += 1;
+    }
+    let n = luma.len().max(1) as f64;
+    let entropy = -hist.iter().filter(|&&c| c > 0).map(|&c| c as f64 / n * (c as f64 / n).log2()).sum::<f64>();
+    let (mut diff, mut pairs) = (0.0, 0usize);
+    for y in 0..h {
+        for x in 0..w {
+            let v = luma[y * w + x];
+            if x + 1 < w {
+                diff += (luma[y * w + x + 1] - v).abs();
+                pairs += 1;
+            }
+            if y + 1 < h {
+                diff += (luma[(y + 1) * w + x] - v).abs();
+                pairs += 1;
+            }
+        }
+    }
+    let texture = if pairs > 0 { diff / pairs as f64 } else { 0.0 };
+    let (mut flat, mut blocks) = (0usize, 0usize);
+    for by in 0..h / 8 {
+        for bx in 0..w / 8 {
+            let values = (0..64).map(|i| luma[(by * 8 + i / 8) * w + bx * 8 + i % 8]);
+            let (sum, sq) = values.fold((0.0, 0.0), |(s, q), v| (s + v, q + v * v));
+            let mean = sum / 64.0;
+            if (sq / 64.0 - mean * mean).max(0.0).sqrt() < FLAT_BLOCK_STDDEV {
+                flat += 1;
+            }
+            blocks += 1;
+        }
+    }
+    let flat_fraction = if blocks > 0 { flat as f64 / blocks as f64 } else { 1.0 };
+    let score = 100.0 * (0.3 * entropy / 8.0 + 0.3 * (texture / FULL_TEXTURE).min(1.0) + 0.4 * (1.0 - flat_fraction));
+    CoverScore {
+        entropy,
+        texture,
+        flat_fraction,
+        capacity_bytes,
+        score,
+    }
+}
+
+/// Rectangle in pixel coordinates of the (orientation-corrected) image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Sliding windows of each tile size (half-tile steps) first, then full-image decode.
+/// Windows go first: on multi-tile images the full-image bit stream starts with tile (0,0)'s magic
+/// and then runs into the neighbouring tile, yielding garbage. Whole-image embeds only happen when
+/// the payload exceeds a tile, so their length field makes every window reject them.
+#[cfg(any(test, feature = "fs"))]
+fn decode_rgba(img_rgba: &image::RgbaImage, key: Option<&StegoKey>) -> Result<Vec<u8>, StegoError> {
+    decode_rgba_frames(img_rgba, key, false, &Progress::default())
+}
+
+/// `decode_rgba`; `fec_only` as in `decode_from_tile`. Window passes (a tile size read one way)
+/// are reported to `progress`.
+fn decode_rgba_frames(img_rgba: &image::RgbaImage, key: Option<&StegoKey>, fec_only: bool, progress: &Progress) -> Result<Vec<u8>, StegoError> {
+    let img_rgba = ensure_even_dimensions(img_rgba);
+    let (w, h) = (img_rgba.width(), img_rgba.height());
+    if w < 2 || h < 2 {
+        return Err(StegoError::NotStego("Image too small or dimensions not even".to_string()));
+    }
+    let raw = img_rgba.as_raw();
+    let tiles: Vec<u32> = TILE_SIZES.iter().copied().filter(|&t| w >= t && h >= t).collect();
+    // A cover smaller than the tile on one side is embedded in edge tiles from the top-left
+    // corner; the first of them holds a whole copy but is not the whole image.
+    let corners: Vec<(u32, u32)> = TILE_SIZES
+        .iter()
+        .filter(|&&t| (w >= t) != (h >= t))
+        .map(|&t| (t.min(w), t.min(h)))
+        .collect();
+    let passes = (tiles.len() + corners.len() + 1) * decode_readers(key, 2, 2).len();
+    let mut pass = 0;
+
+    for tile in tiles {
+        let step = (tile / 2) as usize;
+        let origins: Vec<(u32, u32)> = (0..=h - tile)
+            .step_by(step)
+            .flat_map(|oy| (0..=w - tile).step_by(step).map(move |ox| (ox, oy)))
+            .collect();
+        for reader in decode_readers(key, tile, tile) {
+            let window = |&(ox, oy): &(u32, u32)| match progress.is_cancelled() {
+                true => None,
+                false => reader.read(&tile_at(raw, w, ox, oy, tile, tile), tile, tile, fec_only),
+            };
+            // First window in reading order that decodes; later windows stop once one is found.
+            let found = origins.par_iter().find_map_first(window);
+            progress.check()?;
+            pass += 1;
+            progress.step(pass, passes);
+            if let Some(payload) = found {
+                if crate::stego_fountain::is_fountain_part(&payload) {
+                    // Possibly one stripe of an interleaved embed: rebuild from every window's part.
+                    let mut parts: Vec<Vec<u8>> = origins.par_iter().filter_map(window).collect();
+                    parts.sort();
+                    parts.dedup();
+                    if let Ok(rebuilt) = crate::stego_fountain::decode(&parts) {
+                        return payload_header::decompress(&rebuilt);
+                    }
+                }
+                return payload_header::decompress(&payload);
+            }
+        }
+    }
+
+    for (tw, th) in corners {
+        let corner = tile_at(raw, w, 0, 0, tw, th);
+        for reader in decode_readers(key, tw, th) {
+            progress.check()?;
+            if let Some(payload) = reader.read(&corner, tw, th, fec_only) {
+                return payload_header::decompress(&payload);
+            }
+            pass += 1;
+            progress.step(pass, passes);
+        }
+    }
+
+    for reader in decode_readers(key, w, h) {
+        progress.check()?;
+        if let Some(payload) = reader.read(raw, w, h, fec_only) {
+            return payload_header::decompress(&payload);
+        }
+        pass += 1;
+        progress.step(pass, passes);
+    }
+
+    Err(StegoError::NotStego("Not a Stegstr image (magic not found)".to_string()))
+}
+
+/// Transforms `decode` undoes, besides none: the other 7 of the 8 rotations and mirrorings.
+const ORIENTATIONS: [Orientation; 7] = [
+    Orientation::Rotate90,
+    Orientation::Rotate180,
+    Orientation::Rotate270,
+    Orientation::FlipHorizontal,
+    Orientation::FlipVertical,
+    Orientation::Rotate90FlipH,
+    Orientation::Rotate270FlipH,
+];
+
+/// Name of the transform an image underwent, as reported by `decode_with_orientation`.
+pub fn orientation_name(o: Orientation) -> &'static str {
+    match o {
+        Orientation::NoTransforms => "none",
+        Orientation::Rotate90 => "rotate90",
+        Orientation::Rotate180 => "rotate180",
+        Orientation::Rotate270 => "rotate270",
+        Orientation::FlipHorizontal => "flip_horizontal",
+        Orientation::FlipVertical => "flip_vertical",
+        Orientation::Rotate90FlipH => "rotate90_flip_horizontal",
+        Orientation::Rotate270FlipH => "rotate270_flip_horizontal",
+    }
+}
+
+/// `decode_rgba` of the image as stored, then of each of ORIENTATIONS applied to it. Returns the
+/// transform the image had undergone (the inverse of the one that made it decode): the rotations
+/// by 90 and 270 degrees undo each other, every other one is its own inverse.
+/// Turned images only accept FEC frames: LH coefficient LSBs survive a vertical flip, so a legacy
+/// frame can show its magic under a wrong transform (e.g. rotate180 of a mirrored image) and then
+/// read garbage with nothing to catch it.
+/// Last, an image resized after embedding is scaled back by the pilot pre-pass (`estimate_scale`)
+/// and decoded as stored; the scale is not reported.
+/// `progress` gets one equal stage per attempt; a cancel ends the search.
+fn decode_rgba_any_orientation(img_rgba: &image::RgbaImage, key: Option<&StegoKey>, progress: &Progress) -> Result<(Vec<u8>, Orientation), StegoError> {
+    let stages = ORIENTATIONS.len() + 2;
+    let err = match decode_rgba_frames(img_rgba, key, false, &progress.stage(0, stages)) {
+        Ok(payload) => return Ok((payload, Orientation::NoTransforms)),
+        Err(e @ StegoError::Cancelled(_)) => return Err(e),
+        Err(e) => e,
+    };
+    for (i, o) in ORIENTATIONS.into_iter().enumerate() {
+        let mut turned = image::DynamicImage::ImageRgba8(img_rgba.clone());
+        turned.apply_orientation(o);
+        let decoded = decode_rgba_frames(turned.as_rgba8().expect("rgba stays rgba"), key, true, &progress.stage(i + 1, stages));
+        progress.check()?;
+        if let Ok(payload) = decoded {
+            let undergone = match o {
+                Orientation::Rotate90 => Orientation::Rotate270,
+                Orientation::Rotate270 => Orientation::Rotate90,
+                other => other,
+            };
+            return Ok((payload, undergone));
+        }
+    }
+    if let Some(rescaled) = rescaled_by_pilot(img_rgba) {
+        let decoded = decode_rgba_frames(&rescaled, key, false, &progress.stage(stages - 1, stages));
+        progress.check()?;
+        if let Ok(payload) = decoded {
+            return Ok((payload, Orientation::NoTransforms));
+        }
+    }
+    progress.step(1, 1);
+    Err(err)
+}
+
+/// Decode payload from DWT-embedded image.
+/// Tries a sliding 256x256 window (crop survival), then full-image decode for whole-image embeds,
+/// on the image as stored and then rotated/mirrored back.
+#[cfg(feature = "fs")]
+pub fn decode(image_path: &std::path::Path) -> Result<Vec<u8>, StegoError> {
+    decode_with_key(image_path, None)
+}
+
+/// `decode` of an image embedded with `EncodeOptions::key`; None reads unkeyed images.
+#[cfg(feature = "fs")]
+pub fn decode_with_key(image_path: &std::path::Path, key: Option<&StegoKey>) -> Result<Vec<u8>, StegoError> {
+    decode_with_orientation(image_path, key).map(|(payload, _)| payload)
+}
+
+/// `decode_with_key`, also returning the rotation/mirroring the image had undergone
+/// (`Orientation::NoTransforms` when it decoded as stored; see `orientation_name`).
+#[cfg(feature = "fs")]
+pub fn decode_with_orientation(image_path: &std::path::Path, key: Option<&StegoKey>) -> Result<(Vec<u8>, Orientation), StegoError> {
+    decode_file_bytes(&std::fs::read(image_path)?, key, &Progress::default())
+}
+
+/// `decode_with_orientation` reporting the share of windows searched to `progress`, and failing
+/// with StegoError::Cancelled once it is cancelled.
+#[cfg(feature = "fs")]
+pub fn decode_with_progress(image_path: &std::path::Path, key: Option<&StegoKey>, progress: &Progress) -> Result<(Vec<u8>, Orientation), StegoError> {
+    decode_file_bytes(&std::fs::read(image_path)?, key, progress)
+}
+
+/// `decode` for image file contents held in memory.
+pub fn decode_bytes(image: &[u8]) -> Result<Vec<u8>, StegoError> {
+    decode_bytes_with_key(image, None)
+}
+
+/// `decode_with_key` for image file contents held in memory.
+pub fn decode_bytes_with_key(image: &[u8], key: Option<&StegoKey>) -> Result<Vec<u8>, StegoError> {
+    decode_file_bytes(image, key, &Progress::default()).map(|(payload, _)| payload)
+}
+
+/// `decode_rgba_any_orientation` of image file contents; the frames of an animated PNG are read
+/// as stored.
+fn decode_file_bytes(bytes: &[u8], key: Option<&StegoKey>, progress: &Progress) -> Result<(Vec<u8>, Orientation), StegoError> {
+    match crate::stego_anim::Animation::read(bytes)? {
+        Some(animation) => Ok((animation.extract(|frame| decode_rgba_frames(frame, key, false, progress))?, Orientation::NoTransforms)),
+        None => decode_rgba_any_orientation(&load_image_bytes_with_orientation(bytes)?, key, progress),
+    }
+}
+
+/// Decode payload searching only inside `region` (clamped to the image bounds).
+/// Much faster than `decode` on large images when the caller knows where the tile is.
+#[cfg(feature = "fs")]
+pub fn decode_region(image_path: &std::path::Path, region: Region) -> Result<Vec<u8>, StegoError> {
+    let img_rgba = load_image_with_orientation(image_path)?;
+    let (w, h) = (img_rgba.width(), img_rgba.height());
+    if region.x >= w || region.y >= h {
+        return Err(StegoError::InvalidInput(format!("Region origin ({}, {}) is outside the {}x{} image", region.x, region.y, w, h)));
+    }
+    let rw = region.width.min(w - region.x);
+    let rh = region.height.min(h - region.y);
+    if rw < 2 || rh < 2 {
+        return Err(StegoError::InvalidInput("Region too small".to_string()));
+    }
+    let cropped = image::imageops::crop_imm(&img_rgba, region.x, region.y, rw, rh).to_image();
+    decode_rgba(&cropped, None)
+}
+
+/// A DWT frame header found by `detect`.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Detection {
+    /// Window the header starts in (the whole image for a whole-image embed).
+    pub region: Region,
+    /// Stored payload length the header records.
+    pub payload_bytes: usize,
+    /// True when the header is the Reed-Solomon checked one of an FEC frame.
+    pub fec: bool,
+    /// 0-1: the share of magic bits read intact, times 0.9 for an unchecked legacy length.
+    pub confidence: f64,
+}
+
+/// Confidence below 1 given to a legacy frame: its length has no check bits.
+const LEGACY_CONFIDENCE: f64 = 0.9;
+
+/// Red LH LSBs of the first `n` 2x2 blocks of the `tw` pixel wide window at (ox, oy), in raster
+/// order, where an unkeyed frame starts.
+fn leading_lh_bits(img: &image::RgbaImage, ox: u32, oy: u32, tw: u32, n: usize) -> Vec<bool> {
+    let half_w = (tw / 2) as usize;
+    (0..n)
+        .map(|k| {
+            let (x, y) = (ox + 2 * (k % half_w) as u32, oy + 2 * (k / half_w) as u32);
+            let p = |dx: u32, dy: u32| img.get_pixel(x + dx, y + dy)[0] as i32;
+            ((p(1, 0) + p(1, 1) - p(0, 0) - p(0, 1)) / 4) & 1 != 0
+        })
+        .collect()
+}
+
+/// `detect_rgba` for one window: magic and header only, checked against the window's capacity.
+fn detect_window(img: &image::RgbaImage, region: Region) -> Option<Detection> {
+    let blocks = (region.width / 2) as usize * (region.height / 2) as usize;
+    let bits = leading_lh_bits(img, region.x, region.y, region.width, ((MAGIC_LEN + FEC_HEADER_LEN) * 8).min(blocks));
+    if bits.len() < (MAGIC_LEN + FEC_HEADER_LEN) * 8 {
+        return None;
+    }
+    let errors: usize = bits_to_bytes(&bits[..MAGIC_LEN * 8]).iter().zip(MAGIC).map(|(a, b)| (a ^ b).count_ones() as usize).sum();
+    if errors > QIM_MAGIC_ERRORS {
+        return None;
+    }
+    let capacity = blocks * 3 / 8;
+    let header = &bits[MAGIC_LEN * 8..];
+    if let Some(h) = crate::reed_solomon::decode(&bits_to_bytes(header), HEADER_NSYM, &[]).ok().filter(|h| h[0] == FEC_VERSION && h[1] > 0 && h[1] <= MAX_PARITY) {
+        let len = u32::from_be_bytes([h[2], h[3], h[4], h[5]]) as usize;
+        let fits = MAGIC_LEN + FEC_HEADER_LEN + crate::reed_solomon::encoded_len(len, h[1] as usize) <= capacity;
+        let confidence = 1.0 - errors as f64 / (MAGIC_LEN * 8) as f64;
+        return fits.then_some(Detection { region, payload_bytes: len, fec: true, confidence });
+    }
+    // A legacy length has nothing to check it against: only an intact magic counts.
+    let len = u32::from_be_bytes(bits_to_bytes(&header[..LENGTH_BYTES * 8]).try_into().expect("4 bytes")) as usize;
+    (errors == 0 && len > 0 && MAGIC_LEN + LENGTH_BYTES + len <= capacity).then_some(Detection { region, payload_bytes: len, fec: false, confidence: LEGACY_CONFIDENCE })
+}
+
+/// Cheap test for an unkeyed DWT embed: reads only the magic and length header at the start of
+/// each window `decode` would try (each tile size on its half-tile grid, then the whole image),
+/// without decoding payloads. Returns the first window whose header reads as a full-confidence
+/// FEC frame, else the most confident header found, else None.
+/// Only the image as stored is read (no rotations), and keyed, QIM and dot embeds are not seen:
+/// None means "not an unkeyed DWT image", not "no payload". A detection is not a guarantee the
+/// payload decodes.
+pub fn detect_rgba(img: &image::RgbaImage) -> Option<Detection> {
+    let (w, h) = (img.width() & !1, img.height() & !1);
+    let mut best: Option<Detection> = None;
+    for &tile in TILE_SIZES.iter().filter(|&&t| w >= t && h >= t) {
+        let step = (tile / 2) as usize;
+        let origins: Vec<(u32, u32)> = (0..=h - tile)
+            .step_by(step)
+            .flat_map(|oy| (0..=w - tile).step_by(step).map(move |ox| (ox, oy)))
+            .collect();
+        let found: Vec<Detection> = origins.par_iter().filter_map(|&(x, y)| detect_window(img, Region { x, y, width: tile, height: tile })).collect();
+        if let Some(&sure) = found.iter().find(|d| d.confidence >= 1.0) {
+            return Some(sure);
+        }
+        best = best.into_iter().chain(found).max_by(|a, b| a.confidence.total_cmp(&b.confidence));
+    }
+    let whole = detect_window(img, Region { x: 0, y: 0, width: w, height: h });
+    best.into_iter().chain(whole).max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+}
+
+/// `detect_rgba` of an image file (first frame of an animation).
+#[cfg(feature = "fs")]
+pub fn detect(image_path: &std::path::Path) -> Result<Option<Detection>, StegoError> {
+    Ok(detect_rgba(&load_image_with_orientation(image_path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use image::codecs::png::PngEncoder;
+    #[cfg(feature = "fs")]
+    use image::ImageEncoder;
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let mut img = image::RgbaImage::new(256, 256);
+        for (i, p) in img.pixels_mut().enumerate() {
+            let v = (i % 256) as u8;
+            *p = image::Rgba([v, v.wrapping_add(1), v.wrapping_add(2), 255]);
+        }
+        let mut png_bytes = Vec::new();
+        let encoder = PngEncoder::new(&mut png_bytes);
+        encoder
+            .write_image(img.as_raw(), 256, 256, ExtendedColorType::Rgba8)
+            .unwrap();
+        let cover_path = std::env::temp_dir().join("stego_test_cover.png");
+        std::fs::write(&cover_path, &png_bytes).unwrap();
+
+        let payload = b"Hello, Stegstr!";
+        let encoded = encode(&cover_path, payload, &EncodeOptions::default()).unwrap();
+        let out_path = std::env::temp_dir().join("stego_test_out.png");
+        std::fs::write(&out_path, &encoded).unwrap();
+
+        let decoded = decode(&out_path).unwrap();
+        assert_eq!(decoded, payload);
+
+        let _ = std::fs::remove_file(cover_path);
+        let _ = std::fs::remove_file(out_path);
+    }
+
+    /// The gradient cover of `test_encode_decode_roundtrip`, as PNG bytes.
+    #[cfg(feature = "fs")]
+    fn gradient_cover_png() -> Vec<u8> {
+        let mut img = image::RgbaImage::new(256, 256);
+        for (i, p) in img.pixels_mut().enumerate() {
+            let v = (i % 256) as u8;
+            *p = image::Rgba([v, v.wrapping_add(1), v.wrapping_add(2), 255]);
+        }
+        let mut png_bytes = Vec::new();
+        PngEncoder::new(&mut png_bytes)
+            .write_image(img.as_raw(), 256, 256, ExtendedColorType::Rgba8)
+            .unwrap();
+        png_bytes
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_encode_bytes_matches_file_encode() {
+        let png_bytes = gradient_cover_png();
+        let cover_path = std::env::temp_dir().join(format!("stego_test_bytes_cover_{}.png", std::process::id()));
+        std::fs::write(&cover_path, &png_bytes).unwrap();
+
+        let payload = b"Hello, Stegstr!";
+        let encoded = encode_bytes(&png_bytes, payload).unwrap();
+        assert_eq!(encoded, encode(&cover_path, payload, &EncodeOptions::default()).unwrap());
+        assert_eq!(decode_bytes(&encoded).unwrap(), payload);
+        let _ = std::fs::remove_file(cover_path);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_webp_output_decodes() {
+        let dir = std::env::temp_dir();
+        let cover_path = dir.join(format!("stego_test_webp_cover_{}.png", std::process::id()));
+        std::fs::write(&cover_path, gradient_cover_png()).unwrap();
+
+        let payload = b"Hello, Stegstr!";
+        let png = encode(&cover_path, payload, &EncodeOptions::default()).unwrap();
+        let webp = encode(&cover_path, payload, &EncodeOptions { format: OutputFormat::Webp, ..Default::default() }).unwrap();
+        assert!(webp.len() < png.len());
+        // WebP lossless output decodes too, even under a .png name.
+        let out_path = dir.join(format!("stego_test_webp_out_{}.png", std::process::id()));
+        std::fs::write(&out_path, &webp).unwrap();
+        assert_eq!(decode(&out_path).unwrap(), payload);
+
+        let _ = std::fs::remove_file(cover_path);
+        let _ = std::fs::remove_file(out_path);
+    }
+
+    #[test]
+    fn test_fec_frame_corrects_bit_errors() {
+        let mut img = image::RgbaImage::new(256, 256);
+        for (i, p) in img.pixels_mut().enumerate() {
+            let v = ((i * 3) % 200) as u8 + 20;
+            *p = image::Rgba([v, v.wrapping_add(9), v.wrapping_add(17), 255]);
+        }
+        let payload = b"corrected by Reed-Solomon".repeat(12);
+        let opts = EncodeOptions { parity: 32, ..Default::default() };
+        let encoded = encode_rgba(&img, &payload, None, &opts).unwrap();
+        assert_eq!(decode_bytes(&encoded).unwrap(), payload);
+        // Flip one bit in each of 15 scattered bytes after the header.
+        let framed = frame(&payload, 32).unwrap();
+        let mut bits: Vec<bool> = framed.iter().flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 == 1)).collect();
+        for k in 0..15 {
+            let byte = MAGIC_LEN + FEC_HEADER_LEN + k * 23;
+            bits[byte * 8 + k % 8] ^= true;
+        }
+        assert_eq!(decode_fec_frame(&bits[MAGIC_LEN * 8..]).unwrap(), payload);
+        // Legacy frames still decode.
+        let legacy = encode_rgba(&img, b"legacy", None, &EncodeOptions { parity: 0, ..opts }).unwrap();
+        assert_eq!(decode_bytes(&legacy).unwrap(), b"legacy");
+    }
+
+    #[test]
+    fn test_pilot_recovers_nearest_neighbour_upscale() {
+        let mut img = image::RgbaImage::new(300, 300);
+        let mut seed = 7u32;
+        for p in img.pixels_mut() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let v = (seed >> 16) as u8 / 2 + 60;
+            *p = image::Rgba([v, v.wrapping_add(13), v.wrapping_add(29), 255]);
+        }
+        let encoded = encode_rgba(&img, b"scaled by 120%", None, &EncodeOptions::default()).unwrap();
+        let stego = image::load_from_memory(&encoded).unwrap().to_rgba8();
+        let upscaled = image::imageops::resize(&stego, 360, 360, image::imageops::FilterType::Nearest);
+        assert!(decode_rgba(&upscaled, None).is_err());
+        assert_eq!(estimate_scale(&upscaled), Some((256, 307)));
+        let (payload, _) = decode_rgba_any_orientation(&upscaled, None, &Progress::default()).unwrap();
+        assert_eq!(payload, b"scaled by 120%");
+        // An image without a pilot finds no scale.
+        assert_eq!(estimate_scale(&img), None);
+    }
+
+    #[test]
+    fn test_small_tiles_single_channel() {
+        let mut img = image::RgbaImage::new(200, 200);
+        for (i, p) in img.pixels_mut().enumerate() {
+            let v = ((i * 5) % 180) as u8 + 30;
+            *p = image::Rgba([v, v.wrapping_add(11), v.wrapping_add(23), 255]);
+        }
+        let opts = EncodeOptions { tile_size: 64, redundancy: 2, channels: 1, parity: 0, key: None, format: OutputFormat::Png, interleave: 0, strength: 0, strip_metadata: false, spread: 0 };
+        let encoded = encode_rgba(&img, b"tiny tiles", None, &opts).unwrap();
+        let out = image::load_from_memory(&encoded).unwrap().to_rgba8();
+        // Two 64 px copies in the red channel only; green, blue and the third tile are untouched.
+        for (x, y, p) in out.enumerate_pixels() {
+            let orig = img.get_pixel(x, y);
+            assert_eq!(p.0[1..], orig.0[1..]);
+            if x >= 128 || y >= 64 {
+                assert_eq!(p, orig, "({}, {})", x, y);
+            }
+        }
+        let crop = image::imageops::crop_imm(&out, 64, 0, 64, 64).to_image();
+        assert_eq!(decode_rgba(&crop, None).unwrap(), b"tiny tiles");
+        assert_eq!(decode_rgba(&out, None).unwrap(), b"tiny tiles");
+        assert!(encode_rgba(&img, b"x", None, &EncodeOptions { tile_size: 100, ..opts }).is_err());
+    }
+
+    #[test]
+    fn test_stego_key_permutes_coefficients() {
+        let mut img = image::RgbaImage::new(256, 256);
+        for (i, p) in img.pixels_mut().enumerate() {
+            let v = ((i * 13) % 190) as u8 + 30;
+            *p = image::Rgba([v, v.wrapping_add(7), v.wrapping_add(19), 255]);
+        }
+        let key = StegoKey::from_password("s3cret").unwrap();
+        let opts = EncodeOptions { key: Some(key), ..Default::default() };
+        let encoded = encode_rgba(&img, b"keyed payload", None, &opts).unwrap();
+        let out = image::load_from_memory(&encoded).unwrap().to_rgba8();
+        assert_eq!(decode_rgba(&out, Some(&key)).unwrap(), b"keyed payload");
+        assert!(decode_rgba(&out, None).is_err());
+        let wrong = StegoKey::from_password("guess").unwrap();
+        assert!(decode_rgba(&out, Some(&wrong)).is_err());
+        // Unkeyed images still need no key.
+        let plain = image::load_from_memory(&encode_rgba(&img, b"plain", None, &EncodeOptions::default()).unwrap()).unwrap().to_rgba8();
+        assert_eq!(decode_rgba(&plain, None).unwrap(), b"plain");
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_decoy_embed_reads_per_key() {
+        let mut img = image::RgbaImage::new(512, 256);
+        for (i, p) in img.pixels_mut().enumerate() {
+            let v = ((i * 17) % 170) as u8 + 40;
+            *p = image::Rgba([v, v.wrapping_add(5), v.wrapping_add(21), 255]);
+        }
+        let decoy_key = StegoKey::from_password("shopping list").unwrap();
+        let key = StegoKey::from_password("the real one").unwrap();
+        let opts = EncodeOptions { parity: 16, ..Default::default() };
+        let encoded = encode_decoy_rgba(&img, b"eggs, milk", &decoy_key, b"meet at the dock", &key, &opts).unwrap();
+        let out = image::load_from_memory(&encoded).unwrap().to_rgba8();
+        assert_eq!(decode_rgba(&out, Some(&decoy_key)).unwrap(), b"eggs, milk");
+        assert_eq!(decode_rgba(&out, Some(&key)).unwrap(), b"meet at the dock");
+        assert!(decode_rgba(&out, None).is_err());
+        assert!(decode_rgba(&out, Some(&StegoKey::from_password("guess").unwrap())).is_err());
+        // Crops down to one tile keep both payloads.
+        let crop = image::imageops::crop_imm(&out, 256, 0, 256, 256).to_image();
+        assert_eq!(decode_rgba(&crop, Some(&key)).unwrap(), b"meet at the dock");
+        assert!(encode_decoy_rgba(&img, b"a", &key, b"b", &key, &opts).is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_interleaved_payload_spans_tiles_and_survives_crops() {
+        let mut img = image::RgbaImage::new(768, 512);
+        for (i, p) in img.pixels_mut().enumerate() {
+            let v = ((i * 13) % 190) as u8 + 30;
+            *p = image::Rgba([v, v.wrapping_add(9), v.wrapping_add(23), 255]);
+        }
+        // Incompressible and larger than one tile holds.
+        let payload = crate::test_util::noise_bytes(0x2545_f491, 9000);
+        let whole = Layer { frame: frame(&payload, 16).unwrap(), key: None, lane: None, strength: 0 };
+        assert!(!whole.fits(TILE_SIZE, TILE_SIZE, 3));
+        let opts = EncodeOptions { parity: 16, interleave: 3, ..Default::default() };
+        let out = image::load_from_memory(&encode_rgba(&img, &payload, None, &opts).unwrap()).unwrap().to_rgba8();
+        assert_eq!(decode_rgba(&out, None).unwrap(), payload);
+        // Cropping away three of the six tiles leaves enough stripes.
+        let crop = image::imageops::crop_imm(&out, 0, 256, 768, 256).to_image();
+        assert_eq!(decode_rgba(&crop, None).unwrap(), payload);
+        assert!(encode_rgba(&img, &payload, None, &EncodeOptions { interleave: 7, ..opts }).is_err());
+        let key = StegoKey::from_password("decoy").unwrap();
+        assert!(encode_decoy_rgba(&img, b"a", &key, b"b", &StegoKey::from_password("real").unwrap(), &opts).is_err());
+    }
+
+    #[test]
+    fn test_qim_strength_survives_blur_and_sharpening() {
+        // Smooth, slightly noisy cover: the case mild filtering is usually applied to.
+        let mut rng = crate::test_util::XorShift::new(0x1234_5678);
+        let img = image::RgbaImage::from_fn(256, 256, |x, y| {
+            let noise = (rng.next_u32() % 9) as i32 - 4;
+            let v = |k: f64| ((x as f64 / k).sin() * 50.0 + (y as f64 / (k * 1.3)).cos() * 40.0 + 120.0) as i32 + noise;
+            image::Rgba([v(23.0) as u8, v(31.0) as u8, v(17.0) as u8, 255])
+        });
+        let embed = |strength| {
+            let opts = EncodeOptions { strength, ..Default::default() };
+            image::load_from_memory(&encode_rgba(&img, b"mildly filtered", None, &opts).unwrap()).unwrap().to_rgba8()
+        };
+        let lsb = embed(0);
+        assert!(decode_rgba(&image::imageops::blur(&lsb, 0.6), None).is_err());
+        let qim = embed(3);
+        assert_eq!(decode_rgba(&qim, None).unwrap(), b"mildly filtered");
+        assert_eq!(decode_rgba(&image::imageops::blur(&qim, 0.8), None).unwrap(), b"mildly filtered");
+        assert_eq!(decode_rgba(&image::imageops::unsharpen(&qim, 1.0, 0), None).unwrap(), b"mildly filtered");
+        assert!(EncodeOptions { strength: MAX_STRENGTH + 1, ..Default::default() }.validated().is_err());
+    }
+
+    #[test]
+    fn test_grayscale_and_16_bit_covers_keep_their_colour_type() {
+        let sample = |x: u32, y: u32, k: u32| ((x * 131 + y * 71 + k * 29) * 197 % 60_000 + 2_000) as u16;
+        let covers = [
+            DynamicImage::ImageLuma8(image::GrayImage::from_fn(256, 256, |x, y| image::Luma([(sample(x, y, 0) >> 8) as u8]))),
+            DynamicImage::ImageLuma16(image::ImageBuffer::from_fn(256, 256, |x, y| image::Luma([sample(x, y, 0)]))),
+            DynamicImage::ImageRgb16(image::ImageBuffer::from_fn(256, 256, |x, y| image::Rgb([sample(x, y, 0), sample(x, y, 1), sample(x, y, 2)]))),
+        ];
+        for cover in &covers {
+            let encoded = encode_image(cover, &Metadata::default(), b"deep colour", None, &EncodeOptions::default(), &Progress::default()).unwrap();
+            let out = image::load_from_memory(&encoded).unwrap();
+            assert_eq!(out.color(), cover.color());
+            assert_eq!(decode_rgba(&out.to_rgba8(), None).unwrap(), b"deep colour");
+            if let (DynamicImage::ImageRgb16(before), DynamicImage::ImageRgb16(after)) = (cover, &out) {
+                // Only whole 8-bit steps change; the bits below them survive.
+                assert!(before.as_raw().iter().zip(after.as_raw()).all(|(&b, &a)| (a as i32 - b as i32) % 257 == 0));
+                assert_ne!(before, after);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_undoes_rotation_and_mirroring() {
+        let mut img = image::RgbaImage::new(384, 256);
+        for (i, p) in img.pixels_mut().enumerate() {
+            let v = ((i * 11) % 180) as u8 + 40;
+            *p = image::Rgba([v, v.wrapping_add(13), v.wrapping_add(29), 255]);
+        }
+        let encoded = encode_rgba(&img, b"any way up", None, &EncodeOptions { parity: 16, ..Default::default() }).unwrap();
+        let out = image::load_from_memory(&encoded).unwrap();
+        let legacy = encode_rgba(&img, b"stored only", None, &EncodeOptions { parity: 0, ..Default::default() }).unwrap();
+        let mut mirrored = image::load_from_memory(&legacy).unwrap();
+        mirrored.apply_orientation(Orientation::FlipHorizontal);
+        assert!(decode_rgba_any_orientation(&mirrored.to_rgba8(), None, &Progress::default()).is_err());
+        assert_eq!(decode_rgba_any_orientation(&out.to_rgba8(), None, &Progress::default()).unwrap().1, Orientation::NoTransforms);
+        for o in ORIENTATIONS {
+            let mut turned = out.clone();
+            turned.apply_orientation(o);
+            let (payload, undergone) = decode_rgba_any_orientation(&turned.to_rgba8(), None, &Progress::default()).unwrap();
+            assert_eq!((payload.as_slice(), undergone), (&b"any way up"[..], o), "{}", orientation_name(o));
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_decode_region_finds_tile() {
+        let mut img = image::RgbaImage::new(512, 512);
+        for (i, p) in img.pixels_mut().enumerate() {
+            let v = ((i * 7) % 256) as u8;
+            *p = image::Rgba([v, v.wrapping_add(31), v.wrapping_add(67), 255]);
+        }
+        let cover_path = std::env::temp_dir().join("stego_region_cover.png");
+        img.save(&cover_path).unwrap();
+        let encoded = encode(&cover_path, b"region", &EncodeOptions::default()).unwrap();
+        let out_path = std::env::temp_dir().join("stego_region_out.png");
+        std::fs::write(&out_path, &encoded).unwrap();
+
+        let cap = capacity(&cover_path).unwrap();
+        assert_eq!((cap.max_bytes, cap.tile_bytes, cap.tiles), (24565, 6133, 4));
+
+        let region = Region { x: 256, y: 256, width: 256, height: 256 };
+        assert_eq!(decode_region(&out_path, region).unwrap(), b"region");
+        let outside = Region { x: 600, y: 0, width: 10, height: 10 };
+        assert!(decode_region(&out_path, outside).is_err());
+
+        let _ = std::fs::remove_file(cover_path);
+        let _ = std::fs::remove_file(out_path);
+    }
+
+    #[test]
+    fn test_cover_narrower_than_a_tile_decodes_from_its_corner() {
+        // 300x200 fills edge tiles of 256x200 and 44x200: the first is neither a full tile nor
+        // the whole image.
+        let img = image::RgbaImage::from_fn(300, 200, |x, y| image::Rgba([(40 + (x * 3 + y) % 170) as u8, (40 + y * 5 % 170) as u8, (40 + (x ^ y) % 170) as u8, 255]));
+        for parity in [0, DEFAULT_PARITY] {
+            let encoded = encode_rgba(&img, b"edge tile", None, &EncodeOptions { parity, ..Default::default() }).unwrap();
+            assert_eq!(decode_bytes(&encoded).unwrap(), b"edge tile", "parity {}", parity);
+        }
+    }
+
+    #[test]
+    fn test_detect_reads_headers_only() {
+        let img = image::RgbaImage::from_fn(640, 384, |x, y| image::Rgba([(40 + (x * 3 + y) % 170) as u8, (40 + y * 5 % 170) as u8, (40 + (x ^ y) % 170) as u8, 255]));
+        let cover = OutputFormat::Png.encode(img.as_raw(), 640, 384, ExtendedColorType::Rgba8).unwrap();
+        assert_eq!(detect_rgba(&img), None);
+
+        let read = |bytes: &[u8]| detect_rgba(&load_image_bytes_with_orientation(bytes).unwrap()).unwrap();
+        // The default embed writes the FEC frame; legacy frames are still read.
+        let fec = read(&encode_bytes(&cover, b"detect me").unwrap());
+        assert_eq!((fec.region, fec.fec, fec.confidence), (Region { x: 0, y: 0, width: 256, height: 256 }, true, 1.0));
+        let legacy = read(&encode_bytes_with_options(&cover, b"detect me", &EncodeOptions { parity: 0, ..Default::default() }).unwrap());
+        assert_eq!((legacy.fec, legacy.confidence, legacy.payload_bytes), (false, LEGACY_CONFIDENCE, fec.payload_bytes));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_cover_score_prefers_texture() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(5);
+        let flat = image::RgbaImage::from_fn(128, 128, |x, _| image::Rgba([if x < 64 { 240 } else { 30 }, 120, 120, 255]));
+        let textured = image::RgbaImage::from_fn(128, 128, |x, y| {
+            let v = ((x * 3 + y * 5) % 200) as u8 + rng.gen_range(0..40);
+            image::Rgba([v, v / 2, 255 - v, 255])
+        });
+        let (flat, textured) = (cover_score_rgba(&flat, 100), cover_score_rgba(&textured, 100));
+        assert!(flat.flat_fraction > 0.9 && textured.flat_fraction < 0.1);
+        assert!(textured.entropy > flat.entropy);
+        assert!(textured.score > 80.0 && flat.score < 20.0, "{} vs {}", textured.score, flat.score);
+    }
+}
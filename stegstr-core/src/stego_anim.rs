@@ -0,0 +1,214 @@
+// Animated covers (APNG and animated GIF). Still-image decoding reads only a file's first frame;
+// `Animation::read` yields every frame, composited onto the full canvas, with its delay. The DWT
+// and dot codecs embed into each frame (`Animation::embed`), and the result is re-encoded in the
+// cover's container with the same frame timing.
+// Every frame carries the whole payload by default, so any single frame (a trimmed clip, a
+// thumbnail of frame one) still decodes. With `spread` > 0 the payload is fountain-coded
+// (stego_fountain) into one part per frame, any `spread` of which rebuild it, for payloads too
+// large for one frame.
+// APNG output writes every frame whole (source blending, no disposal, no sub-frame offsets) as
+// RGBA8, keeping the cover's play count. GIF output is palette-quantised per frame, which DWT
+// LSBs do not survive, so GIF covers take the dot codec only; the output loops forever. The
+// cover's metadata chunks and the output format option do not apply to animations.
+// Decoding (`Animation::extract`) reads frames in order until one decodes; a fountain part sends
+// it on to collect the parts of every frame and rebuild the payload.
+
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, Delay, RgbaImage};
+use std::io::Cursor;
+
+use crate::error::StegoError;
+use crate::output::png_chunks;
+use crate::stego_fountain;
+
+/// File format an animation is read from and written back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Apng,
+    Gif,
+}
+
+/// One frame on the full canvas and how long it shows.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub image: RgbaImage,
+    pub delay: Delay,
+}
+
+/// Frames of an animated cover.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub container: Container,
+    pub frames: Vec<Frame>,
+    /// APNG play count (0 = forever).
+    plays: u32,
+}
+
+/// True for an APNG (acTL chunk before the image data) or a GIF of more than one frame.
+pub fn is_animated(bytes: &[u8]) -> bool {
+    apng_plays(bytes).is_some() || bytes.starts_with(b"GIF8") && matches!(Animation::read(bytes), Ok(Some(_)))
+}
+
+/// Play count of an APNG, None for a still PNG or anything else.
+fn apng_plays(bytes: &[u8]) -> Option<u32> {
+    png_chunks(bytes)
+        .take_while(|chunk| &chunk[4..8] != b"IDAT")
+        .find(|chunk| &chunk[4..8] == b"acTL" && chunk.len() >= 20)
+        .map(|chunk| u32::from_be_bytes(chunk[12..16].try_into().expect("4 bytes")))
+}
+
+fn collect_frames(frames: image::Frames<'_>) -> Result<Vec<Frame>, StegoError> {
+    frames
+        .map(|frame| {
+            let frame = frame?;
+            Ok(Frame { delay: frame.delay(), image: frame.into_buffer() })
+        })
+        .collect()
+}
+
+/// APNG delay fraction (seconds) for `delay`, reduced, or rounded to milliseconds when it does not
+/// fit in 16 bits.
+fn apng_delay(delay: Delay) -> (u16, u16) {
+    let (numer, denom_ms) = delay.numer_denom_ms();
+    let (n, d) = (numer as u64, denom_ms.max(1) as u64 * 1000);
+    let (mut a, mut b) = (n, d);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    match (u16::try_from(n / a), u16::try_from(d / a)) {
+        (Ok(n), Ok(d)) => (n, d),
+        _ => (((n * 1000 + d / 2) / d).min(u16::MAX as u64) as u16, 1000),
+    }
+}
+
+impl Animation {
+    /// The frames of an animated cover; None for a still image (or a format without animation).
+    pub fn read(bytes: &[u8]) -> Result<Option<Animation>, StegoError> {
+        let (container, plays, frames) = if let Some(plays) = apng_plays(bytes) {
+            let frames = PngDecoder::new(Cursor::new(bytes))?.apng()?.into_frames();
+            (Container::Apng, plays, collect_frames(frames)?)
+        } else if bytes.starts_with(b"GIF8") {
+            (Container::Gif, 0, collect_frames(GifDecoder::new(Cursor::new(bytes))?.into_frames())?)
+        } else {
+            return Ok(None);
+        };
+        Ok((frames.len() > 1).then_some(Animation { container, frames, plays }))
+    }
+
+    /// Embed `payload` with `embed` (a codec's frame embed, which may crop to even dimensions)
+    /// into every frame, or spread over them as fountain parts any `spread` of which rebuild it.
+    /// Returns the animation re-encoded in its container.
+    pub fn embed(
+        mut self,
+        payload: &[u8],
+        spread: u32,
+        embed: impl Fn(&RgbaImage, &[u8]) -> Result<RgbaImage, StegoError>,
+    ) -> Result<Vec<u8>, StegoError> {
+        let parts = match spread {
+            0 => vec![payload.to_vec(); self.frames.len()],
+            n => stego_fountain::encode(payload, self.frames.len(), n as usize)?,
+        };
+        for (frame, part) in self.frames.iter_mut().zip(&parts) {
+            let embedded = embed(&frame.image, part)?;
+            image::imageops::replace(&mut frame.image, &embedded, 0, 0);
+        }
+        self.write()
+    }
+
+    /// The payload `decode` (a codec's frame decode) reads from the frames (module header).
+    pub fn extract(&self, decode: impl Fn(&RgbaImage) -> Result<Vec<u8>, StegoError>) -> Result<Vec<u8>, StegoError> {
+        let mut frames = self.frames.iter().map(|frame| decode(&frame.image));
+        let mut first_err = None;
+        let first = loop {
+            match frames.next() {
+                Some(Ok(payload)) => break payload,
+                Some(Err(e)) => {
+                    first_err.get_or_insert(e);
+                }
+                None => return Err(first_err.unwrap_or_else(|| StegoError::NotStego("Animation has no frames".to_string()))),
+            }
+        };
+        if !stego_fountain::is_fountain_part(&first) {
+            return Ok(first);
+        }
+        let mut parts: Vec<Vec<u8>> = std::iter::once(first.clone()).chain(frames.flatten()).collect();
+        parts.sort();
+        parts.dedup();
+        Ok(stego_fountain::decode(&parts).unwrap_or(first))
+    }
+
+    /// The frames encoded as APNG or GIF, with their delays.
+    pub fn write(&self) -> Result<Vec<u8>, StegoError> {
+        let mut out = Vec::new();
+        match self.container {
+            Container::Apng => {
+                let image_err = |e: png::EncodingError| StegoError::Image(e.to_string());
+                let (w, h) = self.frames[0].image.dimensions();
+                let mut encoder = png::Encoder::new(&mut out, w, h);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(self.frames.len() as u32, self.plays).map_err(image_err)?;
+                let mut writer = encoder.write_header().map_err(image_err)?;
+                for frame in &self.frames {
+                    let (n, d) = apng_delay(frame.delay);
+                    writer.set_frame_delay(n, d).map_err(image_err)?;
+                    writer.write_image_data(frame.image.as_raw()).map_err(image_err)?;
+                }
+                writer.finish().map_err(image_err)?;
+            }
+            Container::Gif => {
+                let mut encoder = GifEncoder::new(&mut out);
+                encoder.set_repeat(Repeat::Infinite)?;
+                encoder.encode_frames(self.frames.iter().map(|f| image::Frame::from_parts(f.image.clone(), 0, 0, f.delay)))?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apng_and_gif_frames_carry_the_payload_and_keep_their_timing() {
+        let frames: Vec<Frame> = (0..4u32)
+            .map(|i| Frame {
+                image: RgbaImage::from_fn(256, 256, |x, y| image::Rgba([(40 + (x * 3 + i * 40) % 170) as u8, (40 + y * 5 % 170) as u8, (40 + ((x ^ y) + i * 20) % 170) as u8, 255])),
+                delay: Delay::from_numer_denom_ms(40 + 20 * i, 1),
+            })
+            .collect();
+        let apng = Animation { container: Container::Apng, frames: frames.clone(), plays: 3 }.write().unwrap();
+        assert!(is_animated(&apng));
+        assert_eq!(apng_plays(&apng), Some(3));
+
+        let stego = crate::stego::encode_bytes(&apng, b"every frame").unwrap();
+        let read = Animation::read(&stego).unwrap().unwrap();
+        assert_eq!(read.container, Container::Apng);
+        assert_eq!(read.plays, 3);
+        let delays: Vec<Delay> = read.frames.iter().map(|f| f.delay).collect();
+        assert_eq!(delays, frames.iter().map(|f| f.delay).collect::<Vec<_>>());
+        assert_eq!(crate::stego::decode_bytes(&stego).unwrap(), b"every frame");
+        // Any one frame holds the whole payload.
+        let last = &read.frames[3].image;
+        assert_eq!(crate::stego::decode_bytes(&crate::output::OutputFormat::Png.encode(last.as_raw(), 256, 256, image::ExtendedColorType::Rgba8).unwrap()).unwrap(), b"every frame");
+
+        // Spread: a payload larger than one frame holds, any three frames rebuild it.
+        let big = crate::test_util::noise_bytes(0x2545_f491, 9000);
+        let opts = crate::stego::EncodeOptions { spread: 3, ..Default::default() };
+        assert!(crate::stego::encode_bytes(&apng, &big).is_err());
+        let mut spread = Animation::read(&crate::stego::encode_bytes_with_options(&apng, &big, &opts).unwrap()).unwrap().unwrap();
+        spread.frames.remove(1);
+        assert_eq!(crate::stego::decode_bytes(&spread.write().unwrap()).unwrap(), big);
+
+        let gif = Animation { container: Container::Gif, frames, plays: 0 }.write().unwrap();
+        assert!(is_animated(&gif));
+        assert!(crate::stego::encode_bytes(&gif, b"x").is_err());
+        let dot = crate::stego_dot::encode_bytes(&gif, b"gif frames", &Default::default()).unwrap();
+        let read = Animation::read(&dot).unwrap().unwrap();
+        assert_eq!((read.container, read.frames.len()), (Container::Gif, 4));
+        assert_eq!(read.frames[2].delay, Delay::from_numer_denom_ms(80, 1));
+        assert_eq!(crate::stego_dot::decode_bytes(&dot, None).unwrap(), b"gif frames");
+    }
+}
@@ -0,0 +1,892 @@
+// Stegstr app-layer encryption: AES-GCM with app-derived key.
+// Matches stego-crypto.ts: STEGSTR1 + version + iv + ciphertext (tag 128 bits).
+// Passphrase mode (version 2): the key is Argon2id(passphrase, salt); the header carries the
+// cost parameters so they can be raised later: STEGSTR1 + 2 + m_cost + t_cost + p_cost (u32 BE)
+// + salt + iv + ciphertext.
+// Public-key mode (version 3): ECDH between a one-time key and the recipient's Nostr key,
+// AES key = SHA-256(salt + shared x): STEGSTR1 + 3 + ephemeral x-only pubkey + iv + ciphertext.
+// New public-key payloads set HEADER_AAD_FLAG and authenticate everything before the iv (magic,
+// version byte, cipher id, binding salt, ephemeral pubkey) as associated data; payloads without
+// the flag still decrypt as before.
+// Recipients mode: inner JSON envelope { t: "r", s, r: [{ p, k }], c } where k is the
+// NIP-04-encrypted symmetric key per recipient and c = base64(iv + AES-GCM ciphertext).
+// Cipher: AES-256-GCM unless the version byte has CIPHER_FLAG set, in which case a cipher id byte
+// follows it (CIPHER_AES_GCM or CIPHER_CHACHA20_POLY1305, same 12-byte nonce and 16-byte tag) and
+// the rest is laid out as for the unflagged version. AES payloads keep the unflagged layout so
+// stego-crypto.ts and older builds still read them; decrypt detects the cipher from the header.
+// Image binding: with BIND_FLAG set in the version byte a 16-byte random salt follows the version
+// (and cipher) bytes, and the AEAD's associated data is BIND_DOMAIN + the image's long and short
+// side (u32 BE, rounded down to even) + that salt. Decrypting then needs the size of the image the
+// payload was read from, so a ciphertext lifted into a different-size cover fails the tag check.
+// Sides are sorted (rotation keeps the binding) and rounded to even (the DWT codec crops odd
+// edges); codecs that resize the cover (qim, profiles) cannot carry bound payloads.
+// Bundle plaintexts are encrypted in the form EncryptOptions::bundle names (bundle::pack; v1 JSON
+// unless v2 is asked for); decryption hands back v1 JSON either way.
+// NIP-44 v2 (direct messages): HKDF-SHA256 conversation and message keys, ChaCha20, HMAC-SHA256,
+// written out here on sha2 so the wasm build needs no further crates.
+// Derived keys, ECDH secrets and the recipients envelope's symmetric key are held in `Zeroizing`
+// buffers and wiped when dropped.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm,
+};
+use aead::generic_array::GenericArray;
+use base64::Engine;
+use chacha20poly1305::ChaCha20Poly1305;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::bundle::BundleFormat;
+use crate::error::StegoError;
+
+const STEGSTR_MAGIC: &[u8] = b"STEGSTR1";
+pub const VERSION: u8 = 1;
+pub const VERSION_PASSWORD: u8 = 2;
+pub const VERSION_PUBKEY: u8 = 3;
+/// Version byte flag: a cipher id byte follows.
+const CIPHER_FLAG: u8 = 0x80;
+/// Version byte flag: a binding salt follows (after the cipher id byte, if any).
+const BIND_FLAG: u8 = 0x40;
+const BIND_DOMAIN: &[u8] = b"stegstr-bind-v1";
+/// Version byte flag: the header and mode fields are the AEAD's associated data.
+const HEADER_AAD_FLAG: u8 = 0x20;
+const CIPHER_AES_GCM: u8 = 0;
+const CIPHER_CHACHA20_POLY1305: u8 = 1;
+const APP_KEY_SALT: &[u8] = b"stegstr-decrypt-v1";
+const PUBKEY_KEY_SALT: &[u8] = b"stegstr-pubkey-v1";
+const PUBKEY_LEN: usize = 32;
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const SALT_LEN: usize = 16;
+/// Argon2id parameters, 3 x u32 BE.
+const PARAMS_LEN: usize = 12;
+/// Refuse headers asking for more than 1 GiB of Argon2 memory (KiB).
+const MAX_M_COST: u32 = 1 << 20;
+const NIP44_VERSION: u8 = 2;
+const NIP44_SALT: &[u8] = b"nip44-v2";
+/// NIP-44 plaintexts are 1 to 65535 bytes.
+const NIP44_MAX_PLAINTEXT: usize = 65535;
+
+/// AEAD for payload encryption. Both take a 32-byte key and a 12-byte nonce and add a 16-byte tag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Cipher {
+    /// AES-256-GCM (default; readable by every Stegstr build).
+    #[default]
+    Aes256Gcm,
+    /// ChaCha20-Poly1305, for hardware without AES instructions or policies that exclude AES.
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    /// "aes" / "aes-256-gcm" or "chacha20" / "chacha20-poly1305".
+    pub fn parse(name: &str) -> Result<Cipher, StegoError> {
+        match name.to_ascii_lowercase().as_str() {
+            "aes" | "aes-gcm" | "aes-256-gcm" | "aes256gcm" => Ok(Cipher::Aes256Gcm),
+            "chacha20" | "chacha" | "chacha20-poly1305" | "chacha20poly1305" => Ok(Cipher::ChaCha20Poly1305),
+            other => Err(StegoError::InvalidInput(format!("Unknown cipher {:?} (aes-256-gcm or chacha20-poly1305)", other))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Cipher::Aes256Gcm => "aes-256-gcm",
+            Cipher::ChaCha20Poly1305 => "chacha20-poly1305",
+        }
+    }
+
+    fn id(self) -> u8 {
+        match self {
+            Cipher::Aes256Gcm => CIPHER_AES_GCM,
+            Cipher::ChaCha20Poly1305 => CIPHER_CHACHA20_POLY1305,
+        }
+    }
+
+    fn encrypt(self, key: &[u8; 32], iv: &[u8; IV_LEN], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, StegoError> {
+        let nonce = GenericArray::from_slice(iv);
+        let payload = Payload { msg: plaintext, aad };
+        match self {
+            Cipher::Aes256Gcm => Aes256Gcm::new(key.into()).encrypt(nonce, payload),
+            Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new(key.into()).encrypt(nonce, payload),
+        }
+        .map_err(|e| StegoError::Other(e.to_string()))
+    }
+
+    /// None when the tag does not verify (wrong key, wrong image or corrupted data).
+    fn decrypt(self, key: &[u8], iv: &[u8], ciphertext: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        let nonce = GenericArray::from_slice(iv);
+        let payload = Payload { msg: ciphertext, aad };
+        match self {
+            Cipher::Aes256Gcm => Aes256Gcm::new_from_slice(key).ok()?.decrypt(nonce, payload).ok(),
+            Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key).ok()?.decrypt(nonce, payload).ok(),
+        }
+    }
+}
+
+/// Size of the image a payload is bound to (see the module header).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ImageBinding {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageBinding {
+    /// Long and short side, rounded down to even.
+    fn sides(self) -> (u32, u32) {
+        let (w, h) = (self.width & !1, self.height & !1);
+        (w.max(h), w.min(h))
+    }
+
+    /// True if a payload bound to `self` decrypts from an image of size `other`.
+    pub fn matches(self, other: ImageBinding) -> bool {
+        self.sides() == other.sides()
+    }
+}
+
+/// How the `*_with` functions encrypt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncryptOptions {
+    pub cipher: Cipher,
+    /// Bind the payload to an image of this size.
+    pub binding: Option<ImageBinding>,
+    /// Form of bundle plaintexts (bundle::pack): v1 JSON by default, or the smaller v2.
+    pub bundle: BundleFormat,
+}
+
+/// Fields of a STEGSTR1 header.
+struct Header {
+    version: u8,
+    cipher: Cipher,
+    bind_salt: Option<[u8; SALT_LEN]>,
+    /// HEADER_AAD_FLAG: the bytes before the iv are authenticated.
+    header_aad: bool,
+    /// Bytes up to the mode-specific fields.
+    len: usize,
+}
+
+impl Header {
+    fn write(out: &mut Vec<u8>, version: u8, cipher: Cipher, bind_salt: Option<&[u8; SALT_LEN]>, header_aad: bool) {
+        out.extend_from_slice(STEGSTR_MAGIC);
+        let mut flags = if bind_salt.is_some() { BIND_FLAG } else { 0 };
+        if header_aad {
+            flags |= HEADER_AAD_FLAG;
+        }
+        match cipher {
+            Cipher::Aes256Gcm => out.push(version | flags),
+            other => out.extend_from_slice(&[version | flags | CIPHER_FLAG, other.id()]),
+        }
+        if let Some(salt) = bind_salt {
+            out.extend_from_slice(salt);
+        }
+    }
+
+    /// None without the magic, or with an unknown cipher or truncated header.
+    fn read(encrypted: &[u8]) -> Option<Header> {
+        if !is_encrypted_payload(encrypted) {
+            return None;
+        }
+        let mut len = STEGSTR_MAGIC.len() + 1;
+        let byte = *encrypted.get(STEGSTR_MAGIC.len())?;
+        let cipher = if byte & CIPHER_FLAG == 0 {
+            Cipher::Aes256Gcm
+        } else {
+            len += 1;
+            match *encrypted.get(len - 1)? {
+                CIPHER_AES_GCM => Cipher::Aes256Gcm,
+                CIPHER_CHACHA20_POLY1305 => Cipher::ChaCha20Poly1305,
+                _ => return None,
+            }
+        };
+        let bind_salt = if byte & BIND_FLAG == 0 {
+            None
+        } else {
+            len += SALT_LEN;
+            Some(encrypted.get(len - SALT_LEN..len)?.try_into().ok()?)
+        };
+        Some(Header {
+            version: byte & !(CIPHER_FLAG | BIND_FLAG | HEADER_AAD_FLAG),
+            cipher,
+            bind_salt,
+            header_aad: byte & HEADER_AAD_FLAG != 0,
+            len,
+        })
+    }
+
+    /// `message`, noting the image as a cause when the payload is bound.
+    fn tag_error(&self, message: &str) -> String {
+        match self.bind_salt {
+            Some(_) => format!("{} (or not read from the image it was bound to)", message),
+            None => message.to_string(),
+        }
+    }
+
+    /// Associated data: `prefix` (the bytes before the iv) when HEADER_AAD_FLAG is set, then the
+    /// binding; bound payloads need the image's size.
+    fn aad(&self, prefix: &[u8], binding: Option<ImageBinding>) -> Result<Vec<u8>, StegoError> {
+        let mut aad = if self.header_aad { prefix.to_vec() } else { vec![] };
+        if let Some(salt) = &self.bind_salt {
+            let binding = binding.ok_or_else(|| {
+                StegoError::KeyRequired("Payload is bound to the image it was embedded in; decrypt it as read from that image".to_string())
+            })?;
+            aad.extend_from_slice(&bind_aad(binding, salt));
+        }
+        Ok(aad)
+    }
+}
+
+fn bind_aad(binding: ImageBinding, salt: &[u8; SALT_LEN]) -> Vec<u8> {
+    let (long, short) = binding.sides();
+    let mut aad = Vec::with_capacity(BIND_DOMAIN.len() + 8 + SALT_LEN);
+    aad.extend_from_slice(BIND_DOMAIN);
+    aad.extend_from_slice(&long.to_be_bytes());
+    aad.extend_from_slice(&short.to_be_bytes());
+    aad.extend_from_slice(salt);
+    aad
+}
+
+/// Header for `version` under `opts`, then `fields`, iv and ciphertext. With `header_aad` the
+/// header and fields are authenticated too.
+fn seal(version: u8, opts: &EncryptOptions, fields: &[u8], key: &Zeroizing<[u8; 32]>, plaintext: &str, header_aad: bool) -> Result<Vec<u8>, StegoError> {
+    let salt = opts.binding.map(|_| {
+        let mut salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        salt
+    });
+    let mut out = Vec::with_capacity(STEGSTR_MAGIC.len() + 2 + SALT_LEN + fields.len() + IV_LEN + plaintext.len() + TAG_LEN);
+    Header::write(&mut out, version, opts.cipher, salt.as_ref(), header_aad);
+    out.extend_from_slice(fields);
+    let mut aad = if header_aad { out.clone() } else { vec![] };
+    if let (Some(binding), Some(salt)) = (opts.binding, &salt) {
+        aad.extend_from_slice(&bind_aad(binding, salt));
+    }
+    let mut iv = [0u8; IV_LEN];
+    rand::thread_rng().fill_bytes(&mut iv);
+    let plaintext = crate::bundle::pack(plaintext.as_bytes(), opts.bundle);
+    let ciphertext = opts.cipher.encrypt(key, &iv, &plaintext, &aad)?;
+    out.extend_from_slice(&iv);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Cipher of an encrypted payload, from its header.
+pub fn payload_cipher(encrypted: &[u8]) -> Option<Cipher> {
+    Header::read(encrypted).map(|h| h.cipher)
+}
+
+/// True if the payload must be decrypted with the size of its image (`*_bound` functions).
+pub fn is_bound_payload(encrypted: &[u8]) -> bool {
+    Header::read(encrypted).is_some_and(|h| h.bind_salt.is_some())
+}
+
+fn app_key() -> Zeroizing<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update(APP_KEY_SALT);
+    Zeroizing::new(hasher.finalize().into())
+}
+
+/// Encrypt plaintext so only Stegstr can decrypt. Returns binary: magic + version + iv + ciphertext.
+pub fn encrypt_app(plaintext: &str) -> Result<Vec<u8>, StegoError> {
+    encrypt_app_with(plaintext, &EncryptOptions::default())
+}
+
+/// `encrypt_app` with another cipher or an image binding.
+pub fn encrypt_app_with(plaintext: &str, opts: &EncryptOptions) -> Result<Vec<u8>, StegoError> {
+    seal(VERSION, opts, &[], &app_key(), plaintext, false)
+}
+
+/// Decrypt app-encrypted payload. Returns inner plaintext string.
+pub fn decrypt_app(encrypted: &[u8]) -> Result<String, StegoError> {
+    decrypt_app_bound(encrypted, None)
+}
+
+/// `decrypt_app` of a payload read from an image of size `binding`.
+pub fn decrypt_app_bound(encrypted: &[u8], binding: Option<ImageBinding>) -> Result<String, StegoError> {
+    let h = Header::read(encrypted).ok_or_else(|| StegoError::Decrypt("Invalid Stegstr encrypted payload".to_string()))?;
+    let (version, header) = (h.version, h.len);
+    if encrypted.len() < header + IV_LEN + TAG_LEN {
+        return Err(StegoError::Decrypt("Payload too short".to_string()));
+    }
+    if version == VERSION_PASSWORD {
+        return Err(StegoError::KeyRequired("Payload is passphrase-protected; a passphrase is required".to_string()));
+    }
+    if version == VERSION_PUBKEY {
+        return Err(StegoError::KeyRequired("Payload is encrypted for a public key; a private key is required".to_string()));
+    }
+    if version != VERSION {
+        return Err(StegoError::Decrypt("Unsupported encryption version".to_string()));
+    }
+    let dec = h
+        .cipher
+        .decrypt(&*app_key(), &encrypted[header..header + IV_LEN], &encrypted[header + IV_LEN..], &h.aad(&encrypted[..header], binding)?)
+        .ok_or_else(|| StegoError::Decrypt(h.tag_error("aead::Error")))?;
+    crate::bundle::unpack(dec).map_err(|e| StegoError::Decrypt(e.to_string()))
+}
+
+/// True if bytes look like Stegstr encrypted (magic).
+pub fn is_encrypted_payload(bytes: &[u8]) -> bool {
+    bytes.len() >= STEGSTR_MAGIC.len() && bytes[..STEGSTR_MAGIC.len()] == STEGSTR_MAGIC[..]
+}
+
+/// True if the payload was encrypted with `encrypt_password`.
+pub fn is_password_payload(bytes: &[u8]) -> bool {
+    Header::read(bytes).is_some_and(|h| h.version == VERSION_PASSWORD)
+}
+
+fn password_key(passphrase: &str, salt: &[u8], params: argon2::Params) -> Result<Zeroizing<[u8; 32]>, StegoError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut *key)
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+/// Encrypt plaintext under a passphrase (Argon2id + AES-256-GCM). Returns binary:
+/// magic + version 2 + Argon2 params + salt + iv + ciphertext.
+pub fn encrypt_password(plaintext: &str, passphrase: &str) -> Result<Vec<u8>, StegoError> {
+    encrypt_password_with(plaintext, passphrase, &EncryptOptions::default())
+}
+
+/// `encrypt_password` with another cipher or an image binding.
+pub fn encrypt_password_with(plaintext: &str, passphrase: &str, opts: &EncryptOptions) -> Result<Vec<u8>, StegoError> {
+    if passphrase.is_empty() {
+        return Err(StegoError::InvalidInput("Passphrase must not be empty".to_string()));
+    }
+    let params = argon2::Params::default();
+    let mut salt = [0u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let key = password_key(passphrase, &salt, params.clone())?;
+    let mut fields = Vec::with_capacity(PARAMS_LEN + SALT_LEN);
+    for v in [params.m_cost(), params.t_cost(), params.p_cost()] {
+        fields.extend_from_slice(&v.to_be_bytes());
+    }
+    fields.extend_from_slice(&salt);
+    seal(VERSION_PASSWORD, opts, &fields, &key, plaintext, false)
+}
+
+/// Decrypt a passphrase-encrypted payload. A wrong passphrase fails the AEAD tag check.
+pub fn decrypt_password(encrypted: &[u8], passphrase: &str) -> Result<String, StegoError> {
+    decrypt_password_bound(encrypted, passphrase, None)
+}
+
+/// `decrypt_password` of a payload read from an image of size `binding`.
+pub fn decrypt_password_bound(encrypted: &[u8], passphrase: &str, binding: Option<ImageBinding>) -> Result<String, StegoError> {
+    let h = Header::read(encrypted).ok_or_else(|| StegoError::Decrypt("Invalid Stegstr encrypted payload".to_string()))?;
+    let (version, header) = (h.version, h.len);
+    if encrypted.len() < header + PARAMS_LEN + SALT_LEN + IV_LEN + TAG_LEN {
+        return Err(StegoError::Decrypt("Payload too short".to_string()));
+    }
+    if version != VERSION_PASSWORD {
+        return Err(StegoError::Decrypt("Payload is not passphrase-protected".to_string()));
+    }
+    let param = |i: usize| u32::from_be_bytes(encrypted[header + i * 4..header + i * 4 + 4].try_into().expect("4 bytes"));
+    let (m_cost, t_cost, p_cost) = (param(0), param(1), param(2));
+    if m_cost > MAX_M_COST {
+        return Err(StegoError::Decrypt(format!("Argon2 memory cost too large: {} KiB", m_cost)));
+    }
+    let params = argon2::Params::new(m_cost, t_cost, p_cost, Some(32)).map_err(|e| StegoError::Decrypt(e.to_string()))?;
+    let salt_start = header + PARAMS_LEN;
+    let iv_start = salt_start + SALT_LEN;
+    let aad = h.aad(&encrypted[..iv_start], binding)?;
+    let key = password_key(passphrase, &encrypted[salt_start..iv_start], params)?;
+    let dec = h
+        .cipher
+        .decrypt(&*key, &encrypted[iv_start..iv_start + IV_LEN], &encrypted[iv_start + IV_LEN..], &aad)
+        .ok_or_else(|| StegoError::Decrypt(h.tag_error("Wrong passphrase or corrupted payload")))?;
+    crate::bundle::unpack(dec).map_err(|e| StegoError::Decrypt(e.to_string()))
+}
+
+/// True if the payload was encrypted with `encrypt_for_pubkey`.
+pub fn is_pubkey_payload(bytes: &[u8]) -> bool {
+    Header::read(bytes).is_some_and(|h| h.version == VERSION_PUBKEY)
+}
+
+fn pubkey_key(sk: &secp256k1::SecretKey, pubkey_hex: &str) -> Result<Zeroizing<[u8; 32]>, StegoError> {
+    let shared = nip04_shared_key(sk, pubkey_hex)?;
+    let mut hasher = Sha256::new();
+    hasher.update(PUBKEY_KEY_SALT);
+    hasher.update(*shared);
+    Ok(Zeroizing::new(hasher.finalize().into()))
+}
+
+/// Encrypt plaintext so only the holder of `recipient_pubkey` (npub or x-only hex) can decrypt.
+/// Returns binary: magic + version 3 + ephemeral pubkey + iv + ciphertext, with the bytes before
+/// the iv authenticated so neither the version byte nor the ephemeral key can be swapped.
+pub fn encrypt_for_pubkey(plaintext: &str, recipient_pubkey: &str) -> Result<Vec<u8>, StegoError> {
+    encrypt_for_pubkey_with(plaintext, recipient_pubkey, &EncryptOptions::default())
+}
+
+/// `encrypt_for_pubkey` with another cipher or an image binding.
+pub fn encrypt_for_pubkey_with(plaintext: &str, recipient_pubkey: &str, opts: &EncryptOptions) -> Result<Vec<u8>, StegoError> {
+    let recipient = crate::nostr_codes::parse_pubkey(recipient_pubkey)?;
+    let ephemeral = secp256k1::SecretKey::new(&mut rand::thread_rng());
+    let key = pubkey_key(&ephemeral, &recipient)?;
+    let ephemeral_pubkey = hex::decode(crate::nostr_event::pubkey_hex(&ephemeral)).map_err(|e| e.to_string())?;
+    seal(VERSION_PUBKEY, opts, &ephemeral_pubkey, &key, plaintext, true)
+}
+
+/// Decrypt a payload from `encrypt_for_pubkey` with the recipient's secret key.
+pub fn decrypt_with_privkey(encrypted: &[u8], sk: &secp256k1::SecretKey) -> Result<String, StegoError> {
+    decrypt_with_privkey_bound(encrypted, sk, None)
+}
+
+/// `decrypt_with_privkey` of a payload read from an image of size `binding`.
+pub fn decrypt_with_privkey_bound(encrypted: &[u8], sk: &secp256k1::SecretKey, binding: Option<ImageBinding>) -> Result<String, StegoError> {
+    let h = Header::read(encrypted).ok_or_else(|| StegoError::Decrypt("Invalid Stegstr encrypted payload".to_string()))?;
+    let (version, header) = (h.version, h.len);
+    if encrypted.len() < header + PUBKEY_LEN + IV_LEN + TAG_LEN {
+        return Err(StegoError::Decrypt("Payload too short".to_string()));
+    }
+    if version != VERSION_PUBKEY {
+        return Err(StegoError::Decrypt("Payload is not encrypted for a public key".to_string()));
+    }
+    let iv_start = header + PUBKEY_LEN;
+    let aad = h.aad(&encrypted[..iv_start], binding)?;
+    let key = pubkey_key(sk, &hex::encode(&encrypted[header..iv_start]))?;
+    let dec = h
+        .cipher
+        .decrypt(&*key, &encrypted[iv_start..iv_start + IV_LEN], &encrypted[iv_start + IV_LEN..], &aad)
+        .ok_or_else(|| StegoError::Decrypt(h.tag_error("Payload is not encrypted for this key")))?;
+    crate::bundle::unpack(dec).map_err(|e| StegoError::Decrypt(e.to_string()))
+}
+
+fn b64() -> base64::engine::GeneralPurpose {
+    base64::engine::general_purpose::STANDARD
+}
+
+/// NIP-04 shared key: x coordinate of the ECDH point (not hashed). Pubkey is x-only hex, lifted with even y.
+fn nip04_shared_key(sk: &secp256k1::SecretKey, pubkey_hex: &str) -> Result<Zeroizing<[u8; 32]>, StegoError> {
+    let mut compressed = [0u8; 33];
+    compressed[0] = 0x02;
+    let pk_bytes = hex::decode(pubkey_hex).map_err(|e| StegoError::InvalidInput(e.to_string()))?;
+    if pk_bytes.len() != 32 {
+        return Err(StegoError::InvalidInput("Public key must be 32 bytes".to_string()));
+    }
+    compressed[1..].copy_from_slice(&pk_bytes);
+    let pk = secp256k1::PublicKey::from_slice(&compressed).map_err(|e| StegoError::InvalidInput(e.to_string()))?;
+    let point = Zeroizing::new(secp256k1::ecdh::shared_secret_point(&pk, sk));
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&point[..32]);
+    Ok(key)
+}
+
+/// NIP-04 encrypt: AES-256-CBC, output `base64(ct)?iv=base64(iv)`.
+pub fn nip04_encrypt(plaintext: &str, sk: &secp256k1::SecretKey, their_pubkey_hex: &str) -> Result<String, StegoError> {
+    use cbc::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+    let key = nip04_shared_key(sk, their_pubkey_hex)?;
+    let mut iv = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut iv);
+    let ct = cbc::Encryptor::<aes::Aes256>::new(&(*key).into(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes());
+    Ok(format!("{}?iv={}", b64().encode(ct), b64().encode(iv)))
+}
+
+/// NIP-04 decrypt of `base64(ct)?iv=base64(iv)`.
+pub fn nip04_decrypt(content: &str, sk: &secp256k1::SecretKey, their_pubkey_hex: &str) -> Result<String, StegoError> {
+    use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+    let (ct_b64, iv_b64) = content.split_once("?iv=").ok_or_else(|| StegoError::Decrypt("Invalid NIP-04 format".to_string()))?;
+    let ct = b64().decode(ct_b64).map_err(|e| StegoError::Decrypt(e.to_string()))?;
+    let iv: [u8; 16] = b64()
+        .decode(iv_b64)
+        .map_err(|e| StegoError::Decrypt(e.to_string()))?
+        .try_into()
+        .map_err(|_| StegoError::Decrypt("Invalid NIP-04 iv".to_string()))?;
+    let key = nip04_shared_key(sk, their_pubkey_hex)?;
+    let dec = cbc::Decryptor::<aes::Aes256>::new(&(*key).into(), &iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(&ct)
+        .map_err(|e| StegoError::Decrypt(e.to_string()))?;
+    String::from_utf8(dec).map_err(|e| StegoError::Decrypt(e.to_string()))
+}
+
+/// HMAC-SHA256 over the concatenated `parts`. Keys here are at most 32 bytes, so they are only padded.
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    block[..key.len()].copy_from_slice(key);
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    for part in parts {
+        inner.update(part);
+    }
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+fn chacha_quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(16);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(12);
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(8);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(7);
+}
+
+/// ChaCha20 (RFC 8439, 96-bit nonce) keystream XORed into `data`, block counter from 0.
+fn chacha20_xor(key: &[u8; 32], nonce: &[u8; 12], data: &mut [u8]) {
+    let word = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+    for i in 0..8 {
+        state[4 + i] = word(&key[4 * i..]);
+    }
+    for i in 0..3 {
+        state[13 + i] = word(&nonce[4 * i..]);
+    }
+    for (counter, chunk) in data.chunks_mut(64).enumerate() {
+        state[12] = counter as u32;
+        let mut x = state;
+        for _ in 0..10 {
+            chacha_quarter_round(&mut x, 0, 4, 8, 12);
+            chacha_quarter_round(&mut x, 1, 5, 9, 13);
+            chacha_quarter_round(&mut x, 2, 6, 10, 14);
+            chacha_quarter_round(&mut x, 3, 7, 11, 15);
+            chacha_quarter_round(&mut x, 0, 5, 10, 15);
+            chacha_quarter_round(&mut x, 1, 6, 11, 12);
+            chacha_quarter_round(&mut x, 2, 7, 8, 13);
+            chacha_quarter_round(&mut x, 3, 4, 9, 14);
+        }
+        for (i, b) in chunk.iter_mut().enumerate() {
+            *b ^= x[i / 4].wrapping_add(state[i / 4]).to_le_bytes()[i % 4];
+        }
+    }
+}
+
+/// NIP-44 conversation key: HKDF-extract(salt "nip44-v2", ECDH shared x). Both sides derive the
+/// same key from their secret key and the other's pubkey.
+pub fn nip44_conversation_key(sk: &secp256k1::SecretKey, their_pubkey_hex: &str) -> Result<[u8; 32], StegoError> {
+    Ok(hmac_sha256(NIP44_SALT, &[&*nip04_shared_key(sk, their_pubkey_hex)?]))
+}
+
+/// ChaCha20 key, ChaCha20 nonce and HMAC key: HKDF-expand(conversation key, nonce, 76 bytes).
+fn nip44_message_keys(conversation_key: &[u8; 32], nonce: &[u8; 32]) -> ([u8; 32], [u8; 12], [u8; 32]) {
+    let t1 = hmac_sha256(conversation_key, &[nonce, &[1]]);
+    let t2 = hmac_sha256(conversation_key, &[&t1, nonce, &[2]]);
+    let t3 = hmac_sha256(conversation_key, &[&t2, nonce, &[3]]);
+    let mut chacha_nonce = [0u8; 12];
+    let mut hmac_key = [0u8; 32];
+    chacha_nonce.copy_from_slice(&t2[..12]);
+    hmac_key[..20].copy_from_slice(&t2[12..]);
+    hmac_key[20..].copy_from_slice(&t3[..12]);
+    (t1, chacha_nonce, hmac_key)
+}
+
+/// Padded plaintext length: 32 bytes minimum, then chunks of 1/8 of the next power of two.
+fn nip44_padded_len(len: usize) -> usize {
+    if len <= 32 {
+        return 32;
+    }
+    let next_power = 1usize << (usize::BITS - (len - 1).leading_zeros());
+    let chunk = if next_power <= 256 { 32 } else { next_power / 8 };
+    chunk * ((len - 1) / chunk + 1)
+}
+
+/// NIP-44 v2 encrypt: `base64(2 + nonce + ChaCha20(u16 length + padded plaintext) + mac)`.
+pub fn nip44_encrypt(plaintext: &str, sk: &secp256k1::SecretKey, their_pubkey_hex: &str) -> Result<String, StegoError> {
+    let mut nonce = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    nip44_encrypt_with(plaintext, &nip44_conversation_key(sk, their_pubkey_hex)?, &nonce)
+}
+
+fn nip44_encrypt_with(plaintext: &str, conversation_key: &[u8; 32], nonce: &[u8; 32]) -> Result<String, StegoError> {
+    let len = plaintext.len();
+    if len == 0 || len > NIP44_MAX_PLAINTEXT {
+        return Err(StegoError::InvalidInput(format!("NIP-44 plaintext must be 1 to {} bytes", NIP44_MAX_PLAINTEXT)));
+    }
+    let (key, chacha_nonce, hmac_key) = nip44_message_keys(conversation_key, nonce);
+    let mut padded = vec![0u8; 2 + nip44_padded_len(len)];
+    padded[..2].copy_from_slice(&(len as u16).to_be_bytes());
+    padded[2..2 + len].copy_from_slice(plaintext.as_bytes());
+    chacha20_xor(&key, &chacha_nonce, &mut padded);
+    let mac = hmac_sha256(&hmac_key, &[nonce, &padded]);
+    let mut out = Vec::with_capacity(1 + 32 + padded.len() + 32);
+    out.push(NIP44_VERSION);
+    out.extend_from_slice(nonce);
+    out.extend_from_slice(&padded);
+    out.extend_from_slice(&mac);
+    Ok(b64().encode(out))
+}
+
+/// NIP-44 v2 decrypt; fails on any version other than 2, a bad MAC or bad padding.
+pub fn nip44_decrypt(payload: &str, sk: &secp256k1::SecretKey, their_pubkey_hex: &str) -> Result<String, StegoError> {
+    nip44_decrypt_with(payload, &nip44_conversation_key(sk, their_pubkey_hex)?)
+}
+
+fn nip44_decrypt_with(payload: &str, conversation_key: &[u8; 32]) -> Result<String, StegoError> {
+    let err = |m: &str| StegoError::Decrypt(format!("Invalid NIP-44 payload: {}", m));
+    if payload.starts_with('#') {
+        return Err(err("unsupported version"));
+    }
+    if !(132..=87472).contains(&payload.len()) {
+        return Err(err("bad length"));
+    }
+    let data = b64().decode(payload).map_err(|e| err(&e.to_string()))?;
+    if !(99..=65603).contains(&data.len()) {
+        return Err(err("bad length"));
+    }
+    if data[0] != NIP44_VERSION {
+        return Err(err("unsupported version"));
+    }
+    let nonce: [u8; 32] = data[1..33].try_into().map_err(|_| err("bad nonce"))?;
+    let (ciphertext, mac) = data[33..].split_at(data.len() - 33 - 32);
+    let (key, chacha_nonce, hmac_key) = nip44_message_keys(conversation_key, &nonce);
+    let expected = hmac_sha256(&hmac_key, &[&nonce, ciphertext]);
+    if expected.iter().zip(mac).fold(0u8, |acc, (a, b)| acc | (a ^ b)) != 0 {
+        return Err(err("MAC mismatch"));
+    }
+    let mut padded = ciphertext.to_vec();
+    chacha20_xor(&key, &chacha_nonce, &mut padded);
+    let len = u16::from_be_bytes([padded[0], padded[1]]) as usize;
+    if len == 0 || padded.len() != 2 + nip44_padded_len(len) {
+        return Err(err("bad padding"));
+    }
+    String::from_utf8(padded[2..2 + len].to_vec()).map_err(|e| StegoError::Decrypt(e.to_string()))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecipientEntry {
+    p: String,
+    k: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecipientsEnvelope {
+    t: String,
+    s: String,
+    r: Vec<RecipientEntry>,
+    c: String,
+}
+
+/// Encrypt for selected pubkeys only (x-only hex). Include the sender to be able to open it later.
+/// `bundle` is the form of a bundle plaintext, as for EncryptOptions.
+pub fn encrypt_for_recipients(
+    plaintext: &str,
+    sender_sk: &secp256k1::SecretKey,
+    recipient_pubkeys: &[String],
+    bundle: BundleFormat,
+) -> Result<Vec<u8>, StegoError> {
+    let mut sym_key = Zeroizing::new([0u8; 32]);
+    rand::thread_rng().fill_bytes(&mut *sym_key);
+    let mut iv = [0u8; IV_LEN];
+    rand::thread_rng().fill_bytes(&mut iv);
+    let cipher = Aes256Gcm::new_from_slice(&*sym_key).map_err(|e| e.to_string())?;
+    let ciphertext = cipher
+        .encrypt(GenericArray::from_slice(&iv), crate::bundle::pack(plaintext.as_bytes(), bundle).as_slice())
+        .map_err(|e| e.to_string())?;
+    let mut ct_with_iv = Vec::with_capacity(IV_LEN + ciphertext.len());
+    ct_with_iv.extend_from_slice(&iv);
+    ct_with_iv.extend_from_slice(&ciphertext);
+
+    let sym_key_hex = Zeroizing::new(hex::encode(*sym_key));
+    let mut r = Vec::with_capacity(recipient_pubkeys.len());
+    for pk in recipient_pubkeys {
+        r.push(RecipientEntry {
+            p: pk.to_lowercase(),
+            k: nip04_encrypt(&sym_key_hex, sender_sk, pk)?,
+        });
+    }
+    let envelope = RecipientsEnvelope {
+        t: "r".to_string(),
+        s: crate::nostr_event::pubkey_hex(sender_sk),
+        r,
+        c: b64().encode(ct_with_iv),
+    };
+    let json = serde_json::to_string(&envelope).map_err(|e| e.to_string())?;
+    encrypt_app(&json)
+}
+
+/// Decrypt an embedded payload: app layer, then the recipients envelope if present (requires our key).
+/// Public-key payloads are decrypted with our key directly.
+pub fn decrypt_payload(encrypted: &[u8], our_sk: Option<&secp256k1::SecretKey>) -> Result<String, StegoError> {
+    decrypt_payload_bound(encrypted, our_sk, None)
+}
+
+/// `decrypt_payload` of a payload read from an image of size `binding`.
+pub fn decrypt_payload_bound(encrypted: &[u8], our_sk: Option<&secp256k1::SecretKey>, binding: Option<ImageBinding>) -> Result<String, StegoError> {
+    if let (true, Some(sk)) = (is_pubkey_payload(encrypted), our_sk) {
+        return decrypt_with_privkey_bound(encrypted, sk, binding);
+    }
+    let inner = decrypt_app_bound(encrypted, binding)?;
+    let envelope: RecipientsEnvelope = match serde_json::from_str(&inner) {
+        Ok(env) => env,
+        Err(_) => return Ok(inner),
+    };
+    if envelope.t != "r" {
+        return Ok(inner);
+    }
+    let sk = our_sk.ok_or_else(|| StegoError::KeyRequired("Payload is encrypted for specific recipients; a private key is required".to_string()))?;
+    let our_pubkey = crate::nostr_event::pubkey_hex(sk);
+    let entry = envelope
+        .r
+        .iter()
+        .find(|e| e.p.eq_ignore_ascii_case(&our_pubkey))
+        .ok_or_else(|| StegoError::Decrypt("You are not a recipient of this stego image".to_string()))?;
+    let sym_key_hex = Zeroizing::new(nip04_decrypt(&entry.k, sk, &envelope.s)?);
+    let sym_key = Zeroizing::new(hex::decode(sym_key_hex.trim()).map_err(|e| StegoError::Decrypt(e.to_string()))?);
+    let ct_with_iv = b64().decode(&envelope.c).map_err(|e| StegoError::Decrypt(e.to_string()))?;
+    if ct_with_iv.len() < IV_LEN + TAG_LEN {
+        return Err(StegoError::Decrypt("Recipient ciphertext too short".to_string()));
+    }
+    let cipher = Aes256Gcm::new_from_slice(&sym_key).map_err(|e| e.to_string())?;
+    let dec = cipher
+        .decrypt(GenericArray::from_slice(&ct_with_iv[..IV_LEN]), &ct_with_iv[IV_LEN..])
+        .map_err(|e| StegoError::Decrypt(e.to_string()))?;
+    crate::bundle::unpack(dec).map_err(|e| StegoError::Decrypt(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recipients_roundtrip() {
+        let sender = secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let recipient = secp256k1::SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let outsider = secp256k1::SecretKey::from_slice(&[0x33; 32]).unwrap();
+        let recipients = vec![crate::nostr_event::pubkey_hex(&recipient)];
+        let enc = encrypt_for_recipients("{\"version\":1}", &sender, &recipients, BundleFormat::V1).unwrap();
+        assert!(is_encrypted_payload(&enc));
+        assert_eq!(decrypt_payload(&enc, Some(&recipient)).unwrap(), "{\"version\":1}");
+        assert!(decrypt_payload(&enc, Some(&outsider)).is_err());
+        assert!(decrypt_payload(&enc, None).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_bundles_encrypt_as_v1_unless_v2_is_asked_for() {
+        let sk = secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let note = crate::nostr_event::sign_event_at(1, vec![], &"a note that compresses well. ".repeat(20), 100, &sk).unwrap();
+        let json = serde_json::to_string(&crate::nostr_event::Bundle::new(vec![note])).unwrap();
+        let v2 = EncryptOptions { bundle: BundleFormat::V2, ..Default::default() };
+        // Same layout either way, so the ciphertext length shows which form was sealed.
+        let app_v1 = encrypt_app_with(&json, &EncryptOptions::default()).unwrap();
+        assert_eq!(app_v1.len(), encrypt_app(&json).unwrap().len());
+        let app_v2 = encrypt_app_with(&json, &v2).unwrap();
+        assert!(app_v2.len() < app_v1.len() / 2);
+        assert_eq!(app_v1.len() - app_v2.len(), json.len() - crate::bundle::pack(json.as_bytes(), BundleFormat::V2).len());
+        assert_eq!((decrypt_app(&app_v1).unwrap(), decrypt_app(&app_v2).unwrap()), (json.clone(), json.clone()));
+
+        let recipients = vec![crate::nostr_event::pubkey_hex(&sk)];
+        let rec_v1 = encrypt_for_recipients(&json, &sk, &recipients, BundleFormat::V1).unwrap();
+        let rec_v2 = encrypt_for_recipients(&json, &sk, &recipients, BundleFormat::V2).unwrap();
+        assert!(rec_v2.len() < rec_v1.len());
+        assert_eq!(decrypt_payload(&rec_v2, Some(&sk)).unwrap(), json);
+    }
+
+    #[test]
+    fn test_nip44_vector_and_roundtrip() {
+        // NIP-44 v2 test vector: sec1 = 1, sec2 = 2, nonce = 1.
+        let sec1 = secp256k1::SecretKey::from_slice(&[[0u8; 31].as_slice(), &[1]].concat()).unwrap();
+        let sec2 = secp256k1::SecretKey::from_slice(&[[0u8; 31].as_slice(), &[2]].concat()).unwrap();
+        let (pub1, pub2) = (crate::nostr_event::pubkey_hex(&sec1), crate::nostr_event::pubkey_hex(&sec2));
+        let key = nip44_conversation_key(&sec1, &pub2).unwrap();
+        assert_eq!(hex::encode(key), "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d");
+        assert_eq!(nip44_conversation_key(&sec2, &pub1).unwrap(), key);
+        let mut nonce = [0u8; 32];
+        nonce[31] = 1;
+        let payload = nip44_encrypt_with("a", &key, &nonce).unwrap();
+        assert_eq!(
+            payload,
+            "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABee0G5VSK0/9YypIObAtDKfYEAjD35uVkHyB0F4DwrcNaCXlCWZKaArsGrY6M9wnuTMxWfp1RTN9Xga8no+kF5Vsb"
+        );
+        assert_eq!(nip44_decrypt(&payload, &sec2, &pub1).unwrap(), "a");
+
+        let long = "\u{1F600} long message ".repeat(40);
+        let enc = nip44_encrypt(&long, &sec2, &pub1).unwrap();
+        assert_eq!(nip44_decrypt(&enc, &sec1, &pub2).unwrap(), long);
+        let outsider = secp256k1::SecretKey::from_slice(&[0x33; 32]).unwrap();
+        assert!(nip44_decrypt(&enc, &outsider, &pub2).is_err());
+        assert_eq!(nip44_padded_len(33), 64);
+        assert_eq!(nip44_padded_len(257), 320);
+    }
+
+    #[test]
+    fn test_password_roundtrip() {
+        let enc = encrypt_password("{\"version\":1}", "correct horse").unwrap();
+        assert!(is_password_payload(&enc));
+        assert_eq!(decrypt_password(&enc, "correct horse").unwrap(), "{\"version\":1}");
+        assert!(matches!(decrypt_password(&enc, "wrong horse"), Err(StegoError::Decrypt(_))));
+        assert!(matches!(decrypt_app(&enc), Err(StegoError::KeyRequired(m)) if m.contains("passphrase")));
+        assert!(!is_password_payload(&encrypt_app("x").unwrap()));
+    }
+
+    #[test]
+    fn test_pubkey_roundtrip() {
+        let recipient = secp256k1::SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let outsider = secp256k1::SecretKey::from_slice(&[0x33; 32]).unwrap();
+        let pubkey = hex::decode(crate::nostr_event::pubkey_hex(&recipient)).unwrap();
+        let npub = crate::nostr_codes::npub_encode(&pubkey.try_into().unwrap()).unwrap();
+        let enc = encrypt_for_pubkey("secret", &npub).unwrap();
+        assert!(is_pubkey_payload(&enc));
+        assert_eq!(decrypt_with_privkey(&enc, &recipient).unwrap(), "secret");
+        assert_eq!(decrypt_payload(&enc, Some(&recipient)).unwrap(), "secret");
+        assert!(decrypt_with_privkey(&enc, &outsider).is_err());
+        assert!(decrypt_payload(&enc, None).is_err());
+
+        // The version byte and ephemeral key are authenticated: altering either fails the tag.
+        assert_eq!(enc[8], VERSION_PUBKEY | HEADER_AAD_FLAG);
+        for i in [8, 9, 40] {
+            let mut tampered = enc.clone();
+            tampered[i] ^= 0x01;
+            assert!(decrypt_with_privkey(&tampered, &recipient).is_err(), "byte {}", i);
+        }
+        // Payloads from before the flag still decrypt.
+        let ephemeral = secp256k1::SecretKey::from_slice(&[0x44; 32]).unwrap();
+        let key = pubkey_key(&ephemeral, &crate::nostr_event::pubkey_hex(&recipient)).unwrap();
+        let fields = hex::decode(crate::nostr_event::pubkey_hex(&ephemeral)).unwrap();
+        let legacy = seal(VERSION_PUBKEY, &EncryptOptions::default(), &fields, &key, "secret", false).unwrap();
+        assert_eq!(legacy[8], VERSION_PUBKEY);
+        assert_eq!(decrypt_with_privkey(&legacy, &recipient).unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_chacha20_poly1305_detected_from_header() {
+        let chacha = Cipher::parse("chacha20").unwrap();
+        let chacha_opts = EncryptOptions { cipher: chacha, ..Default::default() };
+        let app = encrypt_app_with("x", &chacha_opts).unwrap();
+        assert_eq!((app[8], app[9]), (VERSION | CIPHER_FLAG, CIPHER_CHACHA20_POLY1305));
+        assert_eq!(payload_cipher(&app), Some(chacha));
+        assert_eq!(decrypt_app(&app).unwrap(), "x");
+        // AES keeps the unflagged layout.
+        let aes = encrypt_app("x").unwrap();
+        assert_eq!((aes[8], payload_cipher(&aes)), (VERSION, Some(Cipher::Aes256Gcm)));
+
+        let enc = encrypt_password_with("secret", "correct horse", &chacha_opts).unwrap();
+        assert!(is_password_payload(&enc));
+        assert_eq!(decrypt_password(&enc, "correct horse").unwrap(), "secret");
+        assert!(decrypt_password(&enc, "wrong horse").is_err());
+        assert!(matches!(decrypt_app(&enc), Err(StegoError::KeyRequired(_))));
+
+        let recipient = secp256k1::SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let enc = encrypt_for_pubkey_with("secret", &crate::nostr_event::pubkey_hex(&recipient), &chacha_opts).unwrap();
+        assert!(is_pubkey_payload(&enc));
+        assert_eq!(decrypt_payload(&enc, Some(&recipient)).unwrap(), "secret");
+        assert!(Cipher::parse("des").is_err());
+    }
+
+    #[test]
+    fn test_bound_payload_needs_its_image() {
+        let binding = ImageBinding { width: 641, height: 480 };
+        for cipher in [Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305] {
+            let opts = EncryptOptions { cipher, binding: Some(binding), ..Default::default() };
+            let enc = encrypt_password_with("secret", "pw", &opts).unwrap();
+            assert!(is_bound_payload(&enc) && is_password_payload(&enc));
+            assert_eq!(payload_cipher(&enc), Some(cipher));
+            // Rotated, or with the odd column the DWT codec crops, is the same image.
+            for same in [binding, ImageBinding { width: 480, height: 640 }] {
+                assert_eq!(decrypt_password_bound(&enc, "pw", Some(same)).unwrap(), "secret");
+            }
+            let other = decrypt_password_bound(&enc, "pw", Some(ImageBinding { width: 640, height: 482 }));
+            assert!(matches!(other, Err(StegoError::Decrypt(m)) if m.contains("bound")));
+            assert!(matches!(decrypt_password(&enc, "pw"), Err(StegoError::KeyRequired(_))));
+        }
+        let app = encrypt_app_with("x", &EncryptOptions { binding: Some(binding), ..Default::default() }).unwrap();
+        assert_eq!(decrypt_payload_bound(&app, None, Some(binding)).unwrap(), "x");
+        assert!(decrypt_app(&app).is_err());
+        assert!(!is_bound_payload(&encrypt_app("x").unwrap()));
+    }
+}
@@ -0,0 +1,1241 @@
+// Dot-offset steganography (robust to platform transforms).
+// Each 2x2 cell on a 6 px grid carries one 2-bit symbol (the position of its dark pixel).
+// Frame version 2 (parity > 0, default): RS(8) of [MAGIC, version, parity, 4-byte length], then
+// the payload in Reed-Solomon blocks with `parity` bytes each, one cell per symbol.
+// Legacy frame (parity 0): 2-byte codeword length + MAGIC + 4-byte length + payload, each symbol
+// written to REPEAT cells and majority-voted. The decoder tries version 2, then legacy.
+// With a stego key the cells are visited in a keyed order instead of the spread/shuffled ones.
+// Payloads are zstd-compressed when that makes them smaller (payload_header::compress).
+// With EncodeOptions::fiducials the image also gets corner markers and a size strip (fiducial.rs)
+// and no cell is written under them. An image that does not decode as stored is taken for a
+// camera photo: if the markers are found it is perspective-corrected back to its original size
+// and decoded again. EncodeOptions::calibration frames the cover in a quiet-zone border with the
+// markers and timing patterns instead (fiducial::calibrate), leaving the cover's pixels to the
+// dot grid; the decoder finds the cover inside the frame as stored, or after straightening a
+// screenshot or photo of it.
+// Resized images: the grid pitch is estimated per axis from the image itself (`estimate_grid`):
+// each column's (row's) contrast profile, the sum of |second difference| of luma, peaks on the
+// cell columns; the local maxima of its autocorrelation are candidate pitches, a periodogram
+// around each picks the strongest frequency, and the profile's phase there gives the grid's
+// offset. The image is then resampled (bilinear) to the estimated original size with the cells
+// back on the STEP/OFFSET grid and decoded again, trying the nearby sizes that change the cell
+// count.
+// EncodeOptions::rendering: cells are stamped white with a black symbol pixel by default. The
+// low-visibility renderings instead flatten the cell's pixels to their mean channel sum and move
+// the symbol pixel below it and the other three above, by a gap set by the contrast, so the
+// symbol pixel is still the darkest one the decoder looks for.
+// Transparency: the codec works on the colour channels and writes the cover's alpha channel back
+// unchanged. Cells touching a fully transparent pixel are skipped by encoder and decoder alike
+// (their colour is neither shown nor reliably kept by editors), so stickers and logos keep their
+// shape; fiducial markers are made opaque, and a calibration border is opaque around the cover.
+// EncodeOptions::layout picks the grid step, cell offset and cells per payload symbol. The default
+// (STEP, OFFSET, one cell) writes the version 2 frame; any other layout writes version 3, whose
+// header (one cell per symbol) also records step, offset and repeat. The decoder, failing the
+// default grid, takes the step from the pitch `estimate_grid` measures, tries the offsets at that
+// step nearest the measured phase first, and accepts a header recording the grid it was read on.
+// The cover's ICC profile, EXIF and PNG text chunks are carried into the output (output::Metadata)
+// unless EncodeOptions::strip_metadata is set.
+// Animated covers (APNG, GIF) are embedded frame by frame and decoded from every frame
+// (stego_anim); EncodeOptions::spread spreads the payload over the frames.
+
+use image::metadata::Orientation;
+use image::{ExtendedColorType, GrayImage, ImageDecoder, ImageReader, RgbImage};
+use std::io::Cursor;
+
+use crate::error::StegoError;
+use crate::mask::{Mask, MaskSpec};
+use crate::output::{Metadata, OutputFormat};
+use crate::payload_header;
+use crate::stego_key::StegoKey;
+
+const MAGIC: &[u8] = b"STEGSTR";
+const MAGIC_LEN: usize = 7;
+const LENGTH_BYTES: usize = 4;
+
+const STEP: u32 = 6;
+const OFFSET: u32 = 2;
+const REPEAT: usize = 3;
+const SHUFFLE_SEED: u32 = 42;
+/// Pitches `estimate_grid` considers (the image scaled by about 0.67x to 2.5x, or a layout's step).
+const MIN_PITCH: usize = MIN_STEP as usize;
+const MAX_PITCH: usize = MAX_STEP as usize;
+/// Grid steps a `Layout` may use.
+pub const MIN_STEP: u32 = 4;
+pub const MAX_STEP: u32 = 15;
+/// Most cells per payload symbol a `Layout` may use.
+pub const MAX_REPEAT: u8 = 7;
+
+const FEC_VERSION: u8 = 2;
+/// Parity bytes protecting the version 2 header.
+const HEADER_NSYM: usize = 8;
+/// MAGIC, version, parity, 4-byte length.
+const FEC_HEADER_DATA: usize = MAGIC_LEN + 2 + LENGTH_BYTES;
+const FEC_HEADER_LEN: usize = FEC_HEADER_DATA + HEADER_NSYM;
+const LAYOUT_VERSION: u8 = 3;
+/// MAGIC, version, parity, step, offset, repeat, 4-byte length.
+const LAYOUT_HEADER_DATA: usize = MAGIC_LEN + 5 + LENGTH_BYTES;
+const LAYOUT_HEADER_LEN: usize = LAYOUT_HEADER_DATA + HEADER_NSYM;
+/// Largest parity per 255-byte block (leaves at least half of each block for data).
+pub const MAX_PARITY: u8 = 128;
+/// Parity when STEGSTR_DOT_PARITY is unset: 16 correctable bytes per block.
+pub const DEFAULT_PARITY: u8 = 32;
+/// Reed-Solomon parity bytes per block for `encode`; 0 writes the legacy repetition frame.
+pub const PARITY_ENV: &str = "STEGSTR_DOT_PARITY";
+/// Contrast for the low-visibility renderings when none is given.
+pub const DEFAULT_CONTRAST: u8 = 12;
+/// Smallest contrast that keeps the symbol pixel strictly darkest after rounding.
+pub const MIN_CONTRAST: u8 = 2;
+
+pub fn parity() -> u8 {
+    std::env::var(PARITY_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse::<u8>().ok())
+        .map_or(DEFAULT_PARITY, |p| p.min(MAX_PARITY))
+}
+
+/// How a cell is drawn into the cover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rendering {
+    /// White 2x2 cell with a black symbol pixel: survives the most, plainly visible on photos.
+    #[default]
+    Stamp,
+    /// The cell's pixels modulated in luminance (all channels alike): the symbol pixel `contrast`
+    /// levels per channel below the other three.
+    Luma { contrast: u8 },
+    /// Like `Luma`, moving the blue channel only (red and green where blue runs out of range),
+    /// which the eye notices least.
+    Blue { contrast: u8 },
+}
+
+impl Rendering {
+    /// Draw the cell at (x, y) with its symbol pixel at offset `dark` in the 2x2 block.
+    fn draw(self, img: &mut RgbImage, x: u32, y: u32, dark: (u32, u32)) {
+        let (contrast, channels) = match self {
+            Rendering::Stamp => {
+                for (ox, oy) in CELL_OFFSETS {
+                    img.put_pixel(x + ox, y + oy, image::Rgb([255, 255, 255]));
+                }
+                img.put_pixel(x + dark.0, y + dark.1, image::Rgb([0, 0, 0]));
+                return;
+            }
+            Rendering::Luma { contrast } => (contrast, [0, 1, 2]),
+            Rendering::Blue { contrast } => (contrast, [2, 0, 1]),
+        };
+        let sum = |p: &image::Rgb<u8>| p.0.iter().map(|&c| c as i32).sum::<i32>();
+        let mean = CELL_OFFSETS.iter().map(|&(ox, oy)| sum(img.get_pixel(x + ox, y + oy))).sum::<i32>() / 4;
+        // Gap of 3 * contrast between the symbol pixel and the rest, keeping the cell's mean.
+        let gap = 3 * contrast as i32;
+        for (ox, oy) in CELL_OFFSETS {
+            let target = if (ox, oy) == dark { mean - gap * 3 / 4 } else { mean + gap / 4 }.clamp(0, 765);
+            let p = img.get_pixel_mut(x + ox, y + oy);
+            let mut delta = target - sum(p);
+            if matches!(self, Rendering::Luma { .. }) {
+                let share = delta / 3;
+                for c in 0..3 {
+                    let v = p[c] as i32;
+                    let moved = (v + share).clamp(0, 255);
+                    p[c] = moved as u8;
+                    delta -= moved - v;
+                }
+            }
+            for c in channels {
+                let v = p[c] as i32;
+                let moved = (v + delta).clamp(0, 255);
+                p[c] = moved as u8;
+                delta -= moved - v;
+            }
+        }
+    }
+
+    fn contrast(self) -> Option<u8> {
+        match self {
+            Rendering::Stamp => None,
+            Rendering::Luma { contrast } | Rendering::Blue { contrast } => Some(contrast),
+        }
+    }
+}
+
+/// Pixels of a cell, in symbol order: symbol i puts its dark pixel at CELL_OFFSETS[i].
+const CELL_OFFSETS: [(u32, u32); 4] = [(0, 0), (0, 1), (1, 0), (1, 1)];
+
+/// Dot grid and repetition: capacity against robustness. The default writes the version 2 frame
+/// older builds read; any other layout writes version 3, which records it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// Grid pitch in px, MIN_STEP to MAX_STEP: smaller fits more cells, larger disturbs less.
+    pub step: u32,
+    /// Position of the first cell on each axis; at most `step - 2`, so cells do not touch.
+    pub offset: u32,
+    /// Cells per payload symbol, 1 to MAX_REPEAT, majority-voted on decode.
+    pub repeat: u8,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout { step: STEP, offset: OFFSET, repeat: 1 }
+    }
+}
+
+impl Layout {
+    fn validate(&self, width: u32, height: u32) -> Result<(), StegoError> {
+        if !(MIN_STEP..=MAX_STEP).contains(&self.step) {
+            return Err(StegoError::InvalidInput(format!("Dot step must be {}-{} px", MIN_STEP, MAX_STEP)));
+        }
+        if self.offset + 2 > self.step {
+            return Err(StegoError::InvalidInput(format!("Dot offset must be at most {} for step {}", self.step - 2, self.step)));
+        }
+        if !(1..=MAX_REPEAT).contains(&self.repeat) {
+            return Err(StegoError::InvalidInput(format!("Dot repeat must be 1-{}", MAX_REPEAT)));
+        }
+        if width < self.offset + 2 || height < self.offset + 2 {
+            return Err(StegoError::Capacity("Image too small for this dot layout".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Layout of a dot embed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// Reed-Solomon parity bytes per 255-byte block; 0 writes the legacy frame (REPEAT x cells).
+    pub parity: u8,
+    /// Seeds the cell order; `decode_with_key` needs the same key.
+    pub key: Option<StegoKey>,
+    pub format: OutputFormat,
+    /// Draw corner markers so a photo of the image can be decoded (covers of at least
+    /// `fiducial::MIN_SIDE` px per side).
+    pub fiducials: bool,
+    /// Frame the image in a calibration border (fiducial::BORDER px per side) with corner and
+    /// timing patterns, so the grid is found again after cropping into the border, scaling or
+    /// screenshotting. The output is larger than the cover; not with `fiducials`.
+    pub calibration: bool,
+    /// How cells are drawn; the low-visibility renderings need a lossless output to decode.
+    pub rendering: Rendering,
+    /// Grid step, offset and repetition; anything but the default needs parity.
+    pub layout: Layout,
+    /// Leave the cover's ICC profile, EXIF and PNG text chunks out of the output.
+    pub strip_metadata: bool,
+    /// Animated covers: fountain-code the payload into one part per frame, any this many of which
+    /// rebuild it; 0 embeds the whole payload in every frame. Still covers take 0.
+    pub spread: u32,
+}
+
+impl Default for EncodeOptions {
+    /// Parity from STEGSTR_DOT_PARITY (default 32), no key, PNG, no fiducials or calibration
+    /// border, stamped cells on the default layout, cover metadata kept, payload in every frame.
+    fn default() -> Self {
+        EncodeOptions {
+            parity: parity(),
+            key: None,
+            format: OutputFormat::Png,
+            fiducials: false,
+            calibration: false,
+            rendering: Rendering::Stamp,
+            layout: Layout::default(),
+            strip_metadata: false,
+            spread: 0,
+        }
+    }
+}
+
+/// Colour of a decoded image, with its alpha channel when any pixel is not fully opaque, and the
+/// metadata an encode carries over.
+struct Cover {
+    rgb: RgbImage,
+    alpha: Option<GrayImage>,
+    metadata: Metadata,
+}
+
+#[cfg(feature = "fs")]
+fn load_image_with_orientation(path: &std::path::Path) -> Result<Cover, StegoError> {
+    load_image_bytes_with_orientation(&std::fs::read(path)?)
+}
+
+/// Like `load_image_with_orientation`, for image file contents (format sniffed from the data).
+fn load_image_bytes_with_orientation(bytes: &[u8]) -> Result<Cover, StegoError> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?.into_decoder()?;
+    let metadata = Metadata::read(&mut decoder, bytes);
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = image::DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(cover_of(img, metadata))
+}
+
+/// `img` as a Cover: alpha is kept only when some pixel is not fully opaque.
+fn cover_of(img: image::DynamicImage, metadata: Metadata) -> Cover {
+    if !img.color().has_alpha() {
+        return Cover { rgb: img.to_rgb8(), alpha: None, metadata };
+    }
+    let rgba = img.to_rgba8();
+    let alpha = GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| image::Luma([rgba.get_pixel(x, y)[3]]));
+    let translucent = alpha.pixels().any(|p| p[0] < 255);
+    Cover { rgb: img.to_rgb8(), alpha: translucent.then_some(alpha), metadata }
+}
+
+fn bytes_to_bits(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() * 8);
+    for b in data {
+        for i in (0..8).rev() {
+            out.push((b >> i) & 1);
+        }
+    }
+    out
+}
+
+fn bits_to_bytes(bits: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bits.len().div_ceil(8));
+    for chunk in bits.chunks(8) {
+        let mut byte = 0u8;
+        for (i, &bit) in chunk.iter().enumerate() {
+            byte |= (bit & 1) << (7 - i);
+        }
+        out.push(byte);
+    }
+    out
+}
+
+/// Legacy frame bytes: 2-byte codeword length, then MAGIC + 4-byte length + payload.
+fn legacy_frame(payload: &[u8]) -> Result<Vec<u8>, StegoError> {
+    let wrapped = wrap_payload(payload);
+    let codeword_len = wrapped.len();
+    if codeword_len > u16::MAX as usize {
+        return Err(StegoError::Capacity("Payload too large for dot method".to_string()));
+    }
+    let mut out = Vec::with_capacity(2 + codeword_len);
+    out.extend_from_slice(&(codeword_len as u16).to_be_bytes());
+    out.extend_from_slice(&wrapped);
+    Ok(out)
+}
+
+/// Version 2 frame bytes: header codeword, then the payload codeword.
+fn fec_frame(payload: &[u8], parity: u8) -> Vec<u8> {
+    let mut header = Vec::with_capacity(FEC_HEADER_DATA);
+    header.extend_from_slice(MAGIC);
+    header.push(FEC_VERSION);
+    header.push(parity);
+    header.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    let mut out = crate::reed_solomon::encode(&header, HEADER_NSYM);
+    out.extend_from_slice(&crate::reed_solomon::encode(payload, parity as usize));
+    out
+}
+
+/// Version 3 frame as symbol bits: header codeword recording `layout`, one cell per symbol, then
+/// the payload codeword with each symbol written to `layout.repeat` cells.
+fn layout_frame_bits(payload: &[u8], parity: u8, layout: &Layout) -> Vec<u8> {
+    let mut header = Vec::with_capacity(LAYOUT_HEADER_DATA);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&[LAYOUT_VERSION, parity, layout.step as u8, layout.offset as u8, layout.repeat]);
+    header.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    let mut bits = bytes_to_bits(&crate::reed_solomon::encode(&header, HEADER_NSYM));
+    for symbol in bytes_to_bits(&crate::reed_solomon::encode(payload, parity as usize)).chunks(2) {
+        for _ in 0..layout.repeat {
+            bits.extend_from_slice(symbol);
+        }
+    }
+    bits
+}
+
+/// Largest payload whose version 2 frame fits `frame_bytes`.
+#[cfg(feature = "fs")]
+fn fec_payload_capacity(frame_bytes: usize, parity: u8) -> usize {
+    let Some(avail) = frame_bytes.checked_sub(FEC_HEADER_LEN) else {
+        return 0;
+    };
+    let block = crate::reed_solomon::BLOCK;
+    let nsym = parity as usize;
+    (avail / block) * (block - nsym) + (avail % block).saturating_sub(nsym)
+}
+
+fn wrap_payload(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(MAGIC_LEN + LENGTH_BYTES + payload.len());
+    out.extend_from_slice(MAGIC);
+    let len = payload.len() as u32;
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+fn unwrap_payload(raw: &[u8]) -> Result<Vec<u8>, StegoError> {
+    if raw.len() < MAGIC_LEN + LENGTH_BYTES {
+        return Err(StegoError::NotStego("Payload too short".to_string()));
+    }
+    if &raw[..MAGIC_LEN] != MAGIC {
+        return Err(StegoError::NotStego("Magic not found".to_string()));
+    }
+    let len = u32::from_be_bytes([
+        raw[MAGIC_LEN],
+        raw[MAGIC_LEN + 1],
+        raw[MAGIC_LEN + 2],
+        raw[MAGIC_LEN + 3],
+    ]) as usize;
+    if raw.len() < MAGIC_LEN + LENGTH_BYTES + len {
+        return Err(StegoError::NotStego("Payload length mismatch".to_string()));
+    }
+    Ok(raw[MAGIC_LEN + LENGTH_BYTES..MAGIC_LEN + LENGTH_BYTES + len].to_vec())
+}
+
+/// `layout`'s cells without those that touch a fully transparent pixel of `alpha`.
+fn visible_cells(width: u32, height: u32, alpha: Option<&GrayImage>, layout: &Layout) -> Vec<(u32, u32)> {
+    let mut cells = cell_grid(width, height, layout.step, layout.offset);
+    if let Some(alpha) = alpha {
+        cells.retain(|&(x, y)| CELL_OFFSETS.iter().all(|&(ox, oy)| alpha.get_pixel(x + ox, y + oy)[0] > 0));
+    }
+    cells
+}
+
+fn cell_grid(width: u32, height: u32, step: u32, offset: u32) -> Vec<(u32, u32)> {
+    let mut out = Vec::new();
+    if width < offset + 2 || height < offset + 2 {
+        return out;
+    }
+    let max_x = width - 2;
+    let max_y = height - 2;
+    let mut y = offset;
+    while y <= max_y {
+        let mut x = offset;
+        while x <= max_x {
+            out.push((x, y));
+            x += step;
+        }
+        y += step;
+    }
+    out
+}
+
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+fn spread_positions(positions: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+    let len = positions.len();
+    if len <= 1 {
+        return positions;
+    }
+    let mut step: usize = 131;
+    while gcd(step, len) != 1 {
+        step += 2;
+    }
+    let mut ordered = vec![(0u32, 0u32); len];
+    let mut idx: usize = 0;
+    for slot in ordered.iter_mut() {
+        *slot = positions[idx];
+        idx = (idx + step) % len;
+    }
+    ordered
+}
+
+fn shuffle_positions(mut positions: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+    if positions.len() <= 1 {
+        return positions;
+    }
+    let mut seed = SHUFFLE_SEED;
+    let mut i = positions.len() - 1;
+    while i > 0 {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let j = (seed as usize) % (i + 1);
+        positions.swap(i, j);
+        i -= 1;
+    }
+    positions
+}
+
+fn keyed_positions(positions: Vec<(u32, u32)>, key: &StegoKey) -> Vec<(u32, u32)> {
+    key.permutation("dot", positions.len()).into_iter().map(|i| positions[i]).collect()
+}
+
+/// Largest payload for a frame with `parity` (0: legacy) on an image with `cells` dot cells.
+#[cfg(feature = "fs")]
+fn payload_capacity(cells: usize, parity: u8) -> usize {
+    match parity {
+        0 => (cells * 2 / REPEAT / 8).saturating_sub(2 + MAGIC_LEN + LENGTH_BYTES),
+        p => fec_payload_capacity(cells * 2 / 8, p),
+    }
+}
+
+/// Cells written per symbol: REPEAT for the legacy frame, one under Reed-Solomon.
+fn repeat_for(parity: u8) -> usize {
+    if parity == 0 {
+        REPEAT
+    } else {
+        1
+    }
+}
+
+/// Write symbols at `positions`, each to `repeat` consecutive cells. Cells touching the mask are
+/// left untouched; the decoder reads whatever is there and the majority vote or Reed-Solomon
+/// code corrects it.
+fn encode_offset_with_positions(
+    img: &mut RgbImage,
+    bits: &[u8],
+    positions: Vec<(u32, u32)>,
+    mask: Option<&Mask>,
+    repeat: usize,
+    rendering: Rendering,
+) -> Result<(), StegoError> {
+    let capacity_bits = (positions.len() * 2) / repeat;
+    if bits.len() > capacity_bits {
+        return Err(StegoError::Capacity(format!(
+            "Image too small: need {} bits, have {}",
+            bits.len(),
+            capacity_bits
+        )));
+    }
+    let symbols: Vec<[u8; 2]> = bits
+        .chunks(2)
+        .map(|c| [*c.first().unwrap_or(&0), *c.get(1).unwrap_or(&0)])
+        .collect();
+    let needed_cells = symbols.len() * repeat;
+    if positions.len() < needed_cells {
+        return Err(StegoError::Capacity(format!(
+            "Image too small: need {} cells, have {}",
+            needed_cells,
+            positions.len()
+        )));
+    }
+    for (si, sym) in symbols.iter().enumerate() {
+        let idx = ((sym[0] & 1) << 1) | (sym[1] & 1);
+        for r in 0..repeat {
+            let (x, y) = positions[si * repeat + r];
+            if mask.is_some_and(|m| m.intersects(x, y, 2, 2)) {
+                continue;
+            }
+            rendering.draw(img, x, y, CELL_OFFSETS[idx as usize]);
+        }
+    }
+    Ok(())
+}
+
+/// Symbol index (0..4) the decoder reads at cell (x, y): the darkest pixel of the 2x2 block.
+fn read_symbol(img: &RgbImage, x: u32, y: u32) -> usize {
+    let mut min_idx = 0usize;
+    let mut min_val: u32 = u32::MAX;
+    for (i, (ox, oy)) in CELL_OFFSETS.iter().enumerate() {
+        let p = img.get_pixel(x + ox, y + oy);
+        let v = p[0] as u32 + p[1] as u32 + p[2] as u32;
+        if v < min_val {
+            min_val = v;
+            min_idx = i;
+        }
+    }
+    min_idx
+}
+
+/// Whether the decoder still reads the frame with masked cells keeping the cover's pixels (they
+/// read as whatever the cover shows there): legacy frames replay the majority vote, version 2
+/// frames count damaged bytes per Reed-Solomon block against what its parity corrects.
+fn survives_mask(img: &RgbImage, positions: &[(u32, u32)], bits: &[u8], mask: &Mask, parity: u8) -> bool {
+    if parity == 0 {
+        return lost_groups(img, positions, bits, mask) == 0;
+    }
+    // Block 0 is the header codeword, then the payload codeword's 255-byte blocks.
+    let mut damaged = vec![0usize; 1 + bits.len().div_ceil(8 * crate::reed_solomon::BLOCK)];
+    let mut last_byte = None;
+    for (si, (sym, &(x, y))) in bits.chunks(2).zip(positions).enumerate() {
+        let want = ((sym[0] & 1) << 1 | sym.get(1).copied().unwrap_or(0) & 1) as usize;
+        // Four symbols per byte: count each damaged byte once.
+        let byte = si / 4;
+        if last_byte != Some(byte) && mask.intersects(x, y, 2, 2) && read_symbol(img, x, y) != want {
+            let block = byte.checked_sub(FEC_HEADER_LEN).map_or(0, |offset| 1 + offset / crate::reed_solomon::BLOCK);
+            damaged[block] += 1;
+            last_byte = Some(byte);
+        }
+    }
+    damaged[0] <= HEADER_NSYM / 2 && damaged[1..].iter().all(|&d| d <= parity as usize / 2)
+}
+
+/// `survives_mask` for a version 3 frame: the decoder is replayed on the symbols it would read.
+fn layout_survives_mask(img: &RgbImage, positions: &[(u32, u32)], bits: &[u8], mask: &Mask, layout: &Layout) -> bool {
+    let symbols: Vec<usize> = bits
+        .chunks(2)
+        .zip(positions)
+        .map(|(sym, &(x, y))| {
+            if mask.intersects(x, y, 2, 2) {
+                read_symbol(img, x, y)
+            } else {
+                ((sym[0] & 1) << 1 | sym.get(1).copied().unwrap_or(0) & 1) as usize
+            }
+        })
+        .collect();
+    parse_layout_frame(&symbols, layout).is_ok()
+}
+
+/// Repeat groups that would decode wrongly: masked cells keep the cover's pixels, so they vote
+/// for whatever the cover reads as there, and the decoder's majority vote is replayed.
+fn lost_groups(img: &RgbImage, positions: &[(u32, u32)], bits: &[u8], mask: &Mask) -> usize {
+    bits.chunks(2)
+        .zip(positions.chunks(REPEAT))
+        .filter(|(sym, group)| {
+            let want = ((sym[0] & 1) << 1 | sym.get(1).copied().unwrap_or(0) & 1) as usize;
+            let mut counts = [0u8; 4];
+            for &(x, y) in group.iter() {
+                let vote = if mask.intersects(x, y, 2, 2) { read_symbol(img, x, y) } else { want };
+                counts[vote] += 1;
+            }
+            // First maximum wins, as in decode_offset_with_positions.
+            let decoded = (0..4).fold(0, |best, i| if counts[i] > counts[best] { i } else { best });
+            decoded != want
+        })
+        .count()
+}
+
+fn decode_offset_with_positions(
+    img: &RgbImage,
+    positions: Vec<(u32, u32)>,
+) -> Result<Vec<u8>, StegoError> {
+    if positions.is_empty() {
+        return Err(StegoError::NotStego("Image too small for dot decode".to_string()));
+    }
+    let symbols: Vec<usize> = positions.into_iter().map(|(x, y)| read_symbol(img, x, y)).collect();
+    parse_fec(&symbols).or_else(|_| parse_legacy(&symbols))
+}
+
+fn symbol_bits(idx: usize) -> [u8; 2] {
+    [((idx >> 1) & 1) as u8, (idx & 1) as u8]
+}
+
+/// Version 2 frame, one symbol per cell.
+fn parse_fec(symbols: &[usize]) -> Result<Vec<u8>, StegoError> {
+    let bits: Vec<u8> = symbols.iter().flat_map(|&idx| symbol_bits(idx)).collect();
+    let bytes = bits_to_bytes(&bits);
+    let not_found = || StegoError::NotStego("Magic not found".to_string());
+    let header = bytes.get(..FEC_HEADER_LEN).ok_or_else(not_found)?;
+    let header = crate::reed_solomon::decode(header, HEADER_NSYM, &[]).map_err(|_| not_found())?;
+    if &header[..MAGIC_LEN] != MAGIC || header[MAGIC_LEN] != FEC_VERSION {
+        return Err(not_found());
+    }
+    let parity = header[MAGIC_LEN + 1];
+    if parity == 0 || parity > MAX_PARITY {
+        return Err(StegoError::NotStego("Invalid header".to_string()));
+    }
+    let l = MAGIC_LEN + 2;
+    let len = u32::from_be_bytes([header[l], header[l + 1], header[l + 2], header[l + 3]]) as usize;
+    if len > bytes.len() {
+        return Err(StegoError::NotStego("Payload length mismatch".to_string()));
+    }
+    let codeword_len = crate::reed_solomon::encoded_len(len, parity as usize);
+    let codeword = bytes
+        .get(FEC_HEADER_LEN..FEC_HEADER_LEN + codeword_len)
+        .ok_or_else(|| StegoError::NotStego("Payload length mismatch".to_string()))?;
+    crate::reed_solomon::decode(codeword, parity as usize, &[])
+        .map_err(|e| StegoError::NotStego(format!("Payload damaged beyond repair: {}", e)))
+}
+
+/// Most frequent symbol of a repeat group; the first of equals wins.
+fn majority(group: &[usize]) -> usize {
+    let mut counts = [0u8; 4];
+    for &idx in group {
+        if idx < 4 {
+            counts[idx] += 1;
+        }
+    }
+    let mut max_idx = 0usize;
+    let mut max_count = 0u8;
+    for (i, &c) in counts.iter().enumerate() {
+        if c > max_count {
+            max_count = c;
+            max_idx = i;
+        }
+    }
+    max_idx
+}
+
+/// Version 3 frame read on `layout`'s grid: its header must record that step and offset.
+fn parse_layout_frame(symbols: &[usize], layout: &Layout) -> Result<Vec<u8>, StegoError> {
+    let not_found = || StegoError::NotStego("Magic not found".to_string());
+    let header_symbols = LAYOUT_HEADER_LEN * 4;
+    let header_bits: Vec<u8> = symbols.get(..header_symbols).ok_or_else(not_found)?.iter().flat_map(|&idx| symbol_bits(idx)).collect();
+    let header = crate::reed_solomon::decode(&bits_to_bytes(&header_bits), HEADER_NSYM, &[]).map_err(|_| not_found())?;
+    if &header[..MAGIC_LEN] != MAGIC || header[MAGIC_LEN] != LAYOUT_VERSION {
+        return Err(not_found());
+    }
+    let [parity, step, offset, repeat] = [1, 2, 3, 4].map(|i| header[MAGIC_LEN + i]);
+    if (step as u32, offset as u32) != (layout.step, layout.offset) {
+        return Err(StegoError::NotStego("Header records another dot layout".to_string()));
+    }
+    if parity == 0 || parity > MAX_PARITY || repeat == 0 || repeat > MAX_REPEAT {
+        return Err(StegoError::NotStego("Invalid header".to_string()));
+    }
+    let l = MAGIC_LEN + 5;
+    let len = u32::from_be_bytes([header[l], header[l + 1], header[l + 2], header[l + 3]]) as usize;
+    let codeword_len = crate::reed_solomon::encoded_len(len, parity as usize);
+    let payload_symbols = symbols[header_symbols..]
+        .chunks_exact(repeat as usize)
+        .take(codeword_len * 4)
+        .map(majority)
+        .collect::<Vec<_>>();
+    if payload_symbols.len() < codeword_len * 4 {
+        return Err(StegoError::NotStego("Payload length mismatch".to_string()));
+    }
+    let bits: Vec<u8> = payload_symbols.iter().flat_map(|&idx| symbol_bits(idx)).collect();
+    crate::reed_solomon::decode(&bits_to_bytes(&bits), parity as usize, &[])
+        .map_err(|e| StegoError::NotStego(format!("Payload damaged beyond repair: {}", e)))
+}
+
+/// Legacy frame: REPEAT cells per symbol, majority-voted.
+fn parse_legacy(symbols: &[usize]) -> Result<Vec<u8>, StegoError> {
+    let mut bits: Vec<u8> = Vec::with_capacity((symbols.len() / REPEAT) * 2);
+    for group in symbols.chunks_exact(REPEAT) {
+        bits.extend_from_slice(&symbol_bits(majority(group)));
+    }
+    if bits.len() < 16 {
+        return Err(StegoError::NotStego("Insufficient bits".to_string()));
+    }
+    let codeword_len = {
+        let header = bits_to_bytes(&bits[..16]);
+        if header.len() < 2 {
+            return Err(StegoError::NotStego("Invalid header".to_string()));
+        }
+        u16::from_be_bytes([header[0], header[1]]) as usize
+    };
+    let total_bits = (2 + codeword_len) * 8;
+    if bits.len() < total_bits {
+        return Err(StegoError::NotStego("Insufficient payload bits".to_string()));
+    }
+    let raw = bits_to_bytes(&bits[..total_bits]);
+    if raw.len() < 2 + codeword_len {
+        return Err(StegoError::NotStego("Payload decode failed".to_string()));
+    }
+    let payload_raw = &raw[2..2 + codeword_len];
+    unwrap_payload(payload_raw)
+}
+
+/// `decode_offset` of the image as stored, then perspective-corrected by its fiducials; each also
+/// of the cover inside a calibration border, if it has one. Last, the image resampled to the grid
+/// pitch `estimate_grid` finds. Only the image as stored (and a cover cut from its border at the
+/// stored size) keeps its alpha channel.
+fn decode_offset_or_photo(cover: &Cover, key: Option<&StegoKey>) -> Result<Vec<u8>, StegoError> {
+    let framed = |img: &RgbImage, alpha: Option<&GrayImage>| {
+        decode_offset(img, alpha, key).or_else(|err| match crate::fiducial::calibrated_inner(img, STEP) {
+            Some(inner) => {
+                let border = 2 * crate::fiducial::BORDER;
+                let inner_alpha = alpha
+                    .filter(|a| a.dimensions() == (inner.width() + border, inner.height() + border))
+                    .map(|a| image::imageops::crop_imm(a, border / 2, border / 2, inner.width(), inner.height()).to_image());
+                decode_offset(&inner, inner_alpha.as_ref(), key)
+            }
+            None => Err(err),
+        })
+    };
+    let img = &cover.rgb;
+    framed(img, cover.alpha.as_ref())
+        .or_else(|err| match crate::fiducial::rectify(img) {
+            Some(rectified) => framed(&rectified, None),
+            None => Err(err),
+        })
+        .or_else(|err| repitched(img).iter().find_map(|candidate| decode_offset(candidate, None, key).ok()).ok_or(err))
+}
+
+/// Contrast profile along x (`columns`) or y: sum of |second difference| of luma, taken `spacing`
+/// px apart, across the other axis. Dot cells make it peak once per pitch; spacing 2 keeps the
+/// cells' edges sharp enough in upscaled images.
+fn contrast_profile(img: &RgbImage, columns: bool, spacing: u32) -> Vec<f64> {
+    let (w, h) = img.dimensions();
+    let luma = |x: u32, y: u32| {
+        let p = img.get_pixel(x, y);
+        (p[0] as f64 * 0.299) + (p[1] as f64 * 0.587) + (p[2] as f64 * 0.114)
+    };
+    let (len, across) = if columns { (w, h) } else { (h, w) };
+    let at = |i: u32, j: u32| if columns { luma(i, j) } else { luma(j, i) };
+    let mut profile = vec![0f64; len as usize];
+    for (i, v) in profile.iter_mut().enumerate().take(len.saturating_sub(spacing) as usize).skip(spacing as usize) {
+        let i = i as u32;
+        *v = (0..across).map(|j| (2.0 * at(i, j) - at(i - spacing, j) - at(i + spacing, j)).abs()).sum();
+    }
+    profile
+}
+
+/// Grid pitch (px per cell), offset of the first cell's centre and strength (the share of the
+/// profile's variance at that frequency) along one axis, from its contrast profile. None when no
+/// period stands out.
+fn estimate_grid(profile: &[f64]) -> Option<(f64, f64, f64)> {
+    let n = profile.len();
+    if n < (MAX_PITCH + 1) * 8 {
+        return None;
+    }
+    let mean = profile.iter().sum::<f64>() / n as f64;
+    let centred: Vec<f64> = profile.iter().map(|v| v - mean).collect();
+    let energy: f64 = centred.iter().map(|v| v * v).sum();
+    if energy <= 0.0 {
+        return None;
+    }
+    // Normalised autocorrelation at quarter-pixel lags (the shifted profile linearly
+    // interpolated): the pitch and its multiples are local maxima. A pixel either side of the
+    // pitch range lets its ends be maxima.
+    let autocorrelation: Vec<(f64, f64)> = ((MIN_PITCH - 1) * 4..=(MAX_PITCH + 1) * 4)
+        .map(|quarters| {
+            let lag = quarters as f64 / 4.0;
+            let (whole, frac) = (quarters / 4, lag.fract());
+            let r: f64 = (0..n - whole - 1)
+                .map(|i| centred[i] * (centred[i + whole] * (1.0 - frac) + centred[i + whole + 1] * frac))
+                .sum();
+            (lag, r / energy)
+        })
+        .collect();
+    let coefficient = |pitch: f64| {
+        centred.iter().enumerate().fold((0f64, 0f64), |(re, im), (i, v)| {
+            let angle = std::f64::consts::TAU * (i as f64 + 0.5) / pitch;
+            (re + v * angle.cos(), im - v * angle.sin())
+        })
+    };
+    // Each local maximum is refined by a periodogram within half a pixel of it; the strongest
+    // frequency wins, which tells the pitch from its multiples and from resampling artefacts.
+    let ((re, im), pitch) = autocorrelation
+        .windows(3)
+        .filter(|w| w[1].1 >= 0.02 && w[1].1 >= w[0].1 && w[1].1 >= w[2].1)
+        .flat_map(|w| (0..=100).map(move |k| w[1].0 - 0.5 + k as f64 * 0.01))
+        .filter(|pitch| (MIN_PITCH as f64 - 0.5..=MAX_PITCH as f64 + 0.5).contains(pitch))
+        .map(|pitch| (coefficient(pitch), pitch))
+        .max_by(|a, b| a.0 .0.hypot(a.0 .1).total_cmp(&b.0 .0.hypot(b.0 .1)))?;
+    // Phase of the profile's peaks: the cell centres (continuous coordinates), modulo the pitch.
+    let offset = (-im.atan2(re)).rem_euclid(std::f64::consts::TAU) / std::f64::consts::TAU * pitch;
+    Some((pitch, offset, 2.0 * (re * re + im * im) / (energy * n as f64)))
+}
+
+/// `estimate_grid` of the stronger of the spacing 1 and 2 contrast profiles along one axis.
+fn estimate_axis(img: &RgbImage, columns: bool) -> Option<(f64, f64)> {
+    [1, 2]
+        .into_iter()
+        .filter_map(|spacing| estimate_grid(&contrast_profile(img, columns, spacing)))
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(pitch, offset, _)| (pitch, offset))
+}
+
+/// `img` resampled so its dot cells sit on the STEP/OFFSET grid again, at each estimated
+/// original size (one per distinct cell count); empty when no pitch is found or the image is
+/// already at the grid's scale.
+fn repitched(img: &RgbImage) -> Vec<RgbImage> {
+    let (Some((px, ox)), Some((py, oy))) = (estimate_axis(img, true), estimate_axis(img, false)) else {
+        return Vec::new();
+    };
+    let (sx, sy) = (px / STEP as f64, py / STEP as f64);
+    // Original cell centre OFFSET + 1 maps to the measured offset, to within one pitch.
+    let shift = |offset: f64, pitch: f64, scale: f64| {
+        let t = (offset - (OFFSET + 1) as f64 * scale).rem_euclid(pitch);
+        if t > pitch / 2.0 {
+            t - pitch
+        } else {
+            t
+        }
+    };
+    let (tx, ty) = (shift(ox, px, sx), shift(oy, py, sy));
+    if (sx - 1.0).abs() < 0.01 && (sy - 1.0).abs() < 0.01 && tx.abs() < 0.5 && ty.abs() < 0.5 {
+        return Vec::new();
+    }
+    let (w, h) = img.dimensions();
+    let size = |len: u32, scale: f64, t: f64| ((len as f64 - t) / scale).round() as i64;
+    let (w0, h0) = (size(w, sx, tx), size(h, sy, ty));
+    let mut seen = Vec::new();
+    let mut out = Vec::new();
+    for (dw, dh) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, 1), (-1, 1), (1, -1)] {
+        let (ow, oh) = ((w0 + dw).max(1) as u32, (h0 + dh).max(1) as u32);
+        let grid = (ow.saturating_sub(OFFSET + 2) / STEP, oh.saturating_sub(OFFSET + 2) / STEP);
+        if seen.contains(&grid) || ow < OFFSET + 2 || oh < OFFSET + 2 {
+            continue;
+        }
+        seen.push(grid);
+        out.push(RgbImage::from_fn(ow, oh, |x, y| bilinear(img, (x as f64 + 0.5) * sx + tx, (y as f64 + 0.5) * sy + ty)));
+    }
+    out
+}
+
+/// Bilinear sample of `img` at continuous coordinates (x, y), clamped to the image.
+fn bilinear(img: &RgbImage, x: f64, y: f64) -> image::Rgb<u8> {
+    let (w, h) = img.dimensions();
+    let (fx, fy) = ((x - 0.5).clamp(0.0, (w - 1) as f64), (y - 0.5).clamp(0.0, (h - 1) as f64));
+    let (x0, y0) = (fx.floor() as u32, fy.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (ax, ay) = (fx - x0 as f64, fy - y0 as f64);
+    let mut out = [0u8; 3];
+    for (c, v) in out.iter_mut().enumerate() {
+        let top = img.get_pixel(x0, y0)[c] as f64 * (1.0 - ax) + img.get_pixel(x1, y0)[c] as f64 * ax;
+        let bottom = img.get_pixel(x0, y1)[c] as f64 * (1.0 - ax) + img.get_pixel(x1, y1)[c] as f64 * ax;
+        *v = (top * (1.0 - ay) + bottom * ay).round() as u8;
+    }
+    image::Rgb(out)
+}
+
+/// Payload on the default grid (version 2 or legacy frame), else on a layout `candidate_layouts`
+/// finds in the image (version 3).
+fn decode_offset(img: &RgbImage, alpha: Option<&GrayImage>, key: Option<&StegoKey>) -> Result<Vec<u8>, StegoError> {
+    decode_default_grid(img, alpha, key).or_else(|err| {
+        let (w, h) = img.dimensions();
+        candidate_layouts(img)
+            .into_iter()
+            .find_map(|layout| {
+                let base = visible_cells(w, h, alpha, &layout);
+                let orders = match key {
+                    Some(key) => vec![keyed_positions(base, key)],
+                    None => vec![spread_positions(base.clone()), shuffle_positions(base)],
+                };
+                orders.into_iter().find_map(|positions| {
+                    let symbols: Vec<usize> = positions.into_iter().map(|(x, y)| read_symbol(img, x, y)).collect();
+                    parse_layout_frame(&symbols, &layout).ok()
+                })
+            })
+            .ok_or(err)
+    })
+}
+
+fn decode_default_grid(img: &RgbImage, alpha: Option<&GrayImage>, key: Option<&StegoKey>) -> Result<Vec<u8>, StegoError> {
+    let (w, h) = img.dimensions();
+    let base_positions = visible_cells(w, h, alpha, &Layout::default());
+    if base_positions.is_empty() {
+        return Err(StegoError::NotStego("Image too small for dot decode".to_string()));
+    }
+    if let Some(key) = key {
+        return decode_offset_with_positions(img, keyed_positions(base_positions, key));
+    }
+    let spread = spread_positions(base_positions.clone());
+    if let Ok(payload) = decode_offset_with_positions(img, spread) {
+        return Ok(payload);
+    }
+    let shuffled = shuffle_positions(base_positions);
+    decode_offset_with_positions(img, shuffled)
+}
+
+/// Layouts a version 3 frame may sit on: the step both axes' `estimate_grid` pitch rounds to,
+/// each offset at that step, nearest the measured cell centres first.
+fn candidate_layouts(img: &RgbImage) -> Vec<Layout> {
+    let (Some((px, ox)), Some((py, oy))) = (estimate_axis(img, true), estimate_axis(img, false)) else {
+        return Vec::new();
+    };
+    let step = px.round();
+    if step != py.round() || (px - step).abs() > 0.1 || (py - step).abs() > 0.1 || !(MIN_STEP..=MAX_STEP).contains(&(step as u32)) {
+        return Vec::new();
+    }
+    // Circular distance from a cell centre (offset + 1) to the measured phase.
+    let distance = |offset: u32, phase: f64| {
+        let d = (offset as f64 + 1.0 - phase).rem_euclid(step);
+        d.min(step - d)
+    };
+    let mut offsets: Vec<u32> = (0..=step as u32 - 2).collect();
+    offsets.sort_by(|&a, &b| (distance(a, ox) + distance(a, oy)).total_cmp(&(distance(b, ox) + distance(b, oy))));
+    offsets.into_iter().map(|offset| Layout { step: step as u32, offset, repeat: 1 }).collect()
+}
+
+#[cfg(feature = "fs")]
+pub fn encode(image_path: &std::path::Path, payload: &[u8]) -> Result<Vec<u8>, StegoError> {
+    encode_with_mask(image_path, payload, None)
+}
+
+/// `encode` that leaves masked pixels untouched. Of the two cell orders the decoder tries
+/// (spread, then shuffled), the first where the mask damages no more than the frame corrects
+/// (no lost majority vote, or no Reed-Solomon block past its parity) is used.
+#[cfg(feature = "fs")]
+pub fn encode_with_mask(image_path: &std::path::Path, payload: &[u8], mask: Option<&MaskSpec>) -> Result<Vec<u8>, StegoError> {
+    encode_with_options(image_path, payload, mask, &EncodeOptions::default())
+}
+
+/// `encode_with_mask` with explicit parity, stego key (`decode_with_key` needs the same key) and
+/// output format.
+#[cfg(feature = "fs")]
+pub fn encode_with_options(
+    image_path: &std::path::Path,
+    payload: &[u8],
+    mask: Option<&MaskSpec>,
+    opts: &EncodeOptions,
+) -> Result<Vec<u8>, StegoError> {
+    encode_file_bytes(&std::fs::read(image_path)?, payload, mask, opts)
+}
+
+/// `encode_with_options` for an image held in memory (PNG, JPEG, ... file contents).
+pub fn encode_bytes(image: &[u8], payload: &[u8], opts: &EncodeOptions) -> Result<Vec<u8>, StegoError> {
+    encode_file_bytes(image, payload, None, opts)
+}
+
+/// `encode_cover` of image file contents, or of each frame of an animated cover.
+fn encode_file_bytes(bytes: &[u8], payload: &[u8], mask: Option<&MaskSpec>, opts: &EncodeOptions) -> Result<Vec<u8>, StegoError> {
+    let Some(animation) = crate::stego_anim::Animation::read(bytes)? else {
+        if opts.spread > 0 {
+            return Err(StegoError::InvalidInput("Spread applies to animated covers only".to_string()));
+        }
+        return encode_cover(load_image_bytes_with_orientation(bytes)?, payload, mask, opts);
+    };
+    if opts.calibration {
+        return Err(StegoError::InvalidInput("A calibration border enlarges the canvas, which every frame of an animation shares".to_string()));
+    }
+    animation.embed(payload, opts.spread, |frame, part| {
+        let (img, alpha) = embed_cover(cover_of(image::DynamicImage::ImageRgba8(frame.clone()), Metadata::default()), part, mask, opts)?;
+        Ok(image::RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+            let [r, g, b] = img.get_pixel(x, y).0;
+            image::Rgba([r, g, b, alpha.as_ref().map_or(255, |a| a.get_pixel(x, y)[0])])
+        }))
+    })
+}
+
+fn encode_cover(cover: Cover, payload: &[u8], mask: Option<&MaskSpec>, opts: &EncodeOptions) -> Result<Vec<u8>, StegoError> {
+    let metadata = if opts.strip_metadata { Metadata::default() } else { cover.metadata.clone() };
+    let (img, alpha) = embed_cover(cover, payload, mask, opts)?;
+    match alpha {
+        Some(alpha) => {
+            let rgba = image::RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+                let [r, g, b] = img.get_pixel(x, y).0;
+                image::Rgba([r, g, b, alpha.get_pixel(x, y)[0]])
+            });
+            opts.format.encode_with_metadata(rgba.as_raw(), rgba.width(), rgba.height(), ExtendedColorType::Rgba8, &metadata)
+        }
+        None => opts.format.encode_with_metadata(img.as_raw(), img.width(), img.height(), ExtendedColorType::Rgb8, &metadata),
+    }
+}
+
+/// The cover's colour and alpha with the payload's cells drawn in (and the fiducials or
+/// calibration frame `opts` asks for).
+fn embed_cover(cover: Cover, payload: &[u8], mask: Option<&MaskSpec>, opts: &EncodeOptions) -> Result<(RgbImage, Option<GrayImage>), StegoError> {
+    if opts.parity > MAX_PARITY {
+        return Err(StegoError::InvalidInput(format!("Parity must be at most {} bytes per block", MAX_PARITY)));
+    }
+    if opts.fiducials && opts.calibration {
+        return Err(StegoError::InvalidInput("Use either fiducials or a calibration border, not both".to_string()));
+    }
+    if opts.rendering.contrast().is_some_and(|c| c < MIN_CONTRAST) {
+        return Err(StegoError::InvalidInput(format!("Dot contrast must be at least {}", MIN_CONTRAST)));
+    }
+    let Cover { rgb: mut img, mut alpha, .. } = cover;
+    let (w, h) = img.dimensions();
+    let custom_layout = opts.layout != Layout::default();
+    if custom_layout {
+        opts.layout.validate(w, h)?;
+        if opts.parity == 0 {
+            return Err(StegoError::InvalidInput("A custom dot layout needs parity (the legacy frame has a fixed layout)".to_string()));
+        }
+    }
+    let payload = payload_header::compress(payload);
+    let (bits, repeat) = if custom_layout {
+        (layout_frame_bits(&payload, opts.parity, &opts.layout), 1)
+    } else {
+        let frame = match opts.parity {
+            0 => legacy_frame(&payload)?,
+            p => fec_frame(&payload, p),
+        };
+        (bytes_to_bits(&frame), repeat_for(opts.parity))
+    };
+    let base = visible_cells(w, h, alpha.as_ref(), &opts.layout);
+    let with_fiducials;
+    let mask = if opts.fiducials {
+        let mut spec = mask.cloned().unwrap_or_default();
+        let regions = crate::fiducial::draw(&mut img)?;
+        if let Some(alpha) = alpha.as_mut() {
+            for r in &regions {
+                for (x, y) in (r.y..r.y + r.height).flat_map(|y| (r.x..r.x + r.width).map(move |x| (x, y))) {
+                    alpha.put_pixel(x, y, image::Luma([255]));
+                }
+            }
+        }
+        spec.rects.extend(regions);
+        with_fiducials = spec;
+        Some(&with_fiducials)
+    } else {
+        mask
+    };
+    match (mask, &opts.key) {
+        (Some(spec), key) if !spec.is_empty() => {
+            let mask = spec.build(w, h)?;
+            let orders = match key {
+                Some(k) => vec![keyed_positions(base, k)],
+                None => vec![spread_positions(base.clone()), shuffle_positions(base)],
+            };
+            let positions = orders
+                .into_iter()
+                .find(|p| match custom_layout {
+                    true => layout_survives_mask(&img, p, &bits, &mask, &opts.layout),
+                    false => survives_mask(&img, p, &bits, &mask, opts.parity),
+                })
+                .ok_or_else(|| StegoError::Capacity("Mask covers too much of the dot grid for this payload".to_string()))?;
+            encode_offset_with_positions(&mut img, &bits, positions, Some(&mask), repeat, opts.rendering)?;
+        }
+        (_, Some(k)) => encode_offset_with_positions(&mut img, &bits, keyed_positions(base, k), None, repeat, opts.rendering)?,
+        _ => encode_offset_with_positions(&mut img, &bits, spread_positions(base), None, repeat, opts.rendering)?,
+    }
+    if opts.calibration {
+        img = crate::fiducial::calibrate(&img, STEP)?;
+        alpha = alpha.map(|cover_alpha| {
+            let mut framed = GrayImage::from_pixel(img.width(), img.height(), image::Luma([255]));
+            let border = crate::fiducial::BORDER as i64;
+            image::imageops::replace(&mut framed, &cover_alpha, border, border);
+            framed
+        });
+    }
+    Ok((img, alpha))
+}
+
+#[cfg(feature = "fs")]
+pub fn decode(image_path: &std::path::Path) -> Result<Vec<u8>, StegoError> {
+    decode_with_key(image_path, None)
+}
+
+/// `decode` of an image written with `EncodeOptions::key`; None tries the unkeyed cell orders.
+#[cfg(feature = "fs")]
+pub fn decode_with_key(image_path: &std::path::Path, key: Option<&StegoKey>) -> Result<Vec<u8>, StegoError> {
+    decode_bytes(&std::fs::read(image_path)?, key)
+}
+
+/// `decode_with_key` for image file contents held in memory. Animated covers are read frame by
+/// frame (stego_anim).
+pub fn decode_bytes(image: &[u8], key: Option<&StegoKey>) -> Result<Vec<u8>, StegoError> {
+    if let Some(animation) = crate::stego_anim::Animation::read(image)? {
+        return animation.extract(|frame| {
+            let cover = cover_of(image::DynamicImage::ImageRgba8(frame.clone()), Metadata::default());
+            payload_header::decompress(&decode_offset_or_photo(&cover, key)?)
+        });
+    }
+    payload_header::decompress(&decode_offset_or_photo(&load_image_bytes_with_orientation(image)?, key)?)
+}
+
+/// Largest payload `encode` can embed (at the default parity), in stored bytes
+/// (`payload_header::stored_len`).
+#[cfg(feature = "fs")]
+pub fn max_payload_bytes(image_path: &std::path::Path) -> Result<usize, StegoError> {
+    let cover = load_image_with_orientation(image_path)?;
+    let (w, h) = cover.rgb.dimensions();
+    Ok(payload_capacity(visible_cells(w, h, cover.alpha.as_ref(), &Layout::default()).len(), parity()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_fec_frame_corrects_damaged_cells() {
+        let cover = RgbImage::from_fn(360, 360, |x, y| image::Rgb([(x % 180) as u8 + 40, (y % 180) as u8 + 40, 120]));
+        let mut png = Cursor::new(Vec::new());
+        cover.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let png = png.into_inner();
+        // Random, so compression leaves it as is.
+        let mut payload = vec![0u8; 400];
+        rand::RngCore::fill_bytes(&mut <rand_chacha::ChaCha8Rng as rand::SeedableRng>::seed_from_u64(7), &mut payload);
+        let cells = cell_grid(360, 360, STEP, OFFSET).len();
+        assert!(payload_capacity(cells, DEFAULT_PARITY) > 2 * payload_capacity(cells, 0));
+
+        let opts = EncodeOptions { parity: DEFAULT_PARITY, ..Default::default() };
+        let stego = encode_bytes(&png, &payload, &opts).unwrap();
+        let mut img = image::load_from_memory(&stego).unwrap().to_rgb8();
+        // Move the dark pixel of every 100th cell: one wrong symbol per hit.
+        for &(x, y) in cell_grid(360, 360, STEP, OFFSET).iter().step_by(100) {
+            let wrong = (read_symbol(&img, x, y) + 1) % 4;
+            for (i, (ox, oy)) in [(0u32, 0u32), (0, 1), (1, 0), (1, 1)].into_iter().enumerate() {
+                let v = if i == wrong { 0 } else { 255 };
+                img.put_pixel(x + ox, y + oy, image::Rgb([v, v, v]));
+            }
+        }
+        assert_eq!(decode_offset(&img, None, None).unwrap(), payload);
+
+        let legacy = encode_bytes(&png, b"legacy frame", &EncodeOptions { parity: 0, ..opts }).unwrap();
+        assert_eq!(decode_bytes(&legacy, None).unwrap(), b"legacy frame");
+        assert!(encode_bytes(&png, &payload, &EncodeOptions { parity: 0, ..opts }).is_err());
+    }
+
+    #[test]
+    fn test_calibration_border_survives_screenshot() {
+        let cover = RgbImage::from_fn(240, 220, |x, y| image::Rgb([(x % 120) as u8 + 70, (y % 110) as u8 + 70, 150]));
+        let mut png = Cursor::new(Vec::new());
+        cover.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let opts = EncodeOptions { calibration: true, ..Default::default() };
+        let stego = encode_bytes(png.get_ref(), b"framed", &opts).unwrap();
+        assert_eq!(decode_bytes(&stego, None).unwrap(), b"framed");
+        let framed = image::load_from_memory(&stego).unwrap().to_rgb8();
+        assert_eq!(framed.dimensions(), (240 + 2 * crate::fiducial::BORDER, 220 + 2 * crate::fiducial::BORDER));
+
+        // Screenshot: scaled 125% inside a grey window, with part of the quiet zone cropped off.
+        let trimmed = image::imageops::crop_imm(&framed, 3, 3, framed.width() - 6, framed.height() - 6).to_image();
+        let scaled = image::imageops::resize(&trimmed, trimmed.width() * 5 / 4, trimmed.height() * 5 / 4, image::imageops::FilterType::Nearest);
+        let mut shot = RgbImage::from_pixel(460, 420, image::Rgb([200, 200, 200]));
+        image::imageops::replace(&mut shot, &scaled, 37, 51);
+        let mut shot_png = Cursor::new(Vec::new());
+        shot.write_to(&mut shot_png, image::ImageFormat::Png).unwrap();
+        assert_eq!(decode_bytes(shot_png.get_ref(), None).unwrap(), b"framed");
+        assert!(encode_bytes(png.get_ref(), b"framed", &EncodeOptions { fiducials: true, ..opts }).is_err());
+    }
+
+    #[test]
+    fn test_low_visibility_renderings_decode() {
+        let cover = RgbImage::from_fn(240, 200, |x, y| image::Rgb([(x % 200) as u8 + 30, (y % 180) as u8 + 40, ((x + y) % 160) as u8 + 50]));
+        let mut png = Cursor::new(Vec::new());
+        cover.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        // Mean absolute change per channel value against the cover.
+        let change = |rendering| {
+            let stego = encode_bytes(png.get_ref(), b"subtle", &EncodeOptions { rendering, ..Default::default() }).unwrap();
+            assert_eq!(decode_bytes(&stego, None).unwrap(), b"subtle");
+            let img = image::load_from_memory(&stego).unwrap().to_rgb8();
+            let total: u64 = img.as_raw().iter().zip(cover.as_raw()).map(|(a, b)| a.abs_diff(*b) as u64).sum();
+            total as f64 / cover.as_raw().len() as f64
+        };
+        let stamp = change(Rendering::Stamp);
+        for rendering in [Rendering::Luma { contrast: DEFAULT_CONTRAST }, Rendering::Blue { contrast: 4 }] {
+            assert!(change(rendering) * 5.0 < stamp, "{rendering:?}");
+        }
+        let flat = EncodeOptions { rendering: Rendering::Luma { contrast: 1 }, ..Default::default() };
+        assert!(encode_bytes(png.get_ref(), b"subtle", &flat).is_err());
+    }
+
+    #[test]
+    fn test_alpha_channel_survives_and_transparent_cells_are_skipped() {
+        // Round sticker: opaque disc, translucent rim, transparent corners.
+        let alpha = |x: u32, y: u32| {
+            let d = ((x as f64 - 120.0).powi(2) + (y as f64 - 120.0).powi(2)).sqrt();
+            if d < 100.0 {
+                255
+            } else if d < 115.0 {
+                128
+            } else {
+                0
+            }
+        };
+        let cover = image::RgbaImage::from_fn(240, 240, |x, y| image::Rgba([(x % 200) as u8 + 30, (y % 180) as u8 + 40, 90, alpha(x, y)]));
+        let mut png = Cursor::new(Vec::new());
+        cover.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let stego = encode_bytes(png.get_ref(), b"sticker", &EncodeOptions::default()).unwrap();
+        let mut img = image::load_from_memory(&stego).unwrap().to_rgba8();
+        assert!(img.pixels().zip(cover.pixels()).all(|(a, b)| a[3] == b[3]));
+
+        // An optimiser blanking the colour of fully transparent pixels leaves the payload alone.
+        for p in img.pixels_mut().filter(|p| p[3] == 0) {
+            *p = image::Rgba([0, 0, 0, 0]);
+        }
+        let mut blanked = Cursor::new(Vec::new());
+        img.write_to(&mut blanked, image::ImageFormat::Png).unwrap();
+        assert_eq!(decode_bytes(blanked.get_ref(), None).unwrap(), b"sticker");
+    }
+
+    #[test]
+    fn test_custom_layout_is_read_back_from_header() {
+        let cover = RgbImage::from_fn(300, 240, |x, y| image::Rgb([(x % 150) as u8 + 50, (y % 120) as u8 + 60, 110]));
+        let mut png = Cursor::new(Vec::new());
+        cover.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let key = StegoKey::from_password("layout").unwrap();
+        for (layout, key) in [(Layout { step: 4, offset: 1, repeat: 3 }, None), (Layout { step: 9, offset: 5, repeat: 1 }, Some(key))] {
+            let opts = EncodeOptions { layout, key, ..Default::default() };
+            let stego = encode_bytes(png.get_ref(), b"laid out", &opts).unwrap();
+            assert_eq!(decode_bytes(&stego, key.as_ref()).unwrap(), b"laid out", "{layout:?}");
+        }
+        assert_eq!(visible_cells(300, 240, None, &Layout { step: 4, offset: 1, repeat: 1 }).len(), 75 * 60);
+
+        for layout in [Layout { step: 3, offset: 0, repeat: 1 }, Layout { step: 6, offset: 5, repeat: 1 }, Layout { step: 6, offset: 2, repeat: 0 }] {
+            assert!(encode_bytes(png.get_ref(), b"x", &EncodeOptions { layout, ..Default::default() }).is_err(), "{layout:?}");
+        }
+        let legacy = EncodeOptions { parity: 0, layout: Layout { step: 8, offset: 2, repeat: 1 }, ..Default::default() };
+        assert!(encode_bytes(png.get_ref(), b"x", &legacy).is_err());
+    }
+
+    #[test]
+    fn test_pitch_estimate_recovers_resized_grid() {
+        let cover = RgbImage::from_fn(300, 260, |x, y| image::Rgb([(x % 150) as u8 + 60, (y % 130) as u8 + 60, 140]));
+        let mut png = Cursor::new(Vec::new());
+        cover.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let stego = encode_bytes(png.get_ref(), b"resized", &EncodeOptions::default()).unwrap();
+        let img = image::load_from_memory(&stego).unwrap().to_rgb8();
+
+        // 1.3x, so the pitch is 7.8 px: no longer a whole number.
+        let scaled = image::imageops::resize(&img, 390, 338, image::imageops::FilterType::Triangle);
+        let (pitch, _) = estimate_axis(&scaled, true).unwrap();
+        assert!((pitch - 7.8).abs() < 0.05, "pitch {pitch}");
+        let mut scaled_png = Cursor::new(Vec::new());
+        scaled.write_to(&mut scaled_png, image::ImageFormat::Png).unwrap();
+        assert_eq!(decode_bytes(scaled_png.get_ref(), None).unwrap(), b"resized");
+    }
+}
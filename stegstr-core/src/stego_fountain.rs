@@ -0,0 +1,134 @@
+// Fountain-coded image series (RaptorQ): instead of one fragment per image, where every image is
+// needed, the payload becomes K source symbols plus repair symbols and each image carries
+// SYMBOLS_PER_IMAGE of them. K is chosen so that any `needed` of the images hold K + 2 symbols,
+// which RaptorQ decodes with overwhelming probability; more images only make it surer.
+// Each part is a payload header (MIME_FOUNTAIN, Fragment with the image index and count) over the
+// 12-byte RaptorQ transmission info and the image's serialized packets. `fragment::join` hands
+// fountain parts to `decode`.
+
+use raptorq::{Decoder, Encoder, EncodingPacket, ObjectTransmissionInformation};
+
+use crate::error::StegoError;
+use crate::fragment;
+use crate::payload_header::{self, Fragment, PayloadHeader};
+
+pub const MIME_FOUNTAIN: &str = "application/vnd.stegstr.fountain";
+/// Encoding symbols per image.
+pub const SYMBOLS_PER_IMAGE: usize = 8;
+/// Symbols beyond K that `needed` images provide.
+const OVERHEAD: usize = 2;
+/// Symbol size alignment (RaptorQ default).
+const ALIGNMENT: usize = 8;
+const OTI_LEN: usize = 12;
+/// Largest source block RaptorQ handles in one piece.
+const MAX_SOURCE_SYMBOLS: usize = 56_403;
+
+/// Parts of `payload` for `images` images, any `needed` of which reconstruct it.
+pub fn encode(payload: &[u8], images: usize, needed: usize) -> Result<Vec<Vec<u8>>, StegoError> {
+    if images < 2 || images > u16::MAX as usize || needed == 0 || needed > images {
+        return Err(StegoError::InvalidInput(format!(
+            "Fountain mode needs 2 to {} images and 1 to {} of them sufficing, not {} of {}",
+            u16::MAX,
+            images,
+            needed,
+            images
+        )));
+    }
+    if payload.is_empty() {
+        return Err(StegoError::InvalidInput("Empty payload".to_string()));
+    }
+    let source_symbols = (needed * SYMBOLS_PER_IMAGE - OVERHEAD).min(MAX_SOURCE_SYMBOLS);
+    let symbol_size = payload.len().div_ceil(source_symbols).next_multiple_of(ALIGNMENT);
+    let symbol_size = u16::try_from(symbol_size)
+        .map_err(|_| StegoError::Capacity(format!("Payload too large for fountain mode over {} images", needed)))?;
+    let oti = ObjectTransmissionInformation::new(payload.len() as u64, symbol_size, 1, 1, ALIGNMENT as u8);
+    let encoder = Encoder::new(payload, oti);
+    let k = payload.len().div_ceil(symbol_size as usize);
+    let repair = (images * SYMBOLS_PER_IMAGE).saturating_sub(k) as u32;
+    let packets = encoder.get_encoded_packets(repair);
+    let id = fragment::bundle_id(payload);
+    packets
+        .chunks(SYMBOLS_PER_IMAGE)
+        .take(images)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut data = oti.serialize().to_vec();
+            for packet in chunk {
+                data.extend(packet.serialize());
+            }
+            let header = PayloadHeader {
+                fragment: Some(Fragment { bundle_id: id.clone(), index: i as u16, total: images as u16 }),
+                ..PayloadHeader::new(MIME_FOUNTAIN)
+            };
+            header.write(&data)
+        })
+        .collect()
+}
+
+/// True for a part written by `encode` (as extracted from its image).
+pub fn is_fountain_part(part: &[u8]) -> bool {
+    matches!(payload_header::read(part), Ok((Some(h), _)) if h.mime_type == MIME_FOUNTAIN)
+}
+
+/// Rebuild the payload from any sufficient set of parts (any order, duplicates allowed).
+pub fn decode(parts: &[Vec<u8>]) -> Result<Vec<u8>, StegoError> {
+    let mut id: Option<String> = None;
+    let mut decoder: Option<(Decoder, [u8; OTI_LEN])> = None;
+    for part in parts {
+        let (header, data) = payload_header::read(part)?;
+        let fragment = match header {
+            Some(PayloadHeader { fragment: Some(f), mime_type, .. }) if mime_type == MIME_FOUNTAIN => f,
+            _ => return Err(StegoError::InvalidInput("Image does not hold a fountain-coded part".to_string())),
+        };
+        match &id {
+            Some(id) if *id != fragment.bundle_id => {
+                return Err(StegoError::InvalidInput(format!("Parts of different payloads ({} and {})", id, fragment.bundle_id)))
+            }
+            _ => id = Some(fragment.bundle_id),
+        }
+        let truncated = || StegoError::NotStego("Fountain part truncated".to_string());
+        let (oti_bytes, packets) = data.split_at_checked(OTI_LEN).ok_or_else(truncated)?;
+        let oti_bytes: [u8; OTI_LEN] = oti_bytes.try_into().map_err(|_| truncated())?;
+        let (decoder, expected) = decoder.get_or_insert_with(|| {
+            (Decoder::new(ObjectTransmissionInformation::deserialize(&oti_bytes)), oti_bytes)
+        });
+        if *expected != oti_bytes {
+            return Err(StegoError::InvalidInput("Fountain parts with different parameters".to_string()));
+        }
+        let packet_len = 4 + ObjectTransmissionInformation::deserialize(&oti_bytes).symbol_size() as usize;
+        for packet in packets.chunks(packet_len) {
+            if packet.len() != packet_len {
+                return Err(truncated());
+            }
+            if let Some(payload) = decoder.decode(EncodingPacket::deserialize(packet)) {
+                let id = id.expect("set above");
+                if fragment::bundle_id(&payload) != id {
+                    return Err(StegoError::NotStego(format!("Rebuilt payload does not match bundle {}", id)));
+                }
+                return Ok(payload);
+            }
+        }
+    }
+    Err(StegoError::InvalidInput(format!(
+        "Not enough images to rebuild the payload ({} part(s) given)",
+        parts.len()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_any_sufficient_subset_decodes() {
+        let payload: Vec<u8> = (0..3000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let parts = encode(&payload, 6, 3).unwrap();
+        assert_eq!(parts.len(), 6);
+        assert!(is_fountain_part(&parts[0]));
+        for subset in [[0, 1, 2], [3, 4, 5], [5, 2, 0], [1, 3, 5]] {
+            let chosen: Vec<Vec<u8>> = subset.iter().map(|&i| parts[i].clone()).collect();
+            assert_eq!(decode(&chosen).unwrap(), payload);
+        }
+        assert!(decode(&parts[..2]).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+// Stego key: a password that seeds the order in which the DWT and dot codecs visit their
+// embedding positions. Without one both codecs write at fixed, public positions, so any decoder
+// can lift the payload; with one the positions are a ChaCha20 permutation only the key reproduces.
+// This hides where the payload sits, it does not encrypt it (use --passphrase/--recipient for that).
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+
+const KEY_DOMAIN: &[u8] = b"stegstr-stego-key-v1";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct StegoKey {
+    seed: [u8; 32],
+}
+
+impl std::fmt::Debug for StegoKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StegoKey(..)")
+    }
+}
+
+impl StegoKey {
+    pub fn from_password(password: &str) -> Result<Self, String> {
+        if password.is_empty() {
+            return Err("Stego key must not be empty".to_string());
+        }
+        let mut h = Sha256::new();
+        h.update(KEY_DOMAIN);
+        h.update(password.as_bytes());
+        Ok(StegoKey { seed: h.finalize().into() })
+    }
+
+    /// Keyed permutation of 0..len for one codec (`domain`). Each length gets its own order, so
+    /// tiles of different sizes do not share a prefix.
+    pub fn permutation(&self, domain: &str, len: usize) -> Vec<usize> {
+        let mut h = Sha256::new();
+        h.update(self.seed);
+        h.update(domain.as_bytes());
+        h.update((len as u64).to_be_bytes());
+        let mut rng = ChaCha20Rng::from_seed(h.finalize().into());
+        let mut order: Vec<usize> = (0..len).collect();
+        // Fisher-Yates; the modulo bias is negligible next to 2^64 for any image size.
+        for i in (1..len).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            order.swap(i, j);
+        }
+        order
+    }
+}
+
+/// Key from an optional password, as the commands and CLI take it.
+pub fn parse(password: Option<&str>) -> Result<Option<StegoKey>, String> {
+    password.map(StegoKey::from_password).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutation_is_keyed_and_stable() {
+        let a = StegoKey::from_password("correct horse").unwrap();
+        let p = a.permutation("dwt", 1000);
+        let mut sorted = p.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..1000).collect::<Vec<_>>());
+        assert_eq!(p, StegoKey::from_password("correct horse").unwrap().permutation("dwt", 1000));
+        assert_ne!(p, StegoKey::from_password("battery staple").unwrap().permutation("dwt", 1000));
+        assert_ne!(p, a.permutation("dot", 1000));
+        assert!(StegoKey::from_password("").is_err());
+    }
+}
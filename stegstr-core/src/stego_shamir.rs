@@ -0,0 +1,135 @@
+// Shamir secret sharing over image series (k-of-n): every byte of the payload is the constant
+// term of a random polynomial of degree k - 1 over GF(2^8), and image i carries the polynomials
+// evaluated at x = i + 1. Any k images rebuild the payload by Lagrange interpolation at 0; fewer
+// reveal nothing about it. Each share is as large as the payload.
+// Each part is a payload header (MIME_SHAMIR, Fragment with the image index and count) over the
+// threshold byte and the share. The bundle id (a hash of the payload) is in the clear, so
+// low-entropy payloads should be encrypted before splitting. `fragment::join` hands Shamir parts
+// to `combine`.
+
+use rand::RngCore;
+
+use crate::error::StegoError;
+use crate::fragment;
+use crate::payload_header::{self, Fragment, PayloadHeader};
+use crate::reed_solomon::{gf_div, gf_mul};
+
+pub const MIME_SHAMIR: &str = "application/vnd.stegstr.shamir";
+/// x = index + 1 must be a nonzero field element.
+pub const MAX_SHARES: usize = 255;
+
+/// Shares of `payload` for `images` images, any `threshold` of which reconstruct it.
+pub fn split(payload: &[u8], images: usize, threshold: usize) -> Result<Vec<Vec<u8>>, StegoError> {
+    if !(2..=MAX_SHARES).contains(&images) || threshold < 2 || threshold > images {
+        return Err(StegoError::InvalidInput(format!(
+            "Shamir mode needs 2 to {} images and 2 to {} of them to combine, not {} of {}",
+            MAX_SHARES, images, threshold, images
+        )));
+    }
+    if payload.is_empty() {
+        return Err(StegoError::InvalidInput("Empty payload".to_string()));
+    }
+    // coefficients[j] holds the x^(j+1) coefficient of every byte's polynomial.
+    let mut coefficients = vec![vec![0u8; payload.len()]; threshold - 1];
+    for c in &mut coefficients {
+        rand::thread_rng().fill_bytes(c);
+    }
+    let id = fragment::bundle_id(payload);
+    (0..images)
+        .map(|i| {
+            let x = (i + 1) as u8;
+            let mut data = Vec::with_capacity(1 + payload.len());
+            data.push(threshold as u8);
+            data.extend(payload.iter().enumerate().map(|(b, &secret)| {
+                // Horner from the highest coefficient down to the secret.
+                coefficients.iter().rev().fold(0u8, |acc, c| gf_mul(acc ^ c[b], x)) ^ secret
+            }));
+            let header = PayloadHeader {
+                fragment: Some(Fragment { bundle_id: id.clone(), index: i as u16, total: images as u16 }),
+                ..PayloadHeader::new(MIME_SHAMIR)
+            };
+            header.write(&data)
+        })
+        .collect()
+}
+
+/// True for a part written by `split` (as extracted from its image).
+pub fn is_shamir_part(part: &[u8]) -> bool {
+    matches!(payload_header::read(part), Ok((Some(h), _)) if h.mime_type == MIME_SHAMIR)
+}
+
+/// Rebuild the payload from at least `threshold` parts (any order, duplicates allowed).
+pub fn combine(parts: &[Vec<u8>]) -> Result<Vec<u8>, StegoError> {
+    let mut shares: Vec<(u8, Vec<u8>)> = Vec::new();
+    let mut id: Option<String> = None;
+    let mut threshold = 0usize;
+    for part in parts {
+        let (header, data) = payload_header::read(part)?;
+        let fragment = match header {
+            Some(PayloadHeader { fragment: Some(f), mime_type, .. }) if mime_type == MIME_SHAMIR => f,
+            _ => return Err(StegoError::InvalidInput("Image does not hold a Shamir share".to_string())),
+        };
+        match &id {
+            Some(id) if *id != fragment.bundle_id => {
+                return Err(StegoError::InvalidInput(format!("Shares of different payloads ({} and {})", id, fragment.bundle_id)))
+            }
+            _ => id = Some(fragment.bundle_id),
+        }
+        let (&k, share) = data.split_first().ok_or_else(|| StegoError::NotStego("Shamir share truncated".to_string()))?;
+        if threshold != 0 && (k as usize != threshold || share.len() != shares[0].1.len()) {
+            return Err(StegoError::InvalidInput("Shamir shares with different parameters".to_string()));
+        }
+        threshold = k as usize;
+        let x = u8::try_from(fragment.index + 1).map_err(|_| StegoError::InvalidInput("Shamir share index out of range".to_string()))?;
+        if !shares.iter().any(|(sx, _)| *sx == x) {
+            shares.push((x, share.to_vec()));
+        }
+    }
+    if threshold == 0 || shares.len() < threshold {
+        return Err(StegoError::InvalidInput(format!(
+            "Not enough images to combine the payload ({} of {} shares)",
+            shares.len(),
+            threshold.max(2)
+        )));
+    }
+    let shares = &shares[..threshold];
+    // Lagrange basis at 0: l_i = prod_{j != i} x_j / (x_j - x_i); subtraction is XOR.
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|(xi, _)| {
+            shares
+                .iter()
+                .filter(|(xj, _)| xj != xi)
+                .fold(1u8, |acc, (xj, _)| gf_mul(acc, gf_div(*xj, xj ^ xi)))
+        })
+        .collect();
+    let payload: Vec<u8> = (0..shares[0].1.len())
+        .map(|b| shares.iter().zip(&basis).fold(0u8, |acc, ((_, s), &l)| acc ^ gf_mul(s[b], l)))
+        .collect();
+    let id = id.expect("set with the first share");
+    if fragment::bundle_id(&payload) != id {
+        return Err(StegoError::NotStego(format!("Combined payload does not match bundle {}", id)));
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_any_threshold_subset_combines() {
+        let payload: Vec<u8> = (0..500u32).map(|i| (i * 37 % 256) as u8).collect();
+        let parts = split(&payload, 5, 3).unwrap();
+        assert_eq!(parts.len(), 5);
+        assert!(is_shamir_part(&parts[0]));
+        for subset in [[0, 1, 2], [4, 3, 2], [0, 2, 4], [1, 4, 0]] {
+            let chosen: Vec<Vec<u8>> = subset.iter().map(|&i| parts[i].clone()).collect();
+            assert_eq!(combine(&chosen).unwrap(), payload);
+        }
+        assert_eq!(fragment::join(&parts).unwrap(), payload);
+        let two = [parts[0].clone(), parts[3].clone(), parts[0].clone()];
+        assert!(combine(&two).unwrap_err().to_string().contains("2 of 3"));
+        assert!(split(&payload, 3, 4).is_err());
+    }
+}
@@ -0,0 +1,38 @@
+// Deterministic test data shared by the codec tests here and in src-tauri (via the `test-util` feature).
+// Noisy covers stand in for photos: flat or smooth synthetic images clip or compress unlike real covers.
+
+/// Xorshift32 stream; the same seed always yields the same covers and payloads.
+pub struct XorShift(u32);
+
+impl XorShift {
+    pub fn new(seed: u32) -> Self {
+        XorShift(seed)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+/// Incompressible bytes (the low byte of each step).
+pub fn noise_bytes(seed: u32, len: usize) -> Vec<u8> {
+    let mut rng = XorShift::new(seed);
+    (0..len).map(|_| rng.next_u32() as u8).collect()
+}
+
+/// RGB cover whose channels are uniform noise in `lo..lo + span`.
+pub fn noisy_cover_in(width: u32, height: u32, seed: u32, lo: u8, span: u8) -> image::RgbImage {
+    let mut rng = XorShift::new(seed);
+    image::RgbImage::from_fn(width, height, |_, _| {
+        let s = rng.next_u32();
+        image::Rgb([lo + (s % span as u32) as u8, lo + (s >> 8) as u8 % span, lo + (s >> 16) as u8 % span])
+    })
+}
+
+/// Mid-tone noisy cover (channels in 60..180), away from the clipping the DWT inverse hits near 0 and 255.
+pub fn noisy_cover(width: u32, height: u32, seed: u32) -> image::RgbImage {
+    noisy_cover_in(width, height, seed, 60, 120)
+}
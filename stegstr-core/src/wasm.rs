@@ -0,0 +1,41 @@
+// wasm-bindgen exports for an in-browser viewer: detect and decode Stegstr images from their file
+// bytes (e.g. a fetched or dropped file) with the dot and DWT codecs. Build with
+// `--target wasm32-unknown-unknown --no-default-features --features wasm`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::StegoError;
+use crate::{detect, payload_header, stego_key};
+
+fn js_error(e: impl Into<StegoError>) -> JsError {
+    JsError::new(&e.into().to_string())
+}
+
+/// Payload of a Stegstr image (still encrypted if it was embedded encrypted), without its payload
+/// header. Throws when none is found.
+#[wasm_bindgen]
+pub fn decode_bytes(image: &[u8], stego_key: Option<String>) -> Result<Vec<u8>, JsError> {
+    let key = stego_key::parse(stego_key.as_deref()).map_err(js_error)?;
+    let (payload, _) = detect::detect_bytes(image, key.as_ref()).map_err(js_error)?;
+    payload_header::read(&payload).map(|(_, data)| data).map_err(js_error)
+}
+
+/// MIME type from the payload header of a Stegstr image, or undefined for headerless payloads.
+#[wasm_bindgen]
+pub fn payload_mime_type(image: &[u8], stego_key: Option<String>) -> Result<Option<String>, JsError> {
+    let key = stego_key::parse(stego_key.as_deref()).map_err(js_error)?;
+    let (payload, _) = detect::detect_bytes(image, key.as_ref()).map_err(js_error)?;
+    payload_header::read(&payload).map(|(header, _)| header.map(|h| h.mime_type)).map_err(js_error)
+}
+
+/// Codec ("dot" or "dwt") that finds a payload in the image, or undefined for other images.
+/// Throws for files that are not images.
+#[wasm_bindgen]
+pub fn detect_bytes(image: &[u8], stego_key: Option<String>) -> Result<Option<String>, JsError> {
+    let key = stego_key::parse(stego_key.as_deref()).map_err(js_error)?;
+    match detect::detect_bytes(image, key.as_ref()) {
+        Ok((_, method)) => Ok(Some(method.to_string())),
+        Err(StegoError::NotStego(_)) => Ok(None),
+        Err(e) => Err(js_error(e)),
+    }
+}